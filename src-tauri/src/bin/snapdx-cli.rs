@@ -0,0 +1,325 @@
+//! Headless import and export, for running on a NAS or from scripts without the desktop app.
+//!
+//! ```text
+//! snapdx-cli import <path> --db <file> [--working-dir <dir>]
+//! snapdx-cli export-conversation <id> --db <file> [--format json|text|html] [--output <file>]
+//! snapdx-cli stats --db <file>
+//! snapdx-cli verify --db <file>
+//! ```
+//!
+//! `--json` prints results to stdout as JSON; progress and errors always go to stderr.
+//! `--quiet` hides progress, `--verbose` shows the import log.
+//!
+//! Exit codes: 0 success, 1 the command failed, 2 bad usage, 3 `verify` found problems.
+
+use serde::Serialize;
+use simplelog::{ColorChoice, Config, LevelFilter, TermLogger, TerminalMode};
+use snap_data_explorer_app_lib::db::DatabaseManager;
+use snap_data_explorer_app_lib::error::{AppError, AppResult};
+use snap_data_explorer_app_lib::export;
+use snap_data_explorer_app_lib::ingestion::detector::ExportDetector;
+use snap_data_explorer_app_lib::ingestion::pipeline::{self, ProgressSink, StderrProgress};
+use snap_data_explorer_app_lib::models::{ExportOptions, ExportSourceType, ImportOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+const USAGE: &str = "usage:
+  snapdx-cli import <path> --db <file> [--working-dir <dir>] [--json] [--quiet]
+  snapdx-cli export-conversation <id> --db <file> [--format json|text|html] [--output <file>]
+  snapdx-cli stats --db <file> [--json]
+  snapdx-cli verify --db <file> [--json]
+
+options:
+  --verbose  show the import log on stderr";
+
+const EXIT_FAILED: u8 = 1;
+const EXIT_USAGE: u8 = 2;
+const EXIT_PROBLEMS: u8 = 3;
+
+#[derive(Default)]
+struct Args {
+    command: String,
+    target: Option<String>,
+    db: Option<PathBuf>,
+    working_dir: Option<PathBuf>,
+    format: Option<String>,
+    output: Option<PathBuf>,
+    json: bool,
+    quiet: bool,
+    verbose: bool,
+}
+
+impl Args {
+    fn parse(raw: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut args = Args::default();
+        let mut raw = raw.into_iter();
+        args.command = raw.next().ok_or("no command given")?;
+        while let Some(arg) = raw.next() {
+            let mut value = || raw.next().ok_or_else(|| format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--db" => args.db = Some(value()?.into()),
+                "--working-dir" => args.working_dir = Some(value()?.into()),
+                "--format" => args.format = Some(value()?),
+                "--output" | "-o" => args.output = Some(value()?.into()),
+                "--json" => args.json = true,
+                "--quiet" | "-q" => args.quiet = true,
+                "--verbose" | "-v" => args.verbose = true,
+                flag if flag.starts_with('-') => return Err(format!("unknown option {}", flag)),
+                _ if args.target.is_none() => args.target = Some(arg),
+                _ => return Err(format!("unexpected argument {}", arg)),
+            }
+        }
+        Ok(args)
+    }
+
+    fn db(&self) -> Result<&PathBuf, String> {
+        self.db.as_ref().ok_or_else(|| "--db is required".to_string())
+    }
+
+    fn target(&self, what: &str) -> Result<&str, String> {
+        self.target
+            .as_deref()
+            .ok_or_else(|| format!("{} needs a {}", self.command, what))
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) if matches!(args.command.as_str(), "help" | "--help" | "-h") => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Ok(args) => args,
+        Err(e) => return usage_error(&e),
+    };
+
+    let level = if args.verbose {
+        LevelFilter::Info
+    } else {
+        LevelFilter::Warn
+    };
+    let _ = TermLogger::init(level, Config::default(), TerminalMode::Stderr, ColorChoice::Auto);
+
+    let outcome = match args.command.as_str() {
+        "import" => import(&args),
+        "export-conversation" => export_conversation(&args),
+        "stats" => stats(&args),
+        "verify" => verify(&args),
+        other => Err(CliError::Usage(format!("unknown command {}", other))),
+    };
+    match outcome {
+        Ok(code) => ExitCode::from(code),
+        Err(CliError::Usage(e)) => usage_error(&e),
+        Err(CliError::App(e)) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(EXIT_FAILED)
+        }
+    }
+}
+
+fn usage_error(message: &str) -> ExitCode {
+    eprintln!("error: {}\n\n{}", message, USAGE);
+    ExitCode::from(EXIT_USAGE)
+}
+
+enum CliError {
+    Usage(String),
+    App(AppError),
+}
+
+impl From<String> for CliError {
+    fn from(e: String) -> Self {
+        CliError::Usage(e)
+    }
+}
+
+impl From<AppError> for CliError {
+    fn from(e: AppError) -> Self {
+        CliError::App(e)
+    }
+}
+
+impl From<std::io::Error> for CliError {
+    fn from(e: std::io::Error) -> Self {
+        CliError::App(e.into())
+    }
+}
+
+type CliResult = Result<u8, CliError>;
+
+/// Print `value` as JSON when `--json` was given, otherwise run `human`.
+fn report<T: Serialize>(args: &Args, value: &T, human: impl FnOnce(&T)) -> AppResult<()> {
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(value)?);
+    } else {
+        human(value);
+    }
+    Ok(())
+}
+
+fn import(args: &Args) -> CliResult {
+    let path = PathBuf::from(args.target("path")?);
+    let db_path = args.db()?;
+    if let Some(parent) = db_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    let database = DatabaseManager::new(db_path)?;
+
+    let exports = ExportDetector::detect_in_directory_with(&path, &database.get_detection_settings()?)?;
+    if exports.is_empty() {
+        return Err(AppError::Validation(format!("No Snapchat export found at {}", path.display())).into());
+    }
+    let working_dir = match (&args.working_dir, pipeline::configured_extraction_root(&database)?) {
+        (Some(dir), _) => dir.clone(),
+        (None, Some((dir, _))) => dir,
+        (None, None) => db_path.with_file_name("exports"),
+    };
+
+    let sink: Box<dyn ProgressSink> = if args.quiet {
+        Box::new(())
+    } else {
+        Box::<StderrProgress>::default()
+    };
+    let mut results = Vec::new();
+    for mut export in exports {
+        if export.source_type == ExportSourceType::Zip {
+            ExportDetector::prepare_zip_parts(&mut export, false)?;
+        }
+        // A reimport of a known export picks up where its extraction directory was recorded
+        export.extraction_path = database
+            .get_exports()?
+            .into_iter()
+            .find(|e| e.id == export.id)
+            .and_then(|e| e.extraction_path);
+        results.push(pipeline::import_export(
+            &database,
+            export,
+            &working_dir,
+            ImportOptions::ALL,
+            false,
+            sink.as_ref(),
+        )?);
+    }
+
+    report(args, &results, |results| {
+        for r in results {
+            println!(
+                "{}: {} conversations, {} messages, {} memories, {} warnings, {} errors",
+                r.export_id,
+                r.conversations_parsed,
+                r.events_parsed,
+                r.memories_parsed,
+                r.warnings.len(),
+                r.errors.len()
+            );
+            for warning in &r.warnings {
+                println!("  warning: {}", warning);
+            }
+            for error in &r.errors {
+                println!("  error: {}", error);
+            }
+        }
+    })?;
+    Ok(0)
+}
+
+fn export_conversation(args: &Args) -> CliResult {
+    let conversation_id = args.target("conversation id")?;
+    let format = args.format.as_deref().unwrap_or("json");
+    if !matches!(format, "json" | "text" | "html") {
+        return Err(CliError::Usage(format!(
+            "unknown format {}; use json, text or html",
+            format
+        )));
+    }
+    let database = DatabaseManager::open_readonly(args.db()?)?;
+    if database.get_conversation_name(conversation_id)?.is_none() {
+        return Err(AppError::NotFound(format!("conversation {}", conversation_id)).into());
+    }
+
+    match &args.output {
+        Some(path) => {
+            // Media links in an HTML page are relative to where it is written
+            let options = ExportOptions {
+                link_base: std::path::absolute(path)?.parent().map(Path::to_path_buf),
+                ..Default::default()
+            };
+            let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+            export::write_conversation(&database, conversation_id, format, &options, &mut writer)?;
+            writer.flush()?;
+        }
+        None => {
+            let mut writer = std::io::BufWriter::new(std::io::stdout().lock());
+            export::write_conversation(
+                &database,
+                conversation_id,
+                format,
+                &ExportOptions::default(),
+                &mut writer,
+            )?;
+            // The JSON array ends without a newline; add one for the terminal
+            if format == "json" {
+                writeln!(writer)?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(0)
+}
+
+fn stats(args: &Args) -> CliResult {
+    let database = DatabaseManager::open_readonly(args.db()?)?;
+    let stats = database.get_export_stats(None)?;
+    report(args, &stats, |s| {
+        println!("Conversations: {}", s.total_conversations);
+        println!("Messages:      {}", s.total_messages);
+        println!("Memories:      {}", s.total_memories);
+        println!(
+            "Media files:   {} ({} missing)",
+            s.total_media_files, s.missing_media_count
+        );
+        if let (Some(start), Some(end)) = (s.start_date, s.end_date) {
+            println!(
+                "Date range:    {} to {}",
+                start.format("%Y-%m-%d"),
+                end.format("%Y-%m-%d")
+            );
+        }
+    })?;
+    Ok(0)
+}
+
+#[derive(Serialize)]
+struct VerifyReport {
+    ok: bool,
+    /// Problems SQLite's consistency check found in the database file.
+    integrity_problems: Vec<String>,
+    validation: snap_data_explorer_app_lib::models::ValidationReport,
+}
+
+fn verify(args: &Args) -> CliResult {
+    let database = DatabaseManager::open_readonly(args.db()?)?;
+    let integrity_problems = database.quick_check()?;
+    let verify = VerifyReport {
+        ok: integrity_problems.is_empty(),
+        integrity_problems,
+        validation: database.get_validation_report()?,
+    };
+    report(args, &verify, |v| {
+        if v.ok {
+            println!("Database integrity: ok");
+        }
+        for problem in &v.integrity_problems {
+            println!("Database integrity: {}", problem);
+        }
+        println!(
+            "Media: {} of {} referenced files linked",
+            v.validation.media_found, v.validation.total_media_referenced
+        );
+        for warning in &v.validation.warnings {
+            println!("warning: {}", warning);
+        }
+    })?;
+    Ok(if verify.ok { 0 } else { EXIT_PROBLEMS })
+}
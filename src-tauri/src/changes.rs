@@ -0,0 +1,122 @@
+//! Which kinds of data changed, so open views can refresh after background work such as
+//! downloads, relinking or file moves.
+//!
+//! Every pooled write connection carries SQLite update, commit and rollback hooks. Row changes
+//! are collected per connection and only handed to the `ChangeTracker` when their transaction
+//! commits, so a long operation reports once at its commit and a rolled-back one not at all.
+//! The app polls the tracker and emits the domains once writes have been quiet for `DEBOUNCE`.
+
+use crate::models::DataDomain;
+use rusqlite::Connection;
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long writes must stop before a burst of them is reported.
+pub const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// How often the app checks for settled changes.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The domains a change to `table` affects. Bookkeeping tables (runs, issues, the search
+/// index) affect none.
+pub fn domains_for_table(table: &str) -> &'static [DataDomain] {
+    match table {
+        "conversations" | "people" => &[DataDomain::Conversations],
+        // The media views are built from events' media references
+        "events" => &[DataDomain::Events, DataDomain::Media],
+        "redactions" | "tags" | "taggings" => &[DataDomain::Events],
+        "memories" | "download_jobs" => &[DataDomain::Memories],
+        "media_info" | "fs_operations" => &[DataDomain::Media],
+        "settings" | "saved_searches" => &[DataDomain::Settings],
+        _ => &[],
+    }
+}
+
+#[derive(Default)]
+struct Pending {
+    domains: BTreeSet<DataDomain>,
+    last_change: Option<Instant>,
+}
+
+/// Committed changes not yet reported.
+#[derive(Default)]
+pub struct ChangeTracker {
+    pending: Mutex<Pending>,
+}
+
+impl ChangeTracker {
+    pub fn record(&self, domains: impl IntoIterator<Item = DataDomain>) {
+        let mut pending = self.lock();
+        pending.domains.extend(domains);
+        pending.last_change = Some(Instant::now());
+    }
+
+    /// Everything changed since the last report, once nothing has changed for `quiet`.
+    pub fn take_settled(&self, quiet: Duration) -> Option<Vec<DataDomain>> {
+        let mut pending = self.lock();
+        let last_change = pending.last_change?;
+        if last_change.elapsed() < quiet {
+            return None;
+        }
+        pending.last_change = None;
+        Some(std::mem::take(&mut pending.domains).into_iter().collect())
+    }
+
+    /// Report `conn`'s committed writes to this tracker.
+    pub fn install(self: &Arc<Self>, conn: &Connection) {
+        let uncommitted = Arc::new(Mutex::new(BTreeSet::new()));
+
+        let touched = uncommitted.clone();
+        conn.update_hook(Some(move |_action, _db: &str, table: &str, _rowid| {
+            let domains = domains_for_table(table);
+            if !domains.is_empty() {
+                lock(&touched).extend(domains.iter().copied());
+            }
+        }));
+
+        let committed = uncommitted.clone();
+        let tracker = self.clone();
+        conn.commit_hook(Some(move || {
+            let domains = std::mem::take(&mut *lock(&committed));
+            if !domains.is_empty() {
+                tracker.record(domains);
+            }
+            // Returning true would turn the commit into a rollback
+            false
+        }));
+
+        conn.rollback_hook(Some(move || lock(&uncommitted).clear()));
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Pending> {
+        lock(&self.pending)
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    // Only whole sets are swapped in and out, so a poisoned lock is still usable
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_a_burst_of_changes_is_reported_once_when_quiet() {
+        let tracker = ChangeTracker::default();
+        assert_eq!(tracker.take_settled(Duration::ZERO), None);
+
+        tracker.record([DataDomain::Memories]);
+        tracker.record([DataDomain::Memories, DataDomain::Settings]);
+        assert_eq!(tracker.take_settled(Duration::from_secs(60)), None);
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(
+            tracker.take_settled(Duration::from_millis(10)),
+            Some(vec![DataDomain::Memories, DataDomain::Settings])
+        );
+        assert_eq!(tracker.take_settled(Duration::ZERO), None);
+    }
+}
@@ -1,34 +1,591 @@
-use crate::error::AppResult;
+use crate::changes::ChangeTracker;
+use crate::error::{AppError, AppResult};
+use crate::ingestion::avatars::avatar_color;
+use crate::ingestion::keep_raw_timestamp;
+use crate::ingestion::media_linker::MediaLinker;
 use crate::models::{
-    Conversation, Event, ExportSet, ExportSourceType, ExportStats, MediaStreamEntry, Memory, MessagePage,
-    PaginatedMedia, Person, SearchResult, ValidationReport, ValidationStatus,
+    ActivityBucket, ActivityHeatmap, AdjacentMemories, AnomalyKind, CallTotal, ChatSource, Conversation,
+    ConversationAnomaly, ConversationCompleteness, ConversationMatch, ConversationMediaStats, ConversationPage,
+    ConversationSort, DatabaseInfo, DensityBucket, DetectionSettings, DownloadFailureGroup, DownloadJob,
+    DownloadJobState, DownloadStatus, DownloadVerification, Event, ExportArtifact, ExportSet, ExportSourceType,
+    ExportStats, FsOperation, FsOperationState, ImportOptions, IngestionCleanup, IngestionRunRecord,
+    IngestionRunStatus, JsonFileIssue, LocationPoint, MaintenanceReport, MediaDirection, MediaFilter, MediaInfo,
+    MediaLinkingSettings, MediaStreamEntry, MediaTimelineMonth, MediaVerification, MediaVerifyProgress, Memory,
+    MemoryFilter, MemoryPage, MessagePage, MessageSearchFilters, MessageWindow, NetworkSettings, OnThisDayYear,
+    OwnerProfile, PaginatedMedia, PathSource, PerformanceSettings, Person, PersonMatch, Redaction, RedactionKind,
+    RedactionSummary, SavedSearch, SearchAllResults, SearchIndexProgress, SearchResult, SharedLocation, Tag,
+    TagEntityType, TaggedEntry, TaggedPage, TaggingSnapshot, TextAnalytics, TimelineBucket, TimestampFormatDecision,
+    UserData, UserDataConflictPolicy, UserDataImportSummary, ValidationReport, ValidationStatus, YearSearchResults,
+    SNIPPET_MATCH_END, SNIPPET_MATCH_START, USER_DATA_VERSION,
 };
+use crate::perf::{self, TraceRows};
 use chrono::{DateTime, Utc};
 use r2d2_sqlite::SqliteConnectionManager;
 use rusqlite::params;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
 
 pub type Pool = r2d2::Pool<SqliteConnectionManager>;
 
+/// `WHERE` clause applying a `MemoryFilter` bound as parameters ?1 to ?6.
+const MEMORY_FILTER_WHERE: &str = "WHERE (?1 IS NULL OR export_id = ?1)
+       AND (?2 IS NULL OR timestamp >= ?2)
+       AND (?3 IS NULL OR timestamp <= ?3)
+       AND (?4 IS NULL OR media_type = ?4)
+       AND (?5 IS NULL OR download_status = ?5)
+       AND (?6 IS NULL OR (latitude IS NOT NULL AND longitude IS NOT NULL) = ?6)";
+
 pub struct DatabaseManager {
     pool: Pool,
+    /// Keeps an in-memory database alive while pooled connections come and go.
+    _keepalive: Option<std::sync::Mutex<rusqlite::Connection>>,
+    read_only: bool,
+    /// Ingestion runs in progress; maintenance waits until there are none.
+    active_ingestions: Arc<AtomicUsize>,
+    last_maintenance: Mutex<Option<MaintenanceReport>>,
+    changes: Arc<ChangeTracker>,
+}
+
+/// Settings holding locations on this machine, which user data files leave out.
+fn is_path_setting(key: &str) -> bool {
+    key.ends_with("_path")
+}
+
+fn file_size(path: &Path) -> Option<u64> {
+    std::fs::metadata(path).ok().filter(|m| m.is_file()).map(|m| m.len())
+}
+
+/// How often the background maintenance task runs.
+pub const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// WAL size above which maintenance checkpoints it.
+pub const WAL_CHECKPOINT_THRESHOLD: u64 = 64 * 1024 * 1024;
+
+/// Setting (in milliseconds) for how long a media page may spend reading file sizes the cache
+/// doesn't have yet.
+pub const MEDIA_STAT_BUDGET_SETTING: &str = "media_stat_budget_ms";
+pub const DEFAULT_MEDIA_STAT_BUDGET: Duration = Duration::from_millis(50);
+
+/// Setting holding the last `verify_media_files` result as JSON.
+const MEDIA_VERIFICATION_SETTING: &str = "media_verification";
+/// Missing files a media verification keeps by name; the rest are only counted.
+pub const VERIFY_MISSING_FILES_LIMIT: usize = 500;
+/// Files checked between progress reports of a media verification.
+const VERIFY_PROGRESS_INTERVAL: usize = 2000;
+
+/// Every media file imported data points at: each chat media reference and each downloaded
+/// memory.
+const LINKED_MEDIA_FILES: &str = "
+    SELECT f.value FROM events e, json_each(e.media_references) f
+    WHERE e.media_references IS NOT NULL AND e.media_references != '[]' AND json_valid(e.media_references)
+    UNION ALL
+    SELECT media_path FROM memories WHERE media_path IS NOT NULL";
+
+/// Events indexed for search per transaction.
+pub const FTS_BATCH_SIZE: usize = 2000;
+
+/// Extra attempts a read gets when SQLite is still busy after its busy timeout.
+const READ_RETRIES: u32 = 4;
+
+/// Backoff before the first read retry, doubled for each one after it.
+const READ_RETRY_BACKOFF: Duration = Duration::from_millis(25);
+
+/// Marks an ingestion run as active for as long as it lives.
+#[derive(Debug)]
+pub struct IngestionGuard(Arc<AtomicUsize>);
+
+impl IngestionGuard {
+    fn new(active: &Arc<AtomicUsize>) -> Self {
+        active.fetch_add(1, Ordering::SeqCst);
+        Self(active.clone())
+    }
+}
+
+impl Clone for IngestionGuard {
+    fn clone(&self) -> Self {
+        Self::new(&self.0)
+    }
+}
+
+impl Drop for IngestionGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Setting marking that `purge_os_metadata_media` has cleaned this database. Imports skip
+/// those files, so the scan only has to run once.
+const OS_METADATA_PURGED_SETTING: &str = "os_metadata_media_purged";
+
+/// Setting recording whether the database's events have content-derived ids ("stable") or
+/// predate them ("random").
+const EVENT_ID_SCHEME_KEY: &str = "event_id_scheme";
+
+/// Schema version written to `PRAGMA user_version`. Bump when the schema changes.
+pub const SCHEMA_VERSION: i32 = 20;
+
+/// Every media item shown in the gallery: chat media (`local`) and memories (`cloud`), one row
+/// per file with `id, path, media_type, timestamp, source, direction, conversation_id,
+/// media_index`, `media_type` being "Image" or "Video". Stream, timeline and seek queries all
+/// select from this so their counts and offsets agree.
+///
+/// Chat media counts as sent only when the chat JSON says so; without that it is treated as
+/// received, the copy worth keeping. Memories have no conversation and are always sent.
+const MEDIA_STREAM_SOURCE: &str = "
+    SELECT e.id, f.value AS path,
+           CASE WHEN e.event_type LIKE '%VIDEO%' THEN 'Video' ELSE 'Image' END AS media_type, e.timestamp,
+           'local' AS source,
+           CASE WHEN NOT json_valid(e.metadata) THEN 'received'
+                WHEN json_extract(e.metadata, '$.is_sender') THEN 'sent'
+                ELSE 'received' END AS direction,
+           e.conversation_id, f.key AS media_index
+    FROM events e, json_each(e.media_references) f
+    WHERE e.media_references IS NOT NULL AND e.media_references != '[]' AND json_valid(e.media_references)
+      AND e.event_type IN ('MEDIA', 'SNAP', 'SNAP_VIDEO', 'NOTE', 'STICKER')
+    UNION ALL
+    SELECT id, media_path AS path,
+           CASE WHEN media_type LIKE '%VIDEO%' THEN 'Video' ELSE 'Image' END AS media_type,
+           timestamp, 'cloud' AS source, 'sent' AS direction,
+           NULL AS conversation_id, 0 AS media_index
+    FROM memories
+    WHERE media_path IS NOT NULL";
+
+/// Narrows [`MEDIA_STREAM_SOURCE`] by a `MediaFilter` and direction: `?1` conversation, `?2`
+/// direction ('all', 'sent' or 'received'), `?3` source, `?4` media type, `?5`/`?6` the earliest
+/// and latest timestamp. Unset (NULL) parameters match everything.
+const MEDIA_STREAM_WHERE: &str = "WHERE (?1 IS NULL OR conversation_id = ?1) AND (?2 = 'all' OR direction = ?2)
+    AND (?3 IS NULL OR source = ?3) AND (?4 IS NULL OR media_type = ?4)
+    AND (?5 IS NULL OR timestamp >= ?5) AND (?6 IS NULL OR timestamp <= ?6)";
+
+/// Sort order of the unified media stream. Paging with OFFSET needs a total order, so ties on
+/// timestamp (a burst of snaps in one second) are broken by source and id, and a message's
+/// files keep their order.
+const MEDIA_STREAM_ORDER: &str = "timestamp DESC, source, id, media_index";
+
+/// `WHERE` clause limiting events `e` to conversation ?1, between ?2 and ?3 (RFC 3339, both
+/// inclusive, NULL for no bound) and to the senders in the JSON array ?4 (`[]` for anyone).
+const MESSAGE_FILTER: &str = "e.conversation_id = ?1
+    AND (?2 IS NULL OR e.timestamp >= ?2) AND (?3 IS NULL OR e.timestamp <= ?3)
+    AND (?4 = '[]' OR e.sender IN (SELECT value FROM json_each(?4)))";
+
+/// Tokens of context a search snippet keeps around its matches.
+const SNIPPET_TOKENS: i32 = 16;
+
+/// Ranges of the text between match markers in FTS5 `highlight()` output, in UTF-16 code units
+/// of the text without the markers, as JavaScript string offsets count.
+fn highlighted_ranges(highlighted: &str) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut offset = 0;
+    let mut start = None;
+    for c in highlighted.chars() {
+        if SNIPPET_MATCH_START.starts_with(c) {
+            start = Some(offset);
+        } else if SNIPPET_MATCH_END.starts_with(c) {
+            if let Some(start) = start.take() {
+                ranges.push((start, offset));
+            }
+        } else {
+            offset += c.len_utf16();
+        }
+    }
+    ranges
+}
+
+/// Whether an event is from the account owner: the chat JSON says so, or it comes from the
+/// owner's username. NULL (not sent) when neither is known.
+const SENT_BY_OWNER: &str = "(sender = (SELECT value FROM settings WHERE key = 'owner_username')
+    OR (json_valid(metadata) AND json_extract(metadata, '$.is_sender')))";
+
+/// A message's sender name: the owner's display name for the owner's own messages, otherwise
+/// the name from the friends list (`p`).
+const SENDER_NAME: &str = "COALESCE(
+    CASE WHEN e.sender = (SELECT value FROM settings WHERE key = 'owner_username')
+    THEN NULLIF((SELECT value FROM settings WHERE key = 'owner_display_name'), '') END,
+    p.display_name)";
+
+/// Conversations with their message counts, display names and flags, in the column order
+/// `map_conversation_row` reads. `?1` limits it to one conversation and `?2` to one export's
+/// messages; either is NULL for all. Without an export the stored counts are used, and only a
+/// per-export listing counts the events.
+const CONVERSATION_SELECT: &str = "
+    SELECT c.id, c.display_name, c.participants, c.last_event_at,
+    CASE WHEN ?2 IS NULL THEN c.message_count ELSE COALESCE(ec.msg_count, 0) END as msg_count,
+    p.display_name as resolved_name,
+    CASE WHEN ?2 IS NULL THEN c.linked_media_count ELSE COALESCE(ec.linked_media_count, 0) END as linked_media_count,
+    c.media_count, c.media_bytes, c.missing_media_count, p.avatar_path,
+    (SELECT group_concat(DISTINCT v.kind) FROM validation_issues v WHERE v.conversation_id = c.id) AS anomaly_kinds,
+    c.is_group,
+    CASE WHEN ?2 IS NULL THEN c.saved_count ELSE COALESCE(ec.saved_count, 0) END as saved_count,
+    CASE WHEN ?2 IS NULL THEN c.shared_location_count ELSE COALESCE(ec.shared_location_count, 0) END as shared_location_count,
+    c.completeness
+    FROM conversations c
+    LEFT JOIN people p ON c.id = p.username
+    LEFT JOIN (
+      SELECT conversation_id,
+             COUNT(*) as msg_count,
+             SUM(CASE WHEN media_references != '[]' AND media_references IS NOT NULL THEN 1 ELSE 0 END) as linked_media_count,
+             SUM(saved) as saved_count,
+             SUM(instr(metadata, '\"shared_location\"') > 0) as shared_location_count
+      FROM events
+      WHERE (?1 IS NULL OR conversation_id = ?1) AND export_id = ?2
+      GROUP BY conversation_id
+    ) ec ON ec.conversation_id = c.id
+    WHERE (?1 IS NULL OR c.id = ?1) AND (?2 IS NULL OR c.export_id = ?2 OR ec.msg_count > 0)";
+
+/// Whether conversation `c` belongs to export `?1`: imported with it or holding messages from
+/// it, since a later export merges into conversations an earlier one created. True when `?1`
+/// is NULL.
+const CONVERSATION_IN_EXPORT: &str = "(?1 IS NULL OR c.export_id = ?1
+    OR EXISTS (SELECT 1 FROM events e WHERE e.conversation_id = c.id AND e.export_id = ?1))";
+
+/// Write a parsed conversation. An existing row is updated in place, so what was stored for it
+/// since, such as its completeness summary, is kept.
+const CONVERSATION_UPSERT: &str =
+    "INSERT INTO conversations (id, display_name, participants, last_event_at, media_count, media_bytes, missing_media_count, is_group)
+     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+     ON CONFLICT(id) DO UPDATE SET display_name = excluded.display_name, participants = excluded.participants,
+       last_event_at = excluded.last_event_at, media_count = excluded.media_count, media_bytes = excluded.media_bytes,
+       missing_media_count = excluded.missing_media_count, is_group = excluded.is_group";
+
+/// Whether conversation `c` (joined to its person `p`) matches the `LIKE` pattern `?3`, by
+/// stored name, resolved name or id. True when `?3` is NULL.
+const CONVERSATION_NAME_FILTER: &str = "(?3 IS NULL OR c.display_name LIKE ?3 ESCAPE '\\'
+    OR p.display_name LIKE ?3 ESCAPE '\\' OR c.id LIKE ?3 ESCAPE '\\')";
+
+/// Largest timezone offset accepted, UTC-14:00 to UTC+14:00.
+pub const MAX_TIMEZONE_OFFSET_MINUTES: i32 = 14 * 60;
+
+/// How many unnamed recent searches are kept.
+pub const RECENT_SEARCH_LIMIT: i64 = 50;
+
+/// Tables and columns the read queries rely on; a read-only database must have all of them.
+const REQUIRED_COLUMNS: &[(&str, &str)] = &[
+    ("exports", "source_paths"),
+    ("exports", "source_type"),
+    ("conversations", "participants"),
+    ("events", "media_references"),
+    ("memories", "download_status"),
+    ("people", "display_name"),
+    ("conversations", "media_bytes"),
+    ("ingestion_runs", "file_issues"),
+    ("people", "avatar_path"),
+    ("exports", "import_phases"),
+    ("validation_issues", "kind"),
+    ("conversations", "is_group"),
+    ("events", "saved"),
+    ("ingestion_runs", "timestamp_format"),
+    ("media_info", "size_bytes"),
+    ("exports", "extraction_path"),
+    ("exports", "layout"),
+    ("conversations", "completeness"),
+    ("conversations", "export_id"),
+    ("conversations", "message_count"),
+    ("locations", "source"),
+    ("memories", "overlay_url"),
+    ("memories", "failure_reason"),
+    ("download_jobs", "skipped"),
+    ("exports", "parts"),
+];
+
+/// Parse a stored RFC 3339 timestamp. An unreadable value sorts first as `MIN_UTC` and is also
+/// returned as-is, so callers can keep the original text instead of silently losing it.
+fn parse_stored_timestamp(text: &str) -> (DateTime<Utc>, Option<&str>) {
+    match DateTime::parse_from_rfc3339(text) {
+        Ok(dt) => (dt.with_timezone(&Utc), None),
+        Err(e) => {
+            log::warn!("Bad timestamp in DB: '{}': {}", text, e);
+            (DateTime::<Utc>::MIN_UTC, Some(text))
+        }
+    }
+}
+
+/// A stored avatar path, dropped if the file has since been moved or deleted.
+fn existing_avatar(path: Option<String>) -> Option<PathBuf> {
+    path.map(PathBuf::from).filter(|p| p.is_file())
+}
+
+/// Snap history was part of the chats phase, and location history part of the memories phase,
+/// before each could be imported on its own.
+fn parse_import_phases(json: &str) -> Option<ImportOptions> {
+    let value: serde_json::Value = serde_json::from_str(json).ok()?;
+    let mut phases: ImportOptions = serde_json::from_value(value.clone()).ok()?;
+    if value.get("snap_history").is_none() {
+        phases.snap_history = phases.chats;
+    }
+    if value.get("location_history").is_none() {
+        phases.location_history = phases.memories;
+    }
+    Some(phases)
+}
+
+fn validation_status_str(status: &ValidationStatus) -> &'static str {
+    match status {
+        ValidationStatus::Valid => "Valid",
+        ValidationStatus::Incomplete => "Incomplete",
+        ValidationStatus::Corrupted => "Corrupted",
+        ValidationStatus::Unknown => "Unknown",
+    }
+}
+
+fn write_export(conn: &rusqlite::Connection, export: &ExportSet) -> AppResult<()> {
+    let status_str = validation_status_str(&export.validation_status);
+    let source_type_str = match &export.source_type {
+        ExportSourceType::Zip => "Zip",
+        ExportSourceType::Folder => "Folder",
+    };
+    let paths_json = serde_json::to_string(&export.source_paths).unwrap_or_else(|_| "[]".to_string());
+    let phases_json = export.import_phases.as_ref().map(serde_json::to_string).transpose()?;
+    let layout_json = export.layout.as_ref().map(serde_json::to_string).transpose()?;
+    let parts_json = serde_json::to_string(&export.parts)?;
+
+    conn.execute(
+        "INSERT OR REPLACE INTO exports (id, source_paths, source_type, creation_date, validation_status, import_phases, extraction_path, layout, parts)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            export.id,
+            paths_json,
+            source_type_str,
+            export.creation_date.map(|d| d.to_rfc3339()),
+            status_str,
+            phases_json,
+            export.extraction_path.as_ref().map(|p| p.to_string_lossy().to_string()),
+            layout_json,
+            parts_json
+        ],
+    )?;
+    Ok(())
+}
+
+/// What one stored event adds to its conversation's message, linked media, saved and shared
+/// location counts, counted the way `refresh_conversation_counts_with` counts them.
+const EVENT_COUNTS_SELECT: &str = "
+    SELECT conversation_id,
+           COALESCE(media_references != '[]' AND media_references IS NOT NULL, 0),
+           COALESCE(saved, 0),
+           COALESCE(instr(metadata, '\"shared_location\"') > 0, 0)
+    FROM events WHERE id = ?1";
+
+/// Running changes to the stored counts of conversations while their events are written, so a
+/// chunk of events updates each conversation once instead of recounting all its events.
+#[derive(Default)]
+struct CountChanges(HashMap<String, [i64; 4]>);
+
+impl CountChanges {
+    /// Add what the stored event `id` counts for (`sign` 1), or take it away (`sign` -1). An
+    /// event that isn't stored yet counts for nothing.
+    fn track(&mut self, counts_stmt: &mut rusqlite::Statement, id: &str, sign: i64) -> AppResult<()> {
+        use rusqlite::OptionalExtension;
+        let row = counts_stmt
+            .query_row([id], |r| {
+                Ok((
+                    r.get::<_, Option<String>>(0)?,
+                    [1, r.get::<_, i64>(1)?, r.get::<_, i64>(2)?, r.get::<_, i64>(3)?],
+                ))
+            })
+            .optional()?;
+        if let Some((Some(conversation_id), counts)) = row {
+            let totals = self.0.entry(conversation_id).or_default();
+            for (total, count) in totals.iter_mut().zip(counts) {
+                *total += sign * count;
+            }
+        }
+        Ok(())
+    }
+
+    fn apply(self, conn: &rusqlite::Connection) -> AppResult<()> {
+        let mut stmt = conn.prepare(
+            "UPDATE conversations SET message_count = message_count + ?2,
+                linked_media_count = linked_media_count + ?3, saved_count = saved_count + ?4,
+                shared_location_count = shared_location_count + ?5
+             WHERE id = ?1",
+        )?;
+        for (id, [messages, linked_media, saved, shared_locations]) in self.0 {
+            if [messages, linked_media, saved, shared_locations] != [0; 4] {
+                stmt.execute(params![id, messages, linked_media, saved, shared_locations])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Bookkeeping for one ingestion run: what existed before it started and which rows it wrote,
+/// so a failed run can be rolled back without touching data from earlier imports.
+#[derive(Debug, Clone)]
+pub struct IngestionRun {
+    pub export_id: String,
+    pub started_at: DateTime<Utc>,
+    previous_export: Option<ExportSet>,
+    /// Rows as they were before the run, keyed by conversation id.
+    previous_conversations: HashMap<String, Conversation>,
+    created_conversations: Vec<String>,
+    merged_conversations: Vec<String>,
+    event_ids: Vec<String>,
+    memory_ids: Vec<String>,
+    /// Rowids of location history points the run added.
+    location_ids: Vec<i64>,
+    file_issues: Vec<JsonFileIssue>,
+    timestamp_format: Option<TimestampFormatDecision>,
+    _guard: IngestionGuard,
+}
+
+impl IngestionRun {
+    /// Remember a damaged json/ file so it ends up in the run's history entry.
+    pub fn note_file_issue(&mut self, issue: JsonFileIssue) {
+        self.file_issues.push(issue);
+    }
+
+    pub fn file_issues(&self) -> &[JsonFileIssue] {
+        &self.file_issues
+    }
+
+    /// Remember how slash dates were read so it ends up in the run's history entry.
+    pub fn set_timestamp_format(&mut self, decision: Option<TimestampFormatDecision>) {
+        self.timestamp_format = decision;
+    }
+
+    /// Record conversations about to be written, split into newly created and merged ones.
+    pub fn track_conversations(&mut self, conversations: &[Conversation]) {
+        for convo in conversations {
+            if self.previous_conversations.contains_key(&convo.id) {
+                self.merged_conversations.push(convo.id.clone());
+            } else {
+                self.created_conversations.push(convo.id.clone());
+            }
+        }
+    }
+
+    pub fn track_events(&mut self, events: &[Event]) {
+        self.event_ids.extend(events.iter().map(|e| e.id.clone()));
+    }
+
+    pub fn track_event_ids(&mut self, ids: impl IntoIterator<Item = String>) {
+        self.event_ids.extend(ids);
+    }
+
+    pub fn track_memories(&mut self, memories: &[Memory]) {
+        self.memory_ids.extend(memories.iter().map(|m| m.id.clone()));
+    }
+
+    pub fn track_locations(&mut self, rowids: Vec<i64>) {
+        self.location_ids.extend(rowids);
+    }
+
+    pub fn created_conversations(&self) -> &[String] {
+        &self.created_conversations
+    }
+
+    pub fn merged_conversations(&self) -> &[String] {
+        &self.merged_conversations
+    }
 }
 
 impl DatabaseManager {
     pub fn new(db_path: &Path) -> AppResult<Self> {
+        Self::from_manager(SqliteConnectionManager::file(db_path), None)
+    }
+
+    /// Open a private in-memory database with the full schema.
+    ///
+    /// Uses a uniquely named shared-cache URI so every pooled connection sees the same
+    /// data, which lets tests and sandboxed features run the real query code.
+    pub fn new_in_memory() -> AppResult<Self> {
+        let uri = format!("file:memdb-{}?mode=memory&cache=shared", uuid::Uuid::new_v4());
+        // The database is dropped once its last connection closes, so hold one open
+        let keepalive = rusqlite::Connection::open(&uri)?;
+        Self::from_manager(SqliteConnectionManager::file(&uri), Some(keepalive))
+    }
+
+    /// Open an existing database file without modifying it.
+    ///
+    /// No schema setup or migrations run, and every write method fails with
+    /// `AppError::ReadOnlyDatabase`. Fails up front if the file's schema is newer than this
+    /// build understands, or too old to have the columns the read queries use.
+    pub fn open_readonly(db_path: &Path) -> AppResult<Self> {
+        if !db_path.is_file() {
+            return Err(AppError::Validation(format!(
+                "Database file not found: {}",
+                db_path.display()
+            )));
+        }
+        let flags = rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY
+            | rusqlite::OpenFlags::SQLITE_OPEN_URI
+            | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX;
         let manager = SqliteConnectionManager::file(db_path)
+            .with_flags(flags)
             .with_init(|conn| {
                 conn.execute_batch(
                     "
-                    PRAGMA journal_mode=WAL;
-                    PRAGMA synchronous=NORMAL;
-                    PRAGMA busy_timeout=5000;
-                    PRAGMA foreign_keys=ON;
-                    PRAGMA cache_size=-64000; -- 64MB cache
-                    PRAGMA temp_store=MEMORY;
-                ",
+                PRAGMA query_only=ON;
+                PRAGMA busy_timeout=5000;
+                PRAGMA cache_size=-16000;
+                PRAGMA temp_store=MEMORY;
+            ",
                 )
             });
+        let pool = r2d2::Pool::builder()
+            .max_size(4)
+            .connection_timeout(std::time::Duration::from_secs(10))
+            .build(manager)
+            .map_err(|e| AppError::Generic(format!("Failed to open database: {}", e)))?;
+
+        let db = Self {
+            pool,
+            _keepalive: None,
+            read_only: true,
+            active_ingestions: Arc::default(),
+            last_maintenance: Mutex::new(None),
+            changes: Arc::default(),
+        };
+        db.check_readable_schema()?;
+        Ok(db)
+    }
+
+    fn check_readable_schema(&self) -> AppResult<()> {
+        let conn = self.conn()?;
+        let version: i32 = conn.query_row("PRAGMA user_version", [], |r| r.get(0))?;
+        if version > SCHEMA_VERSION {
+            return Err(AppError::Validation(format!(
+                "Database schema version {} is newer than this app supports ({}). Update the app to open it.",
+                version, SCHEMA_VERSION
+            )));
+        }
+        for (table, column) in REQUIRED_COLUMNS {
+            let present: i32 = conn.query_row(
+                "SELECT COUNT(*) FROM pragma_table_info(?1) WHERE name = ?2",
+                params![table, column],
+                |r| r.get(0),
+            )?;
+            if present == 0 {
+                return Err(AppError::Validation(format!(
+                    "Database schema version {} is too old to open read-only (missing {}.{}). \
+                     Open it as the live database once to upgrade it.",
+                    version, table, column
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn from_manager(manager: SqliteConnectionManager, keepalive: Option<rusqlite::Connection>) -> AppResult<Self> {
+        let changes = Arc::new(ChangeTracker::default());
+        let hooks = changes.clone();
+        let manager = manager.with_init(move |conn| {
+            conn.execute_batch(
+                "
+                PRAGMA journal_mode=WAL;
+                PRAGMA synchronous=NORMAL;
+                PRAGMA busy_timeout=5000;
+                PRAGMA foreign_keys=ON;
+                PRAGMA cache_size=-64000; -- 64MB cache
+                PRAGMA temp_store=MEMORY;
+            ",
+            )?;
+            hooks.install(conn);
+            Ok(())
+        });
 
         let pool = r2d2::Pool::builder()
             .max_size(10)
@@ -36,12 +593,150 @@ impl DatabaseManager {
             .build(manager)
             .map_err(|e| crate::error::AppError::Generic(format!("Failed to create pool: {}", e)))?;
 
-        let manager = Self { pool };
+        let manager = Self {
+            pool,
+            _keepalive: keepalive.map(std::sync::Mutex::new),
+            read_only: false,
+            active_ingestions: Arc::default(),
+            last_maintenance: Mutex::new(None),
+            changes,
+        };
         manager.initialize_schema()?;
         manager.run_migrations()?;
+        // Setting up the schema isn't news to anyone
+        manager.changes.take_settled(Duration::ZERO);
         Ok(manager)
     }
 
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Committed changes not yet reported to the frontend.
+    pub fn changes(&self) -> &ChangeTracker {
+        &self.changes
+    }
+
+    /// Run `run_maintenance` every `MAINTENANCE_INTERVAL` for as long as this database is open.
+    /// The task only holds a weak reference, so dropping the database (reset, reimport) ends it.
+    pub fn start_maintenance(self: &Arc<Self>) {
+        let db: Weak<Self> = Arc::downgrade(self);
+        let spawned = std::thread::Builder::new()
+            .name("db-maintenance".into())
+            .spawn(move || loop {
+                std::thread::sleep(MAINTENANCE_INTERVAL);
+                let Some(db) = db.upgrade() else {
+                    break;
+                };
+                if let Err(e) = db.run_maintenance() {
+                    log::warn!("Database maintenance failed: {}", e);
+                }
+            });
+        if let Err(e) = spawned {
+            log::warn!("Could not start database maintenance: {}", e);
+        }
+    }
+
+    /// Check every idle pooled connection and checkpoint the WAL once it is over
+    /// `WAL_CHECKPOINT_THRESHOLD`. Never waits: busy connections are left alone and the
+    /// checkpoint is PASSIVE. Does nothing while an ingestion run is active.
+    pub fn run_maintenance(&self) -> AppResult<MaintenanceReport> {
+        self.maintain(WAL_CHECKPOINT_THRESHOLD)
+    }
+
+    fn maintain(&self, wal_threshold: u64) -> AppResult<MaintenanceReport> {
+        let started = Instant::now();
+        let mut report = MaintenanceReport {
+            ran_at: Some(Utc::now()),
+            ..Default::default()
+        };
+        if self.active_ingestions.load(Ordering::SeqCst) > 0 {
+            log::debug!("Database maintenance skipped: an import is running");
+            report.skipped_for_ingestion = true;
+            return Ok(self.remember_maintenance(report));
+        }
+
+        // Holding them all at once makes each checkout a different connection. r2d2 tests a
+        // connection on checkout and replaces it if it stopped working (e.g. after sleep).
+        let idle = self.pool.state().idle_connections;
+        let held: Vec<_> = (0..idle).map_while(|_| self.pool.try_get()).collect();
+        for conn in &held {
+            report.connections_checked += 1;
+            if conn.query_row("SELECT 1", [], |r| r.get::<_, i64>(0)).is_err() {
+                report.connections_failed += 1;
+            }
+        }
+        drop(held);
+
+        let conn = self.conn()?;
+        report.wal_bytes = Self::wal_bytes(&conn);
+        if !self.read_only && report.wal_bytes > wal_threshold {
+            report.checkpointed_frames = conn
+                .query_row("PRAGMA wal_checkpoint(PASSIVE)", [], |r| r.get::<_, i64>(2))?
+                .max(0);
+        }
+        report.duration_ms = started.elapsed().as_millis() as u64;
+        log::info!(
+            "Database maintenance: WAL {} KB, {} frame(s) checkpointed, {}/{} connection(s) healthy, {} ms",
+            report.wal_bytes / 1024,
+            report.checkpointed_frames,
+            report.connections_checked - report.connections_failed,
+            report.connections_checked,
+            report.duration_ms
+        );
+        Ok(self.remember_maintenance(report))
+    }
+
+    fn remember_maintenance(&self, report: MaintenanceReport) -> MaintenanceReport {
+        if let Ok(mut last) = self.last_maintenance.lock() {
+            *last = Some(report.clone());
+        }
+        report
+    }
+
+    /// The main database file; None for in-memory databases.
+    fn file_path(conn: &rusqlite::Connection) -> Option<PathBuf> {
+        conn.query_row("SELECT file FROM pragma_database_list WHERE name = 'main'", [], |r| {
+            r.get::<_, String>(0)
+        })
+        .ok()
+        .filter(|f| !f.is_empty())
+        .map(PathBuf::from)
+    }
+
+    fn wal_bytes(conn: &rusqlite::Connection) -> u64 {
+        Self::file_path(conn)
+            .and_then(|p| std::fs::metadata(format!("{}-wal", p.display())).ok())
+            .map_or(0, |m| m.len())
+    }
+
+    pub fn get_database_info(&self) -> AppResult<DatabaseInfo> {
+        let _span = perf::query("get_database_info");
+        let conn = self.conn()?;
+        let state = self.pool.state();
+        Ok(DatabaseInfo {
+            schema_version: conn.query_row("PRAGMA user_version", [], |r| r.get(0))?,
+            read_only: self.read_only,
+            file_bytes: Self::file_path(&conn)
+                .and_then(|p| std::fs::metadata(p).ok())
+                .map_or(0, |m| m.len()),
+            wal_bytes: Self::wal_bytes(&conn),
+            connections: state.connections,
+            idle_connections: state.idle_connections,
+            last_maintenance: self.last_maintenance.lock().ok().and_then(|m| m.clone()),
+        })
+    }
+
+    /// SQLite's own consistency check. Returns the problems it found; empty means healthy.
+    pub fn quick_check(&self) -> AppResult<Vec<String>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare("PRAGMA quick_check")?;
+        let rows = stmt
+            .query_map([], |r| r.get::<_, String>(0))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(rows.into_iter().filter(|r| r != "ok").collect())
+    }
+
     fn conn(&self) -> AppResult<r2d2::PooledConnection<SqliteConnectionManager>> {
         self.pool.get().map_err(|e| {
             log::error!("Failed to acquire database connection: {}", e);
@@ -49,6 +744,53 @@ impl DatabaseManager {
         })
     }
 
+    /// Connection for a method that modifies data; refused on read-only databases.
+    fn write_conn(&self) -> AppResult<r2d2::PooledConnection<SqliteConnectionManager>> {
+        if self.read_only {
+            return Err(AppError::ReadOnlyDatabase(
+                "a read-only database is active; switch back to the live database to make changes".into(),
+            ));
+        }
+        self.conn()
+    }
+
+    /// Run the read-only `query`, retrying with jittered backoff while SQLite reports the
+    /// database busy or locked. `statement` names the query in the contention logs.
+    fn read_retrying<T: TraceRows>(
+        &self,
+        statement: &'static str,
+        query: impl Fn(&rusqlite::Connection) -> AppResult<T>,
+    ) -> AppResult<T> {
+        let mut span = perf::query(statement);
+        let mut attempt = 0;
+        loop {
+            // The connection goes back to the pool before any backoff
+            let result = self.conn().and_then(|conn| query(&conn));
+            match result {
+                Err(AppError::Busy(e)) if attempt < READ_RETRIES => {
+                    let backoff = READ_RETRY_BACKOFF * 2u32.pow(attempt);
+                    let jitter = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .map_or(0, |d| d.subsec_nanos()) as u64
+                        % backoff.as_millis() as u64;
+                    attempt += 1;
+                    log::warn!("{} hit database contention (attempt {}): {}", statement, attempt, e);
+                    std::thread::sleep(backoff + Duration::from_millis(jitter));
+                }
+                Err(AppError::Busy(e)) => {
+                    log::error!("{} still busy after {} retries: {}", statement, READ_RETRIES, e);
+                    return Err(AppError::Busy(format!("{}: {}", statement, e)));
+                }
+                result => {
+                    if let Ok(value) = &result {
+                        span.set_rows(value.trace_rows());
+                    }
+                    return result;
+                }
+            }
+        }
+    }
+
     fn initialize_schema(&self) -> AppResult<()> {
         self.conn()?.execute_batch(
             "
@@ -57,19 +799,34 @@ impl DatabaseManager {
                 source_paths TEXT NOT NULL,
                 source_type TEXT NOT NULL DEFAULT 'Folder',
                 creation_date TEXT,
-                validation_status TEXT NOT NULL
+                validation_status TEXT NOT NULL,
+                import_phases TEXT,
+                extraction_path TEXT,
+                layout TEXT,
+                parts TEXT
             );
 
             CREATE TABLE IF NOT EXISTS people (
                 username TEXT PRIMARY KEY,
-                display_name TEXT
+                display_name TEXT,
+                avatar_path TEXT
             );
 
             CREATE TABLE IF NOT EXISTS conversations (
                 id TEXT PRIMARY KEY,
                 display_name TEXT,
                 participants TEXT,
-                last_event_at TEXT
+                last_event_at TEXT,
+                media_count INTEGER NOT NULL DEFAULT 0,
+                media_bytes INTEGER NOT NULL DEFAULT 0,
+                missing_media_count INTEGER NOT NULL DEFAULT 0,
+                is_group INTEGER NOT NULL DEFAULT 0,
+                completeness TEXT,
+                export_id TEXT,
+                message_count INTEGER NOT NULL DEFAULT 0,
+                linked_media_count INTEGER NOT NULL DEFAULT 0,
+                saved_count INTEGER NOT NULL DEFAULT 0,
+                shared_location_count INTEGER NOT NULL DEFAULT 0
             );
 
             CREATE TABLE IF NOT EXISTS events (
@@ -82,6 +839,7 @@ impl DatabaseManager {
                 event_type TEXT NOT NULL,
                 media_references TEXT,
                 metadata TEXT,
+                saved INTEGER NOT NULL DEFAULT 0,
                 FOREIGN KEY(export_id) REFERENCES exports(id),
                 FOREIGN KEY(conversation_id) REFERENCES conversations(id)
             );
@@ -97,6 +855,8 @@ impl DatabaseManager {
                 proxy_url TEXT,
                 download_status TEXT NOT NULL DEFAULT 'Pending',
                 export_id TEXT NOT NULL,
+                overlay_url TEXT,
+                failure_reason TEXT,
                 FOREIGN KEY(export_id) REFERENCES exports(id)
             );
 
@@ -105,6 +865,142 @@ impl DatabaseManager {
                 value TEXT NOT NULL
             );
 
+            CREATE TABLE IF NOT EXISTS tags (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                color TEXT,
+                created_at TEXT NOT NULL
+            );
+
+            -- anchor: stable description of the entity, used to re-resolve it after a reimport
+            CREATE TABLE IF NOT EXISTS taggings (
+                tag_id INTEGER NOT NULL,
+                entity_type TEXT NOT NULL,
+                entity_id TEXT NOT NULL,
+                anchor TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                PRIMARY KEY(tag_id, entity_type, entity_id),
+                FOREIGN KEY(tag_id) REFERENCES tags(id) ON DELETE CASCADE
+            );
+            CREATE INDEX IF NOT EXISTS idx_taggings_entity ON taggings(entity_type, entity_id);
+
+            -- value: username for Sender rules, message anchor for Event rules
+            CREATE TABLE IF NOT EXISTS redactions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                kind TEXT NOT NULL,
+                value TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                UNIQUE(kind, value)
+            );
+
+            -- Recent searches (name IS NULL, capped) and searches the user saved under a name
+            CREATE TABLE IF NOT EXISTS saved_searches (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT,
+                query TEXT NOT NULL,
+                filters TEXT NOT NULL DEFAULT '',
+                last_used_at TEXT NOT NULL,
+                use_count INTEGER NOT NULL DEFAULT 1
+            );
+            CREATE UNIQUE INDEX IF NOT EXISTS idx_saved_searches_recent ON saved_searches(query, filters)
+                WHERE name IS NULL;
+            CREATE UNIQUE INDEX IF NOT EXISTS idx_saved_searches_name ON saved_searches(name)
+                WHERE name IS NOT NULL;
+
+            CREATE TABLE IF NOT EXISTS export_artifacts (
+                export_id TEXT NOT NULL,
+                path TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                modified_at TEXT,
+                hash TEXT,
+                file_count INTEGER NOT NULL DEFAULT 1,
+                PRIMARY KEY (export_id, path)
+            );
+
+            -- Bulk memory downloads; `filters` is the MemoryFilter as JSON
+            CREATE TABLE IF NOT EXISTS download_jobs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                filters TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                state TEXT NOT NULL,
+                total INTEGER NOT NULL DEFAULT 0,
+                completed INTEGER NOT NULL DEFAULT 0,
+                failed INTEGER NOT NULL DEFAULT 0,
+                skipped INTEGER NOT NULL DEFAULT 0
+            );
+
+            -- Journal of file moves; a row is written before the move and marked Done in the
+            -- same transaction that repoints the database at the new path
+            CREATE TABLE IF NOT EXISTS fs_operations (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                kind TEXT NOT NULL,
+                src TEXT NOT NULL,
+                dst TEXT NOT NULL,
+                state TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+
+            -- Word and emoji counts per conversation ('' for all), valid while the message count matches
+            CREATE TABLE IF NOT EXISTS text_analytics_cache (
+                scope TEXT PRIMARY KEY,
+                event_count INTEGER NOT NULL,
+                result TEXT NOT NULL,
+                computed_at TEXT NOT NULL
+            );
+
+            -- Points from json/location_history.json
+            CREATE TABLE IF NOT EXISTS locations (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                export_id TEXT NOT NULL,
+                timestamp TEXT NOT NULL,
+                latitude REAL NOT NULL,
+                longitude REAL NOT NULL,
+                source TEXT NOT NULL,
+                UNIQUE(export_id, timestamp, latitude, longitude, source)
+            );
+
+            -- Where an unfinished search index build for an export got to
+            CREATE TABLE IF NOT EXISTS fts_population (
+                export_id TEXT PRIMARY KEY,
+                cursor TEXT NOT NULL,
+                indexed INTEGER NOT NULL DEFAULT 0
+            );
+
+            -- File sizes of media paths, filled as media pages are served
+            CREATE TABLE IF NOT EXISTS media_info (
+                path TEXT PRIMARY KEY,
+                size_bytes INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS validation_issues (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                export_id TEXT NOT NULL,
+                conversation_id TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                affected INTEGER NOT NULL DEFAULT 0,
+                total INTEGER NOT NULL DEFAULT 0,
+                detail TEXT NOT NULL DEFAULT '',
+                detected_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_validation_issues_conversation ON validation_issues(conversation_id);
+
+            CREATE TABLE IF NOT EXISTS ingestion_runs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                export_id TEXT NOT NULL,
+                started_at TEXT NOT NULL,
+                finished_at TEXT NOT NULL,
+                status TEXT NOT NULL,
+                error TEXT,
+                conversations INTEGER NOT NULL DEFAULT 0,
+                events INTEGER NOT NULL DEFAULT 0,
+                memories INTEGER NOT NULL DEFAULT 0,
+                cleanup TEXT,
+                file_issues TEXT,
+                timestamp_format TEXT
+            );
+
             -- High-performance Indices
             CREATE INDEX IF NOT EXISTS idx_events_timestamp ON events(timestamp);
             CREATE INDEX IF NOT EXISTS idx_events_export_id ON events(export_id);
@@ -122,7 +1018,8 @@ impl DatabaseManager {
                 event_id UNINDEXED,
                 conversation_id UNINDEXED,
                 sender UNINDEXED,
-                tokenize='unicode61'
+                tokenize='unicode61',
+                prefix='2 3 4'
             );
         ",
         )?;
@@ -155,15 +1052,19 @@ impl DatabaseManager {
             log::info!("Migration: converting source_path to source_paths");
             // Add the new column
             conn.execute_batch("ALTER TABLE exports ADD COLUMN source_paths TEXT;")?;
-            
+
             // For any existing rows, wrap the single source_path in a JSON array
             let mut stmt = conn.prepare("SELECT id, source_path FROM exports WHERE source_paths IS NULL")?;
-            let rows: Vec<(String, String)> = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            let rows: Vec<(String, String)> = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
                 .collect::<std::result::Result<Vec<_>, _>>()?;
-            
+
             for (id, path) in rows {
                 let paths_json = serde_json::to_string(&vec![path]).unwrap_or_else(|_| "[]".to_string());
-                conn.execute("UPDATE exports SET source_paths = ?1 WHERE id = ?2", params![paths_json, id])?;
+                conn.execute(
+                    "UPDATE exports SET source_paths = ?1 WHERE id = ?2",
+                    params![paths_json, id],
+                )?;
             }
         }
 
@@ -185,82 +1086,436 @@ impl DatabaseManager {
             )?;
         }
 
-        Ok(())
-    }
+        // 4. Cached per-conversation media totals
+        let has_media_bytes: bool = conn
+            .prepare("SELECT COUNT(*) FROM pragma_table_info('conversations') WHERE name = 'media_bytes'")?
+            .query_row([], |row| row.get::<_, i32>(0))
+            .unwrap_or(0)
+            > 0;
 
-    pub fn insert_people(&self, people: &[Person]) -> AppResult<()> {
-        let mut conn = self.conn()?;
-        let tx = conn.transaction()?;
-        {
-            let mut stmt = tx.prepare("INSERT OR REPLACE INTO people (username, display_name) VALUES (?1, ?2)")?;
-            for person in people {
-                stmt.execute(params![person.username, person.display_name])?;
-            }
+        if !has_media_bytes {
+            log::info!("Migration: adding media stats columns to conversations table");
+            conn.execute_batch(
+                "
+                ALTER TABLE conversations ADD COLUMN media_count INTEGER NOT NULL DEFAULT 0;
+                ALTER TABLE conversations ADD COLUMN media_bytes INTEGER NOT NULL DEFAULT 0;
+                ALTER TABLE conversations ADD COLUMN missing_media_count INTEGER NOT NULL DEFAULT 0;
+            ",
+            )?;
         }
-        tx.commit()?;
-        Ok(())
-    }
 
-    pub fn insert_export(&self, export: &ExportSet) -> AppResult<()> {
-        let status_str = match &export.validation_status {
-            ValidationStatus::Valid => "Valid",
-            ValidationStatus::Incomplete => "Incomplete",
-            ValidationStatus::Corrupted => "Corrupted",
-            ValidationStatus::Unknown => "Unknown",
-        };
-        let source_type_str = match &export.source_type {
-            ExportSourceType::Zip => "Zip",
-            ExportSourceType::Folder => "Folder",
-        };
-        let paths_json = serde_json::to_string(&export.source_paths).unwrap_or_else(|_| "[]".to_string());
-        
-        self.conn()?.execute(
-            "INSERT OR REPLACE INTO exports (id, source_paths, source_type, creation_date, validation_status) VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![
-                export.id,
-                paths_json,
-                source_type_str,
-                export.creation_date.map(|d| d.to_rfc3339()),
-                status_str
-            ],
+        // 5. storage_path used to cover memory downloads; carry it over to downloads_path
+        conn.execute(
+            "INSERT OR IGNORE INTO settings (key, value)
+             SELECT 'downloads_path', value FROM settings WHERE key = 'storage_path'",
+            [],
         )?;
-        Ok(())
-    }
 
-    pub fn batch_insert_conversations(&self, conversations: &[Conversation]) -> AppResult<()> {
-        let mut conn = self.conn()?;
-        let tx = conn.transaction()?;
-        {
-            let mut stmt = tx.prepare(
-                "INSERT OR REPLACE INTO conversations (id, display_name, participants, last_event_at) VALUES (?1, ?2, ?3, ?4)"
-            )?;
-            for convo in conversations {
-                stmt.execute(params![
-                    convo.id,
-                    convo.display_name,
-                    serde_json::to_string(&convo.participants).unwrap_or_else(|_| "[]".to_string()),
-                    convo.last_event_at.map(|d| d.to_rfc3339())
-                ])?;
-            }
+        // 6. Damaged json/ files recorded per ingestion run
+        let has_file_issues: bool = conn
+            .prepare("SELECT COUNT(*) FROM pragma_table_info('ingestion_runs') WHERE name = 'file_issues'")?
+            .query_row([], |row| row.get::<_, i32>(0))
+            .unwrap_or(0)
+            > 0;
+
+        if !has_file_issues {
+            log::info!("Migration: adding file_issues column to ingestion_runs table");
+            conn.execute("ALTER TABLE ingestion_runs ADD COLUMN file_issues TEXT", [])?;
         }
-        tx.commit()?;
-        Ok(())
-    }
+
+        // 7. Prefix indexes on the FTS table so search-as-you-type doesn't scan the whole index
+        let fts_sql: String = conn.query_row("SELECT sql FROM sqlite_master WHERE name = 'events_fts'", [], |row| {
+            row.get(0)
+        })?;
+        if !fts_sql.contains("prefix=") {
+            log::info!("Migration: rebuilding events_fts with prefix indexes");
+            conn.execute_batch(
+                "
+                BEGIN;
+                CREATE VIRTUAL TABLE events_fts_new USING fts5(
+                    content,
+                    event_id UNINDEXED,
+                    conversation_id UNINDEXED,
+                    sender UNINDEXED,
+                    tokenize='unicode61',
+                    prefix='2 3 4'
+                );
+                INSERT INTO events_fts_new (content, event_id, conversation_id, sender)
+                    SELECT content, event_id, conversation_id, sender FROM events_fts;
+                DROP TABLE events_fts;
+                ALTER TABLE events_fts_new RENAME TO events_fts;
+                COMMIT;
+            ",
+            )?;
+        }
+
+        // 8. Events stored without a conversation move to their export's "Unsorted" conversation
+        let mut recount_conversations = false;
+        let orphans: i64 = conn.query_row("SELECT COUNT(*) FROM events WHERE conversation_id IS NULL", [], |row| {
+            row.get(0)
+        })?;
+        if orphans > 0 {
+            log::info!(
+                "Migration: moving {} event(s) without a conversation to Unsorted",
+                orphans
+            );
+            conn.execute_batch(&format!(
+                "
+                BEGIN;
+                INSERT OR IGNORE INTO conversations (id, display_name, participants, last_event_at)
+                    SELECT '{prefix}' || export_id, '{name}', json_group_array(DISTINCT sender), MAX(timestamp)
+                    FROM events WHERE conversation_id IS NULL GROUP BY export_id;
+                UPDATE events_fts SET conversation_id =
+                    (SELECT '{prefix}' || e.export_id FROM events e WHERE e.id = events_fts.event_id)
+                    WHERE conversation_id IS NULL;
+                UPDATE events SET conversation_id = '{prefix}' || export_id WHERE conversation_id IS NULL;
+                COMMIT;
+            ",
+                prefix = crate::ingestion::UNSORTED_CONVERSATION_PREFIX,
+                name = crate::ingestion::UNSORTED_CONVERSATION_NAME,
+            ))?;
+            recount_conversations = true;
+        }
+
+        // 9. Avatar images found in the export
+        let has_avatar_path: bool = conn
+            .prepare("SELECT COUNT(*) FROM pragma_table_info('people') WHERE name = 'avatar_path'")?
+            .query_row([], |row| row.get::<_, i32>(0))
+            .map(|count| count > 0)?;
+        if !has_avatar_path {
+            log::info!("Migration: adding avatar_path column to people table");
+            conn.execute("ALTER TABLE people ADD COLUMN avatar_path TEXT", [])?;
+        }
+
+        // 10. Which import phases ran; NULL for exports imported before partial imports existed
+        let has_import_phases: bool = conn
+            .prepare("SELECT COUNT(*) FROM pragma_table_info('exports') WHERE name = 'import_phases'")?
+            .query_row([], |row| row.get::<_, i32>(0))
+            .map(|count| count > 0)?;
+        if !has_import_phases {
+            log::info!("Migration: adding import_phases column to exports table");
+            conn.execute("ALTER TABLE exports ADD COLUMN import_phases TEXT", [])?;
+        }
+
+        // 11. Group flag; older imports only knew groups by their title
+        let has_is_group: bool = conn
+            .prepare("SELECT COUNT(*) FROM pragma_table_info('conversations') WHERE name = 'is_group'")?
+            .query_row([], |row| row.get::<_, i32>(0))
+            .map(|count| count > 0)?;
+        if !has_is_group {
+            log::info!("Migration: adding is_group column to conversations table");
+            conn.execute(
+                "ALTER TABLE conversations ADD COLUMN is_group INTEGER NOT NULL DEFAULT 0",
+                [],
+            )?;
+        }
+
+        // 12. Saved-in-chat flag, filled from the metadata older imports already carry
+        let has_saved: bool = conn
+            .prepare("SELECT COUNT(*) FROM pragma_table_info('events') WHERE name = 'saved'")?
+            .query_row([], |row| row.get::<_, i32>(0))
+            .map(|count| count > 0)?;
+        if !has_saved {
+            log::info!("Migration: adding saved column to events table");
+            conn.execute_batch(
+                "ALTER TABLE events ADD COLUMN saved INTEGER NOT NULL DEFAULT 0;
+                 UPDATE events SET saved = 1 WHERE json_valid(metadata) AND json_extract(metadata, '$.saved') = 1;",
+            )?;
+        }
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_events_saved ON events(conversation_id, timestamp) WHERE saved = 1",
+            [],
+        )?;
+
+        // 13. How each run read slash dates
+        let has_timestamp_format: bool = conn
+            .prepare("SELECT COUNT(*) FROM pragma_table_info('ingestion_runs') WHERE name = 'timestamp_format'")?
+            .query_row([], |row| row.get::<_, i32>(0))
+            .map(|count| count > 0)?;
+        if !has_timestamp_format {
+            log::info!("Migration: adding timestamp_format column to ingestion_runs table");
+            conn.execute("ALTER TABLE ingestion_runs ADD COLUMN timestamp_format TEXT", [])?;
+        }
+
+        // 14. Where each zip export was extracted; older imports used a directory named by id
+        let has_extraction_path: bool = conn
+            .prepare("SELECT COUNT(*) FROM pragma_table_info('exports') WHERE name = 'extraction_path'")?
+            .query_row([], |row| row.get::<_, i32>(0))
+            .map(|count| count > 0)?;
+        if !has_extraction_path {
+            log::info!("Migration: adding extraction_path column to exports table");
+            conn.execute("ALTER TABLE exports ADD COLUMN extraction_path TEXT", [])?;
+        }
+
+        // 15. Where each export's artifacts were found, so later runs don't re-guess
+        let has_layout: bool = conn
+            .prepare("SELECT COUNT(*) FROM pragma_table_info('exports') WHERE name = 'layout'")?
+            .query_row([], |row| row.get::<_, i32>(0))
+            .map(|count| count > 0)?;
+        if !has_layout {
+            log::info!("Migration: adding layout column to exports table");
+            conn.execute("ALTER TABLE exports ADD COLUMN layout TEXT", [])?;
+        }
+
+        // 16. Per-conversation completeness summary
+        let has_completeness: bool = conn
+            .prepare("SELECT COUNT(*) FROM pragma_table_info('conversations') WHERE name = 'completeness'")?
+            .query_row([], |row| row.get::<_, i32>(0))
+            .map(|count| count > 0)?;
+        if !has_completeness {
+            log::info!("Migration: adding completeness column to conversations table");
+            conn.execute("ALTER TABLE conversations ADD COLUMN completeness TEXT", [])?;
+        }
+
+        // 17. Conversations remember the export that brought them in
+        let has_conversation_export: bool = conn
+            .prepare("SELECT COUNT(*) FROM pragma_table_info('conversations') WHERE name = 'export_id'")?
+            .query_row([], |row| row.get::<_, i32>(0))
+            .map(|count| count > 0)?;
+        if !has_conversation_export {
+            log::info!("Migration: adding export_id column to conversations table");
+            conn.execute("ALTER TABLE conversations ADD COLUMN export_id TEXT", [])?;
+            Self::assign_conversation_exports_with(&conn)?;
+        }
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_conversations_export_id ON conversations(export_id)",
+            [],
+        )?;
+
+        // 18. Stored message counts, so the conversation list doesn't count every event
+        let has_message_count: bool = conn
+            .prepare("SELECT COUNT(*) FROM pragma_table_info('conversations') WHERE name = 'message_count'")?
+            .query_row([], |row| row.get::<_, i32>(0))
+            .map(|count| count > 0)?;
+        if !has_message_count {
+            log::info!("Migration: adding message count columns to conversations table");
+            conn.execute_batch(
+                "ALTER TABLE conversations ADD COLUMN message_count INTEGER NOT NULL DEFAULT 0;
+                 ALTER TABLE conversations ADD COLUMN linked_media_count INTEGER NOT NULL DEFAULT 0;
+                 ALTER TABLE conversations ADD COLUMN saved_count INTEGER NOT NULL DEFAULT 0;
+                 ALTER TABLE conversations ADD COLUMN shared_location_count INTEGER NOT NULL DEFAULT 0;",
+            )?;
+        }
+        if !has_message_count || recount_conversations {
+            Self::refresh_conversation_counts_with(&conn, None)?;
+        }
+
+        // 19. Location history; the table comes from initialize_schema, its time index from here
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_locations_timestamp ON locations(timestamp)",
+            [],
+        )?;
+
+        // 20. Overlay links of memories
+        let has_overlay_url: bool = conn
+            .prepare("SELECT COUNT(*) FROM pragma_table_info('memories') WHERE name = 'overlay_url'")?
+            .query_row([], |row| row.get::<_, i32>(0))
+            .map(|count| count > 0)?;
+        if !has_overlay_url {
+            log::info!("Migration: adding overlay_url column to memories table");
+            conn.execute("ALTER TABLE memories ADD COLUMN overlay_url TEXT", [])?;
+        }
+
+        // 21. Events imported before ids were derived from their content keep their random ids;
+        // remember that so imports can say a repeat import won't line up with them
+        conn.execute(
+            "INSERT OR IGNORE INTO settings (key, value)
+             SELECT ?1, CASE WHEN EXISTS (SELECT 1 FROM events) THEN 'random' ELSE 'stable' END",
+            [EVENT_ID_SCHEME_KEY],
+        )?;
+        Self::settle_event_id_scheme(&conn)?;
+
+        // 22. Why a memory's download failed
+        let has_failure_reason: bool = conn
+            .prepare("SELECT COUNT(*) FROM pragma_table_info('memories') WHERE name = 'failure_reason'")?
+            .query_row([], |row| row.get::<_, i32>(0))
+            .map(|count| count > 0)?;
+        if !has_failure_reason {
+            log::info!("Migration: adding failure_reason column to memories table");
+            conn.execute("ALTER TABLE memories ADD COLUMN failure_reason TEXT", [])?;
+        }
+
+        // 23. Memories a download job found already on disk
+        let has_skipped: bool = conn
+            .prepare("SELECT COUNT(*) FROM pragma_table_info('download_jobs') WHERE name = 'skipped'")?
+            .query_row([], |row| row.get::<_, i32>(0))
+            .map(|count| count > 0)?;
+        if !has_skipped {
+            log::info!("Migration: adding skipped column to download_jobs table");
+            conn.execute(
+                "ALTER TABLE download_jobs ADD COLUMN skipped INTEGER NOT NULL DEFAULT 0",
+                [],
+            )?;
+        }
+
+        // 24. Which parts of a zip export could be read
+        let has_parts: bool = conn
+            .prepare("SELECT COUNT(*) FROM pragma_table_info('exports') WHERE name = 'parts'")?
+            .query_row([], |row| row.get::<_, i32>(0))
+            .map(|count| count > 0)?;
+        if !has_parts {
+            log::info!("Migration: adding parts column to exports table");
+            conn.execute("ALTER TABLE exports ADD COLUMN parts TEXT", [])?;
+        }
+
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        Ok(())
+    }
+
+    pub fn insert_people(&self, people: &[Person]) -> AppResult<()> {
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        {
+            // Upsert rather than replace so avatars found earlier survive a friends.json reload
+            let mut stmt = tx.prepare(
+                "INSERT INTO people (username, display_name) VALUES (?1, ?2)
+                 ON CONFLICT(username) DO UPDATE SET display_name = excluded.display_name",
+            )?;
+            for person in people {
+                stmt.execute(params![person.username, person.display_name])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Record avatar images by username, adding people that friends.json didn't list.
+    pub fn set_avatar_paths(&self, avatars: &HashMap<String, PathBuf>) -> AppResult<()> {
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO people (username, avatar_path) VALUES (?1, ?2)
+                 ON CONFLICT(username) DO UPDATE SET avatar_path = excluded.avatar_path",
+            )?;
+            for (username, path) in avatars {
+                stmt.execute(params![username, path.to_string_lossy()])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Everyone known to the database, with their avatar (if the file still exists) and
+    /// fallback color.
+    pub fn get_people(&self) -> AppResult<Vec<Person>> {
+        let mut span = perf::query("get_people");
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare("SELECT username, display_name, avatar_path FROM people ORDER BY username")?;
+        let people = stmt
+            .query_map([], |row| {
+                let username: String = row.get(0)?;
+                Ok(Person {
+                    avatar_path: existing_avatar(row.get(2)?),
+                    avatar_color: Some(avatar_color(&username)),
+                    display_name: row.get(1)?,
+                    username,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+        span.set_rows(people.trace_rows());
+        Ok(people)
+    }
+
+    /// Record which import phases have run for an export.
+    pub fn set_import_phases(&self, export_id: &str, phases: &ImportOptions) -> AppResult<()> {
+        self.write_conn()?.execute(
+            "UPDATE exports SET import_phases = ?2 WHERE id = ?1",
+            params![export_id, serde_json::to_string(phases)?],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_export_validation_status(&self, export_id: &str, status: &ValidationStatus) -> AppResult<()> {
+        self.write_conn()?.execute(
+            "UPDATE exports SET validation_status = ?2 WHERE id = ?1",
+            params![export_id, validation_status_str(status)],
+        )?;
+        Ok(())
+    }
+
+    pub fn insert_export(&self, export: &ExportSet) -> AppResult<()> {
+        let conn = self.write_conn()?;
+        write_export(&conn, export)
+    }
+
+    pub fn batch_insert_conversations(&self, conversations: &[Conversation]) -> AppResult<()> {
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(CONVERSATION_UPSERT)?;
+            for convo in conversations {
+                stmt.execute(params![
+                    convo.id,
+                    convo.display_name,
+                    serde_json::to_string(&convo.participants).unwrap_or_else(|_| "[]".to_string()),
+                    convo.last_event_at.map(|d| d.to_rfc3339()),
+                    convo.media_count,
+                    convo.media_bytes,
+                    convo.missing_media_count,
+                    convo.is_group
+                ])?;
+            }
+        }
+        // The counts come from the stored events, not the parsed batch
+        let ids: Vec<String> = conversations.iter().map(|c| c.id.clone()).collect();
+        Self::refresh_conversation_counts_with(&tx, Some(&ids))?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Recount the stored message, linked media, saved and shared location counts of
+    /// `conversation_ids`, or of every conversation. Call after changing events.
+    fn refresh_conversation_counts_with(
+        conn: &rusqlite::Connection,
+        conversation_ids: Option<&[String]>,
+    ) -> AppResult<usize> {
+        let ids = conversation_ids.map(serde_json::to_string).transpose()?;
+        Ok(conn.execute(
+            "UPDATE conversations SET (message_count, linked_media_count, saved_count, shared_location_count) = (
+                SELECT COUNT(*),
+                       COALESCE(SUM(media_references != '[]' AND media_references IS NOT NULL), 0),
+                       COALESCE(SUM(saved), 0),
+                       COALESCE(SUM(instr(metadata, '\"shared_location\"') > 0), 0)
+                FROM events WHERE conversation_id = conversations.id
+             ) WHERE ?1 IS NULL OR id IN (SELECT value FROM json_each(?1))",
+            [ids],
+        )?)
+    }
+
+    /// Give conversations without an export the one holding their earliest message.
+    pub fn assign_conversation_exports(&self) -> AppResult<usize> {
+        let conn = self.write_conn()?;
+        Self::assign_conversation_exports_with(&conn)
+    }
+
+    fn assign_conversation_exports_with(conn: &rusqlite::Connection) -> AppResult<usize> {
+        Ok(conn.execute(
+            "UPDATE conversations SET export_id = (
+                SELECT e.export_id FROM events e WHERE e.conversation_id = conversations.id
+                ORDER BY e.timestamp LIMIT 1
+             ) WHERE export_id IS NULL",
+            [],
+        )?)
+    }
 
     pub fn batch_insert_events(&self, events: &[Event], export_id: &str) -> AppResult<()> {
-        let mut conn = self.conn()?;
+        let mut conn = self.write_conn()?;
         let tx = conn.transaction()?;
+        let mut changes = CountChanges::default();
         {
+            let mut counts_stmt = tx.prepare(EVENT_COUNTS_SELECT)?;
             let mut event_stmt = tx.prepare(
-                "INSERT OR REPLACE INTO events (id, timestamp, sender, export_id, conversation_id, content, event_type, media_references, metadata)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"
-            )?;
-            // FTS5 doesn't support REPLACE — delete any existing entry first, then insert
-            let mut fts_delete_stmt = tx.prepare("DELETE FROM events_fts WHERE event_id = ?1")?;
-            let mut fts_stmt = tx.prepare(
-                "INSERT INTO events_fts (content, event_id, conversation_id, sender) VALUES (?1, ?2, ?3, ?4)",
+                "INSERT INTO events (id, timestamp, sender, export_id, conversation_id, content, event_type, media_references, metadata, saved)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                 ON CONFLICT(id) DO UPDATE SET
+                    timestamp = excluded.timestamp, sender = excluded.sender,
+                    conversation_id = excluded.conversation_id, content = excluded.content,
+                    event_type = excluded.event_type, media_references = excluded.media_references,
+                    metadata = excluded.metadata, saved = excluded.saved"
             )?;
             for event in events {
+                changes.track(&mut counts_stmt, &event.id, -1)?;
                 event_stmt.execute(params![
                     event.id,
                     event.timestamp.to_rfc3339(),
@@ -273,35 +1528,145 @@ impl DatabaseManager {
                         log::warn!("Failed to serialize media_references for event {}: {}", event.id, e);
                         "[]".to_string()
                     }),
-                    event.metadata
+                    event.metadata,
+                    event.is_saved()
                 ])?;
-                if let Some(ref content) = event.content {
-                    if !content.trim().is_empty() {
-                        let _ = fts_delete_stmt.execute(params![event.id]);
-                        fts_stmt.execute(params![content, event.id, event.conversation_id, event.sender])?;
-                    }
-                }
+                changes.track(&mut counts_stmt, &event.id, 1)?;
             }
         }
+        changes.apply(&tx)?;
         tx.commit()?;
         Ok(())
     }
 
+    /// Index an export's messages for search, `FTS_BATCH_SIZE` at a time in id order, one
+    /// transaction per batch. Stopping (via `is_cancelled` or a crash) keeps the batches done so
+    /// far, and the next call picks up after the last one; a call with nothing to resume first
+    /// clears the export's old index rows. Events must already be saved with
+    /// `batch_insert_events`.
+    pub fn populate_fts_for_export<C, P>(
+        &self,
+        export_id: &str,
+        is_cancelled: C,
+        mut on_batch: P,
+    ) -> AppResult<SearchIndexProgress>
+    where
+        C: Fn() -> bool,
+        P: FnMut(&SearchIndexProgress),
+    {
+        use rusqlite::OptionalExtension;
+        let mut conn = self.write_conn()?;
+        let total: i64 = conn.query_row("SELECT COUNT(*) FROM events WHERE export_id = ?1", [export_id], |r| {
+            r.get(0)
+        })?;
+        let resumed: Option<(String, i64)> = conn
+            .query_row(
+                "SELECT cursor, indexed FROM fts_population WHERE export_id = ?1",
+                [export_id],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .optional()?;
+        let mut progress = SearchIndexProgress {
+            export_id: export_id.to_string(),
+            total: total as usize,
+            ..Default::default()
+        };
+        let mut cursor = match resumed {
+            Some((cursor, indexed)) => {
+                log::info!("Resuming search index for {} after {} events", export_id, indexed);
+                progress.indexed = indexed as usize;
+                cursor
+            }
+            None => {
+                // FTS5 has no REPLACE, so rows from an earlier import of these events go first
+                let tx = conn.transaction()?;
+                tx.execute(
+                    "DELETE FROM events_fts WHERE event_id IN (SELECT id FROM events WHERE export_id = ?1)",
+                    [export_id],
+                )?;
+                tx.execute(
+                    "INSERT INTO fts_population (export_id, cursor, indexed) VALUES (?1, '', 0)",
+                    [export_id],
+                )?;
+                tx.commit()?;
+                String::new()
+            }
+        };
+
+        loop {
+            if is_cancelled() {
+                progress.cancelled = true;
+                break;
+            }
+            let tx = conn.transaction()?;
+            let batch = {
+                let mut select = tx.prepare(
+                    "SELECT id, content, conversation_id, sender FROM events
+                     WHERE export_id = ?1 AND id > ?2 ORDER BY id LIMIT ?3",
+                )?;
+                let rows = select
+                    .query_map(params![export_id, cursor, FTS_BATCH_SIZE as i64], |row| {
+                        Ok((
+                            row.get::<_, String>(0)?,
+                            row.get::<_, Option<String>>(1)?,
+                            row.get::<_, Option<String>>(2)?,
+                            row.get::<_, String>(3)?,
+                        ))
+                    })?
+                    .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+                rows
+            };
+            let Some(last) = batch.last().map(|(id, ..)| id.clone()) else {
+                tx.execute("DELETE FROM fts_population WHERE export_id = ?1", [export_id])?;
+                tx.commit()?;
+                progress.done = true;
+                break;
+            };
+            {
+                let mut insert = tx.prepare(
+                    "INSERT INTO events_fts (content, event_id, conversation_id, sender) VALUES (?1, ?2, ?3, ?4)",
+                )?;
+                for (id, content, conversation_id, sender) in &batch {
+                    if let Some(content) = content.as_deref().filter(|c| !c.trim().is_empty()) {
+                        insert.execute(params![content, id, conversation_id, sender])?;
+                    }
+                }
+            }
+            progress.indexed += batch.len();
+            tx.execute(
+                "UPDATE fts_population SET cursor = ?2, indexed = ?3 WHERE export_id = ?1",
+                params![export_id, last, progress.indexed as i64],
+            )?;
+            tx.commit()?;
+            cursor = last;
+            on_batch(&progress);
+        }
+        Ok(progress)
+    }
+
+    /// Insert memories, updating ones already stored under the same id. A downloaded memory
+    /// keeps its file and status unless the update names a file of its own; the links of a
+    /// fresh export replace any recorded failure reason. A memory stays with the export that
+    /// first brought it, so rolling back a later, overlapping import leaves it in place.
     pub fn batch_insert_memories(&self, memories: &[Memory]) -> AppResult<()> {
-        let mut conn = self.conn()?;
+        let mut conn = self.write_conn()?;
         let tx = conn.transaction()?;
         {
             let mut stmt = tx.prepare(
-                "INSERT OR REPLACE INTO memories (id, timestamp, media_type, latitude, longitude, media_path, download_url, proxy_url, download_status, export_id)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)"
+                "INSERT INTO memories (id, timestamp, media_type, latitude, longitude, media_path, download_url, proxy_url, download_status, export_id, overlay_url, failure_reason)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+                 ON CONFLICT(id) DO UPDATE SET
+                    timestamp = excluded.timestamp, media_type = excluded.media_type,
+                    latitude = excluded.latitude, longitude = excluded.longitude,
+                    download_url = excluded.download_url, proxy_url = excluded.proxy_url,
+                    overlay_url = excluded.overlay_url,
+                    failure_reason = excluded.failure_reason,
+                    media_path = COALESCE(excluded.media_path, media_path),
+                    download_status = CASE WHEN excluded.media_path IS NULL AND download_status = 'Downloaded'
+                                           THEN download_status ELSE excluded.download_status END"
             )?;
             for memory in memories {
-                let status_str = match memory.download_status {
-                    crate::models::DownloadStatus::Pending => "Pending",
-                    crate::models::DownloadStatus::Downloading => "Downloading",
-                    crate::models::DownloadStatus::Downloaded => "Downloaded",
-                    crate::models::DownloadStatus::Failed => "Failed",
-                };
+                let status_str = memory.download_status.as_str();
                 stmt.execute(params![
                     memory.id,
                     memory.timestamp.to_rfc3339(),
@@ -312,15 +1677,181 @@ impl DatabaseManager {
                     memory.download_url,
                     memory.proxy_url,
                     status_str,
-                    memory.export_id
+                    memory.export_id,
+                    memory.overlay_url,
+                    memory.failure_reason
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Mark the event id scheme stable once no event with a random id is left. Random ids are
+    /// version 4 UUIDs; content-derived ones are version 5.
+    fn settle_event_id_scheme(conn: &rusqlite::Connection) -> AppResult<()> {
+        use rusqlite::OptionalExtension;
+        let scheme: Option<String> = conn
+            .query_row(
+                "SELECT value FROM settings WHERE key = ?1",
+                [EVENT_ID_SCHEME_KEY],
+                |r| r.get(0),
+            )
+            .optional()?;
+        if scheme.as_deref() != Some("random") {
+            return Ok(());
+        }
+        let random_left: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM events WHERE length(id) = 36 AND substr(id, 15, 1) = '4')",
+            [],
+            |r| r.get(0),
+        )?;
+        if !random_left {
+            log::info!("No events with random ids are left; marking event ids stable");
+            conn.execute(
+                "UPDATE settings SET value = 'stable' WHERE key = ?1",
+                [EVENT_ID_SCHEME_KEY],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Whether the database holds events imported with random ids, which a repeat import of
+    /// the same export can't match.
+    pub fn has_random_event_ids(&self) -> AppResult<bool> {
+        Ok(self.get_setting(EVENT_ID_SCHEME_KEY)?.as_deref() == Some("random"))
+    }
+
+    /// Which of `ids` are already stored as memories.
+    pub fn existing_memory_ids(&self, ids: &[String]) -> AppResult<HashSet<String>> {
+        self.existing_ids("memories", ids)
+    }
+
+    /// Which of `ids` are already stored as events.
+    pub fn existing_event_ids(&self, ids: &[String]) -> AppResult<HashSet<String>> {
+        self.existing_ids("events", ids)
+    }
+
+    fn existing_ids(&self, table: &str, ids: &[String]) -> AppResult<HashSet<String>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id FROM {} WHERE id IN (SELECT value FROM json_each(?1))",
+            table
+        ))?;
+        let existing = stmt
+            .query_map([serde_json::to_string(ids)?], |r| r.get(0))?
+            .collect::<std::result::Result<HashSet<String>, rusqlite::Error>>()?;
+        Ok(existing)
+    }
+
+    /// Store location history points, skipping ones the export already has. Returns the rowids
+    /// of the points added.
+    pub fn insert_location_history(&self, points: &[LocationPoint]) -> AppResult<Vec<i64>> {
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        let mut added = Vec::new();
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR IGNORE INTO locations (export_id, timestamp, latitude, longitude, source)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for point in points {
+                let inserted = stmt.execute(params![
+                    point.export_id,
+                    point.timestamp.to_rfc3339(),
+                    point.latitude,
+                    point.longitude,
+                    point.source
                 ])?;
+                if inserted > 0 {
+                    added.push(tx.last_insert_rowid());
+                }
             }
         }
         tx.commit()?;
+        Ok(added)
+    }
+
+    /// Location history points between `after` and `before` (both inclusive), oldest first.
+    pub fn get_location_history(
+        &self,
+        after: Option<DateTime<Utc>>,
+        before: Option<DateTime<Utc>>,
+        export_id: Option<&str>,
+    ) -> AppResult<Vec<LocationPoint>> {
+        self.read_retrying("get_location_history", |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT timestamp, latitude, longitude, source, export_id FROM locations
+                 WHERE (?1 IS NULL OR timestamp >= ?1) AND (?2 IS NULL OR timestamp <= ?2)
+                   AND (?3 IS NULL OR export_id = ?3)
+                 ORDER BY timestamp ASC, id ASC",
+            )?;
+            let points = stmt
+                .query_map(
+                    params![after.map(|d| d.to_rfc3339()), before.map(|d| d.to_rfc3339()), export_id],
+                    |r| {
+                        Ok(LocationPoint {
+                            timestamp: parse_stored_timestamp(&r.get::<_, String>(0)?).0,
+                            latitude: r.get(1)?,
+                            longitude: r.get(2)?,
+                            source: r.get(3)?,
+                            export_id: r.get(4)?,
+                        })
+                    },
+                )?
+                .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+            Ok(points)
+        })
+    }
+
+    /// Record a download status change for one memory, where the file went once it is
+    /// downloaded, and why it failed (cleared by any other change). A targeted UPDATE of those
+    /// columns only, so it can't undo a concurrent relink, and a single short write, so a
+    /// download in progress doesn't hold the database.
+    pub fn update_memory_status(
+        &self,
+        id: &str,
+        status: DownloadStatus,
+        media_path: Option<&Path>,
+        failure_reason: Option<&str>,
+    ) -> AppResult<()> {
+        let updated = self.write_conn()?.execute(
+            "UPDATE memories SET download_status = ?2, media_path = COALESCE(?3, media_path), failure_reason = ?4
+             WHERE id = ?1",
+            params![
+                id,
+                status.as_str(),
+                media_path.map(|p| p.to_string_lossy().to_string()),
+                failure_reason
+            ],
+        )?;
+        if updated == 0 {
+            return Err(AppError::NotFound(format!("memory {}", id)));
+        }
         Ok(())
     }
 
+    /// Timestamps of the first and last message in a conversation.
+    pub fn get_conversation_time_range(
+        &self,
+        conversation_id: &str,
+    ) -> AppResult<Option<(DateTime<Utc>, DateTime<Utc>)>> {
+        let _span = perf::query("get_conversation_time_range");
+        let conn = self.conn()?;
+        let (first, last): (Option<String>, Option<String>) = conn.query_row(
+            "SELECT MIN(timestamp), MAX(timestamp) FROM events WHERE conversation_id = ?1",
+            [conversation_id],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )?;
+        let parse = |s: Option<String>| {
+            s.and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+                .map(|dt| dt.with_timezone(&Utc))
+        };
+        Ok(parse(first).zip(parse(last)))
+    }
+
     pub fn get_conversation_name(&self, conversation_id: &str) -> AppResult<Option<String>> {
+        let _span = perf::query("get_conversation_name");
         let conn = self.conn()?;
         let name: Option<String> = conn
             .query_row(
@@ -332,76 +1863,199 @@ impl DatabaseManager {
         Ok(name)
     }
 
-    pub fn get_conversations(&self) -> AppResult<Vec<Conversation>> {
-        let conn = self.conn()?;
-        let mut stmt = conn.prepare(
-            "SELECT c.id, c.display_name, c.participants, c.last_event_at,
-             COALESCE(ec.msg_count, 0) as msg_count,
-             p.display_name as resolved_name,
-             COALESCE(ec.media_count, 0) as media_count
-             FROM conversations c
-             LEFT JOIN people p ON c.id = p.username
-             LEFT JOIN (
-               SELECT conversation_id,
-                      COUNT(*) as msg_count,
-                      SUM(CASE WHEN media_references != '[]' AND media_references IS NOT NULL THEN 1 ELSE 0 END) as media_count
-               FROM events
-               GROUP BY conversation_id
-             ) ec ON ec.conversation_id = c.id
-             ORDER BY c.last_event_at DESC"
-        )?;
-
-        let conversation_iter = stmt.query_map([], |row| {
-            let participants_json: String = row.get(2)?;
-            let participants: Vec<String> = serde_json::from_str(&participants_json).unwrap_or_default();
-            let last_event_at_str: Option<String> = row.get(3)?;
-            let last_event_at = last_event_at_str.and_then(|s| {
-                chrono::DateTime::parse_from_rfc3339(&s)
-                    .ok()
-                    .map(|dt| dt.with_timezone(&chrono::Utc))
-            });
+    /// Every conversation, or those with messages in `export_id` (counted over that export only).
+    pub fn get_conversations(&self, export_id: Option<&str>) -> AppResult<Vec<Conversation>> {
+        self.read_retrying("get_conversations", |conn| {
+            Self::conversations_with(conn, export_id, ConversationSort::LastActivity, None, 0, -1)
+        })
+    }
 
-            let resolved_name: Option<String> = row.get(5).ok();
-            let display_name = resolved_name.or_else(|| row.get::<_, Option<String>>(1).ok().flatten());
-            let media_count: i32 = row.get(6)?;
+    /// A page of the conversations `get_conversations` lists, in `sort` order. `name_filter`
+    /// keeps those whose stored or resolved name, or id, contains it.
+    pub fn get_conversations_page(
+        &self,
+        offset: i32,
+        limit: i32,
+        sort: ConversationSort,
+        name_filter: Option<&str>,
+        export_id: Option<&str>,
+    ) -> AppResult<ConversationPage> {
+        let offset = offset.max(0);
+        let limit = limit.clamp(1, 500);
+        let pattern = name_filter
+            .map(str::trim)
+            .filter(|f| !f.is_empty())
+            .map(|f| format!("%{}%", Self::escape_like(f)));
 
-            Ok(Conversation {
-                id: row.get(0)?,
-                display_name,
-                participants,
-                last_event_at,
-                message_count: row.get(4)?,
-                has_media: media_count > 0,
+        self.read_retrying("get_conversations_page", |conn| {
+            let total_count: i32 = conn.query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM conversations c LEFT JOIN people p ON c.id = p.username
+                     WHERE {} AND {}",
+                    CONVERSATION_IN_EXPORT, CONVERSATION_NAME_FILTER
+                ),
+                params![export_id, None::<&str>, pattern],
+                |r| r.get(0),
+            )?;
+            let items = Self::conversations_with(conn, export_id, sort, pattern.as_deref(), offset, limit)?;
+            Ok(ConversationPage {
+                has_more: offset + (items.len() as i32) < total_count,
+                items,
+                total_count,
             })
-        })?;
-
-        let mut conversations = Vec::new();
-        for conversation in conversation_iter {
-            conversations.push(conversation?);
-        }
+        })
+    }
 
+    /// Conversations in `sort` order; `pattern` is a `LIKE` pattern for the name and a
+    /// negative `limit` means all of them.
+    fn conversations_with(
+        conn: &rusqlite::Connection,
+        export_id: Option<&str>,
+        sort: ConversationSort,
+        pattern: Option<&str>,
+        offset: i32,
+        limit: i32,
+    ) -> AppResult<Vec<Conversation>> {
+        let order = match sort {
+            ConversationSort::LastActivity => "c.last_event_at DESC",
+            ConversationSort::MessageCount => "msg_count DESC, c.last_event_at DESC",
+            ConversationSort::Name => "COALESCE(p.display_name, c.display_name, c.id) COLLATE NOCASE ASC",
+        };
+        let mut stmt = conn.prepare(&format!(
+            "{} AND {} ORDER BY {}, c.id LIMIT ?4 OFFSET ?5",
+            CONVERSATION_SELECT, CONVERSATION_NAME_FILTER, order
+        ))?;
+        let conversations = stmt
+            .query_map(
+                params![None::<&str>, export_id, pattern, limit, offset],
+                Self::map_conversation_row,
+            )?
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
         Ok(conversations)
     }
 
-    pub fn get_export_stats(&self) -> AppResult<ExportStats> {
+    pub fn get_conversation(&self, conversation_id: &str) -> AppResult<Conversation> {
+        use rusqlite::OptionalExtension;
+        self.read_retrying("get_conversation", |conn| {
+            conn.query_row(
+                CONVERSATION_SELECT,
+                params![conversation_id, None::<&str>],
+                Self::map_conversation_row,
+            )
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("conversation {}", conversation_id)))
+        })
+    }
+
+    /// Map a row of (id, display_name, participants, last_event_at, msg_count, resolved_name,
+    /// linked_media_count, media_count, media_bytes, missing_media_count, avatar_path,
+    /// comma-separated anomaly kinds, is_group, saved_count, shared_location_count,
+    /// completeness JSON).
+    fn map_conversation_row(row: &rusqlite::Row) -> rusqlite::Result<Conversation> {
+        let participants_json: String = row.get(2)?;
+        let participants: Vec<String> = serde_json::from_str(&participants_json).unwrap_or_default();
+        let last_event_at_str: Option<String> = row.get(3)?;
+        let last_event_at = last_event_at_str.and_then(|s| {
+            chrono::DateTime::parse_from_rfc3339(&s)
+                .ok()
+                .map(|dt| dt.with_timezone(&chrono::Utc))
+        });
+
+        let resolved_name: Option<String> = row.get(5).ok();
+        let display_name = resolved_name.or_else(|| row.get::<_, Option<String>>(1).ok().flatten());
+        let linked_media_count: i32 = row.get(6)?;
+        let id: String = row.get(0)?;
+        let anomaly_kinds: Option<String> = row.get(11)?;
+
+        Ok(Conversation {
+            anomaly_flags: anomaly_kinds
+                .as_deref()
+                .unwrap_or_default()
+                .split(',')
+                .filter_map(AnomalyKind::parse)
+                .collect(),
+            avatar_path: existing_avatar(row.get(10)?),
+            avatar_color: Some(avatar_color(&id)),
+            id,
+            display_name,
+            participants,
+            is_group: row.get(12)?,
+            listed_members: 0,
+            saved_count: row.get(13)?,
+            shared_location_count: row.get(14)?,
+            completeness: row
+                .get::<_, Option<String>>(15)?
+                .and_then(|json| serde_json::from_str(&json).ok()),
+            last_event_at,
+            message_count: row.get(4)?,
+            has_media: linked_media_count > 0,
+            media_count: row.get(7)?,
+            media_bytes: row.get(8)?,
+            missing_media_count: row.get(9)?,
+        })
+    }
+
+    /// Map a row of (id, timestamp, sender, conversation_id, content, event_type, media_references,
+    /// metadata, sender display name).
+    fn map_event_row(row: &rusqlite::Row) -> rusqlite::Result<Event> {
+        let timestamp_str: String = row.get(1)?;
+        let (timestamp, raw_timestamp) = parse_stored_timestamp(&timestamp_str);
+        let metadata: Option<String> = row.get(7)?;
+
+        let media_refs_json: String = row.get(6)?;
+        let media_references: Vec<std::path::PathBuf> = serde_json::from_str(&media_refs_json).unwrap_or_default();
+
+        Ok(Event {
+            id: row.get(0)?,
+            timestamp,
+            sender: row.get(2)?,
+            sender_name: row.get(8).ok(),
+            conversation_id: row.get(3)?,
+            content: row.get(4)?,
+            event_type: row.get(5)?,
+            media_references,
+            metadata: match raw_timestamp {
+                Some(raw) => keep_raw_timestamp(metadata, raw),
+                None => metadata,
+            },
+        })
+    }
+
+    /// Totals over everything imported, or over one export's events and memories.
+    pub fn get_export_stats(&self, export_id: Option<&str>) -> AppResult<ExportStats> {
+        let _span = perf::query("get_export_stats");
         let conn = self.conn()?;
-        let total_messages: i32 = conn.query_row("SELECT COUNT(*) FROM events", [], |r| r.get(0))?;
-        let total_conversations: i32 = conn.query_row("SELECT COUNT(*) FROM conversations", [], |r| r.get(0))?;
+        let total_messages: i32 = conn.query_row(
+            "SELECT COUNT(*) FROM events WHERE (?1 IS NULL OR export_id = ?1)",
+            [export_id],
+            |r| r.get(0),
+        )?;
+        let total_conversations: i32 = conn.query_row(
+            &format!("SELECT COUNT(*) FROM conversations c WHERE {}", CONVERSATION_IN_EXPORT),
+            [export_id],
+            |r| r.get(0),
+        )?;
         let total_memories: i32 = conn
-            .query_row("SELECT COUNT(*) FROM memories", [], |r| r.get(0))
+            .query_row(
+                "SELECT COUNT(*) FROM memories WHERE (?1 IS NULL OR export_id = ?1)",
+                [export_id],
+                |r| r.get(0),
+            )
             .unwrap_or(0);
 
         let total_media_files: i32 = conn
             .query_row(
-                "SELECT COUNT(*) FROM events WHERE media_references != '[]' AND media_references IS NOT NULL",
-                [],
+                "SELECT COUNT(*) FROM events WHERE media_references != '[]' AND media_references IS NOT NULL
+                 AND (?1 IS NULL OR export_id = ?1)",
+                [export_id],
                 |r| r.get(0),
             )
             .unwrap_or(0);
 
         let missing_media_count: i32 = conn.query_row(
-            "SELECT COUNT(*) FROM events WHERE event_type IN ('MEDIA', 'SNAP', 'SNAP_VIDEO', 'NOTE', 'STICKER') AND (media_references = '[]' OR media_references IS NULL)",
-            [],
+            "SELECT COUNT(*) FROM events WHERE event_type IN ('MEDIA', 'SNAP', 'SNAP_VIDEO', 'NOTE', 'STICKER') AND (media_references = '[]' OR media_references IS NULL)
+             AND (?1 IS NULL OR export_id = ?1)",
+            [export_id],
             |r| r.get(0),
         ).unwrap_or(0);
 
@@ -409,21 +2063,23 @@ impl DatabaseManager {
             "SELECT COALESCE(p.display_name, e.sender), COUNT(*) as cnt
              FROM events e
              LEFT JOIN people p ON e.sender = p.username
+             WHERE (?1 IS NULL OR e.export_id = ?1)
              GROUP BY e.sender
              ORDER BY cnt DESC
              LIMIT 5",
         )?;
 
         let top_contacts = stmt
-            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .query_map([export_id], |row| Ok((row.get(0)?, row.get(1)?)))?
             .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
 
-        let start_date_str: Option<String> = conn
-            .query_row("SELECT MIN(timestamp) FROM events", [], |r| r.get(0))
-            .ok();
-        let end_date_str: Option<String> = conn
-            .query_row("SELECT MAX(timestamp) FROM events", [], |r| r.get(0))
-            .ok();
+        let (start_date_str, end_date_str): (Option<String>, Option<String>) = conn
+            .query_row(
+                "SELECT MIN(timestamp), MAX(timestamp) FROM events WHERE (?1 IS NULL OR export_id = ?1)",
+                [export_id],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .unwrap_or((None, None));
 
         let start_date =
             start_date_str.and_then(|s| DateTime::parse_from_rfc3339(&s).ok().map(|dt| dt.with_timezone(&Utc)));
@@ -443,15 +2099,17 @@ impl DatabaseManager {
     }
 
     pub fn get_exports(&self) -> AppResult<Vec<ExportSet>> {
+        let mut span = perf::query("get_exports");
         let conn = self.conn()?;
         let mut stmt =
-            conn.prepare("SELECT id, source_paths, source_type, creation_date, validation_status FROM exports")?;
+            conn.prepare("SELECT id, source_paths, source_type, creation_date, validation_status, import_phases, extraction_path, layout, parts FROM exports")?;
 
         let export_iter = stmt.query_map([], |row| {
             let source_paths_json: String = row.get(1)?;
             let source_type_str: String = row.get::<_, String>(2).unwrap_or_else(|_| "Folder".to_string());
             let creation_date_str: Option<String> = row.get(3)?;
             let validation_status_str: String = row.get(4)?;
+            let import_phases_json: Option<String> = row.get(5)?;
 
             let source_type = match source_type_str.as_str() {
                 "Zip" => ExportSourceType::Zip,
@@ -471,10 +2129,27 @@ impl DatabaseManager {
                 id: row.get(0)?,
                 source_paths,
                 source_type,
-                extraction_path: None,
+                extraction_path: row.get::<_, Option<String>>(6)?.map(PathBuf::from),
                 creation_date: creation_date_str
                     .and_then(|s| DateTime::parse_from_rfc3339(&s).ok().map(|dt| dt.with_timezone(&Utc))),
                 validation_status,
+                // Kept so a damaged multi-part export still names its bad parts after a restart
+                parts: row
+                    .get::<_, Option<String>>(8)?
+                    .and_then(|json| serde_json::from_str(&json).ok())
+                    .unwrap_or_default(),
+                // Older imports always ran every phase
+                import_phases: Some(
+                    import_phases_json
+                        .as_deref()
+                        .and_then(parse_import_phases)
+                        .unwrap_or(ImportOptions::ALL),
+                ),
+                ignored_duplicates: Vec::new(),
+                // A layout this build can't read is re-detected on the next run
+                layout: row
+                    .get::<_, Option<String>>(7)?
+                    .and_then(|json| serde_json::from_str(&json).ok()),
             })
         })?;
 
@@ -483,142 +2158,210 @@ impl DatabaseManager {
             exports.push(export?);
         }
 
+        span.set_rows(exports.trace_rows());
         Ok(exports)
     }
 
-    pub fn foreach_message<F>(&self, conversation_id: &str, mut f: F) -> AppResult<()>
+    pub fn foreach_message<F>(&self, conversation_id: &str, f: F) -> AppResult<()>
+    where
+        F: FnMut(Event) -> AppResult<()>,
+    {
+        self.foreach_message_filtered(conversation_id, None, None, &[], f)
+    }
+
+    /// `foreach_message` over only the messages `get_messages_filtered` returns.
+    pub fn foreach_message_filtered<F>(
+        &self,
+        conversation_id: &str,
+        after: Option<DateTime<Utc>>,
+        before: Option<DateTime<Utc>>,
+        senders: &[String],
+        mut f: F,
+    ) -> AppResult<()>
     where
         F: FnMut(Event) -> AppResult<()>,
     {
         let conn = self.conn()?;
-        let mut stmt = conn.prepare(
-            "SELECT e.id, e.timestamp, e.sender, e.conversation_id, e.content, e.event_type, e.media_references, e.metadata, p.display_name
+        let mut stmt = conn.prepare(&format!(
+            "SELECT e.id, e.timestamp, e.sender, e.conversation_id, e.content, e.event_type, e.media_references, e.metadata, {}
              FROM events e
              LEFT JOIN people p ON e.sender = p.username
-             WHERE e.conversation_id = ?1
-             ORDER BY e.timestamp ASC"
-        )?;
-
-        let event_iter = stmt.query_map([conversation_id], |row| {
-            let timestamp_str: String = row.get(1)?;
-            let timestamp = chrono::DateTime::parse_from_rfc3339(&timestamp_str)
-                .map(|dt| dt.with_timezone(&chrono::Utc))
-                .unwrap_or_else(|e| {
-                    log::warn!("Bad timestamp in DB: '{}': {}", timestamp_str, e);
-                    chrono::DateTime::<chrono::Utc>::MIN_UTC
-                });
-
-            let media_refs_json: String = row.get(6)?;
-            let media_references: Vec<std::path::PathBuf> = serde_json::from_str(&media_refs_json).unwrap_or_default();
+             WHERE {}
+             ORDER BY e.timestamp ASC",
+            SENDER_NAME, MESSAGE_FILTER
+        ))?;
 
-            Ok(Event {
-                id: row.get(0)?,
-                timestamp,
-                sender: row.get(2)?,
-                sender_name: row.get(8).ok(),
-                conversation_id: row.get(3)?,
-                content: row.get(4)?,
-                event_type: row.get(5)?,
-                media_references,
-                metadata: row.get(7)?,
-            })
-        })?;
+        let event_iter = stmt.query_map(
+            params![
+                conversation_id,
+                after.map(|d| d.to_rfc3339()),
+                before.map(|d| d.to_rfc3339()),
+                serde_json::to_string(senders)?
+            ],
+            Self::map_event_row,
+        )?;
 
         for event in event_iter {
-            let event = event.map_err(|e| crate::error::AppError::Generic(format!("Database error during stream: {}", e)))?;
+            let event =
+                event.map_err(|e| crate::error::AppError::Generic(format!("Database error during stream: {}", e)))?;
             f(event)?;
         }
 
         Ok(())
     }
 
+    /// A conversation's messages from `after` to `before` (both inclusive), oldest first, sent
+    /// by one of `senders`. Unset bounds and empty `senders` match everything.
+    pub fn get_messages_filtered(
+        &self,
+        conversation_id: &str,
+        after: Option<DateTime<Utc>>,
+        before: Option<DateTime<Utc>>,
+        senders: &[String],
+    ) -> AppResult<Vec<Event>> {
+        let mut span = perf::query("get_messages_filtered");
+        let mut messages = Vec::new();
+        self.foreach_message_filtered(conversation_id, after, before, senders, |msg| {
+            messages.push(msg);
+            Ok(())
+        })?;
+        span.set_rows(messages.trace_rows());
+        Ok(messages)
+    }
+
+    /// How many messages `get_messages_filtered` would return.
+    pub fn count_messages_filtered(
+        &self,
+        conversation_id: &str,
+        after: Option<DateTime<Utc>>,
+        before: Option<DateTime<Utc>>,
+        senders: &[String],
+    ) -> AppResult<i32> {
+        let _span = perf::query("count_messages_filtered");
+        let count = self.conn()?.query_row(
+            &format!("SELECT COUNT(*) FROM events e WHERE {}", MESSAGE_FILTER),
+            params![
+                conversation_id,
+                after.map(|d| d.to_rfc3339()),
+                before.map(|d| d.to_rfc3339()),
+                serde_json::to_string(senders)?
+            ],
+            |r| r.get(0),
+        )?;
+        Ok(count)
+    }
+
     pub fn get_messages(&self, conversation_id: &str) -> AppResult<Vec<Event>> {
+        let mut span = perf::query("get_messages");
         let conn = self.conn()?;
-        let mut stmt = conn.prepare(
-            "SELECT e.id, e.timestamp, e.sender, e.conversation_id, e.content, e.event_type, e.media_references, e.metadata, p.display_name
+        let mut stmt = conn.prepare(&format!(
+            "SELECT e.id, e.timestamp, e.sender, e.conversation_id, e.content, e.event_type, e.media_references, e.metadata, {}
              FROM events e
              LEFT JOIN people p ON e.sender = p.username
              WHERE e.conversation_id = ?1
-             ORDER BY e.timestamp ASC"
-        )?;
-
-        let event_iter = stmt.query_map([conversation_id], |row| {
-            let timestamp_str: String = row.get(1)?;
-            let timestamp = chrono::DateTime::parse_from_rfc3339(&timestamp_str)
-                .map(|dt| dt.with_timezone(&chrono::Utc))
-                .unwrap_or_else(|e| {
-                    log::warn!("Bad timestamp in DB: '{}': {}", timestamp_str, e);
-                    chrono::DateTime::<chrono::Utc>::MIN_UTC
-                });
+             ORDER BY e.timestamp ASC",
+            SENDER_NAME
+        ))?;
 
-            let media_refs_json: String = row.get(6)?;
-            let media_references: Vec<std::path::PathBuf> = serde_json::from_str(&media_refs_json).unwrap_or_default();
-
-            Ok(Event {
-                id: row.get(0)?,
-                timestamp,
-                sender: row.get(2)?,
-                sender_name: row.get(8).ok(),
-                conversation_id: row.get(3)?,
-                content: row.get(4)?,
-                event_type: row.get(5)?,
-                media_references,
-                metadata: row.get(7)?,
-            })
-        })?;
+        let event_iter = stmt.query_map([conversation_id], Self::map_event_row)?;
 
         let mut events = Vec::new();
         for event in event_iter {
             events.push(event?);
         }
 
+        span.set_rows(events.trace_rows());
         Ok(events)
     }
 
-    pub fn get_messages_page(&self, conversation_id: &str, offset: i32, limit: i32) -> AppResult<MessagePage> {
+    /// A page of a conversation's messages, oldest first. With `saved_only`, only messages
+    /// saved in chat.
+    pub fn get_messages_page(
+        &self,
+        conversation_id: &str,
+        offset: i32,
+        limit: i32,
+        saved_only: bool,
+        event_types: Option<&[String]>,
+    ) -> AppResult<MessagePage> {
         let offset = offset.max(0);
         let limit = limit.clamp(1, 2000);
 
-        let conn = self.conn()?;
+        self.read_retrying("get_messages_page", |conn| {
+            Self::messages_page_with(conn, conversation_id, offset, limit, saved_only, event_types)
+        })
+    }
+
+    /// The messages around `event_id` in its conversation: up to `before` earlier ones, the
+    /// message itself, and up to `after` later ones, with the message's position.
+    pub fn get_messages_around(&self, event_id: &str, before: i32, after: i32) -> AppResult<MessageWindow> {
+        use rusqlite::OptionalExtension;
+        let before = before.clamp(0, 1000);
+        let after = after.clamp(0, 1000);
+
+        self.read_retrying("get_messages_around", |conn| {
+            let (conversation_id, anchor_index): (Option<String>, i32) = conn
+                .query_row(
+                    "SELECT a.conversation_id,
+                     (SELECT COUNT(*) FROM events e WHERE e.conversation_id = a.conversation_id
+                        AND (e.timestamp, e.rowid) < (a.timestamp, a.rowid))
+                     FROM events a WHERE a.id = ?1",
+                    [event_id],
+                    |r| Ok((r.get(0)?, r.get(1)?)),
+                )
+                .optional()?
+                .ok_or_else(|| AppError::NotFound(format!("message {}", event_id)))?;
+            let conversation_id = conversation_id
+                .ok_or_else(|| AppError::Validation(format!("message {} is not in a conversation", event_id)))?;
+
+            let offset = (anchor_index - before).max(0);
+            let limit = anchor_index - offset + 1 + after;
+            let page = Self::messages_page_with(conn, &conversation_id, offset, limit, false, None)?;
+            Ok(MessageWindow {
+                page,
+                offset,
+                anchor_index,
+            })
+        })
+    }
+
+    /// A page of a conversation's messages. `event_types`, when given and not empty, limits
+    /// the page and its counts to those types.
+    fn messages_page_with(
+        conn: &rusqlite::Connection,
+        conversation_id: &str,
+        offset: i32,
+        limit: i32,
+        saved_only: bool,
+        event_types: Option<&[String]>,
+    ) -> AppResult<MessagePage> {
+        let event_types = event_types
+            .filter(|types| !types.is_empty())
+            .map(serde_json::to_string)
+            .transpose()?;
+        // The conversation index narrows to the chat; the type list is a small set checked per row
         let total_count: i32 = conn.query_row(
-            "SELECT COUNT(*) FROM events WHERE conversation_id = ?1",
-            [conversation_id],
+            "SELECT COUNT(*) FROM events WHERE conversation_id = ?1 AND (?2 = 0 OR saved = 1)
+             AND (?3 IS NULL OR event_type IN (SELECT value FROM json_each(?3)))",
+            params![conversation_id, saved_only, event_types],
             |r| r.get(0),
         )?;
 
-        let mut stmt = conn.prepare(
-            "SELECT e.id, e.timestamp, e.sender, e.conversation_id, e.content, e.event_type, e.media_references, e.metadata, p.display_name
+        let mut stmt = conn.prepare(&format!(
+            "SELECT e.id, e.timestamp, e.sender, e.conversation_id, e.content, e.event_type, e.media_references, e.metadata, {}
              FROM events e
              LEFT JOIN people p ON e.sender = p.username
-             WHERE e.conversation_id = ?1
-             ORDER BY e.timestamp ASC
-             LIMIT ?2 OFFSET ?3"
-        )?;
-
-        let event_iter = stmt.query_map(params![conversation_id, limit, offset], |row| {
-            let timestamp_str: String = row.get(1)?;
-            let timestamp = chrono::DateTime::parse_from_rfc3339(&timestamp_str)
-                .map(|dt| dt.with_timezone(&chrono::Utc))
-                .unwrap_or_else(|e| {
-                    log::warn!("Bad timestamp in DB: '{}': {}", timestamp_str, e);
-                    chrono::DateTime::<chrono::Utc>::MIN_UTC
-                });
-
-            let media_refs_json: String = row.get(6)?;
-            let media_references: Vec<std::path::PathBuf> = serde_json::from_str(&media_refs_json).unwrap_or_default();
+             WHERE e.conversation_id = ?1 AND (?4 = 0 OR e.saved = 1)
+               AND (?5 IS NULL OR e.event_type IN (SELECT value FROM json_each(?5)))
+             ORDER BY e.timestamp ASC, e.rowid ASC
+             LIMIT ?2 OFFSET ?3",
+            SENDER_NAME
+        ))?;
 
-            Ok(Event {
-                id: row.get(0)?,
-                timestamp,
-                sender: row.get(2)?,
-                sender_name: row.get(8).ok(),
-                conversation_id: row.get(3)?,
-                content: row.get(4)?,
-                event_type: row.get(5)?,
-                media_references,
-                metadata: row.get(7)?,
-            })
-        })?;
+        let event_iter = stmt.query_map(
+            params![conversation_id, limit, offset, saved_only, event_types],
+            Self::map_event_row,
+        )?;
 
         let mut messages = Vec::new();
         for event in event_iter {
@@ -636,72 +2379,357 @@ impl DatabaseManager {
 
     /// Sanitize a user query for FTS5 MATCH. Wraps each word in double quotes
     /// to prevent FTS5 syntax injection (*, OR, AND, NEAR, etc.).
-    fn sanitize_fts_query(query: &str) -> String {
-        let words: Vec<String> = query
-            .split_whitespace()
-            .filter(|w| !w.is_empty())
-            .map(|w| {
+    ///
+    /// With `prefix`, the last word also matches longer words ("birt" finds "birthday"),
+    /// as long as it is plain alphanumeric and at least 2 characters long.
+    fn sanitize_fts_query(query: &str, prefix: bool) -> String {
+        let tokens: Vec<&str> = query.split_whitespace().filter(|w| !w.is_empty()).collect();
+        let words: Vec<String> = tokens
+            .iter()
+            .enumerate()
+            .map(|(i, w)| {
                 // Escape any double quotes within the word, then wrap in quotes
                 let escaped = w.replace('"', "\"\"");
-                format!("\"{}\"", escaped)
+                let is_prefix =
+                    prefix && i == tokens.len() - 1 && w.chars().count() >= 2 && w.chars().all(char::is_alphanumeric);
+                format!("\"{}\"{}", escaped, if is_prefix { "*" } else { "" })
             })
             .collect();
         words.join(" ")
     }
 
-    pub fn search_messages(&self, query: &str, limit: i32) -> AppResult<Vec<SearchResult>> {
-        let sanitized = Self::sanitize_fts_query(query);
+    /// Full-text search over every export's messages, or only `export_id`'s.
+    pub fn search_messages(
+        &self,
+        query: &str,
+        limit: i32,
+        prefix: bool,
+        export_id: Option<&str>,
+    ) -> AppResult<Vec<SearchResult>> {
+        self.read_retrying("search_messages", |conn| {
+            Self::search_messages_with(conn, query, limit, prefix, export_id)
+        })
+    }
+
+    fn search_messages_with(
+        conn: &rusqlite::Connection,
+        query: &str,
+        limit: i32,
+        prefix: bool,
+        export_id: Option<&str>,
+    ) -> AppResult<Vec<SearchResult>> {
+        let sanitized = Self::sanitize_fts_query(query, prefix);
         if sanitized.is_empty() {
             return Ok(Vec::new());
         }
 
         let limit = limit.clamp(1, 500);
 
-        let conn = self.conn()?;
         let mut stmt = conn.prepare(
             "SELECT f.event_id, f.conversation_id, f.sender, f.content, e.timestamp, e.event_type,
-                    c.display_name as convo_name, p.display_name as sender_name
+                    c.display_name as convo_name, p.display_name as sender_name,
+                    snippet(events_fts, 0, ?4, ?5, '…', ?6), highlight(events_fts, 0, ?4, ?5)
              FROM events_fts f
              JOIN events e ON e.id = f.event_id
              LEFT JOIN conversations c ON f.conversation_id = c.id
              LEFT JOIN people p ON f.sender = p.username
-             WHERE events_fts MATCH ?1
+             WHERE events_fts MATCH ?1 AND (?3 IS NULL OR e.export_id = ?3)
              ORDER BY rank
              LIMIT ?2",
         )?;
 
         let results = stmt
-            .query_map(params![sanitized, limit], |row| {
-                let timestamp_str: String = row.get(4)?;
-                let timestamp = chrono::DateTime::parse_from_rfc3339(&timestamp_str)
-                    .map(|dt| dt.with_timezone(&chrono::Utc))
-                    .unwrap_or_else(|e| {
-                        log::warn!("Bad timestamp in DB: '{}': {}", timestamp_str, e);
-                        chrono::DateTime::<chrono::Utc>::MIN_UTC
-                    });
-
-                Ok(SearchResult {
-                    event_id: row.get(0)?,
-                    conversation_id: row.get(1)?,
-                    conversation_name: row.get(6)?,
-                    sender: row.get(2)?,
-                    sender_name: row.get(7)?,
-                    content: row.get(3)?,
-                    timestamp,
-                    event_type: row.get(5)?,
+            .query_map(
+                params![
+                    sanitized,
+                    limit,
+                    export_id,
+                    SNIPPET_MATCH_START,
+                    SNIPPET_MATCH_END,
+                    SNIPPET_TOKENS
+                ],
+                |row| {
+                    let timestamp_str: String = row.get(4)?;
+                    let (timestamp, _) = parse_stored_timestamp(&timestamp_str);
+
+                    Ok(SearchResult {
+                        event_id: row.get(0)?,
+                        conversation_id: row.get(1)?,
+                        conversation_name: row.get(6)?,
+                        sender: row.get(2)?,
+                        sender_name: row.get(7)?,
+                        content: row.get(3)?,
+                        timestamp,
+                        event_type: row.get(5)?,
+                        snippet: row.get(8)?,
+                        match_ranges: highlighted_ranges(&row.get::<_, String>(9)?),
+                    })
+                },
+            )?
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+
+        Ok(results)
+    }
+
+    /// Memories and messages from one calendar day (UTC) in every year, newest year first. Each
+    /// year keeps at most `per_year` messages, preferring saved ones and media. With
+    /// `leap_fallback`, asking for Feb 29 also returns Feb 28 of years without a Feb 29.
+    pub fn get_on_this_day(
+        &self,
+        month: u32,
+        day: u32,
+        per_year: i32,
+        leap_fallback: bool,
+    ) -> AppResult<Vec<OnThisDayYear>> {
+        if chrono::NaiveDate::from_ymd_opt(2000, month, day).is_none() {
+            return Err(AppError::Validation(format!(
+                "Not a calendar day: {:02}-{:02}",
+                month, day
+            )));
+        }
+        let month_day = format!("{:02}-{:02}", month, day);
+        let leap_fallback = leap_fallback && (month, day) == (2, 29);
+        let per_year = per_year.clamp(1, 500);
+        // Fixed offsets of the RFC3339 timestamps, as in search_messages_on_day
+        let on_day = "(substr(timestamp, 6, 5) = ?1 OR (?2 AND substr(timestamp, 6, 5) = '02-28'
+            AND NOT (CAST(substr(timestamp, 1, 4) AS INTEGER) % 4 = 0
+                AND (CAST(substr(timestamp, 1, 4) AS INTEGER) % 100 != 0
+                     OR CAST(substr(timestamp, 1, 4) AS INTEGER) % 400 = 0))))";
+
+        self.read_retrying("get_on_this_day", |conn| {
+            use chrono::Datelike;
+            let mut years: BTreeMap<i32, OnThisDayYear> = BTreeMap::new();
+            let empty = |year| OnThisDayYear {
+                year,
+                memories: Vec::new(),
+                events: Vec::new(),
+                event_total: 0,
+            };
+
+            let mut memories = conn.prepare(&format!(
+                "SELECT id, timestamp, media_type, latitude, longitude, media_path, download_url, proxy_url, download_status, export_id, overlay_url, failure_reason
+                 FROM memories WHERE {} ORDER BY timestamp",
+                on_day
+            ))?;
+            for memory in memories.query_map(params![month_day, leap_fallback], Self::map_memory_row)? {
+                let memory = memory?;
+                let y = memory.timestamp.year();
+                years.entry(y).or_insert_with(|| empty(y)).memories.push(memory);
+            }
+
+            let mut events = conn.prepare(&format!(
+                "WITH day AS (
+                    SELECT e.*, e.rowid AS seq,
+                           ROW_NUMBER() OVER (PARTITION BY substr(e.timestamp, 1, 4)
+                               ORDER BY e.saved DESC, e.media_references != '[]' DESC, e.timestamp, e.rowid) AS n,
+                           COUNT(*) OVER (PARTITION BY substr(e.timestamp, 1, 4)) AS year_total
+                    FROM events e WHERE {}
+                 )
+                 SELECT e.id, e.timestamp, e.sender, e.conversation_id, e.content, e.event_type, e.media_references,
+                        e.metadata, {}, e.year_total
+                 FROM day e
+                 LEFT JOIN people p ON e.sender = p.username
+                 WHERE e.n <= ?3
+                 ORDER BY e.timestamp, e.seq",
+                on_day, SENDER_NAME
+            ))?;
+            let rows = events.query_map(params![month_day, leap_fallback, per_year], |r| {
+                Ok((Self::map_event_row(r)?, r.get::<_, i32>(9)?))
+            })?;
+            for row in rows {
+                let (event, total) = row?;
+                let y = event.timestamp.year();
+                let entry = years.entry(y).or_insert_with(|| empty(y));
+                entry.event_total = total;
+                entry.events.push(event);
+            }
+            Ok(years.into_values().rev().collect())
+        })
+    }
+
+    /// Full-text search limited to one calendar day (UTC) in every year, grouped by year, newest
+    /// first. Each year carries its total match count and its first `per_year` matches in time
+    /// order, so the UI can expand a year on demand.
+    pub fn search_messages_on_day(
+        &self,
+        query: &str,
+        month: u32,
+        day: u32,
+        filters: &MessageSearchFilters,
+        per_year: i32,
+    ) -> AppResult<Vec<YearSearchResults>> {
+        if chrono::NaiveDate::from_ymd_opt(2000, month, day).is_none() {
+            return Err(AppError::Validation(format!(
+                "Not a calendar day: {:02}-{:02}",
+                month, day
+            )));
+        }
+        let sanitized = Self::sanitize_fts_query(query, false);
+        if sanitized.is_empty() {
+            return Ok(Vec::new());
+        }
+        let month_day = format!("{:02}-{:02}", month, day);
+        let per_year = per_year.clamp(1, 500);
+
+        self.read_retrying("search_messages_on_day", |conn| {
+            // Timestamps are stored as RFC3339 UTC, so the month and day sit at fixed offsets.
+            // substr() measured about 3x faster than strftime('%m-%d', ...), and neither can use
+            // the timestamp index; the FTS match narrows the rows first either way.
+            let mut stmt = conn.prepare(
+                "WITH hits AS (
+                    SELECT f.event_id, f.conversation_id, f.sender, f.content, e.timestamp, e.event_type,
+                           substr(e.timestamp, 1, 4) AS year,
+                           snippet(events_fts, 0, ?6, ?7, '…', ?8) AS snippet,
+                           highlight(events_fts, 0, ?6, ?7) AS highlighted
+                    FROM events_fts f
+                    JOIN events e ON e.id = f.event_id
+                    WHERE events_fts MATCH ?1
+                      AND substr(e.timestamp, 6, 5) = ?2
+                      AND (?3 IS NULL OR f.conversation_id = ?3)
+                      AND (?4 IS NULL OR f.sender = ?4)
+                 ),
+                 ranked AS (
+                    SELECT hits.*,
+                           ROW_NUMBER() OVER (PARTITION BY year ORDER BY timestamp, event_id) AS n,
+                           COUNT(*) OVER (PARTITION BY year) AS year_total
+                    FROM hits
+                 )
+                 SELECT r.event_id, r.conversation_id, r.sender, r.content, r.timestamp, r.event_type,
+                        c.display_name, p.display_name, r.year, r.year_total, r.snippet, r.highlighted
+                 FROM ranked r
+                 LEFT JOIN conversations c ON r.conversation_id = c.id
+                 LEFT JOIN people p ON r.sender = p.username
+                 WHERE r.n <= ?5
+                 ORDER BY r.year DESC, r.n",
+            )?;
+            let rows = stmt.query_map(
+                params![
+                    sanitized,
+                    month_day,
+                    filters.conversation_id,
+                    filters.sender,
+                    per_year,
+                    SNIPPET_MATCH_START,
+                    SNIPPET_MATCH_END,
+                    SNIPPET_TOKENS
+                ],
+                |row| {
+                    let (timestamp, _) = parse_stored_timestamp(&row.get::<_, String>(4)?);
+                    let result = SearchResult {
+                        event_id: row.get(0)?,
+                        conversation_id: row.get(1)?,
+                        conversation_name: row.get(6)?,
+                        sender: row.get(2)?,
+                        sender_name: row.get(7)?,
+                        content: row.get(3)?,
+                        timestamp,
+                        event_type: row.get(5)?,
+                        snippet: row.get(10)?,
+                        match_ranges: highlighted_ranges(&row.get::<_, String>(11)?),
+                    };
+                    Ok((row.get::<_, String>(8)?, row.get::<_, i32>(9)?, result))
+                },
+            )?;
+
+            let mut years: Vec<YearSearchResults> = Vec::new();
+            for row in rows {
+                let (year, total, result) = row?;
+                let Ok(year) = year.parse::<i32>() else { continue };
+                match years.last_mut() {
+                    Some(group) if group.year == year => group.results.push(result),
+                    _ => years.push(YearSearchResults {
+                        year,
+                        total,
+                        results: vec![result],
+                    }),
+                }
+            }
+            Ok(years)
+        })
+    }
+
+    /// Escape `%`, `_` and `\` so user input is matched literally by `LIKE ... ESCAPE '\'`.
+    fn escape_like(query: &str) -> String {
+        query.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+    }
+
+    /// Search conversations by name, people by username/display name, and message content,
+    /// each bucket capped at `limit`. Names that start with the query rank before substring hits.
+    pub fn search_all(&self, query: &str, limit: i32) -> AppResult<SearchAllResults> {
+        let mut span = perf::query("search_all");
+        let trimmed = query.trim();
+        if trimmed.is_empty() {
+            return Ok(SearchAllResults::default());
+        }
+        let limit = limit.clamp(1, 100);
+        let escaped = Self::escape_like(trimmed);
+        let prefix = format!("{}%", escaped);
+        let contains = format!("%{}%", escaped);
+
+        let conn = self.conn()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT c.id, COALESCE(p.display_name, c.display_name) AS name, c.last_event_at,
+                    c.message_count AS msg_count
+             FROM conversations c
+             LEFT JOIN people p ON c.id = p.username
+             WHERE COALESCE(p.display_name, c.display_name, c.id) LIKE ?2 ESCAPE '\\'
+                OR c.id LIKE ?2 ESCAPE '\\'
+             ORDER BY CASE WHEN COALESCE(p.display_name, c.display_name, c.id) LIKE ?1 ESCAPE '\\' THEN 0 ELSE 1 END,
+                      c.last_event_at DESC
+             LIMIT ?3",
+        )?;
+        let conversations = stmt
+            .query_map(params![prefix, contains, limit], |row| {
+                let last_event_at: Option<String> = row.get(2)?;
+                Ok(ConversationMatch {
+                    id: row.get(0)?,
+                    display_name: row.get(1)?,
+                    message_count: row.get(3)?,
+                    last_event_at: last_event_at
+                        .and_then(|s| DateTime::parse_from_rfc3339(&s).ok().map(|dt| dt.with_timezone(&Utc))),
                 })
             })?
             .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
 
-        Ok(results)
+        let mut stmt = conn.prepare(
+            "SELECT p.username, p.display_name, c.id,
+                    (SELECT COUNT(*) FROM events e WHERE e.sender = p.username) AS msg_count
+             FROM people p
+             LEFT JOIN conversations c ON c.id = p.username
+             WHERE p.username LIKE ?2 ESCAPE '\\' OR p.display_name LIKE ?2 ESCAPE '\\'
+             ORDER BY CASE WHEN p.display_name LIKE ?1 ESCAPE '\\' OR p.username LIKE ?1 ESCAPE '\\' THEN 0 ELSE 1 END,
+                      msg_count DESC
+             LIMIT ?3",
+        )?;
+        let people = stmt
+            .query_map(params![prefix, contains, limit], |row| {
+                Ok(PersonMatch {
+                    username: row.get(0)?,
+                    display_name: row.get(1)?,
+                    conversation_id: row.get(2)?,
+                    message_count: row.get(3)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+
+        let messages = Self::search_messages_with(&conn, trimmed, limit, false, None)?;
+
+        span.set_rows(Some(conversations.len() + people.len() + messages.len()));
+        Ok(SearchAllResults {
+            conversations,
+            people,
+            messages,
+        })
     }
 
     pub fn get_memories(&self, export_id: Option<&str>) -> AppResult<Vec<Memory>> {
+        let mut span = perf::query("get_memories");
         let query = if export_id.is_some() {
-            "SELECT id, timestamp, media_type, latitude, longitude, media_path, download_url, proxy_url, download_status, export_id
+            "SELECT id, timestamp, media_type, latitude, longitude, media_path, download_url, proxy_url, download_status, export_id, overlay_url, failure_reason
              FROM memories WHERE export_id = ?1 ORDER BY timestamp DESC"
         } else {
-            "SELECT id, timestamp, media_type, latitude, longitude, media_path, download_url, proxy_url, download_status, export_id
+            "SELECT id, timestamp, media_type, latitude, longitude, media_path, download_url, proxy_url, download_status, export_id, overlay_url, failure_reason
              FROM memories ORDER BY timestamp DESC"
         };
 
@@ -718,17 +2746,147 @@ impl DatabaseManager {
         for row in rows {
             memories.push(row?);
         }
+        span.set_rows(memories.trace_rows());
+        Ok(memories)
+    }
+
+    pub fn get_memory(&self, id: &str) -> AppResult<Memory> {
+        let _span = perf::query("get_memory");
+        use rusqlite::OptionalExtension;
+        let conn = self.conn()?;
+        conn.query_row(
+            "SELECT id, timestamp, media_type, latitude, longitude, media_path, download_url, proxy_url, download_status, export_id, overlay_url, failure_reason
+             FROM memories WHERE id = ?1",
+            [id],
+            Self::map_memory_row,
+        )
+        .optional()?
+        .ok_or_else(|| AppError::NotFound(format!("memory {}", id)))
+    }
+
+    /// Ids of the memories just before and after `id` in time, ties broken by id.
+    pub fn get_adjacent_memories(&self, id: &str) -> AppResult<AdjacentMemories> {
+        let _span = perf::query("get_adjacent_memories");
+        use rusqlite::OptionalExtension;
+        let conn = self.conn()?;
+        let timestamp: String = conn
+            .query_row("SELECT timestamp FROM memories WHERE id = ?1", [id], |r| r.get(0))
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("memory {}", id)))?;
+        let previous = conn
+            .query_row(
+                "SELECT id FROM memories WHERE timestamp < ?1 OR (timestamp = ?1 AND id < ?2)
+                 ORDER BY timestamp DESC, id DESC LIMIT 1",
+                params![timestamp, id],
+                |r| r.get(0),
+            )
+            .optional()?;
+        let next = conn
+            .query_row(
+                "SELECT id FROM memories WHERE timestamp > ?1 OR (timestamp = ?1 AND id > ?2)
+                 ORDER BY timestamp ASC, id ASC LIMIT 1",
+                params![timestamp, id],
+                |r| r.get(0),
+            )
+            .optional()?;
+        Ok(AdjacentMemories { previous, next })
+    }
+
+    /// One page of memories matching `filter`, newest first.
+    pub fn get_memories_page(&self, limit: i32, offset: i32, filter: &MemoryFilter) -> AppResult<MemoryPage> {
+        let limit = limit.clamp(1, 1000);
+        let offset = offset.max(0);
+        let range = filter.date_range.clone().unwrap_or_default();
+        let filter_params = params![
+            filter.export_id,
+            range.start.map(|d| d.to_rfc3339()),
+            range.end.map(|d| d.to_rfc3339()),
+            filter.media_type,
+            filter.download_status.as_ref().map(|s| s.as_str()),
+            filter.has_location,
+        ];
+
+        self.read_retrying("get_memories_page", |conn| {
+            let total_count: i32 = conn.query_row(
+                &format!("SELECT COUNT(*) FROM memories {}", MEMORY_FILTER_WHERE),
+                filter_params,
+                |r| r.get(0),
+            )?;
+            let mut stmt = conn.prepare(&format!(
+                "SELECT id, timestamp, media_type, latitude, longitude, media_path, download_url, proxy_url, download_status, export_id, overlay_url, failure_reason
+                 FROM memories {} ORDER BY timestamp DESC, id DESC LIMIT ?7 OFFSET ?8",
+                MEMORY_FILTER_WHERE
+            ))?;
+            let mut page_params = filter_params.to_vec();
+            page_params.extend(params![limit, offset]);
+            let items = stmt
+                .query_map(page_params.as_slice(), Self::map_memory_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(MemoryPage {
+                has_more: offset + (items.len() as i32) < total_count,
+                items,
+                total_count,
+            })
+        })
+    }
+
+    /// Every memory matching `filter` that is still Pending or Failed, oldest first.
+    pub fn get_undownloaded_memories(&self, filter: &MemoryFilter) -> AppResult<Vec<Memory>> {
+        let range = filter.date_range.clone().unwrap_or_default();
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, timestamp, media_type, latitude, longitude, media_path, download_url, proxy_url, download_status, export_id, overlay_url, failure_reason
+             FROM memories {} AND download_status IN ('Pending', 'Failed') ORDER BY timestamp, id",
+            MEMORY_FILTER_WHERE
+        ))?;
+        let memories = stmt
+            .query_map(
+                params![
+                    filter.export_id,
+                    range.start.map(|d| d.to_rfc3339()),
+                    range.end.map(|d| d.to_rfc3339()),
+                    filter.media_type,
+                    filter.download_status.as_ref().map(|s| s.as_str()),
+                    filter.has_location,
+                ],
+                Self::map_memory_row,
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
         Ok(memories)
     }
 
+    /// Call `f` with every memory matching `filter`, oldest first, without loading them all.
+    pub fn foreach_memory<F>(&self, filter: &MemoryFilter, mut f: F) -> AppResult<()>
+    where
+        F: FnMut(Memory) -> AppResult<()>,
+    {
+        let range = filter.date_range.clone().unwrap_or_default();
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, timestamp, media_type, latitude, longitude, media_path, download_url, proxy_url, download_status, export_id, overlay_url, failure_reason
+             FROM memories {} ORDER BY timestamp, id",
+            MEMORY_FILTER_WHERE
+        ))?;
+        let rows = stmt.query_map(
+            params![
+                filter.export_id,
+                range.start.map(|d| d.to_rfc3339()),
+                range.end.map(|d| d.to_rfc3339()),
+                filter.media_type,
+                filter.download_status.as_ref().map(|s| s.as_str()),
+                filter.has_location,
+            ],
+            Self::map_memory_row,
+        )?;
+        for memory in rows {
+            f(memory?)?;
+        }
+        Ok(())
+    }
+
     fn map_memory_row(row: &rusqlite::Row) -> rusqlite::Result<Memory> {
         let timestamp_str: String = row.get(1)?;
-        let timestamp = chrono::DateTime::parse_from_rfc3339(&timestamp_str)
-            .map(|dt| dt.with_timezone(&chrono::Utc))
-            .unwrap_or_else(|e| {
-                log::warn!("Bad timestamp in DB: '{}': {}", timestamp_str, e);
-                chrono::DateTime::<chrono::Utc>::MIN_UTC
-            });
+        let (timestamp, _) = parse_stored_timestamp(&timestamp_str);
         let media_path_str: Option<String> = row.get(5)?;
         let status_str: String = row.get(8)?;
         let download_status = match status_str.as_str() {
@@ -748,411 +2906,5900 @@ impl DatabaseManager {
             export_id: row.get(9)?,
             download_url: row.get(6)?,
             proxy_url: row.get(7)?,
+            overlay_url: row.get(10)?,
+            failure_reason: row.get(11)?,
             download_status,
         })
     }
 
-    pub fn get_setting(&self, key: &str) -> AppResult<Option<String>> {
-        let conn = self.conn()?;
-        let mut stmt = conn.prepare("SELECT value FROM settings WHERE key = ?1")?;
-        let result = stmt.query_row([key], |row| row.get(0)).ok();
-        Ok(result)
-    }
+    /// Drop media references to macOS metadata files (`._*`, `__MACOSX/`, `.DS_Store`) that
+    /// earlier versions indexed. Returns the number of events updated. Runs once per database;
+    /// later calls return 0 without scanning.
+    pub fn purge_os_metadata_media(&self) -> AppResult<usize> {
+        if self.get_setting(OS_METADATA_PURGED_SETTING)?.is_some() {
+            return Ok(0);
+        }
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        let mut updated = 0;
+        {
+            let mut select = tx.prepare(
+                "SELECT id, media_references FROM events
+                 WHERE media_references LIKE '%/.\\_%' ESCAPE '\\'
+                    OR media_references LIKE '%\\\\.\\_%' ESCAPE '\\'
+                    OR media_references LIKE '%\\_\\_MACOSX%' ESCAPE '\\'
+                    OR media_references LIKE '%.DS\\_Store%' ESCAPE '\\'",
+            )?;
+            let rows = select
+                .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+                .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
 
-    pub fn set_setting(&self, key: &str, value: &str) -> AppResult<()> {
-        self.conn()?.execute(
+            let mut update = tx.prepare("UPDATE events SET media_references = ?1 WHERE id = ?2")?;
+            for (id, refs_json) in rows {
+                let refs: Vec<PathBuf> = serde_json::from_str(&refs_json).unwrap_or_default();
+                let kept: Vec<&PathBuf> = refs.iter().filter(|p| !crate::ingestion::is_os_metadata(p)).collect();
+                if kept.len() != refs.len() {
+                    update.execute(params![serde_json::to_string(&kept)?, id])?;
+                    updated += 1;
+                }
+            }
+        }
+        if updated > 0 {
+            Self::refresh_conversation_counts_with(&tx, None)?;
+        }
+        tx.execute(
             "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
-            params![key, value],
+            params![OS_METADATA_PURGED_SETTING, Utc::now().to_rfc3339()],
         )?;
-        Ok(())
+        tx.commit()?;
+        Ok(updated)
     }
 
-    pub fn get_unified_media_stream(&self, limit: i32, offset: i32) -> AppResult<PaginatedMedia> {
-        let limit = limit.clamp(1, 1000);
-        let offset = offset.max(0);
-        let conn = self.conn()?;
-
-        // 1. Get total count for pagination info
-        let total_count: i32 = conn.query_row(
-            r#"SELECT (
-                SELECT COUNT(*) FROM events 
-                WHERE media_references IS NOT NULL AND media_references != '[]' 
-                AND event_type IN ('MEDIA', 'SNAP', 'SNAP_VIDEO', 'NOTE', 'STICKER')
-            ) + (
-                SELECT COUNT(*) FROM memories WHERE media_path IS NOT NULL
-            )"#,
-            [],
+    /// Journal a move of `src` to `dst`. Call before touching either file.
+    pub fn begin_fs_operation(&self, kind: &str, src: &Path, dst: &Path) -> AppResult<FsOperation> {
+        let created_at = Utc::now();
+        let id: i64 = self.write_conn()?.query_row(
+            "INSERT INTO fs_operations (kind, src, dst, state, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5) RETURNING id",
+            params![
+                kind,
+                src.to_string_lossy(),
+                dst.to_string_lossy(),
+                FsOperationState::Pending.as_str(),
+                created_at.to_rfc3339()
+            ],
             |r| r.get(0),
         )?;
+        Ok(FsOperation {
+            id,
+            kind: kind.to_string(),
+            src: src.to_path_buf(),
+            dst: dst.to_path_buf(),
+            state: FsOperationState::Pending,
+            created_at,
+        })
+    }
 
-        // 2. Optimized UNION query
-        let mut stmt = conn.prepare(
-            r#"SELECT id, json_extract(media_references, '$[0]') as path, event_type as media_type, timestamp, 'local' as source
-             FROM events
-             WHERE media_references IS NOT NULL AND media_references != '[]'
-             AND event_type IN ('MEDIA', 'SNAP', 'SNAP_VIDEO', 'NOTE', 'STICKER')
-             UNION ALL
-             SELECT id, media_path as path, media_type, timestamp, 'cloud' as source
-             FROM memories
-             WHERE media_path IS NOT NULL
-             ORDER BY timestamp DESC
-             LIMIT ?1 OFFSET ?2"#
-        )?;
-
-        let entries = stmt
-            .query_map(params![limit, offset], |row| {
-                let timestamp_str: String = row.get(3)?;
-                let timestamp = DateTime::parse_from_rfc3339(&timestamp_str)
-                    .map(|dt| dt.with_timezone(&Utc))
-                    .unwrap_or_else(|_| Utc::now());
-
-                let media_type_raw: String = row.get(2)?;
-                let media_type = if media_type_raw.contains("VIDEO") || media_type_raw == "Video" {
-                    "Video".to_string()
-                } else {
-                    "Image".to_string()
-                };
+    /// Point every memory and message that references `op.src` at `op.dst` and mark the
+    /// operation Done, in one transaction. Returns how many rows were repointed.
+    pub fn finish_fs_operation(&self, op: &FsOperation) -> AppResult<usize> {
+        let (src, dst) = (op.src.to_string_lossy(), op.dst.to_string_lossy());
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        let mut updated = tx.execute(
+            "UPDATE memories SET media_path = ?2 WHERE media_path = ?1",
+            params![src, dst],
+        )?;
+        {
+            let mut select = tx
+                .prepare("SELECT id, media_references FROM events WHERE instr(media_references, json_quote(?1)) > 0")?;
+            let rows = select
+                .query_map([&src], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+                .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+            let mut update = tx.prepare("UPDATE events SET media_references = ?1 WHERE id = ?2")?;
+            for (id, refs_json) in rows {
+                let mut refs: Vec<PathBuf> = serde_json::from_str(&refs_json).unwrap_or_default();
+                let mut changed = false;
+                for path in refs.iter_mut().filter(|p| **p == op.src) {
+                    *path = op.dst.clone();
+                    changed = true;
+                }
+                if changed {
+                    update.execute(params![serde_json::to_string(&refs)?, id])?;
+                    updated += 1;
+                }
+            }
+        }
+        tx.execute(
+            "UPDATE OR REPLACE media_info SET path = ?2 WHERE path = ?1",
+            params![src, dst],
+        )?;
+        tx.execute(
+            "UPDATE fs_operations SET state = ?2, updated_at = ?3 WHERE id = ?1",
+            params![op.id, FsOperationState::Done.as_str(), Utc::now().to_rfc3339()],
+        )?;
+        tx.commit()?;
+        Ok(updated)
+    }
 
-                Ok(MediaStreamEntry {
+    pub fn set_fs_operation_state(&self, id: i64, state: FsOperationState) -> AppResult<()> {
+        self.write_conn()?.execute(
+            "UPDATE fs_operations SET state = ?2, updated_at = ?3 WHERE id = ?1",
+            params![id, state.as_str(), Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Moves in progress, or cut short by a crash when read at startup. Oldest first.
+    pub fn get_pending_fs_operations(&self) -> AppResult<Vec<FsOperation>> {
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare("SELECT id, kind, src, dst, state, created_at FROM fs_operations WHERE state = ?1 ORDER BY id")?;
+        let rows = stmt
+            .query_map([FsOperationState::Pending.as_str()], |row| {
+                let state: String = row.get(4)?;
+                let created_at: String = row.get(5)?;
+                Ok(FsOperation {
                     id: row.get(0)?,
-                    path: PathBuf::from(row.get::<_, String>(1)?),
-                    media_type,
-                    timestamp,
-                    source: row.get(4)?,
+                    kind: row.get(1)?,
+                    src: PathBuf::from(row.get::<_, String>(2)?),
+                    dst: PathBuf::from(row.get::<_, String>(3)?),
+                    state: FsOperationState::parse(&state).unwrap_or(FsOperationState::Pending),
+                    created_at: parse_stored_timestamp(&created_at).0,
                 })
             })?
             .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+        Ok(rows)
+    }
 
-        Ok(PaginatedMedia {
-            items: entries,
-            total_count,
-            has_more: (offset + limit) < total_count,
-        })
+    /// Forget finished moves. Failed ones are kept so the lost files can be looked into.
+    pub fn prune_fs_operations(&self) -> AppResult<usize> {
+        Ok(self.write_conn()?.execute(
+            "DELETE FROM fs_operations WHERE state IN (?1, ?2)",
+            params![FsOperationState::Done.as_str(), FsOperationState::RolledBack.as_str()],
+        )?)
     }
 
-    pub fn get_message_index_at_date(&self, conversation_id: &str, date: &str) -> AppResult<i32> {
-        // date is expected as "YYYY-MM-DD"
-        let target = format!("{}T00:00:00+00:00", date);
-        let index: i32 = self.conn()?.query_row(
-            r#"SELECT COUNT(*) FROM events
-             WHERE conversation_id = ?1 AND timestamp < ?2"#,
-            params![conversation_id, target],
-            |r| r.get(0),
+    /// An export's events that have no media references yet, for linking after the fact.
+    pub fn get_unlinked_events(&self, export_id: &str) -> AppResult<Vec<Event>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT e.id, e.timestamp, e.sender, e.conversation_id, e.content, e.event_type, e.media_references, e.metadata, NULL
+             FROM events e
+             WHERE e.export_id = ?1 AND (e.media_references = '[]' OR e.media_references IS NULL)",
         )?;
-        Ok(index)
+        let events = stmt
+            .query_map([export_id], Self::map_event_row)?
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+        Ok(events)
     }
 
-    pub fn get_activity_dates(&self, conversation_id: &str) -> AppResult<Vec<String>> {
+    /// Up to `limit` events of the types media is linked to that name media IDs but have no
+    /// media references, in id order starting after `after_id`.
+    pub fn get_unlinked_media_events(&self, after_id: &str, limit: usize) -> AppResult<Vec<Event>> {
         let conn = self.conn()?;
         let mut stmt = conn.prepare(
-            r#"SELECT DISTINCT substr(timestamp, 1, 10) as dt FROM events
-             WHERE conversation_id = ?1
-             ORDER BY dt ASC"#,
+            "SELECT e.id, e.timestamp, e.sender, e.conversation_id, e.content, e.event_type, e.media_references, e.metadata, NULL
+             FROM events e
+             WHERE e.id > ?1 AND (e.media_references = '[]' OR e.media_references IS NULL)
+               AND e.event_type IN (SELECT value FROM json_each(?2))
+               AND json_valid(e.metadata) AND json_array_length(e.metadata, '$.media_ids') > 0
+             ORDER BY e.id LIMIT ?3",
         )?;
-        let dates = stmt
-            .query_map([conversation_id], |row| row.get(0))?
-            .collect::<std::result::Result<Vec<String>, _>>()?;
-        Ok(dates)
+        let events = stmt
+            .query_map(
+                params![
+                    after_id,
+                    serde_json::to_string(&MediaLinker::LINKED_EVENT_TYPES)?,
+                    limit as i64
+                ],
+                Self::map_event_row,
+            )?
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+        Ok(events)
     }
 
-    /// Generate a data integrity report for the dashboard.
-    pub fn get_validation_report(&self) -> AppResult<ValidationReport> {
+    /// Every Media ID some message's metadata names.
+    pub fn get_referenced_media_ids(&self) -> AppResult<HashSet<String>> {
         let conn = self.conn()?;
-        let total_media_referenced: i32 =
-            conn.query_row("SELECT COUNT(*) FROM events WHERE event_type = 'MEDIA'", [], |r| {
-                r.get(0)
-            })?;
-        let media_found: i32 = conn.query_row(
-            r#"SELECT COUNT(*) FROM events WHERE event_type = 'MEDIA' AND media_references != '[]' AND media_references IS NOT NULL"#,
-            [], |r| r.get(0)
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT j.value FROM events e, json_each(e.metadata, '$.media_ids') j
+             WHERE json_valid(e.metadata) AND j.type = 'text'",
         )?;
-        let media_missing = total_media_referenced - media_found;
+        let ids = stmt
+            .query_map([], |r| r.get(0))?
+            .collect::<std::result::Result<HashSet<String>, rusqlite::Error>>()?;
+        Ok(ids)
+    }
 
-        let total_html_files: i32 = conn.query_row("SELECT COUNT(*) FROM conversations", [], |r| r.get(0))?;
+    /// Conversations holding at least one of an export's events.
+    pub fn get_export_conversation_ids(&self, export_id: &str) -> AppResult<Vec<String>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT conversation_id FROM events
+             WHERE export_id = ?1 AND conversation_id IS NOT NULL ORDER BY conversation_id",
+        )?;
+        let ids = stmt
+            .query_map([export_id], |r| r.get(0))?
+            .collect::<std::result::Result<Vec<String>, rusqlite::Error>>()?;
+        Ok(ids)
+    }
 
-        let mut warnings = Vec::new();
-        if media_missing > 0 {
-            warnings.push(format!("{} media events have no linked file", media_missing));
-        }
+    /// An export's events in one conversation, oldest first.
+    pub fn get_export_conversation_events(&self, export_id: &str, conversation_id: &str) -> AppResult<Vec<Event>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT e.id, e.timestamp, e.sender, e.conversation_id, e.content, e.event_type, e.media_references, e.metadata, NULL
+             FROM events e
+             WHERE e.export_id = ?1 AND e.conversation_id = ?2
+             ORDER BY e.timestamp, e.id",
+        )?;
+        let events = stmt
+            .query_map([export_id, conversation_id], Self::map_event_row)?
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+        Ok(events)
+    }
 
-        let empty_convos: i32 = conn.query_row(
-            "SELECT COUNT(*) FROM conversations c WHERE NOT EXISTS (SELECT 1 FROM events WHERE conversation_id = c.id)",
-            [], |r| r.get(0)
-        ).unwrap_or(0);
-        if empty_convos > 0 {
-            warnings.push(format!("{} conversations have no messages", empty_convos));
+    /// An export's events of the given types, oldest first.
+    pub fn get_export_events_of_types(&self, export_id: &str, event_types: &[&str]) -> AppResult<Vec<Event>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT e.id, e.timestamp, e.sender, e.conversation_id, e.content, e.event_type, e.media_references, e.metadata, NULL
+             FROM events e
+             WHERE e.export_id = ?1 AND e.event_type IN (SELECT value FROM json_each(?2))
+             ORDER BY e.timestamp, e.id",
+        )?;
+        let events = stmt
+            .query_map(
+                params![export_id, serde_json::to_string(event_types)?],
+                Self::map_event_row,
+            )?
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+        Ok(events)
+    }
+
+    /// The sender of most of an export's messages marked as sent by the owner, for exports
+    /// whose account file has no username. Ties go to the alphabetically first name.
+    pub fn most_frequent_own_sender(&self, export_id: &str) -> AppResult<Option<String>> {
+        use rusqlite::OptionalExtension;
+        let conn = self.conn()?;
+        Ok(conn
+            .query_row(
+                "SELECT sender FROM events
+                 WHERE export_id = ?1 AND sender != '' AND json_valid(metadata)
+                   AND json_extract(metadata, '$.is_sender') = 1
+                 GROUP BY sender ORDER BY COUNT(*) DESC, sender LIMIT 1",
+                [export_id],
+                |r| r.get(0),
+            )
+            .optional()?)
+    }
+
+    /// Name `username` as the sender of an export's events sent by the owner without one, such
+    /// as stories and outgoing calls. Returns how many were updated.
+    pub fn fill_own_sender(&self, export_id: &str, username: &str) -> AppResult<usize> {
+        Ok(self.write_conn()?.execute(
+            "UPDATE events SET sender = ?2
+             WHERE export_id = ?1 AND (sender = '' OR sender IS NULL) AND json_valid(metadata)
+               AND json_extract(metadata, '$.is_sender') = 1",
+            params![export_id, username],
+        )?)
+    }
+
+    /// Store the media references of events that are already in the database, and update
+    /// their conversations' linked media counts. Returns how many events were updated.
+    pub fn update_event_media_references(&self, events: &[Event]) -> AppResult<usize> {
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        let mut updated = 0;
+        let mut changes = CountChanges::default();
+        {
+            let mut counts_stmt = tx.prepare(EVENT_COUNTS_SELECT)?;
+            let mut stmt = tx.prepare("UPDATE events SET media_references = ?1 WHERE id = ?2")?;
+            for event in events {
+                changes.track(&mut counts_stmt, &event.id, -1)?;
+                updated += stmt.execute(params![serde_json::to_string(&event.media_references)?, event.id])?;
+                changes.track(&mut counts_stmt, &event.id, 1)?;
+            }
         }
+        changes.apply(&tx)?;
+        tx.commit()?;
+        Ok(updated)
+    }
 
-        Ok(ValidationReport {
-            total_html_files,
-            parsed_html_files: total_html_files,
-            total_media_referenced,
-            media_found,
-            media_missing,
-            missing_files: Vec::new(),
-            warnings,
+    pub fn create_tag(&self, name: &str, color: Option<&str>) -> AppResult<Tag> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(AppError::Validation("Tag name cannot be empty".into()));
+        }
+        let conn = self.write_conn()?;
+        let created_at = Utc::now();
+        conn.execute(
+            "INSERT INTO tags (name, color, created_at) VALUES (?1, ?2, ?3)",
+            params![name, color, created_at.to_rfc3339()],
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::SqliteFailure(f, _) if f.code == rusqlite::ErrorCode::ConstraintViolation => {
+                AppError::Validation(format!("A tag named '{}' already exists", name))
+            }
+            other => other.into(),
+        })?;
+        Ok(Tag {
+            id: conn.last_insert_rowid(),
+            name: name.to_string(),
+            color: color.map(str::to_string),
+            created_at,
+            usage_count: 0,
+        })
+    }
+
+    /// Delete a tag; its taggings go with it.
+    pub fn delete_tag(&self, tag_id: i64) -> AppResult<()> {
+        self.write_conn()?.execute("DELETE FROM tags WHERE id = ?1", [tag_id])?;
+        Ok(())
+    }
+
+    pub fn get_tags(&self) -> AppResult<Vec<Tag>> {
+        let mut span = perf::query("get_tags");
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT t.id, t.name, t.color, t.created_at, COUNT(tg.entity_id)
+             FROM tags t
+             LEFT JOIN taggings tg ON tg.tag_id = t.id
+             GROUP BY t.id
+             ORDER BY t.name COLLATE NOCASE",
+        )?;
+        let tags = stmt
+            .query_map([], |row| {
+                let created_at: String = row.get(3)?;
+                Ok(Tag {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    color: row.get(2)?,
+                    created_at: DateTime::parse_from_rfc3339(&created_at)
+                        .map(|dt| dt.with_timezone(&Utc))
+                        .unwrap_or_else(|_| Utc::now()),
+                    usage_count: row.get(4)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+        span.set_rows(tags.trace_rows());
+        Ok(tags)
+    }
+
+    /// Describe an entity by its content rather than its row ID. Event and memory IDs are
+    /// regenerated on every import, so this is what lets a tagging find its entity again.
+    fn entity_anchor(
+        conn: &rusqlite::Connection,
+        entity_type: TagEntityType,
+        entity_id: &str,
+    ) -> AppResult<Option<String>> {
+        use rusqlite::OptionalExtension;
+        let anchor = match entity_type {
+            TagEntityType::Conversation => conn
+                .query_row("SELECT id FROM conversations WHERE id = ?1", [entity_id], |r| {
+                    r.get::<_, String>(0)
+                })
+                .optional()?
+                .map(|id| serde_json::json!([id])),
+            TagEntityType::Event => conn
+                .query_row(
+                    "SELECT conversation_id, timestamp, sender, COALESCE(content, '') FROM events WHERE id = ?1",
+                    [entity_id],
+                    |r| {
+                        Ok(Self::event_anchor(
+                            r.get::<_, Option<String>>(0)?.as_deref(),
+                            &r.get::<_, String>(1)?,
+                            &r.get::<_, String>(2)?,
+                            &r.get::<_, String>(3)?,
+                        ))
+                    },
+                )
+                .optional()?,
+            TagEntityType::Memory => conn
+                .query_row(
+                    "SELECT timestamp, media_type, COALESCE(download_url, '') FROM memories WHERE id = ?1",
+                    [entity_id],
+                    |r| {
+                        Ok(serde_json::json!([
+                            r.get::<_, String>(0)?,
+                            r.get::<_, String>(1)?,
+                            r.get::<_, String>(2)?
+                        ]))
+                    },
+                )
+                .optional()?,
+        };
+        Ok(anchor.map(|a| a.to_string()))
+    }
+
+    fn event_anchor(conversation_id: Option<&str>, timestamp: &str, sender: &str, content: &str) -> serde_json::Value {
+        serde_json::json!([conversation_id, timestamp, sender, content])
+    }
+
+    /// Find the current ID of the entity an anchor describes.
+    fn resolve_anchor(
+        conn: &rusqlite::Connection,
+        entity_type: TagEntityType,
+        anchor: &str,
+    ) -> AppResult<Option<String>> {
+        use rusqlite::OptionalExtension;
+        let parts: Vec<Option<String>> = serde_json::from_str(anchor).unwrap_or_default();
+        let part = |i: usize| parts.get(i).cloned().flatten();
+        let id = match entity_type {
+            TagEntityType::Conversation => conn
+                .query_row("SELECT id FROM conversations WHERE id = ?1", [part(0)], |r| r.get(0))
+                .optional()?,
+            TagEntityType::Event => conn
+                .query_row(
+                    "SELECT id FROM events
+                     WHERE conversation_id IS ?1 AND timestamp = ?2 AND sender = ?3 AND COALESCE(content, '') = ?4
+                     LIMIT 1",
+                    params![part(0), part(1), part(2), part(3)],
+                    |r| r.get(0),
+                )
+                .optional()?,
+            TagEntityType::Memory => conn
+                .query_row(
+                    "SELECT id FROM memories
+                     WHERE timestamp = ?1 AND media_type = ?2 AND COALESCE(download_url, '') = ?3
+                     LIMIT 1",
+                    params![part(0), part(1), part(2)],
+                    |r| r.get(0),
+                )
+                .optional()?,
+        };
+        Ok(id)
+    }
+
+    pub fn tag_entity(&self, tag_id: i64, entity_type: TagEntityType, entity_id: &str) -> AppResult<()> {
+        let conn = self.write_conn()?;
+        let anchor = Self::entity_anchor(&conn, entity_type, entity_id)?.ok_or_else(|| {
+            AppError::Validation(format!(
+                "No {} with id '{}'",
+                entity_type.as_str().to_lowercase(),
+                entity_id
+            ))
+        })?;
+        conn.execute(
+            "INSERT OR IGNORE INTO taggings (tag_id, entity_type, entity_id, anchor, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![tag_id, entity_type.as_str(), entity_id, anchor, Utc::now().to_rfc3339()],
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::SqliteFailure(f, _) if f.code == rusqlite::ErrorCode::ConstraintViolation => {
+                AppError::Validation(format!("No tag with id {}", tag_id))
+            }
+            other => other.into(),
+        })?;
+        Ok(())
+    }
+
+    pub fn untag_entity(&self, tag_id: i64, entity_type: TagEntityType, entity_id: &str) -> AppResult<()> {
+        self.write_conn()?.execute(
+            "DELETE FROM taggings WHERE tag_id = ?1 AND entity_type = ?2 AND entity_id = ?3",
+            params![tag_id, entity_type.as_str(), entity_id],
+        )?;
+        Ok(())
+    }
+
+    /// Entities of one type carrying `tag_id`, most recently tagged first.
+    pub fn get_tagged(
+        &self,
+        tag_id: i64,
+        entity_type: TagEntityType,
+        limit: i32,
+        offset: i32,
+    ) -> AppResult<TaggedPage> {
+        let mut span = perf::query("get_tagged");
+        let offset = offset.max(0);
+        let limit = limit.clamp(1, 500);
+        let conn = self.conn()?;
+        let total_count: i32 = conn.query_row(
+            "SELECT COUNT(*) FROM taggings WHERE tag_id = ?1 AND entity_type = ?2",
+            params![tag_id, entity_type.as_str()],
+            |r| r.get(0),
+        )?;
+
+        let items = match entity_type {
+            TagEntityType::Conversation => conn
+                .prepare(
+                    "SELECT c.id, c.display_name, c.participants, c.last_event_at,
+                     c.message_count, p.display_name as resolved_name, c.linked_media_count,
+                     c.media_count, c.media_bytes, c.missing_media_count, p.avatar_path,
+                     (SELECT group_concat(DISTINCT v.kind) FROM validation_issues v WHERE v.conversation_id = c.id) AS anomaly_kinds,
+                     c.is_group, c.saved_count, c.shared_location_count,
+                     c.completeness
+                     FROM taggings t
+                     JOIN conversations c ON c.id = t.entity_id
+                     LEFT JOIN people p ON c.id = p.username
+                     WHERE t.tag_id = ?1 AND t.entity_type = 'Conversation'
+                     ORDER BY t.created_at DESC, t.entity_id
+                     LIMIT ?2 OFFSET ?3",
+                )?
+                .query_map(params![tag_id, limit, offset], Self::map_conversation_row)?
+                .map(|r| r.map(TaggedEntry::Conversation))
+                .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?,
+            TagEntityType::Event => conn
+                .prepare(
+                    "SELECT e.id, e.timestamp, e.sender, e.conversation_id, e.content, e.event_type, e.media_references, e.metadata, p.display_name
+                     FROM taggings t
+                     JOIN events e ON e.id = t.entity_id
+                     LEFT JOIN people p ON e.sender = p.username
+                     WHERE t.tag_id = ?1 AND t.entity_type = 'Event'
+                     ORDER BY t.created_at DESC, t.entity_id
+                     LIMIT ?2 OFFSET ?3",
+                )?
+                .query_map(params![tag_id, limit, offset], Self::map_event_row)?
+                .map(|r| r.map(TaggedEntry::Event))
+                .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?,
+            TagEntityType::Memory => conn
+                .prepare(
+                    "SELECT m.id, m.timestamp, m.media_type, m.latitude, m.longitude, m.media_path, m.download_url, m.proxy_url, m.download_status, m.export_id, m.overlay_url, m.failure_reason
+                     FROM taggings t
+                     JOIN memories m ON m.id = t.entity_id
+                     WHERE t.tag_id = ?1 AND t.entity_type = 'Memory'
+                     ORDER BY t.created_at DESC, t.entity_id
+                     LIMIT ?2 OFFSET ?3",
+                )?
+                .query_map(params![tag_id, limit, offset], Self::map_memory_row)?
+                .map(|r| r.map(TaggedEntry::Memory))
+                .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?,
+        };
+
+        span.set_rows(items.trace_rows());
+        Ok(TaggedPage {
+            items,
+            total_count,
+            has_more: (offset + limit) < total_count,
+        })
+    }
+
+    /// Snapshot every tagging by anchor, for carrying tags across a reimport.
+    pub fn export_taggings(&self) -> AppResult<Vec<TaggingSnapshot>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT t.name, t.color, tg.entity_type, tg.anchor
+             FROM tags t
+             LEFT JOIN taggings tg ON tg.tag_id = t.id
+             ORDER BY t.name COLLATE NOCASE, tg.entity_type, tg.anchor",
+        )?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                ))
+            })?
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+
+        // Unused tags come back with a NULL entity so they are recreated too
+        Ok(rows
+            .into_iter()
+            .map(|(tag_name, tag_color, entity_type, anchor)| TaggingSnapshot {
+                tag_name,
+                tag_color,
+                entity: entity_type.as_deref().and_then(TagEntityType::parse).zip(anchor),
+            })
+            .collect())
+    }
+
+    /// Recreate tags and re-resolve taggings from a snapshot. Returns (restored, unresolved).
+    pub fn restore_taggings(&self, snapshot: &[TaggingSnapshot]) -> AppResult<(usize, usize)> {
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        let mut restored = 0;
+        let mut unresolved = 0;
+        {
+            let now = Utc::now().to_rfc3339();
+            let mut insert_tag =
+                tx.prepare("INSERT OR IGNORE INTO tags (name, color, created_at) VALUES (?1, ?2, ?3)")?;
+            let mut insert_tagging = tx.prepare(
+                "INSERT OR IGNORE INTO taggings (tag_id, entity_type, entity_id, anchor, created_at)
+                 SELECT id, ?2, ?3, ?4, ?5 FROM tags WHERE name = ?1",
+            )?;
+            for item in snapshot {
+                insert_tag.execute(params![item.tag_name, item.tag_color, now])?;
+                let Some((entity_type, anchor)) = &item.entity else {
+                    continue;
+                };
+                match Self::resolve_anchor(&tx, *entity_type, anchor)? {
+                    Some(entity_id) => {
+                        insert_tagging.execute(params![item.tag_name, entity_type.as_str(), entity_id, anchor, now])?;
+                        restored += 1;
+                    }
+                    None => unresolved += 1,
+                }
+            }
+        }
+        tx.commit()?;
+        Ok((restored, unresolved))
+    }
+
+    /// Delete events (with their FTS rows and taggings) inside `tx`, returning how many were
+    /// removed and the media files they referenced.
+    fn delete_events_tx(tx: &rusqlite::Transaction, event_ids: &[String]) -> AppResult<(usize, Vec<PathBuf>)> {
+        use rusqlite::OptionalExtension;
+        let mut media_stmt = tx.prepare("SELECT media_references, conversation_id FROM events WHERE id = ?1")?;
+        let mut fts_stmt = tx.prepare("DELETE FROM events_fts WHERE event_id = ?1")?;
+        let mut tagging_stmt = tx.prepare("DELETE FROM taggings WHERE entity_type = 'Event' AND entity_id = ?1")?;
+        let mut event_stmt = tx.prepare("DELETE FROM events WHERE id = ?1")?;
+        let mut removed = 0;
+        let mut media = Vec::new();
+        let mut conversation_ids = BTreeSet::new();
+        for id in event_ids {
+            let row: Option<(Option<String>, Option<String>)> =
+                media_stmt.query_row([id], |r| Ok((r.get(0)?, r.get(1)?))).optional()?;
+            let Some((refs, conversation_id)) = row else { continue };
+            if let Some(refs) = refs {
+                media.extend(serde_json::from_str::<Vec<PathBuf>>(&refs).unwrap_or_default());
+            }
+            conversation_ids.extend(conversation_id);
+            fts_stmt.execute([id])?;
+            tagging_stmt.execute([id])?;
+            removed += event_stmt.execute([id])?;
+        }
+        let conversation_ids: Vec<String> = conversation_ids.into_iter().collect();
+        Self::refresh_conversation_counts_with(tx, Some(&conversation_ids))?;
+        Self::settle_event_id_scheme(tx)?;
+        Ok((removed, media))
+    }
+
+    fn add_redaction_tx(tx: &rusqlite::Transaction, kind: RedactionKind, value: &str) -> AppResult<usize> {
+        Ok(tx.execute(
+            "INSERT OR IGNORE INTO redactions (kind, value, created_at) VALUES (?1, ?2, ?3)",
+            params![kind.as_str(), value, Utc::now().to_rfc3339()],
+        )?)
+    }
+
+    /// Permanently remove messages and remember them so later imports skip them too.
+    /// Returns the summary and the media files the removed messages referenced.
+    pub fn redact_events(&self, event_ids: &[String]) -> AppResult<(RedactionSummary, Vec<PathBuf>)> {
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        let mut summary = RedactionSummary::default();
+        let mut found = Vec::new();
+        for id in event_ids {
+            if let Some(anchor) = Self::entity_anchor(&tx, TagEntityType::Event, id)? {
+                summary.rules_added += Self::add_redaction_tx(&tx, RedactionKind::Event, &anchor)?;
+                found.push(id.clone());
+            }
+        }
+        let (removed, media) = Self::delete_events_tx(&tx, &found)?;
+        tx.commit()?;
+        summary.events_removed = removed;
+        Ok((summary, media))
+    }
+
+    /// Permanently remove every message sent by `username`, now and in later imports.
+    pub fn redact_sender(&self, username: &str) -> AppResult<(RedactionSummary, Vec<PathBuf>)> {
+        let username = username.trim();
+        if username.is_empty() {
+            return Err(AppError::Validation("Username cannot be empty".into()));
+        }
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        let mut summary = RedactionSummary {
+            rules_added: Self::add_redaction_tx(&tx, RedactionKind::Sender, username)?,
+            ..Default::default()
+        };
+        let ids = {
+            let mut stmt = tx.prepare("SELECT id FROM events WHERE sender = ?1")?;
+            let ids = stmt
+                .query_map([username], |r| r.get::<_, String>(0))?
+                .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+            ids
+        };
+        let (removed, media) = Self::delete_events_tx(&tx, &ids)?;
+        tx.commit()?;
+        summary.events_removed = removed;
+        Ok((summary, media))
+    }
+
+    pub fn get_redactions(&self) -> AppResult<Vec<Redaction>> {
+        let mut span = perf::query("get_redactions");
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare("SELECT id, kind, value, created_at FROM redactions ORDER BY id")?;
+        let rows = stmt
+            .query_map([], |row| {
+                let kind: String = row.get(1)?;
+                let created_at: String = row.get(3)?;
+                Ok((row.get::<_, i64>(0)?, kind, row.get::<_, String>(2)?, created_at))
+            })?
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+        span.set_rows(rows.trace_rows());
+        Ok(rows
+            .into_iter()
+            .filter_map(|(id, kind, value, created_at)| {
+                Some(Redaction {
+                    id,
+                    kind: RedactionKind::parse(&kind)?,
+                    value,
+                    created_at: DateTime::parse_from_rfc3339(&created_at)
+                        .map(|dt| dt.with_timezone(&Utc))
+                        .unwrap_or_else(|_| Utc::now()),
+                })
+            })
+            .collect())
+    }
+
+    /// Canonical form of a search's filters, so the same filters always dedupe to one row.
+    fn filters_key(filters: Option<&serde_json::Value>) -> String {
+        match filters {
+            None | Some(serde_json::Value::Null) => String::new(),
+            Some(v) if v.as_object().is_some_and(|o| o.is_empty()) => String::new(),
+            Some(v) => v.to_string(),
+        }
+    }
+
+    /// Remember a search in the recent list. Repeating a search bumps its use count and
+    /// recency instead of adding a row; only the newest [`RECENT_SEARCH_LIMIT`] are kept.
+    pub fn record_search(&self, query: &str, filters: Option<&serde_json::Value>) -> AppResult<()> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(());
+        }
+        let conn = self.write_conn()?;
+        conn.execute(
+            "INSERT INTO saved_searches (name, query, filters, last_used_at, use_count)
+             VALUES (NULL, ?1, ?2, ?3, 1)
+             ON CONFLICT(query, filters) WHERE name IS NULL
+             DO UPDATE SET use_count = use_count + 1, last_used_at = excluded.last_used_at",
+            params![query, Self::filters_key(filters), Utc::now().to_rfc3339()],
+        )?;
+        conn.execute(
+            "DELETE FROM saved_searches WHERE name IS NULL AND id NOT IN (
+                 SELECT id FROM saved_searches WHERE name IS NULL
+                 ORDER BY last_used_at DESC, id DESC LIMIT ?1
+             )",
+            [RECENT_SEARCH_LIMIT],
+        )?;
+        Ok(())
+    }
+
+    /// Save a search under `name`, replacing any saved search with that name.
+    pub fn save_search(&self, name: &str, query: &str, filters: Option<&serde_json::Value>) -> AppResult<SavedSearch> {
+        let (name, query) = (name.trim(), query.trim());
+        if name.is_empty() || query.is_empty() {
+            return Err(AppError::Validation("A saved search needs a name and a query".into()));
+        }
+        let id: i64 = self.write_conn()?.query_row(
+            "INSERT INTO saved_searches (name, query, filters, last_used_at, use_count)
+             VALUES (?1, ?2, ?3, ?4, 1)
+             ON CONFLICT(name) WHERE name IS NOT NULL
+             DO UPDATE SET query = excluded.query, filters = excluded.filters, last_used_at = excluded.last_used_at
+             RETURNING id",
+            params![name, query, Self::filters_key(filters), Utc::now().to_rfc3339()],
+            |r| r.get(0),
+        )?;
+        self.get_saved_searches()?
+            .into_iter()
+            .find(|s| s.id == id)
+            .ok_or_else(|| AppError::NotFound(format!("saved search {}", id)))
+    }
+
+    /// Named searches first, then recent ones; each group most recently used first.
+    pub fn get_saved_searches(&self) -> AppResult<Vec<SavedSearch>> {
+        let mut span = perf::query("get_saved_searches");
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, name, query, filters, last_used_at, use_count FROM saved_searches
+             ORDER BY name IS NULL, last_used_at DESC, id DESC",
+        )?;
+        let rows = stmt
+            .query_map([], |row| {
+                let filters: String = row.get(3)?;
+                let last_used_at: String = row.get(4)?;
+                Ok(SavedSearch {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    query: row.get(2)?,
+                    filters: serde_json::from_str(&filters).ok(),
+                    last_used_at: DateTime::parse_from_rfc3339(&last_used_at)
+                        .map(|dt| dt.with_timezone(&Utc))
+                        .unwrap_or_else(|_| Utc::now()),
+                    use_count: row.get(5)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+        span.set_rows(rows.trace_rows());
+        Ok(rows)
+    }
+
+    pub fn delete_saved_search(&self, id: i64) -> AppResult<()> {
+        let removed = self
+            .write_conn()?
+            .execute("DELETE FROM saved_searches WHERE id = ?1", [id])?;
+        if removed == 0 {
+            return Err(AppError::NotFound(format!("saved search {}", id)));
+        }
+        Ok(())
+    }
+
+    /// Copy saved and recent searches into this database, e.g. a fresh one before a reimport.
+    pub fn restore_saved_searches(&self, searches: &[SavedSearch]) -> AppResult<usize> {
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        let mut restored = 0;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR IGNORE INTO saved_searches (name, query, filters, last_used_at, use_count)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for s in searches {
+                restored += stmt.execute(params![
+                    s.name,
+                    s.query,
+                    Self::filters_key(s.filters.as_ref()),
+                    s.last_used_at.to_rfc3339(),
+                    s.use_count
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(restored)
+    }
+
+    /// Copy redaction rules into this database, e.g. a fresh one before a reimport.
+    pub fn restore_redactions(&self, redactions: &[Redaction]) -> AppResult<usize> {
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        let mut restored = 0;
+        {
+            let mut stmt =
+                tx.prepare("INSERT OR IGNORE INTO redactions (kind, value, created_at) VALUES (?1, ?2, ?3)")?;
+            for r in redactions {
+                restored += stmt.execute(params![r.kind.as_str(), r.value, r.created_at.to_rfc3339()])?;
+            }
+        }
+        tx.commit()?;
+        Ok(restored)
+    }
+
+    /// Everything the user added on top of their exports: tags, redaction rules, saved searches
+    /// and settings. Path settings are left out; they only make sense on this machine.
+    pub fn export_user_data(&self) -> AppResult<UserData> {
+        let settings = {
+            let conn = self.conn()?;
+            let mut stmt = conn.prepare("SELECT key, value FROM settings ORDER BY key")?;
+            let rows = stmt
+                .query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?)))?
+                .collect::<std::result::Result<BTreeMap<_, _>, rusqlite::Error>>()?;
+            rows
+        };
+        Ok(UserData {
+            version: USER_DATA_VERSION,
+            exported_at: Utc::now(),
+            tags: self.export_taggings()?,
+            redactions: self.get_redactions()?,
+            saved_searches: self.get_saved_searches()?,
+            settings: settings.into_iter().filter(|(k, _)| !is_path_setting(k)).collect(),
+        })
+    }
+
+    /// Merge user data from `export_user_data` into this database. New entries are always
+    /// added; entries that differ from an existing one (a tag's color, a named search, a
+    /// setting) follow `policy`. Imported redaction rules remove matching messages right away.
+    /// With `dry_run` everything is counted and then rolled back.
+    pub fn import_user_data(
+        &self,
+        data: &UserData,
+        policy: UserDataConflictPolicy,
+        dry_run: bool,
+    ) -> AppResult<UserDataImportSummary> {
+        use rusqlite::OptionalExtension;
+        if data.version > USER_DATA_VERSION {
+            return Err(AppError::Validation(format!(
+                "This user data file is version {}; this version of the app reads up to {}",
+                data.version, USER_DATA_VERSION
+            )));
+        }
+        let overwrite = policy == UserDataConflictPolicy::Overwrite;
+        let mut summary = UserDataImportSummary {
+            dry_run,
+            ..Default::default()
+        };
+        let now = Utc::now().to_rfc3339();
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        {
+            let mut find_tag = tx.prepare("SELECT color FROM tags WHERE name = ?1")?;
+            let mut insert_tag = tx.prepare("INSERT INTO tags (name, color, created_at) VALUES (?1, ?2, ?3)")?;
+            let mut update_tag = tx.prepare("UPDATE tags SET color = ?2 WHERE name = ?1")?;
+            let mut insert_tagging = tx.prepare(
+                "INSERT OR IGNORE INTO taggings (tag_id, entity_type, entity_id, anchor, created_at)
+                 SELECT id, ?2, ?3, ?4, ?5 FROM tags WHERE name = ?1",
+            )?;
+            let mut seen_tags = HashSet::new();
+            for item in &data.tags {
+                // A snapshot repeats the tag once per tagging
+                if seen_tags.insert(item.tag_name.to_lowercase()) {
+                    let existing: Option<Option<String>> =
+                        find_tag.query_row([&item.tag_name], |r| r.get(0)).optional()?;
+                    match existing {
+                        None => {
+                            insert_tag.execute(params![item.tag_name, item.tag_color, now])?;
+                            summary.tags_added += 1;
+                        }
+                        Some(color) if color != item.tag_color && overwrite => {
+                            update_tag.execute(params![item.tag_name, item.tag_color])?;
+                            summary.tags_updated += 1;
+                        }
+                        Some(color) if color != item.tag_color => summary.conflicts_kept += 1,
+                        Some(_) => {}
+                    }
+                }
+                let Some((entity_type, anchor)) = &item.entity else {
+                    continue;
+                };
+                match Self::resolve_anchor(&tx, *entity_type, anchor)? {
+                    Some(entity_id) => {
+                        let params = params![item.tag_name, entity_type.as_str(), entity_id, anchor, now];
+                        summary.taggings_added += insert_tagging.execute(params)?;
+                    }
+                    None => summary.taggings_unresolved += 1,
+                }
+            }
+
+            let mut insert_redaction =
+                tx.prepare("INSERT OR IGNORE INTO redactions (kind, value, created_at) VALUES (?1, ?2, ?3)")?;
+            let mut sender_events = tx.prepare("SELECT id FROM events WHERE sender = ?1")?;
+            let mut redacted = Vec::new();
+            for r in &data.redactions {
+                if insert_redaction.execute(params![r.kind.as_str(), r.value, r.created_at.to_rfc3339()])? == 0 {
+                    continue;
+                }
+                summary.redactions_added += 1;
+                match r.kind {
+                    RedactionKind::Sender => {
+                        for id in sender_events.query_map([&r.value], |row| row.get::<_, String>(0))? {
+                            redacted.push(id?);
+                        }
+                    }
+                    RedactionKind::Event => {
+                        redacted.extend(Self::resolve_anchor(&tx, TagEntityType::Event, &r.value)?);
+                    }
+                }
+            }
+            summary.events_redacted = Self::delete_events_tx(&tx, &redacted)?.0;
+
+            let mut find_named = tx.prepare("SELECT query, filters FROM saved_searches WHERE name = ?1")?;
+            let mut update_named = tx.prepare("UPDATE saved_searches SET query = ?2, filters = ?3 WHERE name = ?1")?;
+            let mut insert_search = tx.prepare(
+                "INSERT OR IGNORE INTO saved_searches (name, query, filters, last_used_at, use_count)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for s in &data.saved_searches {
+                let filters = Self::filters_key(s.filters.as_ref());
+                if let Some(name) = &s.name {
+                    let existing: Option<(String, String)> = find_named
+                        .query_row([name], |r| Ok((r.get(0)?, r.get(1)?)))
+                        .optional()?;
+                    if let Some((query, existing_filters)) = existing {
+                        if query != s.query || existing_filters != filters {
+                            if overwrite {
+                                update_named.execute(params![name, s.query, filters])?;
+                                summary.saved_searches_updated += 1;
+                            } else {
+                                summary.conflicts_kept += 1;
+                            }
+                        }
+                        continue;
+                    }
+                }
+                summary.saved_searches_added += insert_search.execute(params![
+                    s.name,
+                    s.query,
+                    filters,
+                    s.last_used_at.to_rfc3339(),
+                    s.use_count
+                ])?;
+            }
+            tx.execute(
+                "DELETE FROM saved_searches WHERE name IS NULL AND id NOT IN (
+                     SELECT id FROM saved_searches WHERE name IS NULL
+                     ORDER BY last_used_at DESC, id DESC LIMIT ?1
+                 )",
+                [RECENT_SEARCH_LIMIT],
+            )?;
+
+            let mut find_setting = tx.prepare("SELECT value FROM settings WHERE key = ?1")?;
+            let mut set_setting = tx.prepare("INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)")?;
+            for (key, value) in data.settings.iter().filter(|(k, _)| !is_path_setting(k)) {
+                let existing: Option<String> = find_setting.query_row([key], |r| r.get(0)).optional()?;
+                match existing {
+                    None => {
+                        set_setting.execute(params![key, value])?;
+                        summary.settings_added += 1;
+                    }
+                    Some(current) if current != *value && overwrite => {
+                        set_setting.execute(params![key, value])?;
+                        summary.settings_updated += 1;
+                    }
+                    Some(current) if current != *value => summary.conflicts_kept += 1,
+                    Some(_) => {}
+                }
+            }
+        }
+        if !dry_run {
+            tx.commit()?;
+        }
+        Ok(summary)
+    }
+
+    /// Drop parsed events matching a redaction rule before they are written.
+    /// Returns the remaining events and how many were dropped.
+    pub fn filter_redacted(&self, events: Vec<Event>) -> AppResult<(Vec<Event>, usize)> {
+        let redactions = self.get_redactions()?;
+        if redactions.is_empty() {
+            return Ok((events, 0));
+        }
+        let mut senders = std::collections::HashSet::new();
+        let mut anchors = std::collections::HashSet::new();
+        for r in redactions {
+            match r.kind {
+                RedactionKind::Sender => senders.insert(r.value),
+                RedactionKind::Event => anchors.insert(r.value),
+            };
+        }
+        let before = events.len();
+        let kept: Vec<Event> = events
+            .into_iter()
+            .filter(|e| {
+                if senders.contains(&e.sender) {
+                    return false;
+                }
+                let anchor = Self::event_anchor(
+                    e.conversation_id.as_deref(),
+                    &e.timestamp.to_rfc3339(),
+                    &e.sender,
+                    e.content.as_deref().unwrap_or(""),
+                );
+                !anchors.contains(&anchor.to_string())
+            })
+            .collect();
+        let dropped = before - kept.len();
+        Ok((kept, dropped))
+    }
+
+    /// Snapshot the state an ingestion of `export_id` is about to modify.
+    pub fn begin_ingestion_run(&self, export_id: &str) -> AppResult<IngestionRun> {
+        let previous_export = self.get_exports()?.into_iter().find(|e| e.id == export_id);
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, display_name, participants, last_event_at, media_count, media_bytes, missing_media_count, is_group
+             FROM conversations",
+        )?;
+        let previous_conversations = stmt
+            .query_map([], |row| {
+                let participants: Option<String> = row.get(2)?;
+                let last_event_at: Option<String> = row.get(3)?;
+                Ok(Conversation {
+                    id: row.get(0)?,
+                    display_name: row.get(1)?,
+                    participants: participants
+                        .and_then(|p| serde_json::from_str(&p).ok())
+                        .unwrap_or_default(),
+                    last_event_at: last_event_at
+                        .and_then(|s| DateTime::parse_from_rfc3339(&s).ok().map(|dt| dt.with_timezone(&Utc))),
+                    message_count: 0,
+                    has_media: false,
+                    media_count: row.get(4)?,
+                    media_bytes: row.get(5)?,
+                    missing_media_count: row.get(6)?,
+                    avatar_path: None,
+                    avatar_color: None,
+                    is_group: row.get(7)?,
+                    listed_members: 0,
+                    anomaly_flags: Vec::new(),
+                    saved_count: 0,
+                    shared_location_count: 0,
+                    completeness: None,
+                })
+            })?
+            .map(|r| r.map(|c| (c.id.clone(), c)))
+            .collect::<std::result::Result<HashMap<_, _>, rusqlite::Error>>()?;
+
+        Ok(IngestionRun {
+            export_id: export_id.to_string(),
+            started_at: Utc::now(),
+            previous_export,
+            previous_conversations,
+            created_conversations: Vec::new(),
+            merged_conversations: Vec::new(),
+            event_ids: Vec::new(),
+            memory_ids: Vec::new(),
+            location_ids: Vec::new(),
+            file_issues: Vec::new(),
+            timestamp_format: None,
+            _guard: IngestionGuard::new(&self.active_ingestions),
         })
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::NamedTempFile;
+    /// Undo everything a failed run wrote, in one transaction. Rows that predate the run are
+    /// kept, and merged conversations and a pre-existing export row are put back as they were.
+    pub fn rollback_ingestion_run(&self, run: &IngestionRun) -> AppResult<IngestionCleanup> {
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        let mut cleanup = IngestionCleanup::default();
+        {
+            let mut fts_stmt = tx.prepare("DELETE FROM events_fts WHERE event_id = ?1")?;
+            let mut event_stmt = tx.prepare("DELETE FROM events WHERE id = ?1 AND export_id = ?2")?;
+            for id in &run.event_ids {
+                fts_stmt.execute([id])?;
+                cleanup.events_removed += event_stmt.execute(params![id, run.export_id])?;
+            }
+
+            // A search index build the run started would otherwise resume past re-imported rows
+            tx.execute("DELETE FROM fts_population WHERE export_id = ?1", [&run.export_id])?;
+
+            let mut memory_stmt = tx.prepare("DELETE FROM memories WHERE id = ?1 AND export_id = ?2")?;
+            for id in &run.memory_ids {
+                cleanup.memories_removed += memory_stmt.execute(params![id, run.export_id])?;
+            }
+
+            let mut location_stmt = tx.prepare("DELETE FROM locations WHERE id = ?1")?;
+            for id in &run.location_ids {
+                cleanup.locations_removed += location_stmt.execute([id])?;
+            }
+
+            // A "created" conversation may have gained events from another export in the meantime
+            let mut convo_stmt = tx.prepare(
+                "DELETE FROM conversations WHERE id = ?1
+                 AND NOT EXISTS (SELECT 1 FROM events WHERE conversation_id = ?1)",
+            )?;
+            for id in &run.created_conversations {
+                cleanup.conversations_removed += convo_stmt.execute([id])?;
+            }
+
+            let mut restore_stmt = tx.prepare(CONVERSATION_UPSERT)?;
+            for id in &run.merged_conversations {
+                if let Some(convo) = run.previous_conversations.get(id) {
+                    restore_stmt.execute(params![
+                        convo.id,
+                        convo.display_name,
+                        serde_json::to_string(&convo.participants).unwrap_or_else(|_| "[]".to_string()),
+                        convo.last_event_at.map(|d| d.to_rfc3339()),
+                        convo.media_count,
+                        convo.media_bytes,
+                        convo.missing_media_count,
+                        convo.is_group
+                    ])?;
+                    cleanup.conversations_restored += 1;
+                }
+            }
+        }
+        Self::refresh_conversation_counts_with(&tx, None)?;
+        Self::settle_event_id_scheme(&tx)?;
+
+        match &run.previous_export {
+            Some(export) => write_export(&tx, export)?,
+            None => {
+                cleanup.export_removed = tx.execute(
+                    "DELETE FROM exports WHERE id = ?1
+                     AND NOT EXISTS (SELECT 1 FROM events WHERE export_id = ?1)
+                     AND NOT EXISTS (SELECT 1 FROM memories WHERE export_id = ?1)",
+                    [&run.export_id],
+                )? > 0;
+            }
+        }
+        tx.commit()?;
+        Ok(cleanup)
+    }
+
+    /// Replace the recorded source fingerprints of an export.
+    pub fn replace_export_artifacts(&self, export_id: &str, artifacts: &[ExportArtifact]) -> AppResult<()> {
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM export_artifacts WHERE export_id = ?1", [export_id])?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO export_artifacts (export_id, path, size, modified_at, hash, file_count)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for artifact in artifacts {
+                stmt.execute(params![
+                    export_id,
+                    artifact.path,
+                    artifact.size as i64,
+                    artifact.modified_at.map(|d| d.to_rfc3339()),
+                    artifact.hash,
+                    artifact.file_count
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn get_export_artifacts(&self, export_id: &str) -> AppResult<Vec<ExportArtifact>> {
+        let mut span = perf::query("get_export_artifacts");
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT path, size, modified_at, hash, file_count FROM export_artifacts
+             WHERE export_id = ?1 ORDER BY path",
+        )?;
+        let rows = stmt
+            .query_map([export_id], |row| {
+                let modified_at: Option<String> = row.get(2)?;
+                Ok(ExportArtifact {
+                    path: row.get(0)?,
+                    size: row.get::<_, i64>(1)? as u64,
+                    modified_at: modified_at
+                        .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+                        .map(|dt| dt.with_timezone(&Utc)),
+                    hash: row.get(3)?,
+                    file_count: row.get(4)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+        span.set_rows(rows.trace_rows());
+        Ok(rows)
+    }
+
+    /// Replace the anomalies recorded for an export's conversations.
+    pub fn replace_validation_issues(&self, export_id: &str, anomalies: &[ConversationAnomaly]) -> AppResult<()> {
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM validation_issues WHERE export_id = ?1", [export_id])?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO validation_issues (export_id, conversation_id, kind, affected, total, detail, detected_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            let detected_at = Utc::now().to_rfc3339();
+            for anomaly in anomalies {
+                stmt.execute(params![
+                    export_id,
+                    anomaly.conversation_id,
+                    anomaly.kind.as_str(),
+                    anomaly.affected,
+                    anomaly.total,
+                    anomaly.detail,
+                    detected_at
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Recorded anomalies, optionally for one conversation only.
+    pub fn get_validation_issues(&self, conversation_id: Option<&str>) -> AppResult<Vec<ConversationAnomaly>> {
+        let mut span = perf::query("get_validation_issues");
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT conversation_id, kind, affected, total, detail FROM validation_issues
+             WHERE (?1 IS NULL OR conversation_id = ?1)
+             ORDER BY conversation_id, id",
+        )?;
+        let rows = stmt
+            .query_map([conversation_id], |row| {
+                let kind: String = row.get(1)?;
+                Ok((kind, row.get::<_, String>(0)?, row.get(2)?, row.get(3)?, row.get(4)?))
+            })?
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+        span.set_rows(rows.trace_rows());
+        Ok(rows
+            .into_iter()
+            .filter_map(|(kind, conversation_id, affected, total, detail)| {
+                Some(ConversationAnomaly {
+                    conversation_id,
+                    kind: AnomalyKind::parse(&kind)?,
+                    affected,
+                    total,
+                    detail,
+                })
+            })
+            .collect())
+    }
+
+    /// Start recording a bulk download of `total` memories matching `filter`.
+    pub fn create_download_job(&self, filter: &MemoryFilter, total: usize) -> AppResult<DownloadJob> {
+        let now = Utc::now().to_rfc3339();
+        let id: i64 = self.write_conn()?.query_row(
+            "INSERT INTO download_jobs (filters, created_at, updated_at, state, total)
+             VALUES (?1, ?2, ?2, ?3, ?4) RETURNING id",
+            params![
+                serde_json::to_string(filter)?,
+                now,
+                DownloadJobState::Running.as_str(),
+                total as i64
+            ],
+            |r| r.get(0),
+        )?;
+        self.get_download_job(id)
+    }
+
+    /// Save a job's state and counts.
+    pub fn update_download_job(&self, job: &DownloadJob) -> AppResult<()> {
+        self.write_conn()?.execute(
+            "UPDATE download_jobs SET state = ?2, total = ?3, completed = ?4, failed = ?5, skipped = ?6, updated_at = ?7
+             WHERE id = ?1",
+            params![
+                job.id,
+                job.state.as_str(),
+                job.total,
+                job.completed,
+                job.failed,
+                job.skipped,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Failed memory downloads grouped by reason, most common first. Failures recorded before
+    /// reasons were kept are grouped as "Unknown".
+    pub fn get_download_failure_summary(&self) -> AppResult<Vec<DownloadFailureGroup>> {
+        let mut span = perf::query("get_download_failure_summary");
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT COALESCE(failure_reason, 'Unknown') AS reason, COUNT(*) AS count
+             FROM memories WHERE download_status = ?1
+             GROUP BY reason ORDER BY count DESC, reason",
+        )?;
+        let groups = stmt
+            .query_map([DownloadStatus::Failed.as_str()], |row| {
+                Ok(DownloadFailureGroup {
+                    reason: row.get(0)?,
+                    count: row.get(1)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+        span.set_rows(groups.trace_rows());
+        Ok(groups)
+    }
+
+    /// Put memories left Downloading by a previous session back to Pending. Returns how many.
+    pub fn reset_stalled_downloads(&self) -> AppResult<usize> {
+        Ok(self.write_conn()?.execute(
+            "UPDATE memories SET download_status = ?1 WHERE download_status = ?2",
+            params![DownloadStatus::Pending.as_str(), DownloadStatus::Downloading.as_str()],
+        )?)
+    }
+
+    /// Mark jobs left Running by a previous session as Interrupted, and return them.
+    pub fn interrupt_running_download_jobs(&self) -> AppResult<Vec<DownloadJob>> {
+        let ids = {
+            let conn = self.write_conn()?;
+            let mut stmt = conn.prepare("UPDATE download_jobs SET state = ?1 WHERE state = ?2 RETURNING id")?;
+            let ids = stmt
+                .query_map(
+                    params![
+                        DownloadJobState::Interrupted.as_str(),
+                        DownloadJobState::Running.as_str()
+                    ],
+                    |r| r.get::<_, i64>(0),
+                )?
+                .collect::<Result<Vec<_>, _>>()?;
+            ids
+        };
+        Ok(self
+            .get_download_jobs()?
+            .into_iter()
+            .filter(|j| ids.contains(&j.id))
+            .collect())
+    }
+
+    pub fn get_download_job(&self, id: i64) -> AppResult<DownloadJob> {
+        let _span = perf::query("get_download_job");
+        self.get_download_jobs()?
+            .into_iter()
+            .find(|j| j.id == id)
+            .ok_or_else(|| AppError::NotFound(format!("download job {}", id)))
+    }
+
+    /// Download job history, newest first.
+    pub fn get_download_jobs(&self) -> AppResult<Vec<DownloadJob>> {
+        let mut span = perf::query("get_download_jobs");
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, filters, created_at, updated_at, state, total, completed, failed, skipped FROM download_jobs
+             ORDER BY id DESC",
+        )?;
+        let parse_time = |text: String| {
+            DateTime::parse_from_rfc3339(&text)
+                .map(|dt| dt.with_timezone(&Utc))
+                .unwrap_or_else(|_| Utc::now())
+        };
+        let rows = stmt
+            .query_map([], |row| {
+                let filters: String = row.get(1)?;
+                let state: String = row.get(4)?;
+                Ok(DownloadJob {
+                    id: row.get(0)?,
+                    filter: serde_json::from_str(&filters).unwrap_or_default(),
+                    created_at: parse_time(row.get(2)?),
+                    updated_at: parse_time(row.get(3)?),
+                    state: DownloadJobState::parse(&state).unwrap_or(DownloadJobState::Interrupted),
+                    total: row.get(5)?,
+                    completed: row.get(6)?,
+                    failed: row.get(7)?,
+                    skipped: row.get(8)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        span.set_rows(rows.trace_rows());
+        Ok(rows)
+    }
+
+    /// Append a finished run to the ingestion history.
+    pub fn record_ingestion_run(
+        &self,
+        run: &IngestionRun,
+        status: IngestionRunStatus,
+        error: Option<&str>,
+        cleanup: Option<&IngestionCleanup>,
+    ) -> AppResult<()> {
+        let status_str = match status {
+            IngestionRunStatus::Completed => "Completed",
+            IngestionRunStatus::Failed => "Failed",
+            IngestionRunStatus::Cancelled => "Cancelled",
+        };
+        let conversations = run.created_conversations.len() + run.merged_conversations.len();
+        self.write_conn()?.execute(
+            "INSERT INTO ingestion_runs (export_id, started_at, finished_at, status, error, conversations, events, memories, cleanup, file_issues, timestamp_format)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                run.export_id,
+                run.started_at.to_rfc3339(),
+                Utc::now().to_rfc3339(),
+                status_str,
+                error,
+                conversations as i64,
+                run.event_ids.len() as i64,
+                run.memory_ids.len() as i64,
+                cleanup.and_then(|c| serde_json::to_string(c).ok()),
+                (!run.file_issues.is_empty()).then(|| serde_json::to_string(&run.file_issues).ok()).flatten(),
+                run.timestamp_format.and_then(|t| serde_json::to_string(&t).ok()),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Most recent ingestion runs first.
+    pub fn get_ingestion_history(&self, limit: i32) -> AppResult<Vec<IngestionRunRecord>> {
+        let mut span = perf::query("get_ingestion_history");
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, export_id, started_at, finished_at, status, error, conversations, events, memories, cleanup, file_issues, timestamp_format
+             FROM ingestion_runs ORDER BY id DESC LIMIT ?1",
+        )?;
+        let parse_time = |s: String| {
+            DateTime::parse_from_rfc3339(&s)
+                .map(|dt| dt.with_timezone(&Utc))
+                .unwrap_or_default()
+        };
+        let rows = stmt
+            .query_map([limit], |row| {
+                let status: String = row.get(4)?;
+                let cleanup: Option<String> = row.get(9)?;
+                let file_issues: Option<String> = row.get(10)?;
+                let timestamp_format: Option<String> = row.get(11)?;
+                Ok(IngestionRunRecord {
+                    id: row.get(0)?,
+                    export_id: row.get(1)?,
+                    started_at: parse_time(row.get(2)?),
+                    finished_at: parse_time(row.get(3)?),
+                    status: match status.as_str() {
+                        "Completed" => IngestionRunStatus::Completed,
+                        "Cancelled" => IngestionRunStatus::Cancelled,
+                        _ => IngestionRunStatus::Failed,
+                    },
+                    error: row.get(5)?,
+                    conversations: row.get(6)?,
+                    events: row.get(7)?,
+                    memories: row.get(8)?,
+                    cleanup: cleanup.and_then(|c| serde_json::from_str(&c).ok()),
+                    file_issues: file_issues
+                        .and_then(|f| serde_json::from_str(&f).ok())
+                        .unwrap_or_default(),
+                    timestamp_format: timestamp_format.and_then(|t| serde_json::from_str(&t).ok()),
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+        span.set_rows(rows.trace_rows());
+        Ok(rows)
+    }
+
+    pub fn get_setting(&self, key: &str) -> AppResult<Option<String>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare("SELECT value FROM settings WHERE key = ?1")?;
+        let result = stmt.query_row([key], |row| row.get(0)).ok();
+        Ok(result)
+    }
+
+    /// A path setting, falling back to the legacy `storage_path` when it is unset.
+    pub fn get_path_setting(&self, key: &str) -> AppResult<Option<(PathBuf, PathSource)>> {
+        if let Some(path) = self.get_setting(key)?.filter(|p| !p.is_empty()) {
+            return Ok(Some((PathBuf::from(path), PathSource::Configured)));
+        }
+        Ok(self
+            .get_setting("storage_path")?
+            .filter(|p| !p.is_empty())
+            .map(|p| (PathBuf::from(p), PathSource::LegacyStoragePath)))
+    }
+
+    /// Root directory memories are downloaded into, if one is configured.
+    pub fn downloads_root(&self) -> AppResult<Option<PathBuf>> {
+        Ok(self.get_path_setting("downloads_path")?.map(|(p, _)| p))
+    }
+
+    /// Saved network settings, or the defaults when none are stored or they can't be read.
+    pub fn get_network_settings(&self) -> AppResult<NetworkSettings> {
+        Ok(match self.get_setting("network_settings")? {
+            Some(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                log::warn!("Ignoring unreadable network settings: {}", e);
+                NetworkSettings::default()
+            }),
+            None => NetworkSettings::default(),
+        })
+    }
+
+    pub fn set_network_settings(&self, settings: &NetworkSettings) -> AppResult<()> {
+        self.set_setting("network_settings", &serde_json::to_string(settings)?)
+    }
+
+    pub fn get_detection_settings(&self) -> AppResult<DetectionSettings> {
+        Ok(match self.get_setting("export_detection")? {
+            Some(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                log::warn!("Ignoring unreadable export detection settings: {}", e);
+                DetectionSettings::default()
+            }),
+            None => DetectionSettings::default(),
+        })
+    }
+
+    pub fn set_detection_settings(&self, settings: &DetectionSettings) -> AppResult<()> {
+        self.set_setting("export_detection", &serde_json::to_string(settings)?)
+    }
+
+    pub fn get_media_linking_settings(&self) -> AppResult<MediaLinkingSettings> {
+        Ok(match self.get_setting("media_linking")? {
+            Some(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                log::warn!("Ignoring unreadable media linking settings: {}", e);
+                MediaLinkingSettings::default()
+            }),
+            None => MediaLinkingSettings::default(),
+        })
+    }
+
+    pub fn set_media_linking_settings(&self, settings: &MediaLinkingSettings) -> AppResult<()> {
+        self.set_setting("media_linking", &serde_json::to_string(settings)?)
+    }
+
+    pub fn get_performance_settings(&self) -> AppResult<PerformanceSettings> {
+        Ok(match self.get_setting("performance_tracing")? {
+            Some(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                log::warn!("Ignoring unreadable performance settings: {}", e);
+                PerformanceSettings::default()
+            }),
+            None => PerformanceSettings::default(),
+        })
+    }
+
+    pub fn set_performance_settings(&self, settings: &PerformanceSettings) -> AppResult<()> {
+        self.set_setting("performance_tracing", &serde_json::to_string(settings)?)
+    }
+
+    /// Minutes to add to UTC for local-time views such as the activity heatmap; 0 when unset.
+    pub fn get_timezone_offset(&self) -> AppResult<i32> {
+        Ok(self
+            .get_setting("timezone_offset_minutes")?
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0))
+    }
+
+    pub fn set_timezone_offset(&self, minutes: i32) -> AppResult<()> {
+        if minutes.abs() > MAX_TIMEZONE_OFFSET_MINUTES {
+            return Err(AppError::Validation(format!(
+                "Timezone offset {} minutes is out of range",
+                minutes
+            )));
+        }
+        self.set_setting("timezone_offset_minutes", &minutes.to_string())
+    }
+
+    pub fn delete_setting(&self, key: &str) -> AppResult<()> {
+        self.write_conn()?
+            .execute("DELETE FROM settings WHERE key = ?1", [key])?;
+        Ok(())
+    }
+
+    /// The export the views are limited to, or None to show everything. An export that has
+    /// since gone counts as none.
+    pub fn get_active_export(&self) -> AppResult<Option<String>> {
+        let Some(export_id) = self.get_setting("active_export")?.filter(|id| !id.is_empty()) else {
+            return Ok(None);
+        };
+        Ok(self
+            .get_exports()?
+            .iter()
+            .any(|e| e.id == export_id)
+            .then_some(export_id))
+    }
+
+    pub fn set_active_export(&self, export_id: Option<&str>) -> AppResult<()> {
+        if let Some(id) = export_id {
+            if !self.get_exports()?.iter().any(|e| e.id == id) {
+                return Err(AppError::NotFound(format!("export {}", id)));
+            }
+        }
+        self.set_setting("active_export", export_id.unwrap_or(""))
+    }
+
+    /// The account owner found by the last import, if any.
+    pub fn get_owner_profile(&self) -> AppResult<Option<OwnerProfile>> {
+        let _span = perf::query("get_owner_profile");
+        let Some(username) = self.get_setting("owner_username")?.filter(|u| !u.is_empty()) else {
+            return Ok(None);
+        };
+        Ok(Some(OwnerProfile {
+            username,
+            display_name: self.get_setting("owner_display_name")?.filter(|n| !n.is_empty()),
+        }))
+    }
+
+    pub fn set_owner_profile(&self, owner: &OwnerProfile) -> AppResult<()> {
+        self.set_setting("owner_username", &owner.username)?;
+        self.set_setting("owner_display_name", owner.display_name.as_deref().unwrap_or(""))
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) -> AppResult<()> {
+        self.write_conn()?.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    /// One page of the unified media stream, narrowed by `filter` when given; `total_count`
+    /// counts the filtered stream.
+    pub fn get_unified_media_stream(
+        &self,
+        limit: i32,
+        offset: i32,
+        direction: MediaDirection,
+        filter: Option<&MediaFilter>,
+    ) -> AppResult<PaginatedMedia> {
+        let mut span = perf::query("get_unified_media_stream");
+        let unfiltered = MediaFilter::default();
+        let page = self.media_stream_page(filter.unwrap_or(&unfiltered), direction, limit, offset)?;
+        span.set_rows(page.trace_rows());
+        Ok(page)
+    }
+
+    /// One conversation's slice of the media stream.
+    pub fn get_conversation_media(
+        &self,
+        conversation_id: &str,
+        limit: i32,
+        offset: i32,
+        direction: MediaDirection,
+    ) -> AppResult<PaginatedMedia> {
+        let mut span = perf::query("get_conversation_media");
+        let filter = MediaFilter {
+            conversation_id: Some(conversation_id.to_string()),
+            ..Default::default()
+        };
+        let page = self.media_stream_page(&filter, direction, limit, offset)?;
+        span.set_rows(page.trace_rows());
+        Ok(page)
+    }
+
+    fn media_stream_page(
+        &self,
+        filter: &MediaFilter,
+        direction: MediaDirection,
+        limit: i32,
+        offset: i32,
+    ) -> AppResult<PaginatedMedia> {
+        let limit = limit.clamp(1, 1000);
+        let offset = offset.max(0);
+        let (total_count, mut entries) = self.read_retrying("media_stream_page", |conn| {
+            let after = filter.after.map(|d| d.to_rfc3339());
+            let before = filter.before.map(|d| d.to_rfc3339());
+            let filter_params = params![
+                filter.conversation_id,
+                direction.as_str(),
+                filter.source,
+                filter.media_type,
+                after,
+                before
+            ];
+
+            // 1. Get total count for pagination info
+            let total_count: i32 = conn.query_row(
+                &format!("SELECT COUNT(*) FROM ({}) {}", MEDIA_STREAM_SOURCE, MEDIA_STREAM_WHERE),
+                filter_params,
+                |r| r.get(0),
+            )?;
+
+            // 2. One page of the combined stream, with sizes the cache already knows
+            let mut stmt = conn.prepare(&format!(
+                "SELECT id, s.path, media_type, timestamp, source, direction, mi.size_bytes, conversation_id, media_index
+                 FROM ({}) s LEFT JOIN media_info mi ON mi.path = s.path {}
+                 ORDER BY {}
+                 LIMIT ?7 OFFSET ?8",
+                MEDIA_STREAM_SOURCE, MEDIA_STREAM_WHERE, MEDIA_STREAM_ORDER
+            ))?;
+
+            let entries = stmt
+                .query_map(
+                    params![
+                        filter.conversation_id,
+                        direction.as_str(),
+                        filter.source,
+                        filter.media_type,
+                        after,
+                        before,
+                        limit,
+                        offset
+                    ],
+                    |row| {
+                        let timestamp_str: String = row.get(3)?;
+                        let timestamp = DateTime::parse_from_rfc3339(&timestamp_str)
+                            .map(|dt| dt.with_timezone(&Utc))
+                            .unwrap_or_else(|_| Utc::now());
+                        let direction: String = row.get(5)?;
+
+                        Ok(MediaStreamEntry {
+                            id: row.get(0)?,
+                            path: PathBuf::from(row.get::<_, String>(1)?),
+                            media_type: row.get(2)?,
+                            timestamp,
+                            source: row.get(4)?,
+                            direction: if direction == "sent" {
+                                MediaDirection::Sent
+                            } else {
+                                MediaDirection::Received
+                            },
+                            size_bytes: row.get::<_, Option<i64>>(6)?.map(|s| s as u64),
+                            conversation_id: row.get(7)?,
+                            media_index: row.get(8)?,
+                        })
+                    },
+                )?
+                .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+            Ok((total_count, entries))
+        })?;
+
+        self.fill_media_sizes(&mut entries, self.media_stat_budget(), file_size);
+
+        Ok(PaginatedMedia {
+            items: entries,
+            total_count,
+            has_more: (offset + limit) < total_count,
+        })
+    }
+
+    fn media_stat_budget(&self) -> Duration {
+        self.get_setting(MEDIA_STAT_BUDGET_SETTING)
+            .ok()
+            .flatten()
+            .and_then(|ms| ms.parse().ok())
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_MEDIA_STAT_BUDGET)
+    }
+
+    /// Read the sizes of entries the cache didn't have until `budget` runs out, and cache them.
+    /// Entries left when time is up keep `size_bytes: None`.
+    fn fill_media_sizes<F>(&self, entries: &mut [MediaStreamEntry], budget: Duration, stat: F)
+    where
+        F: Fn(&Path) -> Option<u64>,
+    {
+        let started = Instant::now();
+        let mut found = Vec::new();
+        for entry in entries.iter_mut().filter(|e| e.size_bytes.is_none()) {
+            if started.elapsed() >= budget {
+                break;
+            }
+            if let Some(size) = stat(&entry.path) {
+                entry.size_bytes = Some(size);
+                found.push((entry.path.clone(), size));
+            }
+        }
+        if found.is_empty() || self.read_only {
+            return;
+        }
+        if let Err(e) = self.cache_media_sizes(&found) {
+            log::warn!("Could not cache {} media sizes: {}", found.len(), e);
+        }
+    }
+
+    fn cache_media_sizes(&self, sizes: &[(PathBuf, u64)]) -> AppResult<()> {
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare("INSERT OR REPLACE INTO media_info (path, size_bytes) VALUES (?1, ?2)")?;
+            for (path, size) in sizes {
+                stmt.execute(params![path.to_string_lossy(), *size as i64])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Size of one media file, read fresh and cached for later pages.
+    pub fn get_media_info(&self, path: &Path) -> AppResult<MediaInfo> {
+        let _span = perf::query("get_media_info");
+        let size_bytes = file_size(path).ok_or_else(|| AppError::NotFound(path.display().to_string()))?;
+        if !self.read_only {
+            self.cache_media_sizes(&[(path.to_path_buf(), size_bytes)])?;
+        }
+        Ok(MediaInfo {
+            path: path.to_path_buf(),
+            size_bytes,
+        })
+    }
+
+    /// Media counts per month (`YYYY-MM`, newest first) over the unified media stream as
+    /// `direction` and `filter` narrow it, each with the stream offset of the month's first item.
+    pub fn get_media_timeline(
+        &self,
+        direction: MediaDirection,
+        filter: Option<&MediaFilter>,
+    ) -> AppResult<Vec<MediaTimelineMonth>> {
+        let mut span = perf::query("get_media_timeline");
+        let unfiltered = MediaFilter::default();
+        let filter = filter.unwrap_or(&unfiltered);
+        let conn = self.conn()?;
+        // Months are listed in stream order, so the running total is each month's offset
+        let mut stmt = conn.prepare(&format!(
+            "SELECT substr(timestamp, 1, 7) AS month, COUNT(*) FROM ({}) {}
+             GROUP BY month ORDER BY month DESC",
+            MEDIA_STREAM_SOURCE, MEDIA_STREAM_WHERE
+        ))?;
+        let rows = stmt
+            .query_map(
+                params![
+                    filter.conversation_id,
+                    direction.as_str(),
+                    filter.source,
+                    filter.media_type,
+                    filter.after.map(|d| d.to_rfc3339()),
+                    filter.before.map(|d| d.to_rfc3339())
+                ],
+                |r| Ok((r.get::<_, String>(0)?, r.get::<_, i32>(1)?)),
+            )?
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+        span.set_rows(rows.trace_rows());
+        let mut offset = 0;
+        Ok(rows
+            .into_iter()
+            .map(|(month, count)| {
+                let entry = MediaTimelineMonth { month, count, offset };
+                offset += count;
+                entry
+            })
+            .collect())
+    }
+
+    /// Message counts per day, week or month, oldest first, for one conversation or all of them
+    /// (limited to `export_id` when given). Empty buckets are left out, as are messages whose
+    /// timestamp couldn't be read.
+    pub fn get_activity_timeline(
+        &self,
+        conversation_id: Option<&str>,
+        bucket: TimelineBucket,
+        export_id: Option<&str>,
+    ) -> AppResult<Vec<ActivityBucket>> {
+        let bucket_start = match bucket {
+            TimelineBucket::Day => "substr(timestamp, 1, 10)",
+            TimelineBucket::Week => "date(timestamp, 'weekday 0', '-6 days')",
+            TimelineBucket::Month => "substr(timestamp, 1, 7) || '-01'",
+        };
+        self.read_retrying("get_activity_timeline", |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} AS bucket_start, COUNT(*) FROM events
+                 WHERE (?1 IS NULL OR conversation_id = ?1) AND (?2 IS NULL OR export_id = ?2)
+                   AND julianday(timestamp) IS NOT NULL
+                 GROUP BY bucket_start ORDER BY bucket_start ASC",
+                bucket_start
+            ))?;
+            let buckets = stmt
+                .query_map(params![conversation_id, export_id], |r| {
+                    Ok(ActivityBucket {
+                        bucket_start: r.get(0)?,
+                        count: r.get(1)?,
+                    })
+                })?
+                .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+            Ok(buckets)
+        })
+    }
+
+    /// Call counts and durations per conversation, longest total call time first.
+    pub fn get_call_totals(&self, export_id: Option<&str>) -> AppResult<Vec<CallTotal>> {
+        self.read_retrying("get_call_totals", |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT e.conversation_id, c.display_name, COUNT(*),
+                        COALESCE(SUM(CASE WHEN e.event_type = 'CALL_AUDIO' THEN e.seconds END), 0),
+                        COALESCE(SUM(CASE WHEN e.event_type = 'CALL_VIDEO' THEN e.seconds END), 0)
+                 FROM (SELECT conversation_id, event_type,
+                              json_extract(metadata, '$.duration_seconds') AS seconds
+                       FROM events
+                       WHERE event_type IN ('CALL_AUDIO', 'CALL_VIDEO') AND conversation_id IS NOT NULL
+                         AND (?1 IS NULL OR export_id = ?1)) e
+                 LEFT JOIN conversations c ON c.id = e.conversation_id
+                 GROUP BY e.conversation_id
+                 ORDER BY COALESCE(SUM(e.seconds), 0) DESC, COUNT(*) DESC",
+            )?;
+            let totals = stmt
+                .query_map(params![export_id], |r| {
+                    let audio_seconds: i64 = r.get(3)?;
+                    let video_seconds: i64 = r.get(4)?;
+                    Ok(CallTotal {
+                        conversation_id: r.get(0)?,
+                        display_name: r.get(1)?,
+                        calls: r.get(2)?,
+                        audio_seconds,
+                        video_seconds,
+                        total_call_seconds: audio_seconds + video_seconds,
+                    })
+                })?
+                .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+            Ok(totals)
+        })
+    }
+
+    /// A conversation's messages as (timestamp, sender, sent by the owner), oldest first.
+    /// Messages whose timestamp couldn't be read are left out.
+    pub fn get_message_directions(&self, conversation_id: &str) -> AppResult<Vec<(DateTime<Utc>, String, bool)>> {
+        self.read_retrying("get_message_directions", |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT timestamp, sender, {} FROM events
+                 WHERE conversation_id = ?1 AND julianday(timestamp) IS NOT NULL
+                 ORDER BY timestamp ASC, rowid ASC",
+                SENT_BY_OWNER
+            ))?;
+            let rows = stmt
+                .query_map([conversation_id], |r| {
+                    Ok((
+                        parse_stored_timestamp(&r.get::<_, String>(0)?).0,
+                        r.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                        r.get::<_, Option<bool>>(2)?.unwrap_or(false),
+                    ))
+                })?
+                .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+            Ok(rows)
+        })
+    }
+
+    /// Message counts by weekday and hour for one conversation or all of them (limited to
+    /// `export_id` when given), shifted to local time by the timezone offset setting. A
+    /// message is sent when the chat JSON says so or it comes from the owner's username.
+    pub fn get_activity_heatmap(
+        &self,
+        conversation_id: Option<&str>,
+        export_id: Option<&str>,
+    ) -> AppResult<ActivityHeatmap> {
+        let offset = self.get_timezone_offset()?;
+        let shift = format!("{:+} minutes", offset);
+        self.read_retrying("get_activity_heatmap", |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT (CAST(strftime('%w', timestamp, ?3) AS INTEGER) + 6) % 7 AS weekday,
+                        CAST(strftime('%H', timestamp, ?3) AS INTEGER) AS hour,
+                        {} AS sent,
+                        COUNT(*)
+                 FROM events
+                 WHERE (?1 IS NULL OR conversation_id = ?1) AND (?2 IS NULL OR export_id = ?2)
+                   AND julianday(timestamp) IS NOT NULL
+                 GROUP BY weekday, hour, sent",
+                SENT_BY_OWNER
+            ))?;
+            let rows = stmt.query_map(params![conversation_id, export_id, shift], |r| {
+                Ok((
+                    r.get::<_, usize>(0)?,
+                    r.get::<_, usize>(1)?,
+                    r.get::<_, Option<bool>>(2)?.unwrap_or(false),
+                    r.get::<_, i32>(3)?,
+                ))
+            })?;
+            let mut heatmap = ActivityHeatmap {
+                timezone_offset_minutes: offset,
+                ..Default::default()
+            };
+            for row in rows {
+                let (weekday, hour, sent, count) = row?;
+                let grid = if sent { &mut heatmap.sent } else { &mut heatmap.received };
+                if let Some(cell) = grid.get_mut(weekday).and_then(|day| day.get_mut(hour)) {
+                    *cell += count;
+                }
+            }
+            Ok(heatmap)
+        })
+    }
+
+    /// Offset of the first item on or before `date` (`YYYY-MM-DD`) in the unified media stream
+    /// as `direction` and `filter` narrow it. Equals the stream length when everything is newer.
+    pub fn get_media_offset_at_date(
+        &self,
+        date: &str,
+        direction: MediaDirection,
+        filter: Option<&MediaFilter>,
+    ) -> AppResult<i32> {
+        let _span = perf::query("get_media_offset_at_date");
+        let day = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map_err(|_| AppError::Validation(format!("Invalid date '{}', expected YYYY-MM-DD", date)))?;
+        let next_day = day.succ_opt().unwrap_or(day).and_time(chrono::NaiveTime::MIN).and_utc();
+        let unfiltered = MediaFilter::default();
+        let filter = filter.unwrap_or(&unfiltered);
+        let offset: i32 = self.conn()?.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM ({}) {} AND timestamp >= ?7",
+                MEDIA_STREAM_SOURCE, MEDIA_STREAM_WHERE
+            ),
+            params![
+                filter.conversation_id,
+                direction.as_str(),
+                filter.source,
+                filter.media_type,
+                filter.after.map(|d| d.to_rfc3339()),
+                filter.before.map(|d| d.to_rfc3339()),
+                next_day.to_rfc3339()
+            ],
+            |r| r.get(0),
+        )?;
+        Ok(offset)
+    }
+
+    pub fn get_message_index_at_date(&self, conversation_id: &str, date: &str) -> AppResult<i32> {
+        let _span = perf::query("get_message_index_at_date");
+        // date is expected as "YYYY-MM-DD"
+        let target = format!("{}T00:00:00+00:00", date);
+        let index: i32 = self.conn()?.query_row(
+            r#"SELECT COUNT(*) FROM events
+             WHERE conversation_id = ?1 AND timestamp < ?2"#,
+            params![conversation_id, target],
+            |r| r.get(0),
+        )?;
+        Ok(index)
+    }
+
+    pub fn get_activity_dates(&self, conversation_id: &str) -> AppResult<Vec<String>> {
+        let mut span = perf::query("get_activity_dates");
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            r#"SELECT DISTINCT substr(timestamp, 1, 10) as dt FROM events
+             WHERE conversation_id = ?1
+             ORDER BY dt ASC"#,
+        )?;
+        let dates = stmt
+            .query_map([conversation_id], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<String>, _>>()?;
+        span.set_rows(dates.trace_rows());
+        Ok(dates)
+    }
+
+    /// Feed the text of a conversation's messages to `f` in rowid order, reading in batches so
+    /// large chats are never fully in memory. Stops early when `f` returns false.
+    pub fn for_each_message_text(
+        &self,
+        conversation_id: &str,
+        sender: Option<&str>,
+        f: impl FnMut(&str) -> bool,
+    ) -> AppResult<usize> {
+        let mut span = perf::query("for_each_message_text");
+        let seen = self.for_each_content(
+            "SELECT rowid, content FROM events
+             WHERE conversation_id = ?1 AND (?2 IS NULL OR sender = ?2)
+               AND content IS NOT NULL AND content != '' AND rowid > ?3
+             ORDER BY rowid LIMIT ?4",
+            vec![conversation_id.to_string().into(), sender.map(str::to_string).into()],
+            f,
+        )?;
+        span.set_rows(Some(seen));
+        Ok(seen)
+    }
+
+    /// Feed the text of the TEXT events in one conversation, or in all of them, to `f` the same
+    /// way. A conversation's scan reads only that conversation's rows.
+    pub fn for_each_text_event(&self, conversation_id: Option<&str>, f: impl FnMut(&str) -> bool) -> AppResult<usize> {
+        let mut span = perf::query("for_each_text_event");
+        let seen = match conversation_id {
+            Some(id) => self.for_each_content(
+                "SELECT rowid, content FROM events
+                 WHERE conversation_id = ?1 AND event_type = 'TEXT'
+                   AND content IS NOT NULL AND content != '' AND rowid > ?2
+                 ORDER BY rowid LIMIT ?3",
+                vec![id.to_string().into()],
+                f,
+            ),
+            None => self.for_each_content(
+                "SELECT rowid, content FROM events
+                 WHERE event_type = 'TEXT' AND content IS NOT NULL AND content != '' AND rowid > ?1
+                 ORDER BY rowid LIMIT ?2",
+                Vec::new(),
+                f,
+            ),
+        }?;
+        span.set_rows(Some(seen));
+        Ok(seen)
+    }
+
+    /// Run `sql`, which selects `rowid, content` and ends with `rowid > ? ... LIMIT ?`, in
+    /// batches after binding `filters`, and feed each content to `f` until it returns false.
+    fn for_each_content(
+        &self,
+        sql: &str,
+        filters: Vec<rusqlite::types::Value>,
+        mut f: impl FnMut(&str) -> bool,
+    ) -> AppResult<usize> {
+        const BATCH: i64 = 5000;
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(sql)?;
+        let mut last_rowid = 0i64;
+        let mut seen = 0usize;
+        loop {
+            let mut bound = filters.clone();
+            bound.extend([last_rowid.into(), BATCH.into()]);
+            let batch = stmt
+                .query_map(rusqlite::params_from_iter(bound), |r| {
+                    Ok((r.get::<_, i64>(0)?, r.get::<_, String>(1)?))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            let Some((rowid, _)) = batch.last() else {
+                return Ok(seen);
+            };
+            last_rowid = *rowid;
+            for (_, text) in &batch {
+                seen += 1;
+                if !f(text) {
+                    return Ok(seen);
+                }
+            }
+            if (batch.len() as i64) < BATCH {
+                return Ok(seen);
+            }
+        }
+    }
+
+    /// Messages in one conversation, or in all of them, from the stored counts.
+    pub fn message_count(&self, conversation_id: Option<&str>) -> AppResult<i64> {
+        Ok(self.conn()?.query_row(
+            "SELECT COALESCE(SUM(message_count), 0) FROM conversations WHERE ?1 IS NULL OR id = ?1",
+            [conversation_id],
+            |r| r.get(0),
+        )?)
+    }
+
+    /// The stored text analytics for a conversation (or all of them), if computed at `event_count`.
+    pub fn get_cached_text_analytics(
+        &self,
+        conversation_id: Option<&str>,
+        event_count: i64,
+    ) -> AppResult<Option<TextAnalytics>> {
+        use rusqlite::OptionalExtension;
+        let json: Option<String> = self
+            .conn()?
+            .query_row(
+                "SELECT result FROM text_analytics_cache WHERE scope = ?1 AND event_count = ?2",
+                params![conversation_id.unwrap_or_default(), event_count],
+                |r| r.get(0),
+            )
+            .optional()?;
+        Ok(json.and_then(|json| serde_json::from_str(&json).ok()))
+    }
+
+    pub fn cache_text_analytics(&self, analytics: &TextAnalytics) -> AppResult<()> {
+        self.write_conn()?.execute(
+            "INSERT OR REPLACE INTO text_analytics_cache (scope, event_count, result, computed_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                analytics.conversation_id.as_deref().unwrap_or_default(),
+                analytics.event_count,
+                serde_json::to_string(analytics)?,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Locations shared in messages, oldest first, across all chats or in one conversation.
+    pub fn get_shared_locations(&self, conversation_id: Option<&str>) -> AppResult<Vec<SharedLocation>> {
+        let mut span = perf::query("get_shared_locations");
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, conversation_id, sender, timestamp,
+                    json_extract(metadata, '$.shared_location.lat'), json_extract(metadata, '$.shared_location.lon')
+             FROM events
+             WHERE instr(metadata, '\"shared_location\"') > 0 AND json_valid(metadata)
+               AND json_extract(metadata, '$.shared_location.lat') IS NOT NULL
+               AND (?1 IS NULL OR conversation_id = ?1)
+             ORDER BY timestamp",
+        )?;
+        let locations = stmt
+            .query_map([conversation_id], |row| {
+                Ok(SharedLocation {
+                    event_id: row.get(0)?,
+                    conversation_id: row.get(1)?,
+                    sender: row.get(2)?,
+                    timestamp: parse_stored_timestamp(&row.get::<_, String>(3)?).0,
+                    lat: row.get(4)?,
+                    lon: row.get(5)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+        span.set_rows(locations.trace_rows());
+        Ok(locations)
+    }
+
+    /// Split a conversation's time range into `buckets` equal slices and count messages in each.
+    ///
+    /// Counting is one grouped query over `julianday(timestamp)`. Conversations spanning fewer
+    /// distinct days than `buckets` get one bucket per active day instead.
+    pub fn get_message_density(&self, conversation_id: &str, buckets: i32) -> AppResult<Vec<DensityBucket>> {
+        let _span = perf::query("get_message_density");
+        let buckets = buckets.clamp(1, 1000);
+        let conn = self.conn()?;
+        let (min_jd, max_jd, days, total): (Option<f64>, Option<f64>, i32, i32) = conn.query_row(
+            "SELECT MIN(julianday(timestamp)), MAX(julianday(timestamp)), COUNT(DISTINCT substr(timestamp, 1, 10)), COUNT(*)
+             FROM events WHERE conversation_id = ?1",
+            [conversation_id],
+            |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?)),
+        )?;
+        let (Some(min_jd), Some(max_jd)) = (min_jd, max_jd) else {
+            return Ok(Vec::new());
+        };
+        let to_datetime = |jd: f64| {
+            let millis = ((jd - 2440587.5) * 86_400_000.0).round() as i64;
+            DateTime::<Utc>::from_timestamp_millis(millis).unwrap_or(DateTime::<Utc>::MIN_UTC)
+        };
+        let span = max_jd - min_jd;
+        if span <= 0.0 {
+            // Every message shares one instant, so there is nothing to divide
+            let start = to_datetime(min_jd);
+            return Ok(vec![DensityBucket {
+                start,
+                end: start,
+                count: total,
+            }]);
+        }
+
+        if days < buckets {
+            let mut stmt = conn.prepare(
+                "SELECT substr(timestamp, 1, 10) AS day, COUNT(*) FROM events
+                 WHERE conversation_id = ?1
+                 GROUP BY day ORDER BY day ASC",
+            )?;
+            let rows = stmt
+                .query_map([conversation_id], |r| Ok((r.get::<_, String>(0)?, r.get::<_, i32>(1)?)))?
+                .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+            return Ok(rows
+                .into_iter()
+                .filter_map(|(day, count)| {
+                    let start = chrono::NaiveDate::parse_from_str(&day, "%Y-%m-%d")
+                        .ok()?
+                        .and_hms_opt(0, 0, 0)?
+                        .and_utc();
+                    Some(DensityBucket {
+                        start,
+                        end: start + chrono::Duration::days(1),
+                        count,
+                    })
+                })
+                .collect());
+        }
+
+        let mut counts = vec![0i32; buckets as usize];
+        let mut stmt = conn.prepare(
+            "SELECT MIN(CAST((julianday(timestamp) - ?2) * ?3 / ?4 AS INTEGER), ?3 - 1) AS bucket, COUNT(*)
+             FROM events WHERE conversation_id = ?1
+             GROUP BY bucket",
+        )?;
+        let rows = stmt.query_map(params![conversation_id, min_jd, buckets, span], |r| {
+            Ok((r.get::<_, i64>(0)?, r.get::<_, i32>(1)?))
+        })?;
+        for row in rows {
+            let (bucket, count) = row?;
+            if let Some(slot) = counts.get_mut(bucket.max(0) as usize) {
+                *slot += count;
+            }
+        }
+
+        let width = span / buckets as f64;
+        Ok(counts
+            .into_iter()
+            .enumerate()
+            .map(|(i, count)| DensityBucket {
+                start: to_datetime(min_jd + width * i as f64),
+                end: to_datetime(min_jd + width * (i + 1) as f64),
+                count,
+            })
+            .collect())
+    }
+
+    /// Events that carry or should carry media, for recomputing media totals.
+    pub fn get_media_events(&self) -> AppResult<Vec<Event>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT e.id, e.timestamp, e.sender, e.conversation_id, e.content, e.event_type, e.media_references, e.metadata, NULL
+             FROM events e
+             WHERE e.event_type = 'MEDIA' OR (e.media_references != '[]' AND e.media_references IS NOT NULL)",
+        )?;
+        let events = stmt
+            .query_map([], Self::map_event_row)?
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+        Ok(events)
+    }
+
+    /// Messages whose `media_ids` list `media_id` or whose linked files mention it.
+    pub fn get_events_for_media_id(&self, media_id: &str) -> AppResult<Vec<Event>> {
+        let mut span = perf::query("get_events_for_media_id");
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT e.id, e.timestamp, e.sender, e.conversation_id, e.content, e.event_type, e.media_references, e.metadata, NULL
+             FROM events e
+             WHERE (json_valid(e.metadata)
+                    AND EXISTS (SELECT 1 FROM json_each(e.metadata, '$.media_ids') WHERE value = ?1))
+                OR instr(e.media_references, ?1) > 0
+             ORDER BY e.timestamp",
+        )?;
+        let events = stmt
+            .query_map([media_id], Self::map_event_row)?
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+        span.set_rows(events.trace_rows());
+        Ok(events)
+    }
+
+    /// Replace the cached media totals; conversations not in `stats` are reset to zero.
+    pub fn set_conversation_media_stats(&self, stats: &HashMap<String, ConversationMediaStats>) -> AppResult<()> {
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "UPDATE conversations SET media_count = 0, media_bytes = 0, missing_media_count = 0",
+            [],
+        )?;
+        {
+            let mut stmt = tx.prepare(
+                "UPDATE conversations SET media_count = ?2, media_bytes = ?3, missing_media_count = ?4 WHERE id = ?1",
+            )?;
+            for (id, s) in stats {
+                stmt.execute(params![id, s.media_count, s.media_bytes, s.missing_media_count])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Recompute every conversation's completeness from its stored media totals, messages and
+    /// anomalies. `sources` adds to the sources already recorded, so a JSON-only re-import
+    /// doesn't forget that the HTML pages contributed. Returns how many were updated.
+    pub fn refresh_conversation_completeness(
+        &self,
+        sources: &HashMap<String, BTreeSet<ChatSource>>,
+    ) -> AppResult<usize> {
+        let mut conn = self.write_conn()?;
+        let tx = conn.transaction()?;
+        let updated = {
+            let mut select = tx.prepare(
+                "SELECT c.id, c.media_count, c.missing_media_count, c.completeness, c.message_count,
+                 (SELECT COUNT(*) FROM events e WHERE e.conversation_id = c.id
+                  AND (julianday(e.timestamp) IS NULL
+                       OR (json_valid(e.metadata) AND json_type(e.metadata, '$.raw_timestamp') IS NOT NULL))),
+                 (SELECT COUNT(*) FROM validation_issues v WHERE v.conversation_id = c.id)
+                 FROM conversations c",
+            )?;
+            let rows = select
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, i64>(2)?,
+                        row.get::<_, Option<String>>(3)?,
+                        row.get::<_, i64>(4)?,
+                        row.get::<_, i64>(5)?,
+                        row.get::<_, i32>(6)?,
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let mut update = tx.prepare("UPDATE conversations SET completeness = ?2 WHERE id = ?1")?;
+            for (id, media_count, missing_media, stored, messages, unparsed, anomaly_count) in &rows {
+                let mut known: BTreeSet<ChatSource> = stored
+                    .as_deref()
+                    .and_then(|json| serde_json::from_str::<ConversationCompleteness>(json).ok())
+                    .map(|c| c.sources.into_iter().collect())
+                    .unwrap_or_default();
+                if let Some(added) = sources.get(id) {
+                    known.extend(added);
+                }
+                let completeness = ConversationCompleteness {
+                    media_linked_pct: ConversationCompleteness::percent(media_count - missing_media, *media_count),
+                    timestamps_parsed_pct: ConversationCompleteness::percent(messages - unparsed, *messages),
+                    sources: known.into_iter().collect(),
+                    anomaly_count: *anomaly_count,
+                };
+                update.execute(params![id, serde_json::to_string(&completeness)?])?;
+            }
+            rows.len()
+        };
+        tx.commit()?;
+        Ok(updated)
+    }
+
+    /// Check that every file events and memories point at still exists, keep the result for
+    /// later validation reports and return the updated report. `progress` is called every
+    /// `VERIFY_PROGRESS_INTERVAL` files and once when done.
+    pub fn verify_media_files<E, P>(&self, exists: E, mut progress: P) -> AppResult<ValidationReport>
+    where
+        E: Fn(&Path) -> bool,
+        P: FnMut(&MediaVerifyProgress),
+    {
+        let mut status = MediaVerifyProgress::default();
+        let mut missing_files = Vec::new();
+        {
+            let conn = self.conn()?;
+            status.total = conn.query_row(&format!("SELECT COUNT(*) FROM ({})", LINKED_MEDIA_FILES), [], |r| {
+                r.get::<_, i64>(0)
+            })? as usize;
+            progress(&status);
+            let mut stmt = conn.prepare(LINKED_MEDIA_FILES)?;
+            let mut rows = stmt.query([])?;
+            while let Some(row) = rows.next()? {
+                let path: String = row.get(0)?;
+                if !exists(Path::new(&path)) {
+                    status.missing += 1;
+                    if missing_files.len() < VERIFY_MISSING_FILES_LIMIT {
+                        missing_files.push(path);
+                    }
+                }
+                status.checked += 1;
+                if status.checked % VERIFY_PROGRESS_INTERVAL == 0 {
+                    progress(&status);
+                }
+            }
+        }
+        status.done = true;
+        progress(&status);
+
+        let verification = MediaVerification {
+            checked_at: Utc::now(),
+            files_checked: status.checked as i32,
+            missing_count: status.missing as i32,
+            missing_files,
+        };
+        log::info!(
+            "Verified {} media files, {} missing",
+            verification.files_checked,
+            verification.missing_count
+        );
+        if !self.read_only {
+            self.set_setting(MEDIA_VERIFICATION_SETTING, &serde_json::to_string(&verification)?)?;
+        }
+        self.validation_report(Some(verification))
+    }
+
+    /// Check the file of every Downloaded memory. A file that is gone or smaller than
+    /// `min_bytes` puts its memory back to Pending with no media path, so the next download
+    /// fetches it again.
+    /// `progress` is called every `VERIFY_PROGRESS_INTERVAL` files and once when done.
+    pub fn verify_downloaded_memories<P>(&self, min_bytes: u64, mut progress: P) -> AppResult<DownloadVerification>
+    where
+        P: FnMut(&DownloadVerification),
+    {
+        let downloaded: Vec<(String, Option<String>)> = {
+            let conn = self.conn()?;
+            let mut stmt =
+                conn.prepare("SELECT id, media_path FROM memories WHERE download_status = ?1 ORDER BY id")?;
+            let rows = stmt
+                .query_map([DownloadStatus::Downloaded.as_str()], |r| Ok((r.get(0)?, r.get(1)?)))?
+                .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+            rows
+        };
+        let mut status = DownloadVerification {
+            total: downloaded.len(),
+            ..Default::default()
+        };
+        progress(&status);
+
+        let mut stale = Vec::new();
+        for (id, path) in downloaded {
+            match path.and_then(|p| std::fs::metadata(p).ok()).filter(|m| m.is_file()) {
+                Some(meta) if meta.len() >= min_bytes => status.ok += 1,
+                Some(_) => {
+                    status.undersized += 1;
+                    stale.push(id);
+                }
+                None => {
+                    status.missing += 1;
+                    stale.push(id);
+                }
+            }
+            status.checked += 1;
+            if status.checked % VERIFY_PROGRESS_INTERVAL == 0 {
+                progress(&status);
+            }
+        }
+
+        if !stale.is_empty() {
+            let mut conn = self.write_conn()?;
+            let tx = conn.transaction()?;
+            {
+                let mut stmt = tx.prepare("UPDATE memories SET download_status = ?2, media_path = NULL WHERE id = ?1 AND download_status = ?3")?;
+                for id in &stale {
+                    stmt.execute(params![
+                        id,
+                        DownloadStatus::Pending.as_str(),
+                        DownloadStatus::Downloaded.as_str()
+                    ])?;
+                }
+            }
+            tx.commit()?;
+        }
+        log::info!(
+            "Verified {} downloaded memories: {} missing, {} undersized",
+            status.checked,
+            status.missing,
+            status.undersized
+        );
+        status.done = true;
+        progress(&status);
+        Ok(status)
+    }
+
+    /// Every media file events and memories link to, once each.
+    pub fn get_linked_media_paths(&self) -> AppResult<HashSet<PathBuf>> {
+        let mut span = perf::query("get_linked_media_paths");
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(LINKED_MEDIA_FILES)?;
+        let paths = stmt
+            .query_map([], |r| Ok(PathBuf::from(r.get::<_, String>(0)?)))?
+            .collect::<std::result::Result<HashSet<_>, rusqlite::Error>>()?;
+        span.set_rows(Some(paths.len()));
+        Ok(paths)
+    }
+
+    /// The last `verify_media_files` result, if it has run.
+    pub fn get_media_verification(&self) -> AppResult<Option<MediaVerification>> {
+        Ok(self.get_setting(MEDIA_VERIFICATION_SETTING)?.and_then(|json| {
+            serde_json::from_str(&json)
+                .map_err(|e| log::warn!("Ignoring unreadable media verification: {}", e))
+                .ok()
+        }))
+    }
+
+    /// Generate a data integrity report for the dashboard, including the last media
+    /// verification.
+    pub fn get_validation_report(&self) -> AppResult<ValidationReport> {
+        let _span = perf::query("get_validation_report");
+        self.validation_report(self.get_media_verification()?)
+    }
+
+    fn validation_report(&self, verification: Option<MediaVerification>) -> AppResult<ValidationReport> {
+        let conn = self.conn()?;
+        let total_media_referenced: i32 =
+            conn.query_row("SELECT COUNT(*) FROM events WHERE event_type = 'MEDIA'", [], |r| {
+                r.get(0)
+            })?;
+        let media_found: i32 = conn.query_row(
+            r#"SELECT COUNT(*) FROM events WHERE event_type = 'MEDIA' AND media_references != '[]' AND media_references IS NOT NULL"#,
+            [], |r| r.get(0)
+        )?;
+        let media_missing = total_media_referenced - media_found;
+
+        let total_html_files: i32 = conn.query_row("SELECT COUNT(*) FROM conversations", [], |r| r.get(0))?;
+
+        let mut warnings = Vec::new();
+        if media_missing > 0 {
+            warnings.push(format!("{} media events have no linked file", media_missing));
+        }
+
+        let empty_convos: i32 = conn.query_row(
+            "SELECT COUNT(*) FROM conversations c WHERE NOT EXISTS (SELECT 1 FROM events WHERE conversation_id = c.id)",
+            [], |r| r.get(0)
+        ).unwrap_or(0);
+        if empty_convos > 0 {
+            warnings.push(format!("{} conversations have no messages", empty_convos));
+        }
+        if let Some(v) = verification.as_ref().filter(|v| v.missing_count > 0) {
+            warnings.push(format!("{} linked media files are missing on disk", v.missing_count));
+        }
+
+        let mut stmt = conn.prepare(
+            "SELECT id, missing_media_count FROM conversations WHERE missing_media_count > 0
+             ORDER BY missing_media_count DESC, id",
+        )?;
+        let missing_media_by_conversation = stmt
+            .query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, i32>(1)?)))?
+            .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()?;
+
+        Ok(ValidationReport {
+            total_html_files,
+            parsed_html_files: total_html_files,
+            total_media_referenced,
+            media_found,
+            media_missing,
+            missing_file_count: verification.as_ref().map_or(0, |v| v.missing_count),
+            media_verified_at: verification.as_ref().map(|v| v.checked_at),
+            missing_files: verification.map(|v| v.missing_files).unwrap_or_default(),
+            warnings,
+            missing_media_by_conversation,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ingestion::RAW_TIMESTAMP_KEY;
+    use crate::test_fixtures;
+
+    fn test_db() -> DatabaseManager {
+        DatabaseManager::new_in_memory().unwrap()
+    }
+
+    #[test]
+    fn test_sanitize_fts_query_simple() {
+        assert_eq!(
+            DatabaseManager::sanitize_fts_query("hello world", false),
+            "\"hello\" \"world\""
+        );
+    }
+
+    #[test]
+    fn test_sanitize_fts_query_empty() {
+        assert_eq!(DatabaseManager::sanitize_fts_query("", false), "");
+        assert_eq!(DatabaseManager::sanitize_fts_query("   ", false), "");
+    }
+
+    #[test]
+    fn test_sanitize_fts_query_special_chars() {
+        // FTS5 operators should be quoted
+        assert_eq!(
+            DatabaseManager::sanitize_fts_query("hello OR world", false),
+            "\"hello\" \"OR\" \"world\""
+        );
+        assert_eq!(DatabaseManager::sanitize_fts_query("test*", false), "\"test*\"");
+    }
+
+    #[test]
+    fn test_sanitize_fts_query_quotes() {
+        // Double quotes within words are escaped
+        assert_eq!(
+            DatabaseManager::sanitize_fts_query("say \"hi\"", false),
+            "\"say\" \"\"\"hi\"\"\""
+        );
+    }
+
+    #[test]
+    fn test_sanitize_fts_query_unicode() {
+        assert_eq!(
+            DatabaseManager::sanitize_fts_query("caf\u{00e9}", false),
+            "\"caf\u{00e9}\""
+        );
+    }
+
+    #[test]
+    fn test_sanitize_fts_query_prefix_only_on_last_token() {
+        assert_eq!(
+            DatabaseManager::sanitize_fts_query("happy birt", true),
+            "\"happy\" \"birt\"*"
+        );
+        // Too short or not plain alphanumeric: stays an exact match
+        assert_eq!(DatabaseManager::sanitize_fts_query("happy b", true), "\"happy\" \"b\"");
+        assert_eq!(DatabaseManager::sanitize_fts_query("birt!", true), "\"birt!\"");
+        assert_eq!(
+            DatabaseManager::sanitize_fts_query("caf\u{00e9}", true),
+            "\"caf\u{00e9}\"*"
+        );
+    }
+
+    #[test]
+    fn test_insert_and_get_exports() {
+        let db = test_db();
+        let export = ExportSet {
+            id: "test-export".to_string(),
+            source_paths: vec![PathBuf::from("/tmp/test")],
+            source_type: ExportSourceType::Folder,
+            extraction_path: None,
+            creation_date: None,
+            validation_status: ValidationStatus::Valid,
+            parts: Vec::new(),
+            import_phases: None,
+            ignored_duplicates: Vec::new(),
+            layout: None,
+        };
+        db.insert_export(&export).unwrap();
+        let exports = db.get_exports().unwrap();
+        assert_eq!(exports.len(), 1);
+        assert_eq!(exports[0].id, "test-export");
+        assert_eq!(exports[0].source_type, ExportSourceType::Folder);
+        assert_eq!(exports[0].validation_status, ValidationStatus::Valid);
+    }
+
+    #[test]
+    fn test_insert_and_get_exports_zip() {
+        let db = test_db();
+        let export = ExportSet {
+            id: "zip-export".to_string(),
+            source_paths: vec![PathBuf::from("/tmp/test.zip")],
+            source_type: ExportSourceType::Zip,
+            extraction_path: Some(PathBuf::from("/tmp/work/zip-export-0a1b2c3d-20240101T000000")),
+            creation_date: Some(chrono::Utc::now()),
+            validation_status: ValidationStatus::Incomplete,
+            parts: Vec::new(),
+            import_phases: None,
+            ignored_duplicates: Vec::new(),
+            layout: None,
+        };
+        db.insert_export(&export).unwrap();
+        let exports = db.get_exports().unwrap();
+        assert_eq!(exports[0].source_type, ExportSourceType::Zip);
+        assert_eq!(exports[0].validation_status, ValidationStatus::Incomplete);
+        assert_eq!(exports[0].extraction_path, export.extraction_path);
+    }
+
+    #[test]
+    fn test_batch_insert_and_get_conversations() {
+        let db = test_db();
+        let convos = vec![Conversation {
+            id: "conv1".to_string(),
+            display_name: Some("Alice".to_string()),
+            participants: vec!["alice".to_string(), "bob".to_string()],
+            last_event_at: Some(chrono::Utc::now()),
+            message_count: 5,
+            has_media: false,
+            media_count: 0,
+            media_bytes: 0,
+            missing_media_count: 0,
+            avatar_path: None,
+            avatar_color: None,
+            is_group: false,
+            listed_members: 0,
+            anomaly_flags: Vec::new(),
+            saved_count: 0,
+            shared_location_count: 0,
+            completeness: None,
+        }];
+        db.insert_export(&ExportSet {
+            id: "e1".to_string(),
+            source_paths: vec![PathBuf::from("/tmp")],
+            source_type: ExportSourceType::Folder,
+            extraction_path: None,
+            creation_date: None,
+            validation_status: ValidationStatus::Valid,
+            parts: Vec::new(),
+            import_phases: None,
+            ignored_duplicates: Vec::new(),
+            layout: None,
+        })
+        .unwrap();
+        db.batch_insert_conversations(&convos).unwrap();
+
+        let result = db.get_conversations(None).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].id, "conv1");
+    }
+
+    #[test]
+    fn test_batch_insert_events_and_search() {
+        let db = test_db();
+        db.insert_export(&ExportSet {
+            id: "e1".to_string(),
+            source_paths: vec![PathBuf::from("/tmp")],
+            source_type: ExportSourceType::Folder,
+            extraction_path: None,
+            creation_date: None,
+            validation_status: ValidationStatus::Valid,
+            parts: Vec::new(),
+            import_phases: None,
+            ignored_duplicates: Vec::new(),
+            layout: None,
+        })
+        .unwrap();
+        db.batch_insert_conversations(&[Conversation {
+            id: "conv1".to_string(),
+            display_name: None,
+            participants: vec![],
+            last_event_at: None,
+            message_count: 0,
+            has_media: false,
+            media_count: 0,
+            media_bytes: 0,
+            missing_media_count: 0,
+            avatar_path: None,
+            avatar_color: None,
+            is_group: false,
+            listed_members: 0,
+            anomaly_flags: Vec::new(),
+            saved_count: 0,
+            shared_location_count: 0,
+            completeness: None,
+        }])
+        .unwrap();
+
+        let events = vec![Event {
+            id: "evt1".to_string(),
+            timestamp: chrono::Utc::now(),
+            sender: "alice".to_string(),
+            sender_name: None,
+            media_references: vec![],
+            conversation_id: Some("conv1".to_string()),
+            content: Some("hello world test message".to_string()),
+            event_type: "TEXT".to_string(),
+            metadata: None,
+        }];
+        db.batch_insert_events(&events, "e1").unwrap();
+        db.populate_fts_for_export("e1", || false, |_| {}).unwrap();
+
+        // Search should find the message
+        let results = db.search_messages("hello", 50, false, None).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].event_id, "evt1");
+    }
+
+    #[test]
+    fn test_search_empty_query() {
+        let db = test_db();
+        let results = db.search_messages("", 50, false, None).unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_get_messages_page_clamping() {
+        let db = test_db();
+        db.insert_export(&ExportSet {
+            id: "e1".to_string(),
+            source_paths: vec![PathBuf::from("/tmp")],
+            source_type: ExportSourceType::Folder,
+            extraction_path: None,
+            creation_date: None,
+            validation_status: ValidationStatus::Valid,
+            parts: Vec::new(),
+            import_phases: None,
+            ignored_duplicates: Vec::new(),
+            layout: None,
+        })
+        .unwrap();
+        db.batch_insert_conversations(&[Conversation {
+            id: "conv1".to_string(),
+            display_name: None,
+            participants: vec![],
+            last_event_at: None,
+            message_count: 0,
+            has_media: false,
+            media_count: 0,
+            media_bytes: 0,
+            missing_media_count: 0,
+            avatar_path: None,
+            avatar_color: None,
+            is_group: false,
+            listed_members: 0,
+            anomaly_flags: Vec::new(),
+            saved_count: 0,
+            shared_location_count: 0,
+            completeness: None,
+        }])
+        .unwrap();
+
+        // Even with negative offset/limit, should not crash
+        let page = db.get_messages_page("conv1", -5, -10, false, None).unwrap();
+        assert_eq!(page.total_count, 0);
+        assert!(!page.has_more);
+    }
+
+    #[test]
+    fn test_search_index_build_resumes_after_cancel() {
+        let db = DatabaseManager::new_in_memory().unwrap();
+        db.insert_export(&test_fixtures::export()).unwrap();
+        db.batch_insert_conversations(&test_fixtures::conversations()).unwrap();
+        let template = test_fixtures::events().remove(0);
+        let events: Vec<Event> = (0..FTS_BATCH_SIZE * 2 + 10)
+            .map(|i| Event {
+                id: format!("bulk_{:05}", i),
+                content: Some(format!("needle word{}", i)),
+                ..template.clone()
+            })
+            .collect();
+        db.batch_insert_events(&events, test_fixtures::EXPORT_ID).unwrap();
+        let fts_rows = || -> usize {
+            db.conn()
+                .unwrap()
+                .query_row("SELECT COUNT(*) FROM events_fts", [], |r| r.get::<_, i64>(0))
+                .unwrap() as usize
+        };
+        assert_eq!(fts_rows(), 0);
+
+        let batches = std::cell::Cell::new(0);
+        let stopped = db
+            .populate_fts_for_export(test_fixtures::EXPORT_ID, || batches.get() == 1, |_| batches.set(1))
+            .unwrap();
+        assert!(stopped.cancelled && !stopped.done);
+        assert_eq!((stopped.indexed, stopped.total), (FTS_BATCH_SIZE, events.len()));
+        assert_eq!(fts_rows(), FTS_BATCH_SIZE);
+
+        let mut seen = Vec::new();
+        let finished = db
+            .populate_fts_for_export(test_fixtures::EXPORT_ID, || false, |p| seen.push(p.indexed))
+            .unwrap();
+        assert!(finished.done && !finished.cancelled);
+        assert_eq!(seen, [FTS_BATCH_SIZE * 2, events.len()]);
+        assert_eq!(fts_rows(), events.len());
+        let last = format!("word{}", events.len() - 1);
+        assert_eq!(db.search_messages(&last, 10, false, None).unwrap().len(), 1);
+        assert_eq!(db.search_messages("word0", 10, false, None).unwrap().len(), 1);
+
+        // A fresh build replaces the export's rows rather than adding to them
+        db.populate_fts_for_export(test_fixtures::EXPORT_ID, || false, |_| {})
+            .unwrap();
+        assert_eq!(fts_rows(), events.len());
+    }
+
+    #[test]
+    fn test_prefix_search_matches_partial_last_word() {
+        let db = test_fixtures::standard_db();
+        assert!(db.search_messages("piz", 50, false, None).unwrap().is_empty());
+        assert_eq!(db.search_messages("piz", 50, true, None).unwrap().len(), 10);
+        assert_eq!(db.search_messages("grab piz", 50, true, None).unwrap().len(), 10);
+        // Earlier tokens stay exact
+        assert!(db.search_messages("gra piz", 50, true, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_prefix_search_latency_on_populated_index() {
+        let db = test_fixtures::standard_db();
+        // Bulk rows straight through SQL; batch_insert_events and the index build are too slow
+        // for a dataset this size in a debug build
+        db.write_conn()
+            .unwrap()
+            .execute_batch(
+                "WITH RECURSIVE n(i) AS (SELECT 0 UNION ALL SELECT i + 1 FROM n WHERE i < 49999)
+                 INSERT INTO events (id, timestamp, sender, export_id, conversation_id, content, event_type)
+                 SELECT 'bulk_' || i, '2023-02-01T00:00:00+00:00', 'alice', 'fixture_export', 'alice',
+                        json_extract('[\"birthday\",\"birds\",\"birch\",\"bistro\",\"bicycle\",\"binder\"]',
+                                     '$[' || (i % 6) || ']') || ' number ' || i,
+                        'TEXT'
+                 FROM n;
+                 INSERT INTO events_fts (content, event_id, conversation_id, sender)
+                 SELECT content, id, conversation_id, sender FROM events WHERE id LIKE 'bulk_%';",
+            )
+            .unwrap();
+
+        let start = std::time::Instant::now();
+        for query in ["bi", "bir", "birt", "birth", "number bis", "bic"] {
+            assert!(
+                !db.search_messages(query, 50, true, None).unwrap().is_empty(),
+                "{}",
+                query
+            );
+        }
+        // Generous bound for debug builds; each keystroke should come back well under this
+        assert!(
+            start.elapsed() < std::time::Duration::from_secs(2),
+            "{:?}",
+            start.elapsed()
+        );
+    }
+
+    #[test]
+    fn test_reimported_memories_keep_downloads() {
+        let db = test_fixtures::standard_db();
+        let mut memories = test_fixtures::memories();
+        for memory in &mut memories {
+            memory.media_path = None;
+            memory.download_status = DownloadStatus::Pending;
+            memory.overlay_url = Some(format!("https://example.com/overlay/{}", memory.id));
+        }
+        let ids: Vec<String> = memories.iter().map(|m| m.id.clone()).collect();
+        assert_eq!(db.existing_memory_ids(&ids).unwrap().len(), test_fixtures::MEMORY_COUNT);
+        assert!(db.existing_memory_ids(&["unknown".to_string()]).unwrap().is_empty());
+
+        db.batch_insert_memories(&memories).unwrap();
+        let stored = db.get_memories(None).unwrap();
+        assert_eq!(stored.len(), test_fixtures::MEMORY_COUNT);
+        for memory in &stored {
+            let downloaded = memory.id == "fixture_memory_0" || memory.id == "fixture_memory_1";
+            assert_eq!(memory.download_status == DownloadStatus::Downloaded, downloaded);
+            assert_eq!(memory.media_path.is_some(), downloaded);
+            assert!(memory.overlay_url.as_deref().unwrap().ends_with(&memory.id));
+        }
+    }
+
+    #[test]
+    fn test_location_history_skips_repeats_and_filters_by_time() {
+        let db = test_fixtures::standard_db();
+        let point = |hour: u32, source: &str| LocationPoint {
+            timestamp: test_fixtures::base_time() + chrono::Duration::hours(hour as i64),
+            latitude: 48.8584,
+            longitude: 2.2945 + hour as f64,
+            source: source.to_string(),
+            export_id: test_fixtures::EXPORT_ID.to_string(),
+        };
+        let points = [point(0, "Latest Location"), point(2, "Areas"), point(5, "Areas")];
+        assert_eq!(db.insert_location_history(&points).unwrap().len(), 3);
+        // A re-import adds only what is new
+        assert_eq!(
+            db.insert_location_history(&[point(2, "Areas"), point(7, "Areas")])
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let all = db.get_location_history(None, None, None).unwrap();
+        assert_eq!(all.len(), 4);
+        assert_eq!(all[0], points[0]);
+        let window = db
+            .get_location_history(Some(points[1].timestamp), Some(points[2].timestamp), None)
+            .unwrap();
+        assert_eq!(window, points[1..]);
+        assert!(db
+            .get_location_history(None, None, Some("other_export"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_call_totals_sum_logged_durations() {
+        let db = test_fixtures::standard_db();
+        assert!(db.get_call_totals(None).unwrap().is_empty());
+        let template = test_fixtures::events().remove(0);
+        let call = |id: &str, conversation: &str, event_type: &str, seconds: Option<i64>| Event {
+            id: id.to_string(),
+            conversation_id: Some(conversation.to_string()),
+            event_type: event_type.to_string(),
+            metadata: Some(match seconds {
+                Some(s) => format!(r#"{{"is_sender":true,"duration_seconds":{}}}"#, s),
+                None => r#"{"is_sender":true}"#.to_string(),
+            }),
+            ..template.clone()
+        };
+        let calls = [
+            call("call_1", "alice", "CALL_AUDIO", Some(120)),
+            call("call_2", "alice", "CALL_VIDEO", Some(30)),
+            call("call_3", "bob", "CALL_AUDIO", Some(600)),
+            call("call_4", "bob", "CALL_AUDIO", None),
+        ];
+        db.batch_insert_events(&calls, test_fixtures::EXPORT_ID).unwrap();
+
+        let totals = db.get_call_totals(None).unwrap();
+        let summary: Vec<(&str, i32, i64, i64, i64)> = totals
+            .iter()
+            .map(|t| {
+                (
+                    t.conversation_id.as_str(),
+                    t.calls,
+                    t.audio_seconds,
+                    t.video_seconds,
+                    t.total_call_seconds,
+                )
+            })
+            .collect();
+        assert_eq!(summary, [("bob", 2, 600, 0, 600), ("alice", 2, 120, 30, 150)]);
+        assert!(db.get_call_totals(Some("other_export")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_on_this_day_caps_messages_and_handles_leap_days() {
+        let db = test_fixtures::standard_db();
+        let jan_1 = db.get_on_this_day(1, 1, 3, false).unwrap();
+        assert_eq!(jan_1.len(), 1);
+        assert_eq!((jan_1[0].year, jan_1[0].event_total), (2023, 12));
+        // Saved messages and media come before the rest of the day
+        let ids: Vec<&str> = jan_1[0].events.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, ["fixture_event_002", "fixture_event_009", "fixture_event_010"]);
+        let memories: Vec<&str> = jan_1[0].memories.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(memories, ["fixture_memory_0"]);
+
+        let at = |timestamp: &str| DateTime::parse_from_rfc3339(timestamp).unwrap().with_timezone(&Utc);
+        let mut event = test_fixtures::events().remove(0);
+        let events: Vec<Event> = [
+            "2020-02-29T10:00:00+00:00",
+            "2021-02-28T10:00:00+00:00",
+            "2024-02-28T10:00:00+00:00",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, timestamp)| {
+            event.id = format!("leap_{}", i);
+            event.timestamp = at(timestamp);
+            event.clone()
+        })
+        .collect();
+        db.batch_insert_events(&events, test_fixtures::EXPORT_ID).unwrap();
+        let mut memory = test_fixtures::memories().remove(0);
+        memory.id = "leap_memory".to_string();
+        memory.timestamp = at("2022-02-28T10:00:00+00:00");
+        db.batch_insert_memories(&[memory]).unwrap();
+
+        let years = |fallback| -> Vec<i32> {
+            db.get_on_this_day(2, 29, 10, fallback)
+                .unwrap()
+                .iter()
+                .map(|y| y.year)
+                .collect()
+        };
+        assert_eq!(years(false), [2020]);
+        // 2024 had its own Feb 29, so its Feb 28 doesn't stand in
+        assert_eq!(years(true), [2022, 2021, 2020]);
+        assert!(db.get_on_this_day(2, 30, 10, true).is_err());
+    }
+
+    #[test]
+    fn test_search_on_day_groups_years_and_skips_near_misses() {
+        let db = test_fixtures::standard_db();
+        let event = |id: &str, timestamp: &str, conversation: &str, content: &str| Event {
+            id: id.to_string(),
+            timestamp: DateTime::parse_from_rfc3339(timestamp).unwrap().with_timezone(&Utc),
+            sender: conversation.to_string(),
+            sender_name: None,
+            conversation_id: Some(conversation.to_string()),
+            content: Some(content.to_string()),
+            event_type: "TEXT".to_string(),
+            media_references: Vec::new(),
+            metadata: None,
+        };
+        let mut events = vec![
+            event("day_2021", "2021-07-04T09:00:00Z", "alice", "camping at the lake"),
+            event("day_2022", "2022-07-04T23:59:59Z", "bob", "camping again"),
+            event("near_0703", "2022-07-03T23:59:59Z", "alice", "camping prep"),
+            event("near_0705", "2022-07-05T00:00:00Z", "alice", "camping recap"),
+            event("near_0604", "2022-06-04T12:00:00Z", "alice", "camping in june"),
+            event("day_other", "2023-07-04T12:00:00Z", "alice", "fireworks tonight"),
+        ];
+        for i in 0..5 {
+            events.push(event(
+                &format!("day_2023_{}", i),
+                &format!("2023-07-04T1{}:00:00Z", i),
+                "alice",
+                "camping",
+            ));
+        }
+        db.batch_insert_events(&events, test_fixtures::EXPORT_ID).unwrap();
+        db.populate_fts_for_export(test_fixtures::EXPORT_ID, || false, |_| {})
+            .unwrap();
+
+        let all = MessageSearchFilters::default();
+        let years = db.search_messages_on_day("camping", 7, 4, &all, 3).unwrap();
+        let summary: Vec<(i32, i32, usize)> = years.iter().map(|y| (y.year, y.total, y.results.len())).collect();
+        assert_eq!(summary, vec![(2023, 5, 3), (2022, 1, 1), (2021, 1, 1)]);
+        let ids: Vec<&str> = years[0].results.iter().map(|r| r.event_id.as_str()).collect();
+        assert_eq!(ids, ["day_2023_0", "day_2023_1", "day_2023_2"]);
+        assert_eq!(years[1].results[0].event_id, "day_2022");
+
+        let bob = MessageSearchFilters {
+            conversation_id: Some("bob".to_string()),
+            sender: None,
+        };
+        let years = db.search_messages_on_day("camping", 7, 4, &bob, 20).unwrap();
+        assert_eq!(years.iter().map(|y| y.year).collect::<Vec<_>>(), [2022]);
+
+        assert!(db
+            .search_messages_on_day("camping", 2, 29, &all, 20)
+            .unwrap()
+            .is_empty());
+        assert!(matches!(
+            db.search_messages_on_day("camping", 2, 30, &all, 20),
+            Err(AppError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn test_migration_adds_prefix_indexes_to_fts() {
+        let db = test_fixtures::standard_db();
+        {
+            let conn = db.conn().unwrap();
+            conn.execute_batch(
+                "DROP TABLE events_fts;
+                 CREATE VIRTUAL TABLE events_fts USING fts5(
+                     content, event_id UNINDEXED, conversation_id UNINDEXED, sender UNINDEXED,
+                     tokenize='unicode61'
+                 );
+                 INSERT INTO events_fts (content, event_id, conversation_id, sender)
+                     SELECT content, id, conversation_id, sender FROM events WHERE content IS NOT NULL;",
+            )
+            .unwrap();
+        }
+        db.run_migrations().unwrap();
+        let sql: String = db
+            .conn()
+            .unwrap()
+            .query_row("SELECT sql FROM sqlite_master WHERE name = 'events_fts'", [], |r| {
+                r.get(0)
+            })
+            .unwrap();
+        assert!(sql.contains("prefix='2 3 4'"));
+        assert_eq!(db.search_messages("pizz", 50, true, None).unwrap().len(), 10);
+    }
+
+    #[test]
+    fn test_media_timeline_and_seek_agree_with_stream() {
+        let db = test_fixtures::standard_db();
+        let extra: Vec<Memory> = [(2022, 6, 15), (2022, 6, 1), (2022, 11, 30), (2021, 12, 31)]
+            .iter()
+            .enumerate()
+            .map(|(i, &(y, m, d))| Memory {
+                id: format!("timeline_{}", i),
+                timestamp: chrono::TimeZone::with_ymd_and_hms(&Utc, y, m, d, 20, 0, 0).unwrap(),
+                media_type: "Image".to_string(),
+                latitude: None,
+                longitude: None,
+                media_path: Some(PathBuf::from(format!("/fixtures/memories/timeline_{}.jpg", i))),
+                export_id: test_fixtures::EXPORT_ID.to_string(),
+                download_url: None,
+                proxy_url: None,
+                overlay_url: None,
+                download_status: crate::models::DownloadStatus::Downloaded,
+                failure_reason: None,
+            })
+            .collect();
+        db.batch_insert_memories(&extra).unwrap();
+
+        let stream = db.get_unified_media_stream(1000, 0, MediaDirection::All, None).unwrap();
+        assert_eq!(stream.total_count as usize, stream.items.len());
+
+        let timeline = db.get_media_timeline(MediaDirection::All, None).unwrap();
+        let months: Vec<&str> = timeline.iter().map(|m| m.month.as_str()).collect();
+        assert_eq!(months, ["2023-01", "2022-11", "2022-06", "2021-12"]);
+        assert_eq!(timeline.iter().map(|m| m.count).sum::<i32>(), stream.total_count);
+        for month in &timeline {
+            let first = &stream.items[month.offset as usize];
+            assert_eq!(first.timestamp.format("%Y-%m").to_string(), month.month);
+        }
+
+        for date in [
+            "2023-01-02",
+            "2022-12-25",
+            "2022-06-15",
+            "2022-06-14",
+            "2021-01-01",
+            "2030-01-01",
+        ] {
+            let offset = db.get_media_offset_at_date(date, MediaDirection::All, None).unwrap() as usize;
+            let expected = stream
+                .items
+                .iter()
+                .position(|item| item.timestamp.format("%Y-%m-%d").to_string().as_str() <= date)
+                .unwrap_or(stream.items.len());
+            assert_eq!(offset, expected, "{}", date);
+        }
+        assert!(db
+            .get_media_offset_at_date("June 2022", MediaDirection::All, None)
+            .is_err());
+
+        // A filtered gallery seeks within the stream it shows
+        let memories = MediaFilter {
+            source: Some("cloud".to_string()),
+            ..Default::default()
+        };
+        for (direction, filter) in [(MediaDirection::Sent, None), (MediaDirection::All, Some(&memories))] {
+            let stream = db.get_unified_media_stream(1000, 0, direction, filter).unwrap();
+            assert!(stream.total_count > 0);
+            let timeline = db.get_media_timeline(direction, filter).unwrap();
+            assert_eq!(timeline.iter().map(|m| m.count).sum::<i32>(), stream.total_count);
+            for month in &timeline {
+                let first = &stream.items[month.offset as usize];
+                assert_eq!(first.timestamp.format("%Y-%m").to_string(), month.month);
+            }
+            let offset = db.get_media_offset_at_date("2022-06-14", direction, filter).unwrap() as usize;
+            let expected = stream
+                .items
+                .iter()
+                .position(|item| item.timestamp.format("%Y-%m-%d").to_string().as_str() <= "2022-06-14")
+                .unwrap_or(stream.items.len());
+            assert_eq!(offset, expected);
+        }
+    }
+
+    #[test]
+    fn test_offset_paging_visits_every_row_exactly_once() {
+        let db = test_fixtures::standard_db();
+        // Many rows sharing one timestamp, and ids repeated across events and memories, so any
+        // order that isn't total shows up as a skipped or repeated row between pages
+        db.write_conn()
+            .unwrap()
+            .execute_batch(
+                "WITH RECURSIVE n(i) AS (SELECT 0 UNION ALL SELECT i + 1 FROM n WHERE i < 39)
+                 INSERT INTO events (id, timestamp, sender, export_id, conversation_id, content, event_type, media_references)
+                 SELECT 'tied_' || i, '2024-05-01T10:00:00+00:00', 'alice', 'fixture_export', 'alice', 'burst ' || i,
+                        'MEDIA', json_array('/fixtures/media/tied_' || i || '.jpg')
+                 FROM n;
+                 WITH RECURSIVE n(i) AS (SELECT 0 UNION ALL SELECT i + 1 FROM n WHERE i < 39)
+                 INSERT INTO memories (id, timestamp, media_type, media_path, export_id)
+                 SELECT 'tied_' || i, '2024-05-01T10:00:00+00:00', 'Image', '/fixtures/memories/tied_' || i || '.jpg',
+                        'fixture_export'
+                 FROM n;",
+            )
+            .unwrap();
+
+        let everything = db.get_unified_media_stream(1000, 0, MediaDirection::All, None).unwrap();
+        let mut paged = Vec::new();
+        let mut offset = 0;
+        loop {
+            let page = db
+                .get_unified_media_stream(7, offset, MediaDirection::All, None)
+                .unwrap();
+            assert_eq!(page.total_count, everything.total_count);
+            offset += page.items.len() as i32;
+            paged.extend(page.items.into_iter().map(|m| (m.source, m.id)));
+            if !page.has_more {
+                break;
+            }
+        }
+        let expected: Vec<(String, String)> = everything.items.into_iter().map(|m| (m.source, m.id)).collect();
+        assert_eq!(paged, expected);
+        assert_eq!(paged.iter().collect::<HashSet<_>>().len(), paged.len());
+
+        let all_messages = db.get_messages_page("alice", 0, 1000, false, None).unwrap();
+        let mut paged_messages = Vec::new();
+        for offset in (0..all_messages.total_count).step_by(6) {
+            let page = db.get_messages_page("alice", offset, 6, false, None).unwrap();
+            paged_messages.extend(page.messages.into_iter().map(|e| e.id));
+        }
+        let expected: Vec<String> = all_messages.messages.into_iter().map(|e| e.id).collect();
+        assert_eq!(paged_messages, expected);
+        assert_eq!(
+            paged_messages.iter().collect::<HashSet<_>>().len(),
+            paged_messages.len()
+        );
+    }
+
+    #[test]
+    fn test_media_stream_interleaves_memories_and_chat_media_across_pages() {
+        let db = test_fixtures::standard_db();
+        // Chat media on odd minutes and memories on even ones, so every page mixes both halves
+        // of the union and only an ordering over the combined set keeps them in sequence
+        db.write_conn()
+            .unwrap()
+            .execute_batch(
+                "WITH RECURSIVE n(i) AS (SELECT 0 UNION ALL SELECT i + 1 FROM n WHERE i < 19)
+                 INSERT INTO events (id, timestamp, sender, export_id, conversation_id, content, event_type, media_references)
+                 SELECT 'chat_' || i, printf('2024-06-01T10:%02d:00+00:00', 2 * i + 1), 'alice', 'fixture_export',
+                        'alice', NULL, 'MEDIA', json_array('/fixtures/media/chat_' || i || '.jpg')
+                 FROM n;
+                 WITH RECURSIVE n(i) AS (SELECT 0 UNION ALL SELECT i + 1 FROM n WHERE i < 19)
+                 INSERT INTO memories (id, timestamp, media_type, media_path, export_id)
+                 SELECT 'memory_' || i, printf('2024-06-01T10:%02d:00+00:00', 2 * i), 'Image',
+                        '/fixtures/memories/memory_' || i || '.jpg', 'fixture_export'
+                 FROM n;",
+            )
+            .unwrap();
+
+        let mut paged = Vec::new();
+        let mut offset = 0;
+        loop {
+            let page = db
+                .get_unified_media_stream(9, offset, MediaDirection::All, None)
+                .unwrap();
+            offset += page.items.len() as i32;
+            paged.extend(page.items);
+            if !page.has_more {
+                break;
+            }
+        }
+        let seeded: Vec<&MediaStreamEntry> = paged
+            .iter()
+            .filter(|m| m.id.starts_with("chat_") || m.id.starts_with("memory_"))
+            .collect();
+        assert_eq!(seeded.len(), 40);
+        assert!(seeded.windows(2).all(|w| w[0].timestamp > w[1].timestamp));
+        assert_eq!(seeded[0].id, "chat_19");
+        assert_eq!(seeded[1].id, "memory_19");
+        assert!(paged.windows(2).all(|w| w[0].timestamp >= w[1].timestamp));
+        let keys: HashSet<(&str, &str)> = paged.iter().map(|m| (m.source.as_str(), m.id.as_str())).collect();
+        assert_eq!(keys.len(), paged.len());
+    }
+
+    #[test]
+    fn test_filtered_messages_include_both_bounds() {
+        let db = test_fixtures::standard_db();
+        let base = test_fixtures::base_time();
+        let hours = |h| Some(base + chrono::Duration::hours(h));
+        // alice's chat has a message at base + 0h, 1h, 5h, 6h, 10h, 11h, ...; odd hours are hers
+        let ids = |after, before, senders: &[String]| -> Vec<String> {
+            let messages = db.get_messages_filtered("alice", after, before, senders).unwrap();
+            assert_eq!(
+                db.count_messages_filtered("alice", after, before, senders).unwrap() as usize,
+                messages.len()
+            );
+            messages.into_iter().map(|e| e.id).collect()
+        };
+        assert_eq!(ids(None, None, &[]).len(), 20);
+        assert_eq!(
+            ids(hours(5), hours(11), &[]),
+            [
+                "fixture_event_005",
+                "fixture_event_006",
+                "fixture_event_010",
+                "fixture_event_011"
+            ]
+        );
+        // A second either side of a message leaves it out
+        let just_after = hours(5).map(|t| t + chrono::Duration::seconds(1));
+        let just_before = hours(11).map(|t| t - chrono::Duration::seconds(1));
+        assert_eq!(
+            ids(just_after, just_before, &[]),
+            ["fixture_event_006", "fixture_event_010"]
+        );
+        assert_eq!(ids(hours(46), None, &[]), ["fixture_event_046"]);
+        assert_eq!(ids(None, hours(0), &[]), ["fixture_event_000"]);
+
+        let alice = ["alice".to_string()];
+        assert_eq!(
+            ids(hours(5), hours(11), &alice),
+            ["fixture_event_005", "fixture_event_011"]
+        );
+        assert_eq!(ids(None, None, &alice).len(), 10);
+        assert!(ids(None, None, &["nobody".to_string()]).is_empty());
+    }
+
+    #[test]
+    fn test_recent_searches_dedupe_and_evict() {
+        let db = test_db();
+        let filters = serde_json::json!({ "conversation_id": "alice" });
+        db.record_search("pizza", None).unwrap();
+        db.record_search(" pizza ", Some(&serde_json::json!({}))).unwrap();
+        db.record_search("pizza", Some(&filters)).unwrap();
+        db.record_search("", None).unwrap();
+        let recent = db.get_saved_searches().unwrap();
+        assert_eq!(recent.len(), 2);
+        let plain = recent.iter().find(|s| s.filters.is_none()).unwrap();
+        assert_eq!(plain.use_count, 2);
+        assert_eq!(recent[0].filters.as_ref(), Some(&filters));
+
+        db.save_search("Pizza plans", "pizza", None).unwrap();
+        for i in 0..RECENT_SEARCH_LIMIT + 5 {
+            db.record_search(&format!("query {}", i), None).unwrap();
+        }
+        let all = db.get_saved_searches().unwrap();
+        assert_eq!(all.len() as i64, RECENT_SEARCH_LIMIT + 1);
+        assert_eq!(all[0].name.as_deref(), Some("Pizza plans"));
+        assert_eq!(all[1].query, format!("query {}", RECENT_SEARCH_LIMIT + 4));
+        assert!(!all
+            .iter()
+            .any(|s| s.query == "query 4" || s.query == "pizza" && s.name.is_none()));
+    }
+
+    #[test]
+    fn test_save_search_replaces_by_name_and_delete() {
+        let db = test_db();
+        let first = db.save_search("Weekend", "beach", None).unwrap();
+        let second = db
+            .save_search("Weekend", "camping", Some(&serde_json::json!({ "sender": "carol" })))
+            .unwrap();
+        assert_eq!(first.id, second.id);
+        assert_eq!(second.query, "camping");
+        assert!(matches!(db.save_search(" ", "x", None), Err(AppError::Validation(_))));
+
+        let copy = test_db();
+        assert_eq!(
+            copy.restore_saved_searches(&db.get_saved_searches().unwrap()).unwrap(),
+            1
+        );
+        assert_eq!(copy.get_saved_searches().unwrap()[0].query, "camping");
+
+        db.delete_saved_search(second.id).unwrap();
+        assert!(db.get_saved_searches().unwrap().is_empty());
+        assert!(matches!(db.delete_saved_search(second.id), Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_migration_moves_orphan_events_to_unsorted() {
+        let db = test_fixtures::standard_db();
+        let mut orphan = test_fixtures::events().remove(0);
+        orphan.id = "orphan".to_string();
+        orphan.conversation_id = None;
+        db.batch_insert_events(&[orphan], test_fixtures::EXPORT_ID).unwrap();
+        db.populate_fts_for_export(test_fixtures::EXPORT_ID, || false, |_| {})
+            .unwrap();
+        db.run_migrations().unwrap();
+
+        let id = crate::ingestion::unsorted_conversation_id(test_fixtures::EXPORT_ID);
+        let convo = db
+            .get_conversations(None)
+            .unwrap()
+            .into_iter()
+            .find(|c| c.id == id)
+            .unwrap();
+        assert_eq!(convo.message_count, 1);
+        assert_eq!(convo.participants, ["me"]);
+        let hit = db.search_messages("around later", 50, false, None).unwrap();
+        assert!(hit
+            .iter()
+            .any(|h| h.event_id == "orphan" && h.conversation_id.as_deref() == Some(id.as_str())));
+    }
+
+    #[test]
+    fn test_event_id_scheme_flags_databases_with_older_events() {
+        assert!(!test_db().has_random_event_ids().unwrap());
+        let db = test_fixtures::standard_db();
+        assert!(!db.has_random_event_ids().unwrap());
+        let mut older = test_fixtures::events().remove(0);
+        older.id = uuid::Uuid::new_v4().to_string();
+        db.batch_insert_events(&[older.clone()], test_fixtures::EXPORT_ID)
+            .unwrap();
+        db.write_conn()
+            .unwrap()
+            .execute("DELETE FROM settings WHERE key = ?1", [EVENT_ID_SCHEME_KEY])
+            .unwrap();
+        db.run_migrations().unwrap();
+        assert!(db.has_random_event_ids().unwrap());
+
+        // Stays flagged until the last event with a random id is gone
+        db.run_migrations().unwrap();
+        assert!(db.has_random_event_ids().unwrap());
+        db.redact_events(&[older.id]).unwrap();
+        assert!(!db.has_random_event_ids().unwrap());
+    }
+
+    #[test]
+    fn test_repeat_import_keeps_each_events_export() {
+        let db = test_fixtures::standard_db();
+        let mut export = test_fixtures::export();
+        export.id = "second_export".to_string();
+        db.insert_export(&export).unwrap();
+        let mut events = test_fixtures::events();
+        events[0].content = Some("edited in the second export".to_string());
+        db.batch_insert_events(&events, "second_export").unwrap();
+
+        let conn = db.conn().unwrap();
+        let (export_id, content): (String, String) = conn
+            .query_row(
+                "SELECT export_id, content FROM events WHERE id = ?1",
+                [&events[0].id],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(export_id, test_fixtures::EXPORT_ID);
+        assert_eq!(content, "edited in the second export");
+        let moved: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM events WHERE export_id = 'second_export'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(moved, 0);
+    }
+
+    #[test]
+    fn test_run_migrations_idempotent() {
+        let db = test_db();
+        // Running migrations again should not fail
+        db.run_migrations().unwrap();
+        db.run_migrations().unwrap();
+    }
+
+    #[test]
+    fn test_storage_path_migrates_to_downloads_path() {
+        let db = test_db();
+        db.set_setting("storage_path", "/data/snap").unwrap();
+        db.run_migrations().unwrap();
+        assert_eq!(db.get_setting("downloads_path").unwrap().as_deref(), Some("/data/snap"));
+        assert_eq!(db.get_setting("storage_path").unwrap().as_deref(), Some("/data/snap"));
+
+        // An explicitly configured downloads path wins over the legacy value
+        db.set_setting("downloads_path", "/data/memories").unwrap();
+        db.run_migrations().unwrap();
+        assert_eq!(db.downloads_root().unwrap(), Some(PathBuf::from("/data/memories")));
+
+        // Extraction has no own setting yet, so it falls back to storage_path
+        assert_eq!(
+            db.get_path_setting("extraction_path").unwrap(),
+            Some((PathBuf::from("/data/snap"), PathSource::LegacyStoragePath))
+        );
+        db.set_setting("extraction_path", "/data/extract").unwrap();
+        assert_eq!(
+            db.get_path_setting("extraction_path").unwrap(),
+            Some((PathBuf::from("/data/extract"), PathSource::Configured))
+        );
+    }
+
+    #[test]
+    fn test_single_memory_neighbours_and_pages() {
+        let db = test_fixtures::standard_db();
+        let memory = db.get_memory("fixture_memory_2").unwrap();
+        assert_eq!(memory.timestamp, test_fixtures::base_time() + chrono::Duration::days(2));
+        assert!(matches!(db.get_memory("nope"), Err(AppError::NotFound(_))));
+        assert!(matches!(db.get_adjacent_memories("nope"), Err(AppError::NotFound(_))));
+
+        let middle = db.get_adjacent_memories("fixture_memory_2").unwrap();
+        assert_eq!(middle.previous.as_deref(), Some("fixture_memory_1"));
+        assert_eq!(middle.next.as_deref(), Some("fixture_memory_3"));
+        let first = db.get_adjacent_memories("fixture_memory_0").unwrap();
+        assert_eq!(
+            (first.previous, first.next.as_deref()),
+            (None, Some("fixture_memory_1"))
+        );
+
+        let page = db.get_memories_page(2, 0, &MemoryFilter::default()).unwrap();
+        assert_eq!(page.total_count, test_fixtures::MEMORY_COUNT as i32);
+        assert!(page.has_more);
+        assert_eq!(page.items[0].id, "fixture_memory_4");
+
+        let filter = MemoryFilter {
+            media_type: Some("Image".to_string()),
+            download_status: Some(crate::models::DownloadStatus::Pending),
+            ..Default::default()
+        };
+        let pending_images = db.get_memories_page(10, 0, &filter).unwrap();
+        let ids: Vec<&str> = pending_images.items.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["fixture_memory_4", "fixture_memory_2"]);
+        assert!(!pending_images.has_more);
+    }
+
+    #[test]
+    fn test_download_jobs_track_progress_and_interruption() {
+        let db = test_fixtures::standard_db();
+        let videos = MemoryFilter {
+            media_type: Some("Video".to_string()),
+            ..Default::default()
+        };
+        let remaining = db.get_undownloaded_memories(&videos).unwrap();
+        let ids: Vec<&str> = remaining.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["fixture_memory_3"]);
+        let remaining = db.get_undownloaded_memories(&MemoryFilter::default()).unwrap();
+        assert_eq!(remaining.len(), 3);
+
+        let mut job = db.create_download_job(&videos, 1).unwrap();
+        assert_eq!(job.state, DownloadJobState::Running);
+        assert_eq!(job.filter.media_type.as_deref(), Some("Video"));
+        job.completed = 1;
+        db.update_download_job(&job).unwrap();
+        let other = db.create_download_job(&MemoryFilter::default(), 3).unwrap();
+
+        // A restart finds both still Running
+        let interrupted = db.interrupt_running_download_jobs().unwrap();
+        assert_eq!(interrupted.iter().map(|j| j.id).collect::<Vec<_>>(), [other.id, job.id]);
+        assert!(db.interrupt_running_download_jobs().unwrap().is_empty());
+
+        let history = db.get_download_jobs().unwrap();
+        assert_eq!(history[1].completed, 1);
+        assert!(history.iter().all(|j| j.state == DownloadJobState::Interrupted));
+        assert!(matches!(db.get_download_job(999), Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_stalled_downloads_go_back_to_pending() {
+        let db = test_fixtures::standard_db();
+        db.update_memory_status("fixture_memory_2", DownloadStatus::Downloading, None, None)
+            .unwrap();
+        db.update_memory_status("fixture_memory_3", DownloadStatus::Failed, None, None)
+            .unwrap();
+        assert_eq!(db.reset_stalled_downloads().unwrap(), 1);
+        assert_eq!(
+            db.get_memory("fixture_memory_2").unwrap().download_status,
+            DownloadStatus::Pending
+        );
+        assert_eq!(
+            db.get_memory("fixture_memory_3").unwrap().download_status,
+            DownloadStatus::Failed
+        );
+        assert_eq!(
+            db.get_memory("fixture_memory_0").unwrap().download_status,
+            DownloadStatus::Downloaded
+        );
+        assert_eq!(db.reset_stalled_downloads().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_download_failures_group_by_reason() {
+        let db = test_fixtures::standard_db();
+        let expired = "Download link expired — request a fresh export";
+        for id in ["fixture_memory_2", "fixture_memory_3"] {
+            db.update_memory_status(id, DownloadStatus::Failed, None, Some(expired))
+                .unwrap();
+        }
+        db.update_memory_status("fixture_memory_4", DownloadStatus::Failed, None, None)
+            .unwrap();
+        assert_eq!(
+            db.get_memory("fixture_memory_2").unwrap().failure_reason.as_deref(),
+            Some(expired)
+        );
+
+        let group = |reason: &str, count| DownloadFailureGroup {
+            reason: reason.to_string(),
+            count,
+        };
+        assert_eq!(
+            db.get_download_failure_summary().unwrap(),
+            vec![group(expired, 2), group("Unknown", 1)]
+        );
+
+        // A later attempt clears the reason, and a fresh export's links replace it
+        db.update_memory_status("fixture_memory_2", DownloadStatus::Downloading, None, None)
+            .unwrap();
+        assert_eq!(db.get_memory("fixture_memory_2").unwrap().failure_reason, None);
+        db.batch_insert_memories(&test_fixtures::memories()).unwrap();
+        let memory = db.get_memory("fixture_memory_3").unwrap();
+        assert_eq!(
+            (memory.download_status, memory.failure_reason),
+            (DownloadStatus::Pending, None)
+        );
+        assert!(db.get_download_failure_summary().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_verify_downloaded_memories_requeues_missing_and_undersized_files() {
+        let db = test_fixtures::standard_db();
+        let dir = tempfile::tempdir().unwrap();
+        let (photo, error_page) = (dir.path().join("photo.jpg"), dir.path().join("error.jpg"));
+        std::fs::write(&photo, vec![0u8; 4096]).unwrap();
+        std::fs::write(&error_page, b"<html>expired</html>").unwrap();
+        db.update_memory_status("fixture_memory_0", DownloadStatus::Downloaded, Some(&photo), None)
+            .unwrap();
+        db.update_memory_status("fixture_memory_1", DownloadStatus::Downloaded, Some(&error_page), None)
+            .unwrap();
+        // Downloaded to a folder that has since moved
+        db.update_memory_status(
+            "fixture_memory_2",
+            DownloadStatus::Downloaded,
+            Some(Path::new("/gone/memory_2.jpg")),
+            None,
+        )
+        .unwrap();
+
+        let mut reports = Vec::new();
+        let result = db
+            .verify_downloaded_memories(1024, |p| reports.push(p.clone()))
+            .unwrap();
+        assert_eq!(
+            result,
+            DownloadVerification {
+                checked: 3,
+                total: 3,
+                ok: 1,
+                missing: 1,
+                undersized: 1,
+                done: true
+            }
+        );
+        assert_eq!(reports.first().map(|p| (p.total, p.checked)), Some((3, 0)));
+        assert_eq!(reports.last(), Some(&result));
+
+        let status = |id| db.get_memory(id).unwrap().download_status;
+        assert_eq!(status("fixture_memory_0"), DownloadStatus::Downloaded);
+        assert_eq!(status("fixture_memory_1"), DownloadStatus::Pending);
+        assert_eq!(status("fixture_memory_2"), DownloadStatus::Pending);
+        assert_eq!(db.get_memory("fixture_memory_0").unwrap().media_path, Some(photo));
+        assert_eq!(db.get_memory("fixture_memory_1").unwrap().media_path, None);
+        assert_eq!(db.get_memory("fixture_memory_2").unwrap().media_path, None);
+        assert_eq!(db.verify_downloaded_memories(1024, |_| {}).unwrap().total, 1);
+    }
+
+    #[test]
+    fn test_concurrent_memory_status_updates_lose_no_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(DatabaseManager::new(&dir.path().join("index.db")).unwrap());
+        test_fixtures::populate_standard(&db).unwrap();
+        let ids: Vec<String> = test_fixtures::memories().into_iter().map(|m| m.id).collect();
+
+        // One thread walks every memory through a download while the other keeps relinking the
+        // first memory's file; each must keep the columns the other one wrote
+        let downloads = std::thread::spawn({
+            let (db, ids) = (db.clone(), ids.clone());
+            move || -> AppResult<()> {
+                for round in 0..20 {
+                    for id in &ids {
+                        db.update_memory_status(id, DownloadStatus::Downloading, None, None)?;
+                        let reason = format!("round {}", round);
+                        db.update_memory_status(id, DownloadStatus::Failed, None, Some(&reason))?;
+                    }
+                }
+                Ok(())
+            }
+        });
+        let relinks = std::thread::spawn({
+            let db = db.clone();
+            move || -> AppResult<PathBuf> {
+                let mut current = PathBuf::from("/fixtures/memories/memory_0.jpg");
+                for round in 0..40 {
+                    let moved = PathBuf::from(format!("/relinked/{}/memory_0.jpg", round));
+                    let op = db.begin_fs_operation("relocate", &current, &moved)?;
+                    assert_eq!(db.finish_fs_operation(&op)?, 1);
+                    current = moved;
+                }
+                Ok(current)
+            }
+        });
+        downloads.join().unwrap().unwrap();
+        let relinked = relinks.join().unwrap().unwrap();
+
+        for id in &ids {
+            let memory = db.get_memory(id).unwrap();
+            assert_eq!(memory.download_status, DownloadStatus::Failed, "{}", id);
+            assert_eq!(memory.failure_reason.as_deref(), Some("round 19"), "{}", id);
+        }
+        assert_eq!(db.get_memory(&ids[0]).unwrap().media_path, Some(relinked));
+        assert_eq!(
+            db.get_memory(&ids[1]).unwrap().media_path,
+            Some(PathBuf::from("/fixtures/memories/memory_1.jpg"))
+        );
+    }
+
+    #[test]
+    fn test_committed_writes_are_tagged_with_their_domains() {
+        use crate::models::DataDomain;
+        let db = DatabaseManager::new_in_memory().unwrap();
+        assert_eq!(db.changes().take_settled(Duration::ZERO), None);
+
+        crate::test_fixtures::populate_standard(&db).unwrap();
+        assert_eq!(
+            db.changes().take_settled(Duration::ZERO),
+            Some(vec![
+                DataDomain::Conversations,
+                DataDomain::Events,
+                DataDomain::Memories,
+                DataDomain::Media
+            ])
+        );
+
+        let memory = &crate::test_fixtures::memories()[0];
+        db.update_memory_status(&memory.id, DownloadStatus::Downloaded, None, None)
+            .unwrap();
+        db.set_setting("theme", "dark").unwrap();
+        assert_eq!(
+            db.changes().take_settled(Duration::ZERO),
+            Some(vec![DataDomain::Memories, DataDomain::Settings])
+        );
+
+        // A rolled-back transaction changed nothing
+        let mut conn = db.write_conn().unwrap();
+        let tx = conn.transaction().unwrap();
+        tx.execute("DELETE FROM memories", []).unwrap();
+        drop(tx);
+        drop(conn);
+        assert_eq!(db.changes().take_settled(Duration::ZERO), None);
+        assert_eq!(
+            db.get_memories(None).unwrap().len(),
+            crate::test_fixtures::memories().len()
+        );
+    }
+
+    #[test]
+    fn test_busy_reads_are_retried_then_reported_as_busy() {
+        let db = DatabaseManager::new_in_memory().unwrap();
+        let busy = || AppError::from(rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(5), None));
+        let attempts = std::cell::Cell::new(0);
+        let value = db
+            .read_retrying("flaky", |conn| {
+                attempts.set(attempts.get() + 1);
+                if attempts.get() < 3 {
+                    return Err(busy());
+                }
+                Ok(conn.query_row("SELECT 7", [], |r| r.get::<_, i32>(0))?)
+            })
+            .unwrap();
+        assert_eq!((value, attempts.get()), (7, 3));
+
+        attempts.set(0);
+        let err = db
+            .read_retrying("stuck", |_| -> AppResult<()> {
+                attempts.set(attempts.get() + 1);
+                Err(busy())
+            })
+            .unwrap_err();
+        assert_eq!(attempts.get(), READ_RETRIES + 1);
+        assert!(err.to_string().starts_with("DatabaseBusy: stuck"));
+        assert!(matches!(
+            db.read_retrying("bad", |conn| Ok(conn.execute_batch("SELECT * FROM missing")?)),
+            Err(AppError::Sqlite(_))
+        ));
+    }
+
+    #[test]
+    fn test_concurrent_writers_and_readers_never_see_busy() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(DatabaseManager::new(&dir.path().join("index.db")).unwrap());
+        test_fixtures::populate_standard(&db).unwrap();
+
+        let mut handles = Vec::new();
+        for writer in 0..3 {
+            let db = db.clone();
+            handles.push(std::thread::spawn(move || -> AppResult<()> {
+                for round in 0..20 {
+                    let events: Vec<Event> = test_fixtures::events()
+                        .into_iter()
+                        .map(|mut e| {
+                            e.id = format!("{}_w{}_{}", e.id, writer, round);
+                            e
+                        })
+                        .collect();
+                    db.batch_insert_events(&events, test_fixtures::EXPORT_ID)?;
+                    db.update_memory_status("fixture_memory_0", DownloadStatus::Downloading, None, None)?;
+                }
+                Ok(())
+            }));
+        }
+        for _ in 0..4 {
+            let db = db.clone();
+            handles.push(std::thread::spawn(move || -> AppResult<()> {
+                for _ in 0..40 {
+                    db.get_conversations(None)?;
+                    db.get_messages_page("group_weekend", 0, 50, false, None)?;
+                    db.get_unified_media_stream(50, 0, MediaDirection::All, None)?;
+                    db.get_memories_page(50, 0, &MemoryFilter::default())?;
+                }
+                Ok(())
+            }));
+        }
+        handles.push(std::thread::spawn({
+            let db = db.clone();
+            move || -> AppResult<()> {
+                for _ in 0..10 {
+                    db.maintain(0)?;
+                }
+                Ok(())
+            }
+        }));
+
+        for handle in handles {
+            handle.join().unwrap().unwrap();
+        }
+        assert_eq!(
+            db.get_export_stats(None).unwrap().total_messages as usize,
+            test_fixtures::EVENT_COUNT * 61
+        );
+    }
+
+    #[test]
+    fn test_maintenance_waits_for_ingestion_and_checkpoints_wal() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = DatabaseManager::new(&dir.path().join("index.db")).unwrap();
+        test_fixtures::populate_standard(&db).unwrap();
+
+        let run = db.begin_ingestion_run("second_export").unwrap();
+        let copy = run.clone();
+        drop(run);
+        assert!(db.maintain(0).unwrap().skipped_for_ingestion);
+        drop(copy);
+
+        let report = db.maintain(0).unwrap();
+        assert!(!report.skipped_for_ingestion);
+        assert!(report.wal_bytes > 0 && report.checkpointed_frames > 0);
+        assert_eq!(report.connections_failed, 0);
+        // Below the threshold nothing is checkpointed
+        assert_eq!(db.run_maintenance().unwrap().checkpointed_frames, 0);
+
+        let info = db.get_database_info().unwrap();
+        assert_eq!(info.schema_version, SCHEMA_VERSION);
+        assert!(info.file_bytes > 0 && !info.read_only);
+        assert!(info.last_maintenance.is_some_and(|m| m.checkpointed_frames == 0));
+    }
+
+    #[test]
+    fn test_user_data_round_trips_through_a_fresh_database() {
+        let db = test_fixtures::standard_db();
+        let trip = db.create_tag("Trip", Some("#ff0000")).unwrap();
+        db.create_tag("Unused", None).unwrap();
+        db.tag_entity(trip.id, TagEntityType::Event, "fixture_event_000")
+            .unwrap();
+        db.tag_entity(trip.id, TagEntityType::Conversation, "bob").unwrap();
+        db.redact_sender("carol").unwrap();
+        db.save_search("Pizza", "pizza", None).unwrap();
+        db.record_search("tonight", None).unwrap();
+        db.set_setting("parse_threads", "4").unwrap();
+        db.set_setting("storage_path", "/data/snap").unwrap();
+
+        let exported = db.export_user_data().unwrap();
+        assert!(!exported.settings.contains_key("storage_path"));
+        let file = serde_json::to_string(&exported).unwrap();
+        let data: UserData = serde_json::from_str(&file).unwrap();
+
+        // A reset followed by a reimport of the same export
+        let fresh = test_fixtures::standard_db();
+        let before = fresh.get_export_stats(None).unwrap().total_messages;
+        let preview = fresh
+            .import_user_data(&data, UserDataConflictPolicy::KeepExisting, true)
+            .unwrap();
+        assert!(preview.dry_run && preview.events_redacted > 0);
+        assert_eq!((preview.tags_added, preview.taggings_added), (2, 2));
+        assert!(fresh.get_tags().unwrap().is_empty());
+        assert_eq!(fresh.get_export_stats(None).unwrap().total_messages, before);
+
+        let summary = fresh
+            .import_user_data(&data, UserDataConflictPolicy::KeepExisting, false)
+            .unwrap();
+        assert_eq!(
+            summary,
+            UserDataImportSummary {
+                dry_run: false,
+                ..preview
+            }
+        );
+        let restored = fresh.export_user_data().unwrap();
+        assert_eq!(restored.tags, exported.tags);
+        assert_eq!(restored.redactions, exported.redactions);
+        assert_eq!(restored.saved_searches, exported.saved_searches);
+        assert_eq!(restored.settings, exported.settings);
+        assert_eq!(
+            fresh.get_export_stats(None).unwrap().total_messages,
+            db.get_export_stats(None).unwrap().total_messages
+        );
+
+        // Importing twice adds nothing; differing entries follow the policy
+        fresh.save_search("Pizza", "pasta", None).unwrap();
+        fresh.set_setting("parse_threads", "2").unwrap();
+        let again = fresh
+            .import_user_data(&data, UserDataConflictPolicy::KeepExisting, false)
+            .unwrap();
+        assert_eq!(again.conflicts_kept, 2);
+        assert_eq!(again.tags_added + again.taggings_added + again.saved_searches_added, 0);
+        assert_eq!(fresh.get_setting("parse_threads").unwrap().as_deref(), Some("2"));
+        let overwritten = fresh
+            .import_user_data(&data, UserDataConflictPolicy::Overwrite, false)
+            .unwrap();
+        assert_eq!(
+            (overwritten.saved_searches_updated, overwritten.settings_updated),
+            (1, 1)
+        );
+        assert_eq!(fresh.get_setting("parse_threads").unwrap().as_deref(), Some("4"));
+
+        let future = UserData {
+            version: USER_DATA_VERSION + 1,
+            ..data
+        };
+        assert!(matches!(
+            fresh.import_user_data(&future, UserDataConflictPolicy::Overwrite, true),
+            Err(AppError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn test_shared_locations_listed_and_counted_per_conversation() {
+        let db = test_fixtures::standard_db();
+        let mut events: Vec<Event> = test_fixtures::events()
+            .into_iter()
+            .filter(|e| e.event_type == "TEXT")
+            .take(3)
+            .collect();
+        events[0].content = Some("https://map.snapchat.com/@51.5074,-0.1278,12z".to_string());
+        events[1].content = Some("pin: 48.8584, 2.2945".to_string());
+        events[2].content = Some("see you at 12.5, 13.5".to_string());
+        assert_eq!(crate::ingestion::locations::tag_shared_locations(&mut events), 2);
+        db.batch_insert_events(&events, test_fixtures::EXPORT_ID).unwrap();
+
+        let all = db.get_shared_locations(None).unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(
+            (all[0].event_id.as_str(), all[0].lat, all[0].lon),
+            (events[0].id.as_str(), 51.5074, -0.1278)
+        );
+        assert_eq!(all[1].sender, events[1].sender);
+
+        let first_chat = events[0].conversation_id.as_deref().unwrap();
+        let in_chat = db.get_shared_locations(Some(first_chat)).unwrap();
+        let convo = db
+            .get_conversations(None)
+            .unwrap()
+            .into_iter()
+            .find(|c| c.id == first_chat)
+            .unwrap();
+        assert_eq!(convo.shared_location_count as usize, in_chat.len());
+        assert!(in_chat.iter().all(|l| l.conversation_id.as_deref() == Some(first_chat)));
+    }
+
+    #[test]
+    fn test_saved_messages_filter_and_counts() {
+        let db = test_fixtures::standard_db();
+        let convos = db.get_conversations(None).unwrap();
+        let saved: HashMap<&str, i32> = convos.iter().map(|c| (c.id.as_str(), c.saved_count)).collect();
+        assert_eq!(saved, HashMap::from([("alice", 2), ("bob", 3), ("group_weekend", 1)]));
+
+        let page = db.get_messages_page("bob", 0, 50, true, None).unwrap();
+        let ids: Vec<&str> = page.messages.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["fixture_event_002", "fixture_event_018", "fixture_event_042"]);
+        assert_eq!(page.total_count, 3);
+        assert!(page
+            .messages
+            .iter()
+            .all(|m| m.metadata.as_deref().is_some_and(|m| m.contains("Screenshot Count"))));
+        assert_eq!(db.get_messages_page("bob", 0, 50, false, None).unwrap().total_count, 20);
+
+        // Databases from before the column existed are backfilled from metadata
+        db.write_conn()
+            .unwrap()
+            .execute_batch("DROP INDEX idx_events_saved; ALTER TABLE events DROP COLUMN saved;")
+            .unwrap();
+        db.run_migrations().unwrap();
+        assert_eq!(db.get_messages_page("alice", 0, 50, true, None).unwrap().total_count, 2);
+    }
+
+    #[test]
+    fn test_stored_conversation_counts_match_counting_events() {
+        let db = test_fixtures::standard_db();
+        let counted = || -> Vec<(String, i32, bool, i32, i32)> {
+            let conn = db.conn().unwrap();
+            let mut stmt = conn
+                .prepare(
+                    "SELECT c.id,
+                     (SELECT COUNT(*) FROM events e WHERE e.conversation_id = c.id),
+                     (SELECT COUNT(*) FROM events e WHERE e.conversation_id = c.id
+                        AND e.media_references != '[]' AND e.media_references IS NOT NULL) > 0,
+                     (SELECT COUNT(*) FROM events e WHERE e.conversation_id = c.id AND e.saved = 1),
+                     (SELECT COUNT(*) FROM events e WHERE e.conversation_id = c.id
+                        AND instr(e.metadata, '\"shared_location\"') > 0)
+                     FROM conversations c ORDER BY c.id",
+                )
+                .unwrap();
+            stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?)))
+                .unwrap()
+                .collect::<std::result::Result<_, _>>()
+                .unwrap()
+        };
+        let stored = || -> Vec<(String, i32, bool, i32, i32)> {
+            let mut convos = db.get_conversations(None).unwrap();
+            convos.sort_by(|a, b| a.id.cmp(&b.id));
+            convos
+                .into_iter()
+                .map(|c| {
+                    (
+                        c.id,
+                        c.message_count,
+                        c.has_media,
+                        c.saved_count,
+                        c.shared_location_count,
+                    )
+                })
+                .collect()
+        };
+        assert_eq!(stored(), counted());
+        assert!(stored().iter().all(|(_, messages, _, _, _)| *messages > 0));
+        assert!(stored()
+            .iter()
+            .any(|(_, _, has_media, saved, _)| *has_media && *saved > 0));
+
+        // Writing stored events again moves their counts instead of adding to them
+        let mut rewritten = test_fixtures::events();
+        rewritten[2].conversation_id = Some("alice".into());
+        rewritten[2].metadata = Some(r#"{"shared_location": {"lat": 1.0, "lon": 2.0}}"#.into());
+        let linked = std::mem::take(&mut rewritten[9].media_references);
+        db.batch_insert_events(&rewritten[..10], test_fixtures::EXPORT_ID)
+            .unwrap();
+        assert_eq!(stored(), counted());
+        rewritten[9].media_references = linked;
+        db.update_event_media_references(&rewritten[9..10]).unwrap();
+        assert_eq!(stored(), counted());
+
+        db.redact_events(&["fixture_event_000".to_string(), "fixture_event_001".to_string()])
+            .unwrap();
+        assert_eq!(stored(), counted());
+
+        // Databases from before the columns existed are counted once on upgrade
+        db.write_conn()
+            .unwrap()
+            .execute_batch(
+                "ALTER TABLE conversations DROP COLUMN message_count;
+                 ALTER TABLE conversations DROP COLUMN linked_media_count;
+                 ALTER TABLE conversations DROP COLUMN saved_count;
+                 ALTER TABLE conversations DROP COLUMN shared_location_count;",
+            )
+            .unwrap();
+        db.run_migrations().unwrap();
+        assert_eq!(stored(), counted());
+    }
+
+    #[test]
+    fn test_activity_timeline_counts_per_bucket() {
+        use chrono::Datelike;
+        let db = test_fixtures::standard_db();
+        let messages = db.get_messages_page("alice", 0, 2000, false, None).unwrap().messages;
+        let mut weeks: BTreeMap<String, i32> = BTreeMap::new();
+        for m in &messages {
+            let date = m.timestamp.date_naive();
+            let monday = date - chrono::Duration::days(date.weekday().num_days_from_monday() as i64);
+            *weeks.entry(monday.format("%Y-%m-%d").to_string()).or_default() += 1;
+        }
+        let expected: Vec<ActivityBucket> = weeks
+            .into_iter()
+            .map(|(bucket_start, count)| ActivityBucket { bucket_start, count })
+            .collect();
+        assert_eq!(
+            db.get_activity_timeline(Some("alice"), TimelineBucket::Week, None)
+                .unwrap(),
+            expected
+        );
+
+        let total = db.get_export_stats(None).unwrap().total_messages;
+        for bucket in [TimelineBucket::Day, TimelineBucket::Month] {
+            let timeline = db.get_activity_timeline(None, bucket, None).unwrap();
+            assert_eq!(timeline.iter().map(|b| b.count).sum::<i32>(), total);
+            assert!(timeline.windows(2).all(|w| w[0].bucket_start < w[1].bucket_start));
+            assert!(timeline.iter().all(|b| b.count > 0));
+        }
+        let months = db.get_activity_timeline(None, TimelineBucket::Month, None).unwrap();
+        assert!(months
+            .iter()
+            .all(|b| b.bucket_start.len() == 10 && b.bucket_start.ends_with("-01")));
+        assert!(db
+            .get_activity_timeline(None, TimelineBucket::Day, Some("other_export"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_activity_heatmap_by_local_hour_and_direction() {
+        let db = test_db();
+        db.insert_export(&test_fixtures::export()).unwrap();
+        db.batch_insert_conversations(&test_fixtures::conversations()).unwrap();
+        db.set_owner_profile(&OwnerProfile {
+            username: "me".to_string(),
+            display_name: None,
+        })
+        .unwrap();
+        let event = |id: &str, timestamp: &str, sender: &str, metadata: Option<&str>| Event {
+            id: id.to_string(),
+            timestamp: DateTime::parse_from_rfc3339(timestamp).unwrap().with_timezone(&Utc),
+            sender: sender.to_string(),
+            sender_name: None,
+            conversation_id: Some("alice".to_string()),
+            content: Some("hi".to_string()),
+            event_type: "TEXT".to_string(),
+            media_references: Vec::new(),
+            metadata: metadata.map(str::to_string),
+        };
+        // 2024-03-04 is a Monday
+        db.batch_insert_events(
+            &[
+                event("mon_09_owner", "2024-03-04T09:15:00Z", "me", None),
+                event(
+                    "mon_09_flag",
+                    "2024-03-04T09:45:00Z",
+                    "phone",
+                    Some(r#"{"is_sender": true}"#),
+                ),
+                event(
+                    "mon_09_in",
+                    "2024-03-04T09:30:00Z",
+                    "alice",
+                    Some(r#"{"is_sender": false}"#),
+                ),
+                event("sun_23_in", "2024-03-10T23:30:00Z", "alice", None),
+            ],
+            test_fixtures::EXPORT_ID,
+        )
+        .unwrap();
+
+        let heatmap = db.get_activity_heatmap(None, None).unwrap();
+        assert_eq!(heatmap.sent[0][9], 2);
+        assert_eq!(heatmap.received[0][9], 1);
+        assert_eq!(heatmap.received[6][23], 1);
+        assert_eq!(heatmap.sent.iter().flatten().sum::<i32>(), 2);
+
+        // Two hours ahead of UTC, Sunday 23:30 is Monday 01:30
+        db.set_timezone_offset(120).unwrap();
+        let heatmap = db.get_activity_heatmap(Some("alice"), None).unwrap();
+        assert_eq!(heatmap.timezone_offset_minutes, 120);
+        assert_eq!(heatmap.sent[0][11], 2);
+        assert_eq!(heatmap.received[0][1], 1);
+        assert_eq!(heatmap.received[6].iter().sum::<i32>(), 0);
+        assert!(db
+            .get_activity_heatmap(Some("bob"), None)
+            .unwrap()
+            .received
+            .iter()
+            .flatten()
+            .all(|&c| c == 0));
+        assert!(matches!(db.set_timezone_offset(15 * 60), Err(AppError::Validation(_))));
+    }
+
+    #[test]
+    fn test_conversations_page_sorts_filters_and_counts() {
+        let db = test_fixtures::standard_db();
+        let ids = |page: ConversationPage| page.items.into_iter().map(|c| c.id).collect::<Vec<_>>();
+
+        let by_name = db
+            .get_conversations_page(0, 2, ConversationSort::Name, None, None)
+            .unwrap();
+        assert_eq!((by_name.total_count, by_name.has_more), (3, true));
+        assert_eq!(ids(by_name), ["alice", "bob"]);
+        let rest = db
+            .get_conversations_page(2, 2, ConversationSort::Name, None, None)
+            .unwrap();
+        assert!(!rest.has_more);
+        assert_eq!(ids(rest), ["group_weekend"]);
+
+        let page = db
+            .get_conversations_page(0, 10, ConversationSort::MessageCount, None, None)
+            .unwrap();
+        assert!(page.items.windows(2).all(|w| w[0].message_count >= w[1].message_count));
+
+        // The stored name and the name resolved from people both match
+        db.write_conn()
+            .unwrap()
+            .execute("UPDATE people SET display_name = 'Robert' WHERE username = 'bob'", [])
+            .unwrap();
+        let filtered = |filter: &str| {
+            ids(db
+                .get_conversations_page(0, 10, ConversationSort::LastActivity, Some(filter), None)
+                .unwrap())
+        };
+        assert_eq!(filtered("weekend"), ["group_weekend"]);
+        assert_eq!(filtered("ROB"), ["bob"]);
+        assert!(filtered("100%").is_empty());
+        let page = db
+            .get_conversations_page(0, 10, ConversationSort::LastActivity, Some("rob"), None)
+            .unwrap();
+        assert_eq!((page.total_count, page.has_more), (1, false));
+    }
+
+    #[test]
+    fn test_messages_page_filters_by_event_type() {
+        let db = test_fixtures::standard_db();
+        let all = db.get_messages_page("alice", 0, 2000, false, None).unwrap().messages;
+        // Turn some of alice's text messages into snaps, between the remaining TEXT events
+        {
+            let conn = db.write_conn().unwrap();
+            for (i, event) in all.iter().filter(|m| m.event_type == "TEXT").enumerate().take(12) {
+                let event_type = match i % 4 {
+                    0 => "SNAP",
+                    2 => "SNAP_VIDEO",
+                    _ => continue,
+                };
+                conn.execute(
+                    "UPDATE events SET event_type = ?1 WHERE id = ?2",
+                    params![event_type, event.id],
+                )
+                .unwrap();
+            }
+        }
+
+        let types: Vec<String> = ["SNAP", "SNAP_VIDEO", "MEDIA"].map(String::from).to_vec();
+        let expected: Vec<String> = db
+            .get_messages_page("alice", 0, 2000, false, None)
+            .unwrap()
+            .messages
+            .into_iter()
+            .filter(|m| types.contains(&m.event_type))
+            .map(|m| m.id)
+            .collect();
+        assert!(expected.len() > 4 && expected.len() < all.len());
+
+        let first = db.get_messages_page("alice", 0, 4, false, Some(&types)).unwrap();
+        assert_eq!(first.total_count as usize, expected.len());
+        assert!(first.has_more);
+        let ids: Vec<String> = first.messages.into_iter().map(|m| m.id).collect();
+        assert_eq!(ids, expected[..4]);
+
+        let last = db.get_messages_page("alice", 4, 2000, false, Some(&types)).unwrap();
+        assert!(!last.has_more);
+        let ids: Vec<String> = last.messages.into_iter().map(|m| m.id).collect();
+        assert_eq!(ids, expected[4..]);
+
+        // An empty list is no filter
+        let unfiltered = db.get_messages_page("alice", 0, 1, false, Some(&[])).unwrap();
+        assert_eq!(unfiltered.total_count as usize, all.len());
+    }
+
+    #[test]
+    fn test_media_sizes_come_from_cache_or_a_bounded_stat() {
+        let db = test_fixtures::standard_db();
+        let page = || {
+            db.get_unified_media_stream(100, 0, MediaDirection::All, None)
+                .unwrap()
+                .items
+        };
+        // Fixture media paths don't exist on disk
+        let mut items = page();
+        assert!(items.iter().all(|m| m.size_bytes.is_none()));
+
+        let slow_stat = |_: &Path| {
+            std::thread::sleep(Duration::from_millis(20));
+            Some(1234)
+        };
+        db.fill_media_sizes(&mut items, Duration::ZERO, slow_stat);
+        assert!(items.iter().all(|m| m.size_bytes.is_none()));
+        db.fill_media_sizes(&mut items, Duration::from_millis(50), slow_stat);
+        let sized: Vec<String> = items
+            .iter()
+            .filter(|m| m.size_bytes.is_some())
+            .map(|m| m.id.clone())
+            .collect();
+        assert!(!sized.is_empty() && sized.len() < items.len(), "{:?}", sized);
+
+        // Sizes read under budget are served from the cache from now on
+        let cached: Vec<String> = page()
+            .into_iter()
+            .filter(|m| m.size_bytes == Some(1234))
+            .map(|m| m.id)
+            .collect();
+        assert_eq!(cached, sized);
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("clip.mp4");
+        std::fs::write(&file, b"12345").unwrap();
+        assert_eq!(db.get_media_info(&file).unwrap().size_bytes, 5);
+        assert!(matches!(
+            db.get_media_info(&dir.path().join("gone.mp4")),
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_media_stream_direction_filter() {
+        let db = test_fixtures::standard_db();
+        // Media events are all odd-numbered, so sent by friends; mark one as the owner's
+        db.write_conn()
+            .unwrap()
+            .execute_batch(
+                "UPDATE events SET metadata = '{\"is_sender\": true}' WHERE id = 'fixture_event_009';
+                 UPDATE events SET metadata = '{\"is_sender\": false}' WHERE id = 'fixture_event_019';",
+            )
+            .unwrap();
+
+        let ids = |direction| {
+            let page = db.get_unified_media_stream(100, 0, direction, None).unwrap();
+            assert_eq!(page.total_count as usize, page.items.len());
+            let mut ids: Vec<String> = page.items.into_iter().map(|m| m.id).collect();
+            ids.sort();
+            ids
+        };
+        let all = ids(MediaDirection::All);
+        assert_eq!(all.len(), 7);
+        assert_eq!(
+            ids(MediaDirection::Sent),
+            ["fixture_event_009", "fixture_memory_0", "fixture_memory_1"]
+        );
+        let received = ids(MediaDirection::Received);
+        assert_eq!(received.len(), 4);
+        assert!(received.contains(&"fixture_event_019".to_string()));
+
+        let entry = |id: &str| {
+            db.get_unified_media_stream(100, 0, MediaDirection::All, None)
+                .unwrap()
+                .items
+                .into_iter()
+                .find(|m| m.id == id)
+                .unwrap()
+        };
+        assert_eq!(entry("fixture_memory_0").direction, MediaDirection::Sent);
+        assert_eq!(entry("fixture_event_019").direction, MediaDirection::Received);
+
+        // Memories never show up in a conversation
+        let group = db
+            .get_conversation_media("group_weekend", 100, 0, MediaDirection::Sent)
+            .unwrap();
+        assert_eq!(group.total_count, 1);
+        assert_eq!(group.items[0].id, "fixture_event_009");
+        let alice = db.get_conversation_media("alice", 100, 0, MediaDirection::All).unwrap();
+        assert_eq!(alice.total_count, 0);
+
+        // Every file of a message with several is listed, in the message's order
+        let mut album = test_fixtures::events().remove(0);
+        album.event_type = "MEDIA".to_string();
+        album.media_references = ["c.jpg", "a.jpg", "b.jpg"]
+            .iter()
+            .map(|f| PathBuf::from(format!("/media/{}", f)))
+            .collect();
+        db.batch_insert_events(&[album], test_fixtures::EXPORT_ID).unwrap();
+        let alice = db.get_conversation_media("alice", 2, 0, MediaDirection::All).unwrap();
+        assert_eq!(alice.total_count, 3);
+        let next = db.get_conversation_media("alice", 2, 2, MediaDirection::All).unwrap();
+        let files: Vec<(PathBuf, i32)> = alice
+            .items
+            .into_iter()
+            .chain(next.items)
+            .map(|m| (m.path, m.media_index))
+            .collect();
+        assert_eq!(
+            files,
+            [
+                (PathBuf::from("/media/c.jpg"), 0),
+                (PathBuf::from("/media/a.jpg"), 1),
+                (PathBuf::from("/media/b.jpg"), 2)
+            ]
+        );
+        assert_eq!(
+            db.get_unified_media_stream(100, 0, MediaDirection::All, None)
+                .unwrap()
+                .total_count,
+            10
+        );
+    }
+
+    #[test]
+    fn test_media_stream_filters() {
+        let db = test_fixtures::standard_db();
+        // Media events sit at base + 9h, 19h, ... 49h, all in group_weekend; memories with files
+        // at base (an image) and base + 24h (a video)
+        db.write_conn()
+            .unwrap()
+            .execute(
+                "UPDATE events SET event_type = 'SNAP_VIDEO' WHERE id = 'fixture_event_019'",
+                [],
+            )
+            .unwrap();
+        let base = test_fixtures::base_time();
+
+        let ids = |filter: MediaFilter| {
+            let page = db
+                .get_unified_media_stream(100, 0, MediaDirection::All, Some(&filter))
+                .unwrap();
+            assert_eq!(page.total_count as usize, page.items.len());
+            let mut ids: Vec<String> = page.items.into_iter().map(|m| m.id).collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(ids(MediaFilter::default()).len(), 7);
+        assert_eq!(
+            ids(MediaFilter {
+                source: Some("cloud".to_string()),
+                ..Default::default()
+            }),
+            ["fixture_memory_0", "fixture_memory_1"]
+        );
+        assert_eq!(
+            ids(MediaFilter {
+                source: Some("local".to_string()),
+                ..Default::default()
+            })
+            .len(),
+            5
+        );
+        assert_eq!(
+            ids(MediaFilter {
+                media_type: Some("Video".to_string()),
+                ..Default::default()
+            }),
+            ["fixture_event_019", "fixture_memory_1"]
+        );
+        assert_eq!(
+            ids(MediaFilter {
+                media_type: Some("Image".to_string()),
+                ..Default::default()
+            })
+            .len(),
+            5
+        );
+        assert_eq!(
+            ids(MediaFilter {
+                conversation_id: Some("group_weekend".to_string()),
+                ..Default::default()
+            })
+            .len(),
+            5
+        );
+        assert!(ids(MediaFilter {
+            conversation_id: Some("alice".to_string()),
+            ..Default::default()
+        })
+        .is_empty());
+        // Both bounds are inclusive
+        assert_eq!(
+            ids(MediaFilter {
+                after: Some(base + chrono::Duration::hours(19)),
+                before: Some(base + chrono::Duration::hours(29)),
+                ..Default::default()
+            }),
+            ["fixture_event_019", "fixture_event_029", "fixture_memory_1"]
+        );
+
+        let combined = MediaFilter {
+            source: Some("local".to_string()),
+            media_type: Some("Video".to_string()),
+            conversation_id: Some("group_weekend".to_string()),
+            after: Some(base + chrono::Duration::hours(10)),
+            before: None,
+        };
+        assert_eq!(ids(combined.clone()), ["fixture_event_019"]);
+        let page = db
+            .get_unified_media_stream(1, 0, MediaDirection::All, Some(&combined))
+            .unwrap();
+        assert_eq!((page.total_count, page.has_more), (1, false));
+        let videos = MediaFilter {
+            media_type: Some("Video".to_string()),
+            ..Default::default()
+        };
+        let page = db
+            .get_unified_media_stream(1, 0, MediaDirection::All, Some(&videos))
+            .unwrap();
+        assert_eq!((page.total_count, page.has_more), (2, true));
+        assert_eq!(page.items[0].media_type, "Video");
+    }
+
+    #[test]
+    fn test_export_stats_empty_db() {
+        let db = test_db();
+        db.insert_export(&ExportSet {
+            id: "e1".to_string(),
+            source_paths: vec![PathBuf::from("/tmp")],
+            source_type: ExportSourceType::Folder,
+            extraction_path: None,
+            creation_date: None,
+            validation_status: ValidationStatus::Valid,
+            parts: Vec::new(),
+            import_phases: None,
+            ignored_duplicates: Vec::new(),
+            layout: None,
+        })
+        .unwrap();
+        let stats = db.get_export_stats(None).unwrap();
+        assert_eq!(stats.total_messages, 0);
+        assert_eq!(stats.total_conversations, 0);
+    }
+
+    #[test]
+    fn test_insert_people_and_resolve() {
+        let db = test_db();
+        db.insert_export(&ExportSet {
+            id: "e1".to_string(),
+            source_paths: vec![PathBuf::from("/tmp")],
+            source_type: ExportSourceType::Folder,
+            extraction_path: None,
+            creation_date: None,
+            validation_status: ValidationStatus::Valid,
+            parts: Vec::new(),
+            import_phases: None,
+            ignored_duplicates: Vec::new(),
+            layout: None,
+        })
+        .unwrap();
+        let people = vec![Person {
+            username: "alice".to_string(),
+            display_name: Some("Alice Smith".to_string()),
+            avatar_path: None,
+            avatar_color: None,
+        }];
+        db.insert_people(&people).unwrap();
+
+        // Person name should resolve in conversations list
+        db.batch_insert_conversations(&[Conversation {
+            id: "alice".to_string(),
+            display_name: None,
+            participants: vec![],
+            last_event_at: None,
+            message_count: 0,
+            has_media: false,
+            media_count: 0,
+            media_bytes: 0,
+            missing_media_count: 0,
+            avatar_path: None,
+            avatar_color: None,
+            is_group: false,
+            listed_members: 0,
+            anomaly_flags: Vec::new(),
+            saved_count: 0,
+            shared_location_count: 0,
+            completeness: None,
+        }])
+        .unwrap();
+        let convos = db.get_conversations(None).unwrap();
+        assert_eq!(convos[0].display_name.as_deref(), Some("Alice Smith"));
+    }
+
+    #[test]
+    fn test_avatars_survive_people_reload_and_reach_conversations() {
+        let dir = tempfile::tempdir().unwrap();
+        let avatar = dir.path().join("alice.png");
+        std::fs::write(&avatar, b"png").unwrap();
+        let db = test_fixtures::standard_db();
+
+        let avatars = HashMap::from([
+            ("alice".to_string(), avatar.clone()),
+            ("bob".to_string(), dir.path().join("deleted.png")),
+            ("stranger".to_string(), avatar.clone()),
+        ]);
+        db.set_avatar_paths(&avatars).unwrap();
+        // Reloading friends.json must not wipe the avatars
+        db.insert_people(&test_fixtures::people()).unwrap();
+
+        let people = db.get_people().unwrap();
+        let alice = people.iter().find(|p| p.username == "alice").unwrap();
+        assert_eq!(alice.avatar_path.as_deref(), Some(avatar.as_path()));
+        assert_eq!(alice.display_name.as_deref(), Some("Alice"));
+        let bob = people.iter().find(|p| p.username == "bob").unwrap();
+        assert_eq!(bob.avatar_path, None);
+        assert_eq!(bob.avatar_color, Some(avatar_color("bob")));
+        assert!(people
+            .iter()
+            .any(|p| p.username == "stranger" && p.display_name.is_none()));
+
+        let conversations = db.get_conversations(None).unwrap();
+        let dm = conversations.iter().find(|c| c.id == "alice").unwrap();
+        assert_eq!(dm.avatar_path.as_deref(), Some(avatar.as_path()));
+        let group = conversations.iter().find(|c| c.id == "group_weekend").unwrap();
+        assert_eq!(group.avatar_path, None);
+        assert_eq!(group.avatar_color, Some(avatar_color("group_weekend")));
+    }
+
+    #[test]
+    fn test_validation_report() {
+        let db = test_db();
+        db.insert_export(&ExportSet {
+            id: "e1".to_string(),
+            source_paths: vec![PathBuf::from("/tmp")],
+            source_type: ExportSourceType::Folder,
+            extraction_path: None,
+            creation_date: None,
+            validation_status: ValidationStatus::Valid,
+            parts: Vec::new(),
+            import_phases: None,
+            ignored_duplicates: Vec::new(),
+            layout: None,
+        })
+        .unwrap();
+        let report = db.get_validation_report().unwrap();
+        assert_eq!(report.total_html_files, 0);
+        assert_eq!(report.media_missing, 0);
+    }
+
+    #[test]
+    fn test_verify_media_files_reports_and_remembers_missing_files() {
+        let db = test_fixtures::standard_db();
+        let report = db.get_validation_report().unwrap();
+        assert!(report.media_verified_at.is_none());
+        assert!(report.missing_files.is_empty());
+
+        // Five chat media files and two downloaded memories; one of each is gone
+        let missing = [
+            "/fixtures/media/2023-01-01_MEDIA019.jpg",
+            "/fixtures/memories/memory_1.jpg",
+        ];
+        let mut updates = Vec::new();
+        let report = db
+            .verify_media_files(
+                |path| !missing.contains(&path.to_str().unwrap()),
+                |p| updates.push(p.clone()),
+            )
+            .unwrap();
+        assert_eq!(report.missing_file_count, 2);
+        assert_eq!(report.missing_files, missing);
+        assert!(report.media_verified_at.is_some());
+        assert!(report.warnings.iter().any(|w| w.contains("2 linked media files")));
+        let last = updates.last().unwrap();
+        assert_eq!((last.checked, last.total, last.missing, last.done), (7, 7, 2, true));
+        assert!(updates[..updates.len() - 1].iter().all(|p| !p.done));
+
+        // Later reports return the stored result without scanning again
+        let stored = db.get_validation_report().unwrap();
+        assert_eq!(stored.missing_files, missing);
+        assert_eq!(stored.media_verified_at, report.media_verified_at);
+        assert_eq!(db.get_media_verification().unwrap().unwrap().files_checked, 7);
+    }
+
+    #[test]
+    fn test_unlinked_media_events_are_paged_and_updated() {
+        let db = test_fixtures::standard_db();
+        let group = test_fixtures::CONVERSATION_IDS[2];
+        let has_media = |db: &DatabaseManager| {
+            db.get_conversations(None)
+                .unwrap()
+                .into_iter()
+                .find(|c| c.id == group)
+                .unwrap()
+                .has_media
+        };
+        assert!(has_media(&db));
+
+        // Media messages imported before their files were found, and a text message naming media
+        let unlinked: Vec<Event> = test_fixtures::events()
+            .into_iter()
+            .filter(|e| e.event_type == "MEDIA" || e.id == "fixture_event_000")
+            .map(|mut e| {
+                e.media_references.clear();
+                e.metadata = Some(format!(r#"{{"media_ids": ["{}"]}}"#, e.id));
+                e
+            })
+            .collect();
+        db.batch_insert_events(&unlinked, test_fixtures::EXPORT_ID).unwrap();
+        assert!(!has_media(&db));
+
+        let mut pages = Vec::new();
+        let mut after = String::new();
+        loop {
+            let page = db.get_unlinked_media_events(&after, 2).unwrap();
+            let Some(last) = page.last() else { break };
+            after = last.id.clone();
+            pages.push(page.len());
+        }
+        assert_eq!(pages, [2, 2, 1]);
+
+        let mut linked = db.get_unlinked_media_events("", 1).unwrap();
+        linked[0].media_references = vec![PathBuf::from("/media/found.jpg")];
+        assert_eq!(db.update_event_media_references(&linked).unwrap(), 1);
+        assert!(has_media(&db));
+        assert_eq!(db.get_unlinked_media_events("", 10).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_zip_parts_survive_a_reload() {
+        let db = test_fixtures::standard_db();
+        let export = ExportSet {
+            source_type: ExportSourceType::Zip,
+            validation_status: ValidationStatus::Corrupted,
+            parts: vec![
+                crate::models::ExportPart {
+                    path: PathBuf::from("/exports/mydata~1.zip"),
+                    readable: true,
+                    error: None,
+                },
+                crate::models::ExportPart {
+                    path: PathBuf::from("/exports/mydata~2.zip"),
+                    readable: false,
+                    error: Some("invalid Zip archive".into()),
+                },
+            ],
+            ..test_fixtures::export()
+        };
+        db.insert_export(&export).unwrap();
+        assert_eq!(db.get_exports().unwrap()[0].parts, export.parts);
+
+        // Rows from before parts were stored load with none
+        db.write_conn()
+            .unwrap()
+            .execute_batch("ALTER TABLE exports DROP COLUMN parts;")
+            .unwrap();
+        db.run_migrations().unwrap();
+        assert!(db.get_exports().unwrap()[0].parts.is_empty());
+    }
+
+    #[test]
+    fn test_import_phases_round_trip_and_late_media_linking() {
+        let db = test_fixtures::standard_db();
+        // Rows written before phases were tracked count as fully imported
+        db.conn()
+            .unwrap()
+            .execute("UPDATE exports SET import_phases = NULL", [])
+            .unwrap();
+        assert_eq!(db.get_exports().unwrap()[0].import_phases, Some(ImportOptions::ALL));
+
+        let memories_only = ImportOptions {
+            memories: true,
+            location_history: true,
+            ..ImportOptions::NONE
+        };
+        db.set_import_phases(test_fixtures::EXPORT_ID, &memories_only).unwrap();
+        let done = db.get_exports().unwrap()[0].import_phases.unwrap();
+        assert_eq!(done, memories_only);
+        let todo = ImportOptions::ALL.remaining(&done);
+        assert!(todo.chats && todo.snap_history && todo.media_linking && !todo.memories);
+        assert_eq!(done.union(&todo), ImportOptions::ALL);
+
+        // Older rows recorded snap history under chats, and location history under memories
+        db.conn()
+            .unwrap()
+            .execute(
+                r#"UPDATE exports SET import_phases = '{"chats":false,"memories":true,"media_linking":false}'"#,
+                [],
+            )
+            .unwrap();
+        assert_eq!(db.get_exports().unwrap()[0].import_phases, Some(memories_only));
+
+        let mut unlinked = db.get_unlinked_events(test_fixtures::EXPORT_ID).unwrap();
+        assert_eq!(
+            unlinked.len(),
+            test_fixtures::EVENT_COUNT - test_fixtures::EVENT_COUNT / 10
+        );
+        assert!(db.get_unlinked_events("other_export").unwrap().is_empty());
+        unlinked[0].media_references = vec![PathBuf::from("/media/late.jpg")];
+        assert_eq!(db.update_event_media_references(&unlinked[..1]).unwrap(), 1);
+        assert_eq!(
+            db.get_unlinked_events(test_fixtures::EXPORT_ID).unwrap().len(),
+            unlinked.len() - 1
+        );
+    }
+
+    #[test]
+    fn test_export_events_by_conversation_and_own_sender() {
+        let db = test_fixtures::standard_db();
+        assert_eq!(
+            db.get_export_conversation_ids(test_fixtures::EXPORT_ID).unwrap(),
+            test_fixtures::CONVERSATION_IDS
+        );
+        assert!(db.get_export_conversation_ids("other_export").unwrap().is_empty());
+        let alice = db
+            .get_export_conversation_events(test_fixtures::EXPORT_ID, "alice")
+            .unwrap();
+        assert_eq!(alice.len(), test_fixtures::EVENT_COUNT * 2 / 5);
+        assert!(alice.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+        assert_eq!(
+            db.get_export_events_of_types(test_fixtures::EXPORT_ID, &["MEDIA"])
+                .unwrap()
+                .len(),
+            test_fixtures::EVENT_COUNT / 10
+        );
+
+        // Marked-as-sent messages name the owner; ties go to the alphabetically first sender
+        assert_eq!(db.most_frequent_own_sender(test_fixtures::EXPORT_ID).unwrap(), None);
+        let sent = |id: &str, sender: &str| Event {
+            id: id.to_string(),
+            sender: sender.to_string(),
+            metadata: Some(r#"{"is_sender":true}"#.to_string()),
+            ..alice[0].clone()
+        };
+        let events = [
+            sent("s1", "kody"),
+            sent("s2", "kody"),
+            sent("s3", "zed"),
+            sent("s4", "zed"),
+            sent("s5", ""),
+        ];
+        db.batch_insert_events(&events, test_fixtures::EXPORT_ID).unwrap();
+        assert_eq!(
+            db.most_frequent_own_sender(test_fixtures::EXPORT_ID)
+                .unwrap()
+                .as_deref(),
+            Some("kody")
+        );
+        assert_eq!(db.fill_own_sender(test_fixtures::EXPORT_ID, "kody").unwrap(), 1);
+        let filled = db
+            .get_export_conversation_events(test_fixtures::EXPORT_ID, "alice")
+            .unwrap();
+        assert_eq!(filled.iter().find(|e| e.id == "s5").unwrap().sender, "kody");
+    }
+
+    #[test]
+    fn test_anomalies_flag_conversations_and_raw_timestamps_survive() {
+        let db = test_fixtures::standard_db();
+        let anomaly = ConversationAnomaly {
+            conversation_id: "alice".to_string(),
+            kind: AnomalyKind::EpochTimestamps,
+            affected: 3,
+            total: 17,
+            detail: "3 messages are dated 1970 or earlier".to_string(),
+        };
+        db.replace_validation_issues(test_fixtures::EXPORT_ID, std::slice::from_ref(&anomaly))
+            .unwrap();
+        assert_eq!(db.get_validation_issues(Some("alice")).unwrap(), [anomaly]);
+        assert!(db.get_validation_issues(Some("bob")).unwrap().is_empty());
+
+        let conversations = db.get_conversations(None).unwrap();
+        let flags = |id: &str| conversations.iter().find(|c| c.id == id).unwrap().anomaly_flags.clone();
+        assert_eq!(flags("alice"), [AnomalyKind::EpochTimestamps]);
+        assert!(flags("bob").is_empty());
+
+        // A rerun replaces the export's findings
+        db.replace_validation_issues(test_fixtures::EXPORT_ID, &[]).unwrap();
+        assert!(db.get_validation_issues(None).unwrap().is_empty());
+
+        let conn = db.conn().unwrap();
+        conn.execute(
+            "UPDATE events SET timestamp = 'yesterday-ish', metadata = '{\"is_sender\": true}'
+             WHERE id = 'fixture_event_000'",
+            [],
+        )
+        .unwrap();
+        drop(conn);
+        let event = db
+            .get_messages("alice")
+            .unwrap()
+            .into_iter()
+            .find(|e| e.id == "fixture_event_000")
+            .unwrap();
+        assert_eq!(event.timestamp, DateTime::<Utc>::MIN_UTC);
+        let metadata: serde_json::Value = serde_json::from_str(event.metadata.as_deref().unwrap()).unwrap();
+        assert_eq!(metadata[RAW_TIMESTAMP_KEY], "yesterday-ish");
+        assert_eq!(metadata["is_sender"], true);
+    }
+
+    #[test]
+    fn test_in_memory_instances_are_isolated() {
+        let a = test_db();
+        let b = test_db();
+        test_fixtures::populate_standard(&a).unwrap();
+        assert_eq!(
+            a.get_export_stats(None).unwrap().total_messages,
+            test_fixtures::EVENT_COUNT as i32
+        );
+        assert_eq!(b.get_export_stats(None).unwrap().total_messages, 0);
+    }
+
+    #[test]
+    fn test_in_memory_matches_file_backed() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_db = DatabaseManager::new(&dir.path().join("index.db")).unwrap();
+        let mem_db = test_db();
+        test_fixtures::populate_standard(&file_db).unwrap();
+        test_fixtures::populate_standard(&mem_db).unwrap();
+
+        let file_stats = file_db.get_export_stats(None).unwrap();
+        let mem_stats = mem_db.get_export_stats(None).unwrap();
+        assert_eq!(file_stats.total_messages, mem_stats.total_messages);
+        assert_eq!(file_stats.total_conversations, mem_stats.total_conversations);
+        assert_eq!(file_stats.total_memories, mem_stats.total_memories);
+        assert_eq!(file_stats.top_contacts, mem_stats.top_contacts);
+
+        let file_convos = file_db.get_conversations(None).unwrap();
+        let mem_convos = mem_db.get_conversations(None).unwrap();
+        assert_eq!(file_convos.len(), test_fixtures::CONVERSATION_COUNT);
+        for (f, m) in file_convos.iter().zip(&mem_convos) {
+            assert_eq!(f.id, m.id);
+            assert_eq!(f.message_count, m.message_count);
+            assert_eq!(f.display_name, m.display_name);
+        }
+
+        let query = "pizza";
+        assert_eq!(
+            file_db.search_messages(query, 50, false, None).unwrap().len(),
+            mem_db.search_messages(query, 50, false, None).unwrap().len()
+        );
+    }
+
+    #[test]
+    fn test_standard_fixture_counts() {
+        let db = test_db();
+        test_fixtures::populate_standard(&db).unwrap();
+        let stats = db.get_export_stats(None).unwrap();
+        assert_eq!(stats.total_messages, test_fixtures::EVENT_COUNT as i32);
+        assert_eq!(stats.total_conversations, test_fixtures::CONVERSATION_COUNT as i32);
+        assert_eq!(stats.total_memories, test_fixtures::MEMORY_COUNT as i32);
+        assert_eq!(db.get_memories(None).unwrap().len(), test_fixtures::MEMORY_COUNT);
+    }
+
+    #[test]
+    fn test_search_all_groups_people_conversations_and_messages() {
+        let db = test_fixtures::standard_db();
+        // "Alice" names a person and a conversation; add a message that mentions her too
+        db.batch_insert_events(
+            &[Event {
+                id: "mention".to_string(),
+                timestamp: test_fixtures::base_time(),
+                sender: "bob".to_string(),
+                sender_name: None,
+                media_references: vec![],
+                conversation_id: Some("bob".to_string()),
+                content: Some("did alice reply?".to_string()),
+                event_type: "TEXT".to_string(),
+                metadata: None,
+            }],
+            test_fixtures::EXPORT_ID,
+        )
+        .unwrap();
+        db.populate_fts_for_export(test_fixtures::EXPORT_ID, || false, |_| {})
+            .unwrap();
+
+        let results = db.search_all("ali", 10).unwrap();
+        assert_eq!(results.people.len(), 1);
+        assert_eq!(results.people[0].username, "alice");
+        assert_eq!(results.people[0].conversation_id.as_deref(), Some("alice"));
+        assert_eq!(results.conversations.len(), 1);
+        assert_eq!(results.conversations[0].display_name.as_deref(), Some("Alice"));
+        assert_eq!(results.conversations[0].message_count, 20);
+        // FTS matches whole tokens, so the message bucket needs the full word
+        assert!(db
+            .search_all("alice", 10)
+            .unwrap()
+            .messages
+            .iter()
+            .any(|m| m.event_id == "mention"));
+
+        // Prefix matches rank before substring matches
+        let results = db.search_all("e", 10).unwrap();
+        let first_non_prefix = results
+            .people
+            .iter()
+            .position(|p| !p.display_name.as_deref().unwrap_or("").to_lowercase().starts_with('e'))
+            .unwrap();
+        assert!(results.people[..first_non_prefix].iter().all(|p| p.username == "erin"));
+
+        // Group chat is found by its name, and LIKE wildcards are treated literally
+        assert_eq!(
+            db.search_all("weekend", 10).unwrap().conversations[0].id,
+            "group_weekend"
+        );
+        assert!(db.search_all("%", 10).unwrap().people.is_empty());
+    }
+
+    #[test]
+    fn test_purge_os_metadata_media() {
+        let db = test_fixtures::standard_db();
+        let mut event = test_fixtures::events().remove(9);
+        event.media_references = vec![
+            PathBuf::from("/data/chat_media/2023-01-01_A.jpg"),
+            PathBuf::from("/data/chat_media/._2023-01-01_A.jpg"),
+        ];
+        db.batch_insert_events(&[event.clone()], test_fixtures::EXPORT_ID)
+            .unwrap();
+
+        assert_eq!(db.purge_os_metadata_media().unwrap(), 1);
+        let stored = db
+            .get_messages(event.conversation_id.as_deref().unwrap())
+            .unwrap()
+            .into_iter()
+            .find(|e| e.id == event.id)
+            .unwrap();
+        assert_eq!(
+            stored.media_references,
+            vec![PathBuf::from("/data/chat_media/2023-01-01_A.jpg")]
+        );
+        assert!(db.get_setting(OS_METADATA_PURGED_SETTING).unwrap().is_some());
+
+        // Later opens skip the scan
+        db.batch_insert_events(&[event], test_fixtures::EXPORT_ID).unwrap();
+        assert_eq!(db.purge_os_metadata_media().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_tags_on_each_entity_type() {
+        let db = test_fixtures::standard_db();
+        let tag = db.create_tag("college", Some("#ff0000")).unwrap();
+        assert!(
+            db.create_tag("College", None).is_err(),
+            "names are case-insensitive unique"
+        );
+
+        db.tag_entity(tag.id, TagEntityType::Conversation, "alice").unwrap();
+        db.tag_entity(tag.id, TagEntityType::Event, "fixture_event_003")
+            .unwrap();
+        db.tag_entity(tag.id, TagEntityType::Memory, "fixture_memory_1")
+            .unwrap();
+        // Re-tagging is a no-op; unknown entities are rejected
+        db.tag_entity(tag.id, TagEntityType::Event, "fixture_event_003")
+            .unwrap();
+        assert!(db.tag_entity(tag.id, TagEntityType::Event, "nope").is_err());
+
+        assert_eq!(db.get_tags().unwrap()[0].usage_count, 3);
+
+        let convos = db.get_tagged(tag.id, TagEntityType::Conversation, 10, 0).unwrap();
+        assert!(
+            matches!(&convos.items[..], [TaggedEntry::Conversation(c)] if c.id == "alice" && c.message_count == 20)
+        );
+        let events = db.get_tagged(tag.id, TagEntityType::Event, 10, 0).unwrap();
+        assert!(matches!(&events.items[..], [TaggedEntry::Event(e)] if e.id == "fixture_event_003"));
+        let memories = db.get_tagged(tag.id, TagEntityType::Memory, 10, 0).unwrap();
+        assert!(matches!(&memories.items[..], [TaggedEntry::Memory(m)] if m.id == "fixture_memory_1"));
+
+        db.untag_entity(tag.id, TagEntityType::Memory, "fixture_memory_1")
+            .unwrap();
+        assert_eq!(
+            db.get_tagged(tag.id, TagEntityType::Memory, 10, 0).unwrap().total_count,
+            0
+        );
+    }
+
+    #[test]
+    fn test_delete_tag_cascades() {
+        let db = test_fixtures::standard_db();
+        let tag = db.create_tag("funny", None).unwrap();
+        db.tag_entity(tag.id, TagEntityType::Event, "fixture_event_000")
+            .unwrap();
+        db.delete_tag(tag.id).unwrap();
+        let remaining: i32 = db
+            .conn()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM taggings", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(remaining, 0);
+    }
+
+    #[test]
+    fn test_taggings_survive_reimport() {
+        let db = test_fixtures::standard_db();
+        let tag = db.create_tag("trip-2022", None).unwrap();
+        db.create_tag("unused", None).unwrap();
+        db.tag_entity(tag.id, TagEntityType::Conversation, "group_weekend")
+            .unwrap();
+        db.tag_entity(tag.id, TagEntityType::Event, "fixture_event_004")
+            .unwrap();
+        db.tag_entity(tag.id, TagEntityType::Memory, "fixture_memory_3")
+            .unwrap();
+        let snapshot = db.export_taggings().unwrap();
+
+        // A reimport regenerates event and memory IDs
+        let fresh = DatabaseManager::new_in_memory().unwrap();
+        fresh.insert_export(&test_fixtures::export()).unwrap();
+        fresh
+            .batch_insert_conversations(&test_fixtures::conversations())
+            .unwrap();
+        let mut events = test_fixtures::events();
+        events.iter_mut().for_each(|e| e.id = format!("new_{}", e.id));
+        fresh.batch_insert_events(&events, test_fixtures::EXPORT_ID).unwrap();
+        let mut memories = test_fixtures::memories();
+        memories.iter_mut().for_each(|m| m.id = format!("new_{}", m.id));
+        fresh.batch_insert_memories(&memories).unwrap();
+
+        assert_eq!(fresh.restore_taggings(&snapshot).unwrap(), (3, 0));
+        let tags = fresh.get_tags().unwrap();
+        assert_eq!(tags.len(), 2);
+        let restored = tags.iter().find(|t| t.name == "trip-2022").unwrap();
+        let events = fresh.get_tagged(restored.id, TagEntityType::Event, 10, 0).unwrap();
+        assert!(matches!(&events.items[..], [TaggedEntry::Event(e)] if e.id == "new_fixture_event_004"));
+        let memories = fresh.get_tagged(restored.id, TagEntityType::Memory, 10, 0).unwrap();
+        assert!(matches!(&memories.items[..], [TaggedEntry::Memory(m)] if m.id == "new_fixture_memory_3"));
+    }
+
+    fn snapshot_file() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("snapshot.db");
+        let db = DatabaseManager::new(&path).unwrap();
+        test_fixtures::populate_standard(&db).unwrap();
+        drop(db);
+        (dir, path)
+    }
+
+    #[test]
+    fn test_open_readonly_snapshot() {
+        let (_dir, path) = snapshot_file();
+        let db = DatabaseManager::open_readonly(&path).unwrap();
+        assert!(db.is_read_only());
+        assert_eq!(
+            db.get_conversations(None).unwrap().len(),
+            test_fixtures::CONVERSATION_COUNT
+        );
+        assert_eq!(db.search_messages("pizza", 50, false, None).unwrap().len(), 10);
+    }
+
+    #[test]
+    fn test_readonly_rejects_writes() {
+        let (_dir, path) = snapshot_file();
+        let db = DatabaseManager::open_readonly(&path).unwrap();
+        for result in [
+            db.create_tag("nope", None).map(|_| ()),
+            db.set_setting("storage_path", "/tmp"),
+            db.batch_insert_memories(&test_fixtures::memories()),
+        ] {
+            assert!(matches!(result, Err(AppError::ReadOnlyDatabase(_))));
+        }
+        assert!(AppError::ReadOnlyDatabase("x".into())
+            .to_string()
+            .starts_with("ReadOnlyDatabase"));
+    }
+
+    #[test]
+    fn test_readonly_schema_version_checks() {
+        let (_dir, path) = snapshot_file();
+        {
+            let conn = rusqlite::Connection::open(&path).unwrap();
+            conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1).unwrap();
+        }
+        let err = DatabaseManager::open_readonly(&path).err().unwrap();
+        assert!(err.to_string().contains("newer"));
+
+        // A pre-download-tracking database is refused rather than migrated
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("old.db");
+        {
+            let conn = rusqlite::Connection::open(&old).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE exports (id TEXT, source_paths TEXT, source_type TEXT);
+                 CREATE TABLE conversations (id TEXT, participants TEXT);
+                 CREATE TABLE events (id TEXT, media_references TEXT);
+                 CREATE TABLE memories (id TEXT);
+                 CREATE TABLE people (username TEXT, display_name TEXT);",
+            )
+            .unwrap();
+        }
+        let err = DatabaseManager::open_readonly(&old).err().unwrap();
+        assert!(err.to_string().contains("memories.download_status"));
+        let conn = rusqlite::Connection::open(&old).unwrap();
+        let columns: i32 = conn
+            .query_row("SELECT COUNT(*) FROM pragma_table_info('memories')", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(columns, 1, "read-only open must not migrate");
+    }
+
+    #[test]
+    fn test_message_density_totals_match() {
+        let db = test_fixtures::standard_db();
+        // Fixture events span ~2 days; spread one conversation out so bucketing kicks in
+        let events: Vec<Event> = (0..120)
+            .map(|i| Event {
+                id: format!("density_{}", i),
+                timestamp: test_fixtures::base_time() + chrono::Duration::hours(i * i),
+                sender: "alice".to_string(),
+                sender_name: None,
+                media_references: vec![],
+                conversation_id: Some("alice".to_string()),
+                content: Some("hi".to_string()),
+                event_type: "TEXT".to_string(),
+                metadata: None,
+            })
+            .collect();
+        db.batch_insert_events(&events, test_fixtures::EXPORT_ID).unwrap();
+
+        let total = db.get_messages_page("alice", 0, 1, false, None).unwrap().total_count;
+        let buckets = db.get_message_density("alice", 40).unwrap();
+        assert_eq!(buckets.len(), 40);
+        assert_eq!(buckets.iter().map(|b| b.count).sum::<i32>(), total);
+        assert!(buckets.windows(2).all(|w| w[0].end == w[1].start));
+        // Quadratic spacing puts most messages early
+        assert!(buckets[0].count > buckets[39].count);
+    }
+
+    #[test]
+    fn test_message_density_short_and_empty() {
+        let db = test_fixtures::standard_db();
+        // "bob" has 20 fixture messages over ~2 days: one bucket per active day
+        let buckets = db.get_message_density("bob", 50).unwrap();
+        assert_eq!(buckets.len(), 3);
+        assert_eq!(buckets.iter().map(|b| b.count).sum::<i32>(), 20);
+        assert_eq!(buckets[0].end - buckets[0].start, chrono::Duration::days(1));
+
+        assert!(db.get_message_density("nobody", 50).unwrap().is_empty());
+
+        // Messages that all share one timestamp make a single bucket, however many are asked for
+        let at = test_fixtures::base_time();
+        let conversation = Conversation {
+            id: "same_time".to_string(),
+            ..test_fixtures::conversations().remove(0)
+        };
+        db.batch_insert_conversations(&[conversation]).unwrap();
+        let same_time: Vec<Event> = (0..3)
+            .map(|i| Event {
+                id: format!("same_time_{}", i),
+                timestamp: at,
+                conversation_id: Some("same_time".to_string()),
+                ..test_fixtures::events().remove(0)
+            })
+            .collect();
+        db.batch_insert_events(&same_time, test_fixtures::EXPORT_ID).unwrap();
+        for requested in [1, 50] {
+            let buckets = db.get_message_density("same_time", requested).unwrap();
+            assert_eq!(buckets.len(), 1);
+            assert_eq!((buckets[0].start, buckets[0].count), (at, 3));
+        }
+    }
+
+    type ObservableState = (Vec<String>, Vec<(String, Option<String>, Vec<String>)>, ExportStats);
 
-    fn test_db() -> DatabaseManager {
-        let tmp = NamedTempFile::new().unwrap();
-        DatabaseManager::new(tmp.path()).unwrap()
+    /// Everything a reader can observe, for before/after comparisons.
+    fn observable_state(db: &DatabaseManager) -> ObservableState {
+        let exports = db
+            .get_exports()
+            .unwrap()
+            .into_iter()
+            .map(|e| format!("{}:{:?}", e.id, e.validation_status))
+            .collect();
+        let conversations = db
+            .get_conversations(None)
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.id, c.display_name, c.participants))
+            .collect();
+        (exports, conversations, db.get_export_stats(None).unwrap())
     }
 
     #[test]
-    fn test_sanitize_fts_query_simple() {
-        assert_eq!(
-            DatabaseManager::sanitize_fts_query("hello world"),
-            "\"hello\" \"world\""
+    fn test_failed_ingestion_rolls_back_to_pre_import_state() {
+        let db = test_fixtures::standard_db();
+        let before = observable_state(&db);
+
+        // A second export that merges into "alice" and creates a new conversation, then fails
+        let mut run = db.begin_ingestion_run("second_export").unwrap();
+        let mut export = test_fixtures::export();
+        export.id = "second_export".to_string();
+        export.validation_status = ValidationStatus::Incomplete;
+        db.insert_export(&export).unwrap();
+
+        let conversations = vec![
+            Conversation {
+                id: "alice".to_string(),
+                display_name: Some("Renamed".to_string()),
+                participants: vec!["alice".to_string(), "zed".to_string()],
+                last_event_at: None,
+                message_count: 0,
+                has_media: false,
+                media_count: 0,
+                media_bytes: 0,
+                missing_media_count: 0,
+                avatar_path: None,
+                avatar_color: None,
+                is_group: false,
+                listed_members: 0,
+                anomaly_flags: Vec::new(),
+                saved_count: 0,
+                shared_location_count: 0,
+                completeness: None,
+            },
+            Conversation {
+                id: "zed".to_string(),
+                display_name: None,
+                participants: vec!["zed".to_string()],
+                last_event_at: None,
+                message_count: 0,
+                has_media: false,
+                media_count: 0,
+                media_bytes: 0,
+                missing_media_count: 0,
+                avatar_path: None,
+                avatar_color: None,
+                is_group: false,
+                listed_members: 0,
+                anomaly_flags: Vec::new(),
+                saved_count: 0,
+                shared_location_count: 0,
+                completeness: None,
+            },
+        ];
+        run.track_conversations(&conversations);
+        assert_eq!(run.created_conversations(), ["zed".to_string()]);
+        assert_eq!(run.merged_conversations(), ["alice".to_string()]);
+        db.batch_insert_conversations(&conversations).unwrap();
+
+        let events: Vec<Event> = (0..4)
+            .map(|i| Event {
+                id: format!("second_{}", i),
+                timestamp: test_fixtures::base_time(),
+                sender: "zed".to_string(),
+                sender_name: None,
+                media_references: vec![],
+                conversation_id: Some(if i % 2 == 0 { "alice" } else { "zed" }.to_string()),
+                content: Some("unmistakable rollback marker".to_string()),
+                event_type: "TEXT".to_string(),
+                metadata: None,
+            })
+            .collect();
+        run.track_events(&events);
+        db.batch_insert_events(&events, "second_export").unwrap();
+        let mut memories = test_fixtures::memories();
+        for (i, m) in memories.iter_mut().enumerate() {
+            m.id = format!("second_memory_{}", i);
+            m.export_id = "second_export".to_string();
+        }
+        run.track_memories(&memories);
+        db.batch_insert_memories(&memories).unwrap();
+        let location = LocationPoint {
+            timestamp: test_fixtures::base_time(),
+            latitude: 1.0,
+            longitude: 2.0,
+            source: "Latest Location".to_string(),
+            export_id: "second_export".to_string(),
+        };
+        run.track_locations(db.insert_location_history(&[location]).unwrap());
+
+        let parse_error = crate::ingestion::parser::ChatJsonParser::parse_chat_history_reader(
+            &b"{ not json"[..],
+            crate::models::TimestampFormatHint::default(),
         );
-    }
+        assert!(parse_error.is_err());
 
-    #[test]
-    fn test_sanitize_fts_query_empty() {
-        assert_eq!(DatabaseManager::sanitize_fts_query(""), "");
-        assert_eq!(DatabaseManager::sanitize_fts_query("   "), "");
+        let cleanup = db.rollback_ingestion_run(&run).unwrap();
+        assert_eq!(cleanup.events_removed, 4);
+        assert_eq!(cleanup.memories_removed, test_fixtures::MEMORY_COUNT);
+        assert_eq!(cleanup.locations_removed, 1);
+        assert_eq!(cleanup.conversations_removed, 1);
+        assert_eq!(cleanup.conversations_restored, 1);
+        assert!(cleanup.export_removed);
+        assert_eq!(observable_state(&db), before);
+        assert!(db
+            .search_messages("rollback marker", 10, false, None)
+            .unwrap()
+            .is_empty());
+
+        let issue = JsonFileIssue {
+            file: "chat_history.json".to_string(),
+            problem: crate::models::JsonFileProblem::HtmlErrorPage,
+            salvaged_conversations: 0,
+            salvaged_events: 0,
+        };
+        run.note_file_issue(issue.clone());
+        let timestamp_format = crate::models::TimestampFormatDecision {
+            hint: crate::models::TimestampFormatHint::DayFirst,
+            dates_sampled: 12,
+            ambiguous: false,
+        };
+        run.set_timestamp_format(Some(timestamp_format));
+        db.record_ingestion_run(
+            &run,
+            IngestionRunStatus::Failed,
+            Some("Parsing: bad json"),
+            Some(&cleanup),
+        )
+        .unwrap();
+        let history = db.get_ingestion_history(10).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].status, IngestionRunStatus::Failed);
+        assert_eq!(history[0].events, 4);
+        assert_eq!(history[0].cleanup.as_ref(), Some(&cleanup));
+        assert_eq!(history[0].file_issues, vec![issue]);
+        assert_eq!(history[0].timestamp_format, Some(timestamp_format));
     }
 
     #[test]
-    fn test_sanitize_fts_query_special_chars() {
-        // FTS5 operators should be quoted
+    fn test_rollback_of_overlapping_export_keeps_the_earlier_exports_memories() {
+        let db = test_fixtures::standard_db();
+        let before = observable_state(&db);
+
+        // A second export listing the same memories plus one of its own, which then fails
+        let mut run = db.begin_ingestion_run("second_export").unwrap();
+        let mut export = test_fixtures::export();
+        export.id = "second_export".to_string();
+        export.validation_status = ValidationStatus::Incomplete;
+        db.insert_export(&export).unwrap();
+        let mut memories = test_fixtures::memories();
+        let mut own = memories[0].clone();
+        own.id = "second_only".to_string();
+        memories.push(own);
+        for memory in &mut memories {
+            memory.export_id = "second_export".to_string();
+        }
+        let ids: Vec<String> = memories.iter().map(|m| m.id.clone()).collect();
+        let existing = db.existing_memory_ids(&ids).unwrap();
+        let added: Vec<Memory> = memories.iter().filter(|m| !existing.contains(&m.id)).cloned().collect();
+        run.track_memories(&added);
+        db.batch_insert_memories(&memories).unwrap();
         assert_eq!(
-            DatabaseManager::sanitize_fts_query("hello OR world"),
-            "\"hello\" \"OR\" \"world\""
+            db.get_memories(Some(test_fixtures::EXPORT_ID)).unwrap().len(),
+            test_fixtures::MEMORY_COUNT
         );
-        assert_eq!(DatabaseManager::sanitize_fts_query("test*"), "\"test*\"");
-    }
+        assert_eq!(db.get_memories(Some("second_export")).unwrap().len(), 1);
 
-    #[test]
-    fn test_sanitize_fts_query_quotes() {
-        // Double quotes within words are escaped
+        let cleanup = db.rollback_ingestion_run(&run).unwrap();
+        assert_eq!(cleanup.memories_removed, 1);
+        assert!(cleanup.export_removed);
+        assert_eq!(observable_state(&db), before);
         assert_eq!(
-            DatabaseManager::sanitize_fts_query("say \"hi\""),
-            "\"say\" \"\"\"hi\"\"\""
+            db.get_memories(Some(test_fixtures::EXPORT_ID)).unwrap().len(),
+            test_fixtures::MEMORY_COUNT
         );
     }
 
     #[test]
-    fn test_sanitize_fts_query_unicode() {
-        assert_eq!(DatabaseManager::sanitize_fts_query("caf\u{00e9}"), "\"caf\u{00e9}\"");
+    fn test_rollback_restores_pre_existing_export_row() {
+        let db = test_fixtures::standard_db();
+        let before = observable_state(&db);
+        let run = db.begin_ingestion_run(test_fixtures::EXPORT_ID).unwrap();
+        let mut export = test_fixtures::export();
+        export.validation_status = ValidationStatus::Incomplete;
+        db.insert_export(&export).unwrap();
+
+        let cleanup = db.rollback_ingestion_run(&run).unwrap();
+        assert!(!cleanup.export_removed);
+        assert_eq!(observable_state(&db), before);
     }
 
     #[test]
-    fn test_insert_and_get_exports() {
-        let db = test_db();
-        let export = ExportSet {
-            id: "test-export".to_string(),
-            source_paths: vec![PathBuf::from("/tmp/test")],
-            source_type: ExportSourceType::Folder,
-            extraction_path: None,
-            creation_date: None,
-            validation_status: ValidationStatus::Valid,
-        };
-        db.insert_export(&export).unwrap();
-        let exports = db.get_exports().unwrap();
-        assert_eq!(exports.len(), 1);
-        assert_eq!(exports[0].id, "test-export");
-        assert_eq!(exports[0].source_type, ExportSourceType::Folder);
-        assert_eq!(exports[0].validation_status, ValidationStatus::Valid);
+    fn test_redacted_content_is_gone_from_search() {
+        let db = test_fixtures::standard_db();
+        let pizza = db.search_messages("pizza", 50, false, None).unwrap();
+        assert!(!pizza.is_empty());
+        let target = pizza[0].event_id.clone();
+        let tag = db.create_tag("painful", None).unwrap();
+        db.tag_entity(tag.id, TagEntityType::Event, &target).unwrap();
+
+        let (summary, _) = db
+            .redact_events(&[target.clone(), "no_such_event".to_string()])
+            .unwrap();
+        assert_eq!(summary.events_removed, 1);
+        assert_eq!(summary.rules_added, 1);
+        let after = db.search_messages("pizza", 50, false, None).unwrap();
+        assert_eq!(after.len(), pizza.len() - 1);
+        assert!(after.iter().all(|r| r.event_id != target));
+        assert_eq!(
+            db.get_tagged(tag.id, TagEntityType::Event, 10, 0).unwrap().total_count,
+            0
+        );
+
+        // Media of redacted messages is reported so the caller can scrub it
+        let (summary, media) = db.redact_sender("carol").unwrap();
+        assert!(summary.events_removed > 0);
+        assert!(!media.is_empty());
+        assert!(db
+            .get_messages("group_weekend")
+            .unwrap()
+            .iter()
+            .all(|e| e.sender != "carol"));
+        assert_eq!(db.get_redactions().unwrap().len(), 2);
     }
 
     #[test]
-    fn test_insert_and_get_exports_zip() {
-        let db = test_db();
-        let export = ExportSet {
-            id: "zip-export".to_string(),
-            source_paths: vec![PathBuf::from("/tmp/test.zip")],
-            source_type: ExportSourceType::Zip,
-            extraction_path: None,
-            creation_date: Some(chrono::Utc::now()),
-            validation_status: ValidationStatus::Incomplete,
-        };
-        db.insert_export(&export).unwrap();
-        let exports = db.get_exports().unwrap();
-        assert_eq!(exports[0].source_type, ExportSourceType::Zip);
-        assert_eq!(exports[0].validation_status, ValidationStatus::Incomplete);
+    fn test_reimport_reapplies_redactions() {
+        let db = test_fixtures::standard_db();
+        let target = db.search_messages("pizza", 50, false, None).unwrap().remove(0);
+        db.redact_events(std::slice::from_ref(&target.event_id)).unwrap();
+        db.redact_sender("carol").unwrap();
+        let saved = db.get_redactions().unwrap();
+
+        // A reimport starts from an empty database and regenerates every event ID
+        let fresh = DatabaseManager::new_in_memory().unwrap();
+        assert_eq!(fresh.restore_redactions(&saved).unwrap(), 2);
+        let reparsed: Vec<Event> = test_fixtures::events()
+            .into_iter()
+            .map(|mut e| {
+                e.id = format!("reimported_{}", e.id);
+                e
+            })
+            .collect();
+        let (kept, dropped) = fresh.filter_redacted(reparsed).unwrap();
+        let carol_count = test_fixtures::events().iter().filter(|e| e.sender == "carol").count();
+        assert_eq!(dropped, 1 + carol_count);
+
+        fresh.insert_export(&test_fixtures::export()).unwrap();
+        fresh
+            .batch_insert_conversations(&test_fixtures::conversations())
+            .unwrap();
+        fresh.batch_insert_events(&kept, test_fixtures::EXPORT_ID).unwrap();
+        fresh
+            .populate_fts_for_export(test_fixtures::EXPORT_ID, || false, |_| {})
+            .unwrap();
+        let results = fresh.search_messages("pizza", 50, false, None).unwrap();
+        assert!(!results.is_empty());
+        assert!(results.iter().all(|r| r.timestamp != target.timestamp));
+        assert!(fresh
+            .get_messages("group_weekend")
+            .unwrap()
+            .iter()
+            .all(|e| e.sender != "carol"));
     }
 
     #[test]
-    fn test_batch_insert_and_get_conversations() {
-        let db = test_db();
-        let convos = vec![Conversation {
-            id: "conv1".to_string(),
-            display_name: Some("Alice".to_string()),
-            participants: vec!["alice".to_string(), "bob".to_string()],
-            last_event_at: Some(chrono::Utc::now()),
-            message_count: 5,
-            has_media: false,
-        }];
-        db.insert_export(&ExportSet {
-            id: "e1".to_string(),
-            source_paths: vec![PathBuf::from("/tmp")],
-            source_type: ExportSourceType::Folder,
-            extraction_path: None,
-            creation_date: None,
-            validation_status: ValidationStatus::Valid,
-        })
-        .unwrap();
-        db.batch_insert_conversations(&convos).unwrap();
+    fn test_conversation_media_totals() {
+        use crate::ingestion::media_linker::MediaLinker;
+        let db = test_fixtures::standard_db();
+        let dir = tempfile::tempdir().unwrap();
+        let small = dir.path().join("2023-01-01_SMALL.jpg");
+        let large = dir.path().join("2023-01-01_LARGE.mp4");
+        std::fs::write(&small, vec![0u8; 1_024]).unwrap();
+        std::fs::write(&large, vec![0u8; 4_096]).unwrap();
+        let media = [small, large, dir.path().join("2023-01-01_MISSING.jpg")];
+        let events: Vec<Event> = media
+            .iter()
+            .enumerate()
+            .map(|(i, path)| Event {
+                id: format!("media_{}", i),
+                timestamp: test_fixtures::base_time(),
+                sender: "bob".to_string(),
+                sender_name: None,
+                media_references: vec![path.clone()],
+                conversation_id: Some("bob".to_string()),
+                content: None,
+                event_type: "MEDIA".to_string(),
+                metadata: None,
+            })
+            .collect();
+        db.batch_insert_events(&events, test_fixtures::EXPORT_ID).unwrap();
 
-        let result = db.get_conversations().unwrap();
-        assert_eq!(result.len(), 1);
-        assert_eq!(result[0].id, "conv1");
+        let stats = MediaLinker::tally_media(&db.get_media_events().unwrap());
+        db.set_conversation_media_stats(&stats).unwrap();
+
+        let bob = db
+            .get_conversations(None)
+            .unwrap()
+            .into_iter()
+            .find(|c| c.id == "bob")
+            .unwrap();
+        assert_eq!(bob.media_count, 3);
+        assert_eq!(bob.media_bytes, 5_120);
+        assert_eq!(bob.missing_media_count, 1);
+        // The fixture's own media paths don't exist, so the group chat is all missing
+        let group = db
+            .get_conversations(None)
+            .unwrap()
+            .into_iter()
+            .find(|c| c.id == "group_weekend")
+            .unwrap();
+        assert_eq!(group.media_bytes, 0);
+        assert_eq!(group.missing_media_count, group.media_count);
+
+        let report = db.get_validation_report().unwrap();
+        assert!(report.missing_media_by_conversation.contains(&("bob".to_string(), 1)));
     }
 
     #[test]
-    fn test_batch_insert_events_and_search() {
-        let db = test_db();
-        db.insert_export(&ExportSet {
-            id: "e1".to_string(),
-            source_paths: vec![PathBuf::from("/tmp")],
-            source_type: ExportSourceType::Folder,
-            extraction_path: None,
-            creation_date: None,
-            validation_status: ValidationStatus::Valid,
-        })
+    fn test_completeness_scores_media_linkage_and_remembers_sources() {
+        let db = test_fixtures::standard_db();
+        let media = |media_count, missing_media_count| ConversationMediaStats {
+            media_count,
+            media_bytes: 0,
+            missing_media_count,
+        };
+        // Fully linked, partly linked and nothing linked
+        db.set_conversation_media_stats(&HashMap::from([
+            ("alice".to_string(), media(4, 0)),
+            ("bob".to_string(), media(3, 1)),
+            ("group_weekend".to_string(), media(2, 2)),
+        ]))
         .unwrap();
-        db.batch_insert_conversations(&[Conversation {
-            id: "conv1".to_string(),
-            display_name: None,
-            participants: vec![],
-            last_event_at: None,
-            message_count: 0,
-            has_media: false,
-        }])
+        db.replace_validation_issues(
+            test_fixtures::EXPORT_ID,
+            &[ConversationAnomaly {
+                conversation_id: "bob".to_string(),
+                kind: AnomalyKind::DuplicateMessages,
+                affected: 5,
+                total: 20,
+                detail: String::new(),
+            }],
+        )
         .unwrap();
+        // A message kept with its unreadable timestamp counts against the score
+        let mut unreadable = test_fixtures::events()
+            .into_iter()
+            .find(|e| e.conversation_id.as_deref() == Some("alice"))
+            .unwrap();
+        unreadable.id = "alice-unreadable".to_string();
+        unreadable.metadata = Some(format!(r#"{{"{}": "yesterday-ish"}}"#, RAW_TIMESTAMP_KEY));
+        db.batch_insert_events(&[unreadable], test_fixtures::EXPORT_ID).unwrap();
 
-        let events = vec![Event {
-            id: "evt1".to_string(),
-            timestamp: chrono::Utc::now(),
-            sender: "alice".to_string(),
-            sender_name: None,
-            media_references: vec![],
-            conversation_id: Some("conv1".to_string()),
-            content: Some("hello world test message".to_string()),
-            event_type: "TEXT".to_string(),
-            metadata: None,
-        }];
-        db.batch_insert_events(&events, "e1").unwrap();
+        let html = HashMap::from([("alice".to_string(), BTreeSet::from([ChatSource::Html]))]);
+        assert_eq!(db.refresh_conversation_completeness(&html).unwrap(), 3);
+        let json = HashMap::from([("alice".to_string(), BTreeSet::from([ChatSource::Json]))]);
+        db.refresh_conversation_completeness(&json).unwrap();
 
-        // Search should find the message
-        let results = db.search_messages("hello", 50).unwrap();
-        assert_eq!(results.len(), 1);
-        assert_eq!(results[0].event_id, "evt1");
-    }
+        let completeness = |id: &str| db.get_conversation(id).unwrap().completeness.unwrap();
+        let alice = completeness("alice");
+        assert_eq!(alice.media_linked_pct, Some(100.0));
+        let messages = db.get_conversation("alice").unwrap().message_count as i64;
+        assert_eq!(
+            alice.timestamps_parsed_pct,
+            ConversationCompleteness::percent(messages - 1, messages)
+        );
+        assert!(alice.timestamps_parsed_pct < Some(100.0));
+        assert_eq!(alice.sources, vec![ChatSource::Html, ChatSource::Json]);
+        assert_eq!(alice.anomaly_count, 0);
+        assert_eq!(completeness("bob").timestamps_parsed_pct, Some(100.0));
+        let bob = completeness("bob");
+        assert_eq!(bob.media_linked_pct, Some(66.7));
+        assert_eq!(bob.anomaly_count, 1);
+        assert!(bob.sources.is_empty());
+        assert_eq!(completeness("group_weekend").media_linked_pct, Some(0.0));
 
-    #[test]
-    fn test_search_empty_query() {
-        let db = test_db();
-        let results = db.search_messages("", 50).unwrap();
-        assert!(results.is_empty());
+        // Importing the conversations again leaves the summary in place
+        db.batch_insert_conversations(&test_fixtures::conversations()).unwrap();
+        assert_eq!(completeness("alice"), alice);
+
+        let listed = db.get_conversations(None).unwrap();
+        assert!(listed.iter().all(|c| c.completeness.is_some()));
+        assert!(matches!(db.get_conversation("nobody"), Err(AppError::NotFound(_))));
     }
 
     #[test]
-    fn test_get_messages_page_clamping() {
-        let db = test_db();
-        db.insert_export(&ExportSet {
-            id: "e1".to_string(),
-            source_paths: vec![PathBuf::from("/tmp")],
-            source_type: ExportSourceType::Folder,
-            extraction_path: None,
-            creation_date: None,
-            validation_status: ValidationStatus::Valid,
-        })
-        .unwrap();
-        db.batch_insert_conversations(&[Conversation {
-            id: "conv1".to_string(),
-            display_name: None,
-            participants: vec![],
-            last_event_at: None,
-            message_count: 0,
-            has_media: false,
-        }])
-        .unwrap();
+    fn test_queries_can_be_limited_to_one_export() {
+        let db = test_fixtures::standard_db();
+        let later = ExportSet {
+            id: "export_2024".to_string(),
+            ..test_fixtures::export()
+        };
+        db.insert_export(&later).unwrap();
+        // A new chat, plus a message merged into a chat the first export created
+        let carol = Conversation {
+            id: "carol".to_string(),
+            ..test_fixtures::conversations().remove(0)
+        };
+        db.batch_insert_conversations(&[carol]).unwrap();
+        let template = test_fixtures::events().remove(0);
+        let new_events = [("e2024_1", "carol"), ("e2024_2", "alice")].map(|(id, conversation)| Event {
+            id: id.to_string(),
+            conversation_id: Some(conversation.to_string()),
+            content: Some("pizza in 2024".to_string()),
+            ..template.clone()
+        });
+        db.batch_insert_events(&new_events, &later.id).unwrap();
+        db.assign_conversation_exports().unwrap();
+        db.populate_fts_for_export(&later.id, || false, |_| {}).unwrap();
 
-        // Even with negative offset/limit, should not crash
-        let page = db.get_messages_page("conv1", -5, -10).unwrap();
-        assert_eq!(page.total_count, 0);
-        assert!(!page.has_more);
+        let ids = |export_id| -> Vec<String> {
+            let mut ids: Vec<String> = db
+                .get_conversations(export_id)
+                .unwrap()
+                .into_iter()
+                .map(|c| c.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(ids(Some("export_2024")), ["alice", "carol"]);
+        assert_eq!(ids(Some(test_fixtures::EXPORT_ID)), ["alice", "bob", "group_weekend"]);
+        assert_eq!(ids(None).len(), 4);
+
+        let alice = db
+            .get_conversations(Some("export_2024"))
+            .unwrap()
+            .into_iter()
+            .find(|c| c.id == "alice")
+            .unwrap();
+        assert_eq!(alice.message_count, 1);
+        let stats = db.get_export_stats(Some("export_2024")).unwrap();
+        assert_eq!(
+            (stats.total_messages, stats.total_conversations, stats.total_memories),
+            (2, 2, 0)
+        );
+        assert_eq!(db.get_export_stats(None).unwrap().total_conversations, 4);
+        assert_eq!(
+            db.search_messages("pizza", 50, false, Some("export_2024"))
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(db.search_messages("pizza", 50, false, None).unwrap().len(), 12);
+
+        assert_eq!(db.get_active_export().unwrap(), None);
+        db.set_active_export(Some("export_2024")).unwrap();
+        assert_eq!(db.get_active_export().unwrap().as_deref(), Some("export_2024"));
+        assert!(matches!(
+            db.set_active_export(Some("missing")),
+            Err(AppError::NotFound(_))
+        ));
+        db.set_active_export(None).unwrap();
+        assert_eq!(db.get_active_export().unwrap(), None);
     }
 
     #[test]
-    fn test_run_migrations_idempotent() {
-        let db = test_db();
-        // Running migrations again should not fail
-        db.run_migrations().unwrap();
-        db.run_migrations().unwrap();
+    fn test_owner_messages_take_the_owner_display_name() {
+        let db = test_fixtures::standard_db();
+        assert_eq!(db.get_owner_profile().unwrap(), None);
+        let owner = OwnerProfile {
+            username: test_fixtures::OWNER.to_string(),
+            display_name: Some("Me Myself".to_string()),
+        };
+        db.set_owner_profile(&owner).unwrap();
+        assert_eq!(db.get_owner_profile().unwrap(), Some(owner));
+
+        let paged = db.get_messages_page("alice", 0, 50, false, None).unwrap().messages;
+        for messages in [paged, db.get_messages("alice").unwrap()] {
+            let (own, others): (Vec<&Event>, Vec<&Event>) =
+                messages.iter().partition(|m| m.sender == test_fixtures::OWNER);
+            assert!(!own.is_empty() && !others.is_empty());
+            assert!(own.iter().all(|m| m.sender_name.as_deref() == Some("Me Myself")));
+            assert!(others.iter().all(|m| m.sender_name.as_deref() != Some("Me Myself")));
+        }
     }
 
     #[test]
-    fn test_export_stats_empty_db() {
-        let db = test_db();
-        db.insert_export(&ExportSet {
-            id: "e1".to_string(),
-            source_paths: vec![PathBuf::from("/tmp")],
-            source_type: ExportSourceType::Folder,
-            extraction_path: None,
-            creation_date: None,
-            validation_status: ValidationStatus::Valid,
-        })
-        .unwrap();
-        let stats = db.get_export_stats().unwrap();
-        assert_eq!(stats.total_messages, 0);
-        assert_eq!(stats.total_conversations, 0);
+    fn test_search_snippets_mark_matches_on_character_boundaries() {
+        let db = test_fixtures::standard_db();
+        let mut event = test_fixtures::events().remove(0);
+        event.id = "emoji".to_string();
+        let long_tail = " and then some more words".repeat(10);
+        event.content = Some(format!("🍕🍕 Crème brûlée then waffles 🎉 waffles{}", long_tail));
+        db.batch_insert_events(&[event], test_fixtures::EXPORT_ID).unwrap();
+        db.populate_fts_for_export(test_fixtures::EXPORT_ID, || false, |_| {})
+            .unwrap();
+
+        // Ranges count UTF-16 code units, as the frontend slices strings
+        let matched = |content: &str, (start, end): (usize, usize)| {
+            String::from_utf16(&content.encode_utf16().collect::<Vec<u16>>()[start..end]).unwrap()
+        };
+        let hit = db.search_messages("waffles", 50, false, None).unwrap().remove(0);
+        assert_eq!(hit.match_ranges.len(), 2);
+        assert_eq!(hit.match_ranges[0], (23, 30));
+        for &range in &hit.match_ranges {
+            assert_eq!(matched(&hit.content, range), "waffles");
+        }
+        assert!(hit
+            .snippet
+            .contains(&format!("{}waffles{}", SNIPPET_MATCH_START, SNIPPET_MATCH_END)));
+        assert!(hit.snippet.ends_with('…'));
+        assert!(hit.snippet.len() < hit.content.len());
+
+        let hit = db.search_messages("brûlée", 50, false, None).unwrap().remove(0);
+        assert_eq!(matched(&hit.content, hit.match_ranges[0]), "brûlée");
     }
 
     #[test]
-    fn test_insert_people_and_resolve() {
-        let db = test_db();
-        db.insert_export(&ExportSet {
-            id: "e1".to_string(),
-            source_paths: vec![PathBuf::from("/tmp")],
-            source_type: ExportSourceType::Folder,
-            extraction_path: None,
-            creation_date: None,
-            validation_status: ValidationStatus::Valid,
-        })
-        .unwrap();
-        let people = vec![Person {
-            username: "alice".to_string(),
-            display_name: Some("Alice Smith".to_string()),
-        }];
-        db.insert_people(&people).unwrap();
+    fn test_messages_around_an_anchor_clamp_at_the_ends() {
+        let db = test_fixtures::standard_db();
+        let all = db.get_messages_page("alice", 0, 2000, false, None).unwrap().messages;
+        let n = all.len() as i32;
 
-        // Person name should resolve in conversations list
-        db.batch_insert_conversations(&[Conversation {
-            id: "alice".to_string(),
-            display_name: None,
-            participants: vec![],
-            last_event_at: None,
-            message_count: 0,
-            has_media: false,
-        }])
-        .unwrap();
-        let convos = db.get_conversations().unwrap();
-        assert_eq!(convos[0].display_name.as_deref(), Some("Alice Smith"));
+        let middle = db.get_messages_around(&all[10].id, 3, 2).unwrap();
+        assert_eq!((middle.offset, middle.anchor_index), (7, 10));
+        assert_eq!(middle.page.messages.len(), 6);
+        assert_eq!(middle.page.messages[3].id, all[10].id);
+        assert!(middle.page.has_more);
+
+        let first = db.get_messages_around(&all[0].id, 5, 1).unwrap();
+        assert_eq!((first.offset, first.anchor_index), (0, 0));
+        assert_eq!(first.page.messages[0].id, all[0].id);
+        assert_eq!(first.page.messages.len(), 2);
+
+        let last = db.get_messages_around(&all[n as usize - 1].id, 1, 5).unwrap();
+        assert_eq!(last.anchor_index, n - 1);
+        assert_eq!(last.page.messages.len(), 2);
+        assert!(!last.page.has_more);
+
+        assert!(matches!(
+            db.get_messages_around("nope", 1, 1),
+            Err(AppError::NotFound(_))
+        ));
     }
 
     #[test]
-    fn test_validation_report() {
-        let db = test_db();
-        db.insert_export(&ExportSet {
-            id: "e1".to_string(),
-            source_paths: vec![PathBuf::from("/tmp")],
-            source_type: ExportSourceType::Folder,
-            extraction_path: None,
-            creation_date: None,
-            validation_status: ValidationStatus::Valid,
-        })
-        .unwrap();
-        let report = db.get_validation_report().unwrap();
-        assert_eq!(report.total_html_files, 0);
-        assert_eq!(report.media_missing, 0);
+    fn test_traced_reads_record_their_row_counts() {
+        let db = test_fixtures::standard_db();
+        perf::configure(&PerformanceSettings {
+            enabled: true,
+            ..PerformanceSettings::default()
+        });
+        // Other tests may trace at the same time, so look for this test's entries by name
+        let traced_rows = |name: &str| {
+            perf::recent(perf::TRACE_CAPACITY)
+                .into_iter()
+                .rev()
+                .find(|e| e.kind == crate::models::TraceKind::Query && e.name == name)
+                .and_then(|e| e.rows)
+        };
+
+        let messages = db.get_messages("alice").unwrap();
+        assert_eq!(traced_rows("get_messages"), Some(messages.len()));
+        let dates = db.get_activity_dates("alice").unwrap();
+        assert_eq!(traced_rows("get_activity_dates"), Some(dates.len()));
+        let page = db.get_unified_media_stream(5, 0, MediaDirection::All, None).unwrap();
+        assert_eq!(traced_rows("get_unified_media_stream"), Some(page.items.len()));
+        let scanned = db.for_each_message_text("alice", None, |_| true).unwrap();
+        assert_eq!(traced_rows("for_each_message_text"), Some(scanned));
     }
 }
@@ -1,16 +1,128 @@
 use crate::db::DatabaseManager;
-use crate::error::AppResult;
-use crate::models::{DownloadStatus, Memory};
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    ConnectionTestResult, DownloadJob, DownloadJobState, DownloadStatus, Memory, MemoryFilter, NetworkSettings,
+};
 use crate::storage::StorageManager;
 use futures_util::StreamExt;
-use reqwest::Client;
+use reqwest::header::{CONTENT_RANGE, CONTENT_TYPE, RANGE};
+use reqwest::{Client, Proxy, StatusCode};
 use serde::Serialize;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
-use tauri::{AppHandle, Emitter};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
 use tokio::fs as tokio_fs;
 use tokio::io::AsyncWriteExt;
 
+/// Pause between the items of a bulk download, so a large backlog doesn't hammer the server.
+const BULK_DOWNLOAD_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often a paused download looks whether it may go on.
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Smaller than any photo or video Snapchat exports; a body this short is an error message.
+pub const MIN_MEDIA_BYTES: u64 = 1024;
+
+/// Failure reason for links the server no longer honours. Snapchat's download links expire a
+/// while after the export is made, and then answer with an error status or an HTML page.
+pub const LINK_EXPIRED: &str = "Download link expired — request a fresh export";
+
+/// Failure reason for a response too short to be a photo or video.
+pub const TOO_SMALL: &str = "Server sent too little data to be a photo or video";
+
+/// Pause and cancel switches for memory downloads, managed by Tauri. Downloads look at them
+/// between chunks, bulk jobs also between items. Both switches reset once nothing is
+/// downloading any more.
+#[derive(Debug, Default)]
+pub struct DownloadControl {
+    /// Downloads in progress, single or bulk.
+    active: AtomicUsize,
+    paused: AtomicBool,
+    cancel: AtomicBool,
+}
+
+/// Payload of the `download-queue-state` event.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+pub struct DownloadQueueState {
+    pub running: bool,
+    pub paused: bool,
+    /// Cancel was asked for and downloads are still winding down.
+    pub cancelling: bool,
+}
+
+impl DownloadControl {
+    /// Register a download; the switches apply to it until the guard drops.
+    pub fn begin(&self) -> DownloadGuard<'_> {
+        if self.active.fetch_add(1, Ordering::SeqCst) == 0 {
+            self.paused.store(false, Ordering::SeqCst);
+            self.cancel.store(false, Ordering::SeqCst);
+        }
+        DownloadGuard { control: self }
+    }
+
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    /// Stop the current files and every queued one. Does nothing when nothing is downloading.
+    pub fn cancel(&self) {
+        if self.active.load(Ordering::SeqCst) > 0 {
+            self.cancel.store(true, Ordering::SeqCst);
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::SeqCst)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    pub fn state(&self) -> DownloadQueueState {
+        DownloadQueueState {
+            running: self.active.load(Ordering::SeqCst) > 0,
+            paused: self.is_paused(),
+            cancelling: self.is_cancelled(),
+        }
+    }
+
+    /// Wait out a pause. False once downloads are cancelled.
+    pub async fn proceed(&self) -> bool {
+        loop {
+            if self.is_cancelled() {
+                return false;
+            }
+            if !self.is_paused() {
+                return true;
+            }
+            tokio::time::sleep(PAUSE_POLL_INTERVAL).await;
+        }
+    }
+}
+
+pub struct DownloadGuard<'a> {
+    control: &'a DownloadControl,
+}
+
+impl Drop for DownloadGuard<'_> {
+    fn drop(&mut self) {
+        self.control.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Tell the frontend whether downloads are running, paused or being cancelled.
+pub fn emit_queue_state(app_handle: &AppHandle) {
+    let state = app_handle.state::<DownloadControl>().state();
+    app_handle.emit("download-queue-state", state).ok();
+}
+
 #[derive(Debug, Serialize, Clone)]
 pub struct DownloadProgress {
     pub memory_id: String,
@@ -20,6 +132,295 @@ pub struct DownloadProgress {
     pub total_bytes: Option<u64>,
 }
 
+/// Only plain HTTP(S) URLs with a host are accepted, for proxies and test targets alike.
+fn parse_http_url(label: &str, value: &str) -> AppResult<reqwest::Url> {
+    let url = reqwest::Url::parse(value.trim())
+        .map_err(|e| AppError::Validation(format!("Invalid {} '{}': {}", label, value, e)))?;
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return Err(AppError::Validation(format!(
+            "Invalid {} '{}': expected http://host:port or https://host:port",
+            label, value
+        )));
+    }
+    Ok(url)
+}
+
+/// Build the HTTP client for downloads from the user's settings. Settings that could never
+/// work (malformed proxy URL, zero timeout, unusable user agent) are rejected here, so they
+/// fail when saved rather than on the first download.
+///
+/// Explicit proxies take precedence; the system proxy is only used when neither is set.
+pub fn build_client(settings: &NetworkSettings) -> AppResult<Client> {
+    if !(1..=600).contains(&settings.timeout_secs) {
+        return Err(AppError::Validation("Timeout must be between 1 and 600 seconds".into()));
+    }
+    let timeout = Duration::from_secs(settings.timeout_secs);
+    let mut builder = Client::builder().connect_timeout(timeout).read_timeout(timeout);
+
+    let http_proxy = settings.http_proxy.as_deref().filter(|p| !p.trim().is_empty());
+    let https_proxy = settings.https_proxy.as_deref().filter(|p| !p.trim().is_empty());
+    if http_proxy.is_none() && https_proxy.is_none() && !settings.use_system_proxy {
+        builder = builder.no_proxy();
+    }
+    if let Some(proxy) = http_proxy {
+        let url = parse_http_url("HTTP proxy", proxy)?;
+        builder = builder.proxy(Proxy::http(url).map_err(|e| AppError::Validation(e.to_string()))?);
+    }
+    if let Some(proxy) = https_proxy {
+        let url = parse_http_url("HTTPS proxy", proxy)?;
+        builder = builder.proxy(Proxy::https(url).map_err(|e| AppError::Validation(e.to_string()))?);
+    }
+
+    if let Some(agent) = settings.user_agent.as_deref().filter(|a| !a.trim().is_empty()) {
+        let value = reqwest::header::HeaderValue::from_str(agent.trim())
+            .map_err(|_| AppError::Validation("User agent contains invalid characters".into()))?;
+        builder = builder.user_agent(value);
+    }
+
+    builder
+        .build()
+        .map_err(|e| AppError::Validation(format!("Could not set up the HTTP client: {}", e)))
+}
+
+/// Every message in an error's source chain; reqwest's top-level message alone is vague.
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+/// Fetch `url` through `client` and report how it went. Only the response head is awaited.
+pub async fn test_connection(client: &Client, url: &str) -> AppResult<ConnectionTestResult> {
+    let url = parse_http_url("test URL", url)?;
+    let start = Instant::now();
+    let result = client.get(url).send().await;
+    let latency_ms = start.elapsed().as_millis() as u64;
+    Ok(match result {
+        Ok(response) => ConnectionTestResult {
+            ok: response.status().is_success(),
+            status: Some(response.status().as_u16()),
+            latency_ms,
+            error: (!response.status().is_success()).then(|| format!("Server answered {}", response.status())),
+        },
+        Err(e) => ConnectionTestResult {
+            ok: false,
+            status: None,
+            latency_ms,
+            error: Some(error_chain(&e)),
+        },
+    })
+}
+
+/// Where a download is written until it completes: the final name plus `.part`, so an
+/// interrupted transfer never looks like a finished file.
+pub fn part_path(file_path: &Path) -> PathBuf {
+    let mut name = file_path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    file_path.with_file_name(name)
+}
+
+/// How fetching a file ended, when nothing went wrong locally.
+#[derive(Debug, PartialEq)]
+pub enum FetchOutcome {
+    /// Moved into place; holds the file's size.
+    Complete(u64),
+    /// The request or transfer failed. Whatever arrived is kept in the `.part` file for the
+    /// next attempt to resume from.
+    Interrupted(String),
+    /// The server answered with something other than the file: the link expired, or the body
+    /// is an HTML page or too short to be media. Nothing of it is kept, and retrying the same
+    /// link won't help.
+    Rejected(String),
+    /// Downloads were cancelled; the `.part` file is gone.
+    Cancelled,
+}
+
+/// Fetch `url` into `file_path` by way of its [`part_path`]. When a `.part` file is already
+/// there, only the rest is asked for with a `Range` header; a server that answers with the
+/// whole file instead starts it over. `progress` gets the bytes on disk and the expected total
+/// after each chunk. Pausing `control` between chunks lets the connection go and resumes with a
+/// new `Range` request once it is resumed; cancelling stops it.
+pub async fn fetch_resumable<F>(
+    client: &Client,
+    url: &str,
+    file_path: &Path,
+    control: &DownloadControl,
+    mut progress: F,
+) -> AppResult<FetchOutcome>
+where
+    F: FnMut(u64, Option<u64>),
+{
+    let part = part_path(file_path);
+    let (downloaded, total) = loop {
+        if !control.proceed().await {
+            remove_part(&part).await?;
+            return Ok(FetchOutcome::Cancelled);
+        }
+        let existing = tokio_fs::metadata(&part).await.map(|m| m.len()).unwrap_or(0);
+        let mut request = client.get(url);
+        if existing > 0 {
+            request = request.header(RANGE, format!("bytes={}-", existing));
+        }
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(e) => return Ok(network_failure(file_path, &e, "Could not reach the server")),
+        };
+
+        let status = response.status();
+        if status == StatusCode::RANGE_NOT_SATISFIABLE {
+            // The part file doesn't fit the file on the server any more; start over next time
+            tokio_fs::remove_file(&part).await?;
+            return Ok(FetchOutcome::Interrupted(
+                "Server refused to resume; restarting the download".into(),
+            ));
+        }
+        if link_expired(status) {
+            remove_part(&part).await?;
+            return Ok(FetchOutcome::Rejected(LINK_EXPIRED.into()));
+        }
+        if !status.is_success() {
+            return Ok(FetchOutcome::Interrupted(format!("Server answered {}", status)));
+        }
+        if is_html(&response) {
+            log::warn!("Got an HTML page instead of {}", file_path.display());
+            remove_part(&part).await?;
+            return Ok(FetchOutcome::Rejected(LINK_EXPIRED.into()));
+        }
+        let resumed = existing > 0 && status == StatusCode::PARTIAL_CONTENT;
+        if resumed && !content_range_starts_at(&response, existing) {
+            tokio_fs::remove_file(&part).await?;
+            return Ok(FetchOutcome::Interrupted(
+                "Server resumed at the wrong offset; restarting the download".into(),
+            ));
+        }
+
+        let (mut file, mut downloaded) = if resumed {
+            log::info!("Resuming {} at {} bytes", file_path.display(), existing);
+            (tokio_fs::OpenOptions::new().append(true).open(&part).await?, existing)
+        } else {
+            (tokio_fs::File::create(&part).await?, 0)
+        };
+        let total = response.content_length().map(|len| len + downloaded);
+        let mut stream = response.bytes_stream();
+        let mut interrupted = false;
+        while let Some(item) = stream.next().await {
+            let chunk = match item {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    file.flush().await?;
+                    return Ok(network_failure(file_path, &e, "Connection lost during the download"));
+                }
+            };
+            file.write_all(&chunk).await?;
+            downloaded += chunk.len() as u64;
+            progress(downloaded, total);
+            if control.is_paused() || control.is_cancelled() {
+                interrupted = true;
+                break;
+            }
+        }
+        file.flush().await?;
+        drop(file);
+        if !interrupted || total.is_some_and(|total| downloaded >= total) {
+            break (downloaded, total);
+        }
+        // A pause can outlast the read timeout, so the connection isn't held open through it:
+        // the rest is asked for with a new request once downloads resume
+        log::info!("Paused {} at {} bytes", file_path.display(), downloaded);
+    };
+
+    if let Some(total) = total.filter(|&total| downloaded < total) {
+        log::warn!(
+            "Connection closed after {} of {} bytes of {}",
+            downloaded,
+            total,
+            file_path.display()
+        );
+        return Ok(FetchOutcome::Interrupted("Connection lost during the download".into()));
+    }
+    if downloaded < MIN_MEDIA_BYTES {
+        tokio_fs::remove_file(&part).await?;
+        return Ok(FetchOutcome::Rejected(TOO_SMALL.into()));
+    }
+    tokio_fs::rename(&part, file_path).await?;
+    Ok(FetchOutcome::Complete(downloaded))
+}
+
+/// Remove the `.part` file of a download that won't be resumed, if there is one.
+async fn remove_part(part: &Path) -> AppResult<()> {
+    match tokio_fs::remove_file(part).await {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Log the details of a network error and keep a short reason, so failures group by cause
+/// rather than by the exact wording of each error.
+fn network_failure(file_path: &Path, error: &reqwest::Error, reason: &str) -> FetchOutcome {
+    log::warn!("{} ({}): {}", reason, file_path.display(), error_chain(error));
+    FetchOutcome::Interrupted(reason.to_string())
+}
+
+/// Statuses an expired download link answers with.
+fn link_expired(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN | StatusCode::NOT_FOUND | StatusCode::GONE
+    )
+}
+
+/// Whether the response is a web page, which a photo or video download never is.
+fn is_html(response: &reqwest::Response) -> bool {
+    response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.trim_start().to_ascii_lowercase().starts_with("text/html"))
+}
+
+/// Where a memory is downloaded to: `Memories/YYYY/MM/<id>.<ext>` under the storage root.
+pub fn target_path(memory: &Memory, storage_root: &Path) -> PathBuf {
+    let ext = if memory.media_type.to_lowercase() == "video" {
+        "mp4"
+    } else {
+        "jpg"
+    };
+    storage_root
+        .join("Memories")
+        .join(memory.timestamp.format("%Y").to_string())
+        .join(memory.timestamp.format("%m").to_string())
+        .join(format!("{}.{}", memory.id, ext))
+}
+
+/// A file already on disk for `memory`: the one it is linked to, or else the one a download
+/// into `storage_root` would write. Only files big enough to be the photo or video count.
+pub fn existing_download(memory: &Memory, storage_root: &Path) -> Option<PathBuf> {
+    memory
+        .media_path
+        .clone()
+        .into_iter()
+        .chain(std::iter::once(target_path(memory, storage_root)))
+        .find(|path| std::fs::metadata(path).is_ok_and(|m| m.is_file() && m.len() >= MIN_MEDIA_BYTES))
+}
+
+/// Whether a 206 response's `Content-Range` begins at byte `start`.
+fn content_range_starts_at(response: &reqwest::Response, start: u64) -> bool {
+    response
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("bytes "))
+        .and_then(|v| v.split('-').next())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        == Some(start)
+}
+
 pub struct MemoryDownloader {
     client: Client,
     app_handle: AppHandle,
@@ -27,25 +428,31 @@ pub struct MemoryDownloader {
 }
 
 impl MemoryDownloader {
-    pub fn new(app_handle: AppHandle, db: Arc<DatabaseManager>) -> Self {
-        Self {
-            client: Client::new(),
-            app_handle,
-            db,
-        }
+    /// Fails when the saved network settings can't produce a client.
+    pub fn new(app_handle: AppHandle, db: Arc<DatabaseManager>) -> AppResult<Self> {
+        let client = build_client(&db.get_network_settings()?)?;
+        Ok(Self { client, app_handle, db })
     }
 
-    pub async fn download_memory(&self, mut memory: Memory, storage_root: PathBuf) -> AppResult<()> {
+    /// Download one memory and return the status it ended up with.
+    pub async fn download_memory(&self, memory: Memory, storage_root: PathBuf) -> AppResult<DownloadStatus> {
         // Check disk space before starting (require > 500MB buffer)
         match StorageManager::get_disk_space(storage_root.clone()) {
             Ok(info) => {
                 if info.available_bytes < 500 * 1024 * 1024 {
-                    let msg = format!("Insufficient disk space. Available: {} MB, Required Buffer: 500 MB", info.available_bytes / (1024 * 1024));
+                    let msg = format!(
+                        "Insufficient disk space. Available: {} MB, Required Buffer: 500 MB",
+                        info.available_bytes / (1024 * 1024)
+                    );
                     log::error!("{}", msg);
-                    
-                    memory.download_status = DownloadStatus::Failed;
-                    self.db.batch_insert_memories(&[memory])?;
-                    
+
+                    self.db.update_memory_status(
+                        &memory.id,
+                        DownloadStatus::Failed,
+                        None,
+                        Some("Insufficient disk space"),
+                    )?;
+
                     self.app_handle.emit("download-error", msg.clone()).ok();
                     return Err(crate::error::AppError::Generic(msg));
                 }
@@ -60,72 +467,33 @@ impl MemoryDownloader {
             Some(url) => url,
             None => {
                 log::error!("No download URL for memory {}", memory.id);
-                return Ok(());
+                return Ok(memory.download_status);
             }
         };
 
-        // Determine file extension
-        let ext = if memory.media_type.to_lowercase() == "video" {
-            "mp4"
-        } else {
-            "jpg"
-        };
-
-        // Build subfolder structure: Memories/YYYY/MM/id.ext
-        let year = memory.timestamp.format("%Y").to_string();
-        let month = memory.timestamp.format("%m").to_string();
-        let target_dir = storage_root.join("Memories").join(year).join(month);
-
-        if !target_dir.exists() {
-            tokio_fs::create_dir_all(&target_dir).await?;
+        let file_path = target_path(&memory, &storage_root);
+        if let Some(target_dir) = file_path.parent().filter(|dir| !dir.exists()) {
+            tokio_fs::create_dir_all(target_dir).await?;
         }
 
-        let file_name = format!("{}.{}", memory.id, ext);
-        let file_path = target_dir.join(file_name);
-
         log::info!("Downloading memory {} to {:?}", memory.id, file_path);
 
         // Update status to Downloading
-        memory.download_status = DownloadStatus::Downloading;
-        self.db.batch_insert_memories(&[memory.clone()])?;
-
-        let response = match self.client.get(url).send().await {
-            Ok(res) => res,
-            Err(e) => {
-                log::error!("Failed to start download for {}: {}", memory.id, e);
-                memory.download_status = DownloadStatus::Failed;
-                self.db.batch_insert_memories(&[memory])?;
-                return Ok(());
-            }
-        };
-
-        let total_size = response.content_length();
-        let mut downloaded: u64 = 0;
-        let mut stream = response.bytes_stream();
-
-        let mut file = tokio_fs::File::create(&file_path).await?;
-
-        while let Some(item) = stream.next().await {
-            let chunk = match item {
-                Ok(chunk) => chunk,
-                Err(e) => {
-                    log::error!("Error while downloading {}: {}", memory.id, e);
-                    memory.download_status = DownloadStatus::Failed;
-                    self.db.batch_insert_memories(&[memory])?;
-                    return Ok(());
-                }
-            };
-            file.write_all(&chunk).await?;
-            downloaded += chunk.len() as u64;
+        self.db
+            .update_memory_status(&memory.id, DownloadStatus::Downloading, None, None)?;
 
-            if let Some(total) = total_size {
-                let progress = downloaded as f32 / total as f32;
-                self.app_handle
+        let memory_id = memory.id.clone();
+        let app_handle = self.app_handle.clone();
+        let control = self.app_handle.state::<DownloadControl>();
+        let _active = control.begin();
+        let outcome = fetch_resumable(&self.client, url, &file_path, &control, |downloaded, total| {
+            if let Some(total) = total {
+                app_handle
                     .emit(
                         "download-progress",
                         DownloadProgress {
-                            memory_id: memory.id.clone(),
-                            progress,
+                            memory_id: memory_id.clone(),
+                            progress: downloaded as f32 / total as f32,
                             status: "Downloading".to_string(),
                             bytes_downloaded: downloaded,
                             total_bytes: Some(total),
@@ -133,14 +501,39 @@ impl MemoryDownloader {
                     )
                     .ok();
             }
-        }
-
-        file.flush().await?;
+        })
+        .await;
+        let downloaded = match outcome {
+            Ok(FetchOutcome::Complete(bytes)) => bytes,
+            Ok(FetchOutcome::Cancelled) => {
+                log::info!("Download of memory {} cancelled", memory.id);
+                self.db
+                    .update_memory_status(&memory.id, DownloadStatus::Pending, None, None)?;
+                return Ok(DownloadStatus::Pending);
+            }
+            Ok(FetchOutcome::Interrupted(reason)) => {
+                // The .part file stays for the next attempt to resume from
+                log::error!("Error while downloading {}: {}", memory.id, reason);
+                self.db
+                    .update_memory_status(&memory.id, DownloadStatus::Failed, None, Some(&reason))?;
+                return Ok(DownloadStatus::Failed);
+            }
+            Ok(FetchOutcome::Rejected(reason)) => {
+                log::error!("Could not download {}: {}", memory.id, reason);
+                self.db
+                    .update_memory_status(&memory.id, DownloadStatus::Failed, None, Some(&reason))?;
+                return Err(AppError::Validation(reason));
+            }
+            Err(e) => {
+                self.db
+                    .update_memory_status(&memory.id, DownloadStatus::Failed, None, Some(&e.to_string()))?;
+                return Err(e);
+            }
+        };
 
         // Update status to Downloaded
-        memory.download_status = DownloadStatus::Downloaded;
-        memory.media_path = Some(file_path);
-        self.db.batch_insert_memories(&[memory.clone()])?;
+        self.db
+            .update_memory_status(&memory.id, DownloadStatus::Downloaded, Some(&file_path), None)?;
 
         self.app_handle
             .emit(
@@ -150,43 +543,602 @@ impl MemoryDownloader {
                     progress: 1.0,
                     status: "Downloaded".to_string(),
                     bytes_downloaded: downloaded,
-                    total_bytes: total_size,
+                    total_bytes: Some(downloaded),
                 },
             )
             .ok();
 
         log::info!("Successfully downloaded memory {}", memory.id);
-        Ok(())
+        Ok(DownloadStatus::Downloaded)
     }
 
-    pub async fn download_all_pending(&self) -> AppResult<()> {
-        let storage_path = self.db.get_setting("storage_path")?;
-        let storage_root = match storage_path {
-            Some(p) => PathBuf::from(p),
-            None => {
-                log::error!("No storage path set for downloads");
-                return Ok(());
-            }
+    /// Download every Pending or Failed memory matching `filter`, recorded as a new job.
+    pub async fn download_all_pending(&self, filter: MemoryFilter) -> AppResult<DownloadJob> {
+        let pending = self.db.get_undownloaded_memories(&filter)?;
+        let job = self.db.create_download_job(&filter, pending.len())?;
+        log::info!(
+            "Starting download job {} for {} pending memories",
+            job.id,
+            pending.len()
+        );
+        self.run_job(job, pending).await
+    }
+
+    /// Pick an unfinished job back up. Only its memories that are still Pending or Failed are
+    /// fetched; the ones downloaded before it stopped are skipped.
+    pub async fn resume_job(&self, job_id: i64) -> AppResult<DownloadJob> {
+        let job = self.db.get_download_job(job_id)?;
+        if job.state == DownloadJobState::Running {
+            return Err(AppError::Validation(format!(
+                "Download job {} is already running",
+                job_id
+            )));
+        }
+        let pending = self.db.get_undownloaded_memories(&job.filter)?;
+        log::info!(
+            "Resuming download job {} with {} remaining memories",
+            job.id,
+            pending.len()
+        );
+        self.run_job(job, pending).await
+    }
+
+    async fn run_job(&self, job: DownloadJob, pending: Vec<Memory>) -> AppResult<DownloadJob> {
+        let result = {
+            let control = self.app_handle.state::<DownloadControl>();
+            let _active = control.begin();
+            emit_queue_state(&self.app_handle);
+            self.download_each(job, pending, &control).await
         };
+        emit_queue_state(&self.app_handle);
+        result
+    }
 
-        let memories = self.db.get_memories(None)?;
-        let pending: Vec<Memory> = memories
-            .into_iter()
-            .filter(|m| m.download_status == DownloadStatus::Pending || m.download_status == DownloadStatus::Failed)
-            .collect();
+    async fn download_each(
+        &self,
+        mut job: DownloadJob,
+        pending: Vec<Memory>,
+        control: &DownloadControl,
+    ) -> AppResult<DownloadJob> {
+        let storage_root = self
+            .db
+            .downloads_root()?
+            .ok_or_else(|| AppError::Generic("No downloads path set".into()))?;
 
-        log::info!("Starting batch download for {} pending memories", pending.len());
+        job.state = DownloadJobState::Running;
+        job.failed = 0;
+        self.save_job(&job)?;
 
+        let mut fetched_any = false;
         for memory in pending {
-            if let Err(e) = self.download_memory(memory, storage_root.clone()).await {
-                log::error!("Failed to download memory: {}", e);
-                // Stop batch on disk space error
-                if e.to_string().contains("Insufficient disk space") {
-                    break;
+            if !control.proceed().await {
+                return self.stop_cancelled(job);
+            }
+            // Files from an earlier run, e.g. before the database was rebuilt, aren't fetched again
+            if let Some(path) = existing_download(&memory, &storage_root) {
+                log::info!("Memory {} is already at {}", memory.id, path.display());
+                self.db
+                    .update_memory_status(&memory.id, DownloadStatus::Downloaded, Some(&path), None)?;
+                job.skipped += 1;
+                self.save_job(&job)?;
+                continue;
+            }
+            if fetched_any {
+                tokio::time::sleep(BULK_DOWNLOAD_INTERVAL).await;
+            }
+            fetched_any = true;
+            match self.download_memory(memory, storage_root.clone()).await {
+                Ok(DownloadStatus::Downloaded) => job.completed += 1,
+                Ok(DownloadStatus::Pending) if control.is_cancelled() => return self.stop_cancelled(job),
+                Ok(_) => job.failed += 1,
+                Err(e) => {
+                    log::error!("Failed to download memory: {}", e);
+                    job.failed += 1;
+                    // Stop batch on disk space error
+                    if e.to_string().contains("Insufficient disk space") {
+                        job.state = DownloadJobState::Interrupted;
+                        self.save_job(&job)?;
+                        return Ok(job);
+                    }
                 }
             }
+            self.save_job(&job)?;
         }
 
+        job.state = DownloadJobState::Completed;
+        self.save_job(&job)?;
+        Ok(job)
+    }
+
+    /// End a cancelled job as Interrupted, so it can be resumed later.
+    fn stop_cancelled(&self, mut job: DownloadJob) -> AppResult<DownloadJob> {
+        log::info!(
+            "Download job {} cancelled ({} of {} done)",
+            job.id,
+            job.completed,
+            job.total
+        );
+        job.state = DownloadJobState::Interrupted;
+        self.save_job(&job)?;
+        Ok(job)
+    }
+
+    /// Persist the job and tell the frontend about it.
+    fn save_job(&self, job: &DownloadJob) -> AppResult<()> {
+        self.db.update_download_job(job)?;
+        self.app_handle.emit("download-job-progress", job).ok();
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    /// A one-shot HTTP server that answers 204 and hands back the raw request it received.
+    fn one_shot_server() -> (u16, std::thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 4096];
+            let n = stream.read(&mut buf).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            String::from_utf8_lossy(&buf[..n]).into_owned()
+        });
+        (port, handle)
+    }
+
+    /// An HTTP server for `connections` requests in turn, answering each with the raw bytes
+    /// `respond(request)` returns and then closing. Hands back the requests it received.
+    fn scripted_server<R>(connections: usize, respond: R) -> (String, std::thread::JoinHandle<Vec<String>>)
+    where
+        R: Fn(&str) -> Vec<u8> + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://127.0.0.1:{}/memory.mp4", listener.local_addr().unwrap().port());
+        let handle = std::thread::spawn(move || {
+            (0..connections)
+                .map(|_| {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let mut buf = [0u8; 4096];
+                    let n = stream.read(&mut buf).unwrap();
+                    let request = String::from_utf8_lossy(&buf[..n]).to_ascii_lowercase();
+                    stream.write_all(&respond(&request)).unwrap();
+                    request
+                })
+                .collect()
+        });
+        (url, handle)
+    }
+
+    fn video_bytes() -> Vec<u8> {
+        (0..64 * 1024).map(|i| (i % 251) as u8).collect()
+    }
+
+    /// The start of a `Range: bytes=N-` request header.
+    fn range_start(request: &str) -> Option<usize> {
+        let rest = request.split("range: bytes=").nth(1)?;
+        rest.split('-').next()?.parse().ok()
+    }
+
+    fn plain_client() -> Client {
+        build_client(&NetworkSettings {
+            use_system_proxy: false,
+            timeout_secs: 5,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_dropped_download_resumes_with_a_range_request() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("memory.mp4");
+        let body = video_bytes();
+        let half = body.len() / 2;
+        let served = body.clone();
+        let (url, server) = scripted_server(2, move |request| match range_start(request) {
+            // First attempt: promise the whole file, then drop the connection halfway
+            None => {
+                let mut out = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    served.len()
+                )
+                .into_bytes();
+                out.extend_from_slice(&served[..served.len() / 2]);
+                out
+            }
+            Some(start) => {
+                let mut out = format!(
+                    "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    start,
+                    served.len() - 1,
+                    served.len(),
+                    served.len() - start
+                )
+                .into_bytes();
+                out.extend_from_slice(&served[start..]);
+                out
+            }
+        });
+        let (client, control) = (plain_client(), DownloadControl::default());
+
+        let first = fetch_resumable(&client, &url, &target, &control, |_, _| {})
+            .await
+            .unwrap();
+        assert!(matches!(first, FetchOutcome::Interrupted(_)), "{:?}", first);
+        assert!(!target.exists());
+        assert_eq!(std::fs::read(part_path(&target)).unwrap(), body[..half]);
+
+        let mut last_progress = None;
+        let second = fetch_resumable(&client, &url, &target, &control, |done, total| {
+            last_progress = Some((done, total))
+        })
+        .await
+        .unwrap();
+        assert_eq!(second, FetchOutcome::Complete(body.len() as u64));
+        assert_eq!(last_progress, Some((body.len() as u64, Some(body.len() as u64))));
+        assert_eq!(std::fs::read(&target).unwrap(), body);
+        assert!(!part_path(&target).exists());
+
+        let requests = server.join().unwrap();
+        assert_eq!(range_start(&requests[0]), None);
+        assert_eq!(range_start(&requests[1]), Some(half));
+    }
+
+    #[tokio::test]
+    async fn test_server_ignoring_range_restarts_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("memory.mp4");
+        std::fs::write(part_path(&target), b"stale bytes from another attempt").unwrap();
+        let body = video_bytes();
+        let served = body.clone();
+        let (url, server) = scripted_server(1, move |_| {
+            let mut out = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                served.len()
+            )
+            .into_bytes();
+            out.extend_from_slice(&served);
+            out
+        });
+
+        let control = DownloadControl::default();
+        let outcome = fetch_resumable(&plain_client(), &url, &target, &control, |_, _| {})
+            .await
+            .unwrap();
+        assert_eq!(outcome, FetchOutcome::Complete(body.len() as u64));
+        assert_eq!(std::fs::read(&target).unwrap(), body);
+        assert_eq!(range_start(&server.join().unwrap()[0]), Some(32));
+    }
+
+    #[tokio::test]
+    async fn test_failed_response_keeps_the_part_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("memory.mp4");
+        std::fs::write(part_path(&target), b"half").unwrap();
+        let (url, _server) = scripted_server(1, |_| {
+            b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec()
+        });
+
+        let control = DownloadControl::default();
+        let outcome = fetch_resumable(&plain_client(), &url, &target, &control, |_, _| {})
+            .await
+            .unwrap();
+        assert!(
+            matches!(outcome, FetchOutcome::Interrupted(ref e) if e.contains("503")),
+            "{:?}",
+            outcome
+        );
+        assert_eq!(std::fs::read(part_path(&target)).unwrap(), b"half");
+        assert!(!target.exists());
+    }
+
+    #[tokio::test]
+    async fn test_expired_link_answers_are_rejected() {
+        let page = "<html><body>This link has expired</body></html>";
+        let responses = [
+            "HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                page.len(),
+                page
+            ),
+            "HTTP/1.1 200 OK\r\nContent-Type: video/mp4\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}"
+                .to_string(),
+        ];
+        let expected = [LINK_EXPIRED, LINK_EXPIRED, TOO_SMALL];
+        let served = responses.clone();
+        let count = AtomicUsize::new(0);
+        let (url, _server) = scripted_server(responses.len(), move |_| {
+            served[count.fetch_add(1, Ordering::SeqCst)].clone().into_bytes()
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("memory.mp4");
+        let (client, control) = (plain_client(), DownloadControl::default());
+        for reason in expected {
+            // A part left by an earlier attempt goes too; the link it came from is dead
+            std::fs::write(part_path(&target), b"half").unwrap();
+            let outcome = fetch_resumable(&client, &url, &target, &control, |_, _| {})
+                .await
+                .unwrap();
+            assert_eq!(outcome, FetchOutcome::Rejected(reason.to_string()));
+            assert!(!target.exists());
+            assert!(!part_path(&target).exists());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancel_discards_the_part_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("memory.mp4");
+        std::fs::write(part_path(&target), b"half").unwrap();
+        let control = DownloadControl::default();
+        let _active = control.begin();
+        control.cancel();
+        assert!(control.state().cancelling);
+
+        // Nothing listens here; a cancelled fetch never gets as far as connecting
+        let outcome = fetch_resumable(&plain_client(), "http://127.0.0.1:9/x", &target, &control, |_, _| {})
+            .await
+            .unwrap();
+        assert_eq!(outcome, FetchOutcome::Cancelled);
+        assert!(!part_path(&target).exists());
+    }
+
+    #[tokio::test]
+    async fn test_paused_download_waits_for_resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("memory.mp4");
+        let body = video_bytes();
+        let served = body.clone();
+        let (url, _server) = scripted_server(1, move |_| {
+            let mut out = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                served.len()
+            )
+            .into_bytes();
+            out.extend_from_slice(&served);
+            out
+        });
+        let control = Arc::new(DownloadControl::default());
+        let _active = control.begin();
+        control.pause();
+
+        let fetch = tokio::spawn({
+            let (control, target) = (control.clone(), target.clone());
+            async move { fetch_resumable(&plain_client(), &url, &target, &control, |_, _| {}).await }
+        });
+        tokio::time::sleep(PAUSE_POLL_INTERVAL * 3).await;
+        assert!(!fetch.is_finished());
+        assert!(!part_path(&target).exists());
+        assert_eq!(
+            control.state(),
+            DownloadQueueState {
+                running: true,
+                paused: true,
+                cancelling: false
+            }
+        );
+
+        control.resume();
+        assert_eq!(fetch.await.unwrap().unwrap(), FetchOutcome::Complete(body.len() as u64));
+        assert_eq!(std::fs::read(&target).unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn test_pause_longer_than_the_read_timeout_resumes_with_a_range_request() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("memory.mp4");
+        let body = video_bytes();
+        let half = body.len() / 2;
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://127.0.0.1:{}/memory.mp4", listener.local_addr().unwrap().port());
+        let served = body.clone();
+        let server = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buf = [0u8; 4096];
+                let n = stream.read(&mut buf).unwrap();
+                let request = String::from_utf8_lossy(&buf[..n]).to_ascii_lowercase();
+                match range_start(&request) {
+                    // Send half the body, then stall with the connection left open
+                    None => {
+                        let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", served.len());
+                        stream.write_all(head.as_bytes()).unwrap();
+                        stream.write_all(&served[..half]).unwrap();
+                        std::thread::spawn(move || {
+                            std::thread::sleep(Duration::from_secs(5));
+                            drop(stream);
+                        });
+                    }
+                    Some(start) => {
+                        let head = format!(
+                            "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                            start,
+                            served.len() - 1,
+                            served.len(),
+                            served.len() - start
+                        );
+                        stream.write_all(head.as_bytes()).unwrap();
+                        stream.write_all(&served[start..]).unwrap();
+                    }
+                }
+                requests.push(request);
+            }
+            requests
+        });
+        let client = build_client(&NetworkSettings {
+            use_system_proxy: false,
+            timeout_secs: 1,
+            ..Default::default()
+        })
+        .unwrap();
+        let control = Arc::new(DownloadControl::default());
+        let _active = control.begin();
+
+        let fetch = tokio::spawn({
+            let (control, target) = (control.clone(), target.clone());
+            async move {
+                fetch_resumable(&client, &url, &target, &control, |done, _| {
+                    if done >= half as u64 {
+                        control.pause();
+                    }
+                })
+                .await
+            }
+        });
+        while !control.is_paused() && !fetch.is_finished() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        // Paused for longer than the client waits on a silent connection
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(!fetch.is_finished());
+        control.resume();
+
+        assert_eq!(fetch.await.unwrap().unwrap(), FetchOutcome::Complete(body.len() as u64));
+        assert_eq!(std::fs::read(&target).unwrap(), body);
+        let requests = server.join().unwrap();
+        assert_eq!(range_start(&requests[1]), Some(half));
+    }
+
+    #[test]
+    fn test_control_switches_reset_when_downloads_finish() {
+        let control = DownloadControl::default();
+        control.cancel();
+        assert!(!control.is_cancelled(), "cancel with nothing running is a no-op");
+
+        let first = control.begin();
+        control.pause();
+        control.cancel();
+        drop(first);
+        assert!(!control.state().running);
+
+        let _second = control.begin();
+        assert_eq!(
+            control.state(),
+            DownloadQueueState {
+                running: true,
+                paused: false,
+                cancelling: false
+            }
+        );
+    }
+
+    #[test]
+    fn test_existing_download_finds_linked_or_expected_file() {
+        let storage = tempfile::tempdir().unwrap();
+        let mut memory = crate::test_fixtures::memories().remove(2);
+        assert_eq!(existing_download(&memory, storage.path()), None);
+
+        // Left by an earlier run under the storage root
+        let expected = target_path(&memory, storage.path());
+        assert!(expected.ends_with("Memories/2023/01/fixture_memory_2.jpg"));
+        std::fs::create_dir_all(expected.parent().unwrap()).unwrap();
+        std::fs::write(&expected, video_bytes()).unwrap();
+        assert_eq!(existing_download(&memory, storage.path()), Some(expected.clone()));
+
+        // The linked file wins, but only when it is big enough to be the photo
+        let linked = storage.path().join("moved.jpg");
+        memory.media_path = Some(linked.clone());
+        std::fs::write(&linked, b"<html>expired</html>").unwrap();
+        assert_eq!(existing_download(&memory, storage.path()), Some(expected.clone()));
+        std::fs::write(&linked, video_bytes()).unwrap();
+        assert_eq!(existing_download(&memory, storage.path()), Some(linked));
+
+        std::fs::write(&expected, b"tiny").unwrap();
+        memory.media_path = None;
+        assert_eq!(existing_download(&memory, storage.path()), None);
+    }
+
+    #[test]
+    fn test_part_path_keeps_the_extension() {
+        assert_eq!(
+            part_path(Path::new("/m/2023/01/abc.mp4")),
+            PathBuf::from("/m/2023/01/abc.mp4.part")
+        );
+    }
+
+    #[test]
+    fn test_invalid_settings_fail_validation() {
+        let invalid = [
+            NetworkSettings {
+                http_proxy: Some("not a url".into()),
+                ..Default::default()
+            },
+            NetworkSettings {
+                https_proxy: Some("ftp://proxy.local:21".into()),
+                ..Default::default()
+            },
+            NetworkSettings {
+                timeout_secs: 0,
+                ..Default::default()
+            },
+            NetworkSettings {
+                user_agent: Some("bad\nagent".into()),
+                ..Default::default()
+            },
+        ];
+        for settings in invalid {
+            assert!(
+                matches!(build_client(&settings), Err(AppError::Validation(_))),
+                "{:?}",
+                settings
+            );
+        }
+        let valid = NetworkSettings {
+            http_proxy: Some("http://proxy.local:8080".into()),
+            https_proxy: Some("".into()),
+            ..Default::default()
+        };
+        assert!(build_client(&valid).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_connection_goes_through_configured_proxy() {
+        let (port, server) = one_shot_server();
+        let client = build_client(&NetworkSettings {
+            http_proxy: Some(format!("http://127.0.0.1:{}", port)),
+            user_agent: Some("SnapDataExplorer-Test/1.0".into()),
+            ..Default::default()
+        })
+        .unwrap();
+        let result = test_connection(&client, "http://snapdata.invalid/ping").await.unwrap();
+        assert!(result.ok, "{:?}", result.error);
+        assert_eq!(result.status, Some(204));
+
+        let request = server.join().unwrap();
+        assert!(
+            request.starts_with("GET http://snapdata.invalid/ping HTTP/1.1"),
+            "{}",
+            request
+        );
+        assert!(request
+            .to_ascii_lowercase()
+            .contains("user-agent: snapdataexplorer-test/1.0"));
+    }
+
+    #[tokio::test]
+    async fn test_connection_reports_errors_instead_of_failing() {
+        // Bind then drop to get a port nothing listens on
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let client = build_client(&NetworkSettings {
+            use_system_proxy: false,
+            timeout_secs: 5,
+            ..Default::default()
+        })
+        .unwrap();
+        let url = format!("http://127.0.0.1:{}/", port);
+        let result = test_connection(&client, &url).await.unwrap();
+        assert!(!result.ok);
+        assert!(result.error.is_some());
+        assert!(test_connection(&client, "file:///etc/passwd").await.is_err());
+    }
+}
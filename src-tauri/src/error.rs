@@ -11,17 +11,56 @@ pub enum AppError {
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
     #[error("Database error: {0}")]
-    Sqlite(#[from] rusqlite::Error),
+    Sqlite(rusqlite::Error),
+    /// SQLite stayed busy or locked past the busy timeout and any retries. Safe to retry; the
+    /// frontend matches the "DatabaseBusy" prefix.
+    #[error("DatabaseBusy: {0}")]
+    Busy(String),
     #[error("Serialization error: {0}")]
     Serde(#[from] serde_json::Error),
     #[error("Validation error: {0}")]
     Validation(String),
     #[error("Parsing error: {0}")]
     Parsing(String),
+    /// A json/ file that is an HTML error page, truncated, or otherwise not JSON.
+    #[error("{0}")]
+    CorruptFile(crate::models::JsonFileIssue),
+    /// A write was attempted while a read-only database is active. The frontend matches the
+    /// "ReadOnlyDatabase" prefix.
+    #[error("ReadOnlyDatabase: {0}")]
+    ReadOnlyDatabase(String),
+    /// A requested record does not exist. The frontend matches the "NotFound" prefix.
+    #[error("NotFound: {0}")]
+    NotFound(String),
+    /// An output file already exists and overwriting was not requested. The frontend matches
+    /// the "FileExists" prefix.
+    #[error("FileExists: {0}")]
+    FileExists(String),
+    /// The user stopped the operation. The frontend matches the "Cancelled" prefix.
+    #[error("Cancelled: {0}")]
+    Cancelled(String),
     #[error("{0}")]
     Generic(String),
 }
 
+/// Whether SQLite gave up waiting on another connection's lock.
+pub fn is_busy(e: &rusqlite::Error) -> bool {
+    matches!(
+        e.sqlite_error_code(),
+        Some(rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked)
+    )
+}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(e: rusqlite::Error) -> Self {
+        if is_busy(&e) {
+            AppError::Busy(e.to_string())
+        } else {
+            AppError::Sqlite(e)
+        }
+    }
+}
+
 impl Serialize for AppError {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
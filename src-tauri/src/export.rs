@@ -0,0 +1,1340 @@
+//! Helpers for writing conversations out of the app: file naming, output locations and the
+//! exported text itself.
+
+use crate::db::DatabaseManager;
+use crate::error::{AppError, AppResult};
+use crate::gallery::escape_html;
+use crate::ingestion::artifacts::hash_file;
+use crate::models::{BulkExportReport, Event, ExportOptions, ExportProgress, Person};
+use chrono::{DateTime, NaiveDate, Utc};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{BufWriter, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::LazyLock;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Longest file stem we produce, in bytes. Leaves room for a " (n)" suffix and an extension
+/// within the 255-byte name limit common to all supported filesystems.
+const MAX_STEM_BYTES: usize = 150;
+
+/// Characters kept as-is besides letters, digits and spaces; everything else becomes `_`.
+const SAFE_PUNCTUATION: &[char] = &['-', '_', '.', ',', '(', ')', '\'', '&', '+', '!', '#', '='];
+
+/// Device names Windows refuses as file stems, whatever the extension.
+const WINDOWS_RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1", "LPT2",
+    "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Make `name` safe to use as a file stem on Windows, macOS and Linux: reserved characters,
+/// control characters and emoji are replaced, runs of separators collapsed, trailing dots and
+/// spaces removed, and the result trimmed to [`MAX_STEM_BYTES`] on a character boundary.
+pub fn sanitize_file_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for c in name.chars() {
+        let c = if c.is_alphanumeric() || SAFE_PUNCTUATION.contains(&c) {
+            c
+        } else if c.is_whitespace() {
+            ' '
+        } else {
+            '_'
+        };
+        // Collapse "a / b" or "🎉🎉" into a single separator
+        let last = out.chars().last();
+        if (c == ' ' || c == '_') && matches!(last, Some(' ') | Some('_')) {
+            if c == '_' && last == Some(' ') {
+                out.pop();
+                out.push('_');
+            }
+            continue;
+        }
+        out.push(c);
+    }
+
+    let mut cleaned = out.trim_matches([' ', '_', '.']).to_string();
+    if cleaned.len() > MAX_STEM_BYTES {
+        let mut end = MAX_STEM_BYTES;
+        while !cleaned.is_char_boundary(end) {
+            end -= 1;
+        }
+        cleaned.truncate(end);
+        cleaned = cleaned.trim_end_matches([' ', '_', '.']).to_string();
+    }
+    if cleaned.is_empty() {
+        return "conversation".to_string();
+    }
+    let upper = cleaned.split('.').next().unwrap_or_default().to_ascii_uppercase();
+    if WINDOWS_RESERVED.contains(&upper.as_str()) {
+        cleaned.insert(0, '_');
+    }
+    cleaned
+}
+
+/// File extension for an export format name.
+pub fn format_extension(format: &str) -> &'static str {
+    match format {
+        "json" => "json",
+        "html" => "html",
+        "bundle" => "zip",
+        _ => "txt",
+    }
+}
+
+/// Suggested file stem for a conversation export: its name plus the dates it covers.
+pub fn suggested_stem(display_name: &str, range: Option<(DateTime<Utc>, DateTime<Utc>)>) -> String {
+    let label = match range {
+        Some((first, last)) if first.date_naive() == last.date_naive() => {
+            format!("{} ({})", display_name, first.format("%Y-%m-%d"))
+        }
+        Some((first, last)) => format!(
+            "{} ({} to {})",
+            display_name,
+            first.format("%Y-%m-%d"),
+            last.format("%Y-%m-%d")
+        ),
+        None => display_name.to_string(),
+    };
+    sanitize_file_name(&label)
+}
+
+/// `dir/stem.ext`, or the first `dir/stem (n).ext` that does not exist yet.
+pub fn collision_free_path(dir: &Path, stem: &str, extension: &str) -> PathBuf {
+    let candidate = dir.join(format!("{}.{}", stem, extension));
+    if !candidate.exists() {
+        return candidate;
+    }
+    (2..)
+        .map(|n| dir.join(format!("{} ({}).{}", stem, n, extension)))
+        .find(|p| !p.exists())
+        .expect("unbounded suffix search")
+}
+
+/// What an export says about itself before the first message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportHeader {
+    pub display_name: String,
+    /// Which messages were exported, e.g. "2023-06-01 00:00 to 2023-08-31 23:59 UTC, sent by
+    /// alice"; None when all of them were.
+    pub filter: Option<String>,
+    /// Messages the filter left out.
+    pub omitted: i32,
+}
+
+/// Writes one conversation in one export format. `write_conversation` calls `begin` once,
+/// `message` for each message oldest first, then `finish`.
+pub trait ConversationExporter {
+    fn begin(&mut self, header: &ExportHeader, writer: &mut dyn Write) -> AppResult<()>;
+    fn message(&mut self, msg: &Event, writer: &mut dyn Write) -> AppResult<()>;
+    fn finish(&mut self, writer: &mut dyn Write) -> AppResult<()>;
+}
+
+/// The exporter for a format name: "json", "html", or a plain-text transcript for anything else.
+pub fn exporter_for(format: &str, options: &ExportOptions, owner: Option<String>) -> Box<dyn ConversationExporter> {
+    match format {
+        "json" => Box::new(JsonExporter { first: true }),
+        "html" => Box::new(HtmlExporter::new(options, owner)),
+        _ => Box::new(TextExporter),
+    }
+}
+
+/// Write a conversation to `writer` in `format` (see [`exporter_for`]), one message at a time.
+/// Only messages within the options' date range and from their senders are written.
+pub fn write_conversation(
+    db: &DatabaseManager,
+    conversation_id: &str,
+    format: &str,
+    options: &ExportOptions,
+    writer: &mut impl Write,
+) -> AppResult<()> {
+    write_conversation_with(db, conversation_id, format, options, &mut Pseudonyms::default(), writer)
+}
+
+/// `write_conversation` sharing `pseudonyms` with the other conversations of one export.
+fn write_conversation_with(
+    db: &DatabaseManager,
+    conversation_id: &str,
+    format: &str,
+    options: &ExportOptions,
+    pseudonyms: &mut Pseudonyms,
+    writer: &mut impl Write,
+) -> AppResult<()> {
+    stream_conversation(
+        db,
+        conversation_id,
+        options,
+        pseudonyms,
+        |owner| exporter_for(format, options, owner),
+        writer,
+    )
+}
+
+fn stream_conversation<E>(
+    db: &DatabaseManager,
+    conversation_id: &str,
+    options: &ExportOptions,
+    pseudonyms: &mut Pseudonyms,
+    exporter: E,
+    writer: &mut impl Write,
+) -> AppResult<()>
+where
+    E: FnOnce(Option<String>) -> Box<dyn ConversationExporter>,
+{
+    let owner = db.get_owner_profile()?.map(|o| o.username);
+    let (owner, display_name) = if options.anonymize {
+        let conversation = db.get_conversation(conversation_id)?;
+        pseudonyms.recognize(&db.get_people()?, &conversation.participants)?;
+        // Name the owner and participants first so the header can use their pseudonyms
+        let owner = owner.map(|o| pseudonyms.name(&o));
+        let participants: Vec<String> = conversation.participants.iter().map(|p| pseudonyms.name(p)).collect();
+        let display_name = if participants.is_empty() {
+            "Conversation".to_string()
+        } else {
+            participants.join(", ")
+        };
+        (owner, display_name)
+    } else {
+        let display_name = db
+            .get_conversation_name(conversation_id)?
+            .unwrap_or_else(|| conversation_id.to_string());
+        (owner, display_name)
+    };
+    let mut exporter = exporter(owner);
+    let (after, before, senders) = (options.after, options.before, &options.senders);
+    let filter = describe_filter(options, pseudonyms);
+    let omitted = match filter {
+        Some(_) => {
+            db.count_messages_filtered(conversation_id, None, None, &[])?
+                - db.count_messages_filtered(conversation_id, after, before, senders)?
+        }
+        None => 0,
+    };
+    exporter.begin(
+        &ExportHeader {
+            display_name,
+            filter,
+            omitted,
+        },
+        writer,
+    )?;
+    db.foreach_message_filtered(conversation_id, after, before, senders, |mut msg| {
+        if options.anonymize {
+            msg = pseudonyms.anonymize(msg);
+        }
+        if options.mask_contact_details {
+            msg.content = msg.content.map(|c| mask_contact_details(&c));
+        }
+        exporter.message(&msg, writer)
+    })?;
+    exporter.finish(writer)
+}
+
+/// Chat JSON metadata keys that can name a person or conversation: the chat's title and the
+/// unrecognized fields kept as-is.
+const IDENTIFYING_METADATA_KEYS: &[&str] = &["conversation_title", "extra"];
+
+/// Stand-ins for usernames in an anonymized export: each person becomes "Person A",
+/// "Person B", ... in the order first met, and keeps that name for the whole export. The
+/// mapping only lives as long as the export and is never written anywhere.
+#[derive(Debug, Default)]
+pub struct Pseudonyms {
+    names: HashMap<String, String>,
+    /// Usernames and display names to look for in message text, lowercased, with the username
+    /// each stands for.
+    aliases: HashMap<String, String>,
+    /// Matches any of `aliases` as a whole word, in any case.
+    mentions: Option<Regex>,
+}
+
+impl Pseudonyms {
+    pub fn name(&mut self, username: &str) -> String {
+        let next = self.names.len();
+        self.names
+            .entry(username.to_string())
+            .or_insert_with(|| format!("Person {}", pseudonym_letters(next)))
+            .clone()
+    }
+
+    /// Replace the usernames and display names of `people`, and the `usernames` of anyone
+    /// else, where they come up in message text.
+    fn recognize(&mut self, people: &[Person], usernames: &[String]) -> AppResult<()> {
+        let known = self.aliases.len();
+        let names = people
+            .iter()
+            .flat_map(|p| {
+                [Some(&p.username), p.display_name.as_ref()]
+                    .into_iter()
+                    .flatten()
+                    .map(|n| (n, &p.username))
+            })
+            .chain(usernames.iter().map(|u| (u, u)));
+        for (name, username) in names {
+            let name = name.trim().to_lowercase();
+            if !name.is_empty() {
+                self.aliases.entry(name).or_insert_with(|| username.clone());
+            }
+        }
+        if self.aliases.len() == known {
+            return Ok(());
+        }
+
+        // Longest first, so "Alice Smith" wins over "Alice"
+        let mut aliases: Vec<&String> = self.aliases.keys().collect();
+        aliases.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+        let word_edge = |c: Option<char>| {
+            if c.is_some_and(|c| c.is_alphanumeric() || c == '_') {
+                r"\b"
+            } else {
+                ""
+            }
+        };
+        let pattern: Vec<String> = aliases
+            .iter()
+            .map(|a| {
+                format!(
+                    "{}{}{}",
+                    word_edge(a.chars().next()),
+                    regex::escape(a),
+                    word_edge(a.chars().last())
+                )
+            })
+            .collect();
+        let mentions = Regex::new(&format!("(?i)(?:{})", pattern.join("|")))
+            .map_err(|e| AppError::Generic(format!("Could not prepare name replacement: {}", e)))?;
+        self.mentions = Some(mentions);
+        Ok(())
+    }
+
+    /// `text` with every recognized name replaced by that person's pseudonym.
+    fn replace_names(&mut self, text: &str) -> String {
+        let Some(mentions) = self.mentions.clone() else {
+            return text.to_string();
+        };
+        mentions
+            .replace_all(text, |caps: &regex::Captures| {
+                let matched = caps[0].to_lowercase();
+                let username = self.aliases.get(&matched).cloned().unwrap_or(matched);
+                self.name(&username)
+            })
+            .into_owned()
+    }
+
+    /// `msg` without anything naming its sender, its conversation or the people in it: the
+    /// sender and any names in the text are replaced, the display name and conversation id
+    /// dropped, and so are the metadata keys that can carry names.
+    fn anonymize(&mut self, mut msg: Event) -> Event {
+        msg.sender = self.name(&msg.sender);
+        msg.sender_name = None;
+        msg.conversation_id = None;
+        msg.content = msg.content.map(|text| self.replace_names(&text));
+        msg.metadata = msg.metadata.as_deref().and_then(strip_identifying_metadata);
+        msg
+    }
+}
+
+/// `metadata` without [`IDENTIFYING_METADATA_KEYS`]; None when nothing else is left or it
+/// isn't a JSON object.
+fn strip_identifying_metadata(metadata: &str) -> Option<String> {
+    let serde_json::Value::Object(mut map) = serde_json::from_str(metadata).ok()? else {
+        return None;
+    };
+    for key in IDENTIFYING_METADATA_KEYS {
+        map.remove(*key);
+    }
+    (!map.is_empty()).then(|| serde_json::Value::Object(map).to_string())
+}
+
+/// "A" to "Z", then "AA", "AB", ... like spreadsheet columns.
+fn pseudonym_letters(mut n: usize) -> String {
+    let mut letters = Vec::new();
+    loop {
+        letters.push(b'A' + (n % 26) as u8);
+        if n < 26 {
+            break;
+        }
+        n = n / 26 - 1;
+    }
+    letters.iter().rev().map(|&b| b as char).collect()
+}
+
+static EMAIL_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap());
+
+/// Ten-digit numbers, optionally with a country code, in the usual groupings: "555 123 4567",
+/// "(555) 123-4567", "+44 555.123.4567". Dates and short numbers don't match.
+static PHONE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:\+\d{1,3}[\s.-]?)?(?:\(\d{3}\)|\b\d{3})[\s.-]?\d{3}[\s.-]?\d{4}\b").unwrap());
+
+/// `text` with email addresses and phone numbers replaced by "[email]" and "[phone]".
+pub fn mask_contact_details(text: &str) -> String {
+    let text = EMAIL_RE.replace_all(text, "[email]");
+    PHONE_RE.replace_all(&text, "[phone]").into_owned()
+}
+
+/// The options' message filter in words, or None when they don't filter.
+fn describe_filter(options: &ExportOptions, pseudonyms: &mut Pseudonyms) -> Option<String> {
+    let time = |t: DateTime<Utc>| t.format("%Y-%m-%d %H:%M").to_string();
+    let range = match (options.after, options.before) {
+        (Some(after), Some(before)) => Some(format!("{} to {} UTC", time(after), time(before))),
+        (Some(after), None) => Some(format!("from {} UTC", time(after))),
+        (None, Some(before)) => Some(format!("until {} UTC", time(before))),
+        (None, None) => None,
+    };
+    let senders: Vec<String> = if options.anonymize {
+        options.senders.iter().map(|s| pseudonyms.name(s)).collect()
+    } else {
+        options.senders.clone()
+    };
+    let senders = (!senders.is_empty()).then(|| format!("sent by {}", senders.join(", ")));
+    match (range, senders) {
+        (Some(range), Some(senders)) => Some(format!("{}, {}", range, senders)),
+        (range, senders) => range.or(senders),
+    }
+}
+
+/// Write every conversation to its own file in `output_dir`, named after the conversation
+/// with its id appended so two chats of the same name can't collide. A conversation that
+/// fails is logged and reported, and the rest are still written. `progress` is called after
+/// each conversation and once when done.
+pub fn export_all_conversations<F>(
+    db: &DatabaseManager,
+    format: &str,
+    options: &ExportOptions,
+    output_dir: &Path,
+    mut progress: F,
+) -> AppResult<BulkExportReport>
+where
+    F: FnMut(&ExportProgress),
+{
+    fs::create_dir_all(output_dir)?;
+    let options = ExportOptions {
+        link_base: Some(output_dir.to_path_buf()),
+        ..options.clone()
+    };
+    let conversations = db.get_conversations(None)?;
+    let mut report = BulkExportReport {
+        output_dir: output_dir.to_path_buf(),
+        ..Default::default()
+    };
+    let mut status = ExportProgress {
+        total: conversations.len(),
+        ..Default::default()
+    };
+
+    // One set of pseudonyms, so a person has the same one in every file
+    let mut pseudonyms = Pseudonyms::default();
+
+    for (index, conversation) in conversations.iter().enumerate() {
+        let name = conversation.display_name.as_deref().unwrap_or(&conversation.id);
+        let stem = if options.anonymize {
+            format!("Conversation {}", index + 1)
+        } else {
+            bulk_stem(name, &conversation.id)
+        };
+        let path = collision_free_path(output_dir, &stem, format_extension(format));
+        let written = (|| {
+            let mut writer = BufWriter::new(fs::File::create(&path)?);
+            if format == "bundle" {
+                write_bundle_with(db, &conversation.id, &options, &mut pseudonyms, &mut writer, |_| {})?;
+            } else {
+                write_conversation_with(db, &conversation.id, format, &options, &mut pseudonyms, &mut writer)?;
+            }
+            writer.flush()?;
+            Ok::<_, AppError>(())
+        })();
+        match written {
+            Ok(()) => report.written += 1,
+            Err(e) => {
+                log::warn!("Could not export conversation {}: {}", conversation.id, e);
+                let _ = fs::remove_file(&path);
+                report.failed += 1;
+                report.failures.push((conversation.id.clone(), e.to_string()));
+            }
+        }
+        status.processed += 1;
+        status.current = Some(if options.anonymize { stem } else { name.to_string() });
+        progress(&status);
+    }
+
+    status.done = true;
+    progress(&status);
+    log::info!(
+        "Exported {} conversations to {} ({} failed)",
+        report.written,
+        output_dir.display(),
+        report.failed
+    );
+    Ok(report)
+}
+
+/// File stem for one conversation of a bulk export: its name followed by its id, unless the
+/// name is just the id. A long name is shortened so the id always fits.
+fn bulk_stem(display_name: &str, conversation_id: &str) -> String {
+    let name = sanitize_file_name(display_name);
+    let id = sanitize_file_name(conversation_id);
+    let mut end = name.len().min(MAX_STEM_BYTES.saturating_sub(id.len() + 3));
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    let name = name[..end].trim_end_matches([' ', '_', '.']);
+    if name.is_empty() || name == id {
+        id
+    } else {
+        format!("{} ({})", name, id)
+    }
+}
+
+/// Folder inside a bundle that the conversation's media is copied to.
+const BUNDLE_MEDIA_DIR: &str = "media";
+
+/// Write a conversation as a zip bundle: `transcript.html`, copies of its media under `media/`
+/// that the transcript links to, and `missing_media.txt` listing media no longer on disk.
+/// Identical files are stored once. `progress` is called after each media file and once when
+/// done.
+pub fn write_bundle<W, F>(
+    db: &DatabaseManager,
+    conversation_id: &str,
+    options: &ExportOptions,
+    writer: W,
+    progress: F,
+) -> AppResult<()>
+where
+    W: Write + Seek,
+    F: FnMut(&ExportProgress),
+{
+    write_bundle_with(
+        db,
+        conversation_id,
+        options,
+        &mut Pseudonyms::default(),
+        writer,
+        progress,
+    )
+}
+
+/// `write_bundle` sharing `pseudonyms` with the other conversations of one export.
+fn write_bundle_with<W, F>(
+    db: &DatabaseManager,
+    conversation_id: &str,
+    options: &ExportOptions,
+    pseudonyms: &mut Pseudonyms,
+    writer: W,
+    mut progress: F,
+) -> AppResult<()>
+where
+    W: Write + Seek,
+    F: FnMut(&ExportProgress),
+{
+    let mut media = Vec::new();
+    let mut seen = HashSet::new();
+    db.foreach_message_filtered(
+        conversation_id,
+        options.after,
+        options.before,
+        &options.senders,
+        |msg| {
+            media.extend(msg.media_references.into_iter().filter(|p| seen.insert(p.clone())));
+            Ok(())
+        },
+    )?;
+
+    let mut zip = ZipWriter::new(writer);
+    // Photos and videos are already compressed
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let mut bundled: HashMap<PathBuf, String> = HashMap::new();
+    let mut by_content: HashMap<(u64, String), String> = HashMap::new();
+    let mut used_names = HashSet::new();
+    let mut missing = Vec::new();
+    let mut status = ExportProgress {
+        total: media.len(),
+        ..Default::default()
+    };
+
+    for path in &media {
+        let content = fs::metadata(path)
+            .ok()
+            .filter(|m| m.is_file())
+            .and_then(|m| hash_file(path).ok().map(|hash| (m.len(), hash)));
+        match content {
+            None => {
+                log::warn!("Bundle export: media file is missing: {}", path.display());
+                missing.push(path);
+            }
+            Some(key) => {
+                if let Some(name) = by_content.get(&key) {
+                    bundled.insert(path.clone(), name.clone());
+                } else {
+                    let name = unique_file_name(path, &mut used_names);
+                    zip.start_file(
+                        format!("{}/{}", BUNDLE_MEDIA_DIR, name),
+                        stored.large_file(key.0 > u32::MAX as u64),
+                    )
+                    .map_err(zip_error)?;
+                    std::io::copy(&mut fs::File::open(path)?, &mut zip)?;
+                    bundled.insert(path.clone(), name.clone());
+                    by_content.insert(key, name);
+                }
+            }
+        }
+        status.processed += 1;
+        status.current = path.file_name().map(|n| n.to_string_lossy().into_owned());
+        progress(&status);
+    }
+
+    if !missing.is_empty() {
+        zip.start_file("missing_media.txt", SimpleFileOptions::default())
+            .map_err(zip_error)?;
+        for path in &missing {
+            writeln!(zip, "{}", path.display())?;
+        }
+    }
+
+    zip.start_file("transcript.html", SimpleFileOptions::default())
+        .map_err(zip_error)?;
+    let exporter = |owner| -> Box<dyn ConversationExporter> {
+        Box::new(HtmlExporter {
+            bundled: Some(bundled),
+            ..HtmlExporter::new(options, owner)
+        })
+    };
+    stream_conversation(db, conversation_id, options, pseudonyms, exporter, &mut zip)?;
+    zip.finish().map_err(zip_error)?;
+
+    status.done = true;
+    progress(&status);
+    log::info!(
+        "Bundled conversation {} with {} media files ({} missing)",
+        conversation_id,
+        by_content.len(),
+        missing.len()
+    );
+    Ok(())
+}
+
+fn zip_error(e: zip::result::ZipError) -> AppError {
+    AppError::Generic(format!("Could not write bundle: {}", e))
+}
+
+/// `path`'s file name, or `name (n).ext` when another file already took it.
+fn unique_file_name(path: &Path, used: &mut HashSet<String>) -> String {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "media".to_string());
+    if used.insert(name.clone()) {
+        return name;
+    }
+    let stem = Path::new(&name)
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = Path::new(&name)
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    (2..)
+        .map(|n| format!("{} ({}){}", stem, n, extension))
+        .find(|candidate| used.insert(candidate.clone()))
+        .expect("unbounded suffix search")
+}
+
+/// A JSON object with the conversation's name, the filter and how many messages it left out
+/// (`filter` is null when there is none), and a `messages` array.
+struct JsonExporter {
+    first: bool,
+}
+
+impl ConversationExporter for JsonExporter {
+    fn begin(&mut self, header: &ExportHeader, writer: &mut dyn Write) -> AppResult<()> {
+        writer.write_all(
+            format!(
+                "{{\"conversation\":{},\"filter\":{},\"omitted\":{},\"messages\":[\n",
+                serde_json::to_string(&header.display_name)?,
+                serde_json::to_string(&header.filter)?,
+                header.omitted
+            )
+            .as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    fn message(&mut self, msg: &Event, writer: &mut dyn Write) -> AppResult<()> {
+        if !self.first {
+            writer.write_all(b",\n")?;
+        }
+        serde_json::to_writer(&mut *writer, msg).map_err(|e| AppError::Generic(e.to_string()))?;
+        self.first = false;
+        Ok(())
+    }
+
+    fn finish(&mut self, writer: &mut dyn Write) -> AppResult<()> {
+        writer.write_all(b"\n]}")?;
+        Ok(())
+    }
+}
+
+/// A plain-text transcript, one line per message.
+struct TextExporter;
+
+impl ConversationExporter for TextExporter {
+    fn begin(&mut self, header: &ExportHeader, writer: &mut dyn Write) -> AppResult<()> {
+        writer.write_all(format!("Conversation: {}\n", header.display_name).as_bytes())?;
+        if let Some(filter) = &header.filter {
+            writer.write_all(format!("Messages: {} ({} omitted)\n", filter, header.omitted).as_bytes())?;
+        }
+        writer.write_all(b"---\n\n")?;
+        Ok(())
+    }
+
+    fn message(&mut self, msg: &Event, writer: &mut dyn Write) -> AppResult<()> {
+        let sender = msg.sender_name.as_deref().unwrap_or(&msg.sender);
+        let time = msg.timestamp.format("%Y-%m-%d %H:%M:%S");
+        let line = format!("[{}] {}: {}\n", time, sender, msg.content.as_deref().unwrap_or(""));
+        writer.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    fn finish(&mut self, _writer: &mut dyn Write) -> AppResult<()> {
+        Ok(())
+    }
+}
+
+/// Largest image embedded as a data URI when `ExportOptions::embed_images` is set; bigger
+/// ones are linked.
+pub const EMBED_IMAGE_MAX_BYTES: u64 = 256 * 1024;
+
+const HTML_STYLE: &str =
+    "body{font-family:sans-serif;margin:0 auto;max-width:720px;padding:24px;background:#f4f4f5;color:#18181b}\
+h1{font-size:22px}\
+.day{text-align:center;color:#71717a;font-size:12px;font-weight:bold;margin:24px 0 8px}\
+.msg{display:flex;flex-direction:column;align-items:flex-start;margin:6px 0}\
+.msg.me{align-items:flex-end}\
+.bubble{max-width:75%;background:#fff;border-radius:16px;padding:8px 12px;box-shadow:0 1px 2px rgba(0,0,0,.1)}\
+.me .bubble{background:#fde047}\
+.sender{font-size:12px;font-weight:bold;color:#52525b;margin:0 4px 2px}\
+.time{font-size:11px;color:#a1a1aa;margin:2px 4px 0}\
+.text{white-space:pre-wrap;word-wrap:break-word}\
+.media img,.media video{display:block;max-width:100%;max-height:360px;border-radius:10px;margin-top:4px}\
+.type{font-size:12px;color:#71717a;font-style:italic}\
+.filter{color:#71717a;font-size:13px}";
+
+/// A chat-style HTML page with inline styles: a bubble per message with the sender's name and
+/// time, the owner's messages on the right, a separator for each day, and media shown inline.
+struct HtmlExporter {
+    embed_images: bool,
+    /// Directory media links are relative to, when the page is written to a file.
+    link_base: Option<PathBuf>,
+    owner: Option<String>,
+    last_day: Option<NaiveDate>,
+    /// In a bundle, each media file's name under `media/`; files not in it are missing.
+    bundled: Option<HashMap<PathBuf, String>>,
+}
+
+impl HtmlExporter {
+    fn new(options: &ExportOptions, owner: Option<String>) -> Self {
+        Self {
+            embed_images: options.embed_images,
+            link_base: options.link_base.clone(),
+            owner,
+            last_day: None,
+            bundled: None,
+        }
+    }
+
+    fn media_html(&self, path: &Path) -> String {
+        let mime = image_mime(path);
+        if let Some(mime) = mime.filter(|_| self.embed_images) {
+            let small = fs::metadata(path).is_ok_and(|m| m.is_file() && m.len() <= EMBED_IMAGE_MAX_BYTES);
+            if small {
+                if let Ok(bytes) = fs::read(path) {
+                    return format!("<img src=\"data:{};base64,{}\" alt=\"\">", mime, base64_encode(&bytes));
+                }
+            }
+        }
+        let href = match &self.bundled {
+            Some(bundled) => match bundled.get(path) {
+                Some(name) => format!("{}/{}", BUNDLE_MEDIA_DIR, url_escape(name)),
+                None => {
+                    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+                    return format!("<div class=\"type\">Missing: {}</div>", escape_html(&name));
+                }
+            },
+            None => media_link(path, self.link_base.as_deref()),
+        };
+        let href = escape_html(&href);
+        if mime.is_some() {
+            format!("<a href=\"{0}\"><img loading=\"lazy\" src=\"{0}\" alt=\"\"></a>", href)
+        } else if is_video(path) {
+            format!("<video controls preload=\"none\" src=\"{}\"></video>", href)
+        } else {
+            let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+            format!("<a href=\"{}\">{}</a>", href, escape_html(&name))
+        }
+    }
+}
+
+impl ConversationExporter for HtmlExporter {
+    fn begin(&mut self, header: &ExportHeader, writer: &mut dyn Write) -> AppResult<()> {
+        let title = escape_html(&header.display_name);
+        writer.write_all(
+            format!(
+                "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{}</title><style>{}</style></head>\n<body>\n<h1>{}</h1>\n",
+                title, HTML_STYLE, title
+            )
+            .as_bytes(),
+        )?;
+        if let Some(filter) = &header.filter {
+            writer.write_all(
+                format!(
+                    "<p class=\"filter\">Messages {} ({} omitted)</p>\n",
+                    escape_html(filter),
+                    header.omitted
+                )
+                .as_bytes(),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn message(&mut self, msg: &Event, writer: &mut dyn Write) -> AppResult<()> {
+        let mut html = String::new();
+        let day = msg.timestamp.date_naive();
+        if self.last_day != Some(day) {
+            html.push_str(&format!("<div class=\"day\">{}</div>\n", day.format("%A, %B %-d, %Y")));
+            self.last_day = Some(day);
+        }
+        let from_owner = self.owner.as_deref() == Some(msg.sender.as_str());
+        let sender = msg.sender_name.as_deref().unwrap_or(&msg.sender);
+        html.push_str(&format!(
+            "<div class=\"msg{}\"><div class=\"sender\">{}</div><div class=\"bubble\">",
+            if from_owner { " me" } else { "" },
+            escape_html(sender)
+        ));
+        match msg.content.as_deref().filter(|c| !c.is_empty()) {
+            Some(text) => html.push_str(&format!("<div class=\"text\">{}</div>", escape_html(text))),
+            None if msg.media_references.is_empty() => {
+                html.push_str(&format!("<div class=\"type\">{}</div>", escape_html(&msg.event_type)))
+            }
+            None => {}
+        }
+        if !msg.media_references.is_empty() {
+            html.push_str("<div class=\"media\">");
+            for path in &msg.media_references {
+                html.push_str(&self.media_html(path));
+            }
+            html.push_str("</div>");
+        }
+        html.push_str(&format!(
+            "</div><div class=\"time\" title=\"{}\">{}</div></div>\n",
+            msg.timestamp.to_rfc3339(),
+            msg.timestamp.format("%H:%M")
+        ));
+        writer.write_all(html.as_bytes())?;
+        Ok(())
+    }
+
+    fn finish(&mut self, writer: &mut dyn Write) -> AppResult<()> {
+        writer.write_all(b"</body></html>\n")?;
+        Ok(())
+    }
+}
+
+fn image_mime(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    match ext.as_str() {
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "png" => Some("image/png"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+fn is_video(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| matches!(e.to_ascii_lowercase().as_str(), "mp4" | "mov" | "webm" | "m4v"))
+}
+
+/// A link to `path` relative to `base`, or a `file://` URL when there is no base or the two
+/// share no root (such as different Windows drives).
+fn media_link(path: &Path, base: Option<&Path>) -> String {
+    let relative = base.and_then(|base| {
+        let path_parts: Vec<Component> = path.components().collect();
+        let base_parts: Vec<Component> = base.components().collect();
+        let common = path_parts.iter().zip(&base_parts).take_while(|(a, b)| a == b).count();
+        let shares_root = (common > 0 && !matches!(path_parts[0], Component::Prefix(_))) || common > 1;
+        shares_root.then(|| {
+            let mut parts: Vec<String> = vec!["..".to_string(); base_parts.len() - common];
+            parts.extend(
+                path_parts[common..]
+                    .iter()
+                    .map(|c| url_escape(&c.as_os_str().to_string_lossy())),
+            );
+            parts.join("/")
+        })
+    });
+    relative.unwrap_or_else(|| {
+        let parts: Vec<String> = path
+            .components()
+            .filter(|c| !matches!(c, Component::RootDir))
+            .map(|c| url_escape(&c.as_os_str().to_string_lossy()))
+            .collect();
+        format!("file:///{}", parts.join("/"))
+    })
+}
+
+/// Percent-encode the characters of a path segment that would break a URL.
+fn url_escape(segment: &str) -> String {
+    let mut out = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b':' => out.push(byte as char),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | ((b as u32) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_sanitize_reserved_and_emoji() {
+        assert_eq!(sanitize_file_name("Alice / Bob: \"plans\"?"), "Alice_Bob_plans");
+        assert_eq!(sanitize_file_name("🎉🎉 Party Crew 🎉"), "Party Crew");
+        assert_eq!(sanitize_file_name("Zoë & José"), "Zoë & José");
+        assert_eq!(sanitize_file_name("con"), "_con");
+        assert_eq!(sanitize_file_name("...  "), "conversation");
+        assert_eq!(sanitize_file_name("line\nbreak\ttab"), "line break tab");
+    }
+
+    #[test]
+    fn test_sanitize_trims_long_names_on_char_boundary() {
+        let long = "日本語".repeat(100);
+        let cleaned = sanitize_file_name(&long);
+        assert!(cleaned.len() <= MAX_STEM_BYTES);
+        assert!(long.starts_with(&cleaned));
+        assert!(cleaned.chars().count() >= 40);
+    }
+
+    #[test]
+    fn test_suggested_path_avoids_collisions() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = Utc.with_ymd_and_hms(2023, 1, 1, 12, 0, 0).unwrap();
+        let last = Utc.with_ymd_and_hms(2023, 3, 5, 8, 0, 0).unwrap();
+        let stem = suggested_stem("Weekend 🏕️ Plans", Some((first, last)));
+        assert_eq!(stem, "Weekend_Plans (2023-01-01 to 2023-03-05)");
+
+        let path = collision_free_path(dir.path(), &stem, "txt");
+        assert_eq!(path, dir.path().join(format!("{}.txt", stem)));
+        std::fs::write(&path, "x").unwrap();
+        let next = collision_free_path(dir.path(), &stem, "txt");
+        assert_eq!(next, dir.path().join(format!("{} (2).txt", stem)));
+    }
+
+    fn html_for(db: &DatabaseManager, conversation_id: &str, options: &ExportOptions) -> String {
+        let mut out = Vec::new();
+        write_conversation(db, conversation_id, "html", options, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_html_export_renders_days_senders_and_media_links() {
+        let db = test_fixtures::standard_db();
+        db.set_setting("owner_username", test_fixtures::OWNER).unwrap();
+        let html = html_for(&db, "group_weekend", &ExportOptions::default());
+        assert!(html.starts_with("<!DOCTYPE html>") && html.ends_with("</body></html>\n"));
+        assert!(html.contains("<title>Weekend Plans</title>"));
+        // Messages run from Jan 1 16:00 to Jan 3 13:00
+        assert_eq!(html.matches("<div class=\"day\">").count(), 3);
+        assert!(html.contains("<div class=\"day\">Monday, January 2, 2023</div>"));
+        assert!(html.contains("<div class=\"msg me\"><div class=\"sender\">Me</div>"));
+        assert!(html.contains("<div class=\"sender\">Erin</div>"));
+        assert!(html.contains("src=\"file:///fixtures/media/2023-01-01_MEDIA009.jpg\""));
+
+        let options = ExportOptions {
+            link_base: Some(PathBuf::from("/fixtures/exports")),
+            ..Default::default()
+        };
+        assert!(html_for(&db, "group_weekend", &options).contains("src=\"../media/2023-01-01_MEDIA009.jpg\""));
+    }
+
+    #[test]
+    fn test_streamed_export_matches_the_whole_conversation_at_once() {
+        let db = test_fixtures::standard_db();
+        db.set_owner_profile(&crate::models::OwnerProfile {
+            username: test_fixtures::OWNER.to_string(),
+            display_name: Some("Me Myself".to_string()),
+        })
+        .unwrap();
+        for conversation in ["alice", "group_weekend"] {
+            let messages = db.get_messages(conversation).unwrap();
+            let name = db.get_conversation_name(conversation).unwrap().unwrap();
+
+            let mut text = format!("Conversation: {}\n---\n\n", name);
+            for msg in &messages {
+                text.push_str(&format!(
+                    "[{}] {}: {}\n",
+                    msg.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                    msg.sender_name.as_deref().unwrap_or(&msg.sender),
+                    msg.content.as_deref().unwrap_or("")
+                ));
+            }
+            let mut out = Vec::new();
+            write_conversation(&db, conversation, "text", &ExportOptions::default(), &mut out).unwrap();
+            assert_eq!(String::from_utf8(out).unwrap(), text);
+
+            let rows: Vec<String> = messages.iter().map(|m| serde_json::to_string(m).unwrap()).collect();
+            let mut out = Vec::new();
+            write_conversation(&db, conversation, "json", &ExportOptions::default(), &mut out).unwrap();
+            let name = db
+                .get_conversation_name(conversation)
+                .unwrap()
+                .unwrap_or(conversation.to_string());
+            assert_eq!(
+                String::from_utf8(out).unwrap(),
+                format!(
+                    "{{\"conversation\":{},\"filter\":null,\"omitted\":0,\"messages\":[\n{}\n]}}",
+                    serde_json::to_string(&name).unwrap(),
+                    rows.join(",\n")
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_html_export_escapes_text_and_embeds_small_images() {
+        let dir = tempfile::tempdir().unwrap();
+        let small = dir.path().join("small photo.png");
+        std::fs::write(&small, b"png").unwrap();
+        let large = dir.path().join("large.jpg");
+        std::fs::write(&large, vec![0u8; EMBED_IMAGE_MAX_BYTES as usize + 1]).unwrap();
+
+        let db = test_fixtures::standard_db();
+        let mut message = test_fixtures::events().remove(0);
+        message.id = "html_export".to_string();
+        message.content = Some("<b>bold</b> & \"quoted\"".to_string());
+        message.media_references = vec![small.clone(), large.clone()];
+        db.batch_insert_events(&[message], test_fixtures::EXPORT_ID).unwrap();
+
+        let linked = html_for(&db, "alice", &ExportOptions::default());
+        assert!(linked.contains("&lt;b&gt;bold&lt;/b&gt; &amp; &quot;quoted&quot;"));
+        assert!(linked.contains("small%20photo.png"));
+        assert!(!linked.contains("data:image"));
+
+        let embedded = html_for(
+            &db,
+            "alice",
+            &ExportOptions {
+                embed_images: true,
+                ..Default::default()
+            },
+        );
+        assert!(embedded.contains("<img src=\"data:image/png;base64,cG5n\" alt=\"\">"));
+        assert!(embedded.contains("large.jpg\"><img loading=\"lazy\""));
+    }
+
+    #[test]
+    fn test_bundle_copies_media_once_and_lists_missing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = |sub: &str, data: &[u8]| {
+            let path = dir.path().join(sub).join("photo.jpg");
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, data).unwrap();
+            path
+        };
+        let (first, copy, other) = (file("a", b"same"), file("b", b"same"), file("c", b"other"));
+        let gone = dir.path().join("gone").join("clip.mp4");
+
+        let db = test_fixtures::standard_db();
+        let mut message = test_fixtures::events().remove(0);
+        message.id = "bundle_export".to_string();
+        message.media_references = vec![first, copy, other, gone.clone()];
+        db.batch_insert_events(&[message], test_fixtures::EXPORT_ID).unwrap();
+
+        let mut out = std::io::Cursor::new(Vec::new());
+        let mut updates = Vec::new();
+        write_bundle(
+            &db,
+            "alice",
+            &ExportOptions::default(),
+            &mut out,
+            |p: &ExportProgress| updates.push(p.clone()),
+        )
+        .unwrap();
+        assert_eq!(updates.len(), 5);
+        assert_eq!(
+            updates.last().map(|p| (p.processed, p.total, p.done)),
+            Some((4, 4, true))
+        );
+
+        let mut archive = zip::ZipArchive::new(out).unwrap();
+        let mut names: Vec<&str> = archive.file_names().collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "media/photo (2).jpg",
+                "media/photo.jpg",
+                "missing_media.txt",
+                "transcript.html"
+            ]
+        );
+        let read = |archive: &mut zip::ZipArchive<_>, name: &str| {
+            let mut text = String::new();
+            std::io::Read::read_to_string(&mut archive.by_name(name).unwrap(), &mut text).unwrap();
+            text
+        };
+        assert_eq!(read(&mut archive, "media/photo.jpg"), "same");
+        assert_eq!(read(&mut archive, "media/photo (2).jpg"), "other");
+        assert_eq!(read(&mut archive, "missing_media.txt"), format!("{}\n", gone.display()));
+
+        let transcript = read(&mut archive, "transcript.html");
+        assert_eq!(transcript.matches("src=\"media/photo.jpg\"").count(), 2);
+        assert!(transcript.contains("src=\"media/photo%20%282%29.jpg\""));
+        assert!(transcript.contains("Missing: clip.mp4"));
+        assert!(!transcript.contains(&dir.path().display().to_string()));
+    }
+
+    #[test]
+    fn test_filtered_export_states_range_and_omitted_count() {
+        let db = test_fixtures::standard_db();
+        let base = test_fixtures::base_time();
+        let options = ExportOptions {
+            after: Some(base + chrono::Duration::hours(5)),
+            before: Some(base + chrono::Duration::hours(11)),
+            senders: vec!["alice".to_string()],
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        write_conversation(&db, "alice", "text", &options, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("Messages: 2023-01-01 17:00 to 2023-01-01 23:00 UTC, sent by alice (18 omitted)\n"));
+        let lines: Vec<&str> = text.lines().filter(|l| l.starts_with('[')).collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("[2023-01-01 17:00:00] Alice:"));
+
+        let mut out = Vec::new();
+        write_conversation(&db, "alice", "json", &options, &mut out).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(
+            json["filter"],
+            "2023-01-01 17:00 to 2023-01-01 23:00 UTC, sent by alice"
+        );
+        assert_eq!(json["omitted"], 18);
+        let messages: Vec<Event> = serde_json::from_value(json["messages"].clone()).unwrap();
+        assert_eq!(messages.len(), 2);
+
+        let unfiltered = html_for(&db, "alice", &ExportOptions::default());
+        assert!(!unfiltered.contains("class=\"filter\""));
+        assert!(html_for(&db, "alice", &options).contains("<p class=\"filter\">Messages 2023-01-01 17:00 to"));
+    }
+
+    #[test]
+    fn test_bulk_export_writes_a_file_per_conversation() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_fixtures::standard_db();
+        let mut updates = Vec::new();
+        let report = export_all_conversations(&db, "text", &ExportOptions::default(), dir.path(), |p| {
+            updates.push(p.clone())
+        })
+        .unwrap();
+        assert_eq!((report.written, report.failed), (3, 0));
+        let mut names: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["Weekend Plans (group_weekend).txt", "alice.txt", "bob.txt"]);
+        let group = std::fs::read_to_string(dir.path().join("Weekend Plans (group_weekend).txt")).unwrap();
+        assert!(group.starts_with("Conversation: Weekend Plans"));
+
+        assert_eq!(updates.len(), 4);
+        assert_eq!(updates[2].processed, 3);
+        assert!(updates[..3].iter().all(|p| !p.done && p.total == 3));
+        assert!(updates[3].done);
+
+        // A second run doesn't overwrite the first
+        export_all_conversations(&db, "text", &ExportOptions::default(), dir.path(), |_| {}).unwrap();
+        assert!(dir.path().join("alice (2).txt").exists());
+    }
+
+    #[test]
+    fn test_bulk_stem_keeps_the_id() {
+        assert_eq!(bulk_stem("alice", "alice"), "alice");
+        assert_eq!(bulk_stem("🎉 Party / Crew", "group:1"), "Party_Crew (group_1)");
+        assert_eq!(bulk_stem("🎉", "abc"), "conversation (abc)");
+        let long = bulk_stem(&"Long name ".repeat(30), "some_id");
+        assert!(long.len() <= MAX_STEM_BYTES && long.ends_with(" (some_id)"));
+    }
+
+    /// Every username and display name in the fixture chats, lowercased.
+    const FIXTURE_NAMES: &[&str] = &["alice", "bob", "carol", "dave", "erin", "weekend"];
+
+    fn names_anyone(output: &[u8]) -> bool {
+        let text = String::from_utf8_lossy(output).to_lowercase();
+        FIXTURE_NAMES.iter().any(|name| text.contains(name))
+    }
+
+    #[test]
+    fn test_anonymized_export_never_names_anyone() {
+        let db = test_fixtures::standard_db();
+        db.set_setting("owner_username", test_fixtures::OWNER).unwrap();
+        let mention = Event {
+            id: "mentions_people".to_string(),
+            sender: "alice".to_string(),
+            content: Some("tell BOB @carol is in, Erin too (plans with Dave)".to_string()),
+            ..test_fixtures::events().remove(1)
+        };
+        db.batch_insert_events(&[mention], test_fixtures::EXPORT_ID).unwrap();
+        let anonymized = ExportOptions {
+            anonymize: true,
+            ..Default::default()
+        };
+        for format in ["text", "json", "html"] {
+            for conversation in ["alice", "bob", "group_weekend"] {
+                let mut out = Vec::new();
+                write_conversation(&db, conversation, format, &anonymized, &mut out).unwrap();
+                assert!(
+                    !names_anyone(&out),
+                    "{} export of {} names someone",
+                    format,
+                    conversation
+                );
+            }
+        }
+
+        // Names in the text become the same pseudonyms as the senders
+        let mut out = Vec::new();
+        write_conversation(&db, "alice", "text", &anonymized, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("] Person B: tell Person C @Person D is in, Person E too"));
+
+        // Metadata keeps its flags but not the fields that can name someone
+        let mut out = Vec::new();
+        write_conversation(&db, "bob", "json", &anonymized, &mut out).unwrap();
+        let json = String::from_utf8(out).unwrap();
+        assert!(json.contains(r#"\"saved\":true"#));
+        assert!(!json.contains("Screenshot"));
+        let options = ExportOptions {
+            senders: vec!["alice".to_string()],
+            ..anonymized.clone()
+        };
+
+        // The owner is met first, then the conversation's participants
+        let mut out = Vec::new();
+        write_conversation(&db, "alice", "text", &options, &mut out).unwrap();
+        assert!(!names_anyone(&out));
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("Conversation: Person B\nMessages: sent by Person B (10 omitted)\n"));
+        assert!(text
+            .lines()
+            .filter(|l| l.starts_with('['))
+            .all(|l| l.contains("] Person B: ")));
+
+        let html = html_for(&db, "alice", &anonymized);
+        assert!(html.contains("<div class=\"msg me\"><div class=\"sender\">Person A</div>"));
+
+        let mut out = std::io::Cursor::new(Vec::new());
+        write_bundle(&db, "group_weekend", &anonymized, &mut out, |_| {}).unwrap();
+        let mut archive = zip::ZipArchive::new(out).unwrap();
+        let mut transcript = Vec::new();
+        std::io::Read::read_to_end(&mut archive.by_name("transcript.html").unwrap(), &mut transcript).unwrap();
+        assert!(!names_anyone(&transcript));
+    }
+
+    #[test]
+    fn test_bulk_anonymized_export_keeps_pseudonyms_across_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_fixtures::standard_db();
+        db.set_setting("owner_username", test_fixtures::OWNER).unwrap();
+        let options = ExportOptions {
+            anonymize: true,
+            ..Default::default()
+        };
+        export_all_conversations(&db, "json", &options, dir.path(), |_| {}).unwrap();
+        let mut names: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            ["Conversation 1.json", "Conversation 2.json", "Conversation 3.json"]
+        );
+
+        let person = Regex::new(r"Person [A-Z]+").unwrap();
+        let mut seen = HashSet::new();
+        for name in names {
+            let bytes = std::fs::read(dir.path().join(name)).unwrap();
+            assert!(!names_anyone(&bytes));
+            let text = String::from_utf8(bytes).unwrap();
+            // The owner is in every conversation
+            assert!(text.contains("\"sender\":\"Person A\""));
+            seen.extend(person.find_iter(&text).map(|m| m.as_str().to_string()));
+        }
+        // Owner, alice, bob and the three in the group: six people, six pseudonyms
+        assert_eq!(seen.len(), 6);
+    }
+
+    #[test]
+    fn test_mask_contact_details() {
+        assert_eq!(
+            mask_contact_details("mail jo.smith+snap@example.co.uk or call (555) 123-4567"),
+            "mail [email] or call [phone]"
+        );
+        assert_eq!(
+            mask_contact_details("+44 555.123.4567 / 5551234567"),
+            "[phone] / [phone]"
+        );
+        let untouched = "meet 2023-01-01 at 12:30, room 101, code 1234567";
+        assert_eq!(mask_contact_details(untouched), untouched);
+    }
+
+    #[test]
+    fn test_pseudonym_letters() {
+        assert_eq!(pseudonym_letters(0), "A");
+        assert_eq!(pseudonym_letters(25), "Z");
+        assert_eq!(pseudonym_letters(26), "AA");
+        assert_eq!(pseudonym_letters(27), "AB");
+        assert_eq!(pseudonym_letters(26 * 27), "AAA");
+        let mut pseudonyms = Pseudonyms::default();
+        assert_eq!(pseudonyms.name("alice"), "Person A");
+        assert_eq!(pseudonyms.name("bob"), "Person B");
+        assert_eq!(pseudonyms.name("alice"), "Person A");
+    }
+
+    #[test]
+    fn test_base64_padding() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"a"), "YQ==");
+        assert_eq!(base64_encode(b"ab"), "YWI=");
+        assert_eq!(base64_encode(b"abc"), "YWJj");
+        assert_eq!(base64_encode(&[0xff, 0xfe, 0xfd, 0x00]), "//79AA==");
+    }
+}
@@ -0,0 +1,207 @@
+//! Moves of indexed media files, journaled in `fs_operations` so a crash between moving a file
+//! and updating the database can be repaired on the next start.
+//!
+//! A move is journaled as Pending before the file is touched, and the database update commits
+//! together with the Done mark. A Pending move found at startup therefore means the database
+//! still points at the source, and only the file system needs looking at to finish or undo it.
+//! Anything that moves a file the database refers to should go through `move_indexed_file`, as
+//! `relocate_downloads` does when the downloads folder moves.
+
+use crate::db::DatabaseManager;
+use crate::error::{AppError, AppResult};
+use crate::models::{FsOperationState, FsRecoveryReport};
+use std::fs;
+use std::path::Path;
+
+/// Move `src` to `dst` and repoint the database at it. Returns how many memories and messages
+/// now reference `dst`.
+pub fn move_indexed_file(db: &DatabaseManager, kind: &str, src: &Path, dst: &Path) -> AppResult<usize> {
+    if !src.is_file() {
+        return Err(AppError::NotFound(src.display().to_string()));
+    }
+    if dst.exists() {
+        return Err(AppError::FileExists(dst.display().to_string()));
+    }
+    let op = db.begin_fs_operation(kind, src, dst)?;
+    if let Err(e) = move_file(src, dst) {
+        db.set_fs_operation_state(op.id, FsOperationState::RolledBack)?;
+        return Err(e.into());
+    }
+    db.finish_fs_operation(&op)
+}
+
+/// Move every downloaded memory file under `from` to the same place under `to`, each through
+/// [`move_indexed_file`]. Files kept anywhere else stay where they are. Returns how many moved.
+pub fn relocate_downloads(db: &DatabaseManager, from: &Path, to: &Path) -> AppResult<usize> {
+    let mut moved = 0;
+    for memory in db.get_memories(None)? {
+        let Some(src) = memory.media_path.as_deref().filter(|p| p.is_file()) else {
+            continue;
+        };
+        let Ok(relative) = src.strip_prefix(from) else {
+            continue;
+        };
+        move_indexed_file(db, "relocate", src, &to.join(relative))?;
+        moved += 1;
+    }
+    log::info!("Relocated {} downloaded memories", moved);
+    Ok(moved)
+}
+
+/// Rename, or copy and delete when `dst` is on another file system. On failure `src` is left
+/// in place and no copy remains at `dst`.
+fn move_file(src: &Path, dst: &Path) -> std::io::Result<()> {
+    if let Some(parent) = dst.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(src, dst).is_ok() {
+        return Ok(());
+    }
+    let copied = fs::copy(src, dst).and_then(|_| fs::remove_file(src));
+    if copied.is_err() {
+        let _ = fs::remove_file(dst);
+    }
+    copied
+}
+
+/// Finish or undo the moves a crash left Pending, depending on where the file actually is.
+pub fn recover(db: &DatabaseManager) -> AppResult<FsRecoveryReport> {
+    let mut report = FsRecoveryReport::default();
+    for op in db.get_pending_fs_operations()? {
+        match (op.src.exists(), op.dst.exists()) {
+            (false, true) => {
+                db.finish_fs_operation(&op)?;
+                report.completed += 1;
+            }
+            (true, partial_copy) => {
+                // Never moved, or a cross-device copy was cut short before the source went
+                if partial_copy {
+                    fs::remove_file(&op.dst)?;
+                }
+                db.set_fs_operation_state(op.id, FsOperationState::RolledBack)?;
+                report.rolled_back += 1;
+            }
+            (false, false) => {
+                log::warn!("{} of {:?} to {:?} lost the file", op.kind, op.src, op.dst);
+                db.set_fs_operation_state(op.id, FsOperationState::Failed)?;
+                report.failed += 1;
+            }
+        }
+    }
+    db.prune_fs_operations()?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Event;
+    use crate::test_fixtures;
+    use std::path::PathBuf;
+
+    /// A standard database whose first memory and first media message point at real files.
+    fn indexed_files(dir: &Path) -> (DatabaseManager, PathBuf, PathBuf) {
+        let db = test_fixtures::standard_db();
+        let memory = dir.join("memory_0.jpg");
+        let media = dir.join("chat_009.jpg");
+        fs::write(&memory, b"memory").unwrap();
+        fs::write(&media, b"media").unwrap();
+        let mut first = db.get_memory("fixture_memory_0").unwrap();
+        first.media_path = Some(memory.clone());
+        db.batch_insert_memories(&[first]).unwrap();
+        let mut event = media_message(&db);
+        event.media_references = vec![media.clone()];
+        db.update_event_media_references(&[event]).unwrap();
+        (db, memory, media)
+    }
+
+    fn media_message(db: &DatabaseManager) -> Event {
+        let messages = db.get_messages("group_weekend").unwrap();
+        messages.into_iter().find(|e| e.id == "fixture_event_009").unwrap()
+    }
+
+    #[test]
+    fn test_move_repoints_memories_and_messages() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, memory, media) = indexed_files(dir.path());
+        let moved = dir.path().join("library/2023/memory_0.jpg");
+        assert_eq!(move_indexed_file(&db, "consolidate", &memory, &moved).unwrap(), 1);
+        assert_eq!(
+            db.get_memory("fixture_memory_0").unwrap().media_path,
+            Some(moved.clone())
+        );
+        assert!(moved.is_file() && !memory.exists());
+
+        let target = dir.path().join("library/chat_009.jpg");
+        assert_eq!(move_indexed_file(&db, "consolidate", &media, &target).unwrap(), 1);
+        assert_eq!(media_message(&db).media_references, std::slice::from_ref(&target));
+        assert!(matches!(
+            move_indexed_file(&db, "consolidate", &moved, &target),
+            Err(AppError::FileExists(_))
+        ));
+        assert!(db.get_pending_fs_operations().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_relocate_moves_only_files_under_the_old_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, memory, _) = indexed_files(dir.path());
+        let (old_root, new_root) = (dir.path().join("downloads"), dir.path().join("elsewhere"));
+        let downloaded = old_root.join("Memories/2023/01/memory_1.jpg");
+        fs::create_dir_all(downloaded.parent().unwrap()).unwrap();
+        fs::write(&downloaded, b"memory").unwrap();
+        let mut second = db.get_memory("fixture_memory_1").unwrap();
+        second.media_path = Some(downloaded.clone());
+        db.batch_insert_memories(&[second]).unwrap();
+
+        assert_eq!(relocate_downloads(&db, &old_root, &new_root).unwrap(), 1);
+        let relocated = new_root.join("Memories/2023/01/memory_1.jpg");
+        assert_eq!(
+            db.get_memory("fixture_memory_1").unwrap().media_path,
+            Some(relocated.clone())
+        );
+        assert!(relocated.is_file() && !downloaded.exists());
+        // A file outside the old root is left alone
+        assert_eq!(
+            db.get_memory("fixture_memory_0").unwrap().media_path,
+            Some(memory.clone())
+        );
+        assert!(memory.is_file());
+    }
+
+    #[test]
+    fn test_recovery_finishes_or_undoes_interrupted_moves() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db, memory, media) = indexed_files(dir.path());
+
+        // Crash after the file moved but before the database update
+        let moved = dir.path().join("moved/memory_0.jpg");
+        db.begin_fs_operation("relocate", &memory, &moved).unwrap();
+        move_file(&memory, &moved).unwrap();
+        // Crash during a cross-device copy: both files exist
+        let copy = dir.path().join("moved/chat_009.jpg");
+        db.begin_fs_operation("relocate", &media, &copy).unwrap();
+        fs::write(&copy, b"me").unwrap();
+        // Crash before anything moved, and a file that disappeared entirely
+        let untouched = dir.path().join("never.jpg");
+        db.begin_fs_operation("relocate", &untouched, &dir.path().join("moved/never.jpg"))
+            .unwrap();
+        db.begin_fs_operation("relocate", &media, &dir.path().join("moved/unmoved.jpg"))
+            .unwrap();
+
+        let report = recover(&db).unwrap();
+        assert_eq!(
+            report,
+            FsRecoveryReport {
+                completed: 1,
+                rolled_back: 2,
+                failed: 1
+            }
+        );
+        assert_eq!(db.get_memory("fixture_memory_0").unwrap().media_path, Some(moved));
+        assert_eq!(media_message(&db).media_references, std::slice::from_ref(&media));
+        assert!(media.is_file() && !copy.exists());
+        assert!(db.get_pending_fs_operations().unwrap().is_empty());
+        assert_eq!(recover(&db).unwrap(), FsRecoveryReport::default());
+    }
+}
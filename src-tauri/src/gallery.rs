@@ -0,0 +1,428 @@
+//! Static HTML gallery export for downloaded memories.
+//!
+//! Produces a self-contained folder that can be browsed from any file server or NAS:
+//!
+//! ```text
+//! output/
+//!   index.html          months with counts
+//!   thumbs/             generated thumbnails
+//!   2023/01/index.html  grid for the month, plus a "missing" section
+//!   2023/01/<files>
+//! ```
+//!
+//! No external assets are referenced; styles are inlined in each page.
+
+use crate::error::{AppError, AppResult};
+use crate::metadata_scrub;
+use crate::models::{
+    DateRange, DownloadStatus, GalleryProgress, GalleryReport, Memory, ScrubMode, ScrubOutcome, ScrubRecord,
+};
+use crate::thumbnails::{ThumbnailCache, ThumbnailOutcome};
+use chrono::Datelike;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Emit a progress update at most this often (in processed memories).
+const PROGRESS_INTERVAL: usize = 25;
+
+const STYLE: &str = "body{font-family:sans-serif;margin:24px;background:#111;color:#eee}\
+a{color:#fc0}\
+.grid{display:grid;grid-template-columns:repeat(auto-fill,minmax(160px,1fr));gap:8px}\
+.tile{display:block;aspect-ratio:1;background:#222;overflow:hidden;text-align:center}\
+.tile img{width:100%;height:100%;object-fit:cover}\
+.video{display:flex;align-items:center;justify-content:center;height:100%;font-size:32px}\
+table{border-collapse:collapse}td{padding:4px 12px}";
+
+/// A memory that made it into the gallery, with paths relative to its month page.
+struct GalleryItem {
+    file_name: String,
+    thumb: Option<String>,
+    is_video: bool,
+    timestamp: String,
+}
+
+pub struct GalleryExporter {
+    output_dir: PathBuf,
+    thumbnails: ThumbnailCache,
+    scrub: Option<ScrubMode>,
+}
+
+impl GalleryExporter {
+    pub fn new(output_dir: PathBuf) -> Self {
+        let thumbnails = ThumbnailCache::new(output_dir.join("thumbs"));
+        Self {
+            output_dir,
+            thumbnails,
+            scrub: None,
+        }
+    }
+
+    /// Strip metadata from the copies per `mode`. Copies are then always real files, never
+    /// hard links to the originals.
+    pub fn scrub_metadata(mut self, mode: Option<ScrubMode>) -> Self {
+        self.scrub = mode;
+        self
+    }
+
+    /// Write the gallery for `memories`, restricted to `range` when given. The output folder is
+    /// created if missing, but the folder it goes in must exist.
+    ///
+    /// Months are written one at a time so a large library never holds more than one
+    /// month's page in memory; `on_progress` is called periodically and once at the end.
+    pub fn export<F>(
+        &self,
+        memories: Vec<Memory>,
+        range: Option<&DateRange>,
+        mut on_progress: F,
+    ) -> AppResult<GalleryReport>
+    where
+        F: FnMut(&GalleryProgress),
+    {
+        let parent = self.output_dir.parent().unwrap_or(&self.output_dir);
+        if !parent.exists() {
+            return Err(AppError::Validation(format!(
+                "Folder to create the gallery in does not exist: {}",
+                parent.display()
+            )));
+        }
+
+        let mut by_month: BTreeMap<(i32, u32), Vec<Memory>> = BTreeMap::new();
+        for memory in memories {
+            if range.is_some_and(|r| !r.contains(&memory.timestamp)) {
+                continue;
+            }
+            let key = (memory.timestamp.year(), memory.timestamp.month());
+            by_month.entry(key).or_default().push(memory);
+        }
+
+        fs::create_dir_all(&self.output_dir)?;
+        let mut report = GalleryReport {
+            output_dir: self.output_dir.clone(),
+            ..Default::default()
+        };
+        let mut progress = GalleryProgress {
+            total: by_month.values().map(Vec::len).sum(),
+            ..Default::default()
+        };
+        // (label, link, present, missing)
+        let mut month_rows: Vec<(String, String, usize, usize)> = Vec::new();
+
+        for ((year, month), mut memories) in by_month {
+            memories.sort_by_key(|m| m.timestamp);
+            let label = format!("{:04}-{:02}", year, month);
+            let rel_dir = format!("{:04}/{:02}", year, month);
+            let month_dir = self.output_dir.join(&rel_dir);
+            fs::create_dir_all(&month_dir)?;
+            progress.current_month = Some(label.clone());
+
+            let mut items = Vec::new();
+            let mut missing = Vec::new();
+            for memory in memories {
+                match self.add_memory(&memory, &month_dir, &mut report)? {
+                    Ok(item) => items.push(item),
+                    Err(reason) => missing.push((memory, reason)),
+                }
+                progress.processed += 1;
+                if progress.processed.is_multiple_of(PROGRESS_INTERVAL) {
+                    on_progress(&progress);
+                }
+            }
+
+            report.missing += missing.len();
+            fs::write(month_dir.join("index.html"), render_month(&label, &items, &missing))?;
+            month_rows.push((label, format!("{}/index.html", rel_dir), items.len(), missing.len()));
+            report.months += 1;
+        }
+
+        fs::write(self.output_dir.join("index.html"), render_index(&month_rows))?;
+        progress.current_month = None;
+        progress.done = true;
+        on_progress(&progress);
+        Ok(report)
+    }
+
+    /// Copy one memory into `month_dir`. Returns why it is missing instead when there is no
+    /// local file to show, or its metadata couldn't be stripped as asked.
+    fn add_memory(
+        &self,
+        memory: &Memory,
+        month_dir: &Path,
+        report: &mut GalleryReport,
+    ) -> AppResult<Result<GalleryItem, &'static str>> {
+        let source = match (&memory.download_status, &memory.media_path) {
+            (DownloadStatus::Downloaded, Some(p)) if p.is_file() => p,
+            (DownloadStatus::Downloaded, _) => return Ok(Err("file not found")),
+            (DownloadStatus::Failed, _) => return Ok(Err("download failed")),
+            _ => return Ok(Err("not downloaded")),
+        };
+
+        let ext = source
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("bin")
+            .to_lowercase();
+        let file_name = format!(
+            "{}_{}.{}",
+            memory.timestamp.format("%Y-%m-%d_%H%M%S"),
+            sanitize_file_stem(&memory.id),
+            ext
+        );
+        let target = month_dir.join(&file_name);
+        if let Some(mode) = self.scrub {
+            // Never write through a hard link an earlier export left to the original
+            let _ = fs::remove_file(&target);
+            let outcome = metadata_scrub::copy_scrubbed(source, &target, mode)?;
+            let skipped = matches!(outcome, ScrubOutcome::Failed(_));
+            report.scrubbed_files.push(ScrubRecord {
+                file: target.clone(),
+                outcome,
+            });
+            if skipped {
+                return Ok(Err("left out: metadata could not be stripped"));
+            }
+        } else {
+            let up_to_date = fs::metadata(&target)
+                .ok()
+                .zip(fs::metadata(source).ok())
+                .is_some_and(|(t, s)| t.len() == s.len());
+            if !up_to_date {
+                let _ = fs::remove_file(&target);
+                // Hard links are free on the same volume; fall back to a copy across devices
+                if fs::hard_link(source, &target).is_err() {
+                    fs::copy(source, &target)?;
+                }
+            }
+        }
+        report.files_written += 1;
+
+        let thumb = match self.thumbnails.get_or_create(&target) {
+            Ok(ThumbnailOutcome::Cached(p)) | Ok(ThumbnailOutcome::Generated(p)) => {
+                report.thumbnails += 1;
+                p.file_name().map(|n| format!("../../thumbs/{}", n.to_string_lossy()))
+            }
+            Ok(_) => None,
+            Err(e) => {
+                log::warn!("Gallery thumbnail failed for {}: {}", target.display(), e);
+                None
+            }
+        };
+
+        Ok(Ok(GalleryItem {
+            is_video: memory.media_type.eq_ignore_ascii_case("video") || !ThumbnailCache::is_supported(&target),
+            file_name,
+            thumb,
+            timestamp: memory.timestamp.format("%Y-%m-%d %H:%M").to_string(),
+        }))
+    }
+}
+
+fn sanitize_file_stem(id: &str) -> String {
+    let cleaned: String = id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .take(64)
+        .collect();
+    if cleaned.is_empty() {
+        "memory".to_string()
+    } else {
+        cleaned
+    }
+}
+
+pub(crate) fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{}</title><style>{}</style></head>\n<body>\n{}</body></html>\n",
+        escape_html(title),
+        STYLE,
+        body
+    )
+}
+
+fn render_month(label: &str, items: &[GalleryItem], missing: &[(Memory, &str)]) -> String {
+    let mut body = format!(
+        "<p><a href=\"../../index.html\">&larr; All months</a></p>\n<h1>Memories &middot; {}</h1>\n<div class=\"grid\">\n",
+        label
+    );
+    for item in items {
+        let href = escape_html(&item.file_name);
+        let inner = match (&item.thumb, item.is_video) {
+            (Some(thumb), _) => format!("<img loading=\"lazy\" src=\"{}\" alt=\"\">", escape_html(thumb)),
+            (None, true) => "<div class=\"video\">&#9654;</div>".to_string(),
+            (None, false) => format!("<img loading=\"lazy\" src=\"{}\" alt=\"\">", href),
+        };
+        body.push_str(&format!(
+            "<a class=\"tile\" href=\"{}\" title=\"{}\">{}</a>\n",
+            href, item.timestamp, inner
+        ));
+    }
+    body.push_str("</div>\n");
+
+    if !missing.is_empty() {
+        body.push_str(&format!("<h2>Missing ({})</h2>\n<table>\n", missing.len()));
+        for (memory, reason) in missing {
+            body.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                memory.timestamp.format("%Y-%m-%d %H:%M"),
+                escape_html(&memory.media_type),
+                escape_html(&memory.id),
+                reason
+            ));
+        }
+        body.push_str("</table>\n");
+    }
+    page(&format!("Memories {}", label), &body)
+}
+
+fn render_index(months: &[(String, String, usize, usize)]) -> String {
+    let mut body = String::from("<h1>Memories</h1>\n<table>\n<tr><th>Month</th><th>Items</th><th>Missing</th></tr>\n");
+    for (label, link, present, missing) in months {
+        body.push_str(&format!(
+            "<tr><td><a href=\"{}\">{}</a></td><td>{}</td><td>{}</td></tr>\n",
+            link, label, present, missing
+        ));
+    }
+    body.push_str("</table>\n");
+    page("Memories", &body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn memory(id: &str, month: u32, path: Option<PathBuf>, status: DownloadStatus) -> Memory {
+        Memory {
+            id: id.to_string(),
+            timestamp: Utc.with_ymd_and_hms(2023, month, 5, 10, 0, 0).unwrap(),
+            media_type: "Image".to_string(),
+            latitude: None,
+            longitude: None,
+            media_path: path,
+            export_id: "e1".to_string(),
+            download_url: None,
+            proxy_url: None,
+            overlay_url: None,
+            download_status: status,
+            failure_reason: None,
+        }
+    }
+
+    #[test]
+    fn test_gallery_references_only_existing_files() {
+        let src = tempfile::tempdir().unwrap();
+        let out = tempfile::tempdir().unwrap();
+        let jan = src.path().join("jan.png");
+        let feb = src.path().join("feb.png");
+        for p in [&jan, &feb] {
+            image::RgbImage::from_pixel(64, 48, image::Rgb([0, 120, 200]))
+                .save(p)
+                .unwrap();
+        }
+        let memories = vec![
+            memory("jan-1", 1, Some(jan), DownloadStatus::Downloaded),
+            memory("feb-1", 2, Some(feb), DownloadStatus::Downloaded),
+            memory("feb-pending", 2, None, DownloadStatus::Pending),
+            memory(
+                "feb-gone",
+                2,
+                Some(src.path().join("gone.jpg")),
+                DownloadStatus::Downloaded,
+            ),
+        ];
+
+        let mut updates = 0;
+        let report = GalleryExporter::new(out.path().to_path_buf())
+            .export(memories, None, |_| updates += 1)
+            .unwrap();
+        assert_eq!(report.months, 2);
+        assert_eq!(report.files_written, 2);
+        assert_eq!(report.thumbnails, 2);
+        assert_eq!(report.missing, 2);
+        assert!(updates >= 1);
+
+        let link_re = regex::Regex::new(r#"(?:src|href)="([^"]+)""#).unwrap();
+        let pages = [
+            out.path().join("index.html"),
+            out.path().join("2023/01/index.html"),
+            out.path().join("2023/02/index.html"),
+        ];
+        for page in &pages {
+            let html = fs::read_to_string(page).unwrap();
+            let dir = page.parent().unwrap();
+            for cap in link_re.captures_iter(&html) {
+                let target = dir.join(&cap[1]);
+                assert!(target.exists(), "{} references missing {}", page.display(), &cap[1]);
+            }
+        }
+
+        let feb_html = fs::read_to_string(&pages[2]).unwrap();
+        assert!(feb_html.contains("Missing (2)"));
+        assert!(feb_html.contains("feb-pending"));
+        assert!(feb_html.contains("file not found"));
+    }
+
+    #[test]
+    fn test_failed_scrub_leaves_the_file_out() {
+        let src = tempfile::tempdir().unwrap();
+        let out = tempfile::tempdir().unwrap();
+        let broken = src.path().join("broken.jpg");
+        fs::write(&broken, b"\xFF\xD8\xFF not really a jpeg").unwrap();
+        let memories = vec![memory("jan-broken", 1, Some(broken), DownloadStatus::Downloaded)];
+
+        let report = GalleryExporter::new(out.path().to_path_buf())
+            .scrub_metadata(Some(ScrubMode::Gps))
+            .export(memories, None, |_| {})
+            .unwrap();
+        assert_eq!(report.files_written, 0);
+        assert_eq!(report.missing, 1);
+        assert!(matches!(report.scrubbed_files[0].outcome, ScrubOutcome::Failed(_)));
+        assert!(!report.scrubbed_files[0].file.exists());
+        let html = fs::read_to_string(out.path().join("2023/01/index.html")).unwrap();
+        assert!(html.contains("metadata could not be stripped"));
+    }
+
+    #[test]
+    fn test_gallery_date_range() {
+        let out = tempfile::tempdir().unwrap();
+        let memories = vec![
+            memory("a", 1, None, DownloadStatus::Pending),
+            memory("b", 3, None, DownloadStatus::Pending),
+        ];
+        let range = DateRange {
+            start: Some(Utc.with_ymd_and_hms(2023, 2, 1, 0, 0, 0).unwrap()),
+            end: None,
+        };
+        let report = GalleryExporter::new(out.path().to_path_buf())
+            .export(memories, Some(&range), |_| {})
+            .unwrap();
+        assert_eq!(report.months, 1);
+        assert!(out.path().join("2023/03/index.html").exists());
+        assert!(!out.path().join("2023/01").exists());
+    }
+
+    #[test]
+    fn test_gallery_folder_is_created_only_inside_an_existing_one() {
+        let out = tempfile::tempdir().unwrap();
+        let memories = || vec![memory("a", 1, None, DownloadStatus::Pending)];
+        let fresh = out.path().join("gallery");
+        GalleryExporter::new(fresh.clone())
+            .export(memories(), None, |_| {})
+            .unwrap();
+        assert!(fresh.join("index.html").exists());
+
+        let missing = out.path().join("missing");
+        match GalleryExporter::new(missing.join("gallery")).export(memories(), None, |_| {}) {
+            Err(AppError::Validation(message)) => assert!(message.ends_with(&missing.display().to_string())),
+            other => panic!("expected a validation error, got {:?}", other.map(|r| r.output_dir)),
+        }
+        assert!(!missing.exists());
+    }
+}
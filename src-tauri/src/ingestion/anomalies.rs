@@ -0,0 +1,210 @@
+//! Sanity checks on imported conversations: timestamps that collapsed to the epoch or could not
+//! be read, implausibly long silences, and batches of duplicated messages. These usually point
+//! at a parser problem or an export format change rather than at what really happened.
+
+use crate::ingestion::RAW_TIMESTAMP_KEY;
+use crate::models::{AnomalyKind, ConversationAnomaly, Event};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use std::collections::{BTreeMap, HashSet};
+
+/// Epoch-dated messages needed before they count as a cluster rather than a one-off.
+const EPOCH_CLUSTER_MIN: usize = 3;
+
+/// Share of unreadable timestamps above which a conversation is flagged.
+const UNPARSED_RATIO: f64 = 0.05;
+
+/// Silences longer than this are flagged.
+const LARGE_GAP_DAYS: i64 = 730;
+
+/// Share of duplicated messages above which a conversation is flagged...
+const DUPLICATE_RATIO: f64 = 0.10;
+/// ...as long as there are at least this many duplicates.
+const DUPLICATE_MIN: usize = 5;
+
+fn epoch_cutoff() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(1971, 1, 1, 0, 0, 0).unwrap()
+}
+
+fn has_raw_timestamp(event: &Event) -> bool {
+    event.timestamp == DateTime::<Utc>::MIN_UTC
+        || event.metadata.as_deref().is_some_and(|m| {
+            serde_json::from_str::<serde_json::Value>(m)
+                .ok()
+                .is_some_and(|v| v.get(RAW_TIMESTAMP_KEY).is_some())
+        })
+}
+
+/// Check every conversation in `events`. Events without a conversation are ignored.
+pub fn detect_anomalies(events: &[Event]) -> Vec<ConversationAnomaly> {
+    let mut by_conversation: BTreeMap<&str, Vec<&Event>> = BTreeMap::new();
+    for event in events {
+        if let Some(cid) = &event.conversation_id {
+            by_conversation.entry(cid).or_default().push(event);
+        }
+    }
+    by_conversation
+        .into_iter()
+        .flat_map(|(cid, events)| check_conversation(cid, events))
+        .collect()
+}
+
+fn check_conversation(conversation_id: &str, events: Vec<&Event>) -> Vec<ConversationAnomaly> {
+    let total = events.len();
+    let mut found = Vec::new();
+    let mut flag = |kind, affected: usize, detail: String| {
+        found.push(ConversationAnomaly {
+            conversation_id: conversation_id.to_string(),
+            kind,
+            affected: affected as i64,
+            total: total as i64,
+            detail,
+        })
+    };
+
+    let unparsed = events.iter().filter(|e| has_raw_timestamp(e)).count();
+    if unparsed > 0 && unparsed as f64 / total as f64 > UNPARSED_RATIO {
+        flag(
+            AnomalyKind::UnparsedTimestamps,
+            unparsed,
+            format!("{} of {} messages have an unreadable timestamp", unparsed, total),
+        );
+    }
+
+    let cutoff = epoch_cutoff();
+    let mut dated: Vec<DateTime<Utc>> = events
+        .iter()
+        .filter(|e| !has_raw_timestamp(e))
+        .map(|e| e.timestamp)
+        .collect();
+    let epoch = dated.iter().filter(|ts| **ts < cutoff).count();
+    if epoch >= EPOCH_CLUSTER_MIN {
+        flag(
+            AnomalyKind::EpochTimestamps,
+            epoch,
+            format!("{} messages are dated 1970 or earlier", epoch),
+        );
+    }
+
+    dated.retain(|ts| *ts >= cutoff);
+    dated.sort();
+    if let Some((before, after)) = dated
+        .windows(2)
+        .map(|w| (w[0], w[1]))
+        .max_by_key(|(before, after)| *after - *before)
+        .filter(|(before, after)| *after - *before > Duration::days(LARGE_GAP_DAYS))
+    {
+        flag(
+            AnomalyKind::LargeGap,
+            0,
+            format!(
+                "No messages between {} and {}",
+                before.format("%Y-%m-%d"),
+                after.format("%Y-%m-%d")
+            ),
+        );
+    }
+
+    let mut seen = HashSet::new();
+    let duplicates = events
+        .iter()
+        .filter(|e| !seen.insert((&e.sender, &e.content, e.timestamp)))
+        .count();
+    if duplicates >= DUPLICATE_MIN && duplicates as f64 / total as f64 > DUPLICATE_RATIO {
+        flag(
+            AnomalyKind::DuplicateMessages,
+            duplicates,
+            format!("{} of {} messages are exact duplicates", duplicates, total),
+        );
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+
+    fn kinds(anomalies: &[ConversationAnomaly], conversation_id: &str) -> Vec<AnomalyKind> {
+        anomalies
+            .iter()
+            .filter(|a| a.conversation_id == conversation_id)
+            .map(|a| a.kind)
+            .collect()
+    }
+
+    #[test]
+    fn test_clean_conversations_have_no_anomalies() {
+        assert!(detect_anomalies(&test_fixtures::events()).is_empty());
+    }
+
+    #[test]
+    fn test_flags_epoch_unparsed_gap_and_duplicates() {
+        let mut events = test_fixtures::events();
+        let alice: Vec<usize> = (0..events.len())
+            .filter(|&i| events[i].conversation_id.as_deref() == Some("alice"))
+            .collect();
+        for &i in &alice[..3] {
+            events[i].timestamp = DateTime::<Utc>::UNIX_EPOCH;
+        }
+        events[alice[3]].timestamp = DateTime::<Utc>::MIN_UTC;
+        events[alice[4]].metadata = Some(r#"{"raw_timestamp": "31/02/2023"}"#.to_string());
+
+        let bob: Vec<usize> = (0..events.len())
+            .filter(|&i| events[i].conversation_id.as_deref() == Some("bob"))
+            .collect();
+        events[bob[0]].timestamp += Duration::days(LARGE_GAP_DAYS + 10);
+        let template = events[bob[1]].clone();
+        for n in 0..DUPLICATE_MIN {
+            let mut copy = template.clone();
+            copy.id = format!("dup_{}", n);
+            events.push(copy);
+        }
+
+        let anomalies = detect_anomalies(&events);
+        assert_eq!(
+            kinds(&anomalies, "alice"),
+            [AnomalyKind::UnparsedTimestamps, AnomalyKind::EpochTimestamps]
+        );
+        let unparsed = &anomalies[0];
+        assert_eq!((unparsed.affected, unparsed.total), (2, alice.len() as i64));
+        assert_eq!(
+            kinds(&anomalies, "bob"),
+            [AnomalyKind::LargeGap, AnomalyKind::DuplicateMessages]
+        );
+        assert!(kinds(&anomalies, "group_weekend").is_empty());
+    }
+
+    #[test]
+    fn test_messages_the_parser_could_not_date_are_flagged_after_storage() {
+        use crate::ingestion::parser::{ChatParser, ParseDiagnostics};
+        use crate::models::TimestampFormatHint;
+
+        let db = test_fixtures::standard_db();
+        let mut events = db
+            .get_export_conversation_events(test_fixtures::EXPORT_ID, "alice")
+            .unwrap();
+        let mut diagnostics = ParseDiagnostics::default();
+        let garbled: Vec<Event> = events
+            .iter()
+            .take(2)
+            .map(|e| {
+                ChatParser::dated_message(
+                    e.clone(),
+                    "sometime last week",
+                    TimestampFormatHint::default(),
+                    &mut diagnostics,
+                )
+            })
+            .collect();
+        assert_eq!(diagnostics.timestamp_failures, 2);
+        db.batch_insert_events(&garbled, test_fixtures::EXPORT_ID).unwrap();
+
+        events = db
+            .get_export_conversation_events(test_fixtures::EXPORT_ID, "alice")
+            .unwrap();
+        let anomalies = detect_anomalies(&events);
+        assert_eq!(kinds(&anomalies, "alice"), [AnomalyKind::UnparsedTimestamps]);
+        assert_eq!(anomalies[0].affected, 2);
+    }
+}
@@ -0,0 +1,240 @@
+//! Fingerprints of an export's source files, so a later scan can tell what changed.
+//!
+//! Zip exports are fingerprinted per part. Folder exports record every file under `json/`
+//! and `html/` individually (JSON files also get a content hash), and every other top-level
+//! directory as one aggregate entry, since media folders can hold tens of thousands of files.
+
+use crate::error::AppResult;
+use crate::ingestion::is_os_metadata;
+use crate::models::{ExportArtifact, ExportChanges, ExportSet, ExportSourceType};
+use crate::thumbnails::{fnv1a_64_update, FNV1A_64_OFFSET};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+/// Directories whose files are tracked one by one.
+const ITEMIZED_DIRS: &[&str] = &["json", "html"];
+
+/// Fingerprint the current state of an export's sources.
+pub fn scan_artifacts(export: &ExportSet) -> AppResult<Vec<ExportArtifact>> {
+    let mut artifacts = Vec::new();
+    match export.source_type {
+        ExportSourceType::Zip => {
+            for part in &export.source_paths {
+                if part.is_file() {
+                    artifacts.push(file_artifact(part, part.to_string_lossy().into_owned(), false)?);
+                }
+            }
+        }
+        ExportSourceType::Folder => {
+            if let Some(root) = export.source_paths.first().filter(|r| r.is_dir()) {
+                scan_folder(root, &mut artifacts)?;
+            }
+        }
+    }
+    artifacts.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(artifacts)
+}
+
+fn scan_folder(root: &Path, artifacts: &mut Vec<ExportArtifact>) -> AppResult<()> {
+    for entry in fs::read_dir(root)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        if is_os_metadata(Path::new(&name)) {
+            continue;
+        }
+        if path.is_file() {
+            artifacts.push(file_artifact(&path, name, false)?);
+        } else if path.is_dir() && ITEMIZED_DIRS.contains(&name.as_str()) {
+            itemize_dir(&path, &name, artifacts)?;
+        } else if path.is_dir() {
+            artifacts.push(aggregate_dir(&path, format!("{}/", name))?);
+        }
+    }
+    Ok(())
+}
+
+fn itemize_dir(dir: &Path, prefix: &str, artifacts: &mut Vec<ExportArtifact>) -> AppResult<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        if is_os_metadata(Path::new(&name)) {
+            continue;
+        }
+        let relative = format!("{}/{}", prefix, name);
+        if path.is_dir() {
+            itemize_dir(&path, &relative, artifacts)?;
+        } else if path.is_file() {
+            let hash = path.extension().is_some_and(|e| e == "json");
+            artifacts.push(file_artifact(&path, relative, hash)?);
+        }
+    }
+    Ok(())
+}
+
+fn modified_at(meta: &fs::Metadata) -> Option<DateTime<Utc>> {
+    meta.modified().ok().map(DateTime::<Utc>::from)
+}
+
+fn file_artifact(path: &Path, relative: String, hash: bool) -> AppResult<ExportArtifact> {
+    let meta = fs::metadata(path)?;
+    Ok(ExportArtifact {
+        path: relative,
+        size: meta.len(),
+        modified_at: modified_at(&meta),
+        hash: if hash { Some(hash_file(path)?) } else { None },
+        file_count: 1,
+    })
+}
+
+/// One entry for a whole directory: total bytes, file count and the newest modification time.
+fn aggregate_dir(dir: &Path, relative: String) -> AppResult<ExportArtifact> {
+    let mut artifact = ExportArtifact {
+        path: relative,
+        size: 0,
+        modified_at: None,
+        hash: None,
+        file_count: 0,
+    };
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(current) = stack.pop() {
+        for entry in fs::read_dir(&current)? {
+            let path = entry?.path();
+            if path.file_name().is_some_and(|n| is_os_metadata(Path::new(n))) {
+                continue;
+            }
+            if path.is_dir() {
+                stack.push(path);
+                continue;
+            }
+            let meta = fs::metadata(&path)?;
+            artifact.size += meta.len();
+            artifact.file_count += 1;
+            artifact.modified_at = artifact.modified_at.max(modified_at(&meta));
+        }
+    }
+    Ok(artifact)
+}
+
+pub(crate) fn hash_file(path: &Path) -> AppResult<String> {
+    let mut file = fs::File::open(path)?;
+    let mut buf = vec![0u8; 256 * 1024];
+    let mut hash = FNV1A_64_OFFSET;
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hash = fnv1a_64_update(hash, &buf[..n]);
+    }
+    Ok(format!("{:016x}", hash))
+}
+
+/// Compare the artifacts recorded at import time with a fresh scan.
+pub fn diff_artifacts(export_id: &str, recorded: &[ExportArtifact], current: &[ExportArtifact]) -> ExportChanges {
+    let before: HashMap<&str, &ExportArtifact> = recorded.iter().map(|a| (a.path.as_str(), a)).collect();
+    let mut changes = ExportChanges {
+        export_id: export_id.to_string(),
+        recorded: !recorded.is_empty(),
+        ..Default::default()
+    };
+    for artifact in current {
+        match before.get(artifact.path.as_str()) {
+            None => changes.added.push(artifact.path.clone()),
+            Some(old) if *old != artifact => changes.changed.push(artifact.path.clone()),
+            Some(_) => changes.unchanged += 1,
+        }
+    }
+    let now: std::collections::HashSet<&str> = current.iter().map(|a| a.path.as_str()).collect();
+    changes.removed = recorded
+        .iter()
+        .filter(|a| !now.contains(a.path.as_str()))
+        .map(|a| a.path.clone())
+        .collect();
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ValidationStatus;
+    use std::path::PathBuf;
+
+    fn folder_export(root: &Path) -> ExportSet {
+        ExportSet {
+            id: "e1".to_string(),
+            source_paths: vec![root.to_path_buf()],
+            source_type: ExportSourceType::Folder,
+            extraction_path: None,
+            creation_date: None,
+            validation_status: ValidationStatus::Valid,
+            parts: Vec::new(),
+            import_phases: None,
+            ignored_duplicates: Vec::new(),
+            layout: None,
+        }
+    }
+
+    #[test]
+    fn test_detects_changed_added_and_removed_artifacts() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("json")).unwrap();
+        fs::create_dir_all(root.join("chat_media")).unwrap();
+        fs::write(root.join("index.html"), "<html></html>").unwrap();
+        fs::write(root.join("json/chat_history.json"), r#"{"a": []}"#).unwrap();
+        fs::write(root.join("json/friends.json"), r#"{}"#).unwrap();
+        fs::write(root.join("chat_media/2023-01-01_A.jpg"), b"img").unwrap();
+        fs::write(root.join(".DS_Store"), b"junk").unwrap();
+
+        let recorded = scan_artifacts(&folder_export(root)).unwrap();
+        let paths: Vec<&str> = recorded.iter().map(|a| a.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "chat_media/",
+                "index.html",
+                "json/chat_history.json",
+                "json/friends.json"
+            ]
+        );
+        assert!(recorded[2].hash.is_some() && recorded[1].hash.is_none());
+        assert!(!diff_artifacts("e1", &recorded, &recorded).has_changes());
+
+        // Same size, different content: only the hash can tell
+        fs::write(root.join("json/chat_history.json"), r#"{"b": []}"#).unwrap();
+        fs::write(root.join("chat_media/2023-01-02_B.jpg"), b"img2").unwrap();
+        fs::write(root.join("json/memories_history.json"), r#"{}"#).unwrap();
+        fs::remove_file(root.join("json/friends.json")).unwrap();
+
+        let changes = diff_artifacts("e1", &recorded, &scan_artifacts(&folder_export(root)).unwrap());
+        assert!(changes.recorded && changes.has_changes());
+        assert_eq!(changes.changed, ["chat_media/", "json/chat_history.json"]);
+        assert_eq!(changes.added, ["json/memories_history.json"]);
+        assert_eq!(changes.removed, ["json/friends.json"]);
+        assert_eq!(changes.unchanged, 1);
+    }
+
+    #[test]
+    fn test_zip_parts_are_fingerprinted_individually() {
+        let dir = tempfile::tempdir().unwrap();
+        let part = dir.path().join("mydata~1.zip");
+        fs::write(&part, b"PK").unwrap();
+        let mut export = folder_export(dir.path());
+        export.source_type = ExportSourceType::Zip;
+        export.source_paths = vec![part.clone(), PathBuf::from("/missing/mydata~2.zip")];
+
+        let recorded = scan_artifacts(&export).unwrap();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].size, 2);
+
+        let second = dir.path().join("mydata~2.zip");
+        fs::write(&second, b"PK2").unwrap();
+        export.source_paths[1] = second.clone();
+        let changes = diff_artifacts("e1", &recorded, &scan_artifacts(&export).unwrap());
+        assert_eq!(changes.added, [second.to_string_lossy().into_owned()]);
+        assert!(changes.changed.is_empty());
+    }
+}
@@ -0,0 +1,214 @@
+//! Profile pictures and Bitmoji avatars that some exports ship with.
+//!
+//! An avatar is matched to a username either through a profile JSON that names the file, or by
+//! file name (`alice.png`, `bitmoji_alice.png`, `alice-profile.jpg`). Only files that exist are
+//! ever returned. Everyone also gets a stable fallback color derived from their username.
+
+use crate::thumbnails::{fnv1a_64_update, FNV1A_64_OFFSET};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directories (at the export root or under `media/`) that hold avatar images.
+const AVATAR_DIRS: &[&str] = &["profile", "profiles", "profile_pictures", "bitmoji", "bitmojis"];
+
+/// Files under `json/` that may map usernames to avatar images.
+const PROFILE_JSONS: &[&str] = &["profile.json", "bitmoji.json", "account.json"];
+
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "webp"];
+
+/// Characters that separate a username from the rest of a file name.
+const NAME_SEPARATORS: &[char] = &['_', '-', ' '];
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| IMAGE_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+fn collect_images(dir: &Path, images: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_images(&path, images);
+        } else if path.is_file() && is_image(&path) {
+            images.push(path);
+        }
+    }
+}
+
+/// Image files in the export's avatar directories, sorted so matching is deterministic.
+fn avatar_images(source: &Path) -> Vec<PathBuf> {
+    let mut images = Vec::new();
+    for parent in [source.to_path_buf(), source.join("media")] {
+        let Ok(entries) = fs::read_dir(&parent) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_ascii_lowercase();
+            if AVATAR_DIRS.contains(&name.as_str()) && entry.path().is_dir() {
+                collect_images(&entry.path(), &mut images);
+            }
+        }
+    }
+    images.sort();
+    images
+}
+
+/// The known username a file stem refers to: the whole stem, or a prefix or suffix cut at a
+/// separator. The longest match wins, so `bitmoji_alice` prefers user `bitmoji_alice` to `alice`.
+fn username_for_stem<'a>(stem: &str, usernames: &'a HashMap<String, String>) -> Option<&'a String> {
+    let stem = stem.to_lowercase();
+    let mut candidates = vec![stem.as_str()];
+    for (i, c) in stem.char_indices().filter(|(_, c)| NAME_SEPARATORS.contains(c)) {
+        candidates.push(&stem[..i]);
+        candidates.push(&stem[i + c.len_utf8()..]);
+    }
+    candidates
+        .into_iter()
+        .filter_map(|c| usernames.get(c).map(|u| (c.len(), u)))
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, username)| username)
+}
+
+/// Username → image file pairs listed in any object of a profile JSON.
+fn json_mappings(value: &serde_json::Value, out: &mut Vec<(String, String)>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            let username = map
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case("username"))
+                .and_then(|(_, v)| v.as_str());
+            let image = map.values().filter_map(|v| v.as_str()).find(|v| is_image(Path::new(v)));
+            if let (Some(username), Some(image)) = (username, image) {
+                out.push((username.to_string(), image.to_string()));
+            }
+            map.values().for_each(|v| json_mappings(v, out));
+        }
+        serde_json::Value::Array(items) => items.iter().for_each(|v| json_mappings(v, out)),
+        _ => {}
+    }
+}
+
+/// Find avatar images for `usernames` in an extracted export. Profile JSON entries win over
+/// file name matches; every returned path is an existing file.
+pub fn find_avatars(source: &Path, usernames: &HashSet<String>) -> HashMap<String, PathBuf> {
+    let images = avatar_images(source);
+    let mut avatars = HashMap::new();
+
+    for name in PROFILE_JSONS {
+        let Ok(text) = fs::read_to_string(source.join("json").join(name)) else {
+            continue;
+        };
+        let Ok(value) = serde_json::from_str::<serde_json::Value>(&text) else {
+            log::warn!("Ignoring unreadable json/{} while looking for avatars", name);
+            continue;
+        };
+        let mut mappings = Vec::new();
+        json_mappings(&value, &mut mappings);
+        for (username, image) in mappings {
+            let listed = source.join(&image);
+            let file_name = Path::new(&image).file_name();
+            let resolved = if listed.is_file() {
+                Some(listed)
+            } else {
+                images.iter().find(|p| p.file_name() == file_name).cloned()
+            };
+            if let Some(path) = resolved {
+                avatars.entry(username).or_insert(path);
+            }
+        }
+    }
+
+    let lowercase: HashMap<String, String> = usernames.iter().map(|u| (u.to_lowercase(), u.clone())).collect();
+    for image in &images {
+        let stem = image.file_stem().unwrap_or_default().to_string_lossy();
+        if let Some(username) = username_for_stem(&stem, &lowercase) {
+            avatars.entry(username.clone()).or_insert_with(|| image.clone());
+        }
+    }
+    avatars
+}
+
+/// Stable `#rrggbb` color for a username, shown when there is no avatar picture.
+pub fn avatar_color(username: &str) -> String {
+    let hash = fnv1a_64_update(FNV1A_64_OFFSET, username.to_lowercase().as_bytes());
+    let hue = (hash % 360) as f64;
+    // HSL with fixed saturation and lightness keeps every color readable behind white initials
+    let (saturation, lightness) = (0.55, 0.45);
+    let chroma = (1.0 - (2.0 * lightness - 1.0f64).abs()) * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let (r, g, b) = match (hue / 60.0) as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    let channel = |v: f64| ((v + m) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", channel(r), channel(g), channel(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(list: &[&str]) -> HashSet<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_finds_avatars_by_file_name_and_profile_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("bitmoji/nested")).unwrap();
+        fs::create_dir_all(root.join("media/Profile")).unwrap();
+        fs::create_dir_all(root.join("json")).unwrap();
+        fs::write(root.join("bitmoji/bitmoji_alice.png"), b"png").unwrap();
+        fs::write(root.join("bitmoji/nested/Bob-profile.jpg"), b"jpg").unwrap();
+        fs::write(root.join("bitmoji/stranger.png"), b"png").unwrap();
+        fs::write(root.join("bitmoji/carol.txt"), b"not an image").unwrap();
+        fs::write(root.join("media/Profile/avatar_7.webp"), b"webp").unwrap();
+        fs::write(
+            root.join("json/profile.json"),
+            r#"{"Friends": [{"Username": "dave", "Avatar": "media/Profile/avatar_7.webp"},
+                            {"Username": "erin", "Avatar": "missing.png"}]}"#,
+        )
+        .unwrap();
+
+        let avatars = find_avatars(root, &names(&["alice", "bob", "carol", "dave", "erin"]));
+        assert_eq!(avatars.len(), 3);
+        assert_eq!(avatars["alice"], root.join("bitmoji/bitmoji_alice.png"));
+        assert_eq!(avatars["bob"], root.join("bitmoji/nested/Bob-profile.jpg"));
+        assert_eq!(avatars["dave"], root.join("media/Profile/avatar_7.webp"));
+        assert!(avatars.values().all(|p| p.is_file()));
+    }
+
+    #[test]
+    fn test_longest_username_match_wins() {
+        let users: HashMap<String, String> = [("al", "al"), ("al_b", "al_b")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        assert_eq!(
+            username_for_stem("al_b_avatar", &users).map(String::as_str),
+            Some("al_b")
+        );
+        assert_eq!(username_for_stem("AL", &users).map(String::as_str), Some("al"));
+        assert_eq!(username_for_stem("alex", &users), None);
+    }
+
+    #[test]
+    fn test_avatar_color_is_stable_and_case_insensitive() {
+        let color = avatar_color("alice");
+        assert_eq!(color, avatar_color("Alice"));
+        assert_eq!(color.len(), 7);
+        assert!(color.starts_with('#') && color[1..].chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(avatar_color("alice"), avatar_color("bob"));
+    }
+}
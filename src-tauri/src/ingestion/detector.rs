@@ -1,15 +1,59 @@
-use std::path::{Path, PathBuf};
+use crate::error::{AppError, AppResult};
+use crate::models::{DetectionSettings, ExportPart, ExportSet, ExportSourceType, ValidationStatus};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use std::collections::HashMap;
 use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::LazyLock;
-use crate::models::{ExportSet, ValidationStatus, ExportSourceType};
-use crate::error::AppResult;
-use std::collections::HashMap;
-use regex::Regex;
-use chrono::{DateTime, Utc};
 
-static EXPORT_ID_RE: LazyLock<Regex> = LazyLock::new(|| {
-    Regex::new(r"^(mydata~\d+)(?:-\d+)?(?:\.zip)?$").unwrap()
-});
+/// `mydata~<id>` with an optional `-<part>`, allowing for what browsers do to re-downloaded
+/// files: a " (1)" counter, a doubled ".zip.zip", and changed case.
+static EXPORT_ID_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)^(mydata~\d+)(-\d+)?\s*(?:\(\d+\))?\s*((?:\.zip)*)$").unwrap());
+
+/// Whether `name` matches one of the extra patterns in `settings`.
+fn matches_extra_pattern(settings: &DetectionSettings, name: &str) -> bool {
+    settings
+        .name_patterns
+        .iter()
+        .any(|pattern| glob_matches(&pattern.to_lowercase(), name))
+}
+
+/// Whether `name` matches `pattern`, where `*` is any run of characters and `?` any one.
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Where the last `*` was, and how much of the name it has swallowed so far
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// A folder with the top-level files of an export, whatever it was renamed to.
+fn looks_like_export(dir: &Path) -> bool {
+    dir.join("index.html").is_file() && dir.join("json").is_dir()
+}
 
 pub struct ExportDetector;
 
@@ -18,7 +62,7 @@ fn std_time_to_chrono(time: std::time::SystemTime) -> DateTime<Utc> {
 }
 
 impl ExportDetector {
-    pub fn detect_in_standard_paths() -> AppResult<Vec<ExportSet>> {
+    pub fn detect_in_standard_paths(settings: &DetectionSettings) -> AppResult<Vec<ExportSet>> {
         let mut all_exports = Vec::new();
         let mut paths_to_scan = Vec::new();
 
@@ -35,7 +79,7 @@ impl ExportDetector {
         log::info!("Auto-detecting exports in {} standard paths", paths_to_scan.len());
 
         for path in &paths_to_scan {
-            match Self::detect_in_directory(path) {
+            match Self::detect_in_directory_with(path, settings) {
                 Ok(exports) => {
                     all_exports.extend(exports);
                 }
@@ -55,6 +99,12 @@ impl ExportDetector {
     }
 
     pub fn detect_in_directory(path: &Path) -> AppResult<Vec<ExportSet>> {
+        Self::detect_in_directory_with(path, &DetectionSettings::default())
+    }
+
+    /// Detect exports at `path`: a zip, an export folder, or a folder holding exports, which are
+    /// recognized by name, by `settings.name_patterns`, or by probing their contents.
+    pub fn detect_in_directory_with(path: &Path, settings: &DetectionSettings) -> AppResult<Vec<ExportSet>> {
         if path.is_file() {
             // If it's a single zip, wrap it in a group of one
             if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip")) {
@@ -64,8 +114,19 @@ impl ExportDetector {
                         source_paths: vec![path.to_path_buf()],
                         source_type: ExportSourceType::Zip,
                         extraction_path: None,
-                        creation_date: fs::metadata(path).ok().and_then(|m| m.created().ok()).map(std_time_to_chrono),
+                        creation_date: fs::metadata(path)
+                            .ok()
+                            .and_then(|m| m.created().ok())
+                            .map(std_time_to_chrono),
                         validation_status: status,
+                        parts: vec![ExportPart {
+                            path: path.to_path_buf(),
+                            readable: true,
+                            error: None,
+                        }],
+                        import_phases: None,
+                        ignored_duplicates: Vec::new(),
+                        layout: None,
                     }]);
                 }
             }
@@ -82,14 +143,24 @@ impl ExportDetector {
         }
 
         let mut candidates = Vec::new();
+        let mut probed = 0;
         for entry in fs::read_dir(path)? {
             let entry = entry?;
             let p = entry.path();
             let name = p.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
 
             // Broad filter: looks like snapchat data
-            if name.starts_with("mydata~") || name.contains("snapchat") {
+            if name.starts_with("mydata~") || name.contains("snapchat") || matches_extra_pattern(settings, &name) {
                 candidates.push(p);
+            } else if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                // A renamed export folder still has the export's own files at the top
+                if probed < settings.probe_limit && looks_like_export(&p) {
+                    log::info!("Recognized {:?} as an export by its contents", p.file_name());
+                    candidates.push(p);
+                } else if probed == settings.probe_limit {
+                    log::debug!("Probe limit of {} folders reached in {:?}", settings.probe_limit, path);
+                }
+                probed += 1;
             }
         }
 
@@ -99,12 +170,18 @@ impl ExportDetector {
     /// Intelligent grouping of related files and folders.
     fn group_candidates(paths: Vec<PathBuf>) -> AppResult<Vec<ExportSet>> {
         let mut groups: HashMap<String, Vec<PathBuf>> = HashMap::new();
+        // Copies of the same part of the same export: (base id, part suffix, is a zip)
+        let mut copies: HashMap<(String, String, bool), Vec<PathBuf>> = HashMap::new();
 
         for path in paths {
             let name = path.file_name().unwrap_or_default().to_string_lossy();
             if let Some(caps) = EXPORT_ID_RE.captures(&name) {
-                let base_id = caps.get(1).map(|m| m.as_str().to_string()).unwrap();
-                groups.entry(base_id).or_default().push(path);
+                let base_id = caps[1].to_lowercase();
+                let part = caps.get(2).map_or("", |m| m.as_str()).to_string();
+                copies
+                    .entry((base_id, part, !caps[3].is_empty()))
+                    .or_default()
+                    .push(path);
             } else {
                 // Fallback: group by name without extension for non-standard zips
                 let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
@@ -112,29 +189,65 @@ impl ExportDetector {
             }
         }
 
+        let mut duplicates: HashMap<String, Vec<PathBuf>> = HashMap::new();
+        for ((base_id, _, _), mut paths) in copies {
+            // A partial re-download is smaller, so keep the largest copy, then the newest
+            paths.sort_by_key(|p| {
+                let meta = fs::metadata(p).ok();
+                let size = meta.as_ref().map_or(0, |m| m.len());
+                let modified = meta.and_then(|m| m.modified().ok());
+                (
+                    std::cmp::Reverse(size),
+                    std::cmp::Reverse(modified),
+                    p.as_os_str().len(),
+                )
+            });
+            let kept = paths.remove(0);
+            if !paths.is_empty() {
+                log::info!("Ignoring duplicate downloads of {:?}: {:?}", kept, paths);
+                duplicates.entry(base_id.clone()).or_default().extend(paths);
+            }
+            groups.entry(base_id).or_default().push(kept);
+        }
+
         let mut results = Vec::new();
         for (id, mut members) in groups {
             // Sort members to ensure part 1/main file is usually first (lexicographical)
             members.sort();
 
-            let is_zip = members.iter().any(|p| p.extension().is_some_and(|e| e == "zip"));
-            let source_type = if is_zip { ExportSourceType::Zip } else { ExportSourceType::Folder };
+            let is_zip = members
+                .iter()
+                .any(|p| p.extension().is_some_and(|e| e.eq_ignore_ascii_case("zip")));
+            let source_type = if is_zip {
+                ExportSourceType::Zip
+            } else {
+                ExportSourceType::Folder
+            };
 
             // Perform unified validation across all group members
-            let status = if is_zip {
+            let (status, parts) = if is_zip {
                 Self::validate_zip_group(&members)
             } else {
-                Self::validate_folder_group(&members)
+                (Self::validate_folder_group(&members), Vec::new())
             };
 
             if status != ValidationStatus::Unknown {
+                let ignored_duplicates = duplicates.remove(&id).unwrap_or_default();
                 results.push(ExportSet {
                     id,
                     source_paths: members.clone(),
                     source_type,
                     extraction_path: None,
-                    creation_date: members.first().and_then(|p| fs::metadata(p).ok()).and_then(|m| m.created().ok()).map(std_time_to_chrono),
+                    creation_date: members
+                        .first()
+                        .and_then(|p| fs::metadata(p).ok())
+                        .and_then(|m| m.created().ok())
+                        .map(std_time_to_chrono),
                     validation_status: status,
+                    parts,
+                    import_phases: None,
+                    ignored_duplicates,
+                    layout: None,
                 });
             }
         }
@@ -145,10 +258,12 @@ impl ExportDetector {
     fn validate_zip(path: &Path) -> Option<ValidationStatus> {
         let file = fs::File::open(path).ok()?;
         let mut archive = zip::ZipArchive::new(file).ok()?;
-        
+
         let has_index = archive.by_name("index.html").is_ok();
         let has_chat = archive.file_names().any(|n| n.contains("html/chat_history"));
-        let has_media = archive.file_names().any(|n| n.contains("chat_media/") || n.contains("media/"));
+        let has_media = archive
+            .file_names()
+            .any(|n| n.contains("chat_media/") || n.contains("media/"));
 
         if has_index && has_chat && has_media {
             Some(ValidationStatus::Valid)
@@ -159,28 +274,98 @@ impl ExportDetector {
         }
     }
 
-    fn validate_zip_group(paths: &[PathBuf]) -> ValidationStatus {
+    fn validate_zip_group(paths: &[PathBuf]) -> (ValidationStatus, Vec<ExportPart>) {
         let mut has_index = false;
         let mut has_chat = false;
         let mut has_media = false;
+        let mut parts = Vec::new();
 
         for path in paths {
-            if let Ok(file) = fs::File::open(path) {
-                if let Ok(mut archive) = zip::ZipArchive::new(file) {
-                    if !has_index && archive.by_name("index.html").is_ok() { has_index = true; }
-                    if !has_chat && archive.file_names().any(|n| n.contains("html/chat_history")) { has_chat = true; }
-                    if !has_media && archive.file_names().any(|n| n.contains("chat_media/") || n.contains("media/")) { has_media = true; }
+            if !path.extension().is_some_and(|e| e.eq_ignore_ascii_case("zip")) {
+                continue;
+            }
+            match Self::open_zip(path) {
+                Ok(mut archive) => {
+                    if !has_index && archive.by_name("index.html").is_ok() {
+                        has_index = true;
+                    }
+                    if !has_chat && archive.file_names().any(|n| n.contains("html/chat_history")) {
+                        has_chat = true;
+                    }
+                    if !has_media
+                        && archive
+                            .file_names()
+                            .any(|n| n.contains("chat_media/") || n.contains("media/"))
+                    {
+                        has_media = true;
+                    }
+                    parts.push(ExportPart {
+                        path: path.clone(),
+                        readable: true,
+                        error: None,
+                    });
+                }
+                Err(e) => {
+                    log::warn!(
+                        "Export part {:?} is unreadable: {}",
+                        path.file_name().unwrap_or_default(),
+                        e
+                    );
+                    parts.push(ExportPart {
+                        path: path.clone(),
+                        readable: false,
+                        error: Some(e),
+                    });
                 }
             }
         }
 
-        if has_index && has_chat && has_media {
+        // One bad part means extraction would fail partway through, whatever the other parts contain
+        let status = if parts.iter().any(|p| !p.readable) {
+            ValidationStatus::Corrupted
+        } else if has_index && has_chat && has_media {
             ValidationStatus::Valid
         } else if has_index || !paths.is_empty() {
             ValidationStatus::Incomplete
         } else {
             ValidationStatus::Unknown
+        };
+        (status, parts)
+    }
+
+    fn open_zip(path: &Path) -> Result<zip::ZipArchive<fs::File>, String> {
+        let file = fs::File::open(path).map_err(|e| e.to_string())?;
+        zip::ZipArchive::new(file).map_err(|e| e.to_string())
+    }
+
+    /// Re-check every zip part right before import.
+    ///
+    /// Parts may have been re-downloaded since detection, so this opens them again. Any
+    /// unreadable part is an error unless `skip_unreadable` is set, in which case those
+    /// parts are dropped from `source_paths` and the import continues without them.
+    pub fn prepare_zip_parts(export: &mut ExportSet, skip_unreadable: bool) -> AppResult<()> {
+        let (_, parts) = Self::validate_zip_group(&export.source_paths);
+        let bad: Vec<&ExportPart> = parts.iter().filter(|p| !p.readable).collect();
+        if !bad.is_empty() {
+            let names = bad
+                .iter()
+                .map(|p| p.path.file_name().unwrap_or_default().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+                .join(", ");
+            if !skip_unreadable {
+                return Err(AppError::Validation(format!(
+                    "Unreadable export part(s): {}. Re-download them, or choose to skip them.",
+                    names
+                )));
+            }
+            log::warn!("Skipping unreadable export part(s) at user request: {}", names);
+            export.source_paths.retain(|p| !bad.iter().any(|b| &b.path == p));
+            if export.source_paths.is_empty() {
+                return Err(AppError::Validation("No readable export parts left to import".into()));
+            }
         }
+        export.parts = parts;
+        Ok(())
     }
 
     fn validate_folder(path: &Path) -> Option<ExportSet> {
@@ -200,8 +385,15 @@ impl ExportDetector {
                 source_paths: vec![path.to_path_buf()],
                 source_type: ExportSourceType::Folder,
                 extraction_path: None,
-                creation_date: fs::metadata(path).ok().and_then(|m| m.created().ok()).map(std_time_to_chrono),
+                creation_date: fs::metadata(path)
+                    .ok()
+                    .and_then(|m| m.created().ok())
+                    .map(std_time_to_chrono),
                 validation_status: status,
+                parts: Vec::new(),
+                import_phases: None,
+                ignored_duplicates: Vec::new(),
+                layout: None,
             });
         }
         None
@@ -213,14 +405,22 @@ impl ExportDetector {
         let mut has_media = false;
 
         for path in paths {
-            if path.join("index.html").exists() { has_index = true; }
-            if path.join("html/chat_history").is_dir() { has_chat = true; }
-            if path.join("chat_media").is_dir() || path.join("media").is_dir() { has_media = true; }
-            
+            if path.join("index.html").exists() {
+                has_index = true;
+            }
+            if path.join("html/chat_history").is_dir() {
+                has_chat = true;
+            }
+            if path.join("chat_media").is_dir() || path.join("media").is_dir() {
+                has_media = true;
+            }
+
             // Siblings check: if this path is 'chat_media', look for its 'html' sibling
             if !has_index {
                 if let Some(parent) = path.parent() {
-                    if parent.join("index.html").exists() { has_index = true; }
+                    if parent.join("index.html").exists() {
+                        has_index = true;
+                    }
                 }
             }
         }
@@ -234,3 +434,154 @@ impl ExportDetector {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn write_zip(path: &Path, entries: &[&str]) {
+        let mut writer = zip::ZipWriter::new(fs::File::create(path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        for name in entries {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(b"<html></html>").unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    fn mixed_group(dir: &Path) -> (PathBuf, PathBuf) {
+        let good = dir.join("mydata~1700000000.zip");
+        write_zip(
+            &good,
+            &["index.html", "html/chat_history/subpage_a.html", "chat_media/x.jpg"],
+        );
+        // Interrupted download: a second part cut off before its central directory
+        let bad = dir.join("mydata~1700000000-2.zip");
+        write_zip(&bad, &["chat_media/y.jpg"]);
+        let bytes = fs::read(&bad).unwrap();
+        fs::write(&bad, &bytes[..bytes.len() / 2]).unwrap();
+        (good, bad)
+    }
+
+    #[test]
+    fn test_group_with_truncated_part_is_corrupted() {
+        let dir = tempfile::tempdir().unwrap();
+        let (good, bad) = mixed_group(dir.path());
+
+        let exports = ExportDetector::detect_in_directory(dir.path()).unwrap();
+        assert_eq!(exports.len(), 1);
+        let export = &exports[0];
+        assert_eq!(export.validation_status, ValidationStatus::Corrupted);
+        assert_eq!(export.parts.len(), 2);
+        let bad_part = export.parts.iter().find(|p| p.path == bad).unwrap();
+        assert!(!bad_part.readable);
+        assert!(bad_part.error.is_some());
+        assert!(export.parts.iter().find(|p| p.path == good).unwrap().readable);
+    }
+
+    #[test]
+    fn test_prepare_zip_parts_requires_opt_in_to_skip() {
+        let dir = tempfile::tempdir().unwrap();
+        let (good, _) = mixed_group(dir.path());
+        let export = ExportDetector::detect_in_directory(dir.path()).unwrap().remove(0);
+
+        let err = ExportDetector::prepare_zip_parts(&mut export.clone(), false).unwrap_err();
+        assert!(err.to_string().contains("mydata~1700000000-2.zip"));
+
+        let mut skipped = export.clone();
+        ExportDetector::prepare_zip_parts(&mut skipped, true).unwrap();
+        assert_eq!(skipped.source_paths, vec![good]);
+    }
+
+    #[test]
+    fn test_all_parts_readable_is_valid() {
+        let dir = tempfile::tempdir().unwrap();
+        write_zip(
+            &dir.path().join("mydata~1.zip"),
+            &["index.html", "html/chat_history/subpage_a.html"],
+        );
+        write_zip(&dir.path().join("mydata~1-2.zip"), &["chat_media/x.jpg"]);
+        let exports = ExportDetector::detect_in_directory(dir.path()).unwrap();
+        assert_eq!(exports[0].validation_status, ValidationStatus::Valid);
+        assert!(exports[0].parts.iter().all(|p| p.readable));
+    }
+
+    #[test]
+    fn test_redownloaded_parts_merge_into_one_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("mydata~1.zip");
+        write_zip(&main, &["index.html", "html/chat_history/subpage_a.html"]);
+        let partial = dir.path().join("mydata~1 (1).zip");
+        write_zip(&partial, &["index.html"]);
+        let second = dir.path().join("MyData~1-2.zip");
+        write_zip(&second, &["chat_media/x.jpg", "chat_media/y.jpg"]);
+        let doubled = dir.path().join("mydata~1-2.zip.zip");
+        write_zip(&doubled, &["chat_media/x.jpg"]);
+
+        let exports = ExportDetector::detect_in_directory(dir.path()).unwrap();
+        assert_eq!(exports.len(), 1, "{:?}", exports);
+        let export = &exports[0];
+        assert_eq!(export.id, "mydata~1");
+        assert_eq!(export.source_type, ExportSourceType::Zip);
+        assert_eq!(export.source_paths, [second, main]);
+        assert_eq!(export.validation_status, ValidationStatus::Valid);
+        let mut ignored = export.ignored_duplicates.clone();
+        ignored.sort();
+        assert_eq!(ignored, [partial, doubled]);
+    }
+
+    #[test]
+    fn test_renamed_export_folder_is_found_by_its_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let renamed = dir.path().join("old phone backup");
+        fs::create_dir_all(renamed.join("json")).unwrap();
+        fs::write(renamed.join("index.html"), "<html></html>").unwrap();
+        fs::create_dir_all(dir.path().join("holiday photos")).unwrap();
+
+        let exports = ExportDetector::detect_in_directory(dir.path()).unwrap();
+        assert_eq!(exports.len(), 1, "{:?}", exports);
+        assert_eq!(exports[0].source_paths, [renamed]);
+
+        let no_probing = DetectionSettings {
+            probe_limit: 0,
+            ..Default::default()
+        };
+        assert!(ExportDetector::detect_in_directory_with(dir.path(), &no_probing)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_name_pattern_from_settings_applies_to_the_next_scan() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::db::DatabaseManager::new(&dir.path().join("test.db")).unwrap();
+        let exports_dir = dir.path().join("downloads");
+        fs::create_dir_all(&exports_dir).unwrap();
+        write_zip(
+            &exports_dir.join("Export-2024.zip"),
+            &["index.html", "chat_media/x.jpg"],
+        );
+
+        let scan = || ExportDetector::detect_in_directory_with(&exports_dir, &db.get_detection_settings().unwrap());
+        assert!(scan().unwrap().is_empty());
+
+        db.set_detection_settings(&DetectionSettings {
+            name_patterns: vec!["export-????.zip".into()],
+            ..Default::default()
+        })
+        .unwrap();
+        let exports = scan().unwrap();
+        assert_eq!(exports.len(), 1);
+        assert_eq!(exports[0].source_type, ExportSourceType::Zip);
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("backup*", "backup 2024"));
+        assert!(glob_matches("*~*.zip", "mine~1.zip"));
+        assert!(glob_matches("a?c", "abc"));
+        assert!(!glob_matches("a?c", "ac"));
+        assert!(!glob_matches("*.zip", "export.zip.part"));
+    }
+}
@@ -1,34 +1,189 @@
+use crate::error::{AppError, AppResult};
+use crate::ingestion::is_os_metadata;
+use crate::ingestion::pipeline::ProgressSink;
+use crate::models::IngestionProgress;
+use crate::thumbnails::{fnv1a_64_update, FNV1A_64_OFFSET};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::{Path, PathBuf};
-use crate::error::{AppResult, AppError};
 use zip::ZipArchive;
-use tauri::{Emitter, AppHandle};
-use crate::models::IngestionProgress;
 
 pub struct ZipExtractor;
 
+/// File in an extraction directory recording which zips it holds and whether every part was
+/// written. Directories without one are never cleared.
+const EXTRACTION_MARKER: &str = ".snapdata-extraction";
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct ExtractionMarker {
+    sources: Vec<SourceStamp>,
+    complete: bool,
+}
+
+/// A zip part as it was when extracted; a changed size or modification time means the
+/// directory no longer matches it.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct SourceStamp {
+    path: PathBuf,
+    len: u64,
+    modified: Option<u64>,
+}
+
+fn source_stamps(zip_paths: &[PathBuf]) -> Vec<SourceStamp> {
+    zip_paths
+        .iter()
+        .map(|path| {
+            let meta = fs::metadata(path).ok();
+            SourceStamp {
+                path: path.clone(),
+                len: meta.as_ref().map_or(0, |m| m.len()),
+                modified: meta
+                    .and_then(|m| m.modified().ok())
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs()),
+            }
+        })
+        .collect()
+}
+
+fn read_marker(extraction_path: &Path) -> Option<ExtractionMarker> {
+    let text = fs::read_to_string(extraction_path.join(EXTRACTION_MARKER)).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+fn write_marker(extraction_path: &Path, zip_paths: &[PathBuf], complete: bool) -> AppResult<()> {
+    let marker = ExtractionMarker {
+        sources: source_stamps(zip_paths),
+        complete,
+    };
+    fs::write(extraction_path.join(EXTRACTION_MARKER), serde_json::to_string(&marker)?)?;
+    Ok(())
+}
+
+/// Directory name for one import of an export: its id, a short hash of the source paths, and
+/// when the import started. Two accounts' "mydata~1.zip" or a second import of the same zips
+/// never share a directory.
+pub fn working_dir_name(export_id: &str, source_paths: &[PathBuf], started_at: DateTime<Utc>) -> String {
+    let hash = source_paths.iter().fold(FNV1A_64_OFFSET, |hash, path| {
+        fnv1a_64_update(hash, path.to_string_lossy().as_bytes())
+    });
+    format!(
+        "{}-{:08x}-{}",
+        export_id,
+        hash >> 32,
+        started_at.format("%Y%m%dT%H%M%S")
+    )
+}
+
+/// What an extraction run wrote and what it deliberately left out.
+#[derive(Debug, Default)]
+pub struct ExtractionReport {
+    pub extraction_path: PathBuf,
+    /// The directory already held a complete extraction of the same zips and was kept as is.
+    pub reused: bool,
+    pub files_extracted: u64,
+    /// `__MACOSX/`, `._*` and `.DS_Store` entries that were not written.
+    pub skipped_os_metadata: u64,
+}
+
 impl ZipExtractor {
     pub fn extract(
         zip_paths: &[PathBuf],
-        target_dir: &Path,
+        extraction_path: &Path,
+        export_id: &str,
+        sink: &dyn ProgressSink,
+    ) -> AppResult<ExtractionReport> {
+        Self::extract_until(
+            zip_paths,
+            extraction_path,
+            export_id,
+            |progress| sink.progress(progress),
+            || sink.is_cancelled(),
+        )
+    }
+
+    /// Whether `extraction_path` holds a complete extraction of `zip_paths` as they are now.
+    pub fn is_reusable(extraction_path: &Path, zip_paths: &[PathBuf]) -> bool {
+        read_marker(extraction_path).is_some_and(|m| m.complete && m.sources == source_stamps(zip_paths))
+    }
+
+    /// Delete an extraction directory nothing uses any more. One without the extraction marker
+    /// wasn't made by an extraction and is kept. Returns whether it was removed.
+    pub fn remove_extraction(extraction_path: &Path) -> AppResult<bool> {
+        if read_marker(extraction_path).is_none() {
+            return Ok(false);
+        }
+        fs::remove_dir_all(extraction_path)?;
+        Ok(true)
+    }
+
+    /// Make `extraction_path` an empty directory for a fresh extraction. An earlier partial or
+    /// outdated extraction is cleared; a non-empty directory that isn't one is left alone.
+    fn prepare_dir(extraction_path: &Path) -> AppResult<()> {
+        if extraction_path.is_dir() {
+            if read_marker(extraction_path).is_some() {
+                log::info!("ZipExtractor: clearing earlier extraction in {:?}", extraction_path);
+                fs::remove_dir_all(extraction_path)?;
+            } else if fs::read_dir(extraction_path)?.next().is_some() {
+                return Err(AppError::Validation(format!(
+                    "{} already exists and was not created by an extraction; refusing to overwrite it.",
+                    extraction_path.display()
+                )));
+            }
+        }
+        fs::create_dir_all(extraction_path)?;
+        Ok(())
+    }
+
+    /// Extract every part into `extraction_path`, or keep it as is when it already holds a
+    /// complete extraction of the same zips.
+    pub fn extract_with_progress<F: FnMut(IngestionProgress)>(
+        zip_paths: &[PathBuf],
+        extraction_path: &Path,
         export_id: &str,
-        app_handle: &AppHandle
-    ) -> AppResult<PathBuf> {
+        on_progress: F,
+    ) -> AppResult<ExtractionReport> {
+        Self::extract_until(zip_paths, extraction_path, export_id, on_progress, || false)
+    }
+
+    /// `extract_with_progress`, checking `cancelled` before each entry and stopping with
+    /// `AppError::Cancelled` once it returns true. The partial extraction is left in place.
+    pub fn extract_until<F: FnMut(IngestionProgress), C: Fn() -> bool>(
+        zip_paths: &[PathBuf],
+        extraction_path: &Path,
+        export_id: &str,
+        mut on_progress: F,
+        cancelled: C,
+    ) -> AppResult<ExtractionReport> {
         let start_time = std::time::Instant::now();
-        log::info!("ZipExtractor: starting extraction of {} part(s)", zip_paths.len());
-        
-        let extraction_path = target_dir.join(export_id);
-        if !extraction_path.exists() {
-            fs::create_dir_all(&extraction_path)?;
+        let extraction_path = extraction_path.to_path_buf();
+        if Self::is_reusable(&extraction_path, zip_paths) {
+            log::info!("ZipExtractor: reusing complete extraction in {:?}", extraction_path);
+            return Ok(ExtractionReport {
+                extraction_path,
+                reused: true,
+                ..Default::default()
+            });
         }
+        log::info!("ZipExtractor: starting extraction of {} part(s)", zip_paths.len());
+
+        Self::prepare_dir(&extraction_path)?;
+        write_marker(&extraction_path, zip_paths, false)?;
 
         let total_parts = zip_paths.len();
         let mut total_extracted_files = 0u64;
+        let mut skipped_os_metadata = 0u64;
         let mut total_bytes: u64 = 0;
         const MAX_TOTAL_SIZE: u64 = 500 * 1024 * 1024 * 1024; // 500GB safety limit
 
         for (part_idx, zip_path) in zip_paths.iter().enumerate() {
-            log::info!("ZipExtractor: extracting part {}/{}: {:?}", part_idx + 1, total_parts, zip_path);
+            log::info!(
+                "ZipExtractor: extracting part {}/{}: {:?}",
+                part_idx + 1,
+                total_parts,
+                zip_path
+            );
 
             if !zip_path.exists() {
                 log::warn!("ZipExtractor: zip part not found: {:?}", zip_path);
@@ -36,13 +191,16 @@ impl ZipExtractor {
             }
 
             let file = fs::File::open(zip_path)?;
-            let mut archive = ZipArchive::new(file).map_err(|e| {
-                AppError::Parsing(format!("Invalid zip file {:?}: {}", zip_path, e))
-            })?;
+            let mut archive = ZipArchive::new(file)
+                .map_err(|e| AppError::Parsing(format!("Invalid zip file {:?}: {}", zip_path, e)))?;
 
             let total_files_in_part = archive.len();
-            
+
             for i in 0..total_files_in_part {
+                if cancelled() {
+                    log::info!("ZipExtractor: cancelled after {} files", total_extracted_files);
+                    return Err(AppError::Cancelled("extraction stopped".into()));
+                }
                 let mut file = archive.by_index(i).map_err(|e| {
                     AppError::Parsing(format!("Failed to read zip entry {} in {:?}: {}", i, zip_path, e))
                 })?;
@@ -56,6 +214,12 @@ impl ZipExtractor {
                 }
 
                 let outpath = match file.enclosed_name() {
+                    Some(path) if is_os_metadata(&path) => {
+                        if !file.is_dir() {
+                            skipped_os_metadata += 1;
+                        }
+                        continue;
+                    }
                     Some(path) => extraction_path.join(path),
                     None => continue,
                 };
@@ -68,8 +232,8 @@ impl ZipExtractor {
                             fs::create_dir_all(p)?;
                         }
                     }
-                    
-                    // "Newest wins": If file exists, we could check timestamps, 
+
+                    // "Newest wins": If file exists, we could check timestamps,
                     // but usually, later parts in multi-part zips are the intended ones
                     // or contain different files entirely.
                     let mut outfile = fs::File::create(&outpath)?;
@@ -80,22 +244,153 @@ impl ZipExtractor {
                 if i % 100 == 0 || i == total_files_in_part - 1 {
                     let part_progress = i as f32 / total_files_in_part as f32;
                     let total_progress = (part_idx as f32 + part_progress) / total_parts as f32;
-                    
-                    let _ = app_handle.emit("ingestion-progress", IngestionProgress {
+
+                    on_progress(IngestionProgress {
                         export_id: export_id.to_string(),
                         current_step: "Extracting".to_string(),
                         progress: total_progress * 0.10, // Extraction is ~10% of pipeline
                         message: format!(
-                            "Extracting part {} of {} (file {} of {})...", 
-                            part_idx + 1, total_parts, i + 1, total_files_in_part
+                            "Extracting part {} of {} (file {} of {})...",
+                            part_idx + 1,
+                            total_parts,
+                            i + 1,
+                            total_files_in_part
                         ),
                     });
                 }
             }
         }
 
+        write_marker(&extraction_path, zip_paths, true)?;
         let duration = start_time.elapsed();
-        log::info!("ZipExtractor: extraction complete in {:?}. Total files: {}", duration, total_extracted_files);
-        Ok(extraction_path)
+        log::info!(
+            "ZipExtractor: extraction complete in {:?}. Total files: {}, skipped macOS metadata: {}",
+            duration,
+            total_extracted_files,
+            skipped_os_metadata
+        );
+        Ok(ExtractionReport {
+            extraction_path,
+            reused: false,
+            files_extracted: total_extracted_files,
+            skipped_os_metadata,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn write_zip(zip_path: &Path, entries: &[(&str, &[u8])]) {
+        fs::create_dir_all(zip_path.parent().unwrap()).unwrap();
+        let mut writer = zip::ZipWriter::new(fs::File::create(zip_path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, data) in entries {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn test_skips_macos_metadata_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let zip_path = dir.path().join("mydata~1.zip");
+        write_zip(
+            &zip_path,
+            &[
+                ("index.html", b"data"),
+                ("chat_media/2023-01-01_ABC.jpg", b"data"),
+                ("chat_media/._2023-01-01_ABC.jpg", b"data"),
+                ("chat_media/.DS_Store", b"data"),
+                ("__MACOSX/chat_media/._2023-01-01_ABC.jpg", b"data"),
+            ],
+        );
+
+        let report =
+            ZipExtractor::extract_with_progress(&[zip_path], &dir.path().join("out/e1"), "e1", |_| {}).unwrap();
+        assert_eq!(report.files_extracted, 2);
+        assert_eq!(report.skipped_os_metadata, 3);
+        assert!(report.extraction_path.join("chat_media/2023-01-01_ABC.jpg").exists());
+        assert!(!report.extraction_path.join("chat_media/._2023-01-01_ABC.jpg").exists());
+        assert!(!report.extraction_path.join("__MACOSX").exists());
+    }
+
+    #[test]
+    fn test_same_named_zips_get_separate_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let alice = dir.path().join("alice/mydata.zip");
+        let bob = dir.path().join("bob/mydata.zip");
+        write_zip(&alice, &[("index.html", b"alice")]);
+        write_zip(&bob, &[("index.html", b"bob")]);
+
+        let started = Utc::now();
+        let alice_dir = dir
+            .path()
+            .join("work")
+            .join(working_dir_name("mydata", std::slice::from_ref(&alice), started));
+        let bob_dir = dir
+            .path()
+            .join("work")
+            .join(working_dir_name("mydata", std::slice::from_ref(&bob), started));
+        assert_ne!(alice_dir, bob_dir);
+        assert!(alice_dir.file_name().unwrap().to_string_lossy().starts_with("mydata-"));
+
+        ZipExtractor::extract_with_progress(&[alice], &alice_dir, "mydata", |_| {}).unwrap();
+        ZipExtractor::extract_with_progress(&[bob], &bob_dir, "mydata", |_| {}).unwrap();
+        assert_eq!(fs::read(alice_dir.join("index.html")).unwrap(), b"alice");
+        assert_eq!(fs::read(bob_dir.join("index.html")).unwrap(), b"bob");
+    }
+
+    #[test]
+    fn test_reimport_clears_partial_and_reuses_complete_extractions() {
+        let dir = tempfile::tempdir().unwrap();
+        let zip_path = dir.path().join("mydata~1.zip");
+        write_zip(&zip_path, &[("index.html", b"data")]);
+        let parts = [zip_path.clone()];
+        let target = dir.path().join("work/mydata~1");
+
+        // An interrupted extraction left a stray file behind
+        fs::create_dir_all(&target).unwrap();
+        write_marker(&target, &parts, false).unwrap();
+        fs::write(target.join("stray.html"), b"old").unwrap();
+        let report = ZipExtractor::extract_with_progress(&parts, &target, "e1", |_| {}).unwrap();
+        assert!(!report.reused);
+        assert!(!target.join("stray.html").exists());
+        assert!(ZipExtractor::is_reusable(&target, &parts));
+
+        let again = ZipExtractor::extract_with_progress(&parts, &target, "e1", |_| {}).unwrap();
+        assert!(again.reused && again.files_extracted == 0);
+
+        // A zip that changed since is extracted afresh
+        write_zip(&zip_path, &[("index.html", b"newer data")]);
+        assert!(!ZipExtractor::is_reusable(&target, &parts));
+        ZipExtractor::extract_with_progress(&parts, &target, "e1", |_| {}).unwrap();
+        assert_eq!(fs::read(target.join("index.html")).unwrap(), b"newer data");
+
+        // Directories the extractor didn't make are never cleared
+        let foreign = dir.path().join("work/foreign");
+        fs::create_dir_all(&foreign).unwrap();
+        fs::write(foreign.join("keep.txt"), b"mine").unwrap();
+        assert!(matches!(
+            ZipExtractor::extract_with_progress(&parts, &foreign, "e1", |_| {}),
+            Err(AppError::Validation(_))
+        ));
+        assert!(foreign.join("keep.txt").exists());
+    }
+
+    #[test]
+    fn test_cancelled_extraction_stops_before_completing() {
+        let dir = tempfile::tempdir().unwrap();
+        let zip_path = dir.path().join("mydata~1.zip");
+        write_zip(&zip_path, &[("index.html", b"data"), ("json/friends.json", b"{}")]);
+        let target = dir.path().join("out");
+
+        let result = ZipExtractor::extract_until(std::slice::from_ref(&zip_path), &target, "e1", |_| {}, || true);
+        assert!(matches!(result, Err(AppError::Cancelled(_))));
+        assert!(!target.join("index.html").exists());
+        assert!(!ZipExtractor::is_reusable(&target, &[zip_path]));
     }
 }
@@ -0,0 +1,128 @@
+//! Locating an export's artifacts. The standard layout has `json/`, `html/chat_history` and the
+//! media folders at the top; exports that were reorganized or extracted into an extra folder
+//! have them a level or two further down. The result is stored on the export so later runs
+//! reuse it, and re-detected when a stored path has gone.
+
+use crate::ingestion::is_os_metadata;
+use crate::models::ExportLayout;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How many folders below the root to look for an artifact missing from its standard place.
+const MAX_SEARCH_DEPTH: usize = 2;
+
+/// Locate every artifact under `root`.
+pub fn detect_layout(root: &Path) -> ExportLayout {
+    let dirs = search_dirs(root);
+    let find = |relative: &str, want_dir: bool| {
+        dirs.iter()
+            .map(|dir| dir.join(relative))
+            .find(|p| if want_dir { p.is_dir() } else { p.is_file() })
+    };
+    ExportLayout {
+        root: root.to_path_buf(),
+        friends_json: find("json/friends.json", false),
+        chat_history_json: find("json/chat_history.json", false),
+        snap_history_json: find("json/snap_history.json", false),
+        story_history_json: find("json/story_history.json", false),
+        talk_history_json: find("json/talk_history.json", false),
+        location_history_json: find("json/location_history.json", false),
+        memories_json: find("json/memories_history.json", false),
+        account_json: find("json/account.json", false),
+        user_profile_json: find("json/user_profile.json", false),
+        chat_html_dir: find("html/chat_history", true),
+        media_dirs: ["chat_media", "media"]
+            .iter()
+            .filter_map(|name| find(name, true))
+            .collect(),
+    }
+}
+
+/// The layout to import `root` with: `stored` when it was found under the same root and every
+/// path in it still exists, otherwise a fresh detection. The flag is true when a stored layout
+/// was stale and had to be re-detected.
+pub fn resolve_layout(stored: Option<&ExportLayout>, root: &Path) -> (ExportLayout, bool) {
+    match stored.filter(|layout| layout.root == root) {
+        Some(layout) => {
+            let stale = layout.stale_paths();
+            if stale.is_empty() {
+                return (layout.clone(), false);
+            }
+            log::warn!(
+                "{} stored export path(s) no longer exist; detecting the layout again",
+                stale.len()
+            );
+            log::debug!("Stale layout paths: {:?}", stale);
+            (detect_layout(root), true)
+        }
+        None => (detect_layout(root), false),
+    }
+}
+
+impl ExportLayout {
+    /// Stored paths that no longer exist.
+    pub fn stale_paths(&self) -> Vec<&Path> {
+        [
+            &self.friends_json,
+            &self.chat_history_json,
+            &self.snap_history_json,
+            &self.story_history_json,
+            &self.talk_history_json,
+            &self.location_history_json,
+            &self.memories_json,
+            &self.chat_html_dir,
+            &self.account_json,
+            &self.user_profile_json,
+        ]
+        .into_iter()
+        .flatten()
+        .chain(&self.media_dirs)
+        .map(PathBuf::as_path)
+        .filter(|p| !p.exists())
+        .collect()
+    }
+}
+
+/// `root` and its subfolders down to `MAX_SEARCH_DEPTH`, shallowest first so the standard
+/// layout wins over a copy further down.
+fn search_dirs(root: &Path) -> Vec<PathBuf> {
+    let mut dirs = vec![root.to_path_buf()];
+    let mut level = vec![root.to_path_buf()];
+    for _ in 0..MAX_SEARCH_DEPTH {
+        let mut next: Vec<PathBuf> = level
+            .iter()
+            .filter_map(|dir| fs::read_dir(dir).ok())
+            .flat_map(|entries| entries.flatten().map(|e| e.path()))
+            .filter(|p| p.is_dir() && !is_os_metadata(p))
+            .collect();
+        next.sort();
+        dirs.extend(next.iter().cloned());
+        level = next;
+    }
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finds_artifacts_in_the_standard_place_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("json")).unwrap();
+        fs::write(root.join("json/friends.json"), "{}").unwrap();
+        // A reorganized copy one level down, and the chat pages only there
+        fs::create_dir_all(root.join("backup/json")).unwrap();
+        fs::write(root.join("backup/json/friends.json"), "{}").unwrap();
+        fs::create_dir_all(root.join("backup/html/chat_history")).unwrap();
+        fs::create_dir_all(root.join("chat_media")).unwrap();
+
+        let layout = detect_layout(root);
+        assert_eq!(layout.friends_json, Some(root.join("json/friends.json")));
+        assert_eq!(layout.chat_html_dir, Some(root.join("backup/html/chat_history")));
+        assert_eq!(layout.media_dirs, vec![root.join("chat_media")]);
+        assert_eq!(layout.chat_history_json, None);
+        assert!(layout.stale_paths().is_empty());
+    }
+}
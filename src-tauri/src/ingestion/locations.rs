@@ -0,0 +1,143 @@
+//! Shared locations in chat text: map links and "pin:"-style coordinates that Snapchat exports
+//! as plain text. Coordinates are only taken from a recognized map URL or after a location
+//! keyword, and only when both values are in range, so ordinary numbers are left alone.
+
+use crate::models::Event;
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// Metadata key holding `{"lat": .., "lon": ..}` for a message that shares a location.
+pub const SHARED_LOCATION_KEY: &str = "shared_location";
+
+/// Event types whose text can carry a shared location.
+const LOCATION_EVENT_TYPES: &[&str] = &["SHARE", "TEXT"];
+
+/// Snap Map, Google Maps and Apple Maps links with the coordinates in the URL.
+static MAP_URL_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)(?:map\.snapchat\.com/@|google\.[a-z.]+/maps\S*?[@=/]|maps\.google\.[a-z.]+/\S*?[?&](?:q|ll)=|maps\.apple\.com/\S*?[?&](?:ll|q|sll)=)(-?\d{1,2}(?:\.\d+)?)(?:,|%2C)\s*(-?\d{1,3}(?:\.\d+)?)",
+    )
+    .unwrap()
+});
+
+/// A location keyword followed by a decimal coordinate pair, e.g. "pin: 40.7128, -74.0060".
+static KEYWORD_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(?:pin|shared location|my location|location)\s*:?\s*(-?\d{1,2}\.\d+)\s*,\s*(-?\d{1,3}\.\d+)")
+        .unwrap()
+});
+
+/// Coordinates in the "Latitude, Longitude: 40.50679, -123.991455" form used by memories and
+/// location history. An accuracy after a value ("40.50679 ± 12.0 meters") is ignored.
+pub fn parse_location(text: &str) -> (Option<f64>, Option<f64>) {
+    if let Some(coords) = text.trim().strip_prefix("Latitude, Longitude: ") {
+        let parts: Vec<&str> = coords.split(", ").collect();
+        if parts.len() == 2 {
+            let value = |part: &str| part.split_whitespace().next()?.parse::<f64>().ok();
+            return (value(parts[0]), value(parts[1]));
+        }
+    }
+    (None, None)
+}
+
+/// The shared location in `text`, if any, as (latitude, longitude).
+pub fn find_shared_location(text: &str) -> Option<(f64, f64)> {
+    [&*MAP_URL_RE, &*KEYWORD_RE]
+        .iter()
+        .flat_map(|re| re.captures_iter(text))
+        .filter_map(|caps| Some((caps[1].parse::<f64>().ok()?, caps[2].parse::<f64>().ok()?)))
+        .find(|&(lat, lon)| {
+            (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon) && (lat, lon) != (0.0, 0.0)
+        })
+}
+
+/// Record shared locations found in message text in each event's metadata. Returns how many
+/// events had one.
+pub fn tag_shared_locations(events: &mut [Event]) -> usize {
+    let mut found = 0;
+    for event in events
+        .iter_mut()
+        .filter(|e| LOCATION_EVENT_TYPES.contains(&e.event_type.as_str()))
+    {
+        let Some((lat, lon)) = event.content.as_deref().and_then(find_shared_location) else {
+            continue;
+        };
+        let mut metadata = match event.metadata.as_deref().map(serde_json::from_str::<serde_json::Value>) {
+            Some(Ok(serde_json::Value::Object(map))) => map,
+            None => serde_json::Map::new(),
+            // Leave metadata we can't safely extend untouched
+            Some(_) => continue,
+        };
+        metadata.insert(
+            SHARED_LOCATION_KEY.into(),
+            serde_json::json!({ "lat": lat, "lon": lon }),
+        );
+        event.metadata = Some(serde_json::Value::Object(metadata).to_string());
+        found += 1;
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+
+    #[test]
+    fn test_finds_coordinates_in_map_links_and_pins() {
+        assert_eq!(
+            find_shared_location("https://map.snapchat.com/@51.507400,-0.127800,12.00z"),
+            Some((51.5074, -0.1278))
+        );
+        assert_eq!(
+            find_shared_location("meet here https://www.google.com/maps/search/?api=1&query=40.7128%2C-74.0060"),
+            Some((40.7128, -74.006))
+        );
+        assert_eq!(
+            find_shared_location("http://maps.apple.com/?ll=-33.8688,151.2093&q=Sydney"),
+            Some((-33.8688, 151.2093))
+        );
+        assert_eq!(find_shared_location("Pin: 48.8584, 2.2945"), Some((48.8584, 2.2945)));
+    }
+
+    #[test]
+    fn test_parse_location_reads_export_coordinates() {
+        assert_eq!(
+            parse_location("Latitude, Longitude: 40.50679, -123.991455"),
+            (Some(40.50679), Some(-123.991455))
+        );
+        assert_eq!(
+            parse_location("Latitude, Longitude: 51.5 ± 12.0 meters, -0.12 ± 12.0 meters"),
+            (Some(51.5), Some(-0.12))
+        );
+        assert_eq!(parse_location("Latitude, Longitude: unknown"), (None, None));
+        assert_eq!(parse_location(""), (None, None));
+    }
+
+    #[test]
+    fn test_ignores_numbers_without_context_or_out_of_range() {
+        assert_eq!(find_shared_location("scored 40.5, 74.2 on the test"), None);
+        assert_eq!(find_shared_location("location: 95.0000, 10.0000"), None);
+        assert_eq!(find_shared_location("location: 0.0, 0.0"), None);
+        assert_eq!(find_shared_location("location: 12, 34"), None);
+        assert_eq!(find_shared_location("https://example.com/@51.5,-0.12"), None);
+    }
+
+    #[test]
+    fn test_tags_share_and_text_events_only() {
+        let mut events = test_fixtures::events();
+        events[0].event_type = "SHARE".to_string();
+        events[0].content = Some("https://map.snapchat.com/@51.5074,-0.1278,12z".to_string());
+        events[0].metadata = Some(r#"{"saved": true}"#.to_string());
+        events[1].event_type = "MEDIA".to_string();
+        events[1].content = Some("pin: 48.8584, 2.2945".to_string());
+
+        assert_eq!(tag_shared_locations(&mut events), 1);
+        let meta: serde_json::Value = serde_json::from_str(events[0].metadata.as_deref().unwrap()).unwrap();
+        assert_eq!(meta[SHARED_LOCATION_KEY]["lat"], 51.5074);
+        assert_eq!(meta["saved"], true);
+        assert!(events[1]
+            .metadata
+            .as_deref()
+            .is_none_or(|m| !m.contains(SHARED_LOCATION_KEY)));
+    }
+}
@@ -1,18 +1,101 @@
-use std::collections::HashMap;
-use std::path::{Path, PathBuf};
+use crate::ingestion::is_os_metadata;
+use crate::models::{ConversationMediaStats, Event, ExportLayout, MediaLinkingSettings};
+use chrono::{DateTime, Duration, Utc};
+use regex::Regex;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use crate::models::Event;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MediaKind {
+    Image,
+    Video,
+}
+
+impl MediaKind {
+    fn of_file(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "jpg" | "jpeg" | "png" | "gif" | "webp" | "heic" => Some(Self::Image),
+            "mp4" | "mov" | "webm" | "m4v" => Some(Self::Video),
+            _ => None,
+        }
+    }
+}
+
+/// A photo or video whose name carries no Media ID, matched to messages by when it was saved.
+struct DatedFile {
+    path: PathBuf,
+    modified: DateTime<Utc>,
+    kind: MediaKind,
+}
 
 pub struct MediaLinker {
-    /// Maps media ID (from filename) -> absolute file path
-    id_map: HashMap<String, PathBuf>,
+    /// Maps media ID (from filename) -> absolute paths of the files carrying it. Exports can
+    /// hold several, such as an original in chat_media/ and a thumbnail in media/.
+    id_map: HashMap<String, Vec<PathBuf>>,
+    /// Names of older exports' files; the first capture group is the media ID.
+    legacy_patterns: Vec<Regex>,
+    /// Files not named "<date>_<MEDIA_ID>", oldest first.
+    dated_files: Vec<DatedFile>,
+    /// Files already linked, or carrying a Media ID some message names, so date matching never
+    /// hands them to another message.
+    claimed: HashSet<PathBuf>,
+    date_window: Duration,
+    linked_by_id: usize,
+    linked_by_date: usize,
 }
 
 impl MediaLinker {
-    pub fn new(media_dir: &Path) -> Self {
-        let mut linker = Self {
+    /// Event types that carry media.
+    pub const LINKED_EVENT_TYPES: [&'static str; 5] = ["MEDIA", "NOTE", "SNAP", "SNAP_VIDEO", "STICKER"];
+
+    /// An empty index that reads file names and falls back to file dates as `settings` say.
+    pub fn with_settings(settings: &MediaLinkingSettings) -> Self {
+        let legacy_patterns = settings
+            .filename_patterns
+            .iter()
+            .filter_map(|pattern| match Regex::new(pattern) {
+                Ok(regex) => Some(regex),
+                Err(e) => {
+                    log::warn!("MediaLinker: ignoring invalid file name pattern {:?}: {}", pattern, e);
+                    None
+                }
+            })
+            .collect();
+        Self {
             id_map: HashMap::new(),
-        };
+            legacy_patterns,
+            dated_files: Vec::new(),
+            claimed: HashSet::new(),
+            date_window: Duration::minutes(settings.date_window_minutes),
+            linked_by_id: 0,
+            linked_by_date: 0,
+        }
+    }
+
+    /// Index the media folders of an extracted export or export folder.
+    pub fn for_export(source_path: &Path) -> Self {
+        let mut linker = Self::new(&source_path.join("chat_media"));
+        let media_dir = source_path.join("media");
+        if media_dir.is_dir() {
+            linker.add_media_directory(&media_dir);
+        }
+        linker
+    }
+
+    /// Index the media folders recorded in an export's layout.
+    pub fn for_layout(layout: &ExportLayout, settings: &MediaLinkingSettings) -> Self {
+        let mut linker = Self::with_settings(settings);
+        for dir in &layout.media_dirs {
+            linker.add_media_directory(dir);
+        }
+        linker
+    }
+
+    pub fn new(media_dir: &Path) -> Self {
+        let mut linker = Self::with_settings(&MediaLinkingSettings::default());
         linker.add_media_directory(media_dir);
         linker
     }
@@ -28,8 +111,20 @@ impl MediaLinker {
         let mut id_indexed = 0;
 
         self.scan_recursive(media_dir, &mut file_count, &mut id_indexed);
-
-        log::info!("MediaLinker: indexed {} files ({} by ID) recursively", file_count, id_indexed);
+        self.dated_files.sort_by_key(|f| f.modified);
+
+        log::info!(
+            "MediaLinker: indexed {} files ({} by ID) recursively",
+            file_count,
+            id_indexed
+        );
+        let colliding = self.id_map.values().filter(|paths| paths.len() > 1).count();
+        if colliding > 0 {
+            log::warn!(
+                "MediaLinker: {} media ID(s) are shared by more than one file",
+                colliding
+            );
+        }
         log::debug!("MediaLinker: indexed from {:?}", media_dir);
     }
 
@@ -38,6 +133,10 @@ impl MediaLinker {
             Ok(entries) => {
                 for entry in entries.flatten() {
                     let path = entry.path();
+                    // AppleDouble "._<name>" files would otherwise shadow the real file's ID
+                    if path.file_name().is_some_and(|n| is_os_metadata(Path::new(n))) {
+                        continue;
+                    }
                     if path.is_dir() {
                         self.scan_recursive(&path, file_count, id_indexed);
                         continue;
@@ -53,24 +152,22 @@ impl MediaLinker {
 
                     *file_count += 1;
 
-                    // Extract media ID: filename format is "YYYY-MM-DD_<MEDIA_ID>.<ext>"
-                    // The ID is everything between the first '_' and the last '.'
-                    if let Some(underscore_pos) = file_name.find('_') {
-                        let after_underscore = &file_name[underscore_pos + 1..];
-                        let media_id = if let Some(dot_pos) = after_underscore.rfind('.') {
-                            &after_underscore[..dot_pos]
-                        } else {
-                            after_underscore
-                        };
-
-                        if !media_id.is_empty() {
-                            let abs_path = fs::canonicalize(&path).unwrap_or_else(|e| {
-                                log::warn!("MediaLinker: canonicalize failed for {:?}: {}", path, e);
-                                path.clone()
-                            });
-                            self.id_map.insert(media_id.to_string(), abs_path);
-                            *id_indexed += 1;
+                    let abs_path = fs::canonicalize(&path).unwrap_or_else(|e| {
+                        log::warn!("MediaLinker: canonicalize failed for {:?}: {}", path, e);
+                        path.clone()
+                    });
+                    let legacy_id = self.legacy_media_id(&path);
+                    let dated_id = Self::media_id_from_file_name(&file_name).map(str::to_string);
+                    // Only "<date>_<MEDIA_ID>" names are trusted enough to skip linking by date
+                    if legacy_id.is_some() || dated_id.is_none() {
+                        self.add_dated_file(&abs_path);
+                    }
+                    if let Some(media_id) = legacy_id.or(dated_id) {
+                        let paths = self.id_map.entry(media_id).or_default();
+                        if !paths.contains(&abs_path) {
+                            paths.push(abs_path);
                         }
+                        *id_indexed += 1;
                     }
                 }
             }
@@ -80,6 +177,94 @@ impl MediaLinker {
         }
     }
 
+    /// The media ID a file name carries. Names are "YYYY-MM-DD_<MEDIA_ID>.<ext>": the ID is
+    /// everything between the first '_' and the last '.'.
+    pub fn media_id_from_file_name(file_name: &str) -> Option<&str> {
+        let (_, after_underscore) = file_name.split_once('_')?;
+        let media_id = after_underscore
+            .rfind('.')
+            .map_or(after_underscore, |dot_pos| &after_underscore[..dot_pos]);
+        (!media_id.is_empty()).then_some(media_id)
+    }
+
+    /// The media ID in a file name following one of the legacy patterns, such as "media~<ID>".
+    fn legacy_media_id(&self, path: &Path) -> Option<String> {
+        let stem = path.file_stem()?.to_str()?;
+        self.legacy_patterns
+            .iter()
+            .find_map(|pattern| pattern.captures(stem)?.get(1))
+            .map(|id| id.as_str().to_string())
+            .filter(|id| !id.is_empty())
+    }
+
+    fn add_dated_file(&mut self, path: &Path) {
+        let Some(kind) = MediaKind::of_file(path) else {
+            return;
+        };
+        match fs::metadata(path).and_then(|m| m.modified()) {
+            Ok(modified) => self.dated_files.push(DatedFile {
+                path: path.to_path_buf(),
+                modified: modified.into(),
+                kind,
+            }),
+            Err(e) => log::debug!("MediaLinker: no modification date for {:?}: {}", path, e),
+        }
+    }
+
+    /// Messages linked so far by a Media ID in a file name.
+    pub fn linked_by_id(&self) -> usize {
+        self.linked_by_id
+    }
+
+    /// Messages linked so far by a file's date, when no file name matched.
+    pub fn linked_by_date(&self) -> usize {
+        self.linked_by_date
+    }
+
+    /// The file indexed for `media_id`, if any.
+    pub fn path_for(&self, media_id: &str) -> Option<PathBuf> {
+        self.files_for(media_id).into_iter().next()
+    }
+
+    /// The existing files to link for `media_id`: the best copy of each file type, best first.
+    /// A copy under chat_media/ beats one elsewhere, then the largest wins, so a thumbnail never
+    /// stands in for the original while an image and its overlay are both kept.
+    fn files_for(&self, media_id: &str) -> Vec<PathBuf> {
+        let Some(paths) = self.id_map.get(media_id) else {
+            return Vec::new();
+        };
+        let mut ranked: Vec<(&PathBuf, u64)> = paths
+            .iter()
+            .filter_map(|path| match fs::metadata(path) {
+                Ok(meta) if meta.is_file() => Some((path, meta.len())),
+                _ => {
+                    log::debug!("MediaLinker: file no longer exists for ID '{}': {:?}", media_id, path);
+                    None
+                }
+            })
+            .collect();
+        ranked.sort_by_key(|&(path, len)| {
+            let in_chat_media = path.components().any(|c| c.as_os_str() == "chat_media");
+            (Reverse(in_chat_media), Reverse(len), path)
+        });
+        let mut extensions = HashSet::new();
+        ranked
+            .into_iter()
+            .filter(|(path, _)| extensions.insert(path.extension().map(|e| e.to_ascii_lowercase())))
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    /// Keep the files named by `media_ids` out of date matching, so a file a message will link by
+    /// ID is never taken by an earlier batch's date pass.
+    pub fn reserve_media_ids<'a>(&mut self, media_ids: impl IntoIterator<Item = &'a String>) {
+        for media_id in media_ids {
+            if let Some(paths) = self.id_map.get(media_id) {
+                self.claimed.extend(paths.iter().cloned());
+            }
+        }
+    }
+
     pub fn link_media(&mut self, events: &mut [Event]) {
         let mut id_matched = 0;
         let mut no_ids = 0;
@@ -92,10 +277,8 @@ impl MediaLinker {
                 continue;
             }
 
-            // Only link event types that carry media
-            match event.event_type.as_str() {
-                "MEDIA" | "NOTE" | "SNAP" | "SNAP_VIDEO" | "STICKER" => {}
-                _ => continue,
+            if !Self::LINKED_EVENT_TYPES.contains(&event.event_type.as_str()) {
+                continue;
             }
 
             // Only link via ID-based matching from event metadata
@@ -106,38 +289,104 @@ impl MediaLinker {
                 continue;
             }
 
-            let mut found_any = false;
             for mid in &media_ids {
-                if let Some(file_path) = self.id_map.get(mid) {
-                    // Verify file still exists
-                    if file_path.exists() {
-                        event.media_references.push(file_path.clone());
-                        found_any = true;
-                    } else {
-                        log::debug!("MediaLinker: file no longer exists for ID '{}': {:?}", mid, file_path);
-                    }
-                }
+                event.media_references.extend(self.files_for(mid));
             }
+            self.claimed.extend(event.media_references.iter().cloned());
 
-            if found_any {
+            if !event.media_references.is_empty() {
                 id_matched += 1;
             } else {
                 id_not_found += 1;
             }
         }
+        let referenced: HashSet<String> = events
+            .iter()
+            .flat_map(|e| Self::extract_media_ids(&e.metadata))
+            .collect();
+        self.reserve_media_ids(&referenced);
+        let date_matched = self.link_by_date(events);
+        self.linked_by_id += id_matched;
+        self.linked_by_date += date_matched;
+
+        log::debug!(
+            "MediaLinker: ID-matched {}, date-matched {}, no-ids-in-metadata {}, id-not-found {}, already-linked {}",
+            id_matched,
+            date_matched,
+            no_ids,
+            id_not_found,
+            already_linked
+        );
+    }
 
-        log::info!("MediaLinker: ID-matched {}, no-ids-in-metadata {}, id-not-found {}, already-linked {}",
-            id_matched, no_ids, id_not_found, already_linked);
+    /// Give media messages still without a file the unclaimed photo or video saved nearest their
+    /// time, within the date window, so a file never lands outside its conversation's date range
+    /// by more than that. Returns how many were linked.
+    fn link_by_date(&mut self, events: &mut [Event]) -> usize {
+        if self.dated_files.is_empty() {
+            return 0;
+        }
+        let mut linked = 0;
+        for event in events.iter_mut().filter(|e| e.media_references.is_empty()) {
+            let wanted = match event.event_type.as_str() {
+                "MEDIA" => None,
+                "SNAP" => Some(MediaKind::Image),
+                "SNAP_VIDEO" => Some(MediaKind::Video),
+                _ => continue,
+            };
+            let from = self
+                .dated_files
+                .partition_point(|f| f.modified < event.timestamp - self.date_window);
+            let nearest = self.dated_files[from..]
+                .iter()
+                .take_while(|f| f.modified <= event.timestamp + self.date_window)
+                .filter(|f| wanted.is_none_or(|kind| kind == f.kind) && !self.claimed.contains(&f.path))
+                .min_by_key(|f| (f.modified - event.timestamp).abs());
+            if let Some(file) = nearest {
+                self.claimed.insert(file.path.clone());
+                event.media_references.push(file.path.clone());
+                linked += 1;
+            }
+        }
+        linked
+    }
+
+    /// Per-conversation media totals. Every linked file counts and adds its size; files that
+    /// no longer exist, and MEDIA events with nothing linked, count as missing with zero bytes.
+    pub fn tally_media(events: &[Event]) -> HashMap<String, ConversationMediaStats> {
+        let mut stats: HashMap<String, ConversationMediaStats> = HashMap::new();
+        for event in events {
+            let Some(cid) = &event.conversation_id else {
+                continue;
+            };
+            if event.media_references.is_empty() && event.event_type != "MEDIA" {
+                continue;
+            }
+            let entry = stats.entry(cid.clone()).or_default();
+            if event.media_references.is_empty() {
+                entry.media_count += 1;
+                entry.missing_media_count += 1;
+                continue;
+            }
+            for path in &event.media_references {
+                entry.media_count += 1;
+                match fs::metadata(path) {
+                    Ok(meta) if meta.is_file() => entry.media_bytes += meta.len() as i64,
+                    _ => entry.missing_media_count += 1,
+                }
+            }
+        }
+        stats
     }
 
     #[cfg(test)]
-    pub(crate) fn get_id_map(&self) -> &HashMap<String, PathBuf> {
+    pub(crate) fn get_id_map(&self) -> &HashMap<String, Vec<PathBuf>> {
         &self.id_map
     }
 
     /// Extract media_ids array from event metadata JSON string.
     /// Metadata format: {"media_ids": ["id1", "id2"], ...}
-    fn extract_media_ids(metadata: &Option<String>) -> Vec<String> {
+    pub fn extract_media_ids(metadata: &Option<String>) -> Vec<String> {
         let meta_str = match metadata {
             Some(s) => s,
             None => return Vec::new(),
@@ -149,9 +398,7 @@ impl MediaLinker {
         };
 
         match parsed.get("media_ids").and_then(|v| v.as_array()) {
-            Some(arr) => arr.iter()
-                .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                .collect(),
+            Some(arr) => arr.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect(),
             None => Vec::new(),
         }
     }
@@ -226,6 +473,22 @@ mod tests {
         assert_eq!(map.len(), 2);
     }
 
+    #[test]
+    fn test_scan_ignores_macos_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let real = dir.path().join("2023-01-01_ABC.jpg");
+        File::create(&real).unwrap().write_all(b"real image").unwrap();
+        File::create(dir.path().join("._2023-01-01_ABC.jpg")).unwrap();
+        File::create(dir.path().join(".DS_Store")).unwrap();
+        fs::create_dir(dir.path().join("__MACOSX")).unwrap();
+        File::create(dir.path().join("__MACOSX/2023-01-01_XYZ.jpg")).unwrap();
+
+        let linker = MediaLinker::new(dir.path());
+        let map = linker.get_id_map();
+        assert_eq!(map.len(), 1);
+        assert_eq!(map["ABC"], [fs::canonicalize(&real).unwrap()]);
+    }
+
     #[test]
     fn test_link_media_by_id() {
         let dir = tempfile::tempdir().unwrap();
@@ -241,6 +504,65 @@ mod tests {
         assert!(events[0].media_references[0].exists());
     }
 
+    #[test]
+    fn test_same_id_in_two_directories_prefers_the_original() {
+        let export = tempfile::tempdir().unwrap();
+        let chat_media = export.path().join("chat_media");
+        let media = export.path().join("media");
+        fs::create_dir_all(&chat_media).unwrap();
+        fs::create_dir_all(&media).unwrap();
+        File::create(chat_media.join("2023-01-01_ABC.jpg"))
+            .unwrap()
+            .write_all(&[0u8; 100])
+            .unwrap();
+        File::create(media.join("2023-01-01_ABC.jpg"))
+            .unwrap()
+            .write_all(&[0u8; 10])
+            .unwrap();
+        File::create(media.join("2023-01-01_ABC.png"))
+            .unwrap()
+            .write_all(&[0u8; 5])
+            .unwrap();
+        // Outside chat_media the larger copy wins
+        File::create(media.join("2023-01-01_BIG.mp4"))
+            .unwrap()
+            .write_all(&[0u8; 50])
+            .unwrap();
+        let other = tempfile::tempdir().unwrap();
+        File::create(other.path().join("2023-01-01_BIG.mp4"))
+            .unwrap()
+            .write_all(&[0u8; 5])
+            .unwrap();
+
+        let mut linker = MediaLinker::for_export(export.path());
+        linker.add_media_directory(other.path());
+        assert_eq!(linker.get_id_map()["ABC"].len(), 3);
+
+        let mut events = vec![
+            make_event("MEDIA", Some(r#"{"media_ids": ["ABC"]}"#.to_string()), vec![]),
+            make_event("MEDIA", Some(r#"{"media_ids": ["BIG"]}"#.to_string()), vec![]),
+        ];
+        linker.link_media(&mut events);
+
+        // The original, then the overlay that has another extension; the thumbnail is dropped
+        let canonical = |p: PathBuf| fs::canonicalize(p).unwrap();
+        assert_eq!(
+            events[0].media_references,
+            [
+                canonical(chat_media.join("2023-01-01_ABC.jpg")),
+                canonical(media.join("2023-01-01_ABC.png"))
+            ]
+        );
+        assert_eq!(
+            events[1].media_references,
+            [canonical(media.join("2023-01-01_BIG.mp4"))]
+        );
+        assert_eq!(
+            linker.path_for("ABC"),
+            Some(canonical(chat_media.join("2023-01-01_ABC.jpg")))
+        );
+    }
+
     #[test]
     fn test_link_skips_non_media_events() {
         let dir = tempfile::tempdir().unwrap();
@@ -254,4 +576,129 @@ mod tests {
         linker.link_media(&mut events);
         assert!(events[0].media_references.is_empty());
     }
+
+    /// A file last modified at `modified`.
+    fn write_dated(path: &Path, modified: DateTime<Utc>) {
+        let file = File::create(path).unwrap();
+        file.set_modified(modified.into()).unwrap();
+    }
+
+    #[test]
+    fn test_link_media_by_id_in_tilde_names() {
+        let dir = tempfile::tempdir().unwrap();
+        File::create(dir.path().join("media~ABC123.jpg")).unwrap();
+        File::create(dir.path().join("chat_media~DEF456.mp4")).unwrap();
+
+        let mut linker = MediaLinker::new(dir.path());
+        assert!(linker.get_id_map().contains_key("ABC123"));
+        assert!(linker.get_id_map().contains_key("DEF456"));
+        let mut events = vec![make_event(
+            "MEDIA",
+            Some(r#"{"media_ids": ["ABC123"]}"#.to_string()),
+            vec![],
+        )];
+        linker.link_media(&mut events);
+        assert!(events[0].media_references[0].ends_with("media~ABC123.jpg"));
+        assert_eq!((linker.linked_by_id(), linker.linked_by_date()), (1, 0));
+    }
+
+    #[test]
+    fn test_link_media_by_date_for_numeric_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let sent = Utc::now() - Duration::days(30);
+        write_dated(&dir.path().join("1001.jpg"), sent + Duration::minutes(2));
+        write_dated(&dir.path().join("1002.mp4"), sent + Duration::minutes(3));
+        write_dated(&dir.path().join("1003.jpg"), sent + Duration::minutes(40));
+        // Not a photo or video, and too far from any message
+        write_dated(&dir.path().join("1004.txt"), sent);
+        write_dated(&dir.path().join("1005.jpg"), sent + Duration::days(3));
+
+        let mut linker = MediaLinker::new(dir.path());
+        let mut events: Vec<Event> = [("SNAP_VIDEO", 0), ("MEDIA", 1), ("MEDIA", 2), ("MEDIA", 24 * 60)]
+            .into_iter()
+            .map(|(event_type, minutes)| Event {
+                timestamp: sent + Duration::minutes(minutes),
+                ..make_event(event_type, None, vec![])
+            })
+            .collect();
+        linker.link_media(&mut events);
+
+        let names: Vec<Option<&str>> = events
+            .iter()
+            .map(|e| e.media_references.first().and_then(|p| p.file_name()?.to_str()))
+            .collect();
+        // The video goes to the snap video; each file is linked once, nearest first
+        assert_eq!(names, [Some("1002.mp4"), Some("1001.jpg"), Some("1003.jpg"), None]);
+        assert_eq!((linker.linked_by_id(), linker.linked_by_date()), (0, 3));
+    }
+
+    #[test]
+    fn test_date_matching_leaves_files_named_by_a_media_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let sent = Utc::now() - Duration::days(30);
+        write_dated(&dir.path().join("media~ABC123.jpg"), sent);
+        write_dated(&dir.path().join("media~DEF456.jpg"), sent);
+        let dated = |event_type: &str, metadata: Option<&str>| Event {
+            timestamp: sent,
+            ..make_event(event_type, metadata.map(str::to_string), vec![])
+        };
+
+        let mut linker = MediaLinker::new(dir.path());
+        // A later conversation names DEF456, so an earlier one's date pass must not take it
+        linker.reserve_media_ids(&["DEF456".to_string()]);
+        // The message without an ID comes first, yet the file named by ID stays with its message
+        let mut first = vec![
+            dated("MEDIA", None),
+            dated("MEDIA", Some(r#"{"media_ids": ["ABC123"]}"#)),
+        ];
+        linker.link_media(&mut first);
+        assert!(first[0].media_references.is_empty());
+        assert!(first[1].media_references[0].ends_with("media~ABC123.jpg"));
+
+        let mut second = vec![dated("MEDIA", Some(r#"{"media_ids": ["DEF456"]}"#))];
+        linker.link_media(&mut second);
+        assert!(second[0].media_references[0].ends_with("media~DEF456.jpg"));
+        assert_eq!((linker.linked_by_id(), linker.linked_by_date()), (2, 0));
+    }
+
+    #[test]
+    fn test_custom_file_name_patterns() {
+        let dir = tempfile::tempdir().unwrap();
+        File::create(dir.path().join("IMG-XYZ.jpg")).unwrap();
+        let settings = MediaLinkingSettings {
+            filename_patterns: vec![r"^IMG-(\w+)$".to_string(), "(unclosed".to_string()],
+            date_window_minutes: 0,
+        };
+        let mut linker = MediaLinker::with_settings(&settings);
+        linker.add_media_directory(dir.path());
+        assert!(linker.get_id_map().contains_key("XYZ"));
+    }
+
+    #[test]
+    fn test_tally_media_counts_bytes_and_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("2023-01-01_A.jpg");
+        let b = dir.path().join("2023-01-01_B.mp4");
+        File::create(&a).unwrap().write_all(&[0u8; 1000]).unwrap();
+        File::create(&b).unwrap().write_all(&[0u8; 2500]).unwrap();
+        let gone = dir.path().join("2023-01-01_GONE.jpg");
+
+        let mut events = vec![
+            make_event("MEDIA", None, vec![a, b]),
+            make_event("MEDIA", None, vec![gone]),
+            make_event("TEXT", None, vec![]),
+        ];
+        events[2].conversation_id = Some("conv-2".to_string());
+
+        let stats = MediaLinker::tally_media(&events);
+        assert_eq!(
+            stats["conv-1"],
+            ConversationMediaStats {
+                media_count: 3,
+                media_bytes: 3500,
+                missing_media_count: 1,
+            }
+        );
+        assert!(!stats.contains_key("conv-2"));
+    }
 }
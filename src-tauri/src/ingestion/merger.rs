@@ -0,0 +1,367 @@
+//! Merging chat_history.json into the events parsed from the chat HTML. Both describe the same
+//! messages, but their clocks can round differently and several snaps can share a second, so
+//! events are matched on what they say rather than on time alone: same conversation, sender
+//! and normalized content (or event type when there is no text), in the same or a neighbouring
+//! minute, nearest timestamp first.
+
+use crate::models::{Conversation, Event};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+/// Furthest apart two copies of one message may be, in seconds.
+const MATCH_WINDOW_SECS: i64 = 60;
+
+/// What [`merge_json_events`] did.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MergeStats {
+    /// Chat page events that took the JSON copy's metadata (Media IDs, saved flag, ...).
+    pub enriched: usize,
+    /// JSON events with no chat page counterpart, added as events of their own.
+    pub added: usize,
+    /// Added events whose text matches a chat page message of the same sender outside the
+    /// match window, so probably the same message with a shifted clock.
+    pub suspected_duplicates: usize,
+}
+
+/// The part of an event both sources agree on: collapsed, lowercased text, or the event type
+/// for events without text.
+fn content_key(event: &Event) -> String {
+    match event.content.as_deref().map(str::trim).filter(|c| !c.is_empty()) {
+        Some(text) => text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase(),
+        None => format!("\u{1f}{}", event.event_type),
+    }
+}
+
+fn has_text(event: &Event) -> bool {
+    event.content.as_deref().is_some_and(|c| !c.trim().is_empty())
+}
+
+/// Add the JSON copy's metadata to a chat page event, keeping flags the HTML parse set.
+fn merge_metadata(existing: &mut Event, json_event: &Event) {
+    let parse =
+        |m: Option<&str>| m.and_then(|m| serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(m).ok());
+    existing.metadata = match (
+        parse(existing.metadata.as_deref()),
+        parse(json_event.metadata.as_deref()),
+    ) {
+        (Some(mut merged), Some(json)) => {
+            merged.extend(json);
+            Some(serde_json::Value::Object(merged).to_string())
+        }
+        _ => json_event.metadata.clone(),
+    };
+}
+
+/// What merging one JSON conversation produced.
+#[derive(Debug, Default)]
+pub struct ConversationMerge {
+    /// Positions of the chat page events the JSON enriched.
+    pub enriched: Vec<usize>,
+    /// JSON events with no chat page counterpart.
+    pub added: Vec<Event>,
+    /// The conversation, when only the JSON knows about it, named from the messages'
+    /// `conversation_title`.
+    pub conversation: Option<Conversation>,
+}
+
+/// Merges chat_history.json into the chat page events one conversation at a time, so only
+/// that conversation's events need to be at hand.
+#[derive(Debug, Default)]
+pub struct JsonMerger {
+    known_conversations: HashSet<String>,
+    stats: MergeStats,
+}
+
+fn minute(event: &Event) -> i64 {
+    event.timestamp.timestamp().div_euclid(60)
+}
+
+fn content_hash(event: &Event) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content_key(event).hash(&mut hasher);
+    hasher.finish()
+}
+
+impl JsonMerger {
+    /// `known_conversations` are the ones the chat pages already created.
+    pub fn new(known_conversations: HashSet<String>) -> Self {
+        Self {
+            known_conversations,
+            stats: MergeStats::default(),
+        }
+    }
+
+    /// Merge one JSON conversation into `events`, the chat page events it may repeat. Matched
+    /// events are enriched in place; the rest are returned as added.
+    pub fn merge_conversation(
+        &mut self,
+        events: &mut [Event],
+        convo_key: String,
+        json_events: Vec<Event>,
+    ) -> ConversationMerge {
+        // Chat page events by (sender, content hash) and minute
+        let mut index: HashMap<(&str, u64), HashMap<i64, Vec<usize>>> = HashMap::new();
+        // Chat page events without text by sender, for the nearest-time fallback
+        let mut untexted: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (idx, event) in events.iter().enumerate() {
+            if event.conversation_id.as_deref() != Some(convo_key.as_str()) {
+                continue;
+            }
+            index
+                .entry((event.sender.as_str(), content_hash(event)))
+                .or_default()
+                .entry(minute(event))
+                .or_default()
+                .push(idx);
+            if !has_text(event) {
+                untexted.entry(event.sender.as_str()).or_default().push(idx);
+            }
+        }
+
+        let mut matched: HashSet<usize> = HashSet::new();
+        let mut merge = ConversationMerge::default();
+        let mut enrichments = Vec::new();
+        for json_event in json_events {
+            let nearest = |candidates: &mut dyn Iterator<Item = usize>| {
+                candidates
+                    .filter(|idx| !matched.contains(idx))
+                    .map(|idx| (idx, (events[idx].timestamp - json_event.timestamp).num_seconds().abs()))
+                    .filter(|(_, gap)| *gap <= MATCH_WINDOW_SECS)
+                    .min_by_key(|(idx, gap)| (*gap, *idx))
+                    .map(|(idx, _)| idx)
+            };
+            let by_minute = index.get(&(json_event.sender.as_str(), content_hash(&json_event)));
+            let bucket = minute(&json_event);
+            let mut found = nearest(
+                &mut (bucket - 1..=bucket + 1)
+                    .filter_map(|m| by_minute.and_then(|b| b.get(&m)))
+                    .flatten()
+                    .copied(),
+            );
+            if found.is_none() && !has_text(&json_event) {
+                let candidates = untexted.get(json_event.sender.as_str());
+                found = nearest(&mut candidates.into_iter().flatten().copied());
+            }
+
+            if let Some(idx) = found {
+                matched.insert(idx);
+                enrichments.push((idx, json_event));
+                continue;
+            }
+
+            if has_text(&json_event) && by_minute.is_some() {
+                self.stats.suspected_duplicates += 1;
+            }
+            if self.known_conversations.insert(convo_key.clone()) {
+                let display_name = json_event.metadata.as_ref().and_then(|m| {
+                    serde_json::from_str::<serde_json::Value>(m)
+                        .ok()
+                        .and_then(|v| v.get("conversation_title")?.as_str().map(|s| s.to_string()))
+                });
+                merge.conversation = Some(Conversation {
+                    id: convo_key.clone(),
+                    display_name,
+                    participants: Vec::new(),
+                    last_event_at: Some(json_event.timestamp),
+                    message_count: 0,
+                    has_media: false,
+                    media_count: 0,
+                    media_bytes: 0,
+                    missing_media_count: 0,
+                    avatar_path: None,
+                    avatar_color: None,
+                    is_group: false,
+                    listed_members: 0,
+                    anomaly_flags: Vec::new(),
+                    saved_count: 0,
+                    shared_location_count: 0,
+                    completeness: None,
+                });
+            }
+            merge.added.push(json_event);
+        }
+
+        // The index borrows the events, so they are only changed once every copy is placed
+        for (idx, json_event) in enrichments {
+            merge_metadata(&mut events[idx], &json_event);
+            merge.enriched.push(idx);
+        }
+        self.stats.enriched += merge.enriched.len();
+        self.stats.added += merge.added.len();
+        merge
+    }
+
+    pub fn stats(&self) -> MergeStats {
+        self.stats
+    }
+}
+
+/// Merge `json_conversations` into `events` (the chat page events) in one go, appending the
+/// JSON events nothing matched. Returns the conversations only the JSON knew about, with the
+/// merge statistics.
+pub fn merge_json_events(
+    events: &mut Vec<Event>,
+    json_conversations: Vec<(String, Vec<Event>)>,
+    known_conversations: &HashSet<String>,
+) -> (Vec<Conversation>, MergeStats) {
+    let mut merger = JsonMerger::new(known_conversations.clone());
+    let mut new_conversations = Vec::new();
+    for (key, json_events) in json_conversations {
+        let merge = merger.merge_conversation(events, key, json_events);
+        new_conversations.extend(merge.conversation);
+        events.extend(merge.added);
+    }
+    (new_conversations, merger.stats())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, TimeZone, Utc};
+
+    fn at(h: u32, m: u32, s: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 6, 15, h, m, s).unwrap()
+    }
+
+    fn event(
+        id: &str,
+        timestamp: DateTime<Utc>,
+        event_type: &str,
+        content: Option<&str>,
+        metadata: Option<&str>,
+    ) -> Event {
+        Event {
+            id: id.to_string(),
+            timestamp,
+            sender: "alice".to_string(),
+            sender_name: None,
+            media_references: Vec::new(),
+            conversation_id: Some("alice".to_string()),
+            content: content.map(str::to_string),
+            event_type: event_type.to_string(),
+            metadata: metadata.map(str::to_string),
+        }
+    }
+
+    fn json(events: Vec<Event>) -> Vec<(String, Vec<Event>)> {
+        vec![("alice".to_string(), events)]
+    }
+
+    fn known() -> HashSet<String> {
+        HashSet::from(["alice".to_string()])
+    }
+
+    #[test]
+    fn test_matches_across_minute_rounding() {
+        // 3 seconds and a minute boundary apart: outside the old 2-second window
+        let mut events = vec![event("html", at(10, 0, 59), "TEXT", Some("See  you soon"), None)];
+        let copy = event(
+            "json",
+            at(10, 1, 2),
+            "TEXT",
+            Some("see you soon"),
+            Some(r#"{"saved":true}"#),
+        );
+        let (conversations, stats) = merge_json_events(&mut events, json(vec![copy]), &known());
+        assert!(conversations.is_empty());
+        assert_eq!(
+            stats,
+            MergeStats {
+                enriched: 1,
+                added: 0,
+                suspected_duplicates: 0
+            }
+        );
+        assert_eq!(events.len(), 1);
+        assert!(events[0].is_saved());
+    }
+
+    #[test]
+    fn test_snaps_in_the_same_second_match_one_to_one() {
+        let mut events = vec![
+            event("html_1", at(9, 0, 0), "SNAP", None, None),
+            event("html_2", at(9, 0, 0), "SNAP", None, None),
+        ];
+        let copies = vec![
+            event("json_1", at(9, 0, 0), "SNAP", None, Some(r#"{"media_ids":["a"]}"#)),
+            event("json_2", at(9, 0, 1), "SNAP", None, Some(r#"{"media_ids":["b"]}"#)),
+        ];
+        let (_, stats) = merge_json_events(&mut events, json(copies), &known());
+        assert_eq!(
+            stats,
+            MergeStats {
+                enriched: 2,
+                added: 0,
+                suspected_duplicates: 0
+            }
+        );
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|e| e.metadata.is_some()));
+        assert_ne!(events[0].metadata, events[1].metadata);
+    }
+
+    #[test]
+    fn test_different_text_in_the_same_second_is_not_merged() {
+        // The old heuristic enriched the first message with the second one's metadata
+        let mut events = vec![event("html", at(12, 0, 0), "TEXT", Some("hi"), None)];
+        let other = event("json", at(12, 0, 1), "TEXT", Some("are you there?"), Some("{}"));
+        let (_, stats) = merge_json_events(&mut events, json(vec![other]), &known());
+        assert_eq!(
+            stats,
+            MergeStats {
+                enriched: 0,
+                added: 1,
+                suspected_duplicates: 0
+            }
+        );
+        assert!(events[0].metadata.is_none());
+        assert_eq!(events.len(), 2);
+    }
+
+    #[test]
+    fn test_media_falls_back_to_nearest_time_and_keeps_html_flags() {
+        let mut events = vec![
+            event("far", at(8, 0, 0), "MEDIA", None, None),
+            event("near", at(8, 0, 40), "MEDIA", None, Some(r#"{"bidi_removed":true}"#)),
+        ];
+        // Typed differently by the JSON, so only the fallback can place it
+        let copy = event("json", at(8, 0, 50), "STICKER", None, Some(r#"{"media_ids":["x"]}"#));
+        let (_, stats) = merge_json_events(&mut events, json(vec![copy]), &known());
+        assert_eq!(stats.enriched, 1);
+        let near: serde_json::Value = serde_json::from_str(events[1].metadata.as_deref().unwrap()).unwrap();
+        assert_eq!(near["media_ids"][0], "x");
+        assert_eq!(near["bidi_removed"], true);
+        assert!(events[0].metadata.is_none());
+    }
+
+    #[test]
+    fn test_unmatched_copies_are_added_and_flagged() {
+        let mut events = vec![event("html", at(10, 0, 0), "TEXT", Some("lunch?"), None)];
+        let shifted = event("json", at(11, 0, 0), "TEXT", Some("lunch?"), None);
+        let mut elsewhere = event(
+            "bob",
+            at(10, 0, 0),
+            "TEXT",
+            Some("hey"),
+            Some(r#"{"conversation_title":"Bob"}"#),
+        );
+        elsewhere.conversation_id = Some("bob".to_string());
+        let sources = vec![
+            ("alice".to_string(), vec![shifted]),
+            ("bob".to_string(), vec![elsewhere]),
+        ];
+        let (conversations, stats) = merge_json_events(&mut events, sources, &known());
+        assert_eq!(
+            stats,
+            MergeStats {
+                enriched: 0,
+                added: 2,
+                suspected_duplicates: 1
+            }
+        );
+        assert_eq!(conversations.len(), 1);
+        assert_eq!(conversations[0].display_name.as_deref(), Some("Bob"));
+        assert_eq!(events.len(), 3);
+    }
+}
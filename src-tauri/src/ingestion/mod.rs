@@ -1,4 +1,194 @@
+pub mod anomalies;
+pub mod artifacts;
+pub mod avatars;
 pub mod detector;
-pub mod parser;
-pub mod media_linker;
 pub mod extractor;
+pub mod layout;
+pub mod locations;
+pub mod media_linker;
+pub mod merger;
+pub mod parser;
+pub mod pipeline;
+pub mod preview;
+pub mod sanitize;
+pub mod subpage_stream;
+pub mod timestamps;
+pub mod writer;
+
+use crate::models::{Conversation, Event};
+use std::path::Path;
+
+/// Conversation ids starting with this hold an export's events that belong to no chat.
+pub const UNSORTED_CONVERSATION_PREFIX: &str = "__unsorted__";
+
+/// Display name of the synthetic conversation for events without one.
+pub const UNSORTED_CONVERSATION_NAME: &str = "Unsorted messages";
+
+/// Conversation ids starting with this hold the stories an export's owner posted.
+pub const STORIES_CONVERSATION_PREFIX: &str = "__stories__";
+
+/// Display name of the synthetic conversation for the owner's stories.
+pub const STORIES_CONVERSATION_NAME: &str = "My Stories";
+
+/// Metadata key holding the original text of a timestamp that could not be read back.
+pub const RAW_TIMESTAMP_KEY: &str = "raw_timestamp";
+
+/// Upper bound on default parse threads; each in-flight subpage holds a whole DOM.
+const DEFAULT_MAX_PARSE_THREADS: usize = 4;
+
+/// Threads for parsing chat subpages: the `parse_threads` setting when it is a positive
+/// number (capped at the core count), otherwise min(cores, 4).
+pub fn parse_thread_count(setting: Option<&str>) -> usize {
+    let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    match setting.and_then(|s| s.trim().parse::<usize>().ok()).filter(|n| *n > 0) {
+        Some(n) => n.min(cores),
+        None => cores.min(DEFAULT_MAX_PARSE_THREADS),
+    }
+}
+
+/// True for macOS archive clutter: anything under `__MACOSX/`, AppleDouble `._*` resource
+/// forks, and `.DS_Store`. These are never real export data.
+pub fn is_os_metadata(path: &Path) -> bool {
+    path.components().any(|c| {
+        let name = c.as_os_str().to_string_lossy();
+        name == "__MACOSX" || name == ".DS_Store" || name.starts_with("._")
+    })
+}
+
+/// Add the original timestamp text to an event's JSON metadata. Metadata that isn't a JSON
+/// object is kept under `metadata`.
+pub fn keep_raw_timestamp(metadata: Option<String>, raw: &str) -> Option<String> {
+    let mut object = match metadata.as_deref().map(serde_json::from_str::<serde_json::Value>) {
+        Some(Ok(serde_json::Value::Object(map))) => map,
+        None => serde_json::Map::new(),
+        Some(_) => {
+            let mut map = serde_json::Map::new();
+            map.insert("metadata".into(), metadata.clone().into());
+            map
+        }
+    };
+    object.insert(RAW_TIMESTAMP_KEY.into(), raw.into());
+    Some(serde_json::Value::Object(object).to_string())
+}
+
+pub fn unsorted_conversation_id(export_id: &str) -> String {
+    format!("{}{}", UNSORTED_CONVERSATION_PREFIX, export_id)
+}
+
+pub fn stories_conversation_id(export_id: &str) -> String {
+    format!("{}{}", STORIES_CONVERSATION_PREFIX, export_id)
+}
+
+/// The export's "My Stories" conversation for events from `story_history.json`.
+pub fn stories_conversation(export_id: &str, events: &[Event]) -> Conversation {
+    Conversation {
+        id: stories_conversation_id(export_id),
+        display_name: Some(STORIES_CONVERSATION_NAME.to_string()),
+        participants: Vec::new(),
+        last_event_at: events.iter().map(|e| e.timestamp).max(),
+        message_count: events.len().min(i32::MAX as usize) as i32,
+        has_media: false,
+        media_count: 0,
+        media_bytes: 0,
+        missing_media_count: 0,
+        avatar_path: None,
+        avatar_color: None,
+        is_group: false,
+        listed_members: 0,
+        anomaly_flags: Vec::new(),
+        saved_count: 0,
+        shared_location_count: 0,
+        completeness: None,
+    }
+}
+
+/// Move events that no parser could place in a conversation into the export's "Unsorted"
+/// conversation, so lists, paging, search and exports reach them like any other chat.
+/// Returns that conversation when at least one event needed it.
+pub fn assign_orphan_events(events: &mut [Event], export_id: &str) -> Option<Conversation> {
+    let id = unsorted_conversation_id(export_id);
+    let mut participants: Vec<String> = Vec::new();
+    let mut count = 0;
+    let mut last_event_at = None;
+    for event in events.iter_mut().filter(|e| e.conversation_id.is_none()) {
+        event.conversation_id = Some(id.clone());
+        if !participants.contains(&event.sender) {
+            participants.push(event.sender.clone());
+        }
+        count += 1;
+        last_event_at = last_event_at.max(Some(event.timestamp));
+    }
+    if count == 0 {
+        return None;
+    }
+    log::info!("{} event(s) without a conversation placed in {}", count, id);
+    Some(Conversation {
+        id,
+        display_name: Some(UNSORTED_CONVERSATION_NAME.to_string()),
+        participants,
+        last_event_at,
+        message_count: count,
+        has_media: false,
+        media_count: 0,
+        media_bytes: 0,
+        missing_media_count: 0,
+        avatar_path: None,
+        avatar_color: None,
+        is_group: false,
+        listed_members: 0,
+        anomaly_flags: Vec::new(),
+        saved_count: 0,
+        shared_location_count: 0,
+        completeness: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_thread_count() {
+        let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        assert_eq!(parse_thread_count(None), cores.min(4));
+        assert_eq!(parse_thread_count(Some("0")), cores.min(4));
+        assert_eq!(parse_thread_count(Some("lots")), cores.min(4));
+        assert_eq!(parse_thread_count(Some("1")), 1);
+        assert_eq!(parse_thread_count(Some("100000")), cores);
+    }
+
+    #[test]
+    fn test_orphan_events_reachable_through_unsorted_conversation() {
+        let db = crate::test_fixtures::standard_db();
+        let mut events = crate::test_fixtures::events();
+        events.truncate(3);
+        for (i, event) in events.iter_mut().enumerate() {
+            event.id = format!("orphan_{}", i);
+            event.conversation_id = None;
+            event.content = Some("lost and found".to_string());
+        }
+        events[0].conversation_id = Some("alice".to_string());
+
+        let unsorted = assign_orphan_events(&mut events, "fixture_export").unwrap();
+        assert_eq!(unsorted.id, "__unsorted__fixture_export");
+        assert_eq!(unsorted.message_count, 2);
+        assert!(assign_orphan_events(&mut events, "fixture_export").is_none());
+
+        db.batch_insert_conversations(std::slice::from_ref(&unsorted)).unwrap();
+        db.batch_insert_events(&events, "fixture_export").unwrap();
+        db.populate_fts_for_export("fixture_export", || false, |_| {}).unwrap();
+        let listed = db.get_conversations(None).unwrap();
+        let found = listed.iter().find(|c| c.id == unsorted.id).unwrap();
+        assert_eq!(found.display_name.as_deref(), Some(UNSORTED_CONVERSATION_NAME));
+        assert_eq!(found.message_count, 2);
+        let page = db.get_messages_page(&unsorted.id, 0, 50, false, None).unwrap();
+        assert_eq!(page.messages.len(), 2);
+        let hits = db.search_messages("lost and found", 50, false, None).unwrap();
+        assert_eq!(
+            hits.iter()
+                .filter(|h| h.conversation_id.as_deref() == Some(unsorted.id.as_str()))
+                .count(),
+            2
+        );
+    }
+}
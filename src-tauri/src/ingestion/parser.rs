@@ -1,45 +1,273 @@
-use crate::error::AppResult;
-use crate::models::{Conversation, Event, Memory, Person};
+use crate::error::{AppError, AppResult};
+use crate::ingestion::sanitize::{clean_event, clean_name};
+use crate::ingestion::{keep_raw_timestamp, locations, subpage_stream};
+use crate::models::{
+    Conversation, Event, FriendsSchema, FriendsSchemaCount, JsonFileIssue, JsonFileProblem, LocationPoint, Memory,
+    Person, TimestampFormatHint,
+};
 use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
 use kuchikiki::traits::*;
+use regex::Regex;
+use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
 use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
-use std::io::BufReader;
+use std::io::{BufReader, Read};
+use std::ops::ControlFlow;
 use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
 use uuid::Uuid;
 
+/// `<id>_<n>` with a part number of 2 or more, as used for chat continuation files.
+static CONTINUATION_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(.+)_([2-9]|[1-9]\d+)$").unwrap());
+
+/// Members named in an unnamed group's display name before the rest become "+N".
+pub const GROUP_NAME_LIMIT: usize = 3;
+
+/// Event types the rest of the app knows how to render.
+pub const KNOWN_EVENT_TYPES: &[&str] = &[
+    "TEXT",
+    "MEDIA",
+    "MISSED_VIDEO_CHAT",
+    "MISSED_AUDIO_CHAT",
+    "STATUSPARTICIPANTREMOVED",
+    "NOTE",
+    "SNAP",
+    "STICKER",
+    "SHARE",
+    "STATUSPARTICIPANTADDED",
+    "STATUSCONVERSATIONNAMECHANGED",
+];
+
+/// Namespace of the name-based UUIDs [`assign_stable_ids`] gives events.
+const EVENT_ID_NAMESPACE: Uuid = Uuid::from_u128(0x6f1d_2c4e_93a8_4b57_a0e2_5c8d_71f4_b3a9);
+
+/// Replace the ids of one conversation's parsed events with ones derived from the event itself
+/// (conversation, second, sender, content and type), so importing the same export again
+/// rewrites the same rows. Identical events within a second are told apart by their order.
+pub fn assign_stable_ids(events: &mut [Event]) {
+    let mut seen: HashMap<String, u32> = HashMap::new();
+    for event in events {
+        let key = format!(
+            "{}\u{1f}{}\u{1f}{}\u{1f}{}\u{1f}{}",
+            event.conversation_id.as_deref().unwrap_or_default(),
+            event.timestamp.timestamp(),
+            event.sender,
+            event.content.as_deref().unwrap_or_default(),
+            event.event_type
+        );
+        let occurrence = seen.entry(key.clone()).or_default();
+        let name = match *occurrence {
+            0 => key,
+            n => format!("{}\u{1f}{}", key, n),
+        };
+        *occurrence += 1;
+        event.id = Uuid::new_v5(&EVENT_ID_NAMESPACE, name.as_bytes()).to_string();
+    }
+}
+
+/// Diagnostics collected while parsing that the ingestion pipeline ignores but previews report.
+#[derive(Debug, Default, Clone)]
+pub struct ParseDiagnostics {
+    /// Messages whose timestamp could not be parsed, kept at the epoch.
+    pub timestamp_failures: usize,
+    /// Event types not in [`KNOWN_EVENT_TYPES`].
+    pub unknown_event_types: BTreeSet<String>,
+    /// Media IDs seen in chat_history.json metadata.
+    pub media_ids: usize,
+}
+
+impl ParseDiagnostics {
+    pub(crate) fn note_event_type(&mut self, event_type: &str) {
+        if !KNOWN_EVENT_TYPES.contains(&event_type) {
+            self.unknown_event_types.insert(event_type.to_string());
+        }
+    }
+}
+
 pub struct ChatParser;
 
+/// chat_history.json message fields the parser reads. Anything else is kept under `extra`.
+/// "Created(microseconds)" repeats "Created" and would only bloat every message.
+const KNOWN_MESSAGE_KEYS: &[&str] = &[
+    "From",
+    "Media Type",
+    "Created",
+    "Created(microseconds)",
+    "Content",
+    "Conversation Title",
+    "IsSender",
+    "IsSaved",
+    "Saved",
+    "Media IDs",
+];
+
+/// Most bytes of unrecognized fields kept in one message's `extra` metadata.
+pub const EXTRA_METADATA_LIMIT: usize = 1024;
+
+/// Subpages larger than this are parsed with the streaming tokenizer instead of a full DOM.
+pub const STREAMING_SUBPAGE_THRESHOLD: u64 = 32 * 1024 * 1024;
+
 impl ChatParser {
-    pub fn parse_subpage(path: &Path) -> AppResult<(Conversation, Vec<Event>)> {
-        log::debug!("parse_subpage: parsing {:?}", path);
-        
+    pub fn parse_subpage(path: &Path, hint: TimestampFormatHint) -> AppResult<(Conversation, Vec<Event>)> {
         let mut file = fs::File::open(path)?;
-        let document = kuchikiki::parse_html().from_utf8().read_from(&mut file)?;
+        let size = file.metadata()?.len();
+        let conversation_id = Self::conversation_id_from_path(path);
+        let streaming = size > STREAMING_SUBPAGE_THRESHOLD;
+        if streaming {
+            log::info!(
+                "parse_subpage: {:?} is {} MB, using streaming parser",
+                path.file_name(),
+                size / (1024 * 1024)
+            );
+        }
+
+        let (conversation, events, _) = if streaming {
+            Self::parse_subpage_streaming(&mut BufReader::new(file), &conversation_id, hint)?
+        } else {
+            Self::parse_subpage_reader(&mut file, &conversation_id, hint)?
+        };
+
+        log::debug!(
+            "parse_subpage: {:?} ({} parser) -> {} events, display_name={:?}",
+            path.file_name(),
+            if streaming { "streaming" } else { "DOM" },
+            events.len(),
+            conversation.display_name
+        );
+        Ok((conversation, events))
+    }
+
+    /// Derive the conversation id from a `subpage_<id>.html` file name. Continuation files of a
+    /// long chat (`subpage_<id>_2.html`, `subpage_<id>_3.html`, ...) map to the same id.
+    pub fn conversation_id_from_path(path: &Path) -> String {
+        Self::split_continuation(&Self::file_id_from_path(path)).0.to_string()
+    }
 
-        let conversation_id = path
-            .file_stem()
+    /// The id part of a subpage file name, continuation suffix included.
+    fn file_id_from_path(path: &Path) -> String {
+        path.file_stem()
             .and_then(|s| s.to_str())
             .unwrap_or("unknown")
-            .replace("subpage_", "");
-
-        let mut conversation = Conversation {
-            id: conversation_id.clone(),
-            display_name: None,
-            participants: Vec::new(),
-            last_event_at: None,
-            message_count: 0,
-            has_media: false,
-        };
+            .replace("subpage_", "")
+    }
+
+    /// Split `<id>_<n>` into the id and the part number (2 or more); other ids are part 1.
+    pub fn split_continuation(file_id: &str) -> (&str, u32) {
+        match CONTINUATION_RE.captures(file_id) {
+            Some(caps) => match caps[2].parse::<u32>() {
+                Ok(part) => (caps.get(1).map_or(file_id, |m| m.as_str()), part),
+                Err(_) => (file_id, 1),
+            },
+            None => (file_id, 1),
+        }
+    }
+
+    /// Combine parsed subpages so each conversation appears once, with the events of all its
+    /// continuation files in timestamp order and participants, count and last activity
+    /// recomputed over the whole chat.
+    ///
+    /// Usernames can legitimately end in `_2`, so a "continuation" is only merged when the
+    /// first part exists and shows the same chat title; otherwise it keeps its full file id.
+    pub fn merge_subpage_parts(parts: Vec<(PathBuf, Conversation, Vec<Event>)>) -> Vec<(Conversation, Vec<Event>)> {
+        // Part number, file id, and what was parsed from the file
+        type Part = (u32, String, Conversation, Vec<Event>);
+        let mut groups: BTreeMap<String, Vec<Part>> = BTreeMap::new();
+        for (path, conversation, events) in parts {
+            let file_id = Self::file_id_from_path(&path);
+            let part = Self::split_continuation(&file_id).1;
+            groups
+                .entry(conversation.id.clone())
+                .or_default()
+                .push((part, file_id, conversation, events));
+        }
 
-        if let Ok(h1) = document.document_node.select_first("h1") {
-            let text = h1.text_contents();
-            if text.contains("Chat History with ") {
-                conversation.display_name = Some(text.replace("Chat History with ", "").trim().to_string());
-            } else if text.contains("Group Chat") || text.contains("group") {
-                conversation.display_name = Some(text.trim().to_string());
+        let mut merged = Vec::new();
+        for (id, mut group) in groups {
+            group.sort_by_key(|(part, ..)| *part);
+            let has_first_part = group[0].0 == 1;
+            let title = group[0].2.display_name.clone();
+
+            let mut display_name = None;
+            let mut participants: Vec<String> = Vec::new();
+            let mut is_group = false;
+            let mut listed_members = 0;
+            let mut events = Vec::new();
+            let mut continued = 0;
+            for (part, file_id, conversation, mut part_events) in group {
+                let conflicting_title = matches!((&title, &conversation.display_name), (Some(a), Some(b)) if a != b);
+                if part > 1 && (!has_first_part || conflicting_title) {
+                    for event in &mut part_events {
+                        event.conversation_id = Some(file_id.clone());
+                    }
+                    assign_stable_ids(&mut part_events);
+                    let standalone = Self::build_conversation(&file_id, None, &part_events);
+                    merged.push((
+                        Conversation {
+                            display_name: conversation.display_name,
+                            ..standalone
+                        },
+                        part_events,
+                    ));
+                    continue;
+                }
+                if part > 1 {
+                    continued += 1;
+                }
+                display_name = display_name.or(conversation.display_name);
+                is_group |= conversation.is_group;
+                if part == 1 {
+                    // The first part's participants lead, listed members first
+                    listed_members = conversation.listed_members;
+                }
+                for participant in conversation.participants {
+                    if !participants.contains(&participant) {
+                        participants.push(participant);
+                    }
+                }
+                events.append(&mut part_events);
+            }
+            if !has_first_part {
+                continue;
             }
+            if continued > 0 {
+                log::debug!(
+                    "merge_subpage_parts: {} continuation file(s) merged into {}",
+                    continued,
+                    id
+                );
+                events.sort_by_key(|e| e.timestamp);
+            }
+            assign_stable_ids(&mut events);
+            let conversation = Self::build_conversation(&id, None, &events);
+            merged.push((
+                Conversation {
+                    display_name,
+                    participants,
+                    is_group,
+                    listed_members,
+                    ..conversation
+                },
+                events,
+            ));
         }
+        merged
+    }
+
+    /// Parse a chat subpage from any reader (a file, or an entry inside a zip).
+    pub fn parse_subpage_reader<R: Read>(
+        reader: &mut R,
+        conversation_id: &str,
+        hint: TimestampFormatHint,
+    ) -> AppResult<(Conversation, Vec<Event>, ParseDiagnostics)> {
+        let document = kuchikiki::parse_html().from_utf8().read_from(reader)?;
+        let mut diagnostics = ParseDiagnostics::default();
+
+        let heading = document
+            .document_node
+            .select_first("h1")
+            .ok()
+            .map(|h1| h1.text_contents());
 
         let mut events = Vec::new();
 
@@ -47,7 +275,9 @@ impl ChatParser {
             for message_div in right_panel.as_node().children() {
                 if let Some(element) = message_div.as_element() {
                     if element.name.local.as_ref() == "div" {
-                        if let Some(event) = Self::parse_message_node(&message_div, &conversation_id) {
+                        if let Some(event) =
+                            Self::parse_message_node(&message_div, conversation_id, hint, &mut diagnostics)
+                        {
                             events.push(event);
                         }
                     }
@@ -55,29 +285,147 @@ impl ChatParser {
             }
         }
 
-        let mut participants = Vec::new();
-        for event in &events {
+        // Events own their data; free the DOM before doing anything else with them
+        drop(document);
+        let conversation = Self::build_conversation(conversation_id, heading.as_deref(), &events);
+        Ok((conversation, events, diagnostics))
+    }
+
+    /// Parse a chat subpage without building a DOM, keeping memory flat for very large files.
+    /// Produces the same events as [`Self::parse_subpage_reader`].
+    pub fn parse_subpage_streaming<R: Read>(
+        reader: &mut R,
+        conversation_id: &str,
+        hint: TimestampFormatHint,
+    ) -> AppResult<(Conversation, Vec<Event>, ParseDiagnostics)> {
+        let parsed = subpage_stream::parse_subpage_stream(reader, conversation_id, hint)?;
+        let conversation = Self::build_conversation(conversation_id, parsed.heading.as_deref(), &parsed.events);
+        Ok((conversation, parsed.events, parsed.diagnostics))
+    }
+
+    fn build_conversation(conversation_id: &str, heading: Option<&str>, events: &[Event]) -> Conversation {
+        // Unnamed groups list their members: "Chat History with alice, bob, carol"
+        let listed = heading.and_then(Self::heading_names).filter(|names| names.len() > 1);
+        let (display_name, is_group) = match (&listed, heading) {
+            (Some(names), _) => (Some(Self::group_display_name(names)), true),
+            (None, Some(text)) if text.contains("Chat History with ") => {
+                (Some(text.replace("Chat History with ", "").trim().to_string()), false)
+            }
+            (None, Some(text)) if text.contains("Group Chat") || text.contains("group") => {
+                (Some(text.trim().to_string()), true)
+            }
+            _ => (None, false),
+        };
+
+        // Listed members first, so those who never wrote anything are still participants
+        let listed_members = listed.as_ref().map_or(0, Vec::len);
+        let mut participants = listed.unwrap_or_default();
+        for event in events {
             if !participants.contains(&event.sender) {
                 participants.push(event.sender.clone());
             }
         }
-        conversation.participants = participants;
-        conversation.message_count = events.len() as i32;
-        conversation.last_event_at = events.last().map(|e| e.timestamp);
 
-        log::debug!(
-            "parse_subpage: {:?} -> {} events, display_name={:?}",
-            path.file_name(),
-            events.len(),
-            conversation.display_name
-        );
-        Ok((conversation, events))
+        Conversation {
+            id: conversation_id.to_string(),
+            display_name,
+            participants,
+            is_group,
+            listed_members,
+            last_event_at: events.last().map(|e| e.timestamp),
+            message_count: events.len() as i32,
+            has_media: false,
+            media_count: 0,
+            media_bytes: 0,
+            missing_media_count: 0,
+            avatar_path: None,
+            avatar_color: None,
+            anomaly_flags: Vec::new(),
+            saved_count: 0,
+            shared_location_count: 0,
+            completeness: None,
+        }
     }
 
-    fn parse_message_node(node: &kuchikiki::NodeRef, conversation_id: &str) -> Option<Event> {
+    /// The names after "Chat History with", split on commas.
+    fn heading_names(heading: &str) -> Option<Vec<String>> {
+        let (_, names) = heading.split_once("Chat History with ")?;
+        let names: Vec<String> = names
+            .split(',')
+            .map(|n| n.trim().to_string())
+            .filter(|n| !n.is_empty())
+            .collect();
+        (!names.is_empty()).then_some(names)
+    }
+
+    /// "Alice, Bob, Carol +2": the first few names, then how many more there are.
+    pub fn group_display_name(names: &[String]) -> String {
+        let shown = names[..names.len().min(GROUP_NAME_LIMIT)].join(", ");
+        match names.len().saturating_sub(GROUP_NAME_LIMIT) {
+            0 => shown,
+            more => format!("{} +{}", shown, more),
+        }
+    }
+
+    /// How many leading participants an unnamed group's header listed, if it listed them.
+    fn listed_member_count(conversation: &Conversation) -> Option<usize> {
+        let count = conversation.listed_members.min(conversation.participants.len());
+        (conversation.is_group && count > 1).then_some(count)
+    }
+
+    /// Map the members listed in unnamed group headers to known people, by username or display
+    /// name, and rename those groups after the people they resolved to.
+    pub fn resolve_group_members(conversations: &mut [Conversation], people: &[Person]) {
+        let find = |name: &str| {
+            people
+                .iter()
+                .find(|p| p.username.eq_ignore_ascii_case(name))
+                .or_else(|| {
+                    people
+                        .iter()
+                        .find(|p| p.display_name.as_deref().is_some_and(|d| d.eq_ignore_ascii_case(name)))
+                })
+        };
+        for conversation in conversations.iter_mut() {
+            let Some(count) = Self::listed_member_count(conversation) else {
+                continue;
+            };
+            let mut participants: Vec<String> = Vec::with_capacity(conversation.participants.len());
+            let mut names = Vec::with_capacity(count);
+            for listed in &conversation.participants[..count] {
+                let (username, name) = match find(listed) {
+                    Some(person) => (
+                        person.username.clone(),
+                        person.display_name.clone().unwrap_or_else(|| person.username.clone()),
+                    ),
+                    None => (listed.clone(), listed.clone()),
+                };
+                if !participants.contains(&username) {
+                    participants.push(username);
+                    names.push(name);
+                }
+            }
+            for sender in conversation.participants.drain(count..) {
+                if !participants.contains(&sender) {
+                    participants.push(sender);
+                }
+            }
+            conversation.participants = participants;
+            conversation.listed_members = names.len();
+            conversation.display_name = Some(Self::group_display_name(&names));
+        }
+    }
+
+    fn parse_message_node(
+        node: &kuchikiki::NodeRef,
+        conversation_id: &str,
+        hint: TimestampFormatHint,
+        diagnostics: &mut ParseDiagnostics,
+    ) -> Option<Event> {
         let sender = node.select_first("h4").ok()?.text_contents().trim().to_string();
 
         let event_type = Self::detect_event_type(node);
+        diagnostics.note_event_type(&event_type);
 
         let content = node
             .select_first("p")
@@ -85,14 +433,13 @@ impl ChatParser {
             .map(|p| p.text_contents().trim().to_string());
 
         let timestamp_text = node.select_first("h6").ok()?.text_contents();
-        let timestamp = Self::try_parse_timestamp(&timestamp_text)?;
 
         let mut media_references = Vec::new();
         Self::extract_all_media_references(node, &mut media_references);
 
-        Some(Event {
+        let event = Event {
             id: Uuid::new_v4().to_string(),
-            timestamp,
+            timestamp: DateTime::<Utc>::UNIX_EPOCH,
             sender,
             sender_name: None,
             media_references,
@@ -100,7 +447,33 @@ impl ChatParser {
             content,
             event_type,
             metadata: None,
-        })
+        };
+        Some(clean_event(Self::dated_message(
+            event,
+            &timestamp_text,
+            hint,
+            diagnostics,
+        )))
+    }
+
+    /// Date a message by its timestamp text. A message whose timestamp can't be read is kept
+    /// rather than dropped: it is counted in `diagnostics`, dated at the epoch and keeps the
+    /// original text under [`super::RAW_TIMESTAMP_KEY`], so completeness and anomaly checks see it.
+    pub(crate) fn dated_message(
+        mut event: Event,
+        text: &str,
+        hint: TimestampFormatHint,
+        diagnostics: &mut ParseDiagnostics,
+    ) -> Event {
+        match Self::try_parse_timestamp_with(text, hint) {
+            Some(timestamp) => event.timestamp = timestamp,
+            None => {
+                diagnostics.timestamp_failures += 1;
+                event.timestamp = DateTime::<Utc>::UNIX_EPOCH;
+                event.metadata = keep_raw_timestamp(event.metadata.take(), text.trim());
+            }
+        }
+        event
     }
 
     fn detect_event_type(node: &kuchikiki::NodeRef) -> String {
@@ -108,21 +481,8 @@ impl ChatParser {
             for span in spans {
                 let text = span.text_contents();
                 let trimmed = text.trim();
-                match trimmed {
-                    "TEXT"
-                    | "MEDIA"
-                    | "MISSED_VIDEO_CHAT"
-                    | "MISSED_AUDIO_CHAT"
-                    | "STATUSPARTICIPANTREMOVED"
-                    | "NOTE"
-                    | "SNAP"
-                    | "STICKER"
-                    | "SHARE"
-                    | "STATUSPARTICIPANTADDED"
-                    | "STATUSCONVERSATIONNAMECHANGED" => {
-                        return trimmed.to_string();
-                    }
-                    _ => {}
+                if KNOWN_EVENT_TYPES.contains(&trimmed) {
+                    return trimmed.to_string();
                 }
             }
         }
@@ -156,16 +516,7 @@ impl ChatParser {
         if let Ok(links) = node.select("a") {
             for link in links {
                 if let Some(href) = link.attributes.borrow().get("href") {
-                    let lower = href.to_lowercase();
-                    if lower.ends_with(".jpg")
-                        || lower.ends_with(".jpeg")
-                        || lower.ends_with(".png")
-                        || lower.ends_with(".mp4")
-                        || lower.ends_with(".mov")
-                        || lower.ends_with(".webp")
-                        || lower.ends_with(".heif")
-                        || lower.ends_with(".gif")
-                    {
+                    if Self::is_media_link(href) {
                         refs.push(PathBuf::from(href));
                     }
                 }
@@ -173,7 +524,21 @@ impl ChatParser {
         }
     }
 
+    /// Whether an `<a href>` points at a media file we should link.
+    pub(crate) fn is_media_link(href: &str) -> bool {
+        let lower = href.to_lowercase();
+        [".jpg", ".jpeg", ".png", ".mp4", ".mov", ".webp", ".heif", ".gif"]
+            .iter()
+            .any(|ext| lower.ends_with(ext))
+    }
+
+    /// Parse a timestamp, reading slash dates month-first.
     pub fn try_parse_timestamp(text: &str) -> Option<DateTime<Utc>> {
+        Self::try_parse_timestamp_with(text, TimestampFormatHint::default())
+    }
+
+    /// Parse a timestamp, reading slash dates in the order `hint` gives.
+    pub fn try_parse_timestamp_with(text: &str, hint: TimestampFormatHint) -> Option<DateTime<Utc>> {
         let text = text.trim().replace(" UTC", "");
         if let Ok(naive) = NaiveDateTime::parse_from_str(&text, "%Y-%m-%d %H:%M:%S") {
             return Some(Utc.from_utc_datetime(&naive));
@@ -181,7 +546,11 @@ impl ChatParser {
         if let Ok(naive) = NaiveDateTime::parse_from_str(&text, "%b %d, %Y %H:%M:%S") {
             return Some(Utc.from_utc_datetime(&naive));
         }
-        if let Ok(naive) = NaiveDateTime::parse_from_str(&text, "%m/%d/%Y %H:%M:%S") {
+        let slash_format = match hint {
+            TimestampFormatHint::MonthFirst => "%m/%d/%Y %H:%M:%S",
+            TimestampFormatHint::DayFirst => "%d/%m/%Y %H:%M:%S",
+        };
+        if let Ok(naive) = NaiveDateTime::parse_from_str(&text, slash_format) {
             return Some(Utc.from_utc_datetime(&naive));
         }
         None
@@ -190,57 +559,184 @@ impl ChatParser {
 
 pub struct PersonParser;
 
-impl PersonParser {
-    pub fn parse_friends_json(path: &Path) -> AppResult<Vec<Person>> {
-        let file = fs::File::open(path)?;
-        let json: Value = serde_json::from_reader(BufReader::new(file))?;
-        let mut people = Vec::new();
-
-        let categories = [
-            "Friends",
-            "Blocked Users",
-            "Deleted Friends",
-            "Hidden Friend Suggestions",
-        ];
-
-        for cat in categories {
-            if let Some(list) = json.get(cat).and_then(|v| v.as_array()) {
-                for entry in list {
-                    let username = entry.get("Username").and_then(|v| v.as_str()).unwrap_or("").to_string();
-                    let display_name = entry
-                        .get("Display Name")
-                        .and_then(|v| v.as_str())
-                        .filter(|s| !s.is_empty())
-                        .map(|s| s.to_string());
+/// Sections of friends.json holding people, under their original and their lowercase keys.
+const FRIEND_SECTIONS: &[(&str, &str)] = &[
+    ("Friends", "friends"),
+    ("Blocked Users", "blocked_users"),
+    ("Deleted Friends", "deleted_friends"),
+    ("Hidden Friend Suggestions", "hidden_friend_suggestions"),
+];
+
+/// Most top-level keys named when friends.json has no known section.
+const UNKNOWN_KEYS_LIMIT: usize = 20;
+
+/// People read from friends.json, and how many came from each key style.
+#[derive(Debug, Default)]
+pub struct FriendsList {
+    pub people: Vec<Person>,
+    pub by_schema: Vec<FriendsSchemaCount>,
+    /// The file's top-level keys, when none of them is a known section.
+    pub unknown_keys: Option<Vec<String>>,
+}
+
+impl FriendsList {
+    /// A warning for the import result when the file had no section this parser knows.
+    pub fn unrecognized_warning(&self) -> Option<String> {
+        let keys = self.unknown_keys.as_ref()?;
+        Some(if keys.is_empty() {
+            "friends.json has no sections; names can't be resolved".to_string()
+        } else {
+            format!(
+                "friends.json has none of the known sections, so names can't be resolved (found: {})",
+                keys.join(", ")
+            )
+        })
+    }
+}
 
-                    if !username.is_empty() {
-                        people.push(Person { username, display_name });
+fn entry_str<'a>(entry: &'a Value, keys: [&str; 2]) -> Option<&'a str> {
+    keys.iter().find_map(|key| entry.get(*key)?.as_str())
+}
+
+impl PersonParser {
+    /// Read every section of friends.json. Older exports capitalize section and field names
+    /// ("Friends", "Display Name"); newer ones use lowercase keys ("friends", "display_name").
+    /// Both are read, and a person listed under both is kept once.
+    pub fn parse_friends_json(path: &Path) -> AppResult<FriendsList> {
+        let json = read_json_file(path)?;
+        let mut list = FriendsList::default();
+        let mut seen = std::collections::HashSet::new();
+        let mut found_section = false;
+
+        for schema in [FriendsSchema::Classic, FriendsSchema::Lowercase] {
+            let mut count = 0;
+            for &(classic, lowercase) in FRIEND_SECTIONS {
+                let section = match schema {
+                    FriendsSchema::Classic => classic,
+                    FriendsSchema::Lowercase => lowercase,
+                };
+                let Some(entries) = json.get(section).and_then(|v| v.as_array()) else {
+                    continue;
+                };
+                found_section = true;
+                for entry in entries {
+                    let username = clean_name(entry_str(entry, ["Username", "username"]).unwrap_or(""));
+                    let display_name = entry_str(entry, ["Display Name", "display_name"])
+                        .map(clean_name)
+                        .filter(|s| !s.is_empty());
+                    if username.is_empty() || !seen.insert(username.clone()) {
+                        continue;
                     }
+                    count += 1;
+                    list.people.push(Person {
+                        username,
+                        display_name,
+                        avatar_path: None,
+                        avatar_color: None,
+                    });
                 }
             }
+            if count > 0 {
+                list.by_schema.push(FriendsSchemaCount { schema, people: count });
+            }
+        }
+
+        if !found_section {
+            let keys = json
+                .as_object()
+                .map(|o| o.keys().take(UNKNOWN_KEYS_LIMIT).cloned().collect())
+                .unwrap_or_default();
+            list.unknown_keys = Some(keys);
+        }
+        Ok(list)
+    }
+}
+
+pub struct AccountParser;
+
+/// Keys the owner's username is stored under in account.json and user_profile.json.
+const OWNER_USERNAME_KEYS: [&str; 3] = ["Username", "username", "User Name"];
+
+/// Keys the owner's display name is stored under.
+const OWNER_NAME_KEYS: [&str; 4] = ["Display Name", "display_name", "Name", "name"];
+
+/// Sections that describe the owner; other sections name devices, apps and so on.
+const OWNER_SECTIONS: [&str; 4] = ["Basic Information", "basic_information", "App Profile", "app_profile"];
+
+/// What an account file says about the owner. Either part may be missing.
+#[derive(Debug, Default, PartialEq)]
+pub struct AccountOwner {
+    pub username: Option<String>,
+    pub display_name: Option<String>,
+}
+
+impl AccountOwner {
+    /// Fill in whatever this one lacks from `other`.
+    pub fn or(self, other: AccountOwner) -> AccountOwner {
+        AccountOwner {
+            username: self.username.or(other.username),
+            display_name: self.display_name.or(other.display_name),
         }
+    }
+}
+
+impl AccountParser {
+    /// Read the owner from account.json or user_profile.json. The fields sit at the top level
+    /// or in a section such as "Basic Information", depending on the export's age.
+    pub fn parse_account_json(path: &Path) -> AppResult<AccountOwner> {
+        let json = read_json_file(path)?;
+        let sections = std::iter::once(&json).chain(OWNER_SECTIONS.iter().filter_map(|key| json.get(*key)));
+        let mut owner = AccountOwner::default();
+        for section in sections {
+            owner = owner.or(AccountOwner {
+                username: Self::field(section, &OWNER_USERNAME_KEYS),
+                display_name: Self::field(section, &OWNER_NAME_KEYS),
+            });
+        }
+        Ok(owner)
+    }
 
-        Ok(people)
+    fn field(section: &Value, keys: &[&str]) -> Option<String> {
+        keys.iter()
+            .find_map(|key| section.get(*key)?.as_str())
+            .map(clean_name)
+            .filter(|s| !s.is_empty())
     }
 }
 
 pub struct MemoryParser;
 
 impl MemoryParser {
+    /// Parse json/memories_history.json. Memories with a "Media ID" keep it as their id so a
+    /// reimport updates them in place. Overlay entries are attached to the memory they belong to
+    /// as `overlay_url` rather than becoming memories of their own.
     pub fn parse_memories_json(path: &Path, export_id: &str) -> AppResult<Vec<Memory>> {
-        let file = fs::File::open(path)?;
-        let json: Value = serde_json::from_reader(BufReader::new(file))?;
+        let json = read_json_file(path)?;
         let mut memories = Vec::new();
+        // Overlay links keyed by the Media ID or, without one, the Date of their memory
+        let mut overlays: Vec<(String, String)> = Vec::new();
 
         if let Some(saved_media) = json.get("Saved Media").and_then(|v| v.as_array()) {
             for entry in saved_media {
-                let date_str = entry.get("Date").and_then(|v| v.as_str()).unwrap_or("");
-                let media_type = entry
-                    .get("Media Type")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("Image")
-                    .to_string();
-                let location_str = entry.get("Location").and_then(|v| v.as_str()).unwrap_or("");
+                let text = |key: &str| {
+                    entry
+                        .get(key)
+                        .and_then(|v| v.as_str())
+                        .map(str::trim)
+                        .filter(|s| !s.is_empty())
+                };
+                let date_str = text("Date").unwrap_or("");
+                let media_id = text("Media ID");
+                let media_type = text("Media Type").unwrap_or("Image").to_string();
+                let location_str = text("Location").unwrap_or("");
+
+                let link = text("Media Download Url").or(text("Download Link")).map(str::to_string);
+                if media_type.eq_ignore_ascii_case("overlay") {
+                    if let Some(link) = link {
+                        overlays.push((media_id.unwrap_or(date_str).to_string(), link));
+                    }
+                    continue;
+                }
 
                 let timestamp = Self::parse_memory_timestamp(date_str);
                 let timestamp = match timestamp {
@@ -248,238 +744,1090 @@ impl MemoryParser {
                     None => continue,
                 };
 
-                let (latitude, longitude) = Self::parse_location(location_str);
+                let (latitude, longitude) = locations::parse_location(location_str);
+
+                memories.push((
+                    media_id.unwrap_or(date_str).to_string(),
+                    Memory {
+                        id: media_id
+                            .map(str::to_string)
+                            .unwrap_or_else(|| Uuid::new_v4().to_string()),
+                        timestamp,
+                        media_type,
+                        latitude,
+                        longitude,
+                        media_path: None,
+                        export_id: export_id.to_string(),
+                        // The direct link when the export has one, otherwise the download endpoint
+                        download_url: link,
+                        proxy_url: text("Download Link").map(str::to_string),
+                        overlay_url: ["Overlay Download Url", "Overlay Link", "Overlay Url"]
+                            .into_iter()
+                            .find_map(text)
+                            .map(str::to_string),
+                        download_status: crate::models::DownloadStatus::Pending,
+                        failure_reason: None,
+                    },
+                ));
+            }
+        }
+
+        for (key, link) in overlays {
+            match memories.iter_mut().find(|(k, m)| *k == key && m.overlay_url.is_none()) {
+                Some((_, memory)) => memory.overlay_url = Some(link),
+                None => log::debug!("Overlay without a matching memory: {}", key),
+            }
+        }
+
+        Ok(memories.into_iter().map(|(_, memory)| memory).collect())
+    }
+
+    fn parse_memory_timestamp(text: &str) -> Option<DateTime<Utc>> {
+        let text = text.trim().replace(" UTC", "");
+        if let Ok(naive) = NaiveDateTime::parse_from_str(&text, "%Y-%m-%d %H:%M:%S") {
+            return Some(Utc.from_utc_datetime(&naive));
+        }
+        None
+    }
+}
+
+/// Parses `json/location_history.json` — where the account was seen, from "Latest Location",
+/// "Areas you may have visited" and similar lists.
+pub struct LocationHistoryParser;
+
+impl LocationHistoryParser {
+    /// Parse json/location_history.json into points, oldest first. Entries without a readable
+    /// time or coordinates are skipped.
+    pub fn parse_location_history_json(
+        path: &Path,
+        export_id: &str,
+        hint: TimestampFormatHint,
+    ) -> AppResult<Vec<LocationPoint>> {
+        let root = read_json_file(path)?;
+        let mut points: Vec<LocationPoint> = sectioned_entries(&root)
+            .into_iter()
+            .filter_map(|(section, entry)| Self::parse_point(section, entry, export_id, hint))
+            .collect();
+        points.sort_by_key(|p| p.timestamp);
+        Ok(points)
+    }
+
+    fn parse_point(
+        section: Option<&str>,
+        entry: &Value,
+        export_id: &str,
+        hint: TimestampFormatHint,
+    ) -> Option<LocationPoint> {
+        let text = |key: &str| {
+            entry
+                .get(key)
+                .and_then(|v| v.as_str())
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+        };
+        let created = ["Time", "Date", "Created", "Timestamp"].into_iter().find_map(text)?;
+        let timestamp = ChatParser::try_parse_timestamp_with(&created.replace(" UTC", ""), hint)?;
+        // Either a "Latitude, Longitude" field holding "x, y" or a value carrying the label itself
+        let (latitude, longitude) = match text("Latitude, Longitude") {
+            Some(coords) => locations::parse_location(&format!("Latitude, Longitude: {}", coords)),
+            None => entry
+                .as_object()?
+                .values()
+                .filter_map(|v| v.as_str())
+                .map(locations::parse_location)
+                .find(|(lat, lon)| lat.is_some() && lon.is_some())?,
+        };
+        Some(LocationPoint {
+            timestamp,
+            latitude: latitude?,
+            longitude: longitude?,
+            source: text("Source").or(section).unwrap_or("Location History").to_string(),
+            export_id: export_id.to_string(),
+        })
+    }
+}
+
+pub struct ChatJsonParser;
+
+impl ChatJsonParser {
+    /// Parse json/chat_history.json — the primary source for Media IDs — into memory. A
+    /// truncated file yields the conversations read before the damage, with the problem
+    /// reported in `issue`. Imports use [`Self::stream_chat_history_json`] instead.
+    pub fn parse_chat_history_json(path: &Path, hint: TimestampFormatHint) -> AppResult<JsonConversations> {
+        let mut parsed = JsonConversations::default();
+        parsed.issue = Self::stream_chat_history_json(path, hint, |conversation_key, events| {
+            parsed.conversations.push((conversation_key, events));
+            ControlFlow::Continue(())
+        })?;
+        Ok(parsed)
+    }
+
+    /// Stream json/chat_history.json, handing `f` each conversation with events as soon as its
+    /// closing bracket is read. Conversations carry media_ids in event metadata. A truncated
+    /// file keeps the conversations completed before the damage and returns the problem.
+    /// Returning `ControlFlow::Break` from `f` stops reading.
+    pub fn stream_chat_history_json<F>(path: &Path, hint: TimestampFormatHint, f: F) -> AppResult<Option<JsonFileIssue>>
+    where
+        F: FnMut(String, Vec<Event>) -> ControlFlow<()>,
+    {
+        log::debug!("ChatJsonParser: parsing {:?}", path);
+        let mut diagnostics = ParseDiagnostics::default();
+        let issue = stream_json_conversations(
+            path,
+            |reader, each| Self::for_each_conversation(reader, hint, &mut diagnostics, each),
+            f,
+        )?;
+        log::info!("ChatJsonParser: {} media IDs total", diagnostics.media_ids);
+        Ok(issue)
+    }
+
+    /// Parse a whole chat_history.json document from any reader.
+    pub fn parse_chat_history_reader<R: Read>(
+        reader: R,
+        hint: TimestampFormatHint,
+    ) -> AppResult<Vec<(String, Vec<Event>)>> {
+        let mut result = Vec::new();
+        let mut total_events = 0;
+        let mut diagnostics = ParseDiagnostics::default();
+
+        Self::for_each_conversation(reader, hint, &mut diagnostics, |conversation_key, events| {
+            total_events += events.len();
+            if !events.is_empty() {
+                result.push((conversation_key, events));
+            }
+            ControlFlow::Continue(())
+        })?;
+
+        log::info!(
+            "ChatJsonParser: parsed {} conversations, {} events, {} media IDs total",
+            result.len(),
+            total_events,
+            diagnostics.media_ids
+        );
+        Ok(result)
+    }
+
+    /// Stream conversations out of chat_history.json one at a time.
+    ///
+    /// Messages are read one at a time too, so only one conversation's events are held in
+    /// memory at once, never its JSON. Returning `ControlFlow::Break` from the callback stops
+    /// reading without consuming the rest of the document, which lets previews sample huge
+    /// files cheaply.
+    pub fn for_each_conversation<R, F>(
+        reader: R,
+        hint: TimestampFormatHint,
+        diagnostics: &mut ParseDiagnostics,
+        mut f: F,
+    ) -> AppResult<()>
+    where
+        R: Read,
+        F: FnMut(String, Vec<Event>) -> ControlFlow<()>,
+    {
+        let mut events = Vec::new();
+        for_each_json_element(reader, |conversation_key, message| match message {
+            Some(msg) => {
+                events.extend(Self::parse_message(conversation_key, &msg, hint, diagnostics));
+                ControlFlow::Continue(())
+            }
+            None => {
+                let mut conversation = std::mem::take(&mut events);
+                assign_stable_ids(&mut conversation);
+                f(conversation_key.to_string(), conversation)
+            }
+        })
+    }
+
+    fn parse_message(
+        conversation_key: &str,
+        msg: &Value,
+        hint: TimestampFormatHint,
+        diagnostics: &mut ParseDiagnostics,
+    ) -> Option<Event> {
+        let from = msg.get("From").and_then(|v| v.as_str()).unwrap_or("").to_string();
+        let media_type_str = msg.get("Media Type").and_then(|v| v.as_str()).unwrap_or("TEXT");
+        let created = msg.get("Created").and_then(|v| v.as_str()).unwrap_or("");
+        let content_val = msg.get("Content").and_then(|v| v.as_str()).unwrap_or("");
+        let conversation_title = msg.get("Conversation Title").and_then(|v| v.as_str());
+        let is_sender = msg.get("IsSender").and_then(|v| v.as_bool()).unwrap_or(false);
+        let saved = ["IsSaved", "Saved"].iter().find_map(|k| match msg.get(*k)? {
+            Value::Bool(b) => Some(*b),
+            Value::String(s) => Some(s.eq_ignore_ascii_case("true")),
+            _ => None,
+        });
+        let media_ids_raw = msg.get("Media IDs").and_then(|v| v.as_str()).unwrap_or("");
+
+        // Parse pipe-separated Media IDs
+        let media_ids: Vec<String> = if media_ids_raw.is_empty() {
+            Vec::new()
+        } else {
+            media_ids_raw
+                .split(" | ")
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()
+        };
+        diagnostics.media_ids += media_ids.len();
+
+        // Build metadata JSON with media_ids and other fields
+        let mut metadata = serde_json::Map::new();
+        if !media_ids.is_empty() {
+            metadata.insert(
+                "media_ids".to_string(),
+                Value::Array(media_ids.iter().map(|id| Value::String(id.clone())).collect()),
+            );
+        }
+        if let Some(title) = conversation_title {
+            metadata.insert("conversation_title".to_string(), Value::String(title.to_string()));
+        }
+        metadata.insert("is_sender".to_string(), Value::Bool(is_sender));
+        if let Some(saved) = saved {
+            metadata.insert("saved".to_string(), Value::Bool(saved));
+        }
+        let extra = Self::extra_fields(msg);
+        if !extra.is_empty() {
+            metadata.insert("extra".to_string(), Value::Object(extra));
+        }
+
+        let content = if content_val.is_empty() {
+            None
+        } else {
+            Some(content_val.to_string())
+        };
+
+        // The Media Type field already matches our event_type convention
+        diagnostics.note_event_type(media_type_str);
+        let event_type = media_type_str.to_string();
+
+        let event = Event {
+            id: Uuid::new_v4().to_string(),
+            timestamp: DateTime::<Utc>::UNIX_EPOCH,
+            sender: from,
+            sender_name: None,
+            media_references: Vec::new(),
+            conversation_id: Some(conversation_key.to_string()),
+            content,
+            event_type,
+            metadata: if metadata.is_empty() {
+                None
+            } else {
+                Some(serde_json::to_string(&metadata).unwrap_or_default())
+            },
+        };
+        Some(clean_event(ChatParser::dated_message(
+            event,
+            created,
+            hint,
+            diagnostics,
+        )))
+    }
+
+    /// Scalar fields this parser doesn't know, so newer export formats lose nothing. Fields
+    /// that would push the total past `EXTRA_METADATA_LIMIT` are dropped.
+    fn extra_fields(msg: &Value) -> serde_json::Map<String, Value> {
+        let mut extra = serde_json::Map::new();
+        let mut size = 0;
+        for (key, value) in msg.as_object().into_iter().flatten() {
+            let scalar = value.is_string() || value.is_number() || value.is_boolean();
+            if !scalar || KNOWN_MESSAGE_KEYS.contains(&key.as_str()) {
+                continue;
+            }
+            let cost = key.len() + value.to_string().len();
+            if size + cost <= EXTRA_METADATA_LIMIT {
+                size += cost;
+                extra.insert(key.clone(), value.clone());
+            }
+        }
+        extra
+    }
+}
+
+/// Walk the entries of a top-level JSON object without materializing the whole document.
+///
+/// Documents whose top level is not an object yield no entries, matching how the
+/// parsers have always treated them.
+fn for_each_json_entry<R, F>(reader: R, mut f: F) -> AppResult<()>
+where
+    R: Read,
+    F: FnMut(String, Value) -> ControlFlow<()>,
+{
+    struct EntryVisitor<'a, F> {
+        f: &'a mut F,
+        stopped: &'a mut bool,
+    }
+
+    impl<'de, F: FnMut(String, Value) -> ControlFlow<()>> Visitor<'de> for EntryVisitor<'_, F> {
+        type Value = ();
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a JSON object keyed by conversation")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+            while let Some(key) = map.next_key::<String>()? {
+                let value: Value = map.next_value()?;
+                if (self.f)(key, value).is_break() {
+                    *self.stopped = true;
+                    return Err(de::Error::custom("stopped early"));
+                }
+            }
+            Ok(())
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+            while seq.next_element::<de::IgnoredAny>()?.is_some() {}
+            Ok(())
+        }
+    }
+
+    impl<'de, F: FnMut(String, Value) -> ControlFlow<()>> DeserializeSeed<'de> for EntryVisitor<'_, F> {
+        type Value = ();
+
+        fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+            deserializer.deserialize_any(self)
+        }
+    }
+
+    let mut stopped = false;
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let result = EntryVisitor {
+        f: &mut f,
+        stopped: &mut stopped,
+    }
+    .deserialize(&mut deserializer);
+
+    match result {
+        Ok(()) => deserializer.end().map_err(AppError::from),
+        Err(_) if stopped => Ok(()),
+        Err(e) => Err(AppError::from(e)),
+    }
+}
+
+/// Walk the array elements of each entry of a top-level JSON object, one element at a time.
+///
+/// `f` gets `Some(element)` for each element and `None` once an entry is finished. Entries
+/// whose value is not an array are finished without elements.
+fn for_each_json_element<R, F>(reader: R, mut f: F) -> AppResult<()>
+where
+    R: Read,
+    F: FnMut(&str, Option<Value>) -> ControlFlow<()>,
+{
+    struct ElementVisitor<'a, F> {
+        key: Option<&'a str>,
+        f: &'a mut F,
+        stopped: &'a mut bool,
+    }
+
+    impl<F> ElementVisitor<'_, F> {
+        fn stop<E: de::Error>(&mut self) -> Result<(), E> {
+            *self.stopped = true;
+            Err(E::custom("stopped early"))
+        }
+    }
+
+    impl<'de, F: FnMut(&str, Option<Value>) -> ControlFlow<()>> Visitor<'de> for ElementVisitor<'_, F> {
+        type Value = ();
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a JSON object keyed by conversation")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<(), A::Error> {
+            if self.key.is_some() {
+                while map.next_entry::<de::IgnoredAny, de::IgnoredAny>()?.is_some() {}
+                return Ok(());
+            }
+            while let Some(key) = map.next_key::<String>()? {
+                map.next_value_seed(ElementVisitor {
+                    key: Some(&key),
+                    f: &mut *self.f,
+                    stopped: &mut *self.stopped,
+                })?;
+                if (self.f)(&key, None).is_break() {
+                    return self.stop();
+                }
+            }
+            Ok(())
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+            let Some(key) = self.key else {
+                while seq.next_element::<de::IgnoredAny>()?.is_some() {}
+                return Ok(());
+            };
+            while let Some(element) = seq.next_element::<Value>()? {
+                if (self.f)(key, Some(element)).is_break() {
+                    return self.stop();
+                }
+            }
+            Ok(())
+        }
+
+        fn visit_bool<E: de::Error>(self, _: bool) -> Result<(), E> {
+            Ok(())
+        }
+
+        fn visit_i64<E: de::Error>(self, _: i64) -> Result<(), E> {
+            Ok(())
+        }
+
+        fn visit_u64<E: de::Error>(self, _: u64) -> Result<(), E> {
+            Ok(())
+        }
+
+        fn visit_f64<E: de::Error>(self, _: f64) -> Result<(), E> {
+            Ok(())
+        }
+
+        fn visit_str<E: de::Error>(self, _: &str) -> Result<(), E> {
+            Ok(())
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<(), E> {
+            Ok(())
+        }
+    }
+
+    impl<'de, F: FnMut(&str, Option<Value>) -> ControlFlow<()>> DeserializeSeed<'de> for ElementVisitor<'_, F> {
+        type Value = ();
+
+        fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+            deserializer.deserialize_any(self)
+        }
+    }
+
+    let mut stopped = false;
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let result = ElementVisitor {
+        key: None,
+        f: &mut f,
+        stopped: &mut stopped,
+    }
+    .deserialize(&mut deserializer);
+
+    match result {
+        Ok(()) => deserializer.end().map_err(AppError::from),
+        Err(_) if stopped => Ok(()),
+        Err(e) => Err(AppError::from(e)),
+    }
+}
+
+/// Conversations read from a json/ file, and what was wrong with it if it was cut short.
+#[derive(Debug, Default)]
+pub struct JsonConversations {
+    pub conversations: Vec<(String, Vec<Event>)>,
+    pub issue: Option<JsonFileIssue>,
+}
+
+impl JsonConversations {
+    pub fn event_count(&self) -> usize {
+        self.conversations.iter().map(|(_, e)| e.len()).sum()
+    }
+}
+
+/// Classify the start of a file that should be JSON. `None` means it looks like JSON.
+pub fn sniff_json_prefix(prefix: &[u8]) -> Option<JsonFileProblem> {
+    let prefix = prefix.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(prefix);
+    match prefix.iter().find(|b| !b.is_ascii_whitespace()) {
+        None => Some(JsonFileProblem::Truncated { at_byte: 0 }),
+        Some(b'{') | Some(b'[') => None,
+        Some(b'<') => {
+            let head = String::from_utf8_lossy(prefix).to_ascii_lowercase();
+            if head.contains("<!doctype") || head.contains("<html") || head.contains("<head") || head.contains("<body")
+            {
+                Some(JsonFileProblem::HtmlErrorPage)
+            } else {
+                Some(JsonFileProblem::NotJson)
+            }
+        }
+        Some(_) => Some(JsonFileProblem::NotJson),
+    }
+}
+
+fn file_issue(path: &Path, problem: JsonFileProblem) -> JsonFileIssue {
+    JsonFileIssue {
+        file: path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+        problem,
+        salvaged_conversations: 0,
+        salvaged_events: 0,
+    }
+}
+
+/// Refuse files that are not JSON before serde gets to produce an opaque error for them.
+fn sniff_json_file(path: &Path) -> AppResult<u64> {
+    let mut file = fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut prefix = Vec::with_capacity(1024);
+    file.by_ref().take(1024).read_to_end(&mut prefix)?;
+    // A file that is all whitespace in its first KB is still checked by the real parse
+    if prefix.len() == 1024 && prefix.iter().all(|b| b.is_ascii_whitespace()) {
+        return Ok(len);
+    }
+    match sniff_json_prefix(&prefix) {
+        Some(problem) => Err(AppError::CorruptFile(file_issue(path, problem))),
+        None => Ok(len),
+    }
+}
+
+/// Turn an end-of-input parse error into a truncation report; other errors pass through.
+fn classify_json_error(path: &Path, len: u64, error: AppError) -> AppError {
+    match error {
+        AppError::Serde(e) if e.is_eof() => {
+            AppError::CorruptFile(file_issue(path, JsonFileProblem::Truncated { at_byte: len }))
+        }
+        other => other,
+    }
+}
+
+/// Read a whole json/ file, reporting HTML error pages and truncation as [`AppError::CorruptFile`].
+pub fn read_json_file(path: &Path) -> AppResult<Value> {
+    let len = sniff_json_file(path)?;
+    let file = fs::File::open(path)?;
+    serde_json::from_reader(BufReader::new(file)).map_err(|e| classify_json_error(path, len, e.into()))
+}
+
+/// Read a json/ file keyed by conversation, converting each entry with `convert`. When the
+/// file is truncated, the conversations that were complete before the cut are kept.
+fn collect_json_conversations<F>(path: &Path, mut convert: F) -> AppResult<JsonConversations>
+where
+    F: FnMut(&str, Value) -> Vec<Event>,
+{
+    let mut parsed = JsonConversations::default();
+    parsed.issue = stream_json_conversations(
+        path,
+        |reader, each| {
+            for_each_json_entry(reader, |conversation_key, value| {
+                let events = convert(&conversation_key, value);
+                each(conversation_key, events)
+            })
+        },
+        |conversation_key, events| {
+            parsed.conversations.push((conversation_key, events));
+            ControlFlow::Continue(())
+        },
+    )?;
+    Ok(parsed)
+}
+
+/// Run `walk` over a json/ file keyed by conversation and hand `f` each conversation with
+/// events. When the file is truncated, the conversations that were complete before the cut
+/// are kept and the problem is returned. Returning `ControlFlow::Break` from `f` stops reading.
+fn stream_json_conversations<W, F>(path: &Path, walk: W, mut f: F) -> AppResult<Option<JsonFileIssue>>
+where
+    W: FnOnce(BufReader<fs::File>, &mut dyn FnMut(String, Vec<Event>) -> ControlFlow<()>) -> AppResult<()>,
+    F: FnMut(String, Vec<Event>) -> ControlFlow<()>,
+{
+    let len = sniff_json_file(path)?;
+    let file = fs::File::open(path)?;
+    let (mut conversations, mut events) = (0, 0);
+    let result = walk(BufReader::new(file), &mut |conversation_key, parsed| {
+        if parsed.is_empty() {
+            return ControlFlow::Continue(());
+        }
+        conversations += 1;
+        events += parsed.len();
+        f(conversation_key, parsed)
+    });
+    log::debug!(
+        "{:?}: {} conversations, {} events",
+        path.file_name().unwrap_or_default(),
+        conversations,
+        events
+    );
+    match result.map_err(|e| classify_json_error(path, len, e)) {
+        Ok(()) => Ok(None),
+        Err(AppError::CorruptFile(mut issue)) if conversations > 0 => {
+            issue.salvaged_conversations = conversations;
+            issue.salvaged_events = events;
+            log::warn!("Recovered part of a damaged file: {}", issue);
+            Ok(Some(issue))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Parses `json/snap_history.json` — snap send/receive events without Media IDs.
+pub struct SnapHistoryParser;
+
+impl SnapHistoryParser {
+    /// Parse json/snap_history.json. A truncated file yields the conversations read before the
+    /// damage, with the problem reported in `issue`.
+    pub fn parse_snap_history_json(path: &Path, hint: TimestampFormatHint) -> AppResult<JsonConversations> {
+        collect_json_conversations(path, |conversation_key, snaps| {
+            Self::parse_snaps(conversation_key, &snaps, hint)
+        })
+    }
+
+    /// Stream json/snap_history.json, handing `f` each conversation's snaps as it is read.
+    pub fn stream_snap_history_json<F>(path: &Path, hint: TimestampFormatHint, f: F) -> AppResult<Option<JsonFileIssue>>
+    where
+        F: FnMut(String, Vec<Event>) -> ControlFlow<()>,
+    {
+        stream_json_conversations(
+            path,
+            |reader, each| {
+                for_each_json_entry(reader, |conversation_key, snaps| {
+                    let events = Self::parse_snaps(&conversation_key, &snaps, hint);
+                    each(conversation_key, events)
+                })
+            },
+            f,
+        )
+    }
+
+    fn parse_snaps(conversation_key: &str, snaps: &Value, hint: TimestampFormatHint) -> Vec<Event> {
+        let Some(snap_list) = snaps.as_array() else {
+            return Vec::new();
+        };
+        let mut events = Vec::new();
+        for snap in snap_list {
+            let from = snap.get("From").and_then(|v| v.as_str()).unwrap_or("").to_string();
+            let media_type = snap.get("Media Type").and_then(|v| v.as_str()).unwrap_or("IMAGE");
+            let created = snap.get("Created").and_then(|v| v.as_str()).unwrap_or("");
+            let conversation_title = snap.get("Conversation Title").and_then(|v| v.as_str());
+            let is_sender = snap.get("IsSender").and_then(|v| v.as_bool()).unwrap_or(false);
+
+            let timestamp = ChatParser::try_parse_timestamp_with(&created.replace(" UTC", ""), hint);
+            let timestamp = match timestamp {
+                Some(ts) => ts,
+                None => continue,
+            };
+
+            let event_type = if media_type == "VIDEO" { "SNAP_VIDEO" } else { "SNAP" };
+            let content = if is_sender {
+                Some(format!("Sent a {} snap", media_type.to_lowercase()))
+            } else {
+                Some(format!("Received a {} snap", media_type.to_lowercase()))
+            };
+
+            let mut metadata = serde_json::Map::new();
+            if let Some(title) = conversation_title {
+                metadata.insert("conversation_title".to_string(), Value::String(title.to_string()));
+            }
+            metadata.insert("is_sender".to_string(), Value::Bool(is_sender));
+
+            events.push(clean_event(Event {
+                id: Uuid::new_v4().to_string(),
+                timestamp,
+                sender: from,
+                sender_name: None,
+                media_references: Vec::new(),
+                conversation_id: Some(conversation_key.to_string()),
+                content,
+                event_type: event_type.to_string(),
+                metadata: Some(serde_json::to_string(&metadata).unwrap_or_default()),
+            }));
+        }
+        assign_stable_ids(&mut events);
+        events
+    }
+}
+
+/// Entries of a json/ file that is either one list or an object of named lists, each with the
+/// name of the list it came from.
+fn sectioned_entries(root: &Value) -> Vec<(Option<&str>, &Value)> {
+    let sections: Vec<(Option<&str>, &Value)> = match root {
+        Value::Array(_) => vec![(None, root)],
+        Value::Object(map) => map.iter().map(|(key, value)| (Some(key.as_str()), value)).collect(),
+        _ => Vec::new(),
+    };
+    sections
+        .into_iter()
+        .filter_map(|(section, entries)| Some((section, entries.as_array()?)))
+        .flat_map(|(section, entries)| entries.iter().map(move |entry| (section, entry)))
+        .collect()
+}
+
+/// Parses `json/story_history.json` — the stories the owner posted, with their view counts.
+pub struct StoryHistoryParser;
+
+/// Stories read from `json/story_history.json`.
+#[derive(Debug, Default)]
+pub struct StoryHistory {
+    pub events: Vec<Event>,
+    /// Entries left out because their date was missing or could not be read.
+    pub unreadable_dates: usize,
+}
+
+impl StoryHistoryParser {
+    /// Parse json/story_history.json into STORY events in `conversation_id`. Older exports group
+    /// entries under their story type ("Friend Story", "Public Story"); newer ones keep a single
+    /// list with the type, view and screenshot counts on each entry. The sender is left empty
+    /// for the pipeline to fill in once the owner is known.
+    pub fn parse_story_history_json(
+        path: &Path,
+        conversation_id: &str,
+        hint: TimestampFormatHint,
+    ) -> AppResult<StoryHistory> {
+        let root = read_json_file(path)?;
+        let entries = sectioned_entries(&root);
+        let mut events: Vec<Event> = entries
+            .iter()
+            .filter_map(|&(section, entry)| Self::parse_story(section, entry, conversation_id, hint))
+            .collect();
+        events.sort_by_key(|e| e.timestamp);
+        assign_stable_ids(&mut events);
+        Ok(StoryHistory {
+            unreadable_dates: entries.len() - events.len(),
+            events,
+        })
+    }
+
+    /// One story, or `None` when its date is missing or can't be read.
+    fn parse_story(
+        section: Option<&str>,
+        entry: &Value,
+        conversation_id: &str,
+        hint: TimestampFormatHint,
+    ) -> Option<Event> {
+        let field = |keys: &[&str]| keys.iter().find_map(|k| entry.get(*k).filter(|v| !v.is_null()));
+        let text = |keys: &[&str]| {
+            field(keys)
+                .and_then(|v| v.as_str())
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+        };
+        // Counts are numbers in newer exports and strings in older ones
+        let count = |keys: &[&str]| {
+            field(keys).and_then(|v| v.as_i64().or_else(|| v.as_str().and_then(|s| s.trim().parse().ok())))
+        };
+
+        let created = text(&["Story Date", "Created", "Date", "Timestamp"])?;
+        let timestamp = ChatParser::try_parse_timestamp_with(&created.replace(" UTC", ""), hint)?;
+        let media_type = text(&["Media Type"]);
+        // In older exports the section name is the story type
+        let story_type = text(&["Story Type", "Type"]).or(section.filter(|s| s.ends_with("Story")));
+        let content = match (media_type, story_type) {
+            (Some(media), _) => format!("Posted a {} story", media.to_lowercase()),
+            (None, Some(kind)) => format!("Posted to {}", kind),
+            (None, None) => "Posted a story".to_string(),
+        };
+
+        let mut metadata = serde_json::Map::new();
+        metadata.insert("is_sender".to_string(), Value::Bool(true));
+        if let Some(media) = media_type {
+            metadata.insert("media_type".to_string(), Value::String(media.to_string()));
+        }
+        if let Some(kind) = story_type {
+            metadata.insert("story_type".to_string(), Value::String(kind.to_string()));
+        }
+        if let Some(views) = count(&["Story Views", "View Count", "Views"]) {
+            metadata.insert("view_count".to_string(), Value::from(views));
+        }
+        if let Some(screenshots) = count(&["Story Screenshots", "Screenshot Count", "Screenshots"]) {
+            metadata.insert("screenshot_count".to_string(), Value::from(screenshots));
+        }
 
-                let download_url = entry
-                    .get("Media Download Url")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string());
-                let proxy_url = entry
-                    .get("Download Link")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string());
+        Some(clean_event(Event {
+            id: Uuid::new_v4().to_string(),
+            timestamp,
+            sender: String::new(),
+            sender_name: None,
+            media_references: Vec::new(),
+            conversation_id: Some(conversation_id.to_string()),
+            content: Some(content),
+            event_type: "STORY".to_string(),
+            metadata: Some(serde_json::to_string(&metadata).unwrap_or_default()),
+        }))
+    }
+}
 
-                memories.push(Memory {
-                    id: Uuid::new_v4().to_string(),
-                    timestamp,
-                    media_type,
-                    latitude,
-                    longitude,
-                    media_path: None,
-                    export_id: export_id.to_string(),
-                    download_url,
-                    proxy_url,
-                    download_status: crate::models::DownloadStatus::Pending,
-                });
+/// Parses `json/talk_history.json` — audio and video calls, including completed ones the chat
+/// HTML never shows.
+pub struct TalkHistoryParser;
+
+/// Seconds between a logged call and an HTML missed-call event for them to be the same call.
+const CALL_MATCH_WINDOW_SECS: i64 = 60;
+
+impl TalkHistoryParser {
+    /// Parse json/talk_history.json into CALL_AUDIO/CALL_VIDEO events keyed by the other
+    /// participant's conversation. Entries are either one list or grouped under sections such as
+    /// "Outgoing Calls"; the call duration, when present, is kept as `duration_seconds`.
+    pub fn parse_talk_history_json(path: &Path, hint: TimestampFormatHint) -> AppResult<JsonConversations> {
+        let root = read_json_file(path)?;
+        let mut conversations: BTreeMap<String, Vec<Event>> = BTreeMap::new();
+        for (section, entry) in sectioned_entries(&root) {
+            if let Some(event) = Self::parse_call(section, entry, hint) {
+                let key = event.conversation_id.clone().unwrap_or_default();
+                conversations.entry(key).or_default().push(event);
             }
         }
-
-        Ok(memories)
+        let conversations = conversations
+            .into_iter()
+            .map(|(key, mut events)| {
+                events.sort_by_key(|e| e.timestamp);
+                assign_stable_ids(&mut events);
+                (key, events)
+            })
+            .collect();
+        Ok(JsonConversations {
+            conversations,
+            issue: None,
+        })
     }
 
-    fn parse_memory_timestamp(text: &str) -> Option<DateTime<Utc>> {
-        let text = text.trim().replace(" UTC", "");
-        if let Ok(naive) = NaiveDateTime::parse_from_str(&text, "%Y-%m-%d %H:%M:%S") {
-            return Some(Utc.from_utc_datetime(&naive));
+    fn parse_call(section: Option<&str>, entry: &Value, hint: TimestampFormatHint) -> Option<Event> {
+        let field = |keys: &[&str]| keys.iter().find_map(|k| entry.get(*k).filter(|v| !v.is_null()));
+        let text = |keys: &[&str]| {
+            field(keys)
+                .and_then(|v| v.as_str())
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+        };
+
+        let created = text(&["Call Date", "Created", "Date", "Start Time", "Timestamp"])?;
+        let timestamp = ChatParser::try_parse_timestamp_with(&created.replace(" UTC", ""), hint)?;
+        let caller = text(&["Caller", "From"]);
+        let recipient = text(&["Recipient", "To", "Callee"]);
+        let is_sender = field(&["IsSender", "Outgoing"])
+            .and_then(|v| v.as_bool())
+            .or_else(|| section.map(|s| s.to_lowercase().contains("outgoing")))
+            .unwrap_or(false);
+        let other = if is_sender { recipient } else { caller };
+        let conversation_key = text(&["Conversation ID", "Conversation"]).or(other)?;
+
+        let video = text(&["Call Type", "Media Type", "Type"]).is_some_and(|t| t.to_uppercase().contains("VIDEO"));
+        let (event_type, kind) = if video {
+            ("CALL_VIDEO", "Video")
+        } else {
+            ("CALL_AUDIO", "Audio")
+        };
+        let duration =
+            field(&["Duration (seconds)", "Duration", "Call Duration", "Call Length"]).and_then(parse_duration);
+        let content = match duration {
+            Some(seconds) => format!("{} call, {}", kind, format_duration(seconds)),
+            None => format!("{} call", kind),
+        };
+
+        let mut metadata = serde_json::Map::new();
+        metadata.insert("is_sender".to_string(), Value::Bool(is_sender));
+        if let Some(seconds) = duration {
+            metadata.insert("duration_seconds".to_string(), Value::from(seconds));
         }
-        None
+
+        Some(clean_event(Event {
+            id: Uuid::new_v4().to_string(),
+            timestamp,
+            // Outgoing calls often omit the caller; the pipeline names the owner later
+            sender: caller.unwrap_or_default().to_string(),
+            sender_name: None,
+            media_references: Vec::new(),
+            conversation_id: Some(conversation_key.to_string()),
+            content: Some(content),
+            event_type: event_type.to_string(),
+            metadata: Some(serde_json::to_string(&metadata).unwrap_or_default()),
+        }))
     }
 
-    fn parse_location(text: &str) -> (Option<f64>, Option<f64>) {
-        // Format: "Latitude, Longitude: 40.50679, -123.991455"
-        if let Some(coords) = text.strip_prefix("Latitude, Longitude: ") {
-            let parts: Vec<&str> = coords.split(", ").collect();
-            if parts.len() == 2 {
-                let lat = parts[0].parse::<f64>().ok();
-                let lon = parts[1].parse::<f64>().ok();
-                return (lat, lon);
-            }
+    /// Drop unanswered logged calls that the chat HTML already shows as a missed call in
+    /// the same conversation within [`CALL_MATCH_WINDOW_SECS`].
+    pub fn drop_known_missed_calls(calls: &mut JsonConversations, events: &[Event]) {
+        let missed: Vec<(&str, bool, DateTime<Utc>)> = events
+            .iter()
+            .filter_map(|e| {
+                let video = match e.event_type.as_str() {
+                    "MISSED_VIDEO_CHAT" => true,
+                    "MISSED_AUDIO_CHAT" => false,
+                    _ => return None,
+                };
+                Some((e.conversation_id.as_deref()?, video, e.timestamp))
+            })
+            .collect();
+        if missed.is_empty() {
+            return;
         }
-        (None, None)
+        for (key, events) in &mut calls.conversations {
+            events.retain(|call| {
+                let answered = call
+                    .metadata
+                    .as_deref()
+                    .and_then(|m| serde_json::from_str::<Value>(m).ok())
+                    .and_then(|v| v.get("duration_seconds")?.as_i64())
+                    .is_some_and(|seconds| seconds > 0);
+                let video = call.event_type == "CALL_VIDEO";
+                answered
+                    || !missed.iter().any(|(cid, missed_video, ts)| {
+                        cid == key
+                            && *missed_video == video
+                            && (call.timestamp - *ts).num_seconds().abs() <= CALL_MATCH_WINDOW_SECS
+                    })
+            });
+        }
+        calls.conversations.retain(|(_, events)| !events.is_empty());
     }
 }
 
-pub struct ChatJsonParser;
+/// Seconds from a number or a "123", "m:ss" or "h:mm:ss" string.
+fn parse_duration(value: &Value) -> Option<i64> {
+    if let Some(seconds) = value.as_f64() {
+        return (seconds >= 0.0).then_some(seconds.round() as i64);
+    }
+    let text = value.as_str()?.trim();
+    text.split(':').try_fold(0i64, |total, part| {
+        let part: i64 = part.trim().parse().ok().filter(|n| *n >= 0)?;
+        total.checked_mul(60)?.checked_add(part)
+    })
+}
 
-impl ChatJsonParser {
-    /// Parse json/chat_history.json — the primary source for Media IDs.
-    /// Returns Vec<(conversation_id, Vec<Event>)> with media_ids stored in event metadata.
-    pub fn parse_chat_history_json(path: &Path) -> AppResult<Vec<(String, Vec<Event>)>> {
-        log::debug!("ChatJsonParser: parsing {:?}", path);
-        let file = fs::File::open(path)?;
-        let json: Value = serde_json::from_reader(BufReader::new(file))?;
-        let mut result = Vec::new();
-        let mut total_events = 0;
-        let mut media_id_count = 0;
-
-        if let Some(obj) = json.as_object() {
-            for (conversation_key, messages) in obj {
-                if let Some(msg_list) = messages.as_array() {
-                    let mut events = Vec::new();
-                    for msg in msg_list {
-                        let from = msg.get("From").and_then(|v| v.as_str()).unwrap_or("").to_string();
-                        let media_type_str = msg.get("Media Type").and_then(|v| v.as_str()).unwrap_or("TEXT");
-                        let created = msg.get("Created").and_then(|v| v.as_str()).unwrap_or("");
-                        let content_val = msg.get("Content").and_then(|v| v.as_str()).unwrap_or("");
-                        let conversation_title = msg.get("Conversation Title").and_then(|v| v.as_str());
-                        let is_sender = msg.get("IsSender").and_then(|v| v.as_bool()).unwrap_or(false);
-                        let media_ids_raw = msg.get("Media IDs").and_then(|v| v.as_str()).unwrap_or("");
-
-                        let timestamp = match ChatParser::try_parse_timestamp(created) {
-                            Some(ts) => ts,
-                            None => continue,
-                        };
-
-                        // Parse pipe-separated Media IDs
-                        let media_ids: Vec<String> = if media_ids_raw.is_empty() {
-                            Vec::new()
-                        } else {
-                            media_ids_raw
-                                .split(" | ")
-                                .map(|s| s.trim().to_string())
-                                .filter(|s| !s.is_empty())
-                                .collect()
-                        };
-
-                        if !media_ids.is_empty() {
-                            media_id_count += media_ids.len();
-                        }
+fn format_duration(seconds: i64) -> String {
+    let (h, m, s) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{}:{:02}", m, s)
+    }
+}
 
-                        // Build metadata JSON with media_ids and other fields
-                        let mut metadata = serde_json::Map::new();
-                        if !media_ids.is_empty() {
-                            metadata.insert(
-                                "media_ids".to_string(),
-                                Value::Array(media_ids.iter().map(|id| Value::String(id.clone())).collect()),
-                            );
-                        }
-                        if let Some(title) = conversation_title {
-                            metadata.insert("conversation_title".to_string(), Value::String(title.to_string()));
-                        }
-                        metadata.insert("is_sender".to_string(), Value::Bool(is_sender));
-
-                        let content = if content_val.is_empty() {
-                            None
-                        } else {
-                            Some(content_val.to_string())
-                        };
-
-                        // Map Media Type field to event_type (they already match the convention)
-                        let event_type = match media_type_str {
-                            "TEXT"
-                            | "MEDIA"
-                            | "MISSED_VIDEO_CHAT"
-                            | "MISSED_AUDIO_CHAT"
-                            | "STATUSPARTICIPANTREMOVED"
-                            | "NOTE"
-                            | "SNAP"
-                            | "STICKER"
-                            | "SHARE"
-                            | "STATUSPARTICIPANTADDED"
-                            | "STATUSCONVERSATIONNAMECHANGED" => media_type_str.to_string(),
-                            _ => media_type_str.to_string(),
-                        };
-
-                        events.push(Event {
-                            id: Uuid::new_v4().to_string(),
-                            timestamp,
-                            sender: from,
-                            sender_name: None,
-                            media_references: Vec::new(),
-                            conversation_id: Some(conversation_key.clone()),
-                            content,
-                            event_type,
-                            metadata: if metadata.is_empty() {
-                                None
-                            } else {
-                                Some(serde_json::to_string(&metadata).unwrap_or_default())
-                            },
-                        });
-                    }
-                    total_events += events.len();
-                    if !events.is_empty() {
-                        result.push((conversation_key.clone(), events));
-                    }
-                }
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
 
-        log::info!(
-            "ChatJsonParser: parsed {} conversations, {} events, {} media IDs total",
-            result.len(),
-            total_events,
-            media_id_count
+    #[test]
+    fn test_split_continuation() {
+        assert_eq!(ChatParser::split_continuation("alice_2"), ("alice", 2));
+        assert_eq!(ChatParser::split_continuation("alice_12"), ("alice", 12));
+        assert_eq!(ChatParser::split_continuation("alice"), ("alice", 1));
+        assert_eq!(ChatParser::split_continuation("alice_1"), ("alice_1", 1));
+        assert_eq!(ChatParser::split_continuation("alice_02"), ("alice_02", 1));
+        assert_eq!(
+            ChatParser::conversation_id_from_path(Path::new("html/chat_history/subpage_bob_smith_3.html")),
+            "bob_smith"
         );
-        Ok(result)
     }
-}
 
-/// Parses `json/snap_history.json` — snap send/receive events without Media IDs.
-pub struct SnapHistoryParser;
+    #[test]
+    fn test_two_part_subpage_merges_into_one_conversation() {
+        let dir = tempfile::tempdir().unwrap();
+        let html = crate::test_fixtures::subpage_html("alice");
+        let is_message = |l: &&str| l.starts_with("<div style");
+        let head: Vec<&str> = html.lines().take_while(|l| !is_message(l)).collect();
+        // Alternate messages between the two files so only a sort restores the order
+        let messages: Vec<&str> = html.lines().filter(is_message).collect();
+        let mut parts = Vec::new();
+        for (name, offset) in [("subpage_alice.html", 0), ("subpage_alice_2.html", 1)] {
+            let body: Vec<&str> = messages.iter().skip(offset).step_by(2).copied().collect();
+            let path = dir.path().join(name);
+            let page = format!("{}\n{}\n</div>\n</body></html>\n", head.join("\n"), body.join("\n"));
+            fs::write(&path, page).unwrap();
+            let (conversation, events) = ChatParser::parse_subpage(&path, TimestampFormatHint::default()).unwrap();
+            parts.push((path, conversation, events));
+        }
 
-impl SnapHistoryParser {
-    pub fn parse_snap_history_json(path: &Path) -> AppResult<Vec<(String, Vec<Event>)>> {
-        let file = fs::File::open(path)?;
-        let json: Value = serde_json::from_reader(BufReader::new(file))?;
-        let mut result = Vec::new();
+        let merged = ChatParser::merge_subpage_parts(parts);
+        assert_eq!(merged.len(), 1);
+        let (conversation, events) = &merged[0];
+        assert_eq!(conversation.id, "alice");
+        assert_eq!(conversation.display_name.as_deref(), Some("alice"));
+        assert_eq!(conversation.message_count, 20);
+        assert_eq!(events.len(), 20);
+        assert!(events.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+        assert!(events.iter().all(|e| e.conversation_id.as_deref() == Some("alice")));
+        assert_eq!(conversation.last_event_at, events.last().map(|e| e.timestamp));
+        let mut participants = conversation.participants.clone();
+        participants.sort();
+        assert_eq!(participants, ["alice", "me"]);
+    }
 
-        if let Some(obj) = json.as_object() {
-            for (conversation_key, snaps) in obj {
-                if let Some(snap_list) = snaps.as_array() {
-                    let mut events = Vec::new();
-                    for snap in snap_list {
-                        let from = snap.get("From").and_then(|v| v.as_str()).unwrap_or("").to_string();
-                        let media_type = snap.get("Media Type").and_then(|v| v.as_str()).unwrap_or("IMAGE");
-                        let created = snap.get("Created").and_then(|v| v.as_str()).unwrap_or("");
-                        let conversation_title = snap.get("Conversation Title").and_then(|v| v.as_str());
-                        let is_sender = snap.get("IsSender").and_then(|v| v.as_bool()).unwrap_or(false);
-
-                        let timestamp = ChatParser::try_parse_timestamp(&created.replace(" UTC", ""));
-                        let timestamp = match timestamp {
-                            Some(ts) => ts,
-                            None => continue,
-                        };
-
-                        let event_type = if media_type == "VIDEO" { "SNAP_VIDEO" } else { "SNAP" };
-                        let content = if is_sender {
-                            Some(format!("Sent a {} snap", media_type.to_lowercase()))
-                        } else {
-                            Some(format!("Received a {} snap", media_type.to_lowercase()))
-                        };
-
-                        let mut metadata = serde_json::Map::new();
-                        if let Some(title) = conversation_title {
-                            metadata.insert("conversation_title".to_string(), Value::String(title.to_string()));
-                        }
-                        metadata.insert("is_sender".to_string(), Value::Bool(is_sender));
-
-                        events.push(Event {
-                            id: Uuid::new_v4().to_string(),
-                            timestamp,
-                            sender: from,
-                            sender_name: None,
-                            media_references: Vec::new(),
-                            conversation_id: Some(conversation_key.clone()),
-                            content,
-                            event_type: event_type.to_string(),
-                            metadata: Some(serde_json::to_string(&metadata).unwrap_or_default()),
-                        });
-                    }
-                    if !events.is_empty() {
-                        result.push((conversation_key.clone(), events));
-                    }
-                }
-            }
-        }
+    #[test]
+    fn test_event_ids_are_stable_across_parses() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("subpage_alice.html");
+        fs::write(&path, crate::test_fixtures::subpage_html("alice")).unwrap();
+        let parse = || {
+            let (conversation, events) = ChatParser::parse_subpage(&path, TimestampFormatHint::default()).unwrap();
+            let merged = ChatParser::merge_subpage_parts(vec![(path.clone(), conversation, events)]);
+            merged[0].1.iter().map(|e| e.id.clone()).collect::<Vec<_>>()
+        };
+        let first = parse();
+        assert_eq!(first, parse());
+        assert_eq!(first.iter().collect::<BTreeSet<_>>().len(), first.len());
+
+        // Identical events in the same second are numbered rather than collapsed
+        let mut events = crate::test_fixtures::events();
+        events.truncate(1);
+        events.push(events[0].clone());
+        assign_stable_ids(&mut events);
+        assert_ne!(events[0].id, events[1].id);
+        let mut again = events.clone();
+        assign_stable_ids(&mut again);
+        assert_eq!(again[1].id, events[1].id);
+    }
 
-        Ok(result)
+    #[test]
+    fn test_unmatched_continuation_keeps_its_own_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let part = |name: &str, heading: &str| {
+            let path = dir.path().join(name);
+            let html = format!(
+                "<html><body><h1>Chat History with {}</h1><div class=\"rightpanel\">\
+                 <div><h4>me</h4><p>hi</p><h6>2023-01-15 14:30:00 UTC</h6></div></div></body></html>",
+                heading
+            );
+            fs::write(&path, html).unwrap();
+            let (conversation, events) = ChatParser::parse_subpage(&path, TimestampFormatHint::default()).unwrap();
+            (path, conversation, events)
+        };
+        // "sam_2" is a different user, not page two of "sam"; "kim_3" has no first part
+        let merged = ChatParser::merge_subpage_parts(vec![
+            part("subpage_sam.html", "sam"),
+            part("subpage_sam_2.html", "sam_2"),
+            part("subpage_kim_3.html", "kim_3"),
+        ]);
+        let mut ids: Vec<&str> = merged.iter().map(|(c, _)| c.id.as_str()).collect();
+        ids.sort();
+        assert_eq!(ids, ["kim_3", "sam", "sam_2"]);
+        for (conversation, events) in &merged {
+            assert_eq!(conversation.message_count, 1);
+            assert_eq!(events[0].conversation_id.as_deref(), Some(conversation.id.as_str()));
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::io::Write;
+    #[test]
+    fn test_multi_name_heading_lists_silent_members() {
+        let page = |heading: &str, senders: &[&str]| {
+            let messages: String = senders
+                .iter()
+                .map(|s| format!("<div><h4>{}</h4><p>hi</p><h6>2023-01-15 14:30:00 UTC</h6></div>", s))
+                .collect();
+            let html = format!(
+                "<html><body><h1>Chat History with {}</h1><div class=\"rightpanel\">{}</div></body></html>",
+                heading, messages
+            );
+            ChatParser::parse_subpage_reader(&mut html.as_bytes(), "chat", TimestampFormatHint::default())
+                .unwrap()
+                .0
+        };
+
+        // carol never writes anything but is still a member
+        let group = page("Alice, bob, carol", &["me", "alice", "bob"]);
+        assert!(group.is_group);
+        assert_eq!(group.participants, ["Alice", "bob", "carol", "me", "alice"]);
+        let big = page("alice, bob, carol, dave, erin", &["me"]);
+        assert_eq!(big.display_name.as_deref(), Some("alice, bob, carol +2"));
+
+        // A name that no longer carries the "+2" doesn't hide the members it left out
+        let mut renamed = page("alice, bob, carol, dave, erin", &["me"]);
+        renamed.display_name = Some("alice, bob, carol".to_string());
+
+        let mut conversations = vec![group, big, page("alice", &["me", "alice"]), renamed];
+        ChatParser::resolve_group_members(&mut conversations, &crate::test_fixtures::people());
+        assert_eq!(conversations[0].participants, ["alice", "bob", "carol", "me"]);
+        assert_eq!(conversations[0].display_name.as_deref(), Some("Alice, Bob, Carol"));
+        assert_eq!(conversations[1].participants.len(), 6);
+        assert_eq!(conversations[1].display_name.as_deref(), Some("Alice, Bob, Carol +2"));
+
+        // A single friend stays a 1:1 chat named after the heading
+        let single = &conversations[2];
+        assert!(!single.is_group);
+        assert_eq!(single.display_name.as_deref(), Some("alice"));
+        assert_eq!(single.participants, ["me", "alice"]);
+
+        assert_eq!(
+            conversations[3].participants,
+            ["alice", "bob", "carol", "dave", "erin", "me"]
+        );
+        assert_eq!(conversations[3].display_name.as_deref(), Some("Alice, Bob, Carol +2"));
+    }
 
     #[test]
     fn test_try_parse_timestamp_format1() {
@@ -500,6 +1848,15 @@ mod tests {
         assert!(ts.is_some());
     }
 
+    #[test]
+    fn test_try_parse_timestamp_day_first_hint() {
+        let ts = ChatParser::try_parse_timestamp_with("15/01/2023 14:30:00", TimestampFormatHint::DayFirst);
+        assert_eq!(ts.unwrap().format("%Y-%m-%d").to_string(), "2023-01-15");
+        let ts = ChatParser::try_parse_timestamp_with("02/03/2023 14:30:00 UTC", TimestampFormatHint::DayFirst);
+        assert_eq!(ts.unwrap().format("%Y-%m-%d").to_string(), "2023-03-02");
+        assert!(ChatParser::try_parse_timestamp("15/01/2023 14:30:00").is_none());
+    }
+
     #[test]
     fn test_try_parse_timestamp_utc_suffix() {
         let ts = ChatParser::try_parse_timestamp("2023-01-15 14:30:00 UTC");
@@ -527,11 +1884,99 @@ mod tests {
         )
         .unwrap();
 
-        let people = PersonParser::parse_friends_json(tmp.path()).unwrap();
+        let friends = PersonParser::parse_friends_json(tmp.path()).unwrap();
+        let people = &friends.people;
         assert_eq!(people.len(), 2);
         assert_eq!(people[0].username, "alice");
         assert_eq!(people[0].display_name.as_deref(), Some("Alice S"));
         assert!(people[1].display_name.is_none());
+        assert_eq!(
+            friends.by_schema,
+            [FriendsSchemaCount {
+                schema: FriendsSchema::Classic,
+                people: 2
+            }]
+        );
+        assert!(friends.unrecognized_warning().is_none());
+    }
+
+    #[test]
+    fn test_parse_account_json_sections_and_sender_fallback() {
+        let mut tmp = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            tmp,
+            r#"{{
+            "Basic Information": {{"Username": "kody", "Name": "Kody D", "Creation Date": "2015-01-01"}},
+            "Device Information": {{"Name": "iPhone"}}
+        }}"#
+        )
+        .unwrap();
+        let owner = AccountParser::parse_account_json(tmp.path()).unwrap();
+        assert_eq!(owner.username.as_deref(), Some("kody"));
+        assert_eq!(owner.display_name.as_deref(), Some("Kody D"));
+    }
+
+    #[test]
+    fn test_parse_friends_json_lowercase_schema() {
+        let mut tmp = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            tmp,
+            r#"{{
+            "friends": [{{"username": "alice", "display_name": "Alice S"}}],
+            "blocked_users": [{{"username": "mallory", "display_name": ""}}]
+        }}"#
+        )
+        .unwrap();
+
+        let friends = PersonParser::parse_friends_json(tmp.path()).unwrap();
+        let names: Vec<_> = friends
+            .people
+            .iter()
+            .map(|p| (p.username.as_str(), p.display_name.as_deref()))
+            .collect();
+        assert_eq!(names, [("alice", Some("Alice S")), ("mallory", None)]);
+        assert_eq!(
+            friends.by_schema,
+            [FriendsSchemaCount {
+                schema: FriendsSchema::Lowercase,
+                people: 2
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_friends_json_mixed_schemas_merge() {
+        let mut tmp = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            tmp,
+            r#"{{
+            "Friends": [{{"Username": "alice", "Display Name": "Alice S"}}],
+            "friends": [
+                {{"username": "alice", "display_name": "Alice Again"}},
+                {{"username": "bob", "Display Name": "Bob"}}
+            ]
+        }}"#
+        )
+        .unwrap();
+
+        let friends = PersonParser::parse_friends_json(tmp.path()).unwrap();
+        assert_eq!(friends.people.len(), 2);
+        assert_eq!(friends.people[0].display_name.as_deref(), Some("Alice S"));
+        assert_eq!(friends.people[1].display_name.as_deref(), Some("Bob"));
+        let counts: Vec<_> = friends.by_schema.iter().map(|c| (c.schema, c.people)).collect();
+        assert_eq!(counts, [(FriendsSchema::Classic, 1), (FriendsSchema::Lowercase, 1)]);
+    }
+
+    #[test]
+    fn test_parse_friends_json_unknown_schema_names_its_keys() {
+        let mut tmp = tempfile::NamedTempFile::new().unwrap();
+        write!(tmp, r#"{{"contacts": [{{"handle": "alice"}}], "version": 3}}"#).unwrap();
+
+        let friends = PersonParser::parse_friends_json(tmp.path()).unwrap();
+        assert!(friends.people.is_empty());
+        assert!(friends.by_schema.is_empty());
+        let warning = friends.unrecognized_warning().unwrap();
+        assert!(warning.contains("contacts, version"), "{}", warning);
     }
 
     #[test]
@@ -563,6 +2008,56 @@ mod tests {
         assert!((memories[0].latitude.unwrap() - 40.50679).abs() < 0.001);
     }
 
+    #[test]
+    fn test_memories_download_links_media_ids_and_overlays() {
+        let mut tmp = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            tmp,
+            r#"{{
+            "Saved Media": [
+                {{
+                    "Date": "2023-06-15 10:30:00 UTC",
+                    "Media Type": "Video",
+                    "Media ID": "b7c1f0e2",
+                    "Download Link": "https://app.snapchat.com/dmd/memories?uid=1&sid=b7c1f0e2"
+                }},
+                {{
+                    "Date": "2023-06-15 10:30:00 UTC",
+                    "Media Type": "Overlay",
+                    "Media ID": "b7c1f0e2",
+                    "Download Link": "https://app.snapchat.com/dmd/memories?uid=1&sid=b7c1f0e2&overlay=1"
+                }},
+                {{
+                    "Date": "2023-06-16 08:00:00 UTC",
+                    "Media Type": "Image",
+                    "Media Download Url": "https://cf-st.sc-cdn.net/direct.jpg",
+                    "Download Link": "https://app.snapchat.com/dmd/memories?uid=1&sid=c9"
+                }},
+                {{"Date": "2023-06-17 08:00:00 UTC", "Media Type": "Image"}}
+            ]
+        }}"#
+        )
+        .unwrap();
+
+        let memories = MemoryParser::parse_memories_json(tmp.path(), "e").unwrap();
+        assert_eq!(memories.len(), 3);
+        let video = &memories[0];
+        assert_eq!(video.id, "b7c1f0e2");
+        assert_eq!(video.download_url.as_deref(), video.proxy_url.as_deref());
+        assert!(video.download_url.as_deref().unwrap().ends_with("sid=b7c1f0e2"));
+        assert!(video.overlay_url.as_deref().unwrap().ends_with("overlay=1"));
+
+        // The direct link wins over the endpoint, which is kept as the proxy link
+        assert_eq!(
+            memories[1].download_url.as_deref(),
+            Some("https://cf-st.sc-cdn.net/direct.jpg")
+        );
+        assert!(memories[1].proxy_url.as_deref().unwrap().ends_with("sid=c9"));
+        assert!(Uuid::parse_str(&memories[1].id).is_ok());
+
+        assert!(memories[2].download_url.is_none() && memories[2].overlay_url.is_none());
+    }
+
     #[test]
     fn test_parse_chat_history_json() {
         let mut tmp = tempfile::NamedTempFile::new().unwrap();
@@ -591,7 +2086,9 @@ mod tests {
         )
         .unwrap();
 
-        let result = ChatJsonParser::parse_chat_history_json(tmp.path()).unwrap();
+        let result = ChatJsonParser::parse_chat_history_json(tmp.path(), TimestampFormatHint::default())
+            .unwrap()
+            .conversations;
         assert_eq!(result.len(), 1);
         let (convo_key, events) = &result[0];
         assert_eq!(convo_key, "alice - conv1");
@@ -604,6 +2101,40 @@ mod tests {
         assert_eq!(ids[0].as_str().unwrap(), "abc123");
     }
 
+    #[test]
+    fn test_chat_json_keeps_saved_flag_and_unknown_fields() {
+        let long = "x".repeat(EXTRA_METADATA_LIMIT);
+        let mut tmp = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            tmp,
+            r#"{{
+            "alice": [
+                {{"From": "alice", "Media Type": "TEXT", "Created": "2023-06-15 10:30:00 UTC",
+                  "Content": "keep this", "IsSender": false, "IsSaved": true,
+                  "Screenshot Count": 2, "Viewed": "2023-06-15 10:35:00 UTC",
+                  "Reactions": ["heart"], "Notes": "{}"}},
+                {{"From": "alice", "Media Type": "TEXT", "Created": "2023-06-15 10:31:00 UTC",
+                  "Content": "plain", "IsSender": false}}
+            ]
+        }}"#,
+            long
+        )
+        .unwrap();
+
+        let result = ChatJsonParser::parse_chat_history_json(tmp.path(), TimestampFormatHint::default())
+            .unwrap()
+            .conversations;
+        let (_, events) = &result[0];
+        assert!(events[0].is_saved() && !events[1].is_saved());
+        let meta: serde_json::Value = serde_json::from_str(events[0].metadata.as_deref().unwrap()).unwrap();
+        assert_eq!(
+            meta["extra"],
+            serde_json::json!({"Screenshot Count": 2, "Viewed": "2023-06-15 10:35:00 UTC"})
+        );
+        let plain: serde_json::Value = serde_json::from_str(events[1].metadata.as_deref().unwrap()).unwrap();
+        assert!(plain.get("extra").is_none() && plain.get("saved").is_none());
+    }
+
     #[test]
     fn test_chat_json_empty_media_ids() {
         let mut tmp = tempfile::NamedTempFile::new().unwrap();
@@ -624,7 +2155,9 @@ mod tests {
         )
         .unwrap();
 
-        let result = ChatJsonParser::parse_chat_history_json(tmp.path()).unwrap();
+        let result = ChatJsonParser::parse_chat_history_json(tmp.path(), TimestampFormatHint::default())
+            .unwrap()
+            .conversations;
         let (_, events) = &result[0];
         // Empty Media IDs → no media_ids key, or empty array
         let meta: serde_json::Value = serde_json::from_str(events[0].metadata.as_deref().unwrap_or("{}")).unwrap();
@@ -652,7 +2185,9 @@ mod tests {
         )
         .unwrap();
 
-        let result = ChatJsonParser::parse_chat_history_json(tmp.path()).unwrap();
+        let result = ChatJsonParser::parse_chat_history_json(tmp.path(), TimestampFormatHint::default())
+            .unwrap()
+            .conversations;
         let (_, events) = &result[0];
         let meta: serde_json::Value = serde_json::from_str(events[0].metadata.as_ref().unwrap()).unwrap();
         let ids = meta["media_ids"].as_array().unwrap();
@@ -686,7 +2221,9 @@ mod tests {
         )
         .unwrap();
 
-        let result = SnapHistoryParser::parse_snap_history_json(tmp.path()).unwrap();
+        let result = SnapHistoryParser::parse_snap_history_json(tmp.path(), TimestampFormatHint::default())
+            .unwrap()
+            .conversations;
         assert_eq!(result.len(), 1);
         let (_, events) = &result[0];
         assert_eq!(events.len(), 2);
@@ -701,7 +2238,298 @@ mod tests {
         let mut tmp = tempfile::NamedTempFile::new().unwrap();
         write!(tmp, "{{}}").unwrap();
 
-        let result = SnapHistoryParser::parse_snap_history_json(tmp.path()).unwrap();
+        let result = SnapHistoryParser::parse_snap_history_json(tmp.path(), TimestampFormatHint::default())
+            .unwrap()
+            .conversations;
         assert!(result.is_empty());
     }
+
+    #[test]
+    fn test_story_history_old_layout() {
+        let mut tmp = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            tmp,
+            r#"{{
+            "Friend Story": [
+                {{"Story Date": "2018-03-02 20:15:00 UTC", "Story Views": "14", "Story Screenshots": "1"}},
+                {{"Story Date": "2018-03-01 09:00:00 UTC", "Story Views": "3"}}
+            ],
+            "Public Story": [{{"Story Date": "not a date"}}]
+        }}"#
+        )
+        .unwrap();
+
+        let history =
+            StoryHistoryParser::parse_story_history_json(tmp.path(), "stories", TimestampFormatHint::default())
+                .unwrap();
+        assert_eq!(history.unreadable_dates, 1);
+        let events = history.events;
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|e| e.event_type == "STORY" && e.sender.is_empty()));
+        assert!(events.iter().all(|e| e.conversation_id.as_deref() == Some("stories")));
+        assert!(events[0].timestamp < events[1].timestamp);
+        assert_eq!(events[1].content.as_deref(), Some("Posted to Friend Story"));
+        let metadata: Value = serde_json::from_str(events[1].metadata.as_deref().unwrap()).unwrap();
+        assert_eq!(metadata["story_type"], "Friend Story");
+        assert_eq!(metadata["view_count"], 14);
+        assert_eq!(metadata["screenshot_count"], 1);
+        assert_eq!(metadata["is_sender"], true);
+    }
+
+    #[test]
+    fn test_story_history_new_layout() {
+        let mut tmp = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            tmp,
+            r#"{{
+            "Story History": [
+                {{
+                    "Created": "2024-05-10 18:00:00 UTC",
+                    "Media Type": "VIDEO",
+                    "Story Type": "My Story",
+                    "View Count": 42,
+                    "Screenshot Count": 0
+                }},
+                {{"Created": "2024-05-11 08:30:00 UTC", "Media Type": "IMAGE"}}
+            ]
+        }}"#
+        )
+        .unwrap();
+
+        let history =
+            StoryHistoryParser::parse_story_history_json(tmp.path(), "stories", TimestampFormatHint::default())
+                .unwrap();
+        assert_eq!(history.unreadable_dates, 0);
+        let events = history.events;
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].content.as_deref(), Some("Posted a video story"));
+        let metadata: Value = serde_json::from_str(events[0].metadata.as_deref().unwrap()).unwrap();
+        assert_eq!(metadata["view_count"], 42);
+        assert_eq!(metadata["screenshot_count"], 0);
+        assert_eq!(metadata["story_type"], "My Story");
+        assert_eq!(metadata["media_type"], "VIDEO");
+        let metadata: Value = serde_json::from_str(events[1].metadata.as_deref().unwrap()).unwrap();
+        assert!(metadata.get("view_count").is_none());
+        assert!(metadata.get("story_type").is_none());
+    }
+
+    #[test]
+    fn test_talk_history_calls_and_durations() {
+        let mut tmp = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            tmp,
+            r#"{{
+            "Outgoing Calls": [
+                {{"Recipient": "alice", "Call Type": "VIDEO", "Call Date": "2023-06-15 10:00:00 UTC", "Duration": "1:02:05"}},
+                {{"Recipient": "bob", "Call Type": "AUDIO", "Call Date": "2023-06-15 11:00:00 UTC"}}
+            ],
+            "Incoming Calls": [
+                {{"Caller": "alice", "Call Type": "AUDIO", "Call Date": "2023-06-14 09:00:00 UTC", "Duration": 95}},
+                {{"Caller": "carol", "Call Date": "not a date", "Duration": 5}}
+            ]
+        }}"#
+        )
+        .unwrap();
+
+        let calls = TalkHistoryParser::parse_talk_history_json(tmp.path(), TimestampFormatHint::default()).unwrap();
+        assert_eq!(calls.event_count(), 3);
+        let (key, alice) = &calls.conversations[0];
+        assert_eq!(key, "alice");
+        assert_eq!(alice[0].event_type, "CALL_AUDIO");
+        assert_eq!(alice[0].sender, "alice");
+        assert_eq!(alice[0].content.as_deref(), Some("Audio call, 1:35"));
+        assert_eq!(alice[1].event_type, "CALL_VIDEO");
+        assert!(alice[1].sender.is_empty() && alice[1].is_sender());
+        let metadata: Value = serde_json::from_str(alice[1].metadata.as_deref().unwrap()).unwrap();
+        assert_eq!(metadata["duration_seconds"], 3725);
+
+        // A call without a duration is still recorded
+        let (key, bob) = &calls.conversations[1];
+        assert_eq!(key, "bob");
+        assert_eq!(bob[0].content.as_deref(), Some("Audio call"));
+        assert!(!bob[0].metadata.as_deref().unwrap().contains("duration_seconds"));
+    }
+
+    #[test]
+    fn test_talk_history_skips_calls_already_missed_in_html() {
+        let mut tmp = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            tmp,
+            r#"[
+                {{"Caller": "alice", "Call Type": "AUDIO", "Call Date": "2023-06-15 10:00:30 UTC", "Duration": 0}},
+                {{"Caller": "alice", "Call Type": "AUDIO", "Call Date": "2023-06-15 10:01:00 UTC", "Duration": 40}},
+                {{"Caller": "alice", "Call Type": "VIDEO", "Call Date": "2023-06-15 10:00:00 UTC"}}
+            ]"#
+        )
+        .unwrap();
+        let mut calls = TalkHistoryParser::parse_talk_history_json(tmp.path(), TimestampFormatHint::default()).unwrap();
+        let mut missed = crate::test_fixtures::events().remove(0);
+        missed.conversation_id = Some("alice".to_string());
+        missed.event_type = "MISSED_AUDIO_CHAT".to_string();
+        missed.timestamp = Utc.with_ymd_and_hms(2023, 6, 15, 10, 0, 0).unwrap();
+
+        TalkHistoryParser::drop_known_missed_calls(&mut calls, &[missed]);
+        let kept: Vec<&str> = calls.conversations[0].1.iter().map(|e| e.event_type.as_str()).collect();
+        assert_eq!(kept, ["CALL_VIDEO", "CALL_AUDIO"]);
+    }
+
+    #[test]
+    fn test_location_history_sections() {
+        let mut tmp = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            tmp,
+            r#"{{
+            "Latest Location": [
+                {{"City": "Paris", "Time": "2023-06-15 10:00:00 UTC", "Location": "Latitude, Longitude: 48.8584, 2.2945"}}
+            ],
+            "Areas you may have visited in the last two years": [
+                {{"Time": "2023-06-01 08:00:00 UTC", "Latitude, Longitude": "51.5 ± 40.0 meters, -0.12 ± 40.0 meters"}},
+                {{"Time": "2023-06-02 08:00:00 UTC", "City": "Nowhere"}}
+            ],
+            "Home & Work": {{"Home": "Latitude, Longitude: 1.0, 2.0"}}
+        }}"#
+        )
+        .unwrap();
+
+        let points =
+            LocationHistoryParser::parse_location_history_json(tmp.path(), "e", TimestampFormatHint::default())
+                .unwrap();
+        assert_eq!(points.len(), 2);
+        assert_eq!((points[0].latitude, points[0].longitude), (51.5, -0.12));
+        assert_eq!(points[0].source, "Areas you may have visited in the last two years");
+        assert_eq!((points[1].latitude, points[1].longitude), (48.8584, 2.2945));
+        assert_eq!(points[1].source, "Latest Location");
+        assert!(points.iter().all(|p| p.export_id == "e"));
+    }
+
+    #[test]
+    fn test_json_html_error_page_is_classified() {
+        let mut tmp = tempfile::NamedTempFile::new().unwrap();
+        write!(tmp, "\n  <!DOCTYPE html><html><body>Request expired</body></html>").unwrap();
+
+        for err in [
+            ChatJsonParser::parse_chat_history_json(tmp.path(), TimestampFormatHint::default()).unwrap_err(),
+            MemoryParser::parse_memories_json(tmp.path(), "e").unwrap_err(),
+        ] {
+            match err {
+                AppError::CorruptFile(issue) => assert_eq!(issue.problem, JsonFileProblem::HtmlErrorPage),
+                other => panic!("unexpected error: {}", other),
+            }
+        }
+        assert_eq!(sniff_json_prefix(b"\xEF\xBB\xBF {\"a\": 1}"), None);
+        assert_eq!(sniff_json_prefix(b"Access denied"), Some(JsonFileProblem::NotJson));
+    }
+
+    #[test]
+    fn test_truncated_chat_json_salvages_complete_conversations() {
+        let message =
+            r#"{"From": "alice", "Media Type": "TEXT", "Created": "2023-06-15 10:30:00 UTC", "Content": "hi"}"#;
+        let doc = format!(
+            r#"{{"first": [{m}, {m}], "second": [{m}], "third": [{m}, {{"From": "al"#,
+            m = message
+        );
+        let mut tmp = tempfile::NamedTempFile::new().unwrap();
+        tmp.write_all(doc.as_bytes()).unwrap();
+
+        let parsed = ChatJsonParser::parse_chat_history_json(tmp.path(), TimestampFormatHint::default()).unwrap();
+        let keys: Vec<&str> = parsed.conversations.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, ["first", "second"]);
+        let issue = parsed.issue.unwrap();
+        assert_eq!(
+            issue.problem,
+            JsonFileProblem::Truncated {
+                at_byte: doc.len() as u64
+            }
+        );
+        assert_eq!((issue.salvaged_conversations, issue.salvaged_events), (2, 3));
+        assert_eq!(issue.status(), crate::models::ValidationStatus::Incomplete);
+        assert!(issue.to_string().contains(&format!("truncated at byte {}", doc.len())));
+
+        // Nothing complete before the cut is an error, not an empty success
+        let mut cut = tempfile::NamedTempFile::new().unwrap();
+        cut.write_all(br#"{"first": [{"From"#).unwrap();
+        assert!(matches!(
+            SnapHistoryParser::parse_snap_history_json(cut.path(), TimestampFormatHint::default()),
+            Err(AppError::CorruptFile(JsonFileIssue {
+                problem: JsonFileProblem::Truncated { .. },
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_chat_json_streams_conversations_without_reading_ahead() {
+        const CONVERSATIONS: usize = 16;
+        const MESSAGES: usize = 2_500;
+        // ~6MB, written as it is generated
+        let tmp = tempfile::NamedTempFile::new().unwrap();
+        let mut ends = Vec::new();
+        {
+            let mut out = std::io::BufWriter::new(tmp.as_file());
+            let mut written = 0;
+            let mut put = |out: &mut std::io::BufWriter<_>, text: &str| {
+                out.write_all(text.as_bytes()).unwrap();
+                written += text.len();
+                written
+            };
+            put(&mut out, "{");
+            for c in 0..CONVERSATIONS {
+                put(
+                    &mut out,
+                    &format!("{}\"friend_{}\": [", if c > 0 { "," } else { "" }, c),
+                );
+                for m in 0..MESSAGES {
+                    let message = format!(
+                        r#"{{"From": "friend_{c}", "Media Type": "TEXT", "Created": "2023-06-15 10:{:02}:{:02} UTC", "#,
+                        m / 60 % 60,
+                        m % 60
+                    ) + &format!(
+                        r#""Content": "message {m} of a chatty conversation", "Media IDs": "b~{m}"}}"#
+                    );
+                    put(&mut out, &format!("{}{}", if m > 0 { "," } else { "" }, message));
+                }
+                ends.push(put(&mut out, "]"));
+            }
+            put(&mut out, "}");
+        }
+
+        // Each conversation arrives once its closing bracket is read, without the rest of the file
+        struct Counting<R>(R, std::rc::Rc<std::cell::Cell<usize>>);
+        impl<R: Read> Read for Counting<R> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let n = self.0.read(buf)?;
+                self.1.set(self.1.get() + n);
+                Ok(n)
+            }
+        }
+        let consumed = std::rc::Rc::new(std::cell::Cell::new(0));
+        let reader = BufReader::new(Counting(fs::File::open(tmp.path()).unwrap(), consumed.clone()));
+        let mut seen = 0;
+        let mut diagnostics = ParseDiagnostics::default();
+        ChatJsonParser::for_each_conversation(
+            reader,
+            TimestampFormatHint::default(),
+            &mut diagnostics,
+            |key, events| {
+                assert_eq!(key, format!("friend_{}", seen));
+                assert_eq!(events.len(), MESSAGES);
+                assert!(consumed.get() <= ends[seen] + 16 * 1024, "read ahead of {}", key);
+                seen += 1;
+                ControlFlow::Continue(())
+            },
+        )
+        .unwrap();
+        assert_eq!(seen, CONVERSATIONS);
+        assert_eq!(diagnostics.media_ids, CONVERSATIONS * MESSAGES);
+
+        let mut largest = 0;
+        let issue =
+            ChatJsonParser::stream_chat_history_json(tmp.path(), TimestampFormatHint::default(), |_, events| {
+                largest = largest.max(events.len());
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        assert!(issue.is_none());
+        assert_eq!(largest, MESSAGES);
+    }
 }
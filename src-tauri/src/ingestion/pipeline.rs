@@ -0,0 +1,1477 @@
+//! The import pipeline: extraction, parsing and saving an export, independent of the UI that
+//! drives it. Progress goes to a [`ProgressSink`], which the app forwards as Tauri events and the
+//! command-line tool prints to stderr.
+
+use crate::db::{DatabaseManager, IngestionRun};
+use crate::error::{AppError, AppResult};
+use crate::ingestion::artifacts;
+use crate::ingestion::extractor::{self, ZipExtractor};
+use crate::ingestion::media_linker::MediaLinker;
+use crate::ingestion::parser::{
+    AccountOwner, AccountParser, ChatJsonParser, ChatParser, LocationHistoryParser, MemoryParser, PersonParser,
+    SnapHistoryParser, StoryHistoryParser, TalkHistoryParser,
+};
+use crate::ingestion::writer::EventWriter;
+use crate::ingestion::{self, layout, merger, timestamps};
+use crate::models::{
+    ChatSource, Conversation, Event, ExportSet, ExportSourceType, FriendsSchema, FriendsSchemaCount, ImportOptions,
+    IngestionFailure, IngestionProgress, IngestionResult, IngestionRunStatus, Memory, OwnerProfile, PathSource,
+    PhaseTiming, RelinkSummary, TimestampFormatHint,
+};
+use rayon::prelude::*;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Chats parsed per thread before the batch is saved and the next one read.
+const CHATS_PER_PARSE_THREAD: usize = 4;
+
+/// Unlinked messages loaded at a time when relinking media.
+const RELINK_BATCH_SIZE: usize = 5_000;
+
+/// Share of the progress bar each phase of an import takes, in order.
+const PHASE_WEIGHTS: [(&str, f32); 9] = [
+    ("Resolving Identities", 0.02),
+    ("Parsing Chats", 0.28),
+    ("Parsing Chat JSON", 0.04),
+    ("Parsing Snap History", 0.04),
+    ("Parsing Story History", 0.04),
+    ("Linking Media", 0.15),
+    ("Processing Memories", 0.10),
+    ("Saving to Database", 0.03),
+    ("Building Search Index", 0.17),
+];
+
+/// Where each phase sits on the progress bar. Phases an import skips take no room, so the bar
+/// moves through the ones that run at the same pace whatever was selected.
+struct ProgressPlan {
+    phases: HashMap<&'static str, (f32, f32)>,
+}
+
+impl ProgressPlan {
+    const START: f32 = 0.08;
+    const END: f32 = 0.95;
+
+    fn new(runs: impl Fn(&str) -> bool) -> Self {
+        let total: f32 = PHASE_WEIGHTS
+            .iter()
+            .filter(|(step, _)| runs(step))
+            .map(|(_, w)| w)
+            .sum();
+        let mut at = Self::START;
+        let mut phases = HashMap::new();
+        for (step, weight) in PHASE_WEIGHTS {
+            let width = if runs(step) {
+                (Self::END - Self::START) * weight / total
+            } else {
+                0.0
+            };
+            phases.insert(step, (at, width));
+            at += width;
+        }
+        Self { phases }
+    }
+
+    /// Progress `fraction` of the way through `step`.
+    fn at(&self, step: &str, fraction: f32) -> f32 {
+        self.phases
+            .get(step)
+            .map_or(Self::END, |(start, width)| start + width * fraction)
+    }
+
+    fn start(&self, step: &str) -> f32 {
+        self.at(step, 0.0)
+    }
+}
+
+/// Receives an import's progress and outcome.
+pub trait ProgressSink: Sync {
+    fn progress(&self, progress: IngestionProgress);
+
+    /// Whether the import should stop. Checked between zip entries, chat files and phases.
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+
+    /// The import finished; called once, before the final progress update.
+    fn result(&self, _result: &IngestionResult) {}
+
+    /// The import failed and what it wrote was rolled back.
+    fn failed(&self, _failure: IngestionFailure) {}
+}
+
+/// Discards progress, for callers that only want the result.
+impl ProgressSink for () {
+    fn progress(&self, _progress: IngestionProgress) {}
+}
+
+/// Prints progress to stderr, one line per step or whole percent so a long phase doesn't flood
+/// the terminal.
+#[derive(Default)]
+pub struct StderrProgress {
+    last: Mutex<Option<(String, u32)>>,
+}
+
+impl ProgressSink for StderrProgress {
+    fn progress(&self, progress: IngestionProgress) {
+        let percent = (progress.progress.clamp(0.0, 1.0) * 100.0).round() as u32;
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        if last
+            .as_ref()
+            .is_some_and(|(step, p)| *step == progress.current_step && *p == percent)
+        {
+            return;
+        }
+        eprintln!("[{:>3}%] {}: {}", percent, progress.current_step, progress.message);
+        *last = Some((progress.current_step, percent));
+    }
+
+    fn failed(&self, failure: IngestionFailure) {
+        eprintln!("Import of {} failed: {}", failure.export_id, failure.error);
+    }
+}
+
+/// The configured directory zip exports are extracted under, if any. A configured
+/// `extraction_path` is used as-is; the legacy `storage_path` gets an `Exports` subfolder so it
+/// doesn't mix with downloaded memories.
+pub fn configured_extraction_root(database: &DatabaseManager) -> AppResult<Option<(PathBuf, PathSource)>> {
+    Ok(match database.get_path_setting("extraction_path")? {
+        Some((path, PathSource::LegacyStoragePath)) => Some((path.join("Exports"), PathSource::LegacyStoragePath)),
+        resolved => resolved,
+    })
+}
+
+/// Extract (for zips) and import every phase in `options` of `export`, recording the run in the
+/// ingestion history. A zip export reuses the extraction directory recorded for it, or gets a
+/// new one under `working_dir`. On failure everything the run wrote is rolled back, and an
+/// extraction directory it created is removed unless `keep_extracted_on_failure` is set.
+pub fn import_export(
+    database: &DatabaseManager,
+    mut export: ExportSet,
+    working_dir: &Path,
+    options: ImportOptions,
+    keep_extracted_on_failure: bool,
+    sink: &dyn ProgressSink,
+) -> AppResult<IngestionResult> {
+    fs::create_dir_all(working_dir)?;
+
+    let mut run = database.begin_ingestion_run(&export.id)?;
+    // Where an earlier import of the same export was extracted
+    let previous_extraction = match export.source_type {
+        ExportSourceType::Zip => database
+            .get_exports()?
+            .into_iter()
+            .find(|e| e.id == export.id)
+            .and_then(|e| e.extraction_path),
+        ExportSourceType::Folder => None,
+    };
+    // A reimport reuses the directory recorded for the export, or an earlier complete extraction
+    // of the same zips; anything else gets its own
+    let extraction_dir = match export.source_type {
+        ExportSourceType::Zip => {
+            let dir = export
+                .extraction_path
+                .clone()
+                .or_else(|| {
+                    previous_extraction
+                        .clone()
+                        .filter(|dir| ZipExtractor::is_reusable(dir, &export.source_paths))
+                })
+                .unwrap_or_else(|| {
+                    working_dir.join(extractor::working_dir_name(
+                        &export.id,
+                        &export.source_paths,
+                        run.started_at,
+                    ))
+                });
+            export.extraction_path = Some(dir.clone());
+            dir
+        }
+        ExportSourceType::Folder => working_dir.join(&export.id),
+    };
+    // Only an extraction directory created by this run is ours to delete; a cancelled run
+    // removes it even when failed runs keep theirs
+    let created_extraction = export.source_type == ExportSourceType::Zip && !extraction_dir.exists();
+
+    let outcome = (|| {
+        // Extract zips if needed (heavy I/O)
+        let working_path = if export.source_type == ExportSourceType::Zip {
+            ZipExtractor::extract(&export.source_paths, &extraction_dir, &export.id, sink)?.extraction_path
+        } else {
+            // For folders, we use the first path as the primary (usually the one containing index.html)
+            export
+                .source_paths
+                .first()
+                .cloned()
+                .ok_or_else(|| AppError::Generic("No source paths provided".into()))?
+        };
+        reconstruct_from_path(
+            database,
+            &mut run,
+            export.clone(),
+            working_path,
+            options,
+            ImportOptions::NONE,
+            sink,
+        )
+    })();
+
+    match outcome {
+        Ok(result) => {
+            // Fingerprint the sources so a later "has anything changed?" check has a baseline
+            match artifacts::scan_artifacts(&export) {
+                Ok(found) => {
+                    if let Err(e) = database.replace_export_artifacts(&export.id, &found) {
+                        log::warn!("Could not record export artifacts: {}", e);
+                    }
+                }
+                Err(e) => log::warn!("Could not fingerprint export sources: {}", e),
+            }
+            if let Err(e) = database.record_ingestion_run(&run, IngestionRunStatus::Completed, None, None) {
+                log::warn!("Could not record ingestion history: {}", e);
+            }
+            // Only now that the new import points at its own files is the earlier extraction unused
+            if let Some(previous) = previous_extraction.filter(|dir| *dir != extraction_dir) {
+                match ZipExtractor::remove_extraction(&previous) {
+                    Ok(true) => log::info!("Removed the previous extraction of {}", export.id),
+                    Ok(false) => {}
+                    Err(e) => log::warn!("Could not remove the previous extraction: {}", e),
+                }
+            }
+            Ok(result)
+        }
+        Err(e) => {
+            let remove_extraction =
+                created_extraction && (!keep_extracted_on_failure || matches!(e, AppError::Cancelled(_)));
+            clean_up_failed_ingestion(
+                database,
+                &run,
+                remove_extraction.then_some(extraction_dir.as_path()),
+                &e,
+                sink,
+            );
+            Err(e)
+        }
+    }
+}
+
+/// Roll back what a failed or cancelled run wrote, remove its extraction directory, and report
+/// it: a failure through `ProgressSink::failed`, a cancellation as a final "Cancelled" step.
+/// Cleanup problems are logged; the original error is what the caller sees.
+pub fn clean_up_failed_ingestion(
+    database: &DatabaseManager,
+    run: &IngestionRun,
+    extraction_dir: Option<&Path>,
+    error: &AppError,
+    sink: &dyn ProgressSink,
+) {
+    let cancelled = matches!(error, AppError::Cancelled(_));
+    if cancelled {
+        log::info!("Ingestion of {} cancelled; rolling back", run.export_id);
+    } else {
+        log::error!("Ingestion of {} failed: {}", run.export_id, error);
+    }
+    let mut cleanup = database.rollback_ingestion_run(run).unwrap_or_else(|e| {
+        log::error!("Rolling back failed ingestion of {} failed: {}", run.export_id, e);
+        Default::default()
+    });
+    if let Some(dir) = extraction_dir.filter(|d| d.exists()) {
+        match fs::remove_dir_all(dir) {
+            Ok(()) => cleanup.extraction_removed = true,
+            Err(e) => log::warn!("Could not remove extraction directory {:?}: {}", dir, e),
+        }
+    }
+    log::info!("Failed ingestion cleanup: {:?}", cleanup);
+
+    let status = if cancelled {
+        IngestionRunStatus::Cancelled
+    } else {
+        IngestionRunStatus::Failed
+    };
+    if let Err(e) = database.record_ingestion_run(run, status, Some(&error.to_string()), Some(&cleanup)) {
+        log::warn!("Could not record ingestion history: {}", e);
+    }
+    if cancelled {
+        sink.progress(IngestionProgress {
+            export_id: run.export_id.clone(),
+            current_step: "Cancelled".to_string(),
+            progress: 0.0,
+            message: format!(
+                "Import cancelled; removed {} messages and {} memories it had added",
+                cleanup.events_removed, cleanup.memories_removed
+            ),
+        });
+        return;
+    }
+    sink.failed(IngestionFailure {
+        export_id: run.export_id.clone(),
+        error: error.to_string(),
+        cleanup,
+    });
+}
+
+/// Stop with `AppError::Cancelled` once the sink asks the import to.
+pub(crate) fn check_cancelled(sink: &dyn ProgressSink) -> AppResult<()> {
+    if sink.is_cancelled() {
+        log::info!("Import cancelled");
+        return Err(AppError::Cancelled("import stopped".into()));
+    }
+    Ok(())
+}
+
+/// Attach a damaged-file classification from a parser error to the run.
+fn note_corrupt_file(run: &mut IngestionRun, error: &AppError) {
+    if let AppError::CorruptFile(issue) = error {
+        run.note_file_issue(issue.clone());
+    }
+}
+
+/// Save a conversation's events parsed from a json/ file, creating the conversation when the
+/// chat files did not have it.
+fn add_json_conversation(
+    writer: &mut EventWriter,
+    run: &mut IngestionRun,
+    chat_sources: &mut HashMap<String, BTreeSet<ChatSource>>,
+    convo_key: String,
+    events: Vec<Event>,
+) -> AppResult<()> {
+    chat_sources
+        .entry(convo_key.clone())
+        .or_default()
+        .insert(ChatSource::Json);
+    if !writer.conversation_ids().contains(&convo_key) {
+        let conversation = Conversation {
+            id: convo_key,
+            display_name: None,
+            participants: Vec::new(),
+            last_event_at: events.last().map(|e| e.timestamp),
+            message_count: events.len() as i32,
+            has_media: false,
+            media_count: 0,
+            media_bytes: 0,
+            missing_media_count: 0,
+            avatar_path: None,
+            avatar_color: None,
+            is_group: false,
+            listed_members: 0,
+            anomaly_flags: Vec::new(),
+            saved_count: 0,
+            shared_location_count: 0,
+            completeness: None,
+        };
+        writer.add_conversations(run, vec![conversation])?;
+    }
+    writer.push(run, events)
+}
+
+/// Keep reading a streamed json/ file while saving what it yields works; otherwise remember
+/// the error for after the parser returns.
+fn stop_on_error(result: AppResult<()>, failure: &mut Option<AppError>) -> ControlFlow<()> {
+    match result {
+        Ok(()) => ControlFlow::Continue(()),
+        Err(e) => {
+            *failure = Some(e);
+            ControlFlow::Break(())
+        }
+    }
+}
+
+/// Import `options`' phases of an export from its extracted files. `done` lists the phases an
+/// earlier partial import already ran; those are skipped.
+pub fn reconstruct_from_path(
+    database: &DatabaseManager,
+    run: &mut IngestionRun,
+    original_export: ExportSet,
+    source_path: PathBuf,
+    options: ImportOptions,
+    done: ImportOptions,
+    sink: &dyn ProgressSink,
+) -> AppResult<IngestionResult> {
+    let export_id = original_export.id.clone();
+    let mut warnings: Vec<String> = Vec::new();
+    let mut errors: Vec<String> = Vec::new();
+
+    log::info!(
+        "reconstruct_from_path: starting for export_id={}, type={:?}",
+        export_id,
+        original_export.source_type
+    );
+    log::debug!("reconstruct_from_path: source path: {:?}", source_path);
+
+    sink.progress(IngestionProgress {
+        export_id: export_id.clone(),
+        current_step: "Initializing".to_string(),
+        progress: 0.05,
+        message: "Setting up database...".to_string(),
+    });
+
+    // Store original export info (preserves source_path and source_type for reimport)
+    // Mark as Incomplete initially to prevent corruption if process fails mid-way
+    let mut processing_export = original_export.clone();
+    processing_export.validation_status = crate::models::ValidationStatus::Incomplete;
+    processing_export.import_phases = Some(done);
+    // Reuse where the files were found last time unless the export was reorganized since
+    let (layout, redetected) = layout::resolve_layout(original_export.layout.as_ref(), &source_path);
+    if redetected {
+        warnings.push("Some export files moved since the last import; located them again".to_string());
+    }
+    processing_export.layout = Some(layout.clone());
+    database.insert_export(&processing_export)?;
+
+    let todo = options.remaining(&done);
+    let run_chats = todo.chats;
+    // Chats, calls and stories, or snaps: anything that saves messages
+    let run_messages = todo.has_messages();
+    // Linking needs messages, either from this run or an earlier one
+    let link_media = todo.media_linking && (run_messages || done.has_messages());
+    log::info!("reconstruct_from_path: running {:?} (already done: {:?})", todo, done);
+    let plan = ProgressPlan::new(|step| match step {
+        "Parsing Chats" | "Parsing Chat JSON" | "Parsing Story History" => run_chats,
+        // Call history is read alongside snap history
+        "Parsing Snap History" | "Building Search Index" => run_messages,
+        "Linking Media" => run_messages || link_media,
+        "Processing Memories" => todo.memories || todo.location_history,
+        _ => true,
+    });
+    if run_messages && database.has_random_event_ids()? {
+        log::warn!(
+            "Messages imported by older versions have random ids; chats imported again will be added alongside them"
+        );
+    }
+
+    let mut phase_timings: Vec<PhaseTiming> = Vec::new();
+    let mut phase_start = Instant::now();
+
+    // --- Phase: Friends Resolution ---
+    sink.progress(IngestionProgress {
+        export_id: export_id.clone(),
+        current_step: "Resolving Identities".to_string(),
+        progress: plan.start("Resolving Identities"),
+        message: "Resolving friends and contacts...".to_string(),
+    });
+
+    let mut people_by_schema = Vec::new();
+    if let Some(friends_json) = &layout.friends_json {
+        match PersonParser::parse_friends_json(friends_json) {
+            Ok(friends) => {
+                log::info!("Parsed {} people from friends.json", friends.people.len());
+                match friends.by_schema.as_slice() {
+                    []
+                    | [FriendsSchemaCount {
+                        schema: FriendsSchema::Classic,
+                        ..
+                    }] => {}
+                    [only] => log::warn!("friends.json uses the {:?} schema", only.schema),
+                    both => log::warn!("friends.json mixes schemas: {:?}", both),
+                }
+                if let Some(warning) = friends.unrecognized_warning() {
+                    log::warn!("{}", warning);
+                    warnings.push(warning);
+                }
+                database.insert_people(&friends.people)?;
+                people_by_schema = friends.by_schema;
+            }
+            Err(e) => {
+                log::error!("Failed to parse friends.json: {}", e);
+                note_corrupt_file(run, &e);
+                warnings.push(format!("Could not parse friends list: {}", e));
+            }
+        }
+    } else {
+        log::debug!("No friends.json found under {:?}", layout.root);
+    }
+
+    phase_timings.push(PhaseTiming::since("Resolving Identities", phase_start, None));
+    phase_start = Instant::now();
+    check_cancelled(sink)?;
+
+    // The account files name the owner; exports whose files only give a display name fall back
+    // to whoever sent the messages marked as the owner's, once they are saved
+    let mut owner = AccountOwner::default();
+    for path in [&layout.account_json, &layout.user_profile_json].into_iter().flatten() {
+        match AccountParser::parse_account_json(path) {
+            Ok(found) => owner = owner.or(found),
+            Err(e) => log::warn!("Could not read {:?}: {}", path.file_name().unwrap_or_default(), e),
+        }
+    }
+
+    // --- Phase: Chat HTML Parsing ---
+    let parse_threads = ingestion::parse_thread_count(database.get_setting("parse_threads")?.as_deref());
+    // Events are saved as each source is read; the writer keeps the conversations
+    let mut writer = EventWriter::new(database, sink, &export_id, owner.username.clone());
+    // Which files each conversation's messages came from, for its completeness summary
+    let mut chat_sources: HashMap<String, BTreeSet<ChatSource>> = HashMap::new();
+    let mut parse_failures = 0;
+
+    // Slash dates read the same both ways round until a field passes 12; settle it once per export
+    let timestamp_format = if run_messages {
+        timestamps::infer_layout_format(&layout)
+    } else {
+        None
+    };
+    let timestamp_hint = timestamp_format.map(|d| d.hint).unwrap_or_default();
+    if let Some(decision) = timestamp_format {
+        log::info!("Reading slash dates as {:?} ({:?})", decision.hint, decision);
+        if decision.ambiguous {
+            warnings.push(format!(
+                "Could not tell whether dates like 01/02/2023 are month or day first ({} sampled); read them as {}",
+                decision.dates_sampled,
+                match decision.hint {
+                    TimestampFormatHint::MonthFirst => "month first",
+                    TimestampFormatHint::DayFirst => "day first",
+                }
+            ));
+        }
+    }
+    run.set_timestamp_format(timestamp_format);
+
+    if !run_chats {
+        log::info!("Skipping chat parsing");
+    } else if let Some(chat_html_dir) = &layout.chat_html_dir {
+        writer.begin_step("Parsing Chats", plan.start("Parsing Chats"), "Reading chat pages...");
+        let entries: Vec<_> = fs::read_dir(chat_html_dir)?.collect::<Result<Vec<_>, _>>()?;
+        let total_files = entries.len();
+        log::info!("Found {} files in chat_history directory", total_files);
+
+        // Continuation files of a chat are parsed in the same batch so they can be merged
+        let mut chats: HashMap<String, Vec<(PathBuf, u64)>> = HashMap::new();
+        for path in entries.iter().map(|entry| entry.path()).filter(|path| {
+            path.is_file()
+                && path.extension().is_some_and(|ext| ext == "html")
+                && path
+                    .file_name()
+                    .is_some_and(|n| n.to_string_lossy().starts_with("subpage_"))
+        }) {
+            let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            chats
+                .entry(ChatParser::conversation_id_from_path(&path))
+                .or_default()
+                .push((path, size));
+        }
+        let mut chats: Vec<Vec<(PathBuf, u64)>> = chats.into_values().collect();
+        // Largest first, so a few huge chats don't end up running alone at the end
+        chats.sort_by_key(|files| std::cmp::Reverse(files.iter().map(|(_, size)| size).sum::<u64>()));
+
+        // A dedicated pool bounds how many DOMs are alive at once, regardless of core count
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(parse_threads)
+            .thread_name(|i| format!("subpage-parse-{}", i))
+            .build()
+            .map_err(|e| AppError::Generic(format!("Failed to start parse threads: {}", e)))?;
+        let subpage_count: usize = chats.iter().map(Vec::len).sum();
+        log::info!("Parsing {} chat subpages on {} threads", subpage_count, parse_threads);
+        let people = database.get_people()?;
+
+        // Each batch is saved before the next is parsed, so only one batch is in memory
+        for batch in chats.chunks(parse_threads * CHATS_PER_PARSE_THREAD) {
+            let paths: Vec<&PathBuf> = batch.iter().flatten().map(|(path, _)| path).collect();
+            let results: Vec<_> = pool.install(|| {
+                paths
+                    .par_iter()
+                    .with_max_len(1)
+                    .map(|path| {
+                        if sink.is_cancelled() {
+                            return ((*path).clone(), Err(AppError::Cancelled("import stopped".into())));
+                        }
+                        ((*path).clone(), ChatParser::parse_subpage(path, timestamp_hint))
+                    })
+                    .collect()
+            });
+            check_cancelled(sink)?;
+
+            let mut parsed_parts = Vec::with_capacity(results.len());
+            for (path, res) in results {
+                match res {
+                    Ok((conv, events)) => parsed_parts.push((path, conv, events)),
+                    Err(e) => {
+                        parse_failures += 1;
+                        log::error!("Failed to parse {:?}: {}", path.file_name(), e);
+                        warnings.push(format!(
+                            "Failed to parse {}: {}",
+                            path.file_name().unwrap_or_default().to_string_lossy(),
+                            e
+                        ));
+                    }
+                }
+            }
+
+            // Long chats are split over subpage_<id>.html, subpage_<id>_2.html, ...
+            let (mut conversations, events): (Vec<Conversation>, Vec<Vec<Event>>) =
+                ChatParser::merge_subpage_parts(parsed_parts).into_iter().unzip();
+            ChatParser::resolve_group_members(&mut conversations, &people);
+            for conv in &conversations {
+                chat_sources
+                    .entry(conv.id.clone())
+                    .or_default()
+                    .insert(ChatSource::Html);
+            }
+            writer.add_conversations(run, conversations)?;
+            for chat in events {
+                writer.push(run, chat)?;
+            }
+        }
+        writer.flush(run)?;
+    } else {
+        log::warn!("Chat history directory not found in export");
+        log::debug!("No html/chat_history under {:?}", layout.root);
+        warnings.push("No chat_history directory found in export".to_string());
+    }
+
+    if parse_failures > 0 {
+        log::warn!("{} chat files failed to parse", parse_failures);
+    }
+
+    phase_timings.push(PhaseTiming::since("Parsing Chats", phase_start, Some(parse_threads)));
+    phase_start = Instant::now();
+    check_cancelled(sink)?;
+
+    // --- Phase: JSON Chat History (Media IDs source) ---
+    writer.begin_step(
+        "Parsing Chat JSON",
+        plan.start("Parsing Chat JSON"),
+        "Extracting media ID mappings from chat history JSON...",
+    );
+
+    if let Some(chat_json) = layout.chat_history_json.as_ref().filter(|_| run_chats) {
+        // Each conversation is merged with its saved chat page events as it is read
+        let mut json_merger = merger::JsonMerger::new(writer.conversation_ids().clone());
+        let mut json_conversation_count = 0;
+        let mut failure = None;
+        let streamed = ChatJsonParser::stream_chat_history_json(chat_json, timestamp_hint, |convo_key, json_events| {
+            json_conversation_count += 1;
+            chat_sources
+                .entry(convo_key.clone())
+                .or_default()
+                .insert(ChatSource::Json);
+            let merged = (|| {
+                let mut saved = database.get_export_conversation_events(&export_id, &convo_key)?;
+                let merge = json_merger.merge_conversation(&mut saved, convo_key, json_events);
+                writer.add_conversations(run, merge.conversation.into_iter().collect())?;
+                writer.rewrite(run, merge.enriched.iter().map(|&idx| saved[idx].clone()).collect())?;
+                writer.push(run, merge.added)
+            })();
+            stop_on_error(merged, &mut failure)
+        });
+        if let Some(e) = failure {
+            return Err(e);
+        }
+        writer.flush(run)?;
+        let stats = json_merger.stats();
+        match streamed {
+            Ok(issue) => {
+                if let Some(issue) = issue {
+                    errors.push(format!("Chat history JSON is damaged: {}", issue));
+                    run.note_file_issue(issue);
+                }
+                log::info!(
+                    "ChatJsonParser: {} conversations, {} events from JSON",
+                    json_conversation_count,
+                    stats.enriched + stats.added
+                );
+                log::info!(
+                    "JSON merge: {} events enriched with media IDs, {} new events added, {} suspected duplicates",
+                    stats.enriched,
+                    stats.added,
+                    stats.suspected_duplicates
+                );
+                if stats.suspected_duplicates > 0 {
+                    warnings.push(format!(
+                        "{} messages from chat_history.json matched a chat page message only outside the merge \
+                         window and were kept as separate messages",
+                        stats.suspected_duplicates
+                    ));
+                }
+            }
+            Err(e) => {
+                log::error!("Failed to parse chat_history.json: {}", e);
+                note_corrupt_file(run, &e);
+                errors.push(format!("Could not parse chat history JSON: {}", e));
+            }
+        }
+    } else {
+        log::debug!("No chat_history.json found under {:?}", layout.root);
+    }
+
+    phase_timings.push(PhaseTiming::since("Parsing Chat JSON", phase_start, None));
+    phase_start = Instant::now();
+    check_cancelled(sink)?;
+
+    // --- Phase: Snap History (JSON) ---
+    writer.begin_step(
+        "Parsing Snap History",
+        plan.start("Parsing Snap History"),
+        "Processing snap history metadata...",
+    );
+
+    if let Some(snap_json) = layout.snap_history_json.as_ref().filter(|_| todo.snap_history) {
+        let mut snap_conversations = 0;
+        let mut snap_event_count = 0;
+        let mut failure = None;
+        let streamed = SnapHistoryParser::stream_snap_history_json(snap_json, timestamp_hint, |convo_key, snaps| {
+            snap_conversations += 1;
+            snap_event_count += snaps.len();
+            let added = add_json_conversation(&mut writer, run, &mut chat_sources, convo_key, snaps);
+            stop_on_error(added, &mut failure)
+        });
+        if let Some(e) = failure {
+            return Err(e);
+        }
+        match streamed {
+            Ok(issue) => {
+                if let Some(issue) = issue {
+                    errors.push(format!("Snap history is damaged: {}", issue));
+                    run.note_file_issue(issue);
+                }
+                log::info!(
+                    "Parsed {} snap history conversations with {} events",
+                    snap_conversations,
+                    snap_event_count
+                );
+            }
+            Err(e) => {
+                log::error!("Failed to parse snap_history.json: {}", e);
+                note_corrupt_file(run, &e);
+                errors.push(format!("Could not parse snap history: {}", e));
+            }
+        }
+    } else if todo.snap_history {
+        log::info!("No snap_history.json found");
+    }
+
+    if let Some(talk_json) = layout.talk_history_json.as_ref().filter(|_| run_chats) {
+        match TalkHistoryParser::parse_talk_history_json(talk_json, timestamp_hint) {
+            Ok(mut calls) => {
+                let logged = calls.event_count();
+                let missed =
+                    database.get_export_events_of_types(&export_id, &["MISSED_VIDEO_CHAT", "MISSED_AUDIO_CHAT"])?;
+                TalkHistoryParser::drop_known_missed_calls(&mut calls, &missed);
+                log::info!(
+                    "Parsed {} call(s) from talk history; {} already shown as missed calls",
+                    logged,
+                    logged - calls.event_count()
+                );
+                for (convo_key, events) in calls.conversations {
+                    add_json_conversation(&mut writer, run, &mut chat_sources, convo_key, events)?;
+                }
+            }
+            Err(e) => {
+                log::error!("Failed to parse talk_history.json: {}", e);
+                note_corrupt_file(run, &e);
+                errors.push(format!("Could not parse call history: {}", e));
+            }
+        }
+    } else if run_chats {
+        log::info!("No talk_history.json found");
+    }
+    writer.flush(run)?;
+
+    phase_timings.push(PhaseTiming::since("Parsing Snap History", phase_start, None));
+    phase_start = Instant::now();
+    check_cancelled(sink)?;
+
+    // --- Phase: Story History (JSON) ---
+    writer.begin_step(
+        "Parsing Story History",
+        plan.start("Parsing Story History"),
+        "Processing posted stories...",
+    );
+
+    if let Some(story_json) = layout.story_history_json.as_ref().filter(|_| run_chats) {
+        let stories_id = ingestion::stories_conversation_id(&export_id);
+        match StoryHistoryParser::parse_story_history_json(story_json, &stories_id, timestamp_hint) {
+            Ok(history) => {
+                if history.unreadable_dates > 0 {
+                    log::warn!("Skipped {} stories without a readable date", history.unreadable_dates);
+                    warnings.push(format!(
+                        "Skipped {} stories whose date could not be read",
+                        history.unreadable_dates
+                    ));
+                }
+                let stories = history.events;
+                if stories.is_empty() {
+                    log::info!("story_history.json holds no stories");
+                } else {
+                    log::info!("Parsed {} stories", stories.len());
+                    chat_sources.entry(stories_id).or_default().insert(ChatSource::Json);
+                    writer.add_conversations(run, vec![ingestion::stories_conversation(&export_id, &stories)])?;
+                    writer.push(run, stories)?;
+                }
+            }
+            Err(e) => {
+                log::error!("Failed to parse story_history.json: {}", e);
+                note_corrupt_file(run, &e);
+                errors.push(format!("Could not parse story history: {}", e));
+            }
+        }
+    } else if run_chats {
+        log::info!("No story_history.json found");
+    }
+    writer.flush(run)?;
+
+    if writer.shared_locations() > 0 {
+        log::info!("Found {} shared location(s) in messages", writer.shared_locations());
+    }
+    if writer.redacted() > 0 {
+        log::info!("Skipped {} redacted message(s)", writer.redacted());
+    }
+
+    phase_timings.push(PhaseTiming::since("Parsing Story History", phase_start, None));
+    phase_start = Instant::now();
+    check_cancelled(sink)?;
+
+    if owner.username.is_none() && run_messages {
+        owner.username = database.most_frequent_own_sender(&export_id)?;
+        if let Some(username) = &owner.username {
+            // Stories and outgoing calls are logged without naming the owner
+            database.fill_own_sender(&export_id, username)?;
+        }
+    }
+    if let Some(username) = owner.username {
+        log::debug!("Account owner: {} ({:?})", username, owner.display_name);
+        let stories_id = ingestion::stories_conversation_id(&export_id);
+        if let Some(stories) = writer.conversations_mut().iter_mut().find(|c| c.id == stories_id) {
+            stories.participants = vec![username.clone()];
+        }
+        database.set_owner_profile(&OwnerProfile {
+            username,
+            display_name: owner.display_name,
+        })?;
+    } else {
+        log::info!("Could not identify the account owner");
+    }
+
+    // --- Phase: Media Linking ---
+    sink.progress(IngestionProgress {
+        export_id: export_id.clone(),
+        current_step: "Linking Media".to_string(),
+        progress: plan.start("Linking Media"),
+        message: "Resolving media file references...".to_string(),
+    });
+
+    // One saved conversation at a time: link its media, and tally what the final conversation
+    // update and the anomaly check need
+    let mut linker = if link_media {
+        let mut linker = MediaLinker::for_layout(&layout, &database.get_media_linking_settings()?);
+        // Files some message names by ID stay out of every conversation's date pass
+        linker.reserve_media_ids(&database.get_referenced_media_ids()?);
+        Some(linker)
+    } else {
+        None
+    };
+    let mut relinked = 0;
+    let mut last_activity: HashMap<String, chrono::DateTime<chrono::Utc>> = HashMap::new();
+    let mut media_stats = HashMap::new();
+    let mut anomalies = Vec::new();
+    if run_messages || linker.is_some() {
+        for conversation_id in database.get_export_conversation_ids(&export_id)? {
+            check_cancelled(sink)?;
+            let mut events = database.get_export_conversation_events(&export_id, &conversation_id)?;
+            if let Some(linker) = linker.as_mut() {
+                let unlinked: Vec<bool> = events.iter().map(|e| e.media_references.is_empty()).collect();
+                linker.link_media(&mut events);
+                let linked: Vec<Event> = events
+                    .iter()
+                    .zip(unlinked)
+                    .filter(|(e, was_unlinked)| *was_unlinked && !e.media_references.is_empty())
+                    .map(|(e, _)| e.clone())
+                    .collect();
+                if !linked.is_empty() {
+                    relinked += linked.len();
+                    database.update_event_media_references(&linked)?;
+                }
+            }
+            if run_messages {
+                media_stats.extend(MediaLinker::tally_media(&events));
+                anomalies.extend(ingestion::anomalies::detect_anomalies(&events));
+                if let Some(last) = events.iter().map(|e| e.timestamp).max() {
+                    last_activity.insert(conversation_id, last);
+                }
+            }
+        }
+        if let Some(linker) = &linker {
+            log::info!(
+                "Linked media for {} message(s): {} by Media ID, {} by file date",
+                relinked,
+                linker.linked_by_id(),
+                linker.linked_by_date()
+            );
+        }
+    }
+    let (media_linked_by_id, media_linked_by_date) = linker
+        .as_ref()
+        .map_or((0, 0), |l| (l.linked_by_id(), l.linked_by_date()));
+
+    for conv in writer.conversations_mut() {
+        if let Some(ts) = last_activity.get(&conv.id) {
+            conv.last_event_at = Some(*ts);
+        }
+        if let Some(media) = media_stats.get(&conv.id) {
+            conv.media_count = media.media_count;
+            conv.media_bytes = media.media_bytes;
+            conv.missing_media_count = media.missing_media_count;
+        }
+    }
+
+    phase_timings.push(PhaseTiming::since("Linking Media", phase_start, None));
+    phase_start = Instant::now();
+    check_cancelled(sink)?;
+
+    // --- Phase: Memories Parsing ---
+    sink.progress(IngestionProgress {
+        export_id: export_id.clone(),
+        current_step: "Processing Memories".to_string(),
+        progress: plan.start("Processing Memories"),
+        message: "Parsing memories history...".to_string(),
+    });
+
+    let mut all_memories = Vec::new();
+    if !todo.memories {
+        log::info!("Skipping memories");
+    } else if let Some(memories_json) = &layout.memories_json {
+        match MemoryParser::parse_memories_json(memories_json, &export_id) {
+            Ok(memories) => {
+                log::info!("Parsed {} memories", memories.len());
+                all_memories = memories;
+            }
+            Err(e) => {
+                log::error!("Failed to parse memories_history.json: {}", e);
+                note_corrupt_file(run, &e);
+                errors.push(format!("Could not parse memories: {}", e));
+            }
+        }
+    } else {
+        log::info!("No memories_history.json found");
+    }
+
+    let mut all_locations = Vec::new();
+    if let Some(location_json) = layout.location_history_json.as_ref().filter(|_| todo.location_history) {
+        match LocationHistoryParser::parse_location_history_json(location_json, &export_id, timestamp_hint) {
+            Ok(points) => {
+                log::info!("Parsed {} location history point(s)", points.len());
+                all_locations = points;
+            }
+            Err(e) => {
+                log::error!("Failed to parse location_history.json: {}", e);
+                note_corrupt_file(run, &e);
+                errors.push(format!("Could not parse location history: {}", e));
+            }
+        }
+    }
+
+    phase_timings.push(PhaseTiming::since("Processing Memories", phase_start, None));
+    phase_start = Instant::now();
+    check_cancelled(sink)?;
+
+    // --- Phase: Save to Database ---
+    sink.progress(IngestionProgress {
+        export_id: export_id.clone(),
+        current_step: "Saving to Database".to_string(),
+        progress: plan.start("Saving to Database"),
+        message: format!(
+            "Indexing {} conversations, {} messages, {} memories...",
+            writer.conversations().len(),
+            writer.written(),
+            all_memories.len()
+        ),
+    });
+
+    // Conversations were saved as they were found; this fills in their activity and media
+    let conversations = writer.conversations();
+    database.batch_insert_conversations(conversations)?;
+    database.assign_conversation_exports()?;
+
+    if !all_memories.is_empty() {
+        // Memories a reimport updates in place aren't the run's to remove on rollback
+        let ids: Vec<String> = all_memories.iter().map(|m| m.id.clone()).collect();
+        let existing = database.existing_memory_ids(&ids)?;
+        let added: Vec<Memory> = all_memories
+            .iter()
+            .filter(|m| !existing.contains(&m.id))
+            .cloned()
+            .collect();
+        run.track_memories(&added);
+        database.batch_insert_memories(&all_memories)?;
+    }
+    if !all_locations.is_empty() {
+        let added = database.insert_location_history(&all_locations)?;
+        log::info!("Stored {} new location history point(s)", added.len());
+        run.track_locations(added);
+    }
+
+    if run_messages {
+        let mut usernames: std::collections::HashSet<String> =
+            database.get_people()?.into_iter().map(|p| p.username).collect();
+        for conversation in conversations {
+            usernames.insert(conversation.id.clone());
+            usernames.extend(conversation.participants.iter().cloned());
+        }
+        let avatars = ingestion::avatars::find_avatars(&source_path, &usernames);
+        if !avatars.is_empty() {
+            log::info!("Found avatar images for {} people", avatars.len());
+            database.set_avatar_paths(&avatars)?;
+        }
+    }
+    log::info!(
+        "Conversations: {} created, {} merged into existing",
+        run.created_conversations().len(),
+        run.merged_conversations().len()
+    );
+    // Merged conversations also hold media from earlier imports, so recount from the database
+    if !run.merged_conversations().is_empty() || (!run_messages && relinked > 0) {
+        refresh_media_stats_for(database)?;
+    }
+
+    phase_timings.push(PhaseTiming::since("Saving to Database", phase_start, None));
+    phase_start = Instant::now();
+    check_cancelled(sink)?;
+
+    // --- Phase: Search Index ---
+    if run_messages {
+        database.populate_fts_for_export(
+            &export_id,
+            || sink.is_cancelled(),
+            |p| {
+                let fraction = p.indexed as f32 / p.total.max(1) as f32;
+                sink.progress(IngestionProgress {
+                    export_id: export_id.clone(),
+                    current_step: "Building Search Index".to_string(),
+                    progress: plan.at("Building Search Index", fraction),
+                    message: format!("Building search index {}%", (fraction * 100.0).round()),
+                });
+            },
+        )?;
+        check_cancelled(sink)?;
+        phase_timings.push(PhaseTiming::since("Building Search Index", phase_start, None));
+    }
+
+    // Conversations whose data looks mangled (epoch dates, duplicates, ...) were flagged above
+    if run_messages {
+        database.replace_validation_issues(&export_id, &anomalies)?;
+        for anomaly in &anomalies {
+            log::warn!("Anomaly in {}: {}", anomaly.conversation_id, anomaly.detail);
+        }
+        let flagged: std::collections::HashSet<&str> = anomalies.iter().map(|a| a.conversation_id.as_str()).collect();
+        if !flagged.is_empty() {
+            warnings.push(format!(
+                "{} conversation(s) have suspicious data after import",
+                flagged.len()
+            ));
+        }
+        database.refresh_conversation_completeness(&chat_sources)?;
+    }
+    log::info!("Phase timings: {:?}", phase_timings);
+
+    // The export row was marked Incomplete while importing; settle on the detected status,
+    // lowered if any json/ file turned out to be damaged
+    let final_status = run
+        .file_issues()
+        .iter()
+        .fold(original_export.validation_status.clone(), |status, issue| {
+            status.downgrade(issue.status())
+        });
+    database.set_export_validation_status(&export_id, &final_status)?;
+    let phases = done.union(&ImportOptions {
+        media_linking: link_media,
+        ..todo
+    });
+    database.set_import_phases(&export_id, &phases)?;
+
+    log::info!(
+        "Ingestion complete: {} conversations, {} events, {} memories, {} warnings, {} errors",
+        writer.conversations().len(),
+        writer.written(),
+        all_memories.len(),
+        warnings.len(),
+        errors.len()
+    );
+
+    // Emit the detailed result
+    let result = IngestionResult {
+        export_id: export_id.clone(),
+        conversations_parsed: writer.conversations().len() as i32,
+        events_parsed: writer.written() as i32,
+        memories_parsed: all_memories.len() as i32,
+        parse_failures,
+        warnings: warnings.clone(),
+        errors: errors.clone(),
+        phase_timings,
+        anomalies,
+        people_by_schema,
+        media_linked_by_id: media_linked_by_id as i32,
+        media_linked_by_date: media_linked_by_date as i32,
+    };
+    sink.result(&result);
+
+    sink.progress(IngestionProgress {
+        export_id: export_id.clone(),
+        current_step: "Complete".to_string(),
+        progress: 1.0,
+        message: format!(
+            "Indexed {} conversations, {} messages, {} memories.",
+            writer.conversations().len(),
+            writer.written(),
+            all_memories.len()
+        ),
+    });
+
+    Ok(result)
+}
+
+/// Recount every conversation's media totals from the database, statting each linked file.
+pub fn refresh_media_stats_for(db: &DatabaseManager) -> AppResult<usize> {
+    let stats = MediaLinker::tally_media(&db.get_media_events()?);
+    db.set_conversation_media_stats(&stats)?;
+    Ok(stats.len())
+}
+
+/// Link the media files under `media_dir` to messages whose media wasn't found when they were
+/// imported, such as after the export moved or a missing part was extracted.
+pub fn relink_media_for(db: &DatabaseManager, media_dir: &Path) -> AppResult<RelinkSummary> {
+    let mut linker = MediaLinker::with_settings(&db.get_media_linking_settings()?);
+    linker.add_media_directory(media_dir);
+    linker.reserve_media_ids(&db.get_referenced_media_ids()?);
+    let mut summary = RelinkSummary::default();
+    let mut after = String::new();
+    loop {
+        let mut events = db.get_unlinked_media_events(&after, RELINK_BATCH_SIZE)?;
+        let Some(last) = events.last() else {
+            break;
+        };
+        after = last.id.clone();
+        linker.link_media(&mut events);
+        let (linked, missing): (Vec<Event>, Vec<Event>) =
+            events.into_iter().partition(|e| !e.media_references.is_empty());
+        summary.still_missing += missing.len();
+        summary.linked += db.update_event_media_references(&linked)?;
+    }
+    log::info!(
+        "Relinked media for {} message(s); {} still missing",
+        summary.linked,
+        summary.still_missing
+    );
+    if summary.linked > 0 {
+        refresh_media_stats_for(db)?;
+    }
+    Ok(summary)
+}
+
+/// Recount media totals, then recompute every conversation's completeness summary.
+pub fn refresh_conversation_stats_for(db: &DatabaseManager) -> AppResult<usize> {
+    refresh_media_stats_for(db)?;
+    db.refresh_conversation_completeness(&HashMap::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+    use std::io::Write;
+
+    /// Keeps every update, so tests can check what a caller would have been told. With
+    /// `cancel_at` set, asks the import to stop once that step has been reported.
+    #[derive(Default)]
+    struct RecordingSink {
+        steps: Mutex<Vec<IngestionProgress>>,
+        results: Mutex<Vec<IngestionResult>>,
+        cancel_at: Option<&'static str>,
+    }
+
+    impl ProgressSink for RecordingSink {
+        fn progress(&self, progress: IngestionProgress) {
+            self.steps.lock().unwrap().push(progress);
+        }
+
+        fn is_cancelled(&self) -> bool {
+            self.cancel_at
+                .is_some_and(|step| self.steps.lock().unwrap().iter().any(|p| p.current_step == step))
+        }
+
+        fn result(&self, result: &IngestionResult) {
+            self.results.lock().unwrap().push(result.clone());
+        }
+    }
+
+    /// A folder export with the fixture chats as subpages.
+    fn write_folder_export(root: &Path) -> ExportSet {
+        let export_dir = root.join("mydata~fixture");
+        let chat_dir = export_dir.join("html").join("chat_history");
+        fs::create_dir_all(&chat_dir).unwrap();
+        fs::write(export_dir.join("index.html"), "<html></html>").unwrap();
+        for id in test_fixtures::CONVERSATION_IDS {
+            fs::write(
+                chat_dir.join(format!("subpage_{}.html", id)),
+                test_fixtures::subpage_html(id),
+            )
+            .unwrap();
+        }
+        let mut export = test_fixtures::export();
+        export.source_paths = vec![export_dir];
+        export
+    }
+
+    #[test]
+    fn test_imports_a_folder_export_without_an_app_handle() {
+        let dir = tempfile::tempdir().unwrap();
+        let export = write_folder_export(dir.path());
+
+        let db = DatabaseManager::new_in_memory().unwrap();
+        let sink = RecordingSink::default();
+        let result = import_export(&db, export, &dir.path().join("work"), ImportOptions::ALL, false, &sink).unwrap();
+
+        assert_eq!(result.conversations_parsed as usize, test_fixtures::CONVERSATION_COUNT);
+        assert_eq!(result.events_parsed as usize, test_fixtures::EVENT_COUNT);
+        assert_eq!(
+            db.get_conversations(None).unwrap().len(),
+            test_fixtures::CONVERSATION_COUNT
+        );
+        assert_eq!(sink.results.lock().unwrap().len(), 1);
+        let steps = sink.steps.lock().unwrap();
+        assert_eq!(steps.last().map(|p| p.current_step.as_str()), Some("Complete"));
+        assert!(steps.windows(2).all(|w| w[0].progress <= w[1].progress));
+        assert_eq!(db.get_ingestion_history(10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_chat_json_is_merged_into_saved_chat_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let export = write_folder_export(dir.path());
+        let first = test_fixtures::events().remove(0);
+        let created = first.timestamp.format("%Y-%m-%d %H:%M:%S UTC");
+        let json_dir = export.source_paths[0].join("json");
+        fs::create_dir_all(&json_dir).unwrap();
+        let chat_json = serde_json::json!({
+            "alice": [{"From": first.sender, "Media Type": "TEXT", "Created": created.to_string(),
+                       "Content": first.content, "IsSaved": true}],
+            "carol": [{"From": "carol", "Media Type": "TEXT", "Created": created.to_string(), "Content": "only here"}],
+        });
+        fs::write(json_dir.join("chat_history.json"), chat_json.to_string()).unwrap();
+
+        let db = DatabaseManager::new_in_memory().unwrap();
+        let sink = RecordingSink::default();
+        let result = import_export(&db, export, &dir.path().join("work"), ImportOptions::ALL, false, &sink).unwrap();
+
+        assert_eq!(result.events_parsed as usize, test_fixtures::EVENT_COUNT + 1);
+        assert_eq!(
+            result.conversations_parsed as usize,
+            test_fixtures::CONVERSATION_COUNT + 1
+        );
+        let alice = db.get_export_conversation_events(&result.export_id, "alice").unwrap();
+        assert_eq!(alice.len(), test_fixtures::EVENT_COUNT * 2 / 5);
+        assert!(alice.iter().find(|e| e.content == first.content).unwrap().is_saved());
+        assert_eq!(
+            db.get_export_conversation_events(&result.export_id, "carol")
+                .unwrap()
+                .len(),
+            1
+        );
+        let steps = sink.steps.lock().unwrap();
+        assert!(steps
+            .iter()
+            .any(|p| p.message == format!("Saved {} messages", test_fixtures::EVENT_COUNT)));
+        assert!(steps.windows(2).all(|w| w[0].progress <= w[1].progress));
+    }
+
+    #[test]
+    fn test_import_runs_only_the_selected_phases() {
+        let dir = tempfile::tempdir().unwrap();
+        let export = write_folder_export(dir.path());
+        let json_dir = export.source_paths[0].join("json");
+        fs::create_dir_all(&json_dir).unwrap();
+        let snaps = serde_json::json!({
+            "dave": [{"From": "dave", "Media Type": "IMAGE", "Created": "2023-06-15 10:30:00 UTC", "IsSender": false}],
+        });
+        fs::write(json_dir.join("snap_history.json"), snaps.to_string()).unwrap();
+
+        let db = DatabaseManager::new_in_memory().unwrap();
+        let sink = RecordingSink::default();
+        let snaps_only = ImportOptions {
+            snap_history: true,
+            ..ImportOptions::NONE
+        };
+        let result = import_export(&db, export, &dir.path().join("work"), snaps_only, false, &sink).unwrap();
+
+        // The chat pages are left alone
+        assert_eq!(result.events_parsed, 1);
+        assert_eq!(db.get_conversations(None).unwrap().len(), 1);
+        assert_eq!(db.get_exports().unwrap()[0].import_phases, Some(snaps_only));
+
+        // Skipped phases take no room on the progress bar
+        let steps = sink.steps.lock().unwrap();
+        let start = |step: &str| steps.iter().find(|p| p.current_step == step).unwrap().progress;
+        assert_eq!(start("Parsing Chat JSON"), start("Parsing Snap History"));
+        assert!(start("Parsing Snap History") < 0.2);
+        assert!(start("Saving to Database") < 0.6);
+        assert!(steps.windows(2).all(|w| w[0].progress <= w[1].progress));
+        assert_eq!(steps.last().map(|p| p.progress), Some(1.0));
+    }
+
+    #[test]
+    fn test_location_history_is_imported_without_memories() {
+        let dir = tempfile::tempdir().unwrap();
+        let export = write_folder_export(dir.path());
+        let json_dir = export.source_paths[0].join("json");
+        fs::create_dir_all(&json_dir).unwrap();
+        let locations = serde_json::json!({
+            "Latest Location": [{"City": "Paris", "Time": "2023-06-15 10:00:00 UTC",
+                                 "Location": "Latitude, Longitude: 48.8584, 2.2945"}],
+        });
+        fs::write(json_dir.join("location_history.json"), locations.to_string()).unwrap();
+
+        let import = |options: ImportOptions, work: &str| {
+            let db = DatabaseManager::new_in_memory().unwrap();
+            import_export(&db, export.clone(), &dir.path().join(work), options, false, &()).unwrap();
+            db.get_location_history(None, None, None).unwrap().len()
+        };
+        let no_memories = ImportOptions {
+            memories: false,
+            ..ImportOptions::ALL
+        };
+        let no_locations = ImportOptions {
+            location_history: false,
+            ..ImportOptions::ALL
+        };
+        assert_eq!(import(no_memories, "work_a"), 1);
+        assert_eq!(import(no_locations, "work_b"), 0);
+    }
+
+    #[test]
+    fn test_reimport_of_a_changed_zip_removes_the_earlier_extraction() {
+        let dir = tempfile::tempdir().unwrap();
+        let zip_path = dir.path().join("mydata~fixture.zip");
+        let write_zip = |conversations: &[&str]| {
+            let mut writer = zip::ZipWriter::new(fs::File::create(&zip_path).unwrap());
+            let options = zip::write::SimpleFileOptions::default();
+            writer.start_file("index.html", options).unwrap();
+            writer.write_all(b"<html></html>").unwrap();
+            for id in conversations {
+                writer
+                    .start_file(format!("html/chat_history/subpage_{}.html", id), options)
+                    .unwrap();
+                writer.write_all(test_fixtures::subpage_html(id).as_bytes()).unwrap();
+            }
+            writer.finish().unwrap();
+        };
+        let export = ExportSet {
+            source_type: ExportSourceType::Zip,
+            source_paths: vec![zip_path.clone()],
+            ..test_fixtures::export()
+        };
+        let work = dir.path().join("work");
+        let db = DatabaseManager::new_in_memory().unwrap();
+
+        write_zip(&test_fixtures::CONVERSATION_IDS[..1]);
+        import_export(&db, export.clone(), &work, ImportOptions::ALL, false, &()).unwrap();
+        let first = db.get_exports().unwrap()[0].extraction_path.clone().unwrap();
+        assert!(first.is_dir());
+
+        // Importing the unchanged zip again reuses its extraction
+        import_export(&db, export.clone(), &work, ImportOptions::ALL, false, &()).unwrap();
+        assert_eq!(db.get_exports().unwrap()[0].extraction_path.as_ref(), Some(&first));
+
+        write_zip(&test_fixtures::CONVERSATION_IDS);
+        import_export(&db, export, &work, ImportOptions::ALL, false, &()).unwrap();
+        let current = db.get_exports().unwrap()[0].extraction_path.clone().unwrap();
+        assert!(current.join("html/chat_history/subpage_bob.html").is_file());
+        let extractions: Vec<PathBuf> = fs::read_dir(&work).unwrap().map(|e| e.unwrap().path()).collect();
+        assert_eq!(extractions, [current]);
+    }
+
+    #[test]
+    fn test_relink_media_links_files_from_another_folder() {
+        let db = test_fixtures::standard_db();
+        let unlinked: Vec<Event> = test_fixtures::events()
+            .into_iter()
+            .filter(|e| e.event_type == "MEDIA")
+            .map(|mut e| {
+                e.media_references.clear();
+                e.metadata = Some(format!(r#"{{"media_ids": ["ID{}"]}}"#, &e.id[e.id.len() - 3..]));
+                e
+            })
+            .collect();
+        db.batch_insert_events(&unlinked, test_fixtures::EXPORT_ID).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("chat_media").join("part2");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(nested.join("2023-01-01_ID009.jpg"), b"jpg").unwrap();
+        fs::write(nested.join("2023-01-01_ID019.mp4"), b"mp4").unwrap();
+
+        let summary = relink_media_for(&db, dir.path()).unwrap();
+        assert_eq!(
+            summary,
+            RelinkSummary {
+                linked: 2,
+                still_missing: 3
+            }
+        );
+        let events = db
+            .get_export_conversation_events(test_fixtures::EXPORT_ID, test_fixtures::CONVERSATION_IDS[2])
+            .unwrap();
+        let linked = events.iter().find(|e| e.id == "fixture_event_019").unwrap();
+        assert!(linked.media_references[0].ends_with("2023-01-01_ID019.mp4"));
+
+        // Running it again only looks at what is still missing
+        assert_eq!(
+            relink_media_for(&db, dir.path()).unwrap(),
+            RelinkSummary {
+                linked: 0,
+                still_missing: 3
+            }
+        );
+    }
+
+    #[test]
+    fn test_cancelled_import_rolls_back_and_reports_cancelled() {
+        let dir = tempfile::tempdir().unwrap();
+        let export = write_folder_export(dir.path());
+        let db = DatabaseManager::new_in_memory().unwrap();
+        let sink = RecordingSink {
+            cancel_at: Some("Building Search Index"),
+            ..Default::default()
+        };
+
+        let result = import_export(&db, export, &dir.path().join("work"), ImportOptions::ALL, false, &sink);
+        assert!(matches!(result, Err(AppError::Cancelled(_))));
+        assert!(db.get_conversations(None).unwrap().is_empty());
+        assert_eq!(db.get_export_stats(None).unwrap().total_messages, 0);
+        assert!(sink.results.lock().unwrap().is_empty());
+        let steps = sink.steps.lock().unwrap();
+        assert_eq!(steps.last().map(|p| p.current_step.as_str()), Some("Cancelled"));
+        assert_eq!(
+            db.get_ingestion_history(10).unwrap()[0].status,
+            IngestionRunStatus::Cancelled
+        );
+    }
+
+    #[test]
+    fn test_stale_stored_layout_is_detected_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let export = write_folder_export(dir.path());
+        let root = export.source_paths[0].clone();
+        let work = dir.path().join("work");
+        let db = DatabaseManager::new_in_memory().unwrap();
+        import_export(&db, export, &work, ImportOptions::ALL, false, &()).unwrap();
+
+        let stored = db.get_exports().unwrap().remove(0);
+        let layout = stored.layout.clone().unwrap();
+        assert_eq!(layout.chat_html_dir, Some(root.join("html/chat_history")));
+
+        // Reorganize the folder so the stored chat directory no longer exists
+        fs::create_dir_all(root.join("reorganized")).unwrap();
+        fs::rename(root.join("html"), root.join("reorganized/html")).unwrap();
+        assert_eq!(layout.stale_paths(), vec![root.join("html/chat_history").as_path()]);
+
+        let result = import_export(&db, stored, &work, ImportOptions::ALL, false, &()).unwrap();
+        assert!(result.warnings.iter().any(|w| w.contains("located them again")));
+        assert_eq!(result.conversations_parsed as usize, test_fixtures::CONVERSATION_COUNT);
+        let relocated = db.get_exports().unwrap().remove(0).layout.unwrap();
+        assert_eq!(
+            relocated.chat_html_dir,
+            Some(root.join("reorganized/html/chat_history"))
+        );
+        assert!(relocated.stale_paths().is_empty());
+    }
+}
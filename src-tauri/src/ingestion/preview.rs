@@ -0,0 +1,365 @@
+//! Dry-run preview of an export before committing to a full import.
+//!
+//! Reads a couple of chat subpages and the start of chat_history.json straight from
+//! the source folder or zip parts, runs them through the normal parsers, and reports
+//! a small sample. Nothing is extracted and the app database is never touched.
+
+use crate::error::AppResult;
+use crate::ingestion::parser::{sniff_json_prefix, ChatJsonParser, ChatParser, ParseDiagnostics};
+use crate::ingestion::timestamps::{self, SlashDateEvidence};
+use crate::models::{Event, ExportPreview, ExportSet, ExportSourceType, TimestampFormatHint};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+
+/// Number of chat subpages parsed for a preview.
+const PREVIEW_SUBPAGES: usize = 2;
+
+pub struct ExportPreviewer;
+
+impl ExportPreviewer {
+    pub fn preview(export: &ExportSet, sample_size: usize) -> AppResult<ExportPreview> {
+        let mut sampler = Sampler::new(&export.id, sample_size);
+
+        match export.source_type {
+            ExportSourceType::Zip => Self::preview_zips(&export.source_paths, &mut sampler),
+            ExportSourceType::Folder => match export.source_paths.first() {
+                Some(root) => Self::preview_folder(root, &mut sampler)?,
+                None => sampler.warnings.push("Export has no source paths".to_string()),
+            },
+        }
+
+        Ok(sampler.finish())
+    }
+
+    fn preview_folder(root: &Path, sampler: &mut Sampler) -> AppResult<()> {
+        sampler.hint = timestamps::infer_export_format(root)
+            .map(|d| d.hint)
+            .unwrap_or_default();
+        let chat_dir = root.join("html").join("chat_history");
+        let mut subpages: Vec<PathBuf> = match fs::read_dir(&chat_dir) {
+            Ok(entries) => entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| is_subpage(&p.to_string_lossy()))
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        subpages.sort();
+
+        for path in subpages.into_iter().take(PREVIEW_SUBPAGES) {
+            let label = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            match fs::File::open(&path) {
+                Ok(mut file) => sampler.add_subpage(&label, &ChatParser::conversation_id_from_path(&path), &mut file),
+                Err(e) => sampler.warnings.push(format!("Could not open {}: {}", label, e)),
+            }
+        }
+
+        let chat_json = root.join("json").join("chat_history.json");
+        if chat_json.exists() {
+            let file = fs::File::open(&chat_json)?;
+            sampler.add_chat_json("json/chat_history.json", BufReader::new(file));
+        }
+        Ok(())
+    }
+
+    fn preview_zips(zip_paths: &[PathBuf], sampler: &mut Sampler) {
+        sampler.hint = Self::zip_timestamp_hint(zip_paths);
+        let mut subpages_read = 0;
+
+        for zip_path in zip_paths {
+            let part = zip_path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            let mut archive = match fs::File::open(zip_path).map(zip::ZipArchive::new) {
+                Ok(Ok(archive)) => archive,
+                Ok(Err(e)) => {
+                    sampler.warnings.push(format!("Could not read zip {}: {}", part, e));
+                    continue;
+                }
+                Err(e) => {
+                    sampler.warnings.push(format!("Could not open zip {}: {}", part, e));
+                    continue;
+                }
+            };
+
+            let mut names: Vec<String> = archive.file_names().map(|n| n.to_string()).collect();
+            names.sort();
+
+            for name in names.iter().filter(|n| is_subpage(n)) {
+                if subpages_read >= PREVIEW_SUBPAGES {
+                    break;
+                }
+                if let Ok(mut entry) = archive.by_name(name) {
+                    let conversation_id = ChatParser::conversation_id_from_path(Path::new(name));
+                    sampler.add_subpage(name, &conversation_id, &mut entry);
+                    subpages_read += 1;
+                }
+            }
+
+            if !sampler.json_sampled {
+                if let Some(name) = names.iter().find(|n| n.ends_with("json/chat_history.json")) {
+                    if let Ok(entry) = archive.by_name(name) {
+                        sampler.add_chat_json(name, BufReader::new(entry));
+                    }
+                }
+            }
+        }
+    }
+
+    /// The slash-date order, judged from the same entries the preview parses.
+    fn zip_timestamp_hint(zip_paths: &[PathBuf]) -> TimestampFormatHint {
+        let mut evidence = SlashDateEvidence::default();
+        let mut subpages_read = 0;
+        for zip_path in zip_paths {
+            let Ok(Ok(mut archive)) = fs::File::open(zip_path).map(zip::ZipArchive::new) else {
+                continue;
+            };
+            let mut names: Vec<String> = archive.file_names().map(|n| n.to_string()).collect();
+            names.sort();
+            for name in names {
+                let wanted = if is_subpage(&name) {
+                    subpages_read += 1;
+                    subpages_read <= PREVIEW_SUBPAGES
+                } else {
+                    name.ends_with("json/chat_history.json")
+                };
+                if let Some(entry) = wanted.then(|| archive.by_name(&name).ok()).flatten() {
+                    evidence.scan_reader(entry);
+                }
+            }
+        }
+        evidence.decide().map(|d| d.hint).unwrap_or_default()
+    }
+}
+
+fn is_subpage(name: &str) -> bool {
+    let normalized = name.replace('\\', "/");
+    let file_name = normalized.rsplit('/').next().unwrap_or("");
+    normalized.contains("html/chat_history/") && file_name.starts_with("subpage_") && file_name.ends_with(".html")
+}
+
+struct Sampler {
+    export_id: String,
+    sample_size: usize,
+    html_events: Vec<Event>,
+    json_events: Vec<Event>,
+    conversation_names: BTreeSet<String>,
+    diagnostics: ParseDiagnostics,
+    files_sampled: Vec<String>,
+    warnings: Vec<String>,
+    subpages_sampled: usize,
+    json_sampled: bool,
+    hint: TimestampFormatHint,
+}
+
+impl Sampler {
+    fn new(export_id: &str, sample_size: usize) -> Self {
+        Self {
+            export_id: export_id.to_string(),
+            sample_size,
+            html_events: Vec::new(),
+            json_events: Vec::new(),
+            conversation_names: BTreeSet::new(),
+            diagnostics: ParseDiagnostics::default(),
+            files_sampled: Vec::new(),
+            warnings: Vec::new(),
+            subpages_sampled: 0,
+            json_sampled: false,
+            hint: TimestampFormatHint::default(),
+        }
+    }
+
+    fn add_subpage<R: Read>(&mut self, label: &str, conversation_id: &str, reader: &mut R) {
+        match ChatParser::parse_subpage_reader(reader, conversation_id, self.hint) {
+            Ok((conversation, events, diagnostics)) => {
+                self.files_sampled.push(label.to_string());
+                self.subpages_sampled += 1;
+                self.conversation_names
+                    .insert(conversation.display_name.unwrap_or_else(|| conversation.id.clone()));
+                self.merge_diagnostics(diagnostics);
+                let room = self.sample_size.saturating_sub(self.html_events.len());
+                self.html_events.extend(events.into_iter().take(room));
+            }
+            Err(e) => self.warnings.push(format!("Failed to parse {}: {}", label, e)),
+        }
+    }
+
+    fn add_chat_json<R: BufRead>(&mut self, label: &str, mut reader: R) {
+        self.json_sampled = true;
+        // An HTML error page saved as JSON would otherwise show up as a bare syntax error
+        if let Some(problem) = reader.fill_buf().ok().and_then(sniff_json_prefix) {
+            self.warnings.push(format!("{}: {}", label, problem));
+            return;
+        }
+        let sample_size = self.sample_size;
+        let hint = self.hint;
+        let mut diagnostics = ParseDiagnostics::default();
+        let json_events = &mut self.json_events;
+        let names = &mut self.conversation_names;
+
+        let result = ChatJsonParser::for_each_conversation(reader, hint, &mut diagnostics, |key, events| {
+            let title = events
+                .iter()
+                .find_map(|e| {
+                    let meta: serde_json::Value = serde_json::from_str(e.metadata.as_deref()?).ok()?;
+                    meta.get("conversation_title")?.as_str().map(|s| s.to_string())
+                })
+                .unwrap_or_else(|| key.clone());
+            names.insert(title);
+            let room = sample_size.saturating_sub(json_events.len());
+            json_events.extend(events.into_iter().take(room));
+            if json_events.len() >= sample_size {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+
+        self.merge_diagnostics(diagnostics);
+        match result {
+            Ok(()) => self.files_sampled.push(label.to_string()),
+            Err(e) => self.warnings.push(format!("Failed to parse {}: {}", label, e)),
+        }
+    }
+
+    fn merge_diagnostics(&mut self, other: ParseDiagnostics) {
+        self.diagnostics.timestamp_failures += other.timestamp_failures;
+        self.diagnostics.unknown_event_types.extend(other.unknown_event_types);
+    }
+
+    fn finish(mut self) -> ExportPreview {
+        if self.subpages_sampled == 0 {
+            self.warnings.push("No chat subpages found in export".to_string());
+        }
+        if !self.json_sampled {
+            self.warnings
+                .push("No json/chat_history.json found in export".to_string());
+        }
+
+        // Favor an even split so both sources are represented, then top up from whichever has more
+        let html_share = self.html_events.len().min(self.sample_size.div_ceil(2));
+        let json_share = self.json_events.len().min(self.sample_size - html_share);
+        let html_share = self.html_events.len().min(self.sample_size - json_share);
+        let mut sample_events: Vec<Event> = self.html_events.into_iter().take(html_share).collect();
+        sample_events.extend(self.json_events.into_iter().take(json_share));
+
+        ExportPreview {
+            export_id: self.export_id,
+            sample_events,
+            conversation_names: self.conversation_names.into_iter().collect(),
+            unknown_event_types: self.diagnostics.unknown_event_types.into_iter().collect(),
+            timestamp_failures: self.diagnostics.timestamp_failures as i32,
+            files_sampled: self.files_sampled,
+            warnings: self.warnings,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ValidationStatus;
+    use std::io::Write;
+
+    const SUBPAGE_HTML: &str = r#"<html><body>
+        <h1>Chat History with alice</h1>
+        <div class="rightpanel">
+            <div><h4>alice</h4><span>TEXT</span><p>hey there</p><h6>2023-01-15 14:30:00 UTC</h6></div>
+            <div><h4>me</h4><span>TEXT</span><p>hi!</p><h6>2023-01-15 14:31:00 UTC</h6></div>
+            <div><h4>alice</h4><span>TEXT</span><p>broken time</p><h6>yesterday-ish</h6></div>
+        </div></body></html>"#;
+
+    const CHAT_JSON: &str = r#"{
+        "bob - conv2": [
+            {"From": "bob", "Media Type": "TEXT", "Created": "2023-02-01 09:00:00 UTC", "Content": "morning", "IsSender": false, "Conversation Title": "Bob"},
+            {"From": "bob", "Media Type": "HOLOGRAM", "Created": "2023-02-01 09:01:00 UTC", "Content": "", "IsSender": false},
+            {"From": "me", "Media Type": "TEXT", "Created": "not a time", "Content": "lost", "IsSender": true}
+        ],
+        "carol - conv3": [
+            {"From": "carol", "Media Type": "TEXT", "Created": "2023-03-01 09:00:00 UTC", "Content": "later", "IsSender": false}
+        ]
+    }"#;
+
+    fn export_for(paths: Vec<PathBuf>, source_type: ExportSourceType) -> ExportSet {
+        ExportSet {
+            id: "preview-test".to_string(),
+            source_paths: paths,
+            source_type,
+            extraction_path: None,
+            creation_date: None,
+            validation_status: ValidationStatus::Valid,
+            parts: Vec::new(),
+            import_phases: None,
+            ignored_duplicates: Vec::new(),
+            layout: None,
+        }
+    }
+
+    #[test]
+    fn test_preview_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("html/chat_history")).unwrap();
+        fs::create_dir_all(dir.path().join("json")).unwrap();
+        fs::write(dir.path().join("html/chat_history/subpage_alice.html"), SUBPAGE_HTML).unwrap();
+        fs::write(dir.path().join("json/chat_history.json"), CHAT_JSON).unwrap();
+
+        let export = export_for(vec![dir.path().to_path_buf()], ExportSourceType::Folder);
+        let preview = ExportPreviewer::preview(&export, 10).unwrap();
+
+        // Messages with an unreadable timestamp are kept, with the original text
+        assert_eq!(preview.sample_events.len(), 7);
+        assert_eq!(preview.timestamp_failures, 2);
+        let kept: Vec<&str> = preview
+            .sample_events
+            .iter()
+            .filter(|e| {
+                e.metadata
+                    .as_deref()
+                    .is_some_and(|m| m.contains(crate::ingestion::RAW_TIMESTAMP_KEY))
+            })
+            .filter_map(|e| e.content.as_deref())
+            .collect();
+        assert_eq!(kept, ["broken time", "lost"]);
+        assert_eq!(preview.unknown_event_types, vec!["HOLOGRAM".to_string()]);
+        assert!(preview.conversation_names.contains(&"alice".to_string()));
+        assert!(preview.conversation_names.contains(&"Bob".to_string()));
+        assert!(preview.warnings.is_empty(), "{:?}", preview.warnings);
+    }
+
+    #[test]
+    fn test_preview_zip_stops_at_sample_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let zip_path = dir.path().join("mydata~123.zip");
+        {
+            let mut writer = zip::ZipWriter::new(fs::File::create(&zip_path).unwrap());
+            let options = zip::write::SimpleFileOptions::default();
+            writer
+                .start_file("html/chat_history/subpage_alice.html", options)
+                .unwrap();
+            writer.write_all(SUBPAGE_HTML.as_bytes()).unwrap();
+            writer.start_file("json/chat_history.json", options).unwrap();
+            writer.write_all(CHAT_JSON.as_bytes()).unwrap();
+            writer.finish().unwrap();
+        }
+
+        let export = export_for(vec![zip_path], ExportSourceType::Zip);
+        let preview = ExportPreviewer::preview(&export, 2).unwrap();
+
+        // One event from each source, and the JSON walk stops before reaching carol
+        assert_eq!(preview.sample_events.len(), 2);
+        assert_eq!(preview.sample_events[0].sender, "alice");
+        assert_eq!(preview.sample_events[1].sender, "bob");
+        assert!(!preview.conversation_names.contains(&"carol - conv3".to_string()));
+        assert_eq!(preview.files_sampled.len(), 2);
+    }
+
+    #[test]
+    fn test_preview_reports_missing_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let export = export_for(vec![dir.path().to_path_buf()], ExportSourceType::Folder);
+        let preview = ExportPreviewer::preview(&export, 10).unwrap();
+        assert!(preview.sample_events.is_empty());
+        assert_eq!(preview.warnings.len(), 2);
+    }
+}
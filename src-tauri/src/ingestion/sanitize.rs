@@ -0,0 +1,162 @@
+//! Cleanup of text from the export before it is stored and indexed.
+//!
+//! Message text loses control characters (except newlines and tabs), zero-width spaces and
+//! bidi embedding/override/isolate controls, and is normalized to NFC so search matches
+//! however the text was composed. Joiners stay, since emoji sequences and some scripts need
+//! them. An event that lost bidi controls gets a `bidi_controls_removed` metadata flag, as
+//! those are the ones used to disguise text.
+//!
+//! Names get a stricter profile: no control or formatting characters at all, so a sender
+//! can't be made to look like someone else.
+
+use crate::models::Event;
+use icu_normalizer::ComposingNormalizerBorrowed;
+use serde_json::Value;
+
+/// Metadata flag on events whose text contained bidi controls.
+pub const BIDI_REMOVED_FLAG: &str = "bidi_controls_removed";
+
+/// Bidi embeddings, overrides and isolates, which can reorder how the surrounding text shows.
+fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}')
+}
+
+/// Invisible characters that split words for search without joining anything.
+fn is_zero_width_break(c: char) -> bool {
+    matches!(c, '\u{200B}' | '\u{2060}' | '\u{FEFF}')
+}
+
+/// Unicode format (Cf) characters: invisible, and only meaningful inside running text.
+fn is_format_char(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00AD}'
+            | '\u{0600}'..='\u{0605}'
+            | '\u{061C}'
+            | '\u{06DD}'
+            | '\u{070F}'
+            | '\u{0890}'..='\u{0891}'
+            | '\u{08E2}'
+            | '\u{180E}'
+            | '\u{200B}'..='\u{200F}'
+            | '\u{202A}'..='\u{202E}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{2066}'..='\u{206F}'
+            | '\u{FEFF}'
+            | '\u{FFF9}'..='\u{FFFB}'
+            | '\u{110BD}'
+            | '\u{110CD}'
+            | '\u{13430}'..='\u{1343F}'
+            | '\u{1BCA0}'..='\u{1BCA3}'
+            | '\u{1D173}'..='\u{1D17A}'
+            | '\u{E0001}'
+            | '\u{E0020}'..='\u{E007F}'
+    )
+}
+
+fn nfc(text: &str) -> String {
+    if text.is_ascii() {
+        return text.to_string();
+    }
+    ComposingNormalizerBorrowed::new_nfc().normalize(text).into_owned()
+}
+
+/// Message text with disallowed characters removed, and whether bidi controls were among them.
+pub fn clean_text(text: &str) -> (String, bool) {
+    let mut bidi_removed = false;
+    let kept: String = text
+        .chars()
+        .filter(|&c| {
+            if is_bidi_control(c) {
+                bidi_removed = true;
+                return false;
+            }
+            !(is_zero_width_break(c) || c.is_control() && !matches!(c, '\n' | '\t'))
+        })
+        .collect();
+    (nfc(&kept), bidi_removed)
+}
+
+/// A display or user name without control or formatting characters, trimmed.
+pub fn clean_name(name: &str) -> String {
+    let kept: String = name
+        .chars()
+        .filter(|&c| !c.is_control() && !is_format_char(c))
+        .collect();
+    nfc(kept.trim())
+}
+
+/// Apply `clean_text` to the content and `clean_name` to the sender fields.
+pub fn clean_event(mut event: Event) -> Event {
+    event.sender = clean_name(&event.sender);
+    event.sender_name = event.sender_name.as_deref().map(clean_name);
+    if let Some(content) = event.content.take() {
+        let (content, bidi_removed) = clean_text(&content);
+        event.content = Some(content);
+        if bidi_removed {
+            flag_bidi_removed(&mut event);
+        }
+    }
+    event
+}
+
+fn flag_bidi_removed(event: &mut Event) {
+    let mut metadata = event
+        .metadata
+        .as_deref()
+        .and_then(|m| serde_json::from_str::<serde_json::Map<String, Value>>(m).ok())
+        .unwrap_or_default();
+    metadata.insert(BIDI_REMOVED_FLAG.to_string(), Value::Bool(true));
+    event.metadata = Some(Value::Object(metadata).to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emoji_sequences_survive() {
+        // Family emoji and a rainbow flag are held together by zero-width joiners
+        let text = "hi 👨\u{200D}👩\u{200D}👧 and 🏳\u{FE0F}\u{200D}🌈";
+        assert_eq!(clean_text(text), (text.to_string(), false));
+    }
+
+    #[test]
+    fn test_bidi_overrides_are_stripped_and_flagged() {
+        let (text, bidi_removed) = clean_text("invoice_\u{202E}fdp.exe");
+        assert_eq!(text, "invoice_fdp.exe");
+        assert!(bidi_removed);
+
+        let event = clean_event(Event {
+            id: "e1".into(),
+            timestamp: chrono::Utc::now(),
+            sender: "\u{2067}alice\u{2069}".into(),
+            sender_name: Some("Ali\u{200D}ce\u{202E}".into()),
+            media_references: Vec::new(),
+            conversation_id: None,
+            content: Some("see \u{202E}this".into()),
+            event_type: "TEXT".into(),
+            metadata: Some(r#"{"is_sender":false}"#.into()),
+        });
+        assert_eq!(event.sender, "alice");
+        assert_eq!(event.sender_name.as_deref(), Some("Alice"));
+        assert_eq!(event.content.as_deref(), Some("see this"));
+        let metadata: Value = serde_json::from_str(event.metadata.as_deref().unwrap()).unwrap();
+        assert_eq!(metadata[BIDI_REMOVED_FLAG], true);
+        assert_eq!(metadata["is_sender"], false);
+    }
+
+    #[test]
+    fn test_control_bytes_are_stripped_but_layout_whitespace_kept() {
+        assert_eq!(clean_text("a\0b\u{7}c\r\nd\te"), ("abc\nd\te".to_string(), false));
+        assert_eq!(clean_text("sea\u{200B}rch"), ("search".to_string(), false));
+        assert_eq!(clean_name(" bob\0\n\t "), "bob");
+    }
+
+    #[test]
+    fn test_text_is_normalized_to_nfc() {
+        // "e" followed by a combining acute accent composes to "é"
+        assert_eq!(clean_text("cafe\u{301}").0, "caf\u{E9}");
+        assert_eq!(clean_name("Re\u{301}mi"), "R\u{E9}mi");
+    }
+}
@@ -0,0 +1,470 @@
+//! Streaming parser for chat subpages too large to load as a DOM.
+//!
+//! Drives html5ever's tokenizer directly and tracks just enough of the element stack to
+//! recognise the `.rightpanel > div` message structure. It mirrors the selectors used by
+//! [`ChatParser::parse_subpage_reader`] so both paths produce the same events.
+
+use crate::error::AppResult;
+use crate::ingestion::parser::{ChatParser, ParseDiagnostics, KNOWN_EVENT_TYPES};
+use crate::ingestion::sanitize::clean_event;
+use crate::models::{Event, TimestampFormatHint};
+use chrono::{DateTime, Utc};
+use html5ever::tendril::StrTendril;
+use html5ever::tokenizer::states::RawKind;
+use html5ever::tokenizer::{BufferQueue, Tag, TagKind, Token, TokenSink, TokenSinkResult, Tokenizer, TokenizerOpts};
+use std::cell::RefCell;
+use std::io::Read;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+const READ_CHUNK: usize = 256 * 1024;
+
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "keygen", "link", "meta", "param", "source", "track",
+    "wbr",
+];
+
+/// Start tags that implicitly close an open `<p>`, as the HTML tree builder does.
+const CLOSES_P: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "div",
+    "dl",
+    "fieldset",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+
+const HEADINGS: &[&str] = &["h1", "h2", "h3", "h4", "h5", "h6"];
+
+/// Result of a streamed parse: the text of the first `<h1>` and the messages found.
+pub struct StreamedSubpage {
+    pub heading: Option<String>,
+    pub events: Vec<Event>,
+    pub diagnostics: ParseDiagnostics,
+}
+
+pub fn parse_subpage_stream<R: Read>(
+    reader: &mut R,
+    conversation_id: &str,
+    hint: TimestampFormatHint,
+) -> AppResult<StreamedSubpage> {
+    let sink = SubpageSink {
+        state: RefCell::new(SinkState {
+            conversation_id: conversation_id.to_string(),
+            hint,
+            ..Default::default()
+        }),
+    };
+    let tokenizer = Tokenizer::new(sink, TokenizerOpts::default());
+    let queue = BufferQueue::default();
+
+    let mut buf = vec![0u8; READ_CHUNK];
+    let mut pending: Vec<u8> = Vec::new();
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        pending.extend_from_slice(&buf[..n]);
+        let text = take_utf8(&mut pending);
+        if !text.is_empty() {
+            queue.push_back(StrTendril::from(text));
+            let _ = tokenizer.feed(&queue);
+        }
+    }
+    if !pending.is_empty() {
+        queue.push_back(StrTendril::from(String::from_utf8_lossy(&pending).into_owned()));
+        let _ = tokenizer.feed(&queue);
+    }
+    tokenizer.end();
+
+    let mut state = tokenizer.sink.state.into_inner();
+    state.pop_to(0);
+    Ok(StreamedSubpage {
+        heading: state.heading.text,
+        events: state.events,
+        diagnostics: state.diagnostics,
+    })
+}
+
+/// Decode the complete UTF-8 prefix of `pending`, leaving a split trailing character for the
+/// next chunk. Invalid bytes become U+FFFD, matching the DOM path's lossy decoding.
+fn take_utf8(pending: &mut Vec<u8>) -> String {
+    let mut out = String::new();
+    loop {
+        match std::str::from_utf8(pending) {
+            Ok(s) => {
+                out.push_str(s);
+                pending.clear();
+                return out;
+            }
+            Err(e) => {
+                let valid = e.valid_up_to();
+                out.push_str(std::str::from_utf8(&pending[..valid]).unwrap_or_default());
+                match e.error_len() {
+                    Some(len) => {
+                        out.push('\u{FFFD}');
+                        pending.drain(..valid + len);
+                    }
+                    None => {
+                        pending.drain(..valid);
+                        return out;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Text of the first matching element, collected while it is open.
+#[derive(Default)]
+struct Capture {
+    text: Option<String>,
+    open_at: Option<usize>,
+}
+
+impl Capture {
+    fn start(&mut self, depth: usize) {
+        if self.text.is_none() {
+            self.text = Some(String::new());
+            self.open_at = Some(depth);
+        }
+    }
+
+    fn push(&mut self, s: &str) {
+        if self.open_at.is_some() {
+            if let Some(text) = self.text.as_mut() {
+                text.push_str(s);
+            }
+        }
+    }
+
+    fn close(&mut self, depth: usize) {
+        if self.open_at == Some(depth) {
+            self.open_at = None;
+        }
+    }
+}
+
+#[derive(Default)]
+struct MessageState {
+    depth: usize,
+    sender: Capture,
+    content: Capture,
+    timestamp: Capture,
+    /// Text of every `<span>` in document order, plus the depths of the ones still open.
+    spans: Vec<String>,
+    open_spans: Vec<(usize, usize)>,
+    images: Vec<PathBuf>,
+    videos: Vec<PathBuf>,
+    sources: Vec<PathBuf>,
+    links: Vec<PathBuf>,
+}
+
+#[derive(Default)]
+struct SinkState {
+    conversation_id: String,
+    hint: TimestampFormatHint,
+    stack: Vec<String>,
+    heading: Capture,
+    panel_depth: Option<usize>,
+    panel_seen: bool,
+    message: Option<MessageState>,
+    events: Vec<Event>,
+    diagnostics: ParseDiagnostics,
+}
+
+impl SinkState {
+    fn start_tag(&mut self, tag: &Tag) -> TokenSinkResult<()> {
+        let name: &str = &tag.name;
+
+        if CLOSES_P.contains(&name) {
+            if let Some(pos) = self.stack.iter().rposition(|n| n == "p") {
+                self.pop_to(pos);
+            }
+        }
+        if HEADINGS.contains(&name) && self.stack.last().is_some_and(|n| HEADINGS.contains(&n.as_str())) {
+            self.pop_to(self.stack.len() - 1);
+        }
+
+        let attr = |key: &str| {
+            tag.attrs
+                .iter()
+                .find(|a| &*a.name.local == key)
+                .map(|a| a.value.to_string())
+        };
+        let is_void = VOID_ELEMENTS.contains(&name);
+        if !is_void {
+            self.stack.push(name.to_string());
+        }
+        let depth = self.stack.len();
+
+        if name == "h1" {
+            self.heading.start(depth);
+        }
+
+        if let Some(message) = self.message.as_mut() {
+            match name {
+                "h4" => message.sender.start(depth),
+                "p" => message.content.start(depth),
+                "h6" => message.timestamp.start(depth),
+                "span" => {
+                    message.open_spans.push((depth, message.spans.len()));
+                    message.spans.push(String::new());
+                }
+                "img" => {
+                    if let Some(src) = attr("src").filter(|s| !s.starts_with("data:")) {
+                        message.images.push(PathBuf::from(src));
+                    }
+                }
+                "video" => message.videos.extend(attr("src").map(PathBuf::from)),
+                "source" => message.sources.extend(attr("src").map(PathBuf::from)),
+                "a" => {
+                    if let Some(href) = attr("href").filter(|h| ChatParser::is_media_link(h)) {
+                        message.links.push(PathBuf::from(href));
+                    }
+                }
+                _ => {}
+            }
+        } else if name == "div" && self.panel_depth.is_some_and(|d| d + 1 == depth) {
+            self.message = Some(MessageState {
+                depth,
+                ..Default::default()
+            });
+        }
+
+        if !self.panel_seen
+            && !is_void
+            && attr("class").is_some_and(|c| c.split_ascii_whitespace().any(|c| c == "rightpanel"))
+        {
+            self.panel_seen = true;
+            self.panel_depth = Some(depth);
+        }
+
+        if is_void || tag.self_closing {
+            if !is_void {
+                self.pop_to(depth - 1);
+            }
+            return TokenSinkResult::Continue;
+        }
+        match name {
+            "style" | "xmp" | "iframe" | "noembed" | "noframes" => TokenSinkResult::RawData(RawKind::Rawtext),
+            "script" => TokenSinkResult::RawData(RawKind::ScriptData),
+            "title" | "textarea" => TokenSinkResult::RawData(RawKind::Rcdata),
+            _ => TokenSinkResult::Continue,
+        }
+    }
+
+    fn end_tag(&mut self, tag: &Tag) {
+        let name: &str = &tag.name;
+        if let Some(pos) = self.stack.iter().rposition(|n| n == name) {
+            self.pop_to(pos);
+        }
+    }
+
+    fn text(&mut self, s: &str) {
+        self.heading.push(s);
+        if let Some(message) = self.message.as_mut() {
+            message.sender.push(s);
+            message.content.push(s);
+            message.timestamp.push(s);
+            for &(_, idx) in &message.open_spans {
+                message.spans[idx].push_str(s);
+            }
+        }
+    }
+
+    /// Close every open element above `len`, innermost first.
+    fn pop_to(&mut self, len: usize) {
+        while self.stack.len() > len {
+            let depth = self.stack.len();
+            self.stack.pop();
+            self.heading.close(depth);
+
+            if let Some(message) = self.message.as_mut() {
+                message.sender.close(depth);
+                message.content.close(depth);
+                message.timestamp.close(depth);
+                message.open_spans.retain(|&(d, _)| d != depth);
+                if message.depth == depth {
+                    let message = self.message.take().unwrap_or_default();
+                    if let Some(event) = self.finish_message(message) {
+                        self.events.push(event);
+                    }
+                }
+            }
+            if self.panel_depth == Some(depth) {
+                self.panel_depth = None;
+            }
+        }
+    }
+
+    /// Same rules as `ChatParser::parse_message_node`.
+    fn finish_message(&mut self, message: MessageState) -> Option<Event> {
+        let sender = message.sender.text?.trim().to_string();
+
+        let event_type = message
+            .spans
+            .iter()
+            .map(|s| s.trim())
+            .find(|s| KNOWN_EVENT_TYPES.contains(s))
+            .unwrap_or("UNKNOWN")
+            .to_string();
+        self.diagnostics.note_event_type(&event_type);
+
+        let content = message.content.text.map(|c| c.trim().to_string());
+
+        let timestamp_text = message.timestamp.text?;
+
+        let mut media_references = message.images;
+        media_references.extend(message.videos);
+        media_references.extend(message.sources);
+        media_references.extend(message.links);
+
+        let event = Event {
+            id: Uuid::new_v4().to_string(),
+            timestamp: DateTime::<Utc>::UNIX_EPOCH,
+            sender,
+            sender_name: None,
+            media_references,
+            conversation_id: Some(self.conversation_id.clone()),
+            content,
+            event_type,
+            metadata: None,
+        };
+        Some(clean_event(ChatParser::dated_message(
+            event,
+            &timestamp_text,
+            self.hint,
+            &mut self.diagnostics,
+        )))
+    }
+}
+
+struct SubpageSink {
+    state: RefCell<SinkState>,
+}
+
+impl TokenSink for SubpageSink {
+    type Handle = ();
+
+    fn process_token(&self, token: Token, _line_number: u64) -> TokenSinkResult<()> {
+        let mut state = self.state.borrow_mut();
+        match token {
+            Token::TagToken(tag) => match tag.kind {
+                TagKind::StartTag => return state.start_tag(&tag),
+                TagKind::EndTag => state.end_tag(&tag),
+            },
+            Token::CharacterTokens(text) => state.text(&text),
+            _ => {}
+        }
+        TokenSinkResult::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Conversation;
+    use crate::test_fixtures;
+
+    /// Hands out a few bytes per read so tags, entities and UTF-8 sequences straddle chunk boundaries.
+    struct TrickleReader<'a>(&'a [u8]);
+
+    impl Read for TrickleReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.0.len().min(buf.len()).min(7);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    /// Events minus their random ids, for comparing the two parsers.
+    fn comparable(conversation: &Conversation, events: &[Event]) -> String {
+        let events: Vec<Event> = events
+            .iter()
+            .cloned()
+            .map(|mut e| {
+                e.id.clear();
+                e
+            })
+            .collect();
+        format!("{:?}\n{:?}", conversation, events)
+    }
+
+    fn assert_parsers_agree(html: &str, conversation_id: &str) -> Vec<Event> {
+        let (dom_convo, dom_events, dom_diag) =
+            ChatParser::parse_subpage_reader(&mut html.as_bytes(), conversation_id, TimestampFormatHint::default())
+                .unwrap();
+        let (stream_convo, stream_events, stream_diag) = ChatParser::parse_subpage_streaming(
+            &mut TrickleReader(html.as_bytes()),
+            conversation_id,
+            TimestampFormatHint::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            comparable(&dom_convo, &dom_events),
+            comparable(&stream_convo, &stream_events)
+        );
+        assert_eq!(dom_diag.timestamp_failures, stream_diag.timestamp_failures);
+        assert_eq!(dom_diag.unknown_event_types, stream_diag.unknown_event_types);
+        stream_events
+    }
+
+    #[test]
+    fn test_streaming_matches_dom_on_fixture() {
+        for id in test_fixtures::CONVERSATION_IDS {
+            let events = assert_parsers_agree(&test_fixtures::subpage_html(id), id);
+            let expected: Vec<Event> = test_fixtures::events()
+                .into_iter()
+                .filter(|e| e.conversation_id.as_deref() == Some(id))
+                .collect();
+            assert_eq!(events.len(), expected.len());
+            assert_eq!(events[9].media_references, expected[9].media_references);
+        }
+    }
+
+    #[test]
+    fn test_streaming_matches_dom_on_messy_markup() {
+        let html = r#"<html><head><style>div > p { content: "<h4>" }</style></head><body>
+            <h1>Chat History with zoë</h1>
+            <div class="panel rightpanel">
+              <div><h4>zoë <b>(me)</b></h4><span><span>TEXT</span></span><p>fish &amp; chips — 🍟<h6>2023-01-15 14:30:00 UTC</h6></div>
+              <div><h4>bob</h4><span>HOLOGRAM</span><p>unknown type</p><h6>2023-01-15 14:31:00 UTC</h6></div>
+              <div><h4>bob</h4><span>MEDIA</span><img src="data:image/png;base64,AAAA"><img src="../chat_media/a.jpg">
+                <a href="../chat_media/b.MP4">video</a><a href="https://example.com">site</a><h6>2023-01-15 14:32:00 UTC</h6></div>
+              <div><h4>bob</h4><span>TEXT</span><p>no time</p><h6>sometime</h6></div>
+              <div><span>TEXT</span><p>no sender</p><h6>2023-01-15 14:33:00 UTC</h6></div>
+              <div><h4>bob</h4><span>TEXT</span><p>no h6 at all</p></div>
+              <p>stray paragraph</p>
+            </div>
+            <div class="rightpanel"><div><h4>ignored</h4><h6>2023-01-15 14:34:00 UTC</h6></div></div>
+            </body></html>"#;
+        let events = assert_parsers_agree(html, "zoe");
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].sender, "zoë (me)");
+        assert_eq!(events[0].content.as_deref(), Some("fish & chips — 🍟"));
+        assert_eq!(events[1].event_type, "UNKNOWN");
+        assert_eq!(events[2].media_references.len(), 2);
+    }
+}
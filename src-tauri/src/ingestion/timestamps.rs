@@ -0,0 +1,157 @@
+//! Which way round an export writes slash dates like `01/02/2023 14:30:00`.
+//!
+//! Most timestamps are `YYYY-MM-DD`, but exports generated under some locales use slash dates,
+//! which read the same month-first and day-first until a field goes over 12. The pipeline
+//! samples an export's timestamps once, picks an order, and hands it to every parser as a
+//! `TimestampFormatHint`.
+
+use crate::ingestion::layout;
+use crate::models::{ExportLayout, TimestampFormatDecision, TimestampFormatHint};
+use regex::Regex;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// Bytes read from the start of each sampled file.
+const SAMPLE_BYTES: u64 = 256 * 1024;
+
+/// Chat subpages sampled per export, on top of the JSON files.
+const SAMPLE_SUBPAGES: usize = 50;
+
+static SLASH_DATE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(\d{1,2})/(\d{1,2})/\d{4} \d{1,2}:\d{2}").unwrap());
+
+/// Slash dates seen so far, and which of them only make sense one way round.
+#[derive(Debug, Default)]
+pub struct SlashDateEvidence {
+    dates: usize,
+    /// First field over 12, so it can only be the day.
+    day_first: usize,
+    /// Second field over 12, so it can only be the day.
+    month_first: usize,
+}
+
+impl SlashDateEvidence {
+    pub fn scan(&mut self, text: &str) {
+        for caps in SLASH_DATE_RE.captures_iter(text) {
+            let first: u32 = caps[1].parse().unwrap_or(0);
+            let second: u32 = caps[2].parse().unwrap_or(0);
+            self.dates += 1;
+            if first > 12 && second <= 12 {
+                self.day_first += 1;
+            } else if second > 12 && first <= 12 {
+                self.month_first += 1;
+            }
+        }
+    }
+
+    /// Scan the first `SAMPLE_BYTES` of `reader`.
+    pub fn scan_reader<R: Read>(&mut self, reader: R) {
+        let mut buf = Vec::new();
+        if reader.take(SAMPLE_BYTES).read_to_end(&mut buf).is_ok() {
+            self.scan(&String::from_utf8_lossy(&buf));
+        }
+    }
+
+    /// The order to read slash dates in; None when there were none. Without proof either way,
+    /// or with proof both ways, the decision falls back to month-first and is marked ambiguous.
+    pub fn decide(&self) -> Option<TimestampFormatDecision> {
+        if self.dates == 0 {
+            return None;
+        }
+        let hint = if self.day_first > self.month_first {
+            TimestampFormatHint::DayFirst
+        } else {
+            TimestampFormatHint::MonthFirst
+        };
+        Some(TimestampFormatDecision {
+            hint,
+            dates_sampled: self.dates,
+            ambiguous: (self.day_first > 0) == (self.month_first > 0),
+        })
+    }
+}
+
+/// Sample the chat and snap JSON files and the first chat subpages of an extracted export.
+pub fn infer_export_format(source: &Path) -> Option<TimestampFormatDecision> {
+    infer_layout_format(&layout::detect_layout(source))
+}
+
+/// [`infer_export_format`] for an export whose files were already located.
+pub fn infer_layout_format(layout: &ExportLayout) -> Option<TimestampFormatDecision> {
+    let mut evidence = SlashDateEvidence::default();
+    let mut files: Vec<PathBuf> = [
+        &layout.chat_history_json,
+        &layout.snap_history_json,
+        &layout.story_history_json,
+        &layout.talk_history_json,
+    ]
+    .into_iter()
+    .flatten()
+    .cloned()
+    .collect();
+    if let Some(Ok(entries)) = layout.chat_html_dir.as_ref().map(fs::read_dir) {
+        let mut subpages: Vec<_> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| {
+                p.file_name()
+                    .is_some_and(|n| n.to_string_lossy().starts_with("subpage_"))
+            })
+            .collect();
+        subpages.sort();
+        files.extend(subpages.into_iter().take(SAMPLE_SUBPAGES));
+    }
+    for file in files.iter().filter_map(|f| fs::File::open(f).ok()) {
+        evidence.scan_reader(file);
+    }
+    evidence.decide()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_chat_json(root: &Path, created: &[&str]) {
+        let messages: Vec<serde_json::Value> = created
+            .iter()
+            .map(|c| serde_json::json!({"From": "alice", "Media Type": "TEXT", "Created": c, "Content": "hi"}))
+            .collect();
+        fs::create_dir_all(root.join("json")).unwrap();
+        fs::write(
+            root.join("json/chat_history.json"),
+            serde_json::json!({ "alice": messages }).to_string(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_day_over_twelve_proves_day_first() {
+        let dir = tempfile::tempdir().unwrap();
+        write_chat_json(
+            dir.path(),
+            &["03/01/2023 09:00:00", "25/01/2023 10:00:00", "02/02/2023 11:00:00"],
+        );
+        let decision = infer_export_format(dir.path()).unwrap();
+        assert_eq!(decision.hint, TimestampFormatHint::DayFirst);
+        assert_eq!(decision.dates_sampled, 3);
+        assert!(!decision.ambiguous);
+    }
+
+    #[test]
+    fn test_unprovable_or_conflicting_order_is_ambiguous() {
+        let dir = tempfile::tempdir().unwrap();
+        write_chat_json(dir.path(), &["01/02/2023 09:00:00", "03/04/2023 10:00:00"]);
+        let decision = infer_export_format(dir.path()).unwrap();
+        assert_eq!(decision.hint, TimestampFormatHint::MonthFirst);
+        assert!(decision.ambiguous);
+
+        let mut mixed = SlashDateEvidence::default();
+        mixed.scan("25/01/2023 10:00:00 and 01/25/2023 10:00:00");
+        assert!(mixed.decide().unwrap().ambiguous);
+
+        write_chat_json(dir.path(), &["2023-01-02 09:00:00 UTC"]);
+        assert_eq!(infer_export_format(dir.path()), None);
+    }
+}
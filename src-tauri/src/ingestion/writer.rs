@@ -0,0 +1,249 @@
+//! Writing parsed events to the database as they are parsed, [`EVENT_CHUNK_SIZE`] at a time
+//! with one transaction per chunk, so an import holds a chunk of events rather than the whole
+//! export in memory. Conversations are written as they are discovered, since events refer to
+//! them; their activity and media totals are filled in by a final pass.
+
+use crate::db::{DatabaseManager, IngestionRun};
+use crate::error::AppResult;
+use crate::ingestion::pipeline::{check_cancelled, ProgressSink};
+use crate::ingestion::{self, locations};
+use crate::models::{Conversation, Event, IngestionProgress};
+use std::collections::HashSet;
+
+/// Events written per transaction.
+pub const EVENT_CHUNK_SIZE: usize = 10_000;
+
+pub struct EventWriter<'a> {
+    database: &'a DatabaseManager,
+    sink: &'a dyn ProgressSink,
+    export_id: String,
+    /// Fills in the sender of the owner's stories and outgoing calls, when known up front.
+    owner: Option<String>,
+    conversations: Vec<Conversation>,
+    conversation_ids: HashSet<String>,
+    pending: Vec<Event>,
+    written: usize,
+    redacted: usize,
+    shared_locations: usize,
+    /// Progress step and fraction that write updates are reported under.
+    step: (String, f32),
+}
+
+impl<'a> EventWriter<'a> {
+    pub fn new(
+        database: &'a DatabaseManager,
+        sink: &'a dyn ProgressSink,
+        export_id: &str,
+        owner: Option<String>,
+    ) -> Self {
+        Self {
+            database,
+            sink,
+            export_id: export_id.to_string(),
+            owner,
+            conversations: Vec::new(),
+            conversation_ids: HashSet::new(),
+            pending: Vec::new(),
+            written: 0,
+            redacted: 0,
+            shared_locations: 0,
+            step: (String::new(), 0.0),
+        }
+    }
+
+    /// Report the current phase, and later writes under it.
+    pub fn begin_step(&mut self, step: &str, progress: f32, message: &str) {
+        self.step = (step.to_string(), progress);
+        self.sink.progress(IngestionProgress {
+            export_id: self.export_id.clone(),
+            current_step: step.to_string(),
+            progress,
+            message: message.to_string(),
+        });
+    }
+
+    /// Ids of the conversations written so far.
+    pub fn conversation_ids(&self) -> &HashSet<String> {
+        &self.conversation_ids
+    }
+
+    pub fn conversations(&self) -> &[Conversation] {
+        &self.conversations
+    }
+
+    pub fn conversations_mut(&mut self) -> &mut [Conversation] {
+        &mut self.conversations
+    }
+
+    /// New events written so far, not counting redacted ones or rewrites.
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    pub fn redacted(&self) -> usize {
+        self.redacted
+    }
+
+    pub fn shared_locations(&self) -> usize {
+        self.shared_locations
+    }
+
+    /// Write the conversations not written yet, so their events can refer to them.
+    pub fn add_conversations(&mut self, run: &mut IngestionRun, conversations: Vec<Conversation>) -> AppResult<()> {
+        let new: Vec<Conversation> = conversations
+            .into_iter()
+            .filter(|c| self.conversation_ids.insert(c.id.clone()))
+            .collect();
+        if new.is_empty() {
+            return Ok(());
+        }
+        run.track_conversations(&new);
+        self.database.batch_insert_conversations(&new)?;
+        self.conversations.extend(new);
+        Ok(())
+    }
+
+    /// Queue events, writing a chunk whenever enough are waiting.
+    pub fn push(&mut self, run: &mut IngestionRun, events: Vec<Event>) -> AppResult<()> {
+        self.pending.extend(events);
+        while self.pending.len() >= EVENT_CHUNK_SIZE {
+            let rest = self.pending.split_off(EVENT_CHUNK_SIZE);
+            let chunk = std::mem::replace(&mut self.pending, rest);
+            self.write(run, chunk, true)?;
+        }
+        Ok(())
+    }
+
+    /// Write every queued event.
+    pub fn flush(&mut self, run: &mut IngestionRun) -> AppResult<()> {
+        let chunk = std::mem::take(&mut self.pending);
+        self.write(run, chunk, true)
+    }
+
+    /// Write events already stored by this import again, after another source added to them.
+    pub fn rewrite(&mut self, run: &mut IngestionRun, events: Vec<Event>) -> AppResult<()> {
+        for chunk in events.chunks(EVENT_CHUNK_SIZE) {
+            self.write(run, chunk.to_vec(), false)?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, run: &mut IngestionRun, mut events: Vec<Event>, new: bool) -> AppResult<()> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        check_cancelled(self.sink)?;
+        if let Some(owner) = &self.owner {
+            for event in events.iter_mut().filter(|e| e.sender.is_empty() && e.is_sender()) {
+                event.sender = owner.clone();
+            }
+        }
+        let shared = locations::tag_shared_locations(&mut events);
+        // Events no parser could place in a conversation would otherwise be unreachable
+        if let Some(unsorted) = ingestion::assign_orphan_events(&mut events, &self.export_id) {
+            match self.conversations.iter_mut().find(|c| c.id == unsorted.id) {
+                Some(existing) => {
+                    for participant in unsorted.participants {
+                        if !existing.participants.contains(&participant) {
+                            existing.participants.push(participant);
+                        }
+                    }
+                    existing.last_event_at = existing.last_event_at.max(unsorted.last_event_at);
+                }
+                None => self.add_conversations(run, vec![unsorted])?,
+            }
+        }
+        let (events, redacted) = self.database.filter_redacted(events)?;
+
+        // Events a repeat import rewrites aren't the run's to remove on rollback
+        let ids: Vec<String> = events.iter().map(|e| e.id.clone()).collect();
+        let existing = self.database.existing_event_ids(&ids)?;
+        run.track_event_ids(ids.into_iter().filter(|id| !existing.contains(id)));
+        self.database.batch_insert_events(&events, &self.export_id)?;
+
+        if new {
+            self.written += events.len();
+            self.redacted += redacted;
+            self.shared_locations += shared;
+            self.sink.progress(IngestionProgress {
+                export_id: self.export_id.clone(),
+                current_step: self.step.0.clone(),
+                progress: self.step.1,
+                message: format!("Saved {} messages", self.written),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Messages(Mutex<Vec<String>>);
+
+    impl ProgressSink for Messages {
+        fn progress(&self, progress: IngestionProgress) {
+            self.0.lock().unwrap().push(progress.message);
+        }
+    }
+
+    #[test]
+    fn test_writes_in_chunks_and_reports_counts() {
+        let db = DatabaseManager::new_in_memory().unwrap();
+        db.insert_export(&test_fixtures::export()).unwrap();
+        let mut run = db.begin_ingestion_run(test_fixtures::EXPORT_ID).unwrap();
+        let sink = Messages::default();
+        let mut writer = EventWriter::new(&db, &sink, test_fixtures::EXPORT_ID, Some("me".to_string()));
+        writer
+            .add_conversations(&mut run, test_fixtures::conversations())
+            .unwrap();
+        writer
+            .add_conversations(&mut run, test_fixtures::conversations())
+            .unwrap();
+        assert_eq!(writer.conversations().len(), test_fixtures::CONVERSATION_COUNT);
+
+        let template = test_fixtures::events().remove(0);
+        let events: Vec<Event> = (0..EVENT_CHUNK_SIZE * 2 + 5)
+            .map(|i| Event {
+                id: format!("bulk_{}", i),
+                sender: String::new(),
+                // Every thousandth event has no conversation and goes to the export's unsorted one
+                conversation_id: (i % 1000 != 0).then(|| template.conversation_id.clone().unwrap()),
+                metadata: Some(r#"{"is_sender":true}"#.to_string()),
+                ..template.clone()
+            })
+            .collect();
+        writer.push(&mut run, events[..EVENT_CHUNK_SIZE + 1].to_vec()).unwrap();
+        assert_eq!(writer.written(), EVENT_CHUNK_SIZE);
+        writer.push(&mut run, events[EVENT_CHUNK_SIZE + 1..].to_vec()).unwrap();
+        writer.flush(&mut run).unwrap();
+
+        assert_eq!(writer.written(), events.len());
+        assert_eq!(
+            *sink.0.lock().unwrap(),
+            ["Saved 10000 messages", "Saved 20000 messages", "Saved 20005 messages"]
+        );
+        assert_eq!(writer.conversations().len(), test_fixtures::CONVERSATION_COUNT + 1);
+        let unsorted = ingestion::unsorted_conversation_id(test_fixtures::EXPORT_ID);
+        let orphans = db
+            .get_export_conversation_events(test_fixtures::EXPORT_ID, &unsorted)
+            .unwrap();
+        assert_eq!(orphans.len(), 21);
+        assert!(orphans.iter().all(|e| e.sender == "me"));
+
+        // Rewrites update stored events without counting them again
+        let mut again = orphans[0].clone();
+        again.content = Some("edited".to_string());
+        writer.rewrite(&mut run, vec![again]).unwrap();
+        assert_eq!(writer.written(), events.len());
+        let stored = db
+            .get_export_conversation_events(test_fixtures::EXPORT_ID, &unsorted)
+            .unwrap();
+        assert_eq!(stored.len(), 21);
+        assert!(stored.iter().any(|e| e.content.as_deref() == Some("edited")));
+    }
+}
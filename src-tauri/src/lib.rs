@@ -3,30 +3,61 @@
 //! Provides IPC commands for detecting, importing, querying, and exporting
 //! Snapchat "My Data" exports. All data is stored locally in SQLite.
 
+pub mod changes;
 pub mod db;
 pub mod downloader;
 pub mod error;
+pub mod export;
+pub mod fs_journal;
+pub mod gallery;
 pub mod ingestion;
+pub mod media_trace;
+pub mod memory_map;
+pub mod metadata_scrub;
 pub mod models;
+pub mod onboarding;
+pub mod perf;
+pub mod response_stats;
 pub mod storage;
+pub mod tasks;
+#[cfg(any(test, feature = "test-fixtures"))]
+pub mod test_fixtures;
+pub mod text_analysis;
+pub mod thumbnails;
 
 use crate::db::DatabaseManager;
-use crate::downloader::MemoryDownloader;
+use crate::downloader::{DownloadControl, DownloadQueueState, MemoryDownloader};
 use crate::error::{AppError, AppResult};
+use crate::gallery::GalleryExporter;
+use crate::ingestion::artifacts;
 use crate::ingestion::detector::ExportDetector;
-use crate::ingestion::extractor::ZipExtractor;
-use crate::ingestion::media_linker::MediaLinker;
-use crate::ingestion::parser::{ChatJsonParser, ChatParser, MemoryParser, PersonParser, SnapHistoryParser};
+use crate::ingestion::extractor::{self, ZipExtractor};
+use crate::ingestion::pipeline::{self, ProgressSink};
+use crate::ingestion::preview::ExportPreviewer;
+use crate::media_trace::OrphanScan;
 use crate::models::{
-    Conversation, Event, ExportSet, ExportSourceType, ExportStats, IngestionProgress, IngestionResult, Memory,
-    MessagePage, PaginatedMedia, SearchResult, ValidationReport,
+    ActiveDatabase, ActiveTask, ActivityBucket, ActivityHeatmap, AdjacentMemories, AppState, BulkExportReport,
+    CallTotal, ConnectionTestResult, Conversation, ConversationAnomaly, ConversationPage, ConversationSort,
+    DataChanged, DatabaseInfo, DatabaseSlot, DateRange, DensityBucket, DetectionSettings, DiagnosticsBundle,
+    DownloadFailureGroup, DownloadJob, DownloadVerification, Event, ExportChanges, ExportOptions, ExportPreview,
+    ExportProgress, ExportSet, ExportSourceType, ExportStats, FsRecoveryReport, GalleryProgress, GalleryReport,
+    ImportOptions, IngestionFailure, IngestionProgress, IngestionResult, IngestionRunRecord, IngestionRunStatus,
+    LocationPoint, MediaDirection, MediaFilter, MediaIdTrace, MediaInfo, MediaLinkingSettings, MediaTimelineMonth,
+    MediaVerifyProgress, Memory, MemoryDetail, MemoryFilter, MemoryMapExport, MemoryPage, MessagePage,
+    MessageSearchFilters, MessageWindow, NetworkSettings, OnThisDayYear, OrphanedMediaPage, OwnerProfile,
+    PaginatedMedia, PathSource, PathsOverview, PerformanceSettings, Person, Redaction, RedactionSummary, RelinkSummary,
+    ResolvedPath, ResponseStats, SavedSearch, ScrubMode, SearchAllResults, SearchIndexProgress, SearchResult,
+    SharedLocation, Tag, TagEntityType, TaggedPage, TextAnalytics, TimelineBucket, TopPhrases, TraceEntry, UserData,
+    UserDataConflictPolicy, UserDataImportSummary, ValidationReport, YearSearchResults,
 };
+use crate::onboarding::{resolve_app_state, ImportTracker};
 use crate::storage::{DiskSpaceInfo, StorageManager};
+use crate::tasks::{TaskGuard, TaskRegistry};
+use crate::thumbnails::{ThumbnailCache, ThumbnailJobState, ThumbnailOutcome, ThumbnailProgress};
 use rayon::prelude::*;
 use simplelog::{ColorChoice, CombinedLogger, Config, LevelFilter, TermLogger, TerminalMode, WriteLogger};
-use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use tauri::{Emitter, Manager, State};
@@ -38,6 +69,16 @@ static DB_MAINTENANCE: AtomicBool = AtomicBool::new(false);
 /// Wrapped in Arc so callers can use it without holding the Mutex lock.
 type DbState = Mutex<Option<Arc<DatabaseManager>>>;
 
+/// A secondary, read-only database opened for inspection, and whether it is the active one.
+#[derive(Default)]
+struct SnapshotDb {
+    db: Option<Arc<DatabaseManager>>,
+    path: Option<PathBuf>,
+    active: bool,
+}
+
+type SnapshotState = Mutex<SnapshotDb>;
+
 fn db_path(app_handle: &tauri::AppHandle) -> AppResult<PathBuf> {
     let dir = app_handle
         .path()
@@ -47,30 +88,80 @@ fn db_path(app_handle: &tauri::AppHandle) -> AppResult<PathBuf> {
 }
 
 /// Get or initialize the shared DatabaseManager. Returns Arc so callers don't hold the lock.
-fn db_from_state(state: &State<'_, DbState>, app_handle: &tauri::AppHandle) -> AppResult<Option<Arc<DatabaseManager>>> {
+fn db_from_state(state: &State<'_, DbState>, app_handle: &tauri::AppHandle) -> AppResult<Arc<DatabaseManager>> {
     if DB_MAINTENANCE.load(Ordering::SeqCst) {
         return Err(AppError::Generic("Data is being reimported. Please wait.".into()));
     }
 
+    // A read-only snapshot, when active, takes the place of the live database
+    if let Some(snapshot) = active_snapshot(app_handle)? {
+        return Ok(snapshot);
+    }
+    live_database(state, app_handle)
+}
+
+/// The cached live database, opened (and created, on first run or after a reset) if needed.
+fn live_database(state: &DbState, app_handle: &tauri::AppHandle) -> AppResult<Arc<DatabaseManager>> {
     // Fast path: check if DB is already loaded
     {
-        let guard = state.lock().map_err(|e| AppError::Generic(format!("DB lock poisoned: {}", e)))?;
+        let guard = state
+            .lock()
+            .map_err(|e| AppError::Generic(format!("DB lock poisoned: {}", e)))?;
         if let Some(db) = guard.as_ref() {
-            return Ok(Some(db.clone()));
+            return Ok(db.clone());
         }
     }
 
-    // Slow path: check filesystem and initialize
     let path = db_path(app_handle)?;
-    if !path.exists() {
-        return Ok(None);
+    let mut guard = state
+        .lock()
+        .map_err(|e| AppError::Generic(format!("DB lock poisoned: {}", e)))?;
+    if let Some(db) = guard.as_ref() {
+        return Ok(db.clone());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
     }
+    let db = DatabaseManager::new(&path)?;
+    // Older imports may have linked macOS "._" resource forks as media
+    match db.purge_os_metadata_media() {
+        Ok(0) => {}
+        Ok(n) => log::info!("Removed macOS metadata media references from {} event(s)", n),
+        Err(e) => log::warn!("Failed to clean macOS metadata media references: {}", e),
+    }
+    let db = Arc::new(db);
+    db.start_maintenance();
+    *guard = Some(db.clone());
+    Ok(db)
+}
+
+fn active_snapshot(app_handle: &tauri::AppHandle) -> AppResult<Option<Arc<DatabaseManager>>> {
+    let Some(state) = app_handle.try_state::<SnapshotState>() else {
+        return Ok(None);
+    };
+    let guard = state
+        .lock()
+        .map_err(|e| AppError::Generic(format!("Snapshot lock poisoned: {}", e)))?;
+    Ok(if guard.active { guard.db.clone() } else { None })
+}
 
-    let mut guard = state.lock().map_err(|e| AppError::Generic(format!("DB lock poisoned: {}", e)))?;
-    if guard.is_none() {
-        *guard = Some(Arc::new(DatabaseManager::new(&path)?));
+/// Fail with `ReadOnlyDatabase` if a snapshot is active. Used by operations that write to
+/// the live database directly (imports, resets, downloads) rather than through a query.
+fn ensure_live_database(app_handle: &tauri::AppHandle) -> AppResult<()> {
+    if active_snapshot(app_handle)?.is_some() {
+        return Err(AppError::ReadOnlyDatabase(
+            "a read-only database is active; switch back to the live database first".into(),
+        ));
     }
-    Ok(guard.clone())
+    Ok(())
+}
+
+fn thumbnail_cache(app_handle: &tauri::AppHandle) -> AppResult<ThumbnailCache> {
+    let dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::Generic(format!("Failed to resolve app data directory: {}", e)))?;
+    Ok(ThumbnailCache::new(dir.join("thumbnails")))
 }
 
 /// Clear the cached database (called during reset/reimport).
@@ -80,11 +171,29 @@ fn clear_db_cache(app_handle: &tauri::AppHandle) {
     }
 }
 
+/// Saved detection settings, or the defaults when the database can't be opened; a scan
+/// shouldn't fail over settings.
+fn detection_settings(state: &DbState, app_handle: &tauri::AppHandle) -> DetectionSettings {
+    match live_database(state, app_handle).and_then(|db| db.get_detection_settings()) {
+        Ok(settings) => settings,
+        Err(e) => {
+            log::warn!("Using default export detection settings: {}", e);
+            DetectionSettings::default()
+        }
+    }
+}
+
 #[tauri::command]
-async fn detect_exports(path: String) -> AppResult<Vec<ExportSet>> {
+async fn detect_exports(
+    path: String,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<Vec<ExportSet>> {
+    let _trace = perf::command("detect_exports");
     let path = PathBuf::from(&path);
     log::debug!("detect_exports called with path: {:?}", path);
-    let result = ExportDetector::detect_in_directory(&path);
+    let settings = detection_settings(&state, &app_handle);
+    let result = ExportDetector::detect_in_directory_with(&path, &settings);
     match &result {
         Ok(exports) => log::info!("detect_exports: found {} export(s)", exports.len()),
         Err(e) => log::error!("detect_exports failed: {}", e),
@@ -93,853 +202,2533 @@ async fn detect_exports(path: String) -> AppResult<Vec<ExportSet>> {
 }
 
 #[tauri::command]
-async fn auto_detect_exports() -> AppResult<Vec<ExportSet>> {
+async fn auto_detect_exports(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<Vec<ExportSet>> {
+    let _trace = perf::command("auto_detect_exports");
     log::info!("auto_detect_exports called");
-    ExportDetector::detect_in_standard_paths()
+    ExportDetector::detect_in_standard_paths(&detection_settings(&state, &app_handle))
 }
 
 #[tauri::command]
-async fn process_export(export: ExportSet, app_handle: tauri::AppHandle) -> AppResult<()> {
-    log::info!("process_export: starting (type: {:?})", export.source_type);
-    log::debug!("process_export: {} source path(s)", export.source_paths.len());
-
-    let app_data = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| AppError::Generic(format!("Failed to resolve app data directory: {}", e)))?;
-    let working_dir = app_data.join("exports");
+async fn get_detection_settings(
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<DetectionSettings> {
+    let _trace = perf::command("get_detection_settings");
+    live_database(&state, &app_handle)?.get_detection_settings()
+}
 
-    if !working_dir.exists() {
-        fs::create_dir_all(&working_dir)?;
+/// Save the extra name patterns and probe limit used by the next export scan.
+#[tauri::command]
+async fn set_detection_settings(
+    settings: DetectionSettings,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<()> {
+    let _trace = perf::command("set_detection_settings");
+    ensure_live_database(&app_handle)?;
+    let settings = DetectionSettings {
+        name_patterns: settings
+            .name_patterns
+            .into_iter()
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty())
+            .collect(),
+        ..settings
+    };
+    if settings.name_patterns.iter().any(|p| p.chars().all(|c| c == '*')) {
+        return Err(AppError::Validation("A pattern must match more than every name".into()));
     }
+    live_database(&state, &app_handle)?.set_detection_settings(&settings)
+}
 
-    // Run everything on a blocking thread to avoid starving the async runtime
-    let handle = app_handle.clone();
-    let original_export = export.clone();
-    tauri::async_runtime::spawn_blocking(move || {
-        // Extract zips if needed (heavy I/O)
-        let working_path = if original_export.source_type == ExportSourceType::Zip {
-            ZipExtractor::extract(&original_export.source_paths, &working_dir, &original_export.id, &handle)?
-        } else {
-            // For folders, we use the first path as the primary (usually the one containing index.html)
-            original_export.source_paths.first().cloned().ok_or_else(|| AppError::Generic("No source paths provided".into()))?
-        };
-
-        tauri::async_runtime::block_on(reconstruct_from_path(original_export, working_path, handle))
-    })
-    .await
-    .map_err(|e| AppError::Generic(format!("Thread join error: {}", e)))??;
-
-    Ok(())
+#[tauri::command]
+async fn get_media_linking_settings(
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<MediaLinkingSettings> {
+    let _trace = perf::command("get_media_linking_settings");
+    live_database(&state, &app_handle)?.get_media_linking_settings()
 }
 
-async fn reconstruct_from_path(
-    original_export: ExportSet,
-    source_path: PathBuf,
+/// Save the file name patterns and date window used to link media whose names carry no
+/// Media ID. Each pattern needs a capture group for the ID.
+#[tauri::command]
+async fn set_media_linking_settings(
+    settings: MediaLinkingSettings,
+    state: State<'_, DbState>,
     app_handle: tauri::AppHandle,
 ) -> AppResult<()> {
-    let export_id = original_export.id.clone();
-    let db = db_path(&app_handle)?;
-
-    if let Some(parent) = db.parent() {
-        if !parent.exists() {
-            fs::create_dir_all(parent)?;
+    let _trace = perf::command("set_media_linking_settings");
+    ensure_live_database(&app_handle)?;
+    let settings = MediaLinkingSettings {
+        filename_patterns: settings
+            .filename_patterns
+            .into_iter()
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty())
+            .collect(),
+        ..settings
+    };
+    for pattern in &settings.filename_patterns {
+        let regex = regex::Regex::new(pattern)
+            .map_err(|e| AppError::Validation(format!("Invalid pattern {:?}: {}", pattern, e)))?;
+        if regex.captures_len() < 2 {
+            return Err(AppError::Validation(format!(
+                "Pattern {:?} needs a capture group for the media ID",
+                pattern
+            )));
         }
     }
-
-    let database = Arc::new(DatabaseManager::new(&db)?);
-    // Cache the new database in Tauri managed state
-    if let Ok(mut guard) = app_handle.state::<DbState>().lock() {
-        *guard = Some(database.clone());
+    if settings.date_window_minutes < 0 {
+        return Err(AppError::Validation("The date window cannot be negative".into()));
     }
-    let mut warnings: Vec<String> = Vec::new();
-    let mut errors: Vec<String> = Vec::new();
+    live_database(&state, &app_handle)?.set_media_linking_settings(&settings)
+}
 
+/// Parse a small sample of an export without extracting it or touching the database.
+#[tauri::command]
+async fn preview_export(export: ExportSet, sample_size: Option<usize>) -> AppResult<ExportPreview> {
+    let _trace = perf::command("preview_export");
+    let sample_size = sample_size.unwrap_or(50).clamp(1, 500);
     log::info!(
-        "reconstruct_from_path: starting for export_id={}, type={:?}",
-        export_id,
-        original_export.source_type
-    );
-    log::debug!("reconstruct_from_path: source path: {:?}", source_path);
-
-    let _ = app_handle.emit(
-        "ingestion-progress",
-        IngestionProgress {
-            export_id: export_id.clone(),
-            current_step: "Initializing".to_string(),
-            progress: 0.05,
-            message: "Setting up database...".to_string(),
-        },
+        "preview_export: sampling up to {} events (type: {:?})",
+        sample_size,
+        export.source_type
     );
+    tauri::async_runtime::spawn_blocking(move || ExportPreviewer::preview(&export, sample_size))
+        .await
+        .map_err(|e| AppError::Generic(format!("Thread join error: {}", e)))?
+}
 
-    // Store original export info (preserves source_path and source_type for reimport)
-    // Mark as Incomplete initially to prevent corruption if process fails mid-way
-    let mut processing_export = original_export.clone();
-    processing_export.validation_status = crate::models::ValidationStatus::Incomplete;
-    database.insert_export(&processing_export)?;
-
-    // --- Phase: Friends Resolution ---
-    app_handle
-        .emit(
-            "ingestion-progress",
-            IngestionProgress {
-                export_id: export_id.clone(),
-                current_step: "Resolving Identities".to_string(),
-                progress: 0.08,
-                message: "Resolving friends and contacts...".to_string(),
-            },
-        )
-        .ok();
-
-    let friends_json = source_path.join("json").join("friends.json");
-    if friends_json.exists() {
-        match PersonParser::parse_friends_json(&friends_json) {
-            Ok(people) => {
-                log::info!("Parsed {} people from friends.json", people.len());
-                database.insert_people(&people)?;
-            }
-            Err(e) => {
-                log::error!("Failed to parse friends.json: {}", e);
-                warnings.push(format!("Could not parse friends list: {}", e));
-            }
-        }
-    } else {
-        log::debug!("No friends.json found at {:?}", friends_json);
-    }
-
-    // --- Phase: Chat HTML Parsing ---
-    let mut all_conversations = Vec::new();
-    let mut all_events = Vec::new();
-    let mut parse_failures = 0;
-
-    let chat_html_dir = source_path.join("html").join("chat_history");
-    if chat_html_dir.is_dir() {
-        let entries: Vec<_> = fs::read_dir(&chat_html_dir)?.collect::<Result<Vec<_>, _>>()?;
-        let total_files = entries.len();
-        log::info!("Found {} files in chat_history directory", total_files);
-
-        let results: Vec<_> = entries
-            .par_iter()
-            .filter_map(|entry| {
-                let path = entry.path();
-                if path.is_file()
-                    && path.extension().is_some_and(|ext| ext == "html")
-                    && path
-                        .file_name()
-                        .is_some_and(|n| n.to_string_lossy().starts_with("subpage_"))
-                {
-                    Some((path.clone(), ChatParser::parse_subpage(&path)))
-                } else {
-                    None
-                }
-            })
-            .collect();
-
-        for (path, res) in results {
-            match res {
-                Ok((conv, events)) => {
-                    all_conversations.push(conv);
-                    all_events.extend(events);
-                }
-                Err(e) => {
-                    parse_failures += 1;
-                    log::error!("Failed to parse {:?}: {}", path.file_name(), e);
-                    warnings.push(format!(
-                        "Failed to parse {}: {}",
-                        path.file_name().unwrap_or_default().to_string_lossy(),
-                        e
-                    ));
-                }
-            }
-        }
-    } else {
-        log::warn!("Chat history directory not found in export");
-        log::debug!("Expected chat_history at: {:?}", chat_html_dir);
-        warnings.push("No chat_history directory found in export".to_string());
-    }
+#[tauri::command]
+async fn process_export(
+    mut export: ExportSet,
+    skip_unreadable_parts: Option<bool>,
+    keep_extracted_on_failure: Option<bool>,
+    options: Option<ImportOptions>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<()> {
+    let _trace = perf::command("process_export");
+    ensure_live_database(&app_handle)?;
+    let options = options.unwrap_or_default();
+    log::info!(
+        "process_export: starting (type: {:?}, {:?})",
+        export.source_type,
+        options
+    );
+    log::debug!("process_export: {} source path(s)", export.source_paths.len());
 
-    if parse_failures > 0 {
-        log::warn!("{} chat files failed to parse", parse_failures);
+    // Refuse to start on damaged zip parts rather than failing midway through extraction
+    if export.source_type == ExportSourceType::Zip {
+        ExportDetector::prepare_zip_parts(&mut export, skip_unreadable_parts.unwrap_or(false))?;
     }
 
-    // Initialize set for O(1) lookups in subsequent phases
-    let mut convo_set: std::collections::HashSet<String> =
-        all_conversations.iter().map(|c| c.id.clone()).collect();
+    let database = open_live_database(&app_handle)?;
+    let (working_dir, _) = extraction_root(&database, &app_handle)?;
+    let sink = TauriProgress::start(&app_handle, &export.id)?;
 
-    // --- Phase: JSON Chat History (Media IDs source) ---
-    app_handle
-        .emit(
-            "ingestion-progress",
-            IngestionProgress {
-                export_id: export_id.clone(),
-                current_step: "Parsing Chat JSON".to_string(),
-                progress: 0.38,
-                message: "Extracting media ID mappings from chat history JSON...".to_string(),
-            },
-        )
-        .ok();
+    // Run everything on a blocking thread to avoid starving the async runtime
+    let handle = app_handle.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        pipeline::import_export(
+            &database,
+            export,
+            &working_dir,
+            options,
+            keep_extracted_on_failure.unwrap_or(false),
+            &sink,
+        )?;
+        after_import(&handle, &database);
+        Ok::<_, AppError>(())
+    })
+    .await
+    .map_err(|e| AppError::Generic(format!("Thread join error: {}", e)))??;
 
-    let chat_json = source_path.join("json").join("chat_history.json");
-    if chat_json.exists() {
-        match ChatJsonParser::parse_chat_history_json(&chat_json) {
-            Ok(json_conversations) => {
-                let json_event_count: usize = json_conversations.iter().map(|(_, e)| e.len()).sum();
-                log::info!(
-                    "ChatJsonParser: {} conversations, {} events from JSON",
-                    json_conversations.len(),
-                    json_event_count
-                );
+    Ok(())
+}
 
-                let mut merged_ids = 0;
-                let mut new_events_added = 0;
-
-                // Build index for O(1) lookup by (conversation_id, sender) instead of O(n) scan
-                let mut event_index: HashMap<(String, String), Vec<usize>> = HashMap::new();
-                for (idx, event) in all_events.iter().enumerate() {
-                    if let Some(cid) = &event.conversation_id {
-                        event_index
-                            .entry((cid.clone(), event.sender.clone()))
-                            .or_default()
-                            .push(idx);
-                    }
-                }
+/// Run the phases a partial import skipped, without redoing the ones it finished. Reuses the
+/// extraction directory when it is still there and re-extracts the zips otherwise. Returns the
+/// phases imported so far.
+#[tauri::command]
+async fn complete_import(
+    export_id: String,
+    options: Option<ImportOptions>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<ImportOptions> {
+    let _trace = perf::command("complete_import");
+    ensure_live_database(&app_handle)?;
+    let database = open_live_database(&app_handle)?;
+    let mut export = database
+        .get_exports()?
+        .into_iter()
+        .find(|e| e.id == export_id)
+        .ok_or_else(|| AppError::NotFound(format!("Export {}", export_id)))?;
+    let done = export.import_phases.unwrap_or_default();
+    let options = options.unwrap_or_default();
+    if options.remaining(&done).is_empty() {
+        log::info!("complete_import: nothing left to import for {}", export_id);
+        return Ok(done);
+    }
 
-                let mut new_convos = Vec::new();
-                let mut new_convo_ids = std::collections::HashSet::new();
-                let mut new_events = Vec::new();
-
-                for (convo_key, json_events) in json_conversations {
-                    for json_event in json_events {
-                        // Look up candidates by (conversation_id, sender) in O(1)
-                        let key = (convo_key.clone(), json_event.sender.clone());
-                        let matched_idx = event_index.get(&key).and_then(|indices| {
-                            indices.iter().find(|&&idx| {
-                                let existing = &all_events[idx];
-                                (existing.timestamp - json_event.timestamp).num_seconds().abs() <= 2
-                                    && existing.metadata.is_none()
-                            }).copied()
-                        });
-
-                        if let Some(idx) = matched_idx {
-                            all_events[idx].metadata = json_event.metadata.clone();
-                            merged_ids += 1;
-                        } else {
-                            if !convo_set.contains(&convo_key) && !new_convo_ids.contains(&convo_key) {
-                                let display_name = json_event.metadata.as_ref().and_then(|m| {
-                                    serde_json::from_str::<serde_json::Value>(m)
-                                        .ok()
-                                        .and_then(|v| v.get("conversation_title")?.as_str().map(|s| s.to_string()))
-                                });
-                                new_convos.push(Conversation {
-                                    id: convo_key.clone(),
-                                    display_name,
-                                    participants: Vec::new(),
-                                    last_event_at: Some(json_event.timestamp),
-                                    message_count: 0,
-                                    has_media: false,
-                                });
-                                new_convo_ids.insert(convo_key.clone());
-                            }
-                            new_events.push(json_event);
-                            new_events_added += 1;
-                        }
-                    }
+    let (working_dir, _) = extraction_root(&database, &app_handle)?;
+    let (source_path, needs_extraction) = match export.source_type {
+        ExportSourceType::Zip => {
+            let (extraction_dir, needs_extraction) = match export.extraction_path.clone() {
+                Some(dir) => {
+                    let reusable = ZipExtractor::is_reusable(&dir, &export.source_paths);
+                    (dir, !reusable)
                 }
-
-                all_conversations.extend(new_convos);
-                all_events.extend(new_events);
-                // Update convo_set for next phase
-                for id in new_convo_ids {
-                    convo_set.insert(id);
+                // Imports from before directories were recorded extracted into one named by id
+                None if working_dir.join(&export.id).is_dir() => (working_dir.join(&export.id), false),
+                None => (
+                    working_dir.join(extractor::working_dir_name(
+                        &export.id,
+                        &export.source_paths,
+                        chrono::Utc::now(),
+                    )),
+                    true,
+                ),
+            };
+            if needs_extraction {
+                if let Some(missing) = export.source_paths.iter().find(|p| !p.exists()) {
+                    return Err(AppError::Validation(format!(
+                        "The extracted files are gone and {} no longer exists. Reimport the export instead.",
+                        missing.display()
+                    )));
                 }
-
-                log::info!(
-                    "JSON merge: {} events enriched with media IDs, {} new events added",
-                    merged_ids,
-                    new_events_added
-                );
-            }
-            Err(e) => {
-                log::error!("Failed to parse chat_history.json: {}", e);
-                errors.push(format!("Could not parse chat history JSON: {}", e));
+                ExportDetector::prepare_zip_parts(&mut export, false)?;
             }
+            export.extraction_path = Some(extraction_dir.clone());
+            (extraction_dir, needs_extraction)
         }
-    } else {
-        log::debug!("No chat_history.json found at {:?}", chat_json);
-    }
-
-    // --- Phase: Snap History (JSON) ---
-    app_handle
-        .emit(
-            "ingestion-progress",
-            IngestionProgress {
-                export_id: export_id.clone(),
-                current_step: "Parsing Snap History".to_string(),
-                progress: 0.42,
-                message: "Processing snap history metadata...".to_string(),
-            },
-        )
-        .ok();
-
-    let snap_json = source_path.join("json").join("snap_history.json");
-    if snap_json.exists() {
-        match SnapHistoryParser::parse_snap_history_json(&snap_json) {
-            Ok(snap_conversations) => {
-                let snap_event_count: usize = snap_conversations.iter().map(|(_, e)| e.len()).sum();
-                log::info!(
-                    "Parsed {} snap history conversations with {} events",
-                    snap_conversations.len(),
-                    snap_event_count
-                );
-                
-                // Ensure convo_set is up to date (it was updated after JSON merge)
-                if convo_set.is_empty() && !all_conversations.is_empty() {
-                     convo_set = all_conversations.iter().map(|c| c.id.clone()).collect();
-                }
+        ExportSourceType::Folder => {
+            let folder = export
+                .source_paths
+                .first()
+                .filter(|p| p.is_dir())
+                .cloned()
+                .ok_or_else(|| {
+                    AppError::Validation("The export folder no longer exists. Reimport the export instead.".into())
+                })?;
+            (folder, false)
+        }
+    };
 
-                for (convo_key, events) in snap_conversations {
-                    if !convo_set.contains(&convo_key) {
-                        all_conversations.push(Conversation {
-                            id: convo_key.clone(),
-                            display_name: None,
-                            participants: Vec::new(),
-                            last_event_at: events.last().map(|e| e.timestamp),
-                            message_count: events.len() as i32,
-                            has_media: false,
-                        });
-                        convo_set.insert(convo_key.clone());
-                    }
-                    all_events.extend(events);
+    let sink = TauriProgress::start(&app_handle, &export.id)?;
+    let mut run = database.begin_ingestion_run(&export.id)?;
+    let handle = app_handle.clone();
+    let db = database.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let outcome = (|| {
+            if needs_extraction {
+                ZipExtractor::extract(&export.source_paths, &source_path, &export.id, &sink)?;
+            }
+            pipeline::reconstruct_from_path(&db, &mut run, export, source_path.clone(), options, done, &sink)
+        })();
+        match outcome {
+            Ok(_) => {
+                if let Err(e) = db.record_ingestion_run(&run, IngestionRunStatus::Completed, None, None) {
+                    log::warn!("Could not record ingestion history: {}", e);
                 }
+                after_import(&handle, &db);
+                Ok(())
             }
             Err(e) => {
-                log::error!("Failed to parse snap_history.json: {}", e);
-                errors.push(format!("Could not parse snap history: {}", e));
+                // A cancelled re-extraction is incomplete; a failed one is kept to look into
+                let partial_extraction =
+                    (needs_extraction && matches!(e, AppError::Cancelled(_))).then_some(source_path.as_path());
+                pipeline::clean_up_failed_ingestion(&db, &run, partial_extraction, &e, &sink);
+                Err(e)
             }
         }
-    } else {
-        log::info!("No snap_history.json found");
-    }
-
-    // --- Phase: Media Linking ---
-    app_handle
-        .emit(
-            "ingestion-progress",
-            IngestionProgress {
-                export_id: export_id.clone(),
-                current_step: "Linking Media".to_string(),
-                progress: 0.50,
-                message: "Resolving media file references...".to_string(),
-            },
-        )
-        .ok();
+    })
+    .await
+    .map_err(|e| AppError::Generic(format!("Thread join error: {}", e)))??;
 
-    let chat_media_dir = source_path.join("chat_media");
-    let media_dir = source_path.join("media");
+    Ok(database
+        .get_exports()?
+        .into_iter()
+        .find(|e| e.id == export_id)
+        .and_then(|e| e.import_phases)
+        .unwrap_or(done))
+}
+
+/// Open (creating if needed) the live database and cache it in managed state.
+fn open_live_database(app_handle: &tauri::AppHandle) -> AppResult<Arc<DatabaseManager>> {
+    let db = db_path(app_handle)?;
+    if let Some(parent) = db.parent() {
+        if !parent.exists() {
+            fs::create_dir_all(parent)?;
+        }
+    }
 
-    let mut linker = MediaLinker::new(&chat_media_dir);
-    if media_dir.is_dir() {
-        linker.add_media_directory(&media_dir);
+    let database = Arc::new(DatabaseManager::new(&db)?);
+    database.start_maintenance();
+    if let Ok(mut guard) = app_handle.state::<DbState>().lock() {
+        *guard = Some(database.clone());
     }
+    Ok(database)
+}
 
-    all_events.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
-    linker.link_media(&mut all_events);
+/// Relays import progress to the frontend and remembers it for `get_app_state`. Holds the
+/// import's task, which `cancel_ingestion` and `cancel_task` stop it through.
+struct TauriProgress {
+    app_handle: tauri::AppHandle,
+    task: TaskGuard,
+}
 
-    // Build per-conversation stats in O(N) using a HashMap
-    let mut conv_stats: HashMap<String, (usize, Option<chrono::DateTime<chrono::Utc>>)> = HashMap::new();
-    for event in &all_events {
-        if let Some(cid) = &event.conversation_id {
-            let entry = conv_stats.entry(cid.clone()).or_insert((0, None));
-            entry.0 += 1;
-            match entry.1 {
-                Some(ref ts) if event.timestamp > *ts => entry.1 = Some(event.timestamp),
-                None => entry.1 = Some(event.timestamp),
-                _ => {}
-            }
-        }
+impl TauriProgress {
+    /// Register an import of `export_id`; fails while one is already running.
+    fn start(app_handle: &tauri::AppHandle, export_id: &str) -> AppResult<Self> {
+        let task = app_handle
+            .state::<TaskRegistry>()
+            .register_keyed("Import export", export_id)?;
+        Ok(Self {
+            app_handle: app_handle.clone(),
+            task,
+        })
     }
+}
 
-    for conv in &mut all_conversations {
-        if let Some((count, last_ts)) = conv_stats.get(&conv.id) {
-            conv.message_count = (*count).min(i32::MAX as usize) as i32;
-            if let Some(ts) = last_ts {
-                conv.last_event_at = Some(*ts);
-            }
+impl ProgressSink for TauriProgress {
+    fn progress(&self, progress: IngestionProgress) {
+        self.task.heartbeat(Some(progress.progress), Some(&progress.message));
+        if let Some(tracker) = self.app_handle.try_state::<ImportTracker>() {
+            tracker.update(&progress);
         }
+        let _ = self.app_handle.emit("ingestion-progress", progress);
     }
 
-    // --- Phase: Memories Parsing ---
-    app_handle
-        .emit(
-            "ingestion-progress",
-            IngestionProgress {
-                export_id: export_id.clone(),
-                current_step: "Processing Memories".to_string(),
-                progress: 0.65,
-                message: "Parsing memories history...".to_string(),
-            },
-        )
-        .ok();
-
-    let memories_json = source_path.join("json").join("memories_history.json");
-    let mut all_memories = Vec::new();
-    if memories_json.exists() {
-        match MemoryParser::parse_memories_json(&memories_json, &export_id) {
-            Ok(memories) => {
-                log::info!("Parsed {} memories", memories.len());
-                all_memories = memories;
-            }
-            Err(e) => {
-                log::error!("Failed to parse memories_history.json: {}", e);
-                errors.push(format!("Could not parse memories: {}", e));
-            }
+    fn is_cancelled(&self) -> bool {
+        self.task.is_cancelled()
+    }
+
+    fn result(&self, result: &IngestionResult) {
+        let _ = self.app_handle.emit("ingestion-result", result);
+    }
+
+    fn failed(&self, failure: IngestionFailure) {
+        if let Some(tracker) = self.app_handle.try_state::<ImportTracker>() {
+            tracker.finish();
         }
-    } else {
-        log::info!("No memories_history.json found");
-    }
-
-    // --- Phase: Save to Database ---
-    app_handle
-        .emit(
-            "ingestion-progress",
-            IngestionProgress {
-                export_id: export_id.clone(),
-                current_step: "Saving to Database".to_string(),
-                progress: 0.75,
-                message: format!(
-                    "Indexing {} conversations, {} messages, {} memories...",
-                    all_conversations.len(),
-                    all_events.len(),
-                    all_memories.len()
-                ),
-            },
-        )
-        .ok();
+        let _ = self.app_handle.emit("ingestion-failed", failure);
+    }
+}
 
-    database.batch_insert_conversations(&all_conversations)?;
-    database.batch_insert_events(&all_events, &export_id)?;
+/// Stop the running import of `export_id` and return once what it wrote has been rolled back
+/// and its partial extraction removed.
+#[tauri::command]
+async fn cancel_ingestion(export_id: String, app_handle: tauri::AppHandle) -> AppResult<()> {
+    let _trace = perf::command("cancel_ingestion");
+    let tasks = app_handle.state::<TaskRegistry>().inner().clone();
+    let id = tasks
+        .find(&export_id)
+        .ok_or_else(|| AppError::NotFound(format!("running import of {}", export_id)))?;
+    tasks.cancel(id)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        while tasks.is_running(id) {
+            std::thread::sleep(changes::POLL_INTERVAL);
+        }
+    })
+    .await
+    .map_err(|e| AppError::Generic(format!("Thread join error: {}", e)))?;
+    log::info!("Import of {} cancelled", export_id);
+    Ok(())
+}
 
-    if !all_memories.is_empty() {
-        database.batch_insert_memories(&all_memories)?;
+/// Follow-up work the app starts once an import succeeds.
+fn after_import(app_handle: &tauri::AppHandle, database: &Arc<DatabaseManager>) {
+    let auto_pregenerate = database.get_setting("auto_pregenerate_thumbnails").ok().flatten();
+    if auto_pregenerate.as_deref() == Some("true") {
+        if let Err(e) = start_thumbnail_pregeneration(app_handle.clone(), database.clone(), None) {
+            log::warn!("Could not start thumbnail pre-generation: {}", e);
+        }
     }
+}
 
-    log::info!(
-        "Ingestion complete: {} conversations, {} events, {} memories, {} warnings, {} errors",
-        all_conversations.len(),
-        all_events.len(),
-        all_memories.len(),
-        warnings.len(),
-        errors.len()
-    );
+#[tauri::command]
+async fn get_ingestion_history(
+    limit: Option<i32>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<Vec<IngestionRunRecord>> {
+    let _trace = perf::command("get_ingestion_history");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_ingestion_history(limit.unwrap_or(50).clamp(1, 500))
+}
 
-    // Emit the detailed result
-    let result = IngestionResult {
-        export_id: export_id.clone(),
-        conversations_parsed: all_conversations.len() as i32,
-        events_parsed: all_events.len() as i32,
-        memories_parsed: all_memories.len() as i32,
-        parse_failures,
-        warnings: warnings.clone(),
-        errors: errors.clone(),
-    };
-    let _ = app_handle.emit("ingestion-result", &result);
-
-    app_handle
-        .emit(
-            "ingestion-progress",
-            IngestionProgress {
-                export_id: export_id.clone(),
-                current_step: "Complete".to_string(),
-                progress: 1.0,
-                message: format!(
-                    "Indexed {} conversations, {} messages, {} memories.",
-                    all_conversations.len(),
-                    all_events.len(),
-                    all_memories.len()
-                ),
-            },
-        )
-        .ok();
+/// Recompute cached media counts and sizes after files were moved, deleted or relinked.
+#[tauri::command]
+async fn refresh_media_stats(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<usize> {
+    let _trace = perf::command("refresh_media_stats");
+    let db = db_from_state(&state, &app_handle)?;
+    let tasks = app_handle.state::<TaskRegistry>().inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let _task = tasks.register("Refresh media stats", false);
+        pipeline::refresh_media_stats_for(&db)
+    })
+    .await
+    .map_err(|e| AppError::Generic(format!("Thread join error: {}", e)))?
+}
 
-    Ok(())
+/// Link media files in `media_dir` to messages imported without them, without a full reimport.
+#[tauri::command]
+async fn relink_media(
+    media_dir: String,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<RelinkSummary> {
+    let _trace = perf::command("relink_media");
+    ensure_live_database(&app_handle)?;
+    let media_dir = PathBuf::from(media_dir);
+    if !media_dir.is_dir() {
+        return Err(AppError::Validation(format!("{} is not a folder", media_dir.display())));
+    }
+    let db = db_from_state(&state, &app_handle)?;
+    let tasks = app_handle.state::<TaskRegistry>().inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let _task = tasks.register("Relink media", false);
+        pipeline::relink_media_for(&db, &media_dir)
+    })
+    .await
+    .map_err(|e| AppError::Generic(format!("Thread join error: {}", e)))?
 }
 
+/// Recompute media totals and completeness for every conversation.
 #[tauri::command]
-async fn get_conversations(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<Vec<Conversation>> {
-    match db_from_state(&state, &app_handle)? {
-        Some(db) => db.get_conversations(),
-        None => Ok(Vec::new()),
+async fn refresh_conversation_stats(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<usize> {
+    let _trace = perf::command("refresh_conversation_stats");
+    let db = db_from_state(&state, &app_handle)?;
+    let tasks = app_handle.state::<TaskRegistry>().inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let _task = tasks.register("Refresh conversation stats", false);
+        pipeline::refresh_conversation_stats_for(&db)
+    })
+    .await
+    .map_err(|e| AppError::Generic(format!("Thread join error: {}", e)))?
+}
+
+/// The export a query is limited to: the one asked for, else the active export, else none.
+fn export_scope(db: &DatabaseManager, export_id: Option<String>) -> AppResult<Option<String>> {
+    match export_id {
+        Some(id) => Ok(Some(id)),
+        None => db.get_active_export(),
     }
 }
 
+#[tauri::command]
+async fn get_conversations(
+    export_id: Option<String>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<Vec<Conversation>> {
+    let _trace = perf::command("get_conversations");
+    let db = db_from_state(&state, &app_handle)?;
+    let export_id = export_scope(&db, export_id)?;
+    db.get_conversations(export_id.as_deref())
+}
+
+#[tauri::command]
+async fn get_conversations_page(
+    offset: i32,
+    limit: i32,
+    sort: Option<ConversationSort>,
+    name_filter: Option<String>,
+    export_id: Option<String>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<ConversationPage> {
+    let _trace = perf::command("get_conversations_page");
+    let db = db_from_state(&state, &app_handle)?;
+    let export_id = export_scope(&db, export_id)?;
+    db.get_conversations_page(
+        offset,
+        limit,
+        sort.unwrap_or_default(),
+        name_filter.as_deref(),
+        export_id.as_deref(),
+    )
+}
+
+#[tauri::command]
+async fn get_conversation(
+    conversation_id: String,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<Conversation> {
+    let _trace = perf::command("get_conversation");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_conversation(&conversation_id)
+}
+
+/// Problems found in imported conversations, for one conversation or all of them.
+#[tauri::command]
+async fn get_validation_issues(
+    conversation_id: Option<String>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<Vec<ConversationAnomaly>> {
+    let _trace = perf::command("get_validation_issues");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_validation_issues(conversation_id.as_deref())
+}
+
+/// The account the imported data belongs to, so its messages can be shown as "You".
+#[tauri::command]
+async fn get_owner_profile(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<Option<OwnerProfile>> {
+    let _trace = perf::command("get_owner_profile");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_owner_profile()
+}
+
+/// Everyone in the database, with avatar images and fallback colors.
+#[tauri::command]
+async fn get_people(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<Vec<Person>> {
+    let _trace = perf::command("get_people");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_people()
+}
+
 #[tauri::command]
 async fn get_conversation_name(
     conversation_id: String,
     state: State<'_, DbState>,
     app_handle: tauri::AppHandle,
 ) -> AppResult<Option<String>> {
-    match db_from_state(&state, &app_handle)? {
-        Some(db) => db.get_conversation_name(&conversation_id),
-        None => Ok(None),
-    }
+    let _trace = perf::command("get_conversation_name");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_conversation_name(&conversation_id)
+}
+
+#[tauri::command]
+async fn get_messages(
+    conversation_id: String,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<Vec<Event>> {
+    let _trace = perf::command("get_messages");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_messages(&conversation_id)
+}
+
+#[tauri::command]
+async fn get_messages_page(
+    conversation_id: String,
+    offset: i32,
+    limit: i32,
+    saved_only: Option<bool>,
+    event_types: Option<Vec<String>>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<MessagePage> {
+    let _trace = perf::command("get_messages_page");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_messages_page(
+        &conversation_id,
+        offset,
+        limit,
+        saved_only.unwrap_or(false),
+        event_types.as_deref(),
+    )
+}
+
+#[tauri::command]
+async fn get_export_stats(
+    export_id: Option<String>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<Option<ExportStats>> {
+    let _trace = perf::command("get_export_stats");
+    let db = db_from_state(&state, &app_handle)?;
+    let export_id = export_scope(&db, export_id)?;
+    Ok(Some(db.get_export_stats(export_id.as_deref())?))
+}
+
+/// The export the conversation list, stats, memories and search are limited to.
+#[tauri::command]
+async fn get_active_export(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<Option<String>> {
+    let _trace = perf::command("get_active_export");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_active_export()
+}
+
+/// Limit the views to one export, or show every export again with None.
+#[tauri::command]
+async fn set_active_export(
+    export_id: Option<String>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<()> {
+    let _trace = perf::command("set_active_export");
+    ensure_live_database(&app_handle)?;
+    let db = db_from_state(&state, &app_handle)?;
+    db.set_active_export(export_id.as_deref())
+}
+
+#[tauri::command]
+async fn get_exports(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<Vec<ExportSet>> {
+    let _trace = perf::command("get_exports");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_exports()
+}
+
+#[tauri::command]
+async fn search_messages(
+    query: String,
+    limit: Option<i32>,
+    prefix: Option<bool>,
+    export_id: Option<String>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<Vec<SearchResult>> {
+    let _trace = perf::command("search_messages");
+    if query.len() > 500 {
+        return Err(AppError::Validation(
+            "Search query too long (max 500 characters)".into(),
+        ));
+    }
+    let db = db_from_state(&state, &app_handle)?;
+    let prefix = prefix.unwrap_or(false);
+    let export_id = export_scope(&db, export_id)?;
+    let results = db.search_messages(&query, limit.unwrap_or(50), prefix, export_id.as_deref())?;
+    // Prefix searches fire on every keystroke, so only completed searches go in the recent list.
+    // Recording happens off the request path so it never slows the search down.
+    if !prefix && !db.is_read_only() {
+        tauri::async_runtime::spawn_blocking(move || {
+            if let Err(e) = db.record_search(&query, None) {
+                log::warn!("Could not record search: {}", e);
+            }
+        });
+    }
+    Ok(results)
+}
+
+/// Messages matching `query` sent on `month`/`day` of any year, grouped by year with up to
+/// `per_year` (default 20) shown per year.
+#[tauri::command]
+async fn search_messages_on_day(
+    query: String,
+    month: u32,
+    day: u32,
+    filters: Option<MessageSearchFilters>,
+    per_year: Option<i32>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<Vec<YearSearchResults>> {
+    let _trace = perf::command("search_messages_on_day");
+    if query.len() > 500 {
+        return Err(AppError::Validation(
+            "Search query too long (max 500 characters)".into(),
+        ));
+    }
+    let db = db_from_state(&state, &app_handle)?;
+    db.search_messages_on_day(&query, month, day, &filters.unwrap_or_default(), per_year.unwrap_or(20))
+}
+
+#[tauri::command]
+async fn record_search(
+    query: String,
+    filters: Option<serde_json::Value>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<()> {
+    let _trace = perf::command("record_search");
+    ensure_live_database(&app_handle)?;
+    db_from_state(&state, &app_handle)?.record_search(&query, filters.as_ref())
+}
+
+#[tauri::command]
+async fn save_search(
+    name: String,
+    query: String,
+    filters: Option<serde_json::Value>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<SavedSearch> {
+    let _trace = perf::command("save_search");
+    ensure_live_database(&app_handle)?;
+    db_from_state(&state, &app_handle)?.save_search(&name, &query, filters.as_ref())
+}
+
+#[tauri::command]
+async fn get_saved_searches(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<Vec<SavedSearch>> {
+    let _trace = perf::command("get_saved_searches");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_saved_searches()
+}
+
+#[tauri::command]
+async fn delete_saved_search(id: i64, state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<()> {
+    let _trace = perf::command("delete_saved_search");
+    ensure_live_database(&app_handle)?;
+    db_from_state(&state, &app_handle)?.delete_saved_search(id)
+}
+
+#[tauri::command]
+async fn search_all(
+    query: String,
+    limit: Option<i32>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<SearchAllResults> {
+    let _trace = perf::command("search_all");
+    if query.len() > 500 {
+        return Err(AppError::Validation(
+            "Search query too long (max 500 characters)".into(),
+        ));
+    }
+    let db = db_from_state(&state, &app_handle)?;
+    db.search_all(&query, limit.unwrap_or(20))
+}
+
+#[tauri::command]
+async fn create_tag(
+    name: String,
+    color: Option<String>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<Tag> {
+    let _trace = perf::command("create_tag");
+    db_from_state(&state, &app_handle)?.create_tag(&name, color.as_deref())
+}
+
+#[tauri::command]
+async fn delete_tag(tag_id: i64, state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<()> {
+    let _trace = perf::command("delete_tag");
+    db_from_state(&state, &app_handle)?.delete_tag(tag_id)
+}
+
+#[tauri::command]
+async fn get_tags(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<Vec<Tag>> {
+    let _trace = perf::command("get_tags");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_tags()
+}
+
+#[tauri::command]
+async fn tag_entity(
+    tag_id: i64,
+    entity_type: TagEntityType,
+    entity_id: String,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<()> {
+    let _trace = perf::command("tag_entity");
+    db_from_state(&state, &app_handle)?.tag_entity(tag_id, entity_type, &entity_id)
+}
+
+#[tauri::command]
+async fn untag_entity(
+    tag_id: i64,
+    entity_type: TagEntityType,
+    entity_id: String,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<()> {
+    let _trace = perf::command("untag_entity");
+    db_from_state(&state, &app_handle)?.untag_entity(tag_id, entity_type, &entity_id)
+}
+
+#[tauri::command]
+async fn get_tagged(
+    tag_id: i64,
+    entity_type: TagEntityType,
+    limit: Option<i32>,
+    offset: Option<i32>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<TaggedPage> {
+    let _trace = perf::command("get_tagged");
+    db_from_state(&state, &app_handle)?.get_tagged(tag_id, entity_type, limit.unwrap_or(100), offset.unwrap_or(0))
+}
+
+#[tauri::command]
+async fn get_memories(
+    export_id: Option<String>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<Vec<Memory>> {
+    let _trace = perf::command("get_memories");
+    let db = db_from_state(&state, &app_handle)?;
+    let export_id = export_scope(&db, export_id)?;
+    db.get_memories(export_id.as_deref())
+}
+
+/// Coordinates as degrees with hemisphere letters; no geocoding service is involved.
+fn format_coordinates(latitude: f64, longitude: f64) -> String {
+    format!(
+        "{:.5}° {}, {:.5}° {}",
+        latitude.abs(),
+        if latitude < 0.0 { 'S' } else { 'N' },
+        longitude.abs(),
+        if longitude < 0.0 { 'W' } else { 'E' }
+    )
+}
+
+#[tauri::command]
+async fn get_memory(id: String, state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<MemoryDetail> {
+    let _trace = perf::command("get_memory");
+    let memory = db_from_state(&state, &app_handle)?.get_memory(&id)?;
+    let local_file = memory.media_path.as_deref().filter(|p| p.is_file());
+    Ok(MemoryDetail {
+        file_size: local_file.and_then(|p| fs::metadata(p).ok()).map(|m| m.len()),
+        mime_type: local_file.and_then(StorageManager::detect_mime).map(str::to_string),
+        coordinates: memory
+            .latitude
+            .zip(memory.longitude)
+            .map(|(lat, lon)| format_coordinates(lat, lon)),
+        memory,
+    })
+}
+
+#[tauri::command]
+async fn get_adjacent_memories(
+    id: String,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<AdjacentMemories> {
+    let _trace = perf::command("get_adjacent_memories");
+    db_from_state(&state, &app_handle)?.get_adjacent_memories(&id)
+}
+
+#[tauri::command]
+async fn get_memories_page(
+    limit: Option<i32>,
+    offset: Option<i32>,
+    filter: Option<MemoryFilter>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<MemoryPage> {
+    let _trace = perf::command("get_memories_page");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_memories_page(limit.unwrap_or(100), offset.unwrap_or(0), &filter.unwrap_or_default())
+}
+
+/// Per-month media counts for the gallery's date scrubber, over the stream the gallery shows.
+#[tauri::command]
+async fn get_media_timeline(
+    direction: Option<MediaDirection>,
+    filter: Option<MediaFilter>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<Vec<MediaTimelineMonth>> {
+    let _trace = perf::command("get_media_timeline");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_media_timeline(direction.unwrap_or_default(), filter.as_ref())
+}
+
+/// Stream offset to seek the gallery to so it shows `date` (`YYYY-MM-DD`) first, with the same
+/// direction and filter as its `get_unified_media_stream` calls.
+#[tauri::command]
+async fn get_media_offset_at_date(
+    date: String,
+    direction: Option<MediaDirection>,
+    filter: Option<MediaFilter>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<i32> {
+    let _trace = perf::command("get_media_offset_at_date");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_media_offset_at_date(&date, direction.unwrap_or_default(), filter.as_ref())
+}
+
+#[tauri::command]
+async fn get_unified_media_stream(
+    limit: Option<i32>,
+    offset: Option<i32>,
+    direction: Option<MediaDirection>,
+    filter: Option<MediaFilter>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<PaginatedMedia> {
+    let _trace = perf::command("get_unified_media_stream");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_unified_media_stream(
+        limit.unwrap_or(100),
+        offset.unwrap_or(0),
+        direction.unwrap_or_default(),
+        filter.as_ref(),
+    )
+}
+
+#[tauri::command]
+async fn get_conversation_media(
+    conversation_id: String,
+    limit: Option<i32>,
+    offset: Option<i32>,
+    direction: Option<MediaDirection>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<PaginatedMedia> {
+    let _trace = perf::command("get_conversation_media");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_conversation_media(
+        &conversation_id,
+        limit.unwrap_or(100),
+        offset.unwrap_or(0),
+        direction.unwrap_or_default(),
+    )
+}
+
+/// Size of a media file whose page entry came back without one.
+#[tauri::command]
+async fn get_media_info(path: String, state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<MediaInfo> {
+    let _trace = perf::command("get_media_info");
+    db_from_state(&state, &app_handle)?.get_media_info(Path::new(&path))
+}
+
+/// Follow a media ID through the chat JSON, the export's media folders and linked messages.
+#[tauri::command]
+async fn lookup_media_id(
+    media_id: String,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<MediaIdTrace> {
+    let _trace = perf::command("lookup_media_id");
+    let db = db_from_state(&state, &app_handle)?;
+    let source_dirs = export_source_dirs(&db, &app_handle)?;
+    media_trace::lookup_media_id(&db, &media_id, &source_dirs)
+}
+
+/// Files in the exports' media folders that no message or memory links to, to judge what media
+/// linking missed. The first page scans the folders; later pages page through that scan.
+#[tauri::command]
+async fn find_orphaned_media(
+    limit: Option<i32>,
+    offset: Option<i32>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<OrphanedMediaPage> {
+    let _trace = perf::command("find_orphaned_media");
+    let db = db_from_state(&state, &app_handle)?;
+    let source_dirs = export_source_dirs(&db, &app_handle)?;
+    let tasks = app_handle.state::<TaskRegistry>().inner().clone();
+    let handle = app_handle.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let _task = tasks.register("Find orphaned media", false);
+        let scan = handle.state::<OrphanScan>();
+        media_trace::find_orphaned_media(&db, &source_dirs, limit.unwrap_or(100), offset.unwrap_or(0), &scan)
+    })
+    .await
+    .map_err(|e| AppError::Generic(format!("Thread join error: {}", e)))?
+}
+
+/// Where each export's files are: the recorded extraction directory of a zip (or the one named
+/// by id that imports used before directories were recorded), or the export folder.
+fn export_source_dirs(db: &DatabaseManager, app_handle: &tauri::AppHandle) -> AppResult<Vec<PathBuf>> {
+    let working_dir = extraction_root(db, app_handle).ok().map(|(dir, _)| dir);
+    Ok(db
+        .get_exports()?
+        .into_iter()
+        .filter_map(|export| match export.source_type {
+            ExportSourceType::Zip => export
+                .extraction_path
+                .or_else(|| working_dir.as_ref().map(|dir| dir.join(&export.id))),
+            ExportSourceType::Folder => export.source_paths.into_iter().next(),
+        })
+        .collect())
+}
+
+/// A conversation's messages around `event_id`, e.g. to open a search result in context.
+#[tauri::command]
+async fn get_messages_around(
+    event_id: String,
+    before: Option<i32>,
+    after: Option<i32>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<MessageWindow> {
+    let _trace = perf::command("get_messages_around");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_messages_around(&event_id, before.unwrap_or(50), after.unwrap_or(50))
+}
+
+#[tauri::command]
+async fn get_message_index_at_date(
+    conversation_id: String,
+    date: String,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<i32> {
+    let _trace = perf::command("get_message_index_at_date");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_message_index_at_date(&conversation_id, &date)
+}
+
+#[tauri::command]
+async fn get_activity_dates(
+    conversation_id: String,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<Vec<String>> {
+    let _trace = perf::command("get_activity_dates");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_activity_dates(&conversation_id)
+}
+
+#[tauri::command]
+async fn get_message_density(
+    conversation_id: String,
+    buckets: Option<i32>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<Vec<DensityBucket>> {
+    let _trace = perf::command("get_message_density");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_message_density(&conversation_id, buckets.unwrap_or(100))
+}
+
+#[tauri::command]
+async fn get_activity_timeline(
+    conversation_id: Option<String>,
+    bucket: Option<TimelineBucket>,
+    export_id: Option<String>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<Vec<ActivityBucket>> {
+    let _trace = perf::command("get_activity_timeline");
+    let db = db_from_state(&state, &app_handle)?;
+    let export_id = export_scope(&db, export_id)?;
+    db.get_activity_timeline(
+        conversation_id.as_deref(),
+        bucket.unwrap_or_default(),
+        export_id.as_deref(),
+    )
+}
+
+#[tauri::command]
+async fn get_call_totals(
+    export_id: Option<String>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<Vec<CallTotal>> {
+    let _trace = perf::command("get_call_totals");
+    let db = db_from_state(&state, &app_handle)?;
+    let export_id = export_scope(&db, export_id)?;
+    db.get_call_totals(export_id.as_deref())
+}
+
+#[tauri::command]
+async fn get_location_history(
+    after: Option<chrono::DateTime<chrono::Utc>>,
+    before: Option<chrono::DateTime<chrono::Utc>>,
+    export_id: Option<String>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<Vec<LocationPoint>> {
+    let _trace = perf::command("get_location_history");
+    let db = db_from_state(&state, &app_handle)?;
+    let export_id = export_scope(&db, export_id)?;
+    db.get_location_history(after, before, export_id.as_deref())
+}
+
+#[tauri::command]
+async fn get_activity_heatmap(
+    conversation_id: Option<String>,
+    export_id: Option<String>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<ActivityHeatmap> {
+    let _trace = perf::command("get_activity_heatmap");
+    let db = db_from_state(&state, &app_handle)?;
+    let export_id = export_scope(&db, export_id)?;
+    db.get_activity_heatmap(conversation_id.as_deref(), export_id.as_deref())
+}
+
+/// Locations shared in messages, for one conversation or all of them.
+#[tauri::command]
+async fn get_shared_locations(
+    conversation_id: Option<String>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<Vec<SharedLocation>> {
+    let _trace = perf::command("get_shared_locations");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_shared_locations(conversation_id.as_deref())
+}
+
+/// Most frequent words (`n` = 1) or phrases (`n` = 2 or 3) in a conversation.
+#[tauri::command]
+async fn get_top_phrases(
+    conversation_id: String,
+    n: Option<usize>,
+    top_k: Option<usize>,
+    sender: Option<String>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<TopPhrases> {
+    let _trace = perf::command("get_top_phrases");
+    let db = db_from_state(&state, &app_handle)?;
+    text_analysis::top_phrases(
+        &db,
+        &conversation_id,
+        n.unwrap_or(2),
+        top_k.unwrap_or(50),
+        sender.as_deref(),
+        text_analysis::DEFAULT_TOKEN_BUDGET,
+    )
+}
+
+/// Memories and messages from this calendar day in earlier years.
+#[tauri::command]
+async fn get_on_this_day(
+    month: u32,
+    day: u32,
+    per_year: Option<i32>,
+    leap_fallback: Option<bool>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<Vec<OnThisDayYear>> {
+    let _trace = perf::command("get_on_this_day");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_on_this_day(month, day, per_year.unwrap_or(20), leap_fallback.unwrap_or(true))
+}
+
+/// Reply times, the longest silence and the longest daily streak in a conversation.
+#[tauri::command]
+async fn get_response_stats(
+    conversation_id: String,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<ResponseStats> {
+    let _trace = perf::command("get_response_stats");
+    let db = db_from_state(&state, &app_handle)?;
+    response_stats::response_stats(&db, &conversation_id)
+}
+
+/// Most used words and emoji in a conversation, or across all of them.
+#[tauri::command]
+async fn get_text_analytics(
+    conversation_id: Option<String>,
+    top_n: i32,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<TextAnalytics> {
+    let _trace = perf::command("get_text_analytics");
+    let db = db_from_state(&state, &app_handle)?;
+    let tasks = app_handle.state::<TaskRegistry>().inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let _task = tasks.register("Analyse message text", false);
+        text_analysis::text_analytics(&db, conversation_id.as_deref(), top_n.max(1) as usize)
+    })
+    .await
+    .map_err(|e| AppError::Generic(format!("Thread join error: {}", e)))?
+}
+
+/// A safe, unused file path for exporting a conversation, by default in
+/// Documents/SnapDataExplorer (created if missing).
+#[tauri::command]
+async fn suggest_export_path(
+    conversation_id: String,
+    format: String,
+    base_dir: Option<String>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<String> {
+    let _trace = perf::command("suggest_export_path");
+    let db = db_from_state(&state, &app_handle)?;
+    let dir = match base_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => app_handle
+            .path()
+            .document_dir()
+            .map_err(|e| AppError::Generic(format!("Failed to resolve Documents folder: {}", e)))?
+            .join("SnapDataExplorer"),
+    };
+    fs::create_dir_all(&dir)?;
+
+    let name = db
+        .get_conversation_name(&conversation_id)?
+        .unwrap_or_else(|| conversation_id.clone());
+    let stem = export::suggested_stem(&name, db.get_conversation_time_range(&conversation_id)?);
+    let path = export::collision_free_path(&dir, &stem, export::format_extension(&format));
+    Ok(path.to_string_lossy().into_owned())
+}
+
+#[tauri::command]
+async fn export_conversation(
+    conversation_id: String,
+    format: String,
+    output_path: String,
+    overwrite: Option<bool>,
+    options: Option<ExportOptions>,
+    after: Option<chrono::DateTime<chrono::Utc>>,
+    before: Option<chrono::DateTime<chrono::Utc>>,
+    senders: Option<Vec<String>>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<()> {
+    let _trace = perf::command("export_conversation");
+    // Validate output path — must be under user-accessible directories
+    let output = PathBuf::from(&output_path);
+    if let Some(parent) = output.parent() {
+        if !parent.exists() {
+            return Err(AppError::Validation(format!(
+                "Output directory does not exist: {}",
+                parent.display()
+            )));
+        }
+    }
+    // Reject paths that try to traverse outside via ..
+    let canonical_parent = output.parent().and_then(|p| std::fs::canonicalize(p).ok());
+    if canonical_parent.is_none() {
+        return Err(AppError::Validation("Invalid output path".to_string()));
+    }
+
+    if output.exists() && !overwrite.unwrap_or(false) {
+        return Err(AppError::FileExists(output_path));
+    }
+
+    let db = db_from_state(&state, &app_handle)?;
+    let options = options.unwrap_or_default();
+    let options = ExportOptions {
+        after: after.or(options.after),
+        before: before.or(options.before),
+        senders: senders.unwrap_or(options.senders),
+        link_base: canonical_parent,
+        ..options
+    };
+    // Messages are written as they are read, so memory stays flat however long the chat is
+    tauri::async_runtime::spawn_blocking(move || {
+        use std::io::Write;
+        let mut writer = std::io::BufWriter::new(fs::File::create(&output_path)?);
+        if format == "bundle" {
+            // Copying the media can take a while; report each file as `export-progress`
+            let task = app_handle
+                .state::<TaskRegistry>()
+                .register("Export conversation bundle", false);
+            export::write_bundle(
+                &db,
+                &conversation_id,
+                &options,
+                &mut writer,
+                |progress: &ExportProgress| {
+                    task.heartbeat(
+                        Some(progress.processed as f32 / progress.total.max(1) as f32),
+                        progress.current.as_deref(),
+                    );
+                    let _ = app_handle.emit("export-progress", progress);
+                },
+            )?;
+        } else {
+            export::write_conversation(&db, &conversation_id, &format, &options, &mut writer)?;
+        }
+        writer.flush()?;
+        log::info!("Exported conversation to {}", output_path);
+        Ok::<_, AppError>(())
+    })
+    .await
+    .map_err(|e| AppError::Generic(format!("Thread join error: {}", e)))?
+}
+
+/// Write every conversation to its own file in `output_dir`, emitting `export-progress` after
+/// each. Conversations that fail are skipped and listed in the report.
+#[tauri::command]
+async fn export_all_conversations(
+    format: String,
+    output_dir: String,
+    options: Option<ExportOptions>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<BulkExportReport> {
+    let _trace = perf::command("export_all_conversations");
+    let output = PathBuf::from(&output_dir);
+    if let Some(parent) = output.parent().filter(|p| !p.exists()) {
+        return Err(AppError::Validation(format!(
+            "Output directory does not exist: {}",
+            parent.display()
+        )));
+    }
+
+    let db = db_from_state(&state, &app_handle)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let task = app_handle
+            .state::<TaskRegistry>()
+            .register("Export all conversations", false);
+        export::export_all_conversations(
+            &db,
+            &format,
+            &options.unwrap_or_default(),
+            &output,
+            |progress: &ExportProgress| {
+                task.heartbeat(
+                    Some(progress.processed as f32 / progress.total.max(1) as f32),
+                    progress.current.as_deref(),
+                );
+                let _ = app_handle.emit("export-progress", progress);
+            },
+        )
+    })
+    .await
+    .map_err(|e| AppError::Generic(format!("Thread join error: {}", e)))?
+}
+
+/// Write the memories matching `filter` with their locations as `"geojson"` or `"kml"`, for
+/// mapping tools. Memories without coordinates are left out and counted in the result.
+#[tauri::command]
+async fn export_memories(
+    format: String,
+    output_path: String,
+    filter: Option<MemoryFilter>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<MemoryMapExport> {
+    let _trace = perf::command("export_memories");
+    if memory_map::map_extension(&format).is_none() {
+        return Err(AppError::Validation(format!(
+            "Unknown memory export format: {}",
+            format
+        )));
+    }
+    let output = PathBuf::from(&output_path);
+    if let Some(parent) = output.parent().filter(|p| !p.exists()) {
+        return Err(AppError::Validation(format!(
+            "Output directory does not exist: {}",
+            parent.display()
+        )));
+    }
+
+    let db = db_from_state(&state, &app_handle)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        use std::io::Write;
+        let mut writer = std::io::BufWriter::new(fs::File::create(&output)?);
+        let report = memory_map::write_memory_map(&db, &format, &filter.unwrap_or_default(), &mut writer)?;
+        writer.flush()?;
+        Ok::<_, AppError>(report)
+    })
+    .await
+    .map_err(|e| AppError::Generic(format!("Thread join error: {}", e)))?
+}
+
+/// Check that every linked media file is still on disk, emitting `verify-media-progress`, and
+/// return the validation report with the files found missing.
+#[tauri::command]
+async fn verify_media(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<ValidationReport> {
+    let _trace = perf::command("verify_media");
+    let db = db_from_state(&state, &app_handle)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let task = app_handle.state::<TaskRegistry>().register("Verify media files", false);
+        db.verify_media_files(
+            |path| path.exists(),
+            |progress: &MediaVerifyProgress| {
+                task.heartbeat(Some(progress.checked as f32 / progress.total.max(1) as f32), None);
+                let _ = app_handle.emit("verify-media-progress", progress);
+            },
+        )
+    })
+    .await
+    .map_err(|e| AppError::Generic(format!("Thread join error: {}", e)))?
+}
+
+#[tauri::command]
+async fn get_validation_report(
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<Option<ValidationReport>> {
+    let _trace = perf::command("get_validation_report");
+    let db = db_from_state(&state, &app_handle)?;
+    Ok(Some(db.get_validation_report()?))
+}
+
+#[tauri::command]
+async fn reset_data(app_handle: tauri::AppHandle) -> AppResult<()> {
+    let _trace = perf::command("reset_data");
+    ensure_live_database(&app_handle)?;
+    if DB_MAINTENANCE
+        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        return Err(AppError::Generic("A data operation is already in progress.".into()));
+    }
+
+    // Clear the cached pool before deleting files
+    clear_db_cache(&app_handle);
+
+    let result = (|| -> AppResult<()> {
+        let path = db_path(&app_handle)?;
+        if path.exists() {
+            fs::remove_file(&path)?;
+            log::info!("Database deleted: {:?}", path);
+        }
+        // Also remove WAL and SHM files if they exist
+        let wal = path.with_extension("db-wal");
+        let shm = path.with_extension("db-shm");
+        if wal.exists() {
+            let _ = fs::remove_file(&wal);
+        }
+        if shm.exists() {
+            let _ = fs::remove_file(&shm);
+        }
+        // Start over with an empty database rather than none at all
+        live_database(&app_handle.state::<DbState>(), &app_handle)?;
+        Ok(())
+    })();
+
+    DB_MAINTENANCE.store(false, Ordering::SeqCst);
+    result
+}
+
+/// Bulk downloads still marked Running were cut short when the app last quit. Mark them
+/// Interrupted and offer to resume them, and put memories left Downloading back to Pending.
+fn report_interrupted_downloads(db: &DatabaseManager, app_handle: &tauri::AppHandle) {
+    match db.reset_stalled_downloads() {
+        Ok(0) => {}
+        Ok(n) => log::info!("Reset {} memories left downloading to pending", n),
+        Err(e) => log::warn!("Could not reset stalled downloads: {}", e),
+    }
+    match db.interrupt_running_download_jobs() {
+        Ok(jobs) => {
+            for job in jobs {
+                log::info!(
+                    "Download job {} was interrupted ({} of {} done)",
+                    job.id,
+                    job.completed,
+                    job.total
+                );
+                let _ = app_handle.emit("download-job-interrupted", job);
+            }
+        }
+        Err(e) => log::warn!("Could not check for interrupted downloads: {}", e),
+    }
+}
+
+/// What the app should show: onboarding, import progress, or the imported data.
+#[tauri::command]
+async fn get_app_state(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<AppState> {
+    let _trace = perf::command("get_app_state");
+    let import = app_handle.state::<ImportTracker>().current();
+    if import.is_none() && DB_MAINTENANCE.load(Ordering::SeqCst) {
+        // A reset or reimport is replacing the database file
+        return Ok(AppState::NoDatabase);
+    }
+    let db = match active_snapshot(&app_handle)? {
+        Some(snapshot) => Some(snapshot),
+        None => live_database(&state, &app_handle).map(Some).or_else(|e| {
+            log::error!("Could not open the database: {}", e);
+            if db_path(&app_handle)?.exists() {
+                Err(e)
+            } else {
+                Ok(None)
+            }
+        })?,
+    };
+    resolve_app_state(db.as_deref(), import)
+}
+
+/// Which source files of an export changed, appeared or disappeared since it was imported.
+#[tauri::command]
+async fn check_export_changes(
+    export_id: String,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<ExportChanges> {
+    let _trace = perf::command("check_export_changes");
+    let db = db_from_state(&state, &app_handle)?;
+    let export = db
+        .get_exports()?
+        .into_iter()
+        .find(|e| e.id == export_id)
+        .ok_or_else(|| AppError::NotFound(format!("export {}", export_id)))?;
+    let recorded = db.get_export_artifacts(&export_id)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let current = artifacts::scan_artifacts(&export)?;
+        Ok(artifacts::diff_artifacts(&export.id, &recorded, &current))
+    })
+    .await
+    .map_err(|e| AppError::Generic(format!("Thread join error: {}", e)))?
+}
+
+#[tauri::command]
+async fn reimport_data(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<()> {
+    let _trace = perf::command("reimport_data");
+    ensure_live_database(&app_handle)?;
+    // Read export info BEFORE setting maintenance flag
+    let db = db_from_state(&state, &app_handle)?;
+    let export = match db.get_exports()?.into_iter().next() {
+        Some(e) => e,
+        None => return Err(AppError::Generic("No existing import to reimport from.".into())),
+    };
+
+    // Verify the source paths still exist before wiping
+    for path in &export.source_paths {
+        if !path.exists() {
+            return Err(AppError::Generic(format!(
+                "Original export component no longer exists: {}. Cannot reimport.",
+                path.display()
+            )));
+        }
+    }
+
+    let recorded = db.get_export_artifacts(&export.id)?;
+    match artifacts::scan_artifacts(&export) {
+        Ok(current) => {
+            let changes = artifacts::diff_artifacts(&export.id, &recorded, &current);
+            if changes.recorded && !changes.has_changes() {
+                log::info!("reimport_data: nothing has changed since the last import");
+            } else {
+                log::info!(
+                    "reimport_data: {} changed, {} added, {} removed since the last import",
+                    changes.changed.len(),
+                    changes.added.len(),
+                    changes.removed.len()
+                );
+            }
+        }
+        Err(e) => log::warn!("reimport_data: could not compare export sources: {}", e),
+    }
+    // The pool must be closed before the wipe
+    drop(db);
+
+    if DB_MAINTENANCE
+        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        return Err(AppError::Generic("A reimport is already in progress.".into()));
+    }
+
+    let result = reimport_data_inner(&app_handle, export).await;
+    DB_MAINTENANCE.store(false, Ordering::SeqCst);
+    result
+}
+
+async fn reimport_data_inner(app_handle: &tauri::AppHandle, mut export: ExportSet) -> AppResult<()> {
+    log::info!(
+        "reimport_data: reimporting (type: {:?}, {} parts)",
+        export.source_type,
+        export.source_paths.len()
+    );
+
+    // Check the parts before wiping anything so a damaged zip doesn't leave us with no data
+    if export.source_type == ExportSourceType::Zip {
+        ExportDetector::prepare_zip_parts(&mut export, false)?;
+    }
+
+    // Tags reference row IDs that the reimport regenerates; carry them over by anchor
+    let cached_db = app_handle.state::<DbState>().lock().ok().and_then(|g| g.clone());
+    let saved_taggings = match &cached_db {
+        Some(db) => db.export_taggings().unwrap_or_else(|e| {
+            log::warn!("reimport_data: could not snapshot tags: {}", e);
+            Vec::new()
+        }),
+        None => Vec::new(),
+    };
+    // Redaction rules must survive the wipe, or the reimport would bring redacted messages back
+    let saved_redactions = match &cached_db {
+        Some(db) => db.get_redactions()?,
+        None => Vec::new(),
+    };
+    let saved_searches = match &cached_db {
+        Some(db) => db.get_saved_searches().unwrap_or_else(|e| {
+            log::warn!("reimport_data: could not snapshot saved searches: {}", e);
+            Vec::new()
+        }),
+        None => Vec::new(),
+    };
+    drop(cached_db);
+
+    // Clear cached pool before deleting files
+    clear_db_cache(app_handle);
+
+    // Wipe the DB
+    let path = db_path(app_handle)?;
+    if path.exists() {
+        fs::remove_file(&path)?;
+    }
+    let wal = path.with_extension("db-wal");
+    let shm = path.with_extension("db-shm");
+    if wal.exists() {
+        let _ = fs::remove_file(&wal);
+    }
+    if shm.exists() {
+        let _ = fs::remove_file(&shm);
+    }
+
+    if !saved_redactions.is_empty() {
+        let restored = DatabaseManager::new(&path)?.restore_redactions(&saved_redactions)?;
+        log::info!("reimport_data: carried over {} redaction rule(s)", restored);
+    }
+    if !saved_searches.is_empty() {
+        let restored = DatabaseManager::new(&path)?.restore_saved_searches(&saved_searches)?;
+        log::info!("reimport_data: carried over {} saved search(es)", restored);
+    }
+
+    // Re-process the same export, limited to the phases the original import covered
+    let options = export.import_phases;
+    process_export(export, None, None, options, app_handle.clone()).await?;
+
+    if !saved_taggings.is_empty() {
+        let new_db = app_handle.state::<DbState>().lock().ok().and_then(|g| g.clone());
+        if let Some(db) = new_db {
+            let (restored, unresolved) = db.restore_taggings(&saved_taggings)?;
+            log::info!(
+                "reimport_data: restored {} tagging(s), {} could not be re-resolved",
+                restored,
+                unresolved
+            );
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn open_database_readonly(path: String, app_handle: tauri::AppHandle) -> AppResult<ActiveDatabase> {
+    let _trace = perf::command("open_database_readonly");
+    let db_file = PathBuf::from(&path);
+    if db_path(&app_handle).ok().as_deref() == Some(db_file.as_path()) {
+        return Err(AppError::Validation(
+            "That is the live database; open a copy instead.".into(),
+        ));
+    }
+    let opened = tauri::async_runtime::spawn_blocking({
+        let db_file = db_file.clone();
+        move || DatabaseManager::open_readonly(&db_file)
+    })
+    .await
+    .map_err(|e| AppError::Generic(format!("Thread join error: {}", e)))??;
+
+    let snapshots = app_handle.state::<SnapshotState>();
+    let mut guard = snapshots
+        .lock()
+        .map_err(|e| AppError::Generic(format!("Snapshot lock poisoned: {}", e)))?;
+    *guard = SnapshotDb {
+        db: Some(Arc::new(opened)),
+        path: Some(db_file),
+        active: true,
+    };
+    log::info!("Opened read-only database snapshot");
+    Ok(ActiveDatabase {
+        slot: DatabaseSlot::Snapshot,
+        snapshot_path: guard.path.clone(),
+        read_only: true,
+    })
+}
+
+#[tauri::command]
+async fn set_active_database(slot: DatabaseSlot, snapshots: State<'_, SnapshotState>) -> AppResult<ActiveDatabase> {
+    let _trace = perf::command("set_active_database");
+    let mut guard = snapshots
+        .lock()
+        .map_err(|e| AppError::Generic(format!("Snapshot lock poisoned: {}", e)))?;
+    if slot == DatabaseSlot::Snapshot && guard.db.is_none() {
+        return Err(AppError::Validation("No read-only database has been opened".into()));
+    }
+    guard.active = slot == DatabaseSlot::Snapshot;
+    Ok(ActiveDatabase {
+        slot,
+        snapshot_path: guard.path.clone(),
+        read_only: guard.active,
+    })
+}
+
+#[tauri::command]
+async fn get_active_database(snapshots: State<'_, SnapshotState>) -> AppResult<ActiveDatabase> {
+    let _trace = perf::command("get_active_database");
+    let guard = snapshots
+        .lock()
+        .map_err(|e| AppError::Generic(format!("Snapshot lock poisoned: {}", e)))?;
+    Ok(ActiveDatabase {
+        slot: if guard.active {
+            DatabaseSlot::Snapshot
+        } else {
+            DatabaseSlot::Live
+        },
+        snapshot_path: guard.path.clone(),
+        read_only: guard.active,
+    })
+}
+
+fn log_path(app_handle: &tauri::AppHandle) -> PathBuf {
+    // Prefer app data dir for log path, fall back to cwd
+    match app_handle.path().app_data_dir() {
+        Ok(dir) => dir.join("snap_explorer.log"),
+        Err(_) => std::env::current_dir().unwrap_or_default().join("snap_explorer.log"),
+    }
+}
+
+#[tauri::command]
+async fn get_log_path(app_handle: tauri::AppHandle) -> AppResult<String> {
+    let _trace = perf::command("get_log_path");
+    Ok(log_path(&app_handle).to_string_lossy().into_owned())
+}
+
+/// Version, platform, database details and the performance trace summary, for issue reports.
+#[tauri::command]
+async fn get_diagnostics(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<DiagnosticsBundle> {
+    let _trace = perf::command("get_diagnostics");
+    let database = match db_from_state(&state, &app_handle).and_then(|db| db.get_database_info()) {
+        Ok(info) => Some(info),
+        Err(e) => {
+            log::warn!("Diagnostics without database details: {}", e);
+            None
+        }
+    };
+    Ok(DiagnosticsBundle {
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        log_path: Some(log_path(&app_handle).to_string_lossy().into_owned()),
+        database,
+        performance: perf::summary(),
+    })
+}
+
+/// Where zip exports are extracted: the configured directory, or `exports` in app data.
+fn extraction_root(db: &DatabaseManager, app_handle: &tauri::AppHandle) -> AppResult<(PathBuf, PathSource)> {
+    match pipeline::configured_extraction_root(db)? {
+        Some(resolved) => Ok(resolved),
+        None => {
+            let app_data = app_handle
+                .path()
+                .app_data_dir()
+                .map_err(|e| AppError::Generic(format!("Failed to resolve app data directory: {}", e)))?;
+            Ok((app_data.join("exports"), PathSource::Default))
+        }
+    }
+}
+
+/// Directories the app itself writes into: extracted exports and memory downloads.
+fn managed_storage_roots(db: &DatabaseManager, app_handle: &tauri::AppHandle) -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+    if let Ok((extraction, _)) = extraction_root(db, app_handle) {
+        roots.push(extraction);
+    }
+    if let Ok(Some(downloads)) = db.downloads_root() {
+        roots.push(downloads);
+    }
+    roots
+}
+
+/// Securely delete the media of redacted messages, but only files under managed storage;
+/// a user's own export folder is never modified.
+fn scrub_redacted_media(media: Vec<PathBuf>, roots: &[PathBuf], summary: &mut RedactionSummary) {
+    let unique: std::collections::BTreeSet<PathBuf> = media.into_iter().collect();
+    for path in unique {
+        if !path.is_file() {
+            continue;
+        }
+        if !StorageManager::is_within(&path, roots) {
+            summary.media_skipped += 1;
+            continue;
+        }
+        match StorageManager::overwrite_and_remove(&path) {
+            Ok(()) => summary.media_overwritten += 1,
+            Err(e) => {
+                log::warn!("Could not overwrite redacted media {:?}: {}", path.file_name(), e);
+                summary.media_skipped += 1;
+            }
+        }
+    }
+}
+
+/// Permanently remove messages from the index. They stay excluded on future imports.
+#[tauri::command]
+async fn redact_events(
+    event_ids: Vec<String>,
+    overwrite_media: Option<bool>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<RedactionSummary> {
+    let _trace = perf::command("redact_events");
+    let db = db_from_state(&state, &app_handle)?;
+    let (mut summary, media) = db.redact_events(&event_ids)?;
+    if overwrite_media.unwrap_or(false) {
+        scrub_redacted_media(media, &managed_storage_roots(&db, &app_handle), &mut summary);
+    }
+    log::info!("redact_events: {:?}", summary);
+    Ok(summary)
+}
+
+/// Permanently remove every message from one sender. Future imports skip them too.
+#[tauri::command]
+async fn redact_sender(
+    username: String,
+    overwrite_media: Option<bool>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<RedactionSummary> {
+    let _trace = perf::command("redact_sender");
+    let db = db_from_state(&state, &app_handle)?;
+    let (mut summary, media) = db.redact_sender(&username)?;
+    if overwrite_media.unwrap_or(false) {
+        scrub_redacted_media(media, &managed_storage_roots(&db, &app_handle), &mut summary);
+    }
+    log::info!("redact_sender: {:?}", summary);
+    Ok(summary)
+}
+
+/// Write tags, redaction rules, saved searches and settings to a portable JSON file.
+#[tauri::command]
+async fn export_user_data(
+    output_path: String,
+    overwrite: Option<bool>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<()> {
+    let _trace = perf::command("export_user_data");
+    let output = PathBuf::from(&output_path);
+    if let Some(parent) = output.parent().filter(|p| !p.is_dir()) {
+        return Err(AppError::Validation(format!(
+            "Output directory does not exist: {}",
+            parent.display()
+        )));
+    }
+    if output.exists() && !overwrite.unwrap_or(false) {
+        return Err(AppError::FileExists(output_path));
+    }
+    let data = db_from_state(&state, &app_handle)?.export_user_data()?;
+    fs::write(&output, serde_json::to_vec_pretty(&data)?)?;
+    log::info!(
+        "Exported user data to {}: {} tagging(s), {} redaction(s), {} search(es), {} setting(s)",
+        output_path,
+        data.tags.len(),
+        data.redactions.len(),
+        data.saved_searches.len(),
+        data.settings.len()
+    );
+    Ok(())
+}
+
+/// Merge a file written by `export_user_data` into the current database.
+#[tauri::command]
+async fn import_user_data(
+    path: String,
+    policy: Option<UserDataConflictPolicy>,
+    dry_run: Option<bool>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<UserDataImportSummary> {
+    let _trace = perf::command("import_user_data");
+    let data: UserData = serde_json::from_str(&fs::read_to_string(&path)?)
+        .map_err(|e| AppError::Validation(format!("Not a user data file: {}", e)))?;
+    let db = db_from_state(&state, &app_handle)?;
+    let summary = db.import_user_data(&data, policy.unwrap_or_default(), dry_run.unwrap_or(false))?;
+    log::info!("import_user_data from {}: {:?}", path, summary);
+    Ok(summary)
+}
+
+#[tauri::command]
+async fn get_redactions(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<Vec<Redaction>> {
+    let _trace = perf::command("get_redactions");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_redactions()
+}
+
+/// Validate and store a path setting; `None` clears it so the fallback applies again.
+fn update_path_setting(db: &DatabaseManager, key: &str, path: Option<String>) -> AppResult<()> {
+    let Some(path) = path.filter(|p| !p.trim().is_empty()) else {
+        db.delete_setting(key)?;
+        log::info!("{} cleared", key);
+        return Ok(());
+    };
+    let path_buf = PathBuf::from(&path);
+    StorageManager::validate_path(path_buf.clone()).map_err(|e| AppError::Validation(e.to_string()))?;
+    StorageManager::probe_writable(&path_buf).map_err(|e| AppError::Validation(e.to_string()))?;
+    db.set_setting(key, &path)?;
+    log::info!("{} updated", key);
+    log::debug!("{} set to: {}", key, path);
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_extraction_path(
+    path: Option<String>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<()> {
+    let _trace = perf::command("set_extraction_path");
+    ensure_live_database(&app_handle)?;
+    let db = db_from_state(&state, &app_handle)?;
+    update_path_setting(&db, "extraction_path", path)
+}
+
+#[tauri::command]
+async fn set_downloads_path(
+    path: Option<String>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<()> {
+    let _trace = perf::command("set_downloads_path");
+    ensure_live_database(&app_handle)?;
+    let db = db_from_state(&state, &app_handle)?;
+    update_path_setting(&db, "downloads_path", path)
+}
+
+/// Move the downloaded memories from the current downloads folder to `path`, then make it the
+/// downloads folder. Each move is journaled, so a crash part way through is repaired on restart.
+#[tauri::command]
+async fn relocate_downloads(path: String, state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<usize> {
+    let _trace = perf::command("relocate_downloads");
+    ensure_live_database(&app_handle)?;
+    let db = db_from_state(&state, &app_handle)?;
+    let from = db
+        .downloads_root()?
+        .ok_or_else(|| AppError::Validation("No downloads folder is set".into()))?;
+    let tasks = app_handle.state::<TaskRegistry>().inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let _task = tasks.register("Move downloads", false);
+        update_path_setting(&db, "downloads_path", Some(path.clone()))?;
+        fs_journal::relocate_downloads(&db, &from, Path::new(&path))
+    })
+    .await
+    .map_err(|e| AppError::Generic(format!("Thread join error: {}", e)))?
+}
+
+/// Legacy alias for `set_downloads_path`; `storage_path` itself is no longer written.
+#[tauri::command]
+async fn set_storage_path(path: String, state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<()> {
+    let _trace = perf::command("set_storage_path");
+    ensure_live_database(&app_handle)?;
+    let db = db_from_state(&state, &app_handle)?;
+    update_path_setting(&db, "downloads_path", Some(path))
+}
+
+/// The effective downloads path, kept for callers of the pre-split API.
+#[tauri::command]
+async fn get_storage_path(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<Option<String>> {
+    let _trace = perf::command("get_storage_path");
+    let db = db_from_state(&state, &app_handle)?;
+    Ok(db.downloads_root()?.map(|p| p.to_string_lossy().into_owned()))
+}
+
+fn resolved_path(resolved: Option<(PathBuf, PathSource)>) -> ResolvedPath {
+    let Some((path, source)) = resolved else {
+        return ResolvedPath {
+            path: None,
+            source: PathSource::Unset,
+            available_bytes: None,
+            total_bytes: None,
+        };
+    };
+    // Free space is reported for the nearest existing ancestor so not-yet-created folders still show it
+    let space = path
+        .ancestors()
+        .find(|p| p.exists())
+        .and_then(|p| StorageManager::get_disk_space(p.to_path_buf()).ok());
+    ResolvedPath {
+        path: Some(path.to_string_lossy().into_owned()),
+        source,
+        available_bytes: space.as_ref().map(|s| s.available_bytes),
+        total_bytes: space.as_ref().map(|s| s.total_bytes),
+    }
+}
+
+/// Effective extraction and download locations, where each comes from, and free space on each.
+#[tauri::command]
+async fn get_paths_overview(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<PathsOverview> {
+    let _trace = perf::command("get_paths_overview");
+    let db = db_from_state(&state, &app_handle)?;
+    Ok(PathsOverview {
+        extraction: resolved_path(Some(extraction_root(&db, &app_handle)?)),
+        downloads: resolved_path(db.get_path_setting("downloads_path")?),
+    })
+}
+
+#[tauri::command]
+async fn check_disk_space(
+    path: Option<String>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<DiskSpaceInfo> {
+    let _trace = perf::command("check_disk_space");
+    let path_to_check = if let Some(p) = path {
+        PathBuf::from(p)
+    } else {
+        let db = db_from_state(&state, &app_handle)?;
+        match db.downloads_root()? {
+            Some(p) => p,
+            None => return Err(AppError::Generic("No downloads path set".into())),
+        }
+    };
+
+    StorageManager::get_disk_space(path_to_check).map_err(|e| AppError::Generic(e.to_string()))
+}
+
+#[tauri::command]
+async fn get_network_settings(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<NetworkSettings> {
+    let _trace = perf::command("get_network_settings");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_network_settings()
+}
+
+#[tauri::command]
+async fn get_timezone_offset(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<i32> {
+    let _trace = perf::command("get_timezone_offset");
+    let db = db_from_state(&state, &app_handle)?;
+    db.get_timezone_offset()
+}
+
+/// Save the offset from UTC, in minutes, that local-time views use.
+#[tauri::command]
+async fn set_timezone_offset(minutes: i32, state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<()> {
+    let _trace = perf::command("set_timezone_offset");
+    ensure_live_database(&app_handle)?;
+    let db = db_from_state(&state, &app_handle)?;
+    db.set_timezone_offset(minutes)
+}
+
+/// Validate and save the proxy, timeout and user agent used for memory downloads.
+#[tauri::command]
+async fn set_network_settings(
+    settings: NetworkSettings,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<()> {
+    let _trace = perf::command("set_network_settings");
+    ensure_live_database(&app_handle)?;
+    let db = db_from_state(&state, &app_handle)?;
+    downloader::build_client(&settings)?;
+    db.set_network_settings(&settings)
+}
+
+/// Fetch `url` with the given settings (or the saved ones) and report status and latency.
+#[tauri::command]
+async fn test_connection(
+    url: String,
+    settings: Option<NetworkSettings>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<ConnectionTestResult> {
+    let _trace = perf::command("test_connection");
+    let settings = match settings {
+        Some(s) => s,
+        None => db_from_state(&state, &app_handle)?.get_network_settings()?,
+    };
+    let client = downloader::build_client(&settings)?;
+    downloader::test_connection(&client, &url).await
+}
+
+#[tauri::command]
+async fn download_all_memories(
+    filter: Option<MemoryFilter>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<DownloadJob> {
+    let _trace = perf::command("download_all_memories");
+    ensure_live_database(&app_handle)?;
+    let db = db_from_state(&state, &app_handle)?;
+    let downloader = MemoryDownloader::new(app_handle, db)?;
+    downloader.download_all_pending(filter.unwrap_or_default()).await
+}
+
+/// Continue an interrupted bulk download with the memories it hasn't fetched yet.
+#[tauri::command]
+async fn resume_download_job(
+    job_id: i64,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<DownloadJob> {
+    let _trace = perf::command("resume_download_job");
+    ensure_live_database(&app_handle)?;
+    let db = db_from_state(&state, &app_handle)?;
+    let downloader = MemoryDownloader::new(app_handle, db)?;
+    downloader.resume_job(job_id).await
+}
+
+/// Size of the database and what the background maintenance last did.
+#[tauri::command]
+async fn get_database_info(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<DatabaseInfo> {
+    let _trace = perf::command("get_database_info");
+    db_from_state(&state, &app_handle)?.get_database_info()
+}
+
+/// Hold downloads after their current chunk until `resume_downloads`.
+#[tauri::command]
+async fn pause_downloads(control: State<'_, DownloadControl>, app_handle: tauri::AppHandle) -> AppResult<()> {
+    let _trace = perf::command("pause_downloads");
+    log::info!("Pausing memory downloads");
+    control.pause();
+    downloader::emit_queue_state(&app_handle);
+    Ok(())
 }
 
 #[tauri::command]
-async fn get_messages(
-    conversation_id: String,
-    state: State<'_, DbState>,
-    app_handle: tauri::AppHandle,
-) -> AppResult<Vec<Event>> {
-    match db_from_state(&state, &app_handle)? {
-        Some(db) => db.get_messages(&conversation_id),
-        None => Ok(Vec::new()),
-    }
+async fn resume_downloads(control: State<'_, DownloadControl>, app_handle: tauri::AppHandle) -> AppResult<()> {
+    let _trace = perf::command("resume_downloads");
+    log::info!("Resuming memory downloads");
+    control.resume();
+    downloader::emit_queue_state(&app_handle);
+    Ok(())
 }
 
+/// Stop the file being downloaded, putting its memory back to Pending, and the rest of a
+/// bulk download with it.
 #[tauri::command]
-async fn get_messages_page(
-    conversation_id: String,
-    offset: i32,
-    limit: i32,
-    state: State<'_, DbState>,
-    app_handle: tauri::AppHandle,
-) -> AppResult<MessagePage> {
-    match db_from_state(&state, &app_handle)? {
-        Some(db) => db.get_messages_page(&conversation_id, offset, limit),
-        None => Ok(MessagePage {
-            messages: Vec::new(),
-            total_count: 0,
-            has_more: false,
-        }),
+async fn cancel_downloads(control: State<'_, DownloadControl>, app_handle: tauri::AppHandle) -> AppResult<()> {
+    let _trace = perf::command("cancel_downloads");
+    if control.state().running {
+        log::info!("Cancelling memory downloads");
+        control.cancel();
     }
+    downloader::emit_queue_state(&app_handle);
+    Ok(())
 }
 
+/// Whether downloads are running, paused or being cancelled.
 #[tauri::command]
-async fn get_export_stats(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<Option<ExportStats>> {
-    match db_from_state(&state, &app_handle)? {
-        Some(db) => Ok(Some(db.get_export_stats()?)),
-        None => Ok(None),
-    }
+async fn get_download_queue_state(control: State<'_, DownloadControl>) -> AppResult<DownloadQueueState> {
+    let _trace = perf::command("get_download_queue_state");
+    Ok(control.state())
 }
 
+/// Bulk download history, newest first.
 #[tauri::command]
-async fn get_exports(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<Vec<ExportSet>> {
-    match db_from_state(&state, &app_handle)? {
-        Some(db) => db.get_exports(),
-        None => Ok(Vec::new()),
-    }
+async fn get_download_jobs(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<Vec<DownloadJob>> {
+    let _trace = perf::command("get_download_jobs");
+    db_from_state(&state, &app_handle)?.get_download_jobs()
 }
 
+/// Check the files of Downloaded memories, putting missing or undersized ones back to Pending.
+/// Emits `verify-downloads-progress`.
 #[tauri::command]
-async fn search_messages(
-    query: String,
-    limit: Option<i32>,
+async fn verify_downloaded_memories(
     state: State<'_, DbState>,
     app_handle: tauri::AppHandle,
-) -> AppResult<Vec<SearchResult>> {
-    if query.len() > 500 {
-        return Err(AppError::Validation(
-            "Search query too long (max 500 characters)".into(),
-        ));
-    }
-    match db_from_state(&state, &app_handle)? {
-        Some(db) => db.search_messages(&query, limit.unwrap_or(50)),
-        None => Ok(Vec::new()),
-    }
+) -> AppResult<DownloadVerification> {
+    let _trace = perf::command("verify_downloaded_memories");
+    ensure_live_database(&app_handle)?;
+    let db = db_from_state(&state, &app_handle)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let task = app_handle
+            .state::<TaskRegistry>()
+            .register("Verify downloaded memories", false);
+        db.verify_downloaded_memories(downloader::MIN_MEDIA_BYTES, |progress: &DownloadVerification| {
+            task.heartbeat(Some(progress.checked as f32 / progress.total.max(1) as f32), None);
+            let _ = app_handle.emit("verify-downloads-progress", progress);
+        })
+    })
+    .await
+    .map_err(|e| AppError::Generic(format!("Thread join error: {}", e)))?
 }
 
+/// Failed memory downloads grouped by reason, e.g. how many links have expired.
 #[tauri::command]
-async fn get_memories(
-    export_id: Option<String>,
+async fn get_download_failure_summary(
     state: State<'_, DbState>,
     app_handle: tauri::AppHandle,
-) -> AppResult<Vec<Memory>> {
-    match db_from_state(&state, &app_handle)? {
-        Some(db) => db.get_memories(export_id.as_deref()),
-        None => Ok(Vec::new()),
-    }
+) -> AppResult<Vec<DownloadFailureGroup>> {
+    let _trace = perf::command("get_download_failure_summary");
+    db_from_state(&state, &app_handle)?.get_download_failure_summary()
 }
 
 #[tauri::command]
-async fn get_unified_media_stream(
-    limit: Option<i32>,
-    offset: Option<i32>,
-    state: State<'_, DbState>,
-    app_handle: tauri::AppHandle,
-) -> AppResult<PaginatedMedia> {
-    match db_from_state(&state, &app_handle)? {
-        Some(db) => db.get_unified_media_stream(limit.unwrap_or(100), offset.unwrap_or(0)),
-        None => Ok(PaginatedMedia {
-            items: Vec::new(),
-            total_count: 0,
-            has_more: false,
-        }),
-    }
+async fn download_memory(memory: Memory, state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<()> {
+    let _trace = perf::command("download_memory");
+    ensure_live_database(&app_handle)?;
+    let db = db_from_state(&state, &app_handle)?;
+    let storage_root = match db.downloads_root()? {
+        Some(p) => p,
+        None => return Err(AppError::Generic("No downloads path set".into())),
+    };
+
+    let downloader = MemoryDownloader::new(app_handle, db)?;
+    downloader.download_memory(memory, storage_root).await.map(|_| ())
 }
 
 #[tauri::command]
-async fn get_message_index_at_date(
-    conversation_id: String,
-    date: String,
+async fn export_memories_gallery(
+    output_dir: String,
+    date_range: Option<DateRange>,
+    strip_metadata: Option<bool>,
+    strip_all_exif: Option<bool>,
     state: State<'_, DbState>,
     app_handle: tauri::AppHandle,
-) -> AppResult<i32> {
-    match db_from_state(&state, &app_handle)? {
-        Some(db) => db.get_message_index_at_date(&conversation_id, &date),
-        None => Ok(0),
-    }
+) -> AppResult<GalleryReport> {
+    let _trace = perf::command("export_memories_gallery");
+    let output = PathBuf::from(&output_dir);
+    let db = db_from_state(&state, &app_handle)?;
+    let report = tauri::async_runtime::spawn_blocking(move || {
+        let task = app_handle
+            .state::<TaskRegistry>()
+            .register("Export memories gallery", false);
+        let memories = db.get_memories(None)?;
+        let scrub = strip_metadata.unwrap_or(false).then(|| {
+            if strip_all_exif.unwrap_or(false) {
+                ScrubMode::AllExif
+            } else {
+                ScrubMode::Gps
+            }
+        });
+        GalleryExporter::new(output).scrub_metadata(scrub).export(
+            memories,
+            date_range.as_ref(),
+            |progress: &GalleryProgress| {
+                task.heartbeat(
+                    Some(progress.processed as f32 / progress.total.max(1) as f32),
+                    progress.current_month.as_deref(),
+                );
+                let _ = app_handle.emit("gallery-progress", progress);
+            },
+        )
+    })
+    .await
+    .map_err(|e| AppError::Generic(format!("Thread join error: {}", e)))??;
+
+    log::info!(
+        "Exported memories gallery to {} ({} files, {} missing)",
+        output_dir,
+        report.files_written,
+        report.missing
+    );
+    Ok(report)
 }
 
 #[tauri::command]
-async fn get_activity_dates(
-    conversation_id: String,
-    state: State<'_, DbState>,
-    app_handle: tauri::AppHandle,
-) -> AppResult<Vec<String>> {
-    match db_from_state(&state, &app_handle)? {
-        Some(db) => db.get_activity_dates(&conversation_id),
-        None => Ok(Vec::new()),
-    }
+async fn get_thumbnail(path: String, app_handle: tauri::AppHandle) -> AppResult<Option<String>> {
+    let _trace = perf::command("get_thumbnail");
+    let cache = thumbnail_cache(&app_handle)?;
+    let handle = app_handle.clone();
+    let outcome = tauri::async_runtime::spawn_blocking(move || {
+        // Background pre-generation pauses while this guard is alive
+        let jobs = handle.state::<ThumbnailJobState>();
+        let _guard = jobs.interactive_guard();
+        cache.get_or_create(Path::new(&path))
+    })
+    .await
+    .map_err(|e| AppError::Generic(format!("Thread join error: {}", e)))??;
+
+    Ok(match outcome {
+        ThumbnailOutcome::Cached(p) | ThumbnailOutcome::Generated(p) => Some(p.to_string_lossy().into_owned()),
+        ThumbnailOutcome::Missing | ThumbnailOutcome::Unsupported => None,
+    })
 }
 
 #[tauri::command]
-async fn export_conversation(
-    conversation_id: String,
-    format: String,
-    output_path: String,
+async fn pregenerate_thumbnails(
+    limit: Option<usize>,
     state: State<'_, DbState>,
     app_handle: tauri::AppHandle,
 ) -> AppResult<()> {
-    // Validate output path — must be under user-accessible directories
-    let output = PathBuf::from(&output_path);
-    if let Some(parent) = output.parent() {
-        if !parent.exists() {
-            return Err(AppError::Validation(format!(
-                "Output directory does not exist: {}",
-                parent.display()
-            )));
-        }
-    }
-    // Reject paths that try to traverse outside via ..
-    let canonical_parent = output.parent().and_then(|p| std::fs::canonicalize(p).ok());
-    if canonical_parent.is_none() {
-        return Err(AppError::Validation("Invalid output path".to_string()));
-    }
-
-    let db = db_from_state(&state, &app_handle)?.ok_or_else(|| AppError::Generic("No data imported yet".to_string()))?;
-    
-    let file = fs::File::create(&output_path)?;
-    let mut writer = std::io::BufWriter::new(file);
-    use std::io::Write;
+    let _trace = perf::command("pregenerate_thumbnails");
+    let db = db_from_state(&state, &app_handle)?;
+    start_thumbnail_pregeneration(app_handle, db, limit)
+}
 
-    if format == "json" {
-        writer.write_all(b"[\n")?;
-        let mut first = true;
-        db.foreach_message(&conversation_id, |msg| {
-            if !first {
-                writer.write_all(b",\n")?;
-            }
-            serde_json::to_writer(&mut writer, &msg).map_err(|e| AppError::Generic(e.to_string()))?;
-            first = false;
-            Ok(())
-        })?;
-        writer.write_all(b"\n]")?;
-    } else {
-        // Text format
-        let display_name = db.get_conversation_name(&conversation_id)?.unwrap_or_else(|| conversation_id.clone());
-        writer.write_all(format!("Conversation: {}\n", display_name).as_bytes())?;
-        writer.write_all(b"---\n\n")?;
-        
-        db.foreach_message(&conversation_id, |msg| {
-            let sender = msg.sender_name.as_deref().unwrap_or(&msg.sender);
-            let time = msg.timestamp.format("%Y-%m-%d %H:%M:%S");
-            let line = format!(
-                "[{}] {}: {}\n",
-                time,
-                sender,
-                msg.content.as_deref().unwrap_or("")
-            );
-            writer.write_all(line.as_bytes())?;
-            Ok(())
-        })?;
+#[tauri::command]
+async fn cancel_thumbnail_pregeneration(jobs: State<'_, ThumbnailJobState>) -> AppResult<()> {
+    let _trace = perf::command("cancel_thumbnail_pregeneration");
+    if jobs.is_running() {
+        log::info!("Cancelling thumbnail pre-generation");
+        jobs.request_cancel();
     }
-
-    writer.flush()?;
-    log::info!("Exported conversation to {}", output_path);
     Ok(())
 }
 
 #[tauri::command]
-async fn get_validation_report(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<Option<ValidationReport>> {
-    match db_from_state(&state, &app_handle)? {
-        Some(db) => Ok(Some(db.get_validation_report()?)),
-        None => Ok(None),
-    }
+async fn set_auto_pregenerate_thumbnails(
+    enabled: bool,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<()> {
+    let _trace = perf::command("set_auto_pregenerate_thumbnails");
+    let db = db_from_state(&state, &app_handle)?;
+    db.set_setting("auto_pregenerate_thumbnails", if enabled { "true" } else { "false" })
 }
 
+/// Set how many chat subpages are parsed at once; `None` restores the default of min(cores, 4).
+/// Returns the thread count the next import will use.
 #[tauri::command]
-async fn reset_data(app_handle: tauri::AppHandle) -> AppResult<()> {
-    if DB_MAINTENANCE.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst).is_err() {
-        return Err(AppError::Generic("A data operation is already in progress.".into()));
+async fn set_parse_threads(
+    threads: Option<usize>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<usize> {
+    let _trace = perf::command("set_parse_threads");
+    ensure_live_database(&app_handle)?;
+    let db = db_from_state(&state, &app_handle)?;
+    match threads {
+        Some(0) => return Err(AppError::Validation("Parse threads must be at least 1".into())),
+        Some(n) => db.set_setting("parse_threads", &n.to_string())?,
+        None => db.delete_setting("parse_threads")?,
     }
+    Ok(ingestion::parse_thread_count(
+        db.get_setting("parse_threads")?.as_deref(),
+    ))
+}
 
-    // Clear the cached pool before deleting files
-    clear_db_cache(&app_handle);
-
-    let result = (|| -> AppResult<()> {
-        let path = db_path(&app_handle)?;
-        if path.exists() {
-            fs::remove_file(&path)?;
-            log::info!("Database deleted: {:?}", path);
-        }
-        // Also remove WAL and SHM files if they exist
-        let wal = path.with_extension("db-wal");
-        let shm = path.with_extension("db-shm");
-        if wal.exists() {
-            let _ = fs::remove_file(&wal);
-        }
-        if shm.exists() {
-            let _ = fs::remove_file(&shm);
-        }
-        Ok(())
-    })();
-
-    DB_MAINTENANCE.store(false, Ordering::SeqCst);
-    result
+/// Whether a `rebuild_search_index` is running and whether it was asked to stop, managed by Tauri.
+#[derive(Default)]
+struct SearchIndexJob {
+    running: AtomicBool,
+    cancel: AtomicBool,
 }
 
+/// Rebuild the search index export by export, resuming any build that was cancelled or cut
+/// short. Progress arrives via `search-index-progress`.
 #[tauri::command]
-async fn reimport_data(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<()> {
-    // Read export info BEFORE setting maintenance flag
-    let stored_export = match db_from_state(&state, &app_handle)? {
-        Some(db) => {
-            let exports = db.get_exports()?;
-            exports.into_iter().next()
+async fn rebuild_search_index(
+    job: State<'_, SearchIndexJob>,
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<Vec<SearchIndexProgress>> {
+    let _trace = perf::command("rebuild_search_index");
+    ensure_live_database(&app_handle)?;
+    let db = db_from_state(&state, &app_handle)?;
+    if job
+        .running
+        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        return Err(AppError::Generic("The search index is already being rebuilt.".into()));
+    }
+    job.cancel.store(false, Ordering::SeqCst);
+    let task = app_handle
+        .state::<TaskRegistry>()
+        .register("Rebuild search index", true);
+
+    let rebuild = || -> AppResult<Vec<SearchIndexProgress>> {
+        let mut results = Vec::new();
+        for export in db.get_exports()? {
+            let progress = db.populate_fts_for_export(
+                &export.id,
+                || job.cancel.load(Ordering::SeqCst) || task.is_cancelled(),
+                |p| {
+                    task.heartbeat(
+                        (p.total > 0).then(|| p.indexed as f32 / p.total as f32),
+                        Some(&export.id),
+                    );
+                    let _ = app_handle.emit("search-index-progress", p.clone());
+                },
+            )?;
+            let cancelled = progress.cancelled;
+            results.push(progress);
+            if cancelled {
+                log::info!("Search index rebuild cancelled during {}", export.id);
+                break;
+            }
         }
-        None => None,
-    };
-
-    let export = match stored_export {
-        Some(e) => e,
-        None => return Err(AppError::Generic("No existing import to reimport from.".into())),
+        Ok(results)
     };
-
-    // Verify the source paths still exist before wiping
-    for path in &export.source_paths {
-        if !path.exists() {
-            return Err(AppError::Generic(format!(
-                "Original export component no longer exists: {}. Cannot reimport.",
-                path.display()
-            )));
-        }
-    }
-
-    if DB_MAINTENANCE.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst).is_err() {
-        return Err(AppError::Generic("A reimport is already in progress.".into()));
-    }
-
-    let result = reimport_data_inner(&app_handle, export).await;
-    DB_MAINTENANCE.store(false, Ordering::SeqCst);
+    let result = rebuild();
+    job.running.store(false, Ordering::SeqCst);
     result
 }
 
-async fn reimport_data_inner(app_handle: &tauri::AppHandle, export: ExportSet) -> AppResult<()> {
-    log::info!("reimport_data: reimporting (type: {:?}, {} parts)", export.source_type, export.source_paths.len());
-
-    // Clear cached pool before deleting files
-    clear_db_cache(app_handle);
-
-    // Wipe the DB
-    let path = db_path(app_handle)?;
-    if path.exists() {
-        fs::remove_file(&path)?;
-    }
-    let wal = path.with_extension("db-wal");
-    let shm = path.with_extension("db-shm");
-    if wal.exists() {
-        let _ = fs::remove_file(&wal);
-    }
-    if shm.exists() {
-        let _ = fs::remove_file(&shm);
+#[tauri::command]
+async fn cancel_search_index_rebuild(job: State<'_, SearchIndexJob>) -> AppResult<()> {
+    let _trace = perf::command("cancel_search_index_rebuild");
+    if job.running.load(Ordering::SeqCst) {
+        log::info!("Cancelling search index rebuild");
+        job.cancel.store(true, Ordering::SeqCst);
     }
-
-    // Re-process the same export
-    process_export(export, app_handle.clone()).await
+    Ok(())
 }
 
+/// Long-running commands in progress, oldest first.
 #[tauri::command]
-async fn get_log_path(app_handle: tauri::AppHandle) -> AppResult<String> {
-    // Prefer app data dir for log path, fall back to cwd
-    let path = match app_handle.path().app_data_dir() {
-        Ok(dir) => dir.join("snap_explorer.log"),
-        Err(_) => std::env::current_dir().unwrap_or_default().join("snap_explorer.log"),
-    };
-    Ok(path.to_string_lossy().into_owned())
+async fn get_active_tasks(tasks: State<'_, TaskRegistry>) -> AppResult<Vec<ActiveTask>> {
+    let _trace = perf::command("get_active_tasks");
+    Ok(tasks.active())
 }
 
+/// Ask a running task to stop. Fails for tasks whose work can't be interrupted.
 #[tauri::command]
-async fn set_storage_path(path: String, state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<()> {
-    let path_buf = PathBuf::from(&path);
-    StorageManager::validate_path(path_buf.clone()).map_err(|e| AppError::Generic(e.to_string()))?;
+async fn cancel_task(id: u64, tasks: State<'_, TaskRegistry>) -> AppResult<()> {
+    let _trace = perf::command("cancel_task");
+    tasks.cancel(id)
+}
 
-    let db = db_from_state(&state, &app_handle)?.ok_or_else(|| AppError::Generic("Database not initialized".into()))?;
-    db.set_setting("storage_path", &path)?;
-    log::info!("Storage path set to: {}", path);
-    Ok(())
+/// The last `last_n` timed commands and reads (default 200), oldest first. Empty unless
+/// tracing is enabled.
+#[tauri::command]
+async fn get_performance_trace(last_n: Option<usize>) -> AppResult<Vec<TraceEntry>> {
+    let _trace = perf::command("get_performance_trace");
+    Ok(perf::recent(last_n.unwrap_or(200)))
 }
 
 #[tauri::command]
-async fn get_storage_path(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<Option<String>> {
-    let db = db_from_state(&state, &app_handle)?;
-    if let Some(db) = db {
-        db.get_setting("storage_path")
-    } else {
-        Ok(None)
-    }
+async fn get_performance_settings(
+    state: State<'_, DbState>,
+    app_handle: tauri::AppHandle,
+) -> AppResult<PerformanceSettings> {
+    let _trace = perf::command("get_performance_settings");
+    live_database(&state, &app_handle)?.get_performance_settings()
 }
 
+/// Save and apply the tracing settings; they also apply from the next launch.
 #[tauri::command]
-async fn check_disk_space(
-    path: Option<String>,
+async fn set_performance_settings(
+    settings: PerformanceSettings,
     state: State<'_, DbState>,
     app_handle: tauri::AppHandle,
-) -> AppResult<DiskSpaceInfo> {
-    let path_to_check = if let Some(p) = path {
-        PathBuf::from(p)
-    } else {
-        let db = db_from_state(&state, &app_handle)?.ok_or_else(|| AppError::Generic("Database not initialized".into()))?;
-        let stored_path = db.get_setting("storage_path")?;
-        match stored_path {
-            Some(p) => PathBuf::from(p),
-            None => return Err(AppError::Generic("No storage path set".into())),
-        }
-    };
+) -> AppResult<()> {
+    let _trace = perf::command("set_performance_settings");
+    ensure_live_database(&app_handle)?;
+    if settings.slow_threshold_ms == 0 {
+        return Err(AppError::Validation(
+            "The slow call threshold must be at least 1 ms".into(),
+        ));
+    }
+    live_database(&state, &app_handle)?.set_performance_settings(&settings)?;
+    perf::configure(&settings);
+    log::info!(
+        "Performance tracing {}",
+        if settings.enabled { "enabled" } else { "disabled" }
+    );
+    Ok(())
+}
 
-    StorageManager::get_disk_space(path_to_check).map_err(|e| AppError::Generic(e.to_string()))
+/// Relay running tasks as `task-heartbeat` every few seconds, and each one that goes silent
+/// past `tasks::STALL_THRESHOLD` once as `task-stalled`.
+fn start_task_watchdog(app_handle: tauri::AppHandle) {
+    let tasks = app_handle.state::<TaskRegistry>().inner().clone();
+    let spawned = std::thread::Builder::new()
+        .name("task-watchdog".into())
+        .spawn(move || loop {
+            std::thread::sleep(tasks::WATCHDOG_INTERVAL);
+            let active = tasks.active();
+            if active.is_empty() {
+                continue;
+            }
+            for task in tasks.newly_stalled(tasks::STALL_THRESHOLD) {
+                log::warn!(
+                    "Task {} ({}) has reported nothing for {} ms",
+                    task.id,
+                    task.name,
+                    task.since_heartbeat_ms
+                );
+                let _ = app_handle.emit("task-stalled", task);
+            }
+            let _ = app_handle.emit("task-heartbeat", active);
+        });
+    if let Err(e) = spawned {
+        log::warn!("Could not start the task watchdog: {}", e);
+    }
 }
 
-#[tauri::command]
-async fn download_all_memories(state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<()> {
-    let db = db_from_state(&state, &app_handle)?.ok_or_else(|| AppError::Generic("Database not initialized".into()))?;
-    let downloader = MemoryDownloader::new(app_handle, db);
-    downloader.download_all_pending().await
+/// Emit `data-changed` with the domains the live database's writes touched, once a burst of
+/// writes has settled.
+fn start_change_notifier(app_handle: tauri::AppHandle) {
+    let spawned = std::thread::Builder::new()
+        .name("change-notifier".into())
+        .spawn(move || loop {
+            std::thread::sleep(changes::POLL_INTERVAL);
+            // Only a database someone already opened can have changes
+            let db = match app_handle.state::<DbState>().lock() {
+                Ok(guard) => guard.clone(),
+                Err(_) => continue,
+            };
+            if let Some(domains) = db.and_then(|db| db.changes().take_settled(changes::DEBOUNCE)) {
+                log::debug!("Data changed: {:?}", domains);
+                let _ = app_handle.emit("data-changed", DataChanged { domains });
+            }
+        });
+    if let Err(e) = spawned {
+        log::warn!("Could not start the change notifier: {}", e);
+    }
 }
 
-#[tauri::command]
-async fn download_memory(memory: Memory, state: State<'_, DbState>, app_handle: tauri::AppHandle) -> AppResult<()> {
-    let db = db_from_state(&state, &app_handle)?.ok_or_else(|| AppError::Generic("Database not initialized".into()))?;
-    let storage_path = db.get_setting("storage_path")?;
-    let storage_root = match storage_path {
-        Some(p) => PathBuf::from(p),
-        None => return Err(AppError::Generic("No storage path set".into())),
+/// Spawn the background thumbnail task. Returns immediately; progress arrives via `thumbnail-progress`.
+fn start_thumbnail_pregeneration(
+    app_handle: tauri::AppHandle,
+    db: Arc<DatabaseManager>,
+    limit: Option<usize>,
+) -> AppResult<()> {
+    if !app_handle.state::<ThumbnailJobState>().try_start() {
+        return Err(AppError::Generic("Thumbnail generation is already running.".into()));
+    }
+
+    tauri::async_runtime::spawn_blocking(move || {
+        match run_thumbnail_pregeneration(&app_handle, &db, limit) {
+            Ok(p) => log::info!(
+                "Thumbnail pre-generation finished: {} generated, {} skipped, {} failed{}",
+                p.generated,
+                p.skipped,
+                p.failed,
+                if p.cancelled { " (cancelled)" } else { "" }
+            ),
+            Err(e) => log::error!("Thumbnail pre-generation failed: {}", e),
+        }
+        app_handle.state::<ThumbnailJobState>().finish();
+    });
+    Ok(())
+}
+
+fn run_thumbnail_pregeneration(
+    app_handle: &tauri::AppHandle,
+    db: &DatabaseManager,
+    limit: Option<usize>,
+) -> AppResult<ThumbnailProgress> {
+    const PAGE_SIZE: i32 = 100;
+
+    let jobs = app_handle.state::<ThumbnailJobState>();
+    let jobs: &ThumbnailJobState = &jobs;
+    let cache = thumbnail_cache(app_handle)?;
+    let task = app_handle.state::<TaskRegistry>().register("Generate thumbnails", true);
+    let cancelled = || jobs.is_cancelled() || task.is_cancelled();
+
+    // Keep the pool small and leave a core free so interactive requests are never starved
+    let threads = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(2)
+        .saturating_sub(1)
+        .clamp(1, 4);
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|i| format!("thumbnails-{}", i))
+        .build()
+        .map_err(|e| AppError::Generic(format!("Failed to create thumbnail pool: {}", e)))?;
+
+    let available = db
+        .get_unified_media_stream(1, 0, MediaDirection::All, None)?
+        .total_count
+        .max(0) as usize;
+    let mut progress = ThumbnailProgress {
+        total: limit.map_or(available, |l| l.min(available)),
+        ..Default::default()
     };
+    log::info!(
+        "Thumbnail pre-generation: {} items, {} worker(s)",
+        progress.total,
+        threads
+    );
+
+    let mut offset = 0;
+    while progress.processed < progress.total && !cancelled() {
+        let page = db.get_unified_media_stream(PAGE_SIZE, offset, MediaDirection::All, None)?;
+        if page.items.is_empty() {
+            break;
+        }
+        offset += page.items.len() as i32;
+        let take = (progress.total - progress.processed).min(page.items.len());
+
+        let outcomes: Vec<_> = pool.install(|| {
+            page.items[..take]
+                .par_iter()
+                .map(|item| {
+                    if cancelled() {
+                        return None;
+                    }
+                    jobs.yield_to_interactive();
+                    Some((item, cache.get_or_create(&item.path)))
+                })
+                .collect()
+        });
+
+        for (item, outcome) in outcomes.into_iter().flatten() {
+            progress.processed += 1;
+            match outcome {
+                Ok(ThumbnailOutcome::Generated(_)) => progress.generated += 1,
+                Ok(_) => progress.skipped += 1,
+                Err(e) => {
+                    progress.failed += 1;
+                    log::debug!("Thumbnail failed for {}: {}", item.id, e);
+                }
+            }
+        }
+        task.heartbeat(Some(progress.processed as f32 / progress.total.max(1) as f32), None);
+        let _ = app_handle.emit("thumbnail-progress", progress.clone());
+    }
 
-    let downloader = MemoryDownloader::new(app_handle, db);
-    downloader.download_memory(memory, storage_root).await
+    progress.cancelled = cancelled();
+    progress.done = true;
+    let _ = app_handle.emit("thumbnail-progress", progress.clone());
+    Ok(progress)
 }
 
 #[tauri::command]
 async fn show_in_folder(path: String) -> AppResult<()> {
+    let _trace = perf::command("show_in_folder");
     #[cfg(target_os = "macos")]
     {
         std::process::Command::new("open")
@@ -1035,6 +2824,37 @@ pub fn run() {
 
     tauri::Builder::default()
         .manage(Mutex::new(None::<Arc<DatabaseManager>>) as DbState)
+        .manage(ThumbnailJobState::default())
+        .manage(SearchIndexJob::default())
+        .manage(SnapshotState::default())
+        .manage(ImportTracker::default())
+        .manage(TaskRegistry::default())
+        .manage(DownloadControl::default())
+        .manage(OrphanScan::default())
+        .setup(|app| {
+            // Create the database up front so the first screen never has to
+            let handle = app.handle();
+            start_task_watchdog(handle.clone());
+            start_change_notifier(handle.clone());
+            match live_database(&handle.state::<DbState>(), handle) {
+                Ok(db) => {
+                    match db.get_performance_settings() {
+                        Ok(settings) => perf::configure(&settings),
+                        Err(e) => log::warn!("Could not read performance settings: {}", e),
+                    }
+                    report_interrupted_downloads(&db, handle);
+                    match fs_journal::recover(&db) {
+                        Ok(report) if report != FsRecoveryReport::default() => {
+                            log::warn!("Repaired file moves interrupted by a crash: {:?}", report)
+                        }
+                        Ok(_) => {}
+                        Err(e) => log::error!("Could not recover interrupted file moves: {}", e),
+                    }
+                }
+                Err(e) => log::error!("Could not open the database at startup: {}", e),
+            }
+            Ok(())
+        })
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
@@ -1043,28 +2863,124 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             detect_exports,
             auto_detect_exports,
+            get_detection_settings,
+            set_detection_settings,
+            get_media_linking_settings,
+            set_media_linking_settings,
+            preview_export,
             process_export,
+            cancel_ingestion,
+            complete_import,
+            get_ingestion_history,
             get_conversations,
+            get_conversations_page,
+            get_conversation,
+            get_people,
+            get_owner_profile,
+            get_validation_issues,
+            refresh_media_stats,
+            relink_media,
+            refresh_conversation_stats,
             get_conversation_name,
             get_messages,
             get_messages_page,
             get_export_stats,
+            get_active_export,
+            set_active_export,
             get_exports,
+            get_app_state,
             search_messages,
+            search_messages_on_day,
+            search_all,
+            record_search,
+            save_search,
+            get_saved_searches,
+            delete_saved_search,
+            create_tag,
+            delete_tag,
+            get_tags,
+            tag_entity,
+            untag_entity,
+            get_tagged,
+            open_database_readonly,
+            set_active_database,
+            get_active_database,
             get_memories,
+            get_memory,
+            get_adjacent_memories,
+            get_memories_page,
             get_unified_media_stream,
+            get_media_info,
+            lookup_media_id,
+            find_orphaned_media,
+            get_conversation_media,
+            get_media_timeline,
+            get_media_offset_at_date,
             get_validation_report,
+            verify_media,
             get_message_index_at_date,
+            get_messages_around,
             get_activity_dates,
+            get_message_density,
+            get_activity_timeline,
+            get_activity_heatmap,
+            get_call_totals,
+            get_location_history,
+            get_shared_locations,
+            get_top_phrases,
+            get_text_analytics,
+            get_response_stats,
+            get_on_this_day,
+            suggest_export_path,
             export_conversation,
+            export_all_conversations,
+            export_memories,
             reset_data,
+            check_export_changes,
             reimport_data,
             get_log_path,
+            redact_events,
+            redact_sender,
+            get_redactions,
+            export_user_data,
+            import_user_data,
             set_storage_path,
+            relocate_downloads,
             get_storage_path,
+            set_extraction_path,
+            set_downloads_path,
+            get_paths_overview,
             check_disk_space,
             download_memory,
+            export_memories_gallery,
+            get_network_settings,
+            get_timezone_offset,
+            set_timezone_offset,
+            set_network_settings,
+            test_connection,
             download_all_memories,
+            resume_download_job,
+            get_download_jobs,
+            get_download_failure_summary,
+            verify_downloaded_memories,
+            pause_downloads,
+            resume_downloads,
+            cancel_downloads,
+            get_download_queue_state,
+            get_database_info,
+            get_thumbnail,
+            pregenerate_thumbnails,
+            cancel_thumbnail_pregeneration,
+            rebuild_search_index,
+            cancel_search_index_rebuild,
+            get_active_tasks,
+            cancel_task,
+            get_performance_trace,
+            get_performance_settings,
+            set_performance_settings,
+            get_diagnostics,
+            set_auto_pregenerate_thumbnails,
+            set_parse_threads,
             show_in_folder
         ])
         .run(tauri::generate_context!())
@@ -0,0 +1,306 @@
+//! "Why is this photo missing?": follow one media ID from the chat JSON that listed it, through
+//! the file the media linker would pick for it, to the messages it ended up linked to. The
+//! reverse question, which files in an export nothing links to, is `find_orphaned_media`.
+
+use crate::db::DatabaseManager;
+use crate::error::{AppError, AppResult};
+use crate::ingestion::is_os_metadata;
+use crate::ingestion::media_linker::MediaLinker;
+use crate::models::{MediaIdEvent, MediaIdTrace, MediaIdVerdict, OrphanedMediaFile, OrphanedMediaPage};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Trace `media_id` through the database and the media folders of each export in
+/// `source_dirs` (extraction directories or export folders). Reads only. A blank ID is a
+/// `Validation` error, since it would match every message.
+pub fn lookup_media_id(db: &DatabaseManager, media_id: &str, source_dirs: &[PathBuf]) -> AppResult<MediaIdTrace> {
+    let media_id = media_id.trim();
+    if media_id.is_empty() {
+        return Err(AppError::Validation("Media ID cannot be empty".into()));
+    }
+    let events: Vec<MediaIdEvent> = db
+        .get_events_for_media_id(media_id)?
+        .into_iter()
+        .filter_map(|event| {
+            let listed_in_metadata = MediaLinker::extract_media_ids(&event.metadata)
+                .iter()
+                .any(|id| id == media_id);
+            let names_id = event
+                .media_references
+                .iter()
+                .any(|p| file_media_id(p) == Some(media_id));
+            // A longer ID that merely contains this one is not a match
+            (listed_in_metadata || names_id).then(|| MediaIdEvent {
+                event_id: event.id,
+                conversation_id: event.conversation_id,
+                event_type: event.event_type,
+                timestamp: event.timestamp,
+                listed_in_metadata,
+                linked_files: event
+                    .media_references
+                    .into_iter()
+                    .map(|p| {
+                        let exists = p.is_file();
+                        (p, exists)
+                    })
+                    .collect(),
+            })
+        })
+        .collect();
+
+    let indexed_file = source_dirs
+        .iter()
+        .filter(|dir| dir.is_dir())
+        .find_map(|dir| MediaLinker::for_export(dir).path_for(media_id));
+
+    let linked: Vec<bool> = events
+        .iter()
+        .flat_map(|e| &e.linked_files)
+        .filter(|(path, _)| file_media_id(path) == Some(media_id))
+        .map(|(_, exists)| *exists)
+        .collect();
+    let verdict = if linked.contains(&true) {
+        MediaIdVerdict::Linked
+    } else if !linked.is_empty() {
+        MediaIdVerdict::LinkedFileMissing
+    } else {
+        match (events.is_empty(), indexed_file.is_some()) {
+            (false, true) => MediaIdVerdict::NotLinked,
+            (false, false) => MediaIdVerdict::NoFile,
+            (true, true) => MediaIdVerdict::Orphaned,
+            (true, false) => MediaIdVerdict::Unknown,
+        }
+    };
+
+    Ok(MediaIdTrace {
+        media_id: media_id.to_string(),
+        events,
+        indexed_file,
+        verdict,
+    })
+}
+
+/// Media folders of an export that `find_orphaned_media` looks through.
+const MEDIA_FOLDERS: [&str; 3] = ["chat_media", "media", "memories"];
+
+/// The last orphaned-media scan, managed by Tauri, so paging through its results doesn't scan
+/// every folder again for each page.
+#[derive(Default)]
+pub struct OrphanScan {
+    last: Mutex<Option<(Vec<PathBuf>, Vec<OrphanedMediaFile>)>>,
+}
+
+/// One page of the files in the media folders (chat_media/, media/ and memories/) of each
+/// export in `source_dirs` that no message or memory links to, sorted by path, with the count
+/// and size of all of them. Any kind of file counts, with a Media ID in its name or not. The
+/// first page scans the folders and keeps the result in `scan`; later pages of the same
+/// `source_dirs` reuse it. Reads only.
+pub fn find_orphaned_media(
+    db: &DatabaseManager,
+    source_dirs: &[PathBuf],
+    limit: i32,
+    offset: i32,
+    scan: &OrphanScan,
+) -> AppResult<OrphanedMediaPage> {
+    let limit = limit.clamp(1, 1000) as usize;
+    let offset = offset.max(0) as usize;
+    // A scan running for another page finishes before this one looks
+    let mut last = scan.last.lock().unwrap_or_else(|e| e.into_inner());
+    let reusable = offset > 0 && last.as_ref().is_some_and(|(dirs, _)| dirs.as_slice() == source_dirs);
+    if !reusable {
+        *last = Some((source_dirs.to_vec(), scan_orphans(db, source_dirs)?));
+    }
+    let orphans = last.as_ref().map(|(_, orphans)| orphans.as_slice()).unwrap_or_default();
+
+    let total_count = orphans.len();
+    let total_bytes = orphans.iter().map(|o| o.size_bytes).sum();
+    let items: Vec<OrphanedMediaFile> = orphans.iter().skip(offset).take(limit).cloned().collect();
+    Ok(OrphanedMediaPage {
+        has_more: offset + items.len() < total_count,
+        items,
+        total_count: total_count as i32,
+        total_bytes,
+    })
+}
+
+fn scan_orphans(db: &DatabaseManager, source_dirs: &[PathBuf]) -> AppResult<Vec<OrphanedMediaFile>> {
+    // Files are compared by canonical path; memories may have been stored as given
+    let linked: HashSet<PathBuf> = db
+        .get_linked_media_paths()?
+        .into_iter()
+        .map(|path| fs::canonicalize(&path).unwrap_or(path))
+        .collect();
+
+    let mut files = Vec::new();
+    for dir in source_dirs.iter().filter(|dir| dir.is_dir()) {
+        for folder in MEDIA_FOLDERS {
+            collect_files(&dir.join(folder), &mut files);
+        }
+    }
+    let mut orphans: Vec<OrphanedMediaFile> = files
+        .into_iter()
+        .map(|path| fs::canonicalize(&path).unwrap_or(path))
+        .filter(|path| !linked.contains(path))
+        .map(|path| OrphanedMediaFile {
+            size_bytes: fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+            path,
+        })
+        .collect();
+    orphans.sort_by(|a, b| a.path.cmp(&b.path));
+    orphans.dedup_by(|a, b| a.path == b.path);
+
+    let total_bytes: u64 = orphans.iter().map(|o| o.size_bytes).sum();
+    log::info!("Found {} orphaned media files ({} bytes)", orphans.len(), total_bytes);
+    Ok(orphans)
+}
+
+/// Every file under `dir`, leaving out macOS metadata files.
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.file_name().is_some_and(|n| is_os_metadata(Path::new(n))) {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(&path, files);
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+}
+
+fn file_media_id(path: &Path) -> Option<&str> {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .and_then(MediaLinker::media_id_from_file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Event;
+    use crate::test_fixtures;
+    use std::fs;
+
+    fn media_event(id: &str, media_ids: &[&str], linked: Option<PathBuf>) -> Event {
+        Event {
+            id: id.to_string(),
+            timestamp: test_fixtures::base_time(),
+            sender: test_fixtures::OWNER.to_string(),
+            sender_name: None,
+            conversation_id: Some(test_fixtures::CONVERSATION_IDS[0].to_string()),
+            content: None,
+            event_type: "MEDIA".to_string(),
+            media_references: linked.into_iter().collect(),
+            metadata: Some(serde_json::json!({ "media_ids": media_ids }).to_string()),
+        }
+    }
+
+    #[test]
+    fn test_traces_linked_orphaned_and_unknown_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let media_dir = dir.path().join("chat_media");
+        fs::create_dir_all(&media_dir).unwrap();
+        let linked = media_dir.join("2023-01-01_LINKED1.jpg");
+        fs::write(&linked, b"photo").unwrap();
+        fs::write(media_dir.join("2023-01-02_ORPHAN1.jpg"), b"photo").unwrap();
+
+        let db = test_fixtures::standard_db();
+        db.batch_insert_events(
+            &[
+                media_event("trace_linked", &["LINKED1"], Some(linked.clone())),
+                media_event("trace_missing", &["GONE1"], None),
+                media_event("trace_longer", &["LINKED10"], None),
+            ],
+            test_fixtures::EXPORT_ID,
+        )
+        .unwrap();
+        let sources = [dir.path().to_path_buf()];
+
+        let trace = lookup_media_id(&db, "LINKED1", &sources).unwrap();
+        assert_eq!(trace.verdict, MediaIdVerdict::Linked);
+        assert_eq!(trace.events.len(), 1);
+        assert!(trace.events[0].listed_in_metadata);
+        assert_eq!(trace.events[0].linked_files, vec![(linked, true)]);
+        assert!(trace.indexed_file.is_some());
+
+        let orphan = lookup_media_id(&db, "ORPHAN1", &sources).unwrap();
+        assert_eq!(orphan.verdict, MediaIdVerdict::Orphaned);
+        assert!(orphan.events.is_empty());
+
+        assert_eq!(
+            lookup_media_id(&db, "GONE1", &sources).unwrap().verdict,
+            MediaIdVerdict::NoFile
+        );
+
+        let unknown = lookup_media_id(&db, "NOPE", &sources).unwrap();
+        assert_eq!(unknown.verdict, MediaIdVerdict::Unknown);
+        assert!(unknown.events.is_empty() && unknown.indexed_file.is_none());
+
+        for blank in ["", "   "] {
+            assert!(matches!(
+                lookup_media_id(&db, blank, &sources),
+                Err(AppError::Validation(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_finds_files_nothing_links_to() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        for sub in ["chat_media", "media", "memories"] {
+            fs::create_dir_all(root.join(sub)).unwrap();
+        }
+        let linked = root.join("chat_media/2023-01-01_LINKED1.jpg");
+        fs::write(&linked, b"photo").unwrap();
+        let memory = root.join("memories/2023-02-01_MEMORY1.mp4");
+        fs::write(&memory, b"video").unwrap();
+        fs::write(root.join("chat_media/2023-01-02_ORPHAN1.jpg"), b"orphan").unwrap();
+        fs::write(root.join("media/thumbnail.png"), b"png").unwrap();
+        fs::write(root.join("memories/2023-02-02_ORPHAN2.mp4"), b"orphaned video").unwrap();
+        // Neither an image nor a video, and no Media ID in the name
+        fs::write(root.join("chat_media/recording.aac"), b"aac").unwrap();
+
+        let db = test_fixtures::standard_db();
+        db.batch_insert_events(
+            &[media_event("orphan_linked", &["LINKED1"], Some(linked))],
+            test_fixtures::EXPORT_ID,
+        )
+        .unwrap();
+        let mut downloaded = test_fixtures::memories().remove(4);
+        downloaded.media_path = Some(memory);
+        db.batch_insert_memories(&[downloaded]).unwrap();
+        let sources = [root.clone()];
+        let scan = OrphanScan::default();
+
+        let all = find_orphaned_media(&db, &sources, 100, 0, &scan).unwrap();
+        let paths: Vec<PathBuf> = all.items.iter().map(|o| o.path.clone()).collect();
+        assert_eq!(
+            paths,
+            [
+                root.join("chat_media/2023-01-02_ORPHAN1.jpg"),
+                root.join("chat_media/recording.aac"),
+                root.join("media/thumbnail.png"),
+                root.join("memories/2023-02-02_ORPHAN2.mp4")
+            ]
+        );
+        assert_eq!((all.total_count, all.total_bytes, all.has_more), (4, 26, false));
+
+        // Later pages come from the first page's scan; the next first page scans again
+        fs::write(root.join("media/later.png"), b"png").unwrap();
+        let second = find_orphaned_media(&db, &sources, 2, 2, &scan).unwrap();
+        assert_eq!(second.items, all.items[2..]);
+        assert_eq!(
+            (second.total_count, second.total_bytes, second.has_more),
+            (4, 26, false)
+        );
+        let rescanned = find_orphaned_media(&db, &sources, 2, 0, &scan).unwrap();
+        assert!(rescanned.has_more);
+        assert_eq!(rescanned.total_count, 5);
+    }
+}
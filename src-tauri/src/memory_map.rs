@@ -0,0 +1,200 @@
+//! Memory locations as GeoJSON or KML, for Google Earth, QGIS and other mapping tools.
+//!
+//! Each memory with coordinates becomes one point carrying its id, time, media type, download
+//! status and local file. Memories without coordinates are counted and left out.
+
+use crate::db::DatabaseManager;
+use crate::error::{AppError, AppResult};
+use crate::gallery::escape_html;
+use crate::models::{Memory, MemoryFilter, MemoryMapExport};
+use serde_json::json;
+use std::io::Write;
+
+/// File extension for a memory map format name, or None for an unknown format.
+pub fn map_extension(format: &str) -> Option<&'static str> {
+    match format {
+        "geojson" => Some("geojson"),
+        "kml" => Some("kml"),
+        _ => None,
+    }
+}
+
+/// Write the memories matching `filter` to `writer` as a GeoJSON FeatureCollection
+/// (`"geojson"`) or a KML document (`"kml"`), oldest first.
+pub fn write_memory_map(
+    db: &DatabaseManager,
+    format: &str,
+    filter: &MemoryFilter,
+    writer: &mut impl Write,
+) -> AppResult<MemoryMapExport> {
+    let kml = match format {
+        "geojson" => false,
+        "kml" => true,
+        other => return Err(AppError::Validation(format!("Unknown memory map format: {}", other))),
+    };
+    let mut report = MemoryMapExport::default();
+    if kml {
+        writer.write_all(
+            b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n<Document>\n<name>Snapchat Memories</name>\n",
+        )?;
+    } else {
+        writer.write_all(b"{\"type\":\"FeatureCollection\",\"features\":[\n")?;
+    }
+
+    db.foreach_memory(filter, |memory| {
+        let (Some(lat), Some(lon)) = (memory.latitude, memory.longitude) else {
+            report.skipped_without_location += 1;
+            return Ok(());
+        };
+        if kml {
+            writer.write_all(placemark(&memory, lat, lon).as_bytes())?;
+        } else {
+            if report.exported > 0 {
+                writer.write_all(b",\n")?;
+            }
+            serde_json::to_writer(&mut *writer, &feature(&memory, lat, lon))?;
+        }
+        report.exported += 1;
+        Ok(())
+    })?;
+
+    if kml {
+        writer.write_all(b"</Document>\n</kml>\n")?;
+    } else {
+        writer.write_all(b"\n]}\n")?;
+    }
+    log::info!(
+        "Exported {} memories as {} ({} without a location)",
+        report.exported,
+        format,
+        report.skipped_without_location
+    );
+    Ok(report)
+}
+
+/// A GeoJSON Point feature. Positions are `[longitude, latitude]` (RFC 7946, section 3.1.1).
+fn feature(memory: &Memory, lat: f64, lon: f64) -> serde_json::Value {
+    json!({
+        "type": "Feature",
+        "geometry": { "type": "Point", "coordinates": [lon, lat] },
+        "properties": {
+            "id": memory.id,
+            "timestamp": memory.timestamp.to_rfc3339(),
+            "media_type": memory.media_type,
+            "download_status": memory.download_status.as_str(),
+            "media_path": memory.media_path,
+        },
+    })
+}
+
+/// A KML Placemark. KML coordinates are also longitude first.
+fn placemark(memory: &Memory, lat: f64, lon: f64) -> String {
+    let data =
+        |name: &str, value: &str| format!("<Data name=\"{}\"><value>{}</value></Data>", name, escape_html(value));
+    let media_path = memory
+        .media_path
+        .as_ref()
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    format!(
+        "<Placemark><name>{}</name><TimeStamp><when>{}</when></TimeStamp><ExtendedData>{}{}{}{}</ExtendedData><Point><coordinates>{},{}</coordinates></Point></Placemark>\n",
+        escape_html(&format!("{} {}", memory.media_type, memory.timestamp.format("%Y-%m-%d %H:%M"))),
+        memory.timestamp.to_rfc3339(),
+        data("id", &memory.id),
+        data("media_type", &memory.media_type),
+        data("download_status", memory.download_status.as_str()),
+        data("media_path", &media_path),
+        lon,
+        lat
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{DateRange, DownloadStatus};
+    use crate::test_fixtures;
+
+    fn export(db: &DatabaseManager, format: &str, filter: &MemoryFilter) -> (String, MemoryMapExport) {
+        let mut out = Vec::new();
+        let report = write_memory_map(db, format, filter, &mut out).unwrap();
+        (String::from_utf8(out).unwrap(), report)
+    }
+
+    #[test]
+    fn test_geojson_puts_longitude_first_and_skips_memories_without_location() {
+        let db = test_fixtures::standard_db();
+        let mut unplaced = test_fixtures::memories().remove(0);
+        unplaced.id = "unplaced".to_string();
+        unplaced.latitude = None;
+        unplaced.longitude = None;
+        db.batch_insert_memories(&[unplaced]).unwrap();
+
+        let (geojson, report) = export(&db, "geojson", &MemoryFilter::default());
+        assert_eq!(
+            report,
+            MemoryMapExport {
+                exported: 5,
+                skipped_without_location: 1
+            }
+        );
+        let parsed: serde_json::Value = serde_json::from_str(&geojson).unwrap();
+        assert_eq!(parsed["type"], "FeatureCollection");
+        let features = parsed["features"].as_array().unwrap();
+        assert_eq!(features.len(), 5);
+        let first = &features[0];
+        assert_eq!(first["type"], "Feature");
+        assert_eq!(first["geometry"]["type"], "Point");
+        assert_eq!(first["geometry"]["coordinates"], json!([-74.0, 40.0]));
+        assert_eq!(first["properties"]["id"], "fixture_memory_0");
+        assert_eq!(first["properties"]["media_type"], "Image");
+        assert_eq!(first["properties"]["media_path"], "/fixtures/memories/memory_0.jpg");
+        assert_eq!(features[4]["geometry"]["coordinates"][1], json!(40.4));
+        assert!(features[4]["properties"]["media_path"].is_null());
+    }
+
+    #[test]
+    fn test_filter_by_date_and_download_status() {
+        let db = test_fixtures::standard_db();
+        let downloaded = MemoryFilter {
+            download_status: Some(DownloadStatus::Downloaded),
+            ..Default::default()
+        };
+        assert_eq!(export(&db, "geojson", &downloaded).1.exported, 2);
+
+        let base = test_fixtures::base_time();
+        let later = MemoryFilter {
+            date_range: Some(DateRange {
+                start: Some(base + chrono::Duration::days(3)),
+                end: None,
+            }),
+            ..Default::default()
+        };
+        let (kml, report) = export(&db, "kml", &later);
+        assert_eq!(report.exported, 2);
+        assert_eq!(kml.matches("<Placemark>").count(), 2);
+        assert!(kml.contains("<coordinates>-74,40.3</coordinates>"));
+        assert!(kml.contains("<when>2023-01-04T12:00:00+00:00</when>"));
+        assert!(kml.trim_end().ends_with("</kml>"));
+
+        let (empty, report) = export(
+            &db,
+            "geojson",
+            &MemoryFilter {
+                media_type: Some("Nothing".to_string()),
+                ..Default::default()
+            },
+        );
+        assert_eq!(report.exported, 0);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&empty).unwrap()["features"],
+            json!([])
+        );
+    }
+
+    #[test]
+    fn test_rejects_unknown_format() {
+        let db = test_fixtures::standard_db();
+        assert!(write_memory_map(&db, "shapefile", &MemoryFilter::default(), &mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,748 @@
+//! Strip location (or all) EXIF from copies of exported media without re-encoding them.
+//!
+//! JPEG and PNG are rewritten segment by segment, so the image data is copied byte for byte.
+//! HEIC keeps its EXIF at offsets other boxes point past, so it is edited in place instead:
+//! removed entries are zeroed rather than cut out, and the file keeps its size.
+
+use crate::error::{AppError, AppResult};
+use crate::models::{ScrubMode, ScrubOutcome};
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// EXIF tag pointing at the GPS IFD.
+const GPS_IFD_TAG: u16 = 0x8825;
+
+const JPEG_APP1: u8 = 0xE1;
+const JPEG_SOS: u8 = 0xDA;
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const PNG_XMP_KEYWORD: &[u8] = b"XML:com.adobe.xmp\0";
+
+/// Copy `src` to `dst` with metadata removed per `mode`. Formats that can't be scrubbed are
+/// copied unmodified and reported as such. Files whose metadata can't be parsed are not
+/// copied at all, so a scrub that fails never leaks the metadata it was meant to strip.
+pub fn copy_scrubbed(src: &Path, dst: &Path, mode: ScrubMode) -> AppResult<ScrubOutcome> {
+    let ext = src
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let scrubbed = match ext.as_str() {
+        "jpg" | "jpeg" => scrub_stream(src, dst, |r, w| scrub_jpeg(r, w, mode)),
+        "png" => scrub_stream(src, dst, |r, w| scrub_png(r, w, mode)),
+        "heic" | "heif" => scrub_heic(src, dst, mode),
+        _ => {
+            log::warn!(
+                "Metadata scrub: {} is not a supported format; copied unmodified",
+                src.display()
+            );
+            fs::copy(src, dst)?;
+            return Ok(ScrubOutcome::Unsupported);
+        }
+    };
+    match scrubbed {
+        Ok(true) => Ok(ScrubOutcome::Scrubbed),
+        Ok(false) => Ok(ScrubOutcome::Unnecessary),
+        Err(e) => {
+            log::warn!(
+                "Metadata scrub failed for {}: {}; left out of the export",
+                src.display(),
+                e
+            );
+            Ok(ScrubOutcome::Failed(e.to_string()))
+        }
+    }
+}
+
+/// Run a streaming rewrite from `src` into `dst`, removing `dst` again if it fails.
+fn scrub_stream<F>(src: &Path, dst: &Path, rewrite: F) -> AppResult<bool>
+where
+    F: FnOnce(&mut BufReader<fs::File>, &mut BufWriter<fs::File>) -> AppResult<bool>,
+{
+    let mut reader = BufReader::new(fs::File::open(src)?);
+    let mut writer = BufWriter::new(fs::File::create(dst)?);
+    let result = rewrite(&mut reader, &mut writer).and_then(|changed| {
+        writer.flush()?;
+        Ok(changed)
+    });
+    if result.is_err() {
+        drop(writer);
+        let _ = fs::remove_file(dst);
+    }
+    result
+}
+
+fn malformed(what: &str) -> AppError {
+    AppError::Parsing(format!("malformed {}", what))
+}
+
+/// Copy JPEG segments up to the image data, dropping XMP and EXIF (all mode) or editing the
+/// GPS IFD out of EXIF. Returns whether anything changed.
+fn scrub_jpeg<R: Read, W: Write>(r: &mut R, w: &mut W, mode: ScrubMode) -> AppResult<bool> {
+    let mut soi = [0u8; 2];
+    r.read_exact(&mut soi)?;
+    if soi != [0xFF, 0xD8] {
+        return Err(malformed("JPEG header"));
+    }
+    w.write_all(&soi)?;
+    let mut changed = false;
+    loop {
+        let mut marker = [0u8; 2];
+        r.read_exact(&mut marker)?;
+        if marker[0] != 0xFF {
+            return Err(malformed("JPEG segment"));
+        }
+        // Standalone markers carry no length
+        if marker[1] == 0x01 || (0xD0..=0xD7).contains(&marker[1]) {
+            w.write_all(&marker)?;
+            continue;
+        }
+        let mut len = [0u8; 2];
+        r.read_exact(&mut len)?;
+        let len = u16::from_be_bytes(len) as usize;
+        if len < 2 {
+            return Err(malformed("JPEG segment length"));
+        }
+        let mut body = vec![0u8; len - 2];
+        r.read_exact(&mut body)?;
+
+        if marker[1] == JPEG_APP1 && body.starts_with(XMP_HEADER) {
+            changed = true;
+            continue;
+        }
+        if marker[1] == JPEG_APP1 && body.starts_with(EXIF_HEADER) {
+            match mode {
+                ScrubMode::AllExif => {
+                    changed = true;
+                    continue;
+                }
+                ScrubMode::Gps => changed |= scrub_tiff(&mut body[EXIF_HEADER.len()..], mode)?,
+            }
+        }
+        w.write_all(&marker)?;
+        w.write_all(&(len as u16).to_be_bytes())?;
+        w.write_all(&body)?;
+        if marker[1] == JPEG_SOS {
+            // Entropy-coded image data and everything after it is copied as is
+            io::copy(r, w)?;
+            return Ok(changed);
+        }
+    }
+}
+
+/// Copy PNG chunks, dropping XMP text and EXIF (all mode) or editing the GPS IFD out of EXIF.
+fn scrub_png<R: Read, W: Write>(r: &mut R, w: &mut W, mode: ScrubMode) -> AppResult<bool> {
+    let mut signature = [0u8; 8];
+    r.read_exact(&mut signature)?;
+    if signature != PNG_SIGNATURE {
+        return Err(malformed("PNG signature"));
+    }
+    w.write_all(&signature)?;
+    let mut changed = false;
+    loop {
+        let mut head = [0u8; 8];
+        r.read_exact(&mut head)?;
+        let len = u32::from_be_bytes([head[0], head[1], head[2], head[3]]) as usize;
+        let kind = [head[4], head[5], head[6], head[7]];
+        let mut data = vec![0u8; len];
+        r.read_exact(&mut data)?;
+        let mut crc = [0u8; 4];
+        r.read_exact(&mut crc)?;
+
+        let is_xmp = matches!(&kind, b"iTXt" | b"tEXt") && data.starts_with(PNG_XMP_KEYWORD);
+        if is_xmp || (&kind == b"eXIf" && mode == ScrubMode::AllExif) {
+            changed = true;
+            continue;
+        }
+        if &kind == b"eXIf" && scrub_tiff(&mut data, mode)? {
+            changed = true;
+            crc = png_crc(&kind, &data).to_be_bytes();
+        }
+        w.write_all(&head)?;
+        w.write_all(&data)?;
+        w.write_all(&crc)?;
+        if &kind == b"IEND" {
+            return Ok(changed);
+        }
+    }
+}
+
+fn png_crc(kind: &[u8], data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in kind.iter().chain(data) {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Scrub the EXIF item of a HEIC file in place, in a copy written to `dst`. XMP items are
+/// zeroed whole.
+fn scrub_heic(src: &Path, dst: &Path, mode: ScrubMode) -> AppResult<bool> {
+    let mut file = fs::read(src)?;
+    let items = heic_metadata_extents(&file)?;
+    let mut changed = false;
+    if let Some((start, len)) = items.exif {
+        let item = file
+            .get_mut(extent_range(start, len)?)
+            .ok_or_else(|| malformed("HEIC Exif location"))?;
+        // The item starts with the offset of the TIFF header within the rest of it
+        let tiff_offset = be_u32(item, 0)? as usize;
+        let tiff = item
+            .get_mut(tiff_offset.checked_add(4).ok_or_else(|| malformed("HEIC Exif item"))?..)
+            .ok_or_else(|| malformed("HEIC Exif item"))?;
+        changed |= scrub_tiff(tiff, mode)?;
+    }
+    for (start, len) in items.xmp {
+        let item = file
+            .get_mut(extent_range(start, len)?)
+            .ok_or_else(|| malformed("HEIC XMP location"))?;
+        if item.iter().any(|&b| b != 0) {
+            item.fill(0);
+            changed = true;
+        }
+    }
+    fs::write(dst, &file)?;
+    Ok(changed)
+}
+
+/// The byte range of an item extent read from the file, rejecting offsets that overflow.
+fn extent_range(start: u64, len: u64) -> AppResult<std::ops::Range<usize>> {
+    let end = start.checked_add(len).ok_or_else(|| malformed("HEIC item extent"))?;
+    let start = usize::try_from(start).map_err(|_| malformed("HEIC item extent"))?;
+    let end = usize::try_from(end).map_err(|_| malformed("HEIC item extent"))?;
+    Ok(start..end)
+}
+
+/// File extents of the metadata items of a HEIC file.
+#[derive(Debug, Default)]
+struct HeicMetadata {
+    /// The single-extent Exif item.
+    exif: Option<(u64, u64)>,
+    /// Every extent of the XMP (`mime` items with an XMP content type).
+    xmp: Vec<(u64, u64)>,
+}
+
+/// Find the Exif and XMP items of a HEIC file.
+fn heic_metadata_extents(file: &[u8]) -> AppResult<HeicMetadata> {
+    let mut found_items = HeicMetadata::default();
+    let Some(meta) = find_box(file, b"meta")? else {
+        return Ok(found_items);
+    };
+    // meta is a full box: skip version and flags
+    let meta = meta.get(4..).ok_or_else(|| malformed("HEIC meta"))?;
+    let (Some(iinf), Some(iloc)) = (find_box(meta, b"iinf")?, find_box(meta, b"iloc")?) else {
+        return Ok(found_items);
+    };
+
+    // Full box with an entry count sized by version
+    let mut pos = if iinf.first() == Some(&0) { 6 } else { 8 };
+    let mut exif_id = None;
+    let mut xmp_ids = Vec::new();
+    while let Some((infe, next)) = next_box(iinf, pos)? {
+        if &infe.0 == b"infe" && infe.1.len() >= 12 && infe.1[0] >= 2 {
+            let (id, type_at) = if infe.1[0] == 2 {
+                (be_u16(infe.1, 4)? as u32, 8)
+            } else {
+                (be_u32(infe.1, 4)?, 10)
+            };
+            let item_type = infe.1.get(type_at..type_at + 4).ok_or_else(|| malformed("HEIC infe"))?;
+            if item_type == b"Exif" {
+                exif_id = Some(id);
+            } else if item_type == b"mime" && is_xmp_mime(&infe.1[type_at + 4..]) {
+                xmp_ids.push(id);
+            }
+        }
+        pos = next;
+    }
+    if exif_id.is_none() && xmp_ids.is_empty() {
+        return Ok(found_items);
+    }
+
+    // iloc is a full box too; the field sizes follow version and flags
+    let version = *iloc.first().ok_or_else(|| malformed("HEIC iloc"))?;
+    let sizes = *iloc.get(4).ok_or_else(|| malformed("HEIC iloc"))?;
+    let more_sizes = *iloc.get(5).ok_or_else(|| malformed("HEIC iloc"))?;
+    let (offset_size, length_size) = ((sizes >> 4) as usize, (sizes & 0xF) as usize);
+    let base_size = (more_sizes >> 4) as usize;
+    let index_size = if version >= 1 { (more_sizes & 0xF) as usize } else { 0 };
+    let id_size = if version < 2 { 2 } else { 4 };
+    let count = be_uint(iloc, 6, id_size)?;
+    let mut pos = 6 + id_size;
+    for _ in 0..count {
+        let id = be_uint(iloc, pos, id_size)? as u32;
+        pos += id_size;
+        let construction = if version >= 1 {
+            pos += 2;
+            be_u16(iloc, pos - 2)? & 0xF
+        } else {
+            0
+        };
+        pos += 2; // data_reference_index
+        let base = be_uint(iloc, pos, base_size)?;
+        pos += base_size;
+        let extents = be_u16(iloc, pos)?;
+        pos += 2;
+        let mut found = Vec::new();
+        for _ in 0..extents {
+            pos += index_size;
+            let offset = be_uint(iloc, pos, offset_size)?;
+            pos += offset_size;
+            let length = be_uint(iloc, pos, length_size)?;
+            pos += length_size;
+            let start = base.checked_add(offset).ok_or_else(|| malformed("HEIC iloc"))?;
+            found.push((start, length));
+        }
+        if Some(id) == exif_id {
+            found_items.exif = match (construction, found.as_slice()) {
+                (0, [extent]) => Some(*extent),
+                _ => return Err(AppError::Parsing("HEIC Exif item layout is not supported".into())),
+            };
+        } else if xmp_ids.contains(&id) {
+            if construction != 0 {
+                return Err(AppError::Parsing("HEIC XMP item layout is not supported".into()));
+            }
+            found_items.xmp.extend(found);
+        }
+    }
+    Ok(found_items)
+}
+
+/// Whether the rest of an `infe` box after a `mime` item type (item name, then content
+/// type, both NUL-terminated) names XMP.
+fn is_xmp_mime(rest: &[u8]) -> bool {
+    let mut fields = rest.split(|&b| b == 0);
+    let _name = fields.next();
+    fields
+        .next()
+        .is_some_and(|content_type| content_type.eq_ignore_ascii_case(b"application/rdf+xml"))
+}
+
+/// The body of the first box of `kind` among the boxes in `data`.
+fn find_box<'a>(data: &'a [u8], kind: &[u8; 4]) -> AppResult<Option<&'a [u8]>> {
+    let mut pos = 0;
+    while let Some(((found, body), next)) = next_box(data, pos)? {
+        if &found == kind {
+            return Ok(Some(body));
+        }
+        pos = next;
+    }
+    Ok(None)
+}
+
+/// The box starting at `pos` as (type, body), and where the next one starts.
+#[allow(clippy::type_complexity)]
+fn next_box(data: &[u8], pos: usize) -> AppResult<Option<(([u8; 4], &[u8]), usize)>> {
+    if pos + 8 > data.len() {
+        return Ok(None);
+    }
+    let size = be_u32(data, pos)? as usize;
+    let kind = [data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]];
+    let (header, size) = match size {
+        0 => (8, data.len() - pos),
+        1 => (
+            16,
+            usize::try_from(be_uint(data, pos + 8, 8)?).map_err(|_| malformed("HEIC box"))?,
+        ),
+        n => (8, n),
+    };
+    let end = pos.checked_add(size).ok_or_else(|| malformed("HEIC box"))?;
+    let body = data.get(pos + header..end).ok_or_else(|| malformed("HEIC box"))?;
+    Ok(Some(((kind, body), end)))
+}
+
+fn be_u16(data: &[u8], pos: usize) -> AppResult<u16> {
+    Ok(be_uint(data, pos, 2)? as u16)
+}
+
+fn be_u32(data: &[u8], pos: usize) -> AppResult<u32> {
+    Ok(be_uint(data, pos, 4)? as u32)
+}
+
+fn be_uint(data: &[u8], pos: usize, size: usize) -> AppResult<u64> {
+    let bytes = data.get(pos..pos + size).ok_or_else(|| malformed("HEIC box"))?;
+    Ok(bytes.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64))
+}
+
+/// A TIFF structure (the body of an EXIF block) edited in place.
+struct Tiff<'a> {
+    data: &'a mut [u8],
+    little_endian: bool,
+}
+
+impl Tiff<'_> {
+    fn u16(&self, pos: usize) -> AppResult<u16> {
+        let b = self.data.get(pos..pos + 2).ok_or_else(|| malformed("EXIF"))?;
+        Ok(if self.little_endian {
+            u16::from_le_bytes([b[0], b[1]])
+        } else {
+            u16::from_be_bytes([b[0], b[1]])
+        })
+    }
+
+    fn u32(&self, pos: usize) -> AppResult<u32> {
+        let b = self.data.get(pos..pos + 4).ok_or_else(|| malformed("EXIF"))?;
+        let b = [b[0], b[1], b[2], b[3]];
+        Ok(if self.little_endian {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    }
+
+    fn put_u16(&mut self, pos: usize, value: u16) {
+        let b = if self.little_endian {
+            value.to_le_bytes()
+        } else {
+            value.to_be_bytes()
+        };
+        self.data[pos..pos + 2].copy_from_slice(&b);
+    }
+
+    fn put_u32(&mut self, pos: usize, value: u32) {
+        let b = if self.little_endian {
+            value.to_le_bytes()
+        } else {
+            value.to_be_bytes()
+        };
+        self.data[pos..pos + 4].copy_from_slice(&b);
+    }
+
+    fn zero(&mut self, start: usize, len: usize) {
+        let end = (start + len).min(self.data.len());
+        if start < end {
+            self.data[start..end].fill(0);
+        }
+    }
+
+    /// Byte size of one value of an EXIF field type.
+    fn type_size(field_type: u16) -> usize {
+        match field_type {
+            3 | 8 => 2,
+            4 | 9 | 11 | 13 => 4,
+            5 | 10 | 12 => 8,
+            _ => 1,
+        }
+    }
+
+    /// Zero an IFD and every value it stores out of line.
+    fn zero_ifd(&mut self, ifd: usize) -> AppResult<()> {
+        let count = self.u16(ifd)? as usize;
+        for i in 0..count {
+            let entry = ifd + 2 + 12 * i;
+            let size = Self::type_size(self.u16(entry + 2)?) * self.u32(entry + 4)? as usize;
+            if size > 4 {
+                let offset = self.u32(entry + 8)? as usize;
+                self.zero(offset, size);
+            }
+        }
+        self.zero(ifd, 2 + 12 * count + 4);
+        Ok(())
+    }
+}
+
+/// Remove GPS data (or every tag) from a TIFF block without changing its length. Returns
+/// whether there was anything to remove.
+fn scrub_tiff(data: &mut [u8], mode: ScrubMode) -> AppResult<bool> {
+    let little_endian = match data.get(..2) {
+        Some(b"II") => true,
+        Some(b"MM") => false,
+        _ => return Err(malformed("EXIF byte order")),
+    };
+    let mut tiff = Tiff { data, little_endian };
+    let ifd0 = tiff.u32(4)? as usize;
+    let count = tiff.u16(ifd0)? as usize;
+
+    if mode == ScrubMode::AllExif {
+        if count == 0 {
+            return Ok(false);
+        }
+        // Leave a valid, empty TIFF: the header and one IFD with no entries
+        let len = tiff.data.len();
+        tiff.zero(8, len);
+        tiff.put_u32(4, 8);
+        return Ok(true);
+    }
+
+    for i in 0..count {
+        let entry = ifd0 + 2 + 12 * i;
+        if tiff.u16(entry)? != GPS_IFD_TAG {
+            continue;
+        }
+        let gps_ifd = tiff.u32(entry + 8)? as usize;
+        tiff.zero_ifd(gps_ifd)?;
+        // Close the gap in IFD0; the freed entry's bytes at the end become zeros
+        let end = ifd0 + 2 + 12 * count + 4;
+        if end > tiff.data.len() {
+            return Err(malformed("EXIF IFD"));
+        }
+        tiff.data.copy_within(entry + 12..end, entry);
+        tiff.zero(end - 12, 12);
+        tiff.put_u16(ifd0, (count - 1) as u16);
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LATITUDE_MARK: u32 = 0x2A2A_2A2A;
+
+    /// Little-endian TIFF with Make="Test" and a GPS IFD holding a latitude.
+    fn tiff_with_gps() -> Vec<u8> {
+        let mut t: Vec<u8> = b"II*\0".to_vec();
+        t.extend(8u32.to_le_bytes());
+        // IFD0 at 8: two entries, then the next-IFD offset
+        let make_at = 8 + 2 + 2 * 12 + 4;
+        let gps_at = make_at + 8;
+        t.extend(2u16.to_le_bytes());
+        t.extend([0x0F, 0x01, 2, 0]);
+        t.extend(5u32.to_le_bytes());
+        t.extend((make_at as u32).to_le_bytes());
+        t.extend([0x25, 0x88, 4, 0]);
+        t.extend(1u32.to_le_bytes());
+        t.extend((gps_at as u32).to_le_bytes());
+        t.extend(0u32.to_le_bytes());
+        t.extend(b"Test\0\0\0\0");
+        // GPS IFD: latitude ref inline, latitude as three rationals out of line
+        let lat_at = gps_at + 2 + 2 * 12 + 4;
+        t.extend(2u16.to_le_bytes());
+        t.extend([1, 0, 2, 0]);
+        t.extend(2u32.to_le_bytes());
+        t.extend(*b"N\0\0\0");
+        t.extend([2, 0, 5, 0]);
+        t.extend(3u32.to_le_bytes());
+        t.extend((lat_at as u32).to_le_bytes());
+        t.extend(0u32.to_le_bytes());
+        for _ in 0..3 {
+            t.extend(LATITUDE_MARK.to_le_bytes());
+            t.extend(1u32.to_le_bytes());
+        }
+        t
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|w| w == needle)
+    }
+
+    /// A real JPEG with an EXIF segment spliced in after SOI.
+    fn jpeg_with_gps(dir: &Path) -> std::path::PathBuf {
+        let img = image::RgbImage::from_fn(16, 16, |x, y| image::Rgb([x as u8 * 16, y as u8 * 16, 128]));
+        let mut encoded = Vec::new();
+        img.write_to(&mut io::Cursor::new(&mut encoded), image::ImageFormat::Jpeg)
+            .unwrap();
+        let mut segment = EXIF_HEADER.to_vec();
+        segment.extend(tiff_with_gps());
+        let mut file = encoded[..2].to_vec();
+        file.extend([0xFF, JPEG_APP1]);
+        file.extend(((segment.len() + 2) as u16).to_be_bytes());
+        file.extend(&segment);
+        file.extend(&encoded[2..]);
+        let path = dir.join("photo.jpg");
+        fs::write(&path, file).unwrap();
+        path
+    }
+
+    fn image_data(jpeg: &[u8]) -> &[u8] {
+        let sos = jpeg.windows(2).position(|w| w == [0xFF, JPEG_SOS]).unwrap();
+        &jpeg[sos..]
+    }
+
+    #[test]
+    fn test_gps_removed_and_image_data_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = jpeg_with_gps(dir.path());
+        let original = fs::read(&src).unwrap();
+        let dst = dir.path().join("copy.jpg");
+
+        assert_eq!(
+            copy_scrubbed(&src, &dst, ScrubMode::Gps).unwrap(),
+            ScrubOutcome::Scrubbed
+        );
+        let copy = fs::read(&dst).unwrap();
+        assert!(!contains(&copy, &LATITUDE_MARK.to_le_bytes()));
+        assert!(!contains(&copy, &[0x25, 0x88]));
+        assert!(contains(&copy, b"Test\0"), "other tags are kept");
+        assert_eq!(image_data(&copy), image_data(&original));
+        assert_eq!(
+            image::load_from_memory(&copy).unwrap().to_rgb8(),
+            image::load_from_memory(&original).unwrap().to_rgb8()
+        );
+        assert_eq!(fs::read(&src).unwrap(), original, "the original is untouched");
+
+        // Already clean
+        let again = dir.path().join("again.jpg");
+        assert_eq!(
+            copy_scrubbed(&dst, &again, ScrubMode::Gps).unwrap(),
+            ScrubOutcome::Unnecessary
+        );
+    }
+
+    #[test]
+    fn test_all_exif_and_png_and_unsupported_formats() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = jpeg_with_gps(dir.path());
+        let dst = dir.path().join("bare.jpg");
+        assert_eq!(
+            copy_scrubbed(&src, &dst, ScrubMode::AllExif).unwrap(),
+            ScrubOutcome::Scrubbed
+        );
+        let copy = fs::read(&dst).unwrap();
+        assert!(!contains(&copy, EXIF_HEADER));
+        assert_eq!(image_data(&copy), image_data(&fs::read(&src).unwrap()));
+
+        let mut png = Vec::new();
+        image::RgbImage::new(4, 4)
+            .write_to(&mut io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        // Insert an eXIf chunk right after IHDR (8 signature + 25 IHDR bytes)
+        let exif = tiff_with_gps();
+        let mut chunk = (exif.len() as u32).to_be_bytes().to_vec();
+        chunk.extend(b"eXIf");
+        chunk.extend(&exif);
+        chunk.extend(png_crc(b"eXIf", &exif).to_be_bytes());
+        png.splice(33..33, chunk);
+        let png_src = dir.path().join("shot.png");
+        fs::write(&png_src, &png).unwrap();
+        let png_dst = dir.path().join("shot_copy.png");
+        assert_eq!(
+            copy_scrubbed(&png_src, &png_dst, ScrubMode::Gps).unwrap(),
+            ScrubOutcome::Scrubbed
+        );
+        let scrubbed = fs::read(&png_dst).unwrap();
+        assert!(!contains(&scrubbed, &LATITUDE_MARK.to_le_bytes()));
+        assert!(image::load_from_memory(&scrubbed).is_ok(), "chunk CRCs still check out");
+
+        let video = dir.path().join("clip.mp4");
+        fs::write(&video, b"not an image").unwrap();
+        let video_copy = dir.path().join("clip_copy.mp4");
+        assert_eq!(
+            copy_scrubbed(&video, &video_copy, ScrubMode::Gps).unwrap(),
+            ScrubOutcome::Unsupported
+        );
+        assert_eq!(fs::read(&video_copy).unwrap(), b"not an image");
+
+        let broken = dir.path().join("broken.jpg");
+        fs::write(&broken, b"\xFF\xD8\xFF").unwrap();
+        assert!(matches!(
+            copy_scrubbed(&broken, &dir.path().join("broken_copy.jpg"), ScrubMode::Gps).unwrap(),
+            ScrubOutcome::Failed(_)
+        ));
+    }
+
+    fn heif_box(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut b = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        b.extend(kind);
+        b.extend(body);
+        b
+    }
+
+    /// A HEIC file with one Exif item and, if given, an XMP item, stored in `mdat`.
+    /// `exif_extent` overrides the Exif item's file offset and length in `iloc`.
+    fn heic_file(xmp: Option<&[u8]>, exif_extent: Option<(u64, u64)>) -> (Vec<u8>, usize) {
+        let mut exif = 6u32.to_be_bytes().to_vec();
+        exif.extend(EXIF_HEADER);
+        exif.extend(tiff_with_gps());
+
+        // infe v2: item 1 of type Exif, item 2 a mime item holding XMP
+        let mut infes = heif_box(b"infe", &[&[2, 0, 0, 0, 0, 1, 0, 0][..], b"Exif\0"].concat());
+        if xmp.is_some() {
+            infes.extend(heif_box(
+                b"infe",
+                &[&[2, 0, 0, 0, 0, 2, 0, 0][..], b"mime", b"XMP\0application/rdf+xml\0"].concat(),
+            ));
+        }
+        let items = 1 + xmp.is_some() as u16;
+        let iinf = heif_box(b"iinf", &[&[0, 0, 0, 0][..], &items.to_be_bytes(), &infes].concat());
+        // iloc v0 with 8-byte offsets and lengths, one extent per item
+        let iloc_len = 8 + 4 + 2 + 2 + items as usize * (2 + 2 + 2 + 16);
+        let meta_len = 8 + 4 + iinf.len() + iloc_len;
+        let ftyp = heif_box(b"ftyp", b"heic\0\0\0\0mif1heic");
+        let exif_at = ftyp.len() + meta_len + 8;
+        let mut iloc_body = vec![0, 0, 0, 0, 0x88, 0x00];
+        iloc_body.extend(items.to_be_bytes());
+        let mut extent = |id: u16, (offset, len): (u64, u64)| {
+            iloc_body.extend(id.to_be_bytes());
+            iloc_body.extend(0u16.to_be_bytes());
+            iloc_body.extend(1u16.to_be_bytes());
+            iloc_body.extend(offset.to_be_bytes());
+            iloc_body.extend(len.to_be_bytes());
+        };
+        extent(1, exif_extent.unwrap_or((exif_at as u64, exif.len() as u64)));
+        if let Some(xmp) = xmp {
+            extent(2, ((exif_at + exif.len()) as u64, xmp.len() as u64));
+        }
+        let iloc = heif_box(b"iloc", &iloc_body);
+        let meta = heif_box(b"meta", &[&[0, 0, 0, 0][..], &iinf, &iloc].concat());
+        assert_eq!(meta.len(), meta_len);
+        let mdat = heif_box(b"mdat", &[&exif[..], xmp.unwrap_or_default()].concat());
+        ([ftyp, meta, mdat].concat(), exif_at)
+    }
+
+    #[test]
+    fn test_heic_exif_item_scrubbed_in_place() {
+        let (file, item_offset) = heic_file(None, None);
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("IMG_0001.HEIC");
+        fs::write(&src, &file).unwrap();
+        let dst = dir.path().join("copy.heic");
+        assert_eq!(
+            copy_scrubbed(&src, &dst, ScrubMode::Gps).unwrap(),
+            ScrubOutcome::Scrubbed
+        );
+        let copy = fs::read(&dst).unwrap();
+        assert_eq!(copy.len(), file.len());
+        assert!(!contains(&copy, &LATITUDE_MARK.to_le_bytes()));
+        assert!(contains(&copy, b"Test\0"));
+        assert_eq!(copy[..item_offset], file[..item_offset]);
+    }
+
+    #[test]
+    fn test_heic_xmp_item_removed() {
+        let xmp = b"<x:xmpmeta><exif:GPSLatitude>40,42.8N</exif:GPSLatitude></x:xmpmeta>";
+        let (file, _) = heic_file(Some(xmp), None);
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("IMG_0002.HEIC");
+        fs::write(&src, &file).unwrap();
+        let dst = dir.path().join("copy.heic");
+        assert_eq!(
+            copy_scrubbed(&src, &dst, ScrubMode::Gps).unwrap(),
+            ScrubOutcome::Scrubbed
+        );
+        let copy = fs::read(&dst).unwrap();
+        assert_eq!(copy.len(), file.len());
+        assert!(!contains(&copy, b"GPSLatitude"));
+        assert!(!contains(&copy, &LATITUDE_MARK.to_le_bytes()));
+    }
+
+    #[test]
+    fn test_unparseable_files_are_never_copied() {
+        let dir = tempfile::tempdir().unwrap();
+        let (file, _) = heic_file(None, None);
+        // An Exif extent whose offset plus length overflows
+        let (overflowing, _) = heic_file(None, Some((u64::MAX - 8, 64)));
+        let broken = [
+            ("broken.jpg", b"\xFF\xD8\xFF".to_vec()),
+            ("truncated.heic", file[..file.len() - 40].to_vec()),
+            ("overflow.heic", overflowing),
+        ];
+        for (name, bytes) in broken {
+            let src = dir.path().join(name);
+            fs::write(&src, bytes).unwrap();
+            let dst = dir.path().join(format!("copy_{}", name));
+            let outcome = copy_scrubbed(&src, &dst, ScrubMode::Gps).unwrap();
+            assert!(matches!(outcome, ScrubOutcome::Failed(_)), "{}: {:?}", name, outcome);
+            assert!(!dst.exists(), "{} must not be exported with its metadata", name);
+        }
+    }
+}
@@ -3,8 +3,9 @@
 //! Defines all shared types used across the Tauri IPC boundary,
 //! database layer, and ingestion pipeline.
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 /// How an export was originally provided by the user.
@@ -29,6 +30,127 @@ pub struct ExportSet {
     pub creation_date: Option<DateTime<Utc>>,
     /// Validation result from structure detection.
     pub validation_status: ValidationStatus,
+    /// Per-part readability for zip exports (empty for folders).
+    #[serde(default)]
+    pub parts: Vec<ExportPart>,
+    /// Which import phases have run for an imported export; None for detected exports.
+    #[serde(default)]
+    pub import_phases: Option<ImportOptions>,
+    /// Re-downloaded copies of a part (e.g. "mydata~123 (1).zip") left out in favor of the
+    /// largest, newest copy.
+    #[serde(default)]
+    pub ignored_duplicates: Vec<PathBuf>,
+    /// Where the export's files were found when it was last imported.
+    #[serde(default)]
+    pub layout: Option<ExportLayout>,
+}
+
+/// Where each artifact of an export actually is. Exports that were reorganized or extracted
+/// into an extra folder don't always have the standard `json/` and `html/` layout.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct ExportLayout {
+    /// The directory searched; the extraction directory or export folder.
+    pub root: PathBuf,
+    pub friends_json: Option<PathBuf>,
+    pub chat_history_json: Option<PathBuf>,
+    pub snap_history_json: Option<PathBuf>,
+    #[serde(default)]
+    pub story_history_json: Option<PathBuf>,
+    #[serde(default)]
+    pub talk_history_json: Option<PathBuf>,
+    #[serde(default)]
+    pub location_history_json: Option<PathBuf>,
+    pub memories_json: Option<PathBuf>,
+    /// `html/chat_history`, holding the chat subpages.
+    pub chat_html_dir: Option<PathBuf>,
+    /// `chat_media` and `media`, whichever exist.
+    #[serde(default)]
+    pub media_dirs: Vec<PathBuf>,
+    #[serde(default)]
+    pub account_json: Option<PathBuf>,
+    #[serde(default)]
+    pub user_profile_json: Option<PathBuf>,
+}
+
+/// Which parts of an export an import covers. Everything by default; the skipped parts can be
+/// imported later with `complete_import`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct ImportOptions {
+    /// Chat HTML, chat JSON, call and story history.
+    pub chats: bool,
+    /// Memories, from memories_history.json.
+    pub memories: bool,
+    /// Places visited, from location_history.json.
+    pub location_history: bool,
+    /// Snaps sent and received, from snap_history.json.
+    pub snap_history: bool,
+    /// Resolving chat media references to files in the export.
+    pub media_linking: bool,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl ImportOptions {
+    pub const ALL: Self = Self {
+        chats: true,
+        memories: true,
+        location_history: true,
+        snap_history: true,
+        media_linking: true,
+    };
+    pub const NONE: Self = Self {
+        chats: false,
+        memories: false,
+        location_history: false,
+        snap_history: false,
+        media_linking: false,
+    };
+
+    /// The phases requested here that `done` hasn't covered yet.
+    pub fn remaining(&self, done: &Self) -> Self {
+        Self {
+            chats: self.chats && !done.chats,
+            memories: self.memories && !done.memories,
+            location_history: self.location_history && !done.location_history,
+            snap_history: self.snap_history && !done.snap_history,
+            media_linking: self.media_linking && !done.media_linking,
+        }
+    }
+
+    /// Phases covered by either.
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            chats: self.chats || other.chats,
+            memories: self.memories || other.memories,
+            location_history: self.location_history || other.location_history,
+            snap_history: self.snap_history || other.snap_history,
+            media_linking: self.media_linking || other.media_linking,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::NONE
+    }
+
+    /// Whether any phase that saves messages is included.
+    pub fn has_messages(&self) -> bool {
+        self.chats || self.snap_history
+    }
+}
+
+/// One archive of a (possibly multi-part) zip export.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ExportPart {
+    pub path: PathBuf,
+    /// Whether the archive could be opened and its directory read.
+    pub readable: bool,
+    /// Why the part could not be read, if it couldn't.
+    pub error: Option<String>,
 }
 
 /// Result of validating a Snapchat export's directory structure.
@@ -44,6 +166,23 @@ pub enum ValidationStatus {
     Unknown,
 }
 
+impl ValidationStatus {
+    /// The worse of two statuses; problems found later never upgrade an export.
+    pub fn downgrade(self, other: ValidationStatus) -> ValidationStatus {
+        let rank = |s: &ValidationStatus| match s {
+            ValidationStatus::Valid => 0,
+            ValidationStatus::Unknown => 1,
+            ValidationStatus::Incomplete => 2,
+            ValidationStatus::Corrupted => 3,
+        };
+        if rank(&other) > rank(&self) {
+            other
+        } else {
+            self
+        }
+    }
+}
+
 /// A chat conversation (1:1 or group).
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Conversation {
@@ -52,12 +191,112 @@ pub struct Conversation {
     pub display_name: Option<String>,
     /// List of participant usernames.
     pub participants: Vec<String>,
+    /// Group chat rather than a 1:1 conversation.
+    #[serde(default)]
+    pub is_group: bool,
+    /// How many of `participants`, from the start, the chat's header listed as members. Only
+    /// known while importing.
+    #[serde(skip)]
+    pub listed_members: usize,
     /// Timestamp of the most recent event.
     pub last_event_at: Option<DateTime<Utc>>,
     /// Total number of events in this conversation.
     pub message_count: i32,
     /// Whether any events have linked media files.
     pub has_media: bool,
+    /// Media items referenced by the conversation, including ones whose file is missing.
+    #[serde(default)]
+    pub media_count: i32,
+    /// Combined size of the media files that exist on disk.
+    #[serde(default)]
+    pub media_bytes: i64,
+    #[serde(default)]
+    pub missing_media_count: i32,
+    /// For 1:1 chats, the other person's avatar image from the export.
+    #[serde(default)]
+    pub avatar_path: Option<PathBuf>,
+    /// Stable color to show when there is no avatar image.
+    #[serde(default)]
+    pub avatar_color: Option<String>,
+    /// Kinds of problems the import found in this conversation's data.
+    #[serde(default)]
+    pub anomaly_flags: Vec<AnomalyKind>,
+    /// Messages saved in chat.
+    #[serde(default)]
+    pub saved_count: i32,
+    /// Messages sharing a location.
+    #[serde(default)]
+    pub shared_location_count: i32,
+    /// How much of the conversation's history made it in; None until computed.
+    #[serde(default)]
+    pub completeness: Option<ConversationCompleteness>,
+}
+
+/// The account the export belongs to, so the UI can show its messages as "You".
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct OwnerProfile {
+    pub username: String,
+    pub display_name: Option<String>,
+}
+
+/// Where a conversation's messages were read from.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatSource {
+    /// The html/chat_history pages.
+    Html,
+    /// chat_history.json or snap_history.json.
+    Json,
+}
+
+/// How trustworthy a conversation's imported history is, for a badge in the list.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ConversationCompleteness {
+    /// Share of referenced media whose file was found, 0-100; None without media.
+    pub media_linked_pct: Option<f32>,
+    /// Share of messages with a readable timestamp, 0-100; None without messages.
+    pub timestamps_parsed_pct: Option<f32>,
+    /// Sources that contributed messages, in any import.
+    pub sources: Vec<ChatSource>,
+    /// Problems the import flagged in the conversation.
+    pub anomaly_count: i32,
+}
+
+impl ConversationCompleteness {
+    /// `part` of `whole` as a percentage with one decimal, or None when `whole` is zero.
+    pub fn percent(part: i64, whole: i64) -> Option<f32> {
+        (whole > 0).then(|| (part.clamp(0, whole) as f64 * 1000.0 / whole as f64).round() as f32 / 10.0)
+    }
+}
+
+/// A location shared in a message, for the shared locations map.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SharedLocation {
+    pub event_id: String,
+    pub conversation_id: Option<String>,
+    pub sender: String,
+    pub timestamp: DateTime<Utc>,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+/// Where the account was at a point in time, from json/location_history.json.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LocationPoint {
+    pub timestamp: DateTime<Utc>,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// The part of the file the point came from, e.g. "Latest Location".
+    pub source: String,
+    pub export_id: String,
+}
+
+/// Cached media totals for one conversation.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+pub struct ConversationMediaStats {
+    pub media_count: i32,
+    pub media_bytes: i64,
+    pub missing_media_count: i32,
 }
 
 /// A single chat event (message, snap, media, status change, etc.).
@@ -75,17 +314,43 @@ pub struct Event {
     pub conversation_id: Option<String>,
     /// Text content of the message (if any).
     pub content: Option<String>,
-    /// Event type: TEXT, MEDIA, SNAP, SNAP_VIDEO, NOTE, STICKER, etc.
+    /// Event type: TEXT, MEDIA, SNAP, SNAP_VIDEO, NOTE, STICKER, STORY, CALL_AUDIO, etc.
     pub event_type: String,
     /// JSON metadata (e.g., `{"media_ids": [...], "is_sender": true}`).
     pub metadata: Option<String>,
 }
 
+impl Event {
+    /// Whether the message was saved in chat, from the `saved` metadata flag.
+    pub fn is_saved(&self) -> bool {
+        self.metadata
+            .as_deref()
+            .and_then(|m| serde_json::from_str::<serde_json::Value>(m).ok())
+            .and_then(|v| v.get("saved")?.as_bool())
+            .unwrap_or(false)
+    }
+
+    /// Whether the owner sent this, from the `is_sender` metadata flag.
+    pub fn is_sender(&self) -> bool {
+        self.metadata
+            .as_deref()
+            .and_then(|m| serde_json::from_str::<serde_json::Value>(m).ok())
+            .and_then(|v| v.get("is_sender")?.as_bool())
+            .unwrap_or(false)
+    }
+}
+
 /// A person from friends.json.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Person {
     pub username: String,
     pub display_name: Option<String>,
+    /// Profile picture or Bitmoji found in the export; only set when the file exists.
+    #[serde(default)]
+    pub avatar_path: Option<PathBuf>,
+    /// Stable color derived from the username, for when there is no avatar image.
+    #[serde(default)]
+    pub avatar_color: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -96,6 +361,17 @@ pub enum DownloadStatus {
     Failed,
 }
 
+impl DownloadStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DownloadStatus::Pending => "Pending",
+            DownloadStatus::Downloading => "Downloading",
+            DownloadStatus::Downloaded => "Downloaded",
+            DownloadStatus::Failed => "Failed",
+        }
+    }
+}
+
 /// A saved Snapchat memory.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Memory {
@@ -109,11 +385,17 @@ pub struct Memory {
     pub export_id: String,
     pub download_url: Option<String>,
     pub proxy_url: Option<String>,
+    /// The caption/sticker layer some exports list as a separate entry.
+    #[serde(default)]
+    pub overlay_url: Option<String>,
     pub download_status: DownloadStatus,
+    /// Why the last download attempt failed, while `download_status` is Failed.
+    #[serde(default)]
+    pub failure_reason: Option<String>,
 }
 
 /// Aggregate statistics for an imported export.
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct ExportStats {
     pub total_messages: i32,
     pub total_conversations: i32,
@@ -126,6 +408,24 @@ pub struct ExportStats {
     pub end_date: Option<DateTime<Utc>>,
 }
 
+/// Where the app stands, so the frontend can tell "nothing imported yet" from an error or a
+/// running import.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "state")]
+pub enum AppState {
+    /// No database file exists (creating it at startup failed, or a reset is under way).
+    NoDatabase,
+    /// The database exists but nothing has been imported.
+    EmptyDatabase,
+    ImportInProgress {
+        export_id: String,
+        progress: f32,
+    },
+    Ready {
+        stats: ExportStats,
+    },
+}
+
 /// Real-time progress updates emitted during ingestion.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct IngestionProgress {
@@ -146,6 +446,185 @@ pub struct IngestionResult {
     pub parse_failures: i32,
     pub warnings: Vec<String>,
     pub errors: Vec<String>,
+    /// Wall-clock time per ingestion phase, in pipeline order.
+    #[serde(default)]
+    pub phase_timings: Vec<PhaseTiming>,
+    /// Conversations whose imported data looks wrong.
+    #[serde(default)]
+    pub anomalies: Vec<ConversationAnomaly>,
+    /// People read from friends.json, per key style found in it.
+    #[serde(default)]
+    pub people_by_schema: Vec<FriendsSchemaCount>,
+    /// Messages whose media was found by the Media ID in a file name.
+    #[serde(default)]
+    pub media_linked_by_id: i32,
+    /// Messages whose media was found by file date, the names giving no usable ID.
+    #[serde(default)]
+    pub media_linked_by_date: i32,
+}
+
+/// Key style of friends.json, which changed between export versions.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum FriendsSchema {
+    /// "Friends", "Blocked Users" with "Username" and "Display Name".
+    Classic,
+    /// "friends", "blocked_users" with "username" and "display_name".
+    Lowercase,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FriendsSchemaCount {
+    pub schema: FriendsSchema,
+    pub people: usize,
+}
+
+/// Ways a conversation's imported data can look broken.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AnomalyKind {
+    /// Several messages dated at (or before) the UNIX epoch.
+    EpochTimestamps,
+    /// Messages whose stored timestamp could not be read.
+    UnparsedTimestamps,
+    /// Years without a single message between two active periods.
+    LargeGap,
+    /// Many messages with the same sender, content and timestamp.
+    DuplicateMessages,
+}
+
+impl AnomalyKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnomalyKind::EpochTimestamps => "EpochTimestamps",
+            AnomalyKind::UnparsedTimestamps => "UnparsedTimestamps",
+            AnomalyKind::LargeGap => "LargeGap",
+            AnomalyKind::DuplicateMessages => "DuplicateMessages",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "EpochTimestamps" => Some(AnomalyKind::EpochTimestamps),
+            "UnparsedTimestamps" => Some(AnomalyKind::UnparsedTimestamps),
+            "LargeGap" => Some(AnomalyKind::LargeGap),
+            "DuplicateMessages" => Some(AnomalyKind::DuplicateMessages),
+            _ => None,
+        }
+    }
+}
+
+/// One suspicious finding about a conversation, as stored in `validation_issues`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ConversationAnomaly {
+    pub conversation_id: String,
+    pub kind: AnomalyKind,
+    /// Messages involved, e.g. how many share an epoch timestamp.
+    pub affected: i64,
+    /// Messages checked in the conversation.
+    pub total: i64,
+    pub detail: String,
+}
+
+/// How long one ingestion phase took. `threads` is set for phases that run in parallel.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PhaseTiming {
+    pub phase: String,
+    pub duration_ms: u64,
+    pub threads: Option<usize>,
+}
+
+impl PhaseTiming {
+    pub fn since(phase: &str, start: std::time::Instant, threads: Option<usize>) -> Self {
+        Self {
+            phase: phase.to_string(),
+            duration_ms: start.elapsed().as_millis() as u64,
+            threads,
+        }
+    }
+}
+
+/// What was undone after an ingestion run failed.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct IngestionCleanup {
+    pub events_removed: usize,
+    pub conversations_removed: usize,
+    pub conversations_restored: usize,
+    pub memories_removed: usize,
+    #[serde(default)]
+    pub locations_removed: usize,
+    /// The export row was created by the failed run and deleted again.
+    pub export_removed: bool,
+    pub extraction_removed: bool,
+}
+
+/// Payload of the `ingestion-failed` event.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IngestionFailure {
+    pub export_id: String,
+    pub error: String,
+    pub cleanup: IngestionCleanup,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum IngestionRunStatus {
+    Completed,
+    Failed,
+    /// Stopped by the user; what it wrote was rolled back.
+    Cancelled,
+}
+
+/// One entry of the ingestion history.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IngestionRunRecord {
+    pub id: i64,
+    pub export_id: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub status: IngestionRunStatus,
+    pub error: Option<String>,
+    pub conversations: i32,
+    pub events: i32,
+    pub memories: i32,
+    pub cleanup: Option<IngestionCleanup>,
+    /// Damaged json/ files encountered during the run.
+    #[serde(default)]
+    pub file_issues: Vec<JsonFileIssue>,
+    /// How slash dates were read; None when the export had none.
+    #[serde(default)]
+    pub timestamp_format: Option<TimestampFormatDecision>,
+}
+
+/// How slash dates like `01/02/2023` are read.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimestampFormatHint {
+    #[default]
+    MonthFirst,
+    DayFirst,
+}
+
+/// The slash-date order an import settled on, from a sample of the export's timestamps.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampFormatDecision {
+    pub hint: TimestampFormatHint,
+    pub dates_sampled: usize,
+    /// No date proved the order (or dates proved both), so `hint` is a guess.
+    pub ambiguous: bool,
+}
+
+/// A dry-run sample of what importing an export would produce. Nothing is written to disk.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExportPreview {
+    pub export_id: String,
+    /// Up to `sample_size` parsed events, HTML first then chat_history.json.
+    pub sample_events: Vec<Event>,
+    /// Conversation names seen in the sampled files.
+    pub conversation_names: Vec<String>,
+    /// Event types the parser did not recognize.
+    pub unknown_event_types: Vec<String>,
+    /// Messages whose timestamp could not be parsed, kept at the epoch.
+    pub timestamp_failures: i32,
+    /// Files (or zip entries) that were read for the sample.
+    pub files_sampled: Vec<String>,
+    pub warnings: Vec<String>,
 }
 
 /// Data integrity report for a processed export.
@@ -158,6 +637,49 @@ pub struct ValidationReport {
     pub media_missing: i32,
     pub missing_files: Vec<String>,
     pub warnings: Vec<String>,
+    /// Conversations with missing media: `[(conversation_id, missing_count)]`, worst first.
+    #[serde(default)]
+    pub missing_media_by_conversation: Vec<(String, i32)>,
+    /// Linked files `verify_media` found missing on disk; `missing_files` lists at most the
+    /// first few.
+    #[serde(default)]
+    pub missing_file_count: i32,
+    /// When `verify_media` last checked the files; None until it has run.
+    #[serde(default)]
+    pub media_verified_at: Option<DateTime<Utc>>,
+}
+
+/// Result of the last `verify_media` scan, kept in settings for the validation report.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MediaVerification {
+    pub checked_at: DateTime<Utc>,
+    pub files_checked: i32,
+    pub missing_count: i32,
+    /// The first missing files, in event then memory order.
+    pub missing_files: Vec<String>,
+}
+
+/// How far `verify_media` has got; the `verify-media-progress` event payload.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct MediaVerifyProgress {
+    pub checked: usize,
+    pub total: usize,
+    pub missing: usize,
+    pub done: bool,
+}
+
+/// How far `verify_downloaded_memories` has got, and its counts once `done`; the
+/// `verify-downloads-progress` event payload.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct DownloadVerification {
+    pub checked: usize,
+    pub total: usize,
+    pub ok: usize,
+    /// Put back to Pending because the file is gone.
+    pub missing: usize,
+    /// Put back to Pending because the file is too small to be the photo or video.
+    pub undersized: usize,
+    pub done: bool,
 }
 
 /// A full-text search result.
@@ -171,18 +693,95 @@ pub struct SearchResult {
     pub content: String,
     pub timestamp: DateTime<Utc>,
     pub event_type: String,
+    /// The matching part of a long message, with each match between `SNIPPET_MATCH_START` and
+    /// `SNIPPET_MATCH_END` and cut ends marked with "…".
+    #[serde(default)]
+    pub snippet: String,
+    /// Ranges of the matches in `content` in UTF-16 code units, start inclusive and end
+    /// exclusive, so they index the string as the frontend sees it.
+    #[serde(default)]
+    pub match_ranges: Vec<(usize, usize)>,
 }
 
-/// A media file entry for the gallery view.
+/// Marks where a match starts in `SearchResult::snippet`. Control characters are stripped from
+/// imported text, so neither marker can occur in a message.
+pub const SNIPPET_MATCH_START: &str = "\u{2}";
+/// Marks where a match ends in `SearchResult::snippet`.
+pub const SNIPPET_MATCH_END: &str = "\u{3}";
+
+/// Optional narrowing for message searches.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct MessageSearchFilters {
+    pub conversation_id: Option<String>,
+    /// Sender username.
+    pub sender: Option<String>,
+}
+
+/// One year's matches from `search_messages_on_day`.
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct MediaEntry {
-    pub path: PathBuf,
-    /// "Image" or "Video".
-    pub media_type: String,
-    pub timestamp: Option<DateTime<Utc>>,
-    /// Source: "chat", "memory", or "snap".
-    pub source: String,
+pub struct YearSearchResults {
+    pub year: i32,
+    /// Matches that year; `results` holds at most the first few.
+    pub total: i32,
+    pub results: Vec<SearchResult>,
+}
+
+/// One year of `get_on_this_day`: every memory from the day and its most notable messages.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OnThisDayYear {
+    pub year: i32,
+    pub memories: Vec<Memory>,
+    /// Oldest first; at most the per-year cap, saved and media messages chosen first.
+    pub events: Vec<Event>,
+    /// Messages that day, including those over the cap.
+    pub event_total: i32,
+}
+
+/// A conversation whose resolved name matched a `search_all` query.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConversationMatch {
+    pub id: String,
+    pub display_name: Option<String>,
+    pub message_count: i32,
+    pub last_event_at: Option<DateTime<Utc>>,
+}
+
+/// A person whose username or display name matched a `search_all` query.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PersonMatch {
+    pub username: String,
+    pub display_name: Option<String>,
+    /// The 1:1 conversation with this person, if one exists.
     pub conversation_id: Option<String>,
+    pub message_count: i32,
+}
+
+/// Grouped results for the global search box: conversations and people first, then messages.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SearchAllResults {
+    pub conversations: Vec<ConversationMatch>,
+    pub people: Vec<PersonMatch>,
+    pub messages: Vec<SearchResult>,
+}
+
+/// Order of `get_conversations_page`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConversationSort {
+    /// Most recent message first.
+    #[default]
+    LastActivity,
+    /// Most messages first.
+    MessageCount,
+    /// Alphabetical by the name shown in the list.
+    Name,
+}
+
+/// A page of the conversation list.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConversationPage {
+    pub items: Vec<Conversation>,
+    pub total_count: i32,
+    pub has_more: bool,
 }
 
 /// A paginated page of messages.
@@ -193,6 +792,17 @@ pub struct MessagePage {
     pub has_more: bool,
 }
 
+/// Messages around an anchor message, oldest first.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MessageWindow {
+    #[serde(flatten)]
+    pub page: MessagePage,
+    /// Position of the first message in the conversation.
+    pub offset: i32,
+    /// Position of the anchor in the conversation; it is at `anchor_index - offset` in the page.
+    pub anchor_index: i32,
+}
+
 /// A high-performance, lightweight DTO for gallery entries.
 /// Minimizes IPC overhead by only sending what the UI needs for grid rendering.
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -202,6 +812,72 @@ pub struct MediaStreamEntry {
     pub media_type: String, // "Image" | "Video"
     pub timestamp: DateTime<Utc>,
     pub source: String, // "local" | "cloud"
+    /// Sent or Received; memories are always Sent.
+    pub direction: MediaDirection,
+    /// File size; None when it wasn't cached and the page ran out of time to read it. See
+    /// `get_media_info`.
+    pub size_bytes: Option<u64>,
+    /// The chat the media was sent in; None for memories.
+    #[serde(default)]
+    pub conversation_id: Option<String>,
+    /// Position of the file among its message's media; messages with several files have an
+    /// entry for each. Always 0 for memories.
+    #[serde(default)]
+    pub media_index: i32,
+}
+
+/// How far building the search index for an export has got; also the `search-index-progress`
+/// event payload.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct SearchIndexProgress {
+    pub export_id: String,
+    /// Events looked at so far, whether or not they had text to index.
+    pub indexed: usize,
+    pub total: usize,
+    pub done: bool,
+    pub cancelled: bool,
+}
+
+/// Size of a media file, as cached for media pages.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct MediaInfo {
+    pub path: PathBuf,
+    pub size_bytes: u64,
+}
+
+/// Who media came from. `All` only appears as a filter.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaDirection {
+    #[default]
+    All,
+    Sent,
+    Received,
+}
+
+impl MediaDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MediaDirection::All => "all",
+            MediaDirection::Sent => "sent",
+            MediaDirection::Received => "received",
+        }
+    }
+}
+
+/// Narrows the unified media stream; every field left unset matches everything.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct MediaFilter {
+    /// "local" for chat media or "cloud" for memories.
+    pub source: Option<String>,
+    /// "Image" or "Video".
+    pub media_type: Option<String>,
+    /// Only media sent in this chat, which leaves out every memory.
+    pub conversation_id: Option<String>,
+    /// Earliest timestamp to include.
+    pub after: Option<DateTime<Utc>>,
+    /// Latest timestamp to include.
+    pub before: Option<DateTime<Utc>>,
 }
 
 /// A paginated result for the unified media stream.
@@ -211,3 +887,978 @@ pub struct PaginatedMedia {
     pub total_count: i32,
     pub has_more: bool,
 }
+
+/// One month of the media timeline, for jumping the gallery to a date.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct MediaTimelineMonth {
+    /// `YYYY-MM`.
+    pub month: String,
+    pub count: i32,
+    /// Stream offset of the month's first (newest) item.
+    pub offset: i32,
+}
+
+/// An inclusive time window; either end may be open.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DateRange {
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+impl DateRange {
+    pub fn contains(&self, ts: &DateTime<Utc>) -> bool {
+        self.start.is_none_or(|s| *ts >= s) && self.end.is_none_or(|e| *ts <= e)
+    }
+}
+
+/// Options for exporting a conversation; every field defaults to off.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ExportOptions {
+    /// HTML only: put small images in the page as data URIs instead of linking to the files.
+    #[serde(default)]
+    pub embed_images: bool,
+    /// Only messages from this time on.
+    #[serde(default)]
+    pub after: Option<DateTime<Utc>>,
+    /// Only messages up to this time, inclusive.
+    #[serde(default)]
+    pub before: Option<DateTime<Utc>>,
+    /// Only messages from these usernames; empty for everyone.
+    #[serde(default)]
+    pub senders: Vec<String>,
+    /// Replace every username with a pseudonym ("Person A") that stays the same across the
+    /// export, and leave out display names.
+    #[serde(default)]
+    pub anonymize: bool,
+    /// Replace email addresses and phone numbers in message text.
+    #[serde(default)]
+    pub mask_contact_details: bool,
+    /// Directory media links are made relative to, normally the output file's. Set by the
+    /// exporting command, not by callers.
+    #[serde(skip)]
+    pub link_base: Option<PathBuf>,
+}
+
+/// Progress of `export_all_conversations`; the `export-progress` event payload.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ExportProgress {
+    pub processed: usize,
+    pub total: usize,
+    /// Name of the conversation just written.
+    pub current: Option<String>,
+    pub done: bool,
+}
+
+/// Summary of exporting every conversation to its own file.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BulkExportReport {
+    pub output_dir: PathBuf,
+    pub written: usize,
+    pub failed: usize,
+    /// Conversations that could not be written: `[(conversation_id, error)]`.
+    pub failures: Vec<(String, String)>,
+}
+
+/// Summary of exporting memories to a mapping format.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct MemoryMapExport {
+    /// Memories written as points.
+    pub exported: usize,
+    /// Memories left out because they have no coordinates.
+    pub skipped_without_location: usize,
+}
+
+/// Progress payload for the `gallery-progress` event.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct GalleryProgress {
+    pub processed: usize,
+    pub total: usize,
+    /// Month currently being written, as "YYYY-MM".
+    pub current_month: Option<String>,
+    pub done: bool,
+}
+
+/// Summary of a static memories gallery export.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct GalleryReport {
+    pub output_dir: PathBuf,
+    pub months: usize,
+    /// Memory files copied (or hard-linked) into the gallery.
+    pub files_written: usize,
+    pub thumbnails: usize,
+    /// Memories listed as missing because they were never downloaded or the file is gone.
+    pub missing: usize,
+    /// How metadata scrubbing went for each copied file; empty unless scrubbing was asked for.
+    #[serde(default)]
+    pub scrubbed_files: Vec<ScrubRecord>,
+}
+
+/// What to strip from exported media copies.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ScrubMode {
+    /// Location tags only.
+    Gps,
+    /// Every EXIF tag.
+    AllExif,
+}
+
+/// How scrubbing one exported copy went.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum ScrubOutcome {
+    Scrubbed,
+    /// The file had nothing to strip.
+    Unnecessary,
+    /// Not JPEG, PNG or HEIC; copied unmodified.
+    Unsupported,
+    /// The metadata couldn't be parsed; the file was left out of the export.
+    Failed(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ScrubRecord {
+    /// The exported copy.
+    pub file: PathBuf,
+    pub outcome: ScrubOutcome,
+}
+
+/// Kinds of entities a tag can be applied to.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TagEntityType {
+    Conversation,
+    Event,
+    Memory,
+}
+
+impl TagEntityType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TagEntityType::Conversation => "Conversation",
+            TagEntityType::Event => "Event",
+            TagEntityType::Memory => "Memory",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "Conversation" => Some(TagEntityType::Conversation),
+            "Event" => Some(TagEntityType::Event),
+            "Memory" => Some(TagEntityType::Memory),
+            _ => None,
+        }
+    }
+}
+
+/// A user-defined label such as "college" or "trip-2022".
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+    pub color: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// Number of entities currently carrying this tag.
+    pub usage_count: i32,
+}
+
+/// An entity returned by `get_tagged`, labeled with its type.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "entity_type", content = "entry")]
+pub enum TaggedEntry {
+    Conversation(Conversation),
+    Event(Event),
+    Memory(Memory),
+}
+
+/// A page of tagged entities.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TaggedPage {
+    pub items: Vec<TaggedEntry>,
+    pub total_count: i32,
+    pub has_more: bool,
+}
+
+/// Which database commands are routed to.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum DatabaseSlot {
+    /// The app's own index.db.
+    Live,
+    /// A copied index.db opened read-only for inspection.
+    Snapshot,
+}
+
+/// What one run of the background database maintenance did.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct MaintenanceReport {
+    pub ran_at: Option<DateTime<Utc>>,
+    /// Nothing was done because an import was writing.
+    pub skipped_for_ingestion: bool,
+    pub wal_bytes: u64,
+    /// WAL frames copied back into the database; 0 when the WAL was below the threshold.
+    pub checkpointed_frames: i64,
+    pub connections_checked: u32,
+    pub connections_failed: u32,
+    pub duration_ms: u64,
+}
+
+/// Size and health of the live database.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DatabaseInfo {
+    pub schema_version: i32,
+    pub read_only: bool,
+    pub file_bytes: u64,
+    pub wal_bytes: u64,
+    pub connections: u32,
+    pub idle_connections: u32,
+    pub last_maintenance: Option<MaintenanceReport>,
+}
+
+/// The currently active database, as reported to the frontend.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ActiveDatabase {
+    pub slot: DatabaseSlot,
+    /// Path of the opened snapshot, if one is open (even while Live is active).
+    pub snapshot_path: Option<PathBuf>,
+    pub read_only: bool,
+}
+
+/// One slice of a conversation's timeline for the scrollbar minimap. `end` is exclusive,
+/// except for the last bucket which includes the final message.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DensityBucket {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub count: i32,
+}
+
+/// Bucket size of `get_activity_timeline`. Weeks start on Monday.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimelineBucket {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+/// Messages in one bucket of the activity chart.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ActivityBucket {
+    /// First day of the bucket, `YYYY-MM-DD`.
+    pub bucket_start: String,
+    pub count: i32,
+}
+
+/// Calls with one conversation from talk_history.json; seconds only count calls that logged a
+/// duration.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CallTotal {
+    pub conversation_id: String,
+    pub display_name: Option<String>,
+    pub calls: i32,
+    pub audio_seconds: i64,
+    pub video_seconds: i64,
+    pub total_call_seconds: i64,
+}
+
+/// Message counts by weekday (rows, Monday first) and hour of day (columns), in local time.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct ActivityHeatmap {
+    /// Messages from the account owner.
+    pub sent: [[i32; 24]; 7],
+    pub received: [[i32; 24]; 7],
+    /// Offset from UTC the hours were shifted by.
+    pub timezone_offset_minutes: i32,
+}
+
+/// How quickly one side answered the other. Times are in seconds; None without any replies.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ReplyLatency {
+    /// Who replied: the other participant, or the owner's username (`"me"` when unknown).
+    pub sender: String,
+    pub replies: usize,
+    pub median_seconds: Option<f64>,
+    pub mean_seconds: Option<f64>,
+}
+
+/// The longest stretch without messages.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Silence {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub seconds: i64,
+}
+
+/// Reply times and gaps in a conversation. In group chats only replies to or from the owner
+/// count, one entry per other participant.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ResponseStats {
+    pub conversation_id: String,
+    /// How fast the owner answered.
+    pub owner_replies: ReplyLatency,
+    /// How fast each other participant answered the owner.
+    pub participant_replies: Vec<ReplyLatency>,
+    pub longest_silence: Option<Silence>,
+    /// Most consecutive days on which both the owner and someone else wrote.
+    pub longest_streak_days: i32,
+    pub streak_start: Option<NaiveDate>,
+    /// Only one side ever wrote, so there are no replies to measure.
+    pub one_sided: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum RedactionKind {
+    /// A single message, matched by its content anchor.
+    Event,
+    /// Every message from one username.
+    Sender,
+}
+
+impl RedactionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RedactionKind::Event => "Event",
+            RedactionKind::Sender => "Sender",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "Event" => Some(RedactionKind::Event),
+            "Sender" => Some(RedactionKind::Sender),
+            _ => None,
+        }
+    }
+}
+
+/// A search from the recent list (`name` unset) or one the user saved under a name.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SavedSearch {
+    pub id: i64,
+    pub name: Option<String>,
+    pub query: String,
+    /// Whatever filters the search UI applied, stored as given.
+    pub filters: Option<serde_json::Value>,
+    pub last_used_at: DateTime<Utc>,
+    pub use_count: i64,
+}
+
+/// A tagging detached from row IDs, so it can be carried across a reimport.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TaggingSnapshot {
+    pub tag_name: String,
+    pub tag_color: Option<String>,
+    /// Entity type and anchor; None for a tag that isn't applied to anything.
+    pub entity: Option<(TagEntityType, String)>,
+}
+
+/// Format version written by `export_user_data`.
+pub const USER_DATA_VERSION: u32 = 1;
+
+/// The user's own layer on top of their exports, as a portable file. Taggings and message
+/// redactions are stored by anchor, so they re-resolve against any import of the same data.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UserData {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    #[serde(default)]
+    pub tags: Vec<TaggingSnapshot>,
+    #[serde(default)]
+    pub redactions: Vec<Redaction>,
+    #[serde(default)]
+    pub saved_searches: Vec<SavedSearch>,
+    #[serde(default)]
+    pub settings: BTreeMap<String, String>,
+}
+
+/// What `import_user_data` does when an entry already exists with different contents.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum UserDataConflictPolicy {
+    #[default]
+    KeepExisting,
+    Overwrite,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct UserDataImportSummary {
+    /// Nothing was written; the counts say what an import would do.
+    pub dry_run: bool,
+    pub tags_added: usize,
+    pub tags_updated: usize,
+    pub taggings_added: usize,
+    /// Taggings whose message, memory or conversation isn't in this database.
+    pub taggings_unresolved: usize,
+    pub redactions_added: usize,
+    pub events_redacted: usize,
+    pub saved_searches_added: usize,
+    pub saved_searches_updated: usize,
+    pub settings_added: usize,
+    pub settings_updated: usize,
+    /// Entries that differ from the existing ones and were left alone.
+    pub conflicts_kept: usize,
+}
+
+/// A remembered redaction, re-applied whenever data is imported.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Redaction {
+    pub id: i64,
+    pub kind: RedactionKind,
+    /// The username for `Sender`, the message anchor for `Event`.
+    pub value: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Outcome of linking media from a folder to messages imported without it.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct RelinkSummary {
+    pub linked: usize,
+    /// Messages naming media that the folder doesn't have either.
+    pub still_missing: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct RedactionSummary {
+    pub events_removed: usize,
+    pub rules_added: usize,
+    pub media_overwritten: usize,
+    /// Linked files left alone because they live outside app-managed storage.
+    pub media_skipped: usize,
+}
+
+/// Where an effective storage path came from.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum PathSource {
+    /// Set explicitly through its own setting.
+    Configured,
+    /// Falling back to the pre-split `storage_path` setting.
+    LegacyStoragePath,
+    /// The built-in default location.
+    Default,
+    /// Nothing configured and no default exists.
+    Unset,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ResolvedPath {
+    pub path: Option<String>,
+    pub source: PathSource,
+    pub available_bytes: Option<u64>,
+    pub total_bytes: Option<u64>,
+}
+
+/// Effective locations used for export extraction and memory downloads.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PathsOverview {
+    pub extraction: ResolvedPath,
+    pub downloads: ResolvedPath,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PhraseCount {
+    pub phrase: String,
+    pub count: u32,
+}
+
+/// Most frequent phrases of one length in a conversation. `budget_exhausted` means only the
+/// first `messages_scanned` messages were analysed.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TopPhrases {
+    pub n: usize,
+    pub phrases: Vec<PhraseCount>,
+    pub messages_scanned: usize,
+    pub budget_exhausted: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TermCount {
+    pub term: String,
+    pub count: u32,
+}
+
+/// Most used words and emoji in one conversation, or in all of them when `conversation_id`
+/// is None.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TextAnalytics {
+    pub conversation_id: Option<String>,
+    pub top_words: Vec<TermCount>,
+    pub top_emojis: Vec<TermCount>,
+    pub messages_scanned: usize,
+    /// Messages in scope when this was computed; a cached result is reused while it matches.
+    pub event_count: i64,
+}
+
+/// Why a json/ file in an export could not be read normally.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum JsonFileProblem {
+    /// An HTML page (typically "request expired") saved under a .json name.
+    HtmlErrorPage,
+    /// The document ends early; `at_byte` is the file size.
+    Truncated { at_byte: u64 },
+    /// Neither JSON nor HTML.
+    NotJson,
+}
+
+impl std::fmt::Display for JsonFileProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonFileProblem::HtmlErrorPage => write!(f, "file appears to be an HTML error page"),
+            JsonFileProblem::Truncated { at_byte } => {
+                write!(
+                    f,
+                    "file is truncated at byte {} — the export may have been interrupted",
+                    at_byte
+                )
+            }
+            JsonFileProblem::NotJson => write!(f, "file does not contain JSON"),
+        }
+    }
+}
+
+/// A damaged json/ file and how much of it was still recovered.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct JsonFileIssue {
+    pub file: String,
+    pub problem: JsonFileProblem,
+    pub salvaged_conversations: usize,
+    pub salvaged_events: usize,
+}
+
+impl JsonFileIssue {
+    /// Validation status an export with this file drops to.
+    pub fn status(&self) -> ValidationStatus {
+        match self.problem {
+            JsonFileProblem::Truncated { .. } if self.salvaged_conversations > 0 => ValidationStatus::Incomplete,
+            _ => ValidationStatus::Corrupted,
+        }
+    }
+}
+
+impl std::fmt::Display for JsonFileIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.file, self.problem)?;
+        if self.salvaged_conversations > 0 {
+            write!(
+                f,
+                "; recovered {} conversations ({} messages) before the damage",
+                self.salvaged_conversations, self.salvaged_events
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// How memory downloads reach the network. Stored as JSON under the `network_settings` key.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct NetworkSettings {
+    /// Proxy for `http://` URLs, e.g. `http://proxy.corp:8080`.
+    pub http_proxy: Option<String>,
+    /// Proxy for `https://` URLs.
+    pub https_proxy: Option<String>,
+    /// Use the operating system's proxy configuration when no proxy is set above.
+    pub use_system_proxy: bool,
+    /// Connect timeout, and the longest a download may stall between chunks.
+    pub timeout_secs: u64,
+    pub user_agent: Option<String>,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            http_proxy: None,
+            https_proxy: None,
+            use_system_proxy: true,
+            timeout_secs: 60,
+            user_agent: None,
+        }
+    }
+}
+
+/// Extra ways to recognize renamed exports when scanning a folder. Stored as JSON under the
+/// `export_detection` key and read on every scan, so changes apply without a restart.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct DetectionSettings {
+    /// Case-insensitive globs (`*` and `?`) matched against file and folder names.
+    pub name_patterns: Vec<String>,
+    /// How many folders matching no name rule are checked for `index.html` and `json/`, so a
+    /// huge Downloads folder doesn't turn into thousands of lookups.
+    pub probe_limit: usize,
+}
+
+impl Default for DetectionSettings {
+    fn default() -> Self {
+        Self {
+            name_patterns: Vec::new(),
+            probe_limit: 200,
+        }
+    }
+}
+
+/// How chat media is matched to files whose names carry no Media ID.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct MediaLinkingSettings {
+    /// Regular expressions for the file names of older exports, matched against the name
+    /// without its extension. The first capture group is the media ID.
+    pub filename_patterns: Vec<String>,
+    /// How far a file's modification date may be from a message's time for the file to be
+    /// linked to it by date.
+    pub date_window_minutes: i64,
+}
+
+impl Default for MediaLinkingSettings {
+    fn default() -> Self {
+        Self {
+            // "media~<ID>" and similar, and bare numbers
+            filename_patterns: vec![r"^[A-Za-z_]+~(.+)$".to_string(), r"^(\d+)$".to_string()],
+            date_window_minutes: 60,
+        }
+    }
+}
+
+/// Outcome of fetching a test URL through the configured client.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConnectionTestResult {
+    /// True when the server answered with a success status.
+    pub ok: bool,
+    pub status: Option<u16>,
+    pub latency_ms: u64,
+    pub error: Option<String>,
+}
+
+/// Criteria for selecting memories; every field left unset matches everything.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct MemoryFilter {
+    pub export_id: Option<String>,
+    pub date_range: Option<DateRange>,
+    /// "Image" or "Video".
+    pub media_type: Option<String>,
+    pub download_status: Option<DownloadStatus>,
+    pub has_location: Option<bool>,
+}
+
+/// Where a bulk memory download stands.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum DownloadJobState {
+    Running,
+    Completed,
+    /// Stopped before every item was tried, e.g. the app quit or the disk filled up.
+    Interrupted,
+}
+
+impl DownloadJobState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DownloadJobState::Running => "Running",
+            DownloadJobState::Completed => "Completed",
+            DownloadJobState::Interrupted => "Interrupted",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "Running" => Some(DownloadJobState::Running),
+            "Completed" => Some(DownloadJobState::Completed),
+            "Interrupted" => Some(DownloadJobState::Interrupted),
+            _ => None,
+        }
+    }
+}
+
+/// Where a journaled file move stands.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum FsOperationState {
+    /// Journaled; the file may or may not have moved yet.
+    Pending,
+    /// Moved, and the database points at the new location.
+    Done,
+    /// Undone; the file is back at (or never left) its source.
+    RolledBack,
+    /// Neither the source nor the destination exists any more.
+    Failed,
+}
+
+impl FsOperationState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FsOperationState::Pending => "Pending",
+            FsOperationState::Done => "Done",
+            FsOperationState::RolledBack => "RolledBack",
+            FsOperationState::Failed => "Failed",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "Pending" => Some(FsOperationState::Pending),
+            "Done" => Some(FsOperationState::Done),
+            "RolledBack" => Some(FsOperationState::RolledBack),
+            "Failed" => Some(FsOperationState::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// A move of an indexed file, as recorded in `fs_operations`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FsOperation {
+    pub id: i64,
+    /// What the move is part of, e.g. "consolidate" or "relocate".
+    pub kind: String,
+    pub src: PathBuf,
+    pub dst: PathBuf,
+    pub state: FsOperationState,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Where one media ID turns up at each stage, for working out why a photo is missing.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct MediaIdTrace {
+    pub media_id: String,
+    /// Messages whose chat JSON listed the ID, or that link a file named after it.
+    pub events: Vec<MediaIdEvent>,
+    /// The file the media linker's naming rule finds for the ID in the export's media folders.
+    pub indexed_file: Option<PathBuf>,
+    pub verdict: MediaIdVerdict,
+}
+
+/// A file in an export's media folders that no message or memory links to.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct OrphanedMediaFile {
+    pub path: PathBuf,
+    pub size_bytes: u64,
+}
+
+/// One page of `find_orphaned_media`, sorted by path.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OrphanedMediaPage {
+    pub items: Vec<OrphanedMediaFile>,
+    pub total_count: i32,
+    /// Size of every orphaned file, not just this page's.
+    pub total_bytes: u64,
+    pub has_more: bool,
+}
+
+/// A message that refers to a traced media ID.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct MediaIdEvent {
+    pub event_id: String,
+    pub conversation_id: Option<String>,
+    pub event_type: String,
+    pub timestamp: DateTime<Utc>,
+    /// The ID is in the message's `media_ids`, as opposed to only in a linked file name.
+    pub listed_in_metadata: bool,
+    /// Files linked to the message, and whether each exists now.
+    pub linked_files: Vec<(PathBuf, bool)>,
+}
+
+/// What a media ID trace concluded.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum MediaIdVerdict {
+    /// A message links the file and it exists.
+    Linked,
+    /// A message links the file, but it is gone from disk.
+    LinkedFileMissing,
+    /// The export has the file but no message was linked to it.
+    NotLinked,
+    /// Messages list the ID but the export has no file for it.
+    NoFile,
+    /// The export has the file but no message mentions the ID.
+    Orphaned,
+    /// Nothing knows the ID.
+    Unknown,
+}
+
+/// A long-running command in progress, as listed by `get_active_tasks`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ActiveTask {
+    pub id: u64,
+    pub name: String,
+    pub elapsed_ms: u64,
+    /// Time since the task last reported progress.
+    pub since_heartbeat_ms: u64,
+    pub progress: Option<f32>,
+    pub message: Option<String>,
+    pub cancellable: bool,
+    pub cancel_requested: bool,
+    /// Silent for longer than the watchdog's threshold.
+    pub stalled: bool,
+}
+
+/// Whether commands and database reads are timed. Stored as JSON under the
+/// `performance_tracing` key.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct PerformanceSettings {
+    pub enabled: bool,
+    /// Write calls slower than `slow_threshold_ms` to the log.
+    pub log_slow_calls: bool,
+    pub slow_threshold_ms: u64,
+}
+
+impl Default for PerformanceSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            log_slow_calls: true,
+            slow_threshold_ms: 250,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TraceKind {
+    Command,
+    Query,
+}
+
+/// One timed command or database read.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TraceEntry {
+    pub kind: TraceKind,
+    /// The command name, or the statement name for a read.
+    pub name: String,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: f64,
+    /// Rows returned, when the result is a list.
+    pub rows: Option<usize>,
+}
+
+/// Totals for one command or statement over the recorded trace.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct OperationStats {
+    pub kind: TraceKind,
+    pub name: String,
+    pub calls: usize,
+    pub total_ms: f64,
+    pub max_ms: f64,
+    /// Calls at or over the slow threshold.
+    pub slow_calls: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PerformanceSummary {
+    pub enabled: bool,
+    pub slow_threshold_ms: u64,
+    /// Entries currently in the trace.
+    pub recorded: usize,
+    /// Slowest total first.
+    pub operations: Vec<OperationStats>,
+}
+
+/// What to attach to an issue report.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DiagnosticsBundle {
+    pub app_version: String,
+    pub os: String,
+    pub arch: String,
+    pub log_path: Option<String>,
+    /// None when the database couldn't be opened.
+    pub database: Option<DatabaseInfo>,
+    pub performance: PerformanceSummary,
+}
+
+/// A kind of data the frontend shows, as reported by `data-changed`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum DataDomain {
+    Conversations,
+    Events,
+    Memories,
+    Media,
+    Settings,
+}
+
+/// Payload of the `data-changed` event.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DataChanged {
+    pub domains: Vec<DataDomain>,
+}
+
+/// What startup recovery did with moves left Pending by a crash.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct FsRecoveryReport {
+    /// The file had moved; the database now points at it.
+    pub completed: usize,
+    /// The file hadn't moved (or only partly copied); it stays where the database says.
+    pub rolled_back: usize,
+    /// The file is gone from both places.
+    pub failed: usize,
+}
+
+/// A bulk download of the memories matching `filter`, as recorded in `download_jobs`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DownloadJob {
+    pub id: i64,
+    pub filter: MemoryFilter,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub state: DownloadJobState,
+    /// Memories that still needed downloading when the job started.
+    pub total: i64,
+    pub completed: i64,
+    /// Failures since the job was last started or resumed.
+    pub failed: i64,
+    /// Memories whose file was already on disk, so nothing was fetched.
+    #[serde(default)]
+    pub skipped: i64,
+}
+
+/// Failed memory downloads that share a failure reason.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DownloadFailureGroup {
+    pub reason: String,
+    pub count: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MemoryPage {
+    pub items: Vec<Memory>,
+    pub total_count: i32,
+    pub has_more: bool,
+}
+
+/// A memory with details derived from its local file.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MemoryDetail {
+    pub memory: Memory,
+    pub file_size: Option<u64>,
+    pub mime_type: Option<String>,
+    /// Coordinates formatted for display, e.g. "40.50679° N, 123.99146° W".
+    pub coordinates: Option<String>,
+}
+
+/// Neighbours of a memory by timestamp: `previous` is older, `next` is newer.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AdjacentMemories {
+    pub previous: Option<String>,
+    pub next: Option<String>,
+}
+
+/// Fingerprint of one source file (or aggregated directory) of an export, taken at import time.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ExportArtifact {
+    /// Relative to the export folder, or the full path of a zip part. Directories end in `/`.
+    pub path: String,
+    pub size: u64,
+    pub modified_at: Option<DateTime<Utc>>,
+    /// Content hash, only computed for JSON files.
+    pub hash: Option<String>,
+    pub file_count: u32,
+}
+
+/// What differs between an export's sources now and when it was imported.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ExportChanges {
+    pub export_id: String,
+    /// False when nothing was recorded at import time, so no comparison was possible.
+    pub recorded: bool,
+    pub changed: Vec<String>,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub unchanged: usize,
+}
+
+impl ExportChanges {
+    pub fn has_changes(&self) -> bool {
+        !self.changed.is_empty() || !self.added.is_empty() || !self.removed.is_empty()
+    }
+}
@@ -0,0 +1,110 @@
+//! What the app should show: nothing imported yet, an import under way, or imported data.
+
+use crate::db::DatabaseManager;
+use crate::error::AppResult;
+use crate::models::{AppState, IngestionProgress};
+use std::sync::Mutex;
+
+/// The running import's latest progress, managed by Tauri.
+#[derive(Default)]
+pub struct ImportTracker {
+    current: Mutex<Option<IngestionProgress>>,
+}
+
+impl ImportTracker {
+    /// Record progress of the running import. A "Complete" or "Cancelled" step ends it.
+    pub fn update(&self, progress: &IngestionProgress) {
+        if let Ok(mut current) = self.current.lock() {
+            let finished = matches!(progress.current_step.as_str(), "Complete" | "Cancelled");
+            *current = (!finished).then(|| progress.clone());
+        }
+    }
+
+    /// Forget the running import, e.g. after it failed.
+    pub fn finish(&self) {
+        if let Ok(mut current) = self.current.lock() {
+            *current = None;
+        }
+    }
+
+    pub fn current(&self) -> Option<IngestionProgress> {
+        self.current.lock().ok().and_then(|c| c.clone())
+    }
+}
+
+/// Work out the app state from the live database (None if there is no database file) and the
+/// running import, if any.
+pub fn resolve_app_state(db: Option<&DatabaseManager>, import: Option<IngestionProgress>) -> AppResult<AppState> {
+    if let Some(progress) = import {
+        return Ok(AppState::ImportInProgress {
+            export_id: progress.export_id,
+            progress: progress.progress,
+        });
+    }
+    let Some(db) = db else {
+        return Ok(AppState::NoDatabase);
+    };
+    if db.get_exports()?.is_empty() {
+        return Ok(AppState::EmptyDatabase);
+    }
+    Ok(AppState::Ready {
+        stats: db.get_export_stats(None)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+
+    fn progress(step: &str, progress: f32) -> IngestionProgress {
+        IngestionProgress {
+            export_id: test_fixtures::EXPORT_ID.to_string(),
+            current_step: step.to_string(),
+            progress,
+            message: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_states_across_reset_import_and_ready() {
+        let tracker = ImportTracker::default();
+        assert!(matches!(
+            resolve_app_state(None, tracker.current()).unwrap(),
+            AppState::NoDatabase
+        ));
+
+        // A reset leaves a fresh, empty database behind
+        let db = DatabaseManager::new_in_memory().unwrap();
+        assert!(matches!(
+            resolve_app_state(Some(&db), tracker.current()).unwrap(),
+            AppState::EmptyDatabase
+        ));
+
+        tracker.update(&progress("Parsing Chats", 0.3));
+        test_fixtures::populate_standard(&db).unwrap();
+        match resolve_app_state(Some(&db), tracker.current()).unwrap() {
+            AppState::ImportInProgress { export_id, progress } => {
+                assert_eq!(export_id, test_fixtures::EXPORT_ID);
+                assert_eq!(progress, 0.3);
+            }
+            other => panic!("expected an import in progress, got {:?}", other),
+        }
+
+        tracker.update(&progress("Complete", 1.0));
+        match resolve_app_state(Some(&db), tracker.current()).unwrap() {
+            AppState::Ready { stats } => {
+                assert_eq!(stats.total_messages, test_fixtures::EVENT_COUNT as i32)
+            }
+            other => panic!("expected ready, got {:?}", other),
+        }
+
+        // A failed import doesn't leave the app stuck in the progress screen
+        tracker.update(&progress("Parsing Chats", 0.3));
+        tracker.finish();
+        assert!(matches!(
+            resolve_app_state(Some(&db), tracker.current()).unwrap(),
+            AppState::Ready { .. }
+        ));
+    }
+}
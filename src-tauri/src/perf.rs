@@ -0,0 +1,292 @@
+//! Timings of commands and database reads, kept in memory so a report that the app "feels
+//! slow" can come with real numbers.
+//!
+//! Tracing is opt-in. While it is off, starting a span is a single atomic load and nothing is
+//! recorded. While it is on, every finished span goes into a fixed-size ring buffer, and calls
+//! slower than the threshold are also written to the log when slow-call logging is enabled.
+
+use crate::models::{
+    ActivityHeatmap, Conversation, ConversationPage, MemoryPage, MessagePage, MessageWindow, OperationStats,
+    PaginatedMedia, PerformanceSettings, PerformanceSummary, TraceEntry, TraceKind,
+};
+use chrono::Utc;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// Finished spans kept before the oldest are dropped.
+pub const TRACE_CAPACITY: usize = 1000;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+static TRACE: LazyLock<Trace> = LazyLock::new(|| Trace::new(TRACE_CAPACITY));
+
+/// Apply saved settings; takes effect for spans started afterwards.
+pub fn configure(settings: &PerformanceSettings) {
+    TRACE.configure(settings);
+    ENABLED.store(settings.enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Time a Tauri command until the returned span drops.
+pub fn command(name: &'static str) -> Span {
+    Span::start(TraceKind::Command, name)
+}
+
+/// Time a database read until the returned span drops.
+pub fn query(name: &'static str) -> Span {
+    Span::start(TraceKind::Query, name)
+}
+
+/// The last `n` recorded spans, oldest first.
+pub fn recent(n: usize) -> Vec<TraceEntry> {
+    TRACE.recent(n)
+}
+
+pub fn summary() -> PerformanceSummary {
+    let mut summary = TRACE.summary();
+    summary.enabled = is_enabled();
+    summary
+}
+
+/// Results that can say how many rows they hold, for the trace.
+pub trait TraceRows {
+    fn trace_rows(&self) -> Option<usize> {
+        None
+    }
+}
+
+impl TraceRows for () {}
+
+impl TraceRows for i32 {}
+
+impl TraceRows for Conversation {}
+
+impl TraceRows for ActivityHeatmap {}
+
+impl<T> TraceRows for Vec<T> {
+    fn trace_rows(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<A, T> TraceRows for (A, Vec<T>) {
+    fn trace_rows(&self) -> Option<usize> {
+        Some(self.1.len())
+    }
+}
+
+impl TraceRows for MessageWindow {
+    fn trace_rows(&self) -> Option<usize> {
+        self.page.trace_rows()
+    }
+}
+
+impl TraceRows for MessagePage {
+    fn trace_rows(&self) -> Option<usize> {
+        Some(self.messages.len())
+    }
+}
+
+impl TraceRows for ConversationPage {
+    fn trace_rows(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+impl TraceRows for MemoryPage {
+    fn trace_rows(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+impl TraceRows for PaginatedMedia {
+    fn trace_rows(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+/// A running timing; recorded when dropped. Inert when tracing was off at the start.
+#[must_use = "the span records when it is dropped"]
+pub struct Span(Option<ActiveSpan>);
+
+struct ActiveSpan {
+    kind: TraceKind,
+    name: &'static str,
+    started: Instant,
+    rows: Option<usize>,
+}
+
+impl Span {
+    fn start(kind: TraceKind, name: &'static str) -> Self {
+        if !ENABLED.load(Ordering::Relaxed) {
+            return Span(None);
+        }
+        Span(Some(ActiveSpan {
+            kind,
+            name,
+            started: Instant::now(),
+            rows: None,
+        }))
+    }
+
+    pub fn set_rows(&mut self, rows: Option<usize>) {
+        if let Some(span) = &mut self.0 {
+            span.rows = rows;
+        }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let Some(span) = self.0.take() {
+            TRACE.record(span.kind, span.name, span.started.elapsed(), span.rows);
+        }
+    }
+}
+
+/// A ring buffer of finished spans.
+pub struct Trace {
+    entries: Mutex<VecDeque<TraceEntry>>,
+    capacity: usize,
+    log_slow_calls: AtomicBool,
+    slow_threshold_ms: AtomicU64,
+}
+
+impl Trace {
+    pub fn new(capacity: usize) -> Self {
+        let defaults = PerformanceSettings::default();
+        Self {
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            log_slow_calls: AtomicBool::new(defaults.log_slow_calls),
+            slow_threshold_ms: AtomicU64::new(defaults.slow_threshold_ms),
+        }
+    }
+
+    pub fn configure(&self, settings: &PerformanceSettings) {
+        self.log_slow_calls.store(settings.log_slow_calls, Ordering::Relaxed);
+        self.slow_threshold_ms
+            .store(settings.slow_threshold_ms, Ordering::Relaxed);
+    }
+
+    /// Add a finished span, dropping the oldest when full. Returns whether it was logged as slow.
+    pub fn record(&self, kind: TraceKind, name: &str, elapsed: Duration, rows: Option<usize>) -> bool {
+        let duration_ms = elapsed.as_secs_f64() * 1000.0;
+        let slow = self.log_slow_calls.load(Ordering::Relaxed)
+            && elapsed.as_millis() as u64 >= self.slow_threshold_ms.load(Ordering::Relaxed);
+        if slow {
+            match rows {
+                Some(rows) => log::warn!("Slow {:?} {}: {:.0} ms, {} rows", kind, name, duration_ms, rows),
+                None => log::warn!("Slow {:?} {}: {:.0} ms", kind, name, duration_ms),
+            }
+        }
+
+        let mut entries = self.lock();
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(TraceEntry {
+            kind,
+            name: name.to_string(),
+            started_at: Utc::now() - elapsed,
+            duration_ms,
+            rows,
+        });
+        slow
+    }
+
+    pub fn recent(&self, n: usize) -> Vec<TraceEntry> {
+        let entries = self.lock();
+        entries.iter().skip(entries.len().saturating_sub(n)).cloned().collect()
+    }
+
+    /// Per-operation totals over everything in the buffer, slowest total first.
+    pub fn summary(&self) -> PerformanceSummary {
+        let threshold = self.slow_threshold_ms.load(Ordering::Relaxed) as f64;
+        let entries = self.lock();
+        let mut operations: BTreeMap<(TraceKind, &str), OperationStats> = BTreeMap::new();
+        for entry in entries.iter() {
+            let stats = operations
+                .entry((entry.kind, entry.name.as_str()))
+                .or_insert_with(|| OperationStats {
+                    kind: entry.kind,
+                    name: entry.name.clone(),
+                    calls: 0,
+                    total_ms: 0.0,
+                    max_ms: 0.0,
+                    slow_calls: 0,
+                });
+            stats.calls += 1;
+            stats.total_ms += entry.duration_ms;
+            stats.max_ms = stats.max_ms.max(entry.duration_ms);
+            if entry.duration_ms >= threshold {
+                stats.slow_calls += 1;
+            }
+        }
+        let mut operations: Vec<OperationStats> = operations.into_values().collect();
+        operations.sort_by(|a, b| b.total_ms.total_cmp(&a.total_ms));
+        PerformanceSummary {
+            enabled: false,
+            slow_threshold_ms: threshold as u64,
+            recorded: entries.len(),
+            operations,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<TraceEntry>> {
+        // Entries are pushed whole, so a poisoned buffer is still consistent
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_keeps_only_the_newest_entries() {
+        let trace = Trace::new(3);
+        for name in ["a", "b", "c", "d", "e"] {
+            trace.record(TraceKind::Query, name, Duration::from_millis(1), Some(1));
+        }
+
+        let names: Vec<String> = trace.recent(10).into_iter().map(|e| e.name).collect();
+        assert_eq!(names, ["c", "d", "e"]);
+        assert_eq!(trace.recent(2).len(), 2);
+        assert_eq!(trace.recent(2)[0].name, "d");
+        assert_eq!(trace.summary().recorded, 3);
+    }
+
+    #[test]
+    fn test_calls_over_the_threshold_are_logged_as_slow() {
+        let trace = Trace::new(10);
+        trace.configure(&PerformanceSettings {
+            enabled: true,
+            log_slow_calls: true,
+            slow_threshold_ms: 250,
+        });
+        assert!(!trace.record(TraceKind::Command, "get_conversations", Duration::from_millis(40), None));
+        assert!(trace.record(
+            TraceKind::Query,
+            "search_messages",
+            Duration::from_millis(300),
+            Some(12)
+        ));
+
+        let summary = trace.summary();
+        assert_eq!(summary.operations[0].name, "search_messages");
+        assert_eq!(summary.operations[0].slow_calls, 1);
+        assert_eq!(summary.operations[1].slow_calls, 0);
+
+        trace.configure(&PerformanceSettings {
+            log_slow_calls: false,
+            ..PerformanceSettings::default()
+        });
+        assert!(!trace.record(TraceKind::Query, "search_messages", Duration::from_millis(300), None));
+    }
+}
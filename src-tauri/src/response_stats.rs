@@ -0,0 +1,173 @@
+//! Reply times, silences and streaks in a conversation.
+//!
+//! Messages are walked oldest first. A reply is a message from the owner right after someone
+//! else's, or from someone else right after the owner's, and its latency is measured from the
+//! message it follows. Messages between two other people in a group don't count.
+
+use crate::db::DatabaseManager;
+use crate::error::AppResult;
+use crate::models::{ReplyLatency, ResponseStats, Silence};
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, BTreeSet};
+
+fn latency(sender: String, mut seconds: Vec<f64>) -> ReplyLatency {
+    seconds.sort_by(f64::total_cmp);
+    let median = match seconds.len() {
+        0 => None,
+        n if n % 2 == 1 => Some(seconds[n / 2]),
+        n => Some((seconds[n / 2 - 1] + seconds[n / 2]) / 2.0),
+    };
+    ReplyLatency {
+        sender,
+        replies: seconds.len(),
+        median_seconds: median,
+        mean_seconds: (!seconds.is_empty()).then(|| seconds.iter().sum::<f64>() / seconds.len() as f64),
+    }
+}
+
+/// Stats over `messages` of (timestamp, sender, sent by the owner), oldest first.
+pub fn compute(conversation_id: &str, owner: &str, messages: &[(DateTime<Utc>, String, bool)]) -> ResponseStats {
+    let mut owner_replies = Vec::new();
+    let mut participant_replies: BTreeMap<&str, Vec<f64>> = BTreeMap::new();
+    let mut longest_silence: Option<Silence> = None;
+    let mut owner_days = BTreeSet::new();
+    let mut other_days = BTreeSet::new();
+
+    for (i, (timestamp, sender, sent)) in messages.iter().enumerate() {
+        if *sent {
+            owner_days.insert(timestamp.date_naive());
+        } else {
+            other_days.insert(timestamp.date_naive());
+            participant_replies.entry(sender.as_str()).or_default();
+        }
+        let Some((previous, _, previous_sent)) = i.checked_sub(1).map(|p| &messages[p]) else {
+            continue;
+        };
+        let gap = *timestamp - *previous;
+        if longest_silence.as_ref().is_none_or(|s| gap.num_seconds() > s.seconds) {
+            longest_silence = Some(Silence {
+                start: *previous,
+                end: *timestamp,
+                seconds: gap.num_seconds(),
+            });
+        }
+        let seconds = gap.num_milliseconds() as f64 / 1000.0;
+        match (previous_sent, sent) {
+            (false, true) => owner_replies.push(seconds),
+            (true, false) => participant_replies.entry(sender.as_str()).or_default().push(seconds),
+            _ => {}
+        }
+    }
+
+    // Streaks of days on which both sides wrote
+    let mut longest_streak_days = 0;
+    let mut streak_start = None;
+    let mut run: Option<(chrono::NaiveDate, chrono::NaiveDate, i32)> = None;
+    for day in owner_days.intersection(&other_days) {
+        run = match run {
+            Some((start, last, length)) if last.succ_opt() == Some(*day) => Some((start, *day, length + 1)),
+            _ => Some((*day, *day, 1)),
+        };
+        if let Some((start, _, length)) = run {
+            if length > longest_streak_days {
+                longest_streak_days = length;
+                streak_start = Some(start);
+            }
+        }
+    }
+
+    ResponseStats {
+        conversation_id: conversation_id.to_string(),
+        owner_replies: latency(owner.to_string(), owner_replies),
+        participant_replies: participant_replies
+            .into_iter()
+            .map(|(sender, seconds)| latency(sender.to_string(), seconds))
+            .collect(),
+        longest_silence,
+        longest_streak_days,
+        streak_start,
+        one_sided: owner_days.is_empty() || other_days.is_empty(),
+    }
+}
+
+/// Response stats for one conversation, using the stored owner profile to name the owner.
+pub fn response_stats(db: &DatabaseManager, conversation_id: &str) -> AppResult<ResponseStats> {
+    // Checks the conversation exists
+    db.get_conversation(conversation_id)?;
+    let owner = db
+        .get_owner_profile()?
+        .map(|o| o.username)
+        .unwrap_or_else(|| "me".to_string());
+    let messages = db.get_message_directions(conversation_id)?;
+    Ok(compute(conversation_id, &owner, &messages))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, day, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_latencies_silence_and_streak() {
+        let msg = |t, sender: &str| (t, sender.to_string(), sender == "me");
+        let messages = vec![
+            msg(at(1, 10, 0), "alice"),
+            msg(at(1, 10, 1), "alice"),
+            msg(at(1, 10, 11), "me"),
+            msg(at(1, 10, 41), "alice"),
+            msg(at(2, 9, 0), "me"),
+            msg(at(2, 9, 2), "alice"),
+            msg(at(3, 8, 0), "me"),
+            msg(at(3, 8, 4), "alice"),
+            // A week of silence, then a day only the owner wrote
+            msg(at(10, 8, 0), "me"),
+        ];
+        let stats = compute("alice", "me", &messages);
+        // 10 minutes, then the next mornings (22h19m and 22h58m) and a week later
+        assert_eq!(stats.owner_replies.replies, 4);
+        assert_eq!(stats.owner_replies.median_seconds, Some((80340.0 + 82680.0) / 2.0));
+        let alice = &stats.participant_replies[0];
+        assert_eq!((alice.sender.as_str(), alice.replies), ("alice", 3));
+        assert_eq!(alice.median_seconds, Some(240.0));
+        assert_eq!(alice.mean_seconds, Some((1800.0 + 120.0 + 240.0) / 3.0));
+        let silence = stats.longest_silence.unwrap();
+        assert_eq!((silence.start, silence.end), (at(3, 8, 4), at(10, 8, 0)));
+        assert_eq!(stats.longest_streak_days, 3);
+        assert_eq!(stats.streak_start, Some(at(1, 0, 0).date_naive()));
+        assert!(!stats.one_sided);
+    }
+
+    #[test]
+    fn test_one_sided_and_group_conversations() {
+        let only_them: Vec<_> = (0..3).map(|i| (at(1, 10, i), "bob".to_string(), false)).collect();
+        let stats = compute("bob", "me", &only_them);
+        assert!(stats.one_sided);
+        assert_eq!(stats.owner_replies.replies, 0);
+        assert_eq!(stats.owner_replies.median_seconds, None);
+        assert_eq!(stats.participant_replies[0].mean_seconds, None);
+        assert_eq!(stats.longest_streak_days, 0);
+        assert!(compute("empty", "me", &[]).longest_silence.is_none());
+
+        // Carol answering dave isn't a reply to the owner
+        let group = vec![
+            (at(1, 10, 0), "me".to_string(), true),
+            (at(1, 10, 5), "carol".to_string(), false),
+            (at(1, 10, 6), "dave".to_string(), false),
+            (at(1, 10, 20), "me".to_string(), true),
+            (at(1, 10, 21), "dave".to_string(), false),
+        ];
+        let stats = compute("group", "me", &group);
+        let replies: Vec<(&str, usize)> = stats
+            .participant_replies
+            .iter()
+            .map(|r| (r.sender.as_str(), r.replies))
+            .collect();
+        assert_eq!(replies, [("carol", 1), ("dave", 1)]);
+        assert_eq!(stats.participant_replies[1].median_seconds, Some(60.0));
+        assert_eq!(stats.owner_replies.median_seconds, Some(840.0));
+    }
+}
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use sysinfo::Disks;
 use thiserror::Error;
 
@@ -64,4 +66,73 @@ impl StorageManager {
         }
         Ok(())
     }
+
+    /// Check that files can actually be created in `path`, which `validate_path` can't tell
+    /// (read-only mounts, permission-restricted folders).
+    pub fn probe_writable(path: &Path) -> Result<(), StorageError> {
+        let probe = path.join(format!(".snapdataexplorer-write-test-{}", std::process::id()));
+        fs::write(&probe, b"probe").map_err(|e| StorageError::IoError(format!("Folder is not writable: {}", e)))?;
+        let _ = fs::remove_file(&probe);
+        Ok(())
+    }
+
+    /// Whether `path` resolves to somewhere inside one of `roots`. Nonexistent paths never match.
+    pub fn is_within(path: &Path, roots: &[PathBuf]) -> bool {
+        let Ok(path) = fs::canonicalize(path) else {
+            return false;
+        };
+        roots
+            .iter()
+            .filter_map(|r| fs::canonicalize(r).ok())
+            .any(|root| path.starts_with(root))
+    }
+
+    /// Overwrite a file's contents with zeros, flush to disk, then delete it.
+    pub fn overwrite_and_remove(path: &Path) -> std::io::Result<()> {
+        let len = fs::metadata(path)?.len();
+        {
+            let mut file = fs::OpenOptions::new().write(true).open(path)?;
+            let zeros = vec![0u8; 64 * 1024];
+            let mut remaining = len;
+            while remaining > 0 {
+                let n = remaining.min(zeros.len() as u64) as usize;
+                file.write_all(&zeros[..n])?;
+                remaining -= n as u64;
+            }
+            file.sync_all()?;
+        }
+        fs::remove_file(path)
+    }
+
+    /// MIME type of a media file from its leading bytes, falling back to the extension.
+    pub fn detect_mime(path: &Path) -> Option<&'static str> {
+        let mut head = [0u8; 12];
+        let read = fs::File::open(path).and_then(|mut f| f.read(&mut head)).unwrap_or(0);
+        let head = &head[..read];
+        let sniffed = match head {
+            [0xFF, 0xD8, 0xFF, ..] => Some("image/jpeg"),
+            [0x89, b'P', b'N', b'G', ..] => Some("image/png"),
+            [b'G', b'I', b'F', b'8', ..] => Some("image/gif"),
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P'] => Some("image/webp"),
+            [_, _, _, _, b'f', b't', b'y', b'p', brand @ ..] => Some(match brand {
+                b"qt  " => "video/quicktime",
+                b"heic" | b"heix" | b"mif1" => "image/heic",
+                _ => "video/mp4",
+            }),
+            _ => None,
+        };
+        sniffed.or_else(|| {
+            let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+            Some(match ext.as_str() {
+                "jpg" | "jpeg" => "image/jpeg",
+                "png" => "image/png",
+                "gif" => "image/gif",
+                "webp" => "image/webp",
+                "heic" => "image/heic",
+                "mp4" => "video/mp4",
+                "mov" => "video/quicktime",
+                _ => return None,
+            })
+        })
+    }
 }
@@ -0,0 +1,303 @@
+//! Long-running commands in progress, so the UI can tell a slow command from a stuck one and
+//! ask it to stop.
+//!
+//! A command registers itself for as long as it runs and reports a heartbeat as it makes
+//! progress. A watchdog thread relays every running task every few seconds and flags the ones
+//! that have gone silent for longer than `STALL_THRESHOLD`.
+
+use crate::error::{AppError, AppResult};
+use crate::models::ActiveTask;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a task may go without a heartbeat before it is reported as stalled.
+pub const STALL_THRESHOLD: Duration = Duration::from_secs(30);
+
+/// How often the watchdog relays running tasks and looks for stalled ones.
+pub const WATCHDOG_INTERVAL: Duration = Duration::from_secs(3);
+
+struct TaskEntry {
+    name: String,
+    /// Identifies the work for commands that stop it by what it is rather than by task id.
+    key: Option<String>,
+    started: Instant,
+    last_heartbeat: Instant,
+    progress: Option<f32>,
+    message: Option<String>,
+    /// None when the work has no point at which it can stop early.
+    cancel: Option<Arc<AtomicBool>>,
+    /// Already reported as stalled during the current silence.
+    stall_reported: bool,
+}
+
+impl TaskEntry {
+    fn snapshot(&self, id: u64, now: Instant) -> ActiveTask {
+        let silent = now.duration_since(self.last_heartbeat);
+        ActiveTask {
+            id,
+            name: self.name.clone(),
+            elapsed_ms: now.duration_since(self.started).as_millis() as u64,
+            since_heartbeat_ms: silent.as_millis() as u64,
+            progress: self.progress,
+            message: self.message.clone(),
+            cancellable: self.cancel.is_some(),
+            cancel_requested: self.cancel.as_ref().is_some_and(|c| c.load(Ordering::SeqCst)),
+            stalled: silent > STALL_THRESHOLD,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Tasks {
+    next_id: u64,
+    running: BTreeMap<u64, TaskEntry>,
+}
+
+impl Tasks {
+    fn find(&self, key: &str) -> Option<u64> {
+        self.running
+            .iter()
+            .find(|(_, entry)| entry.key.as_deref() == Some(key))
+            .map(|(id, _)| *id)
+    }
+}
+
+/// Running tasks, managed by Tauri and cloned into the watchdog.
+#[derive(Clone, Default)]
+pub struct TaskRegistry {
+    tasks: Arc<Mutex<Tasks>>,
+}
+
+impl TaskRegistry {
+    /// Register a task for as long as the returned guard lives. A `cancellable` task must check
+    /// `TaskGuard::is_cancelled` at points where it can stop.
+    pub fn register(&self, name: &str, cancellable: bool) -> TaskGuard {
+        self.insert(&mut self.lock(), name, None, cancellable)
+    }
+
+    /// Register a cancellable task under `key`, e.g. the export an import is for. Fails while
+    /// another task holds the same key.
+    pub fn register_keyed(&self, name: &str, key: &str) -> AppResult<TaskGuard> {
+        // Checked and inserted under one lock, so two callers can't both get the key
+        let mut tasks = self.lock();
+        if tasks.find(key).is_some() {
+            return Err(AppError::Validation(format!("{} is already running for {}", name, key)));
+        }
+        Ok(self.insert(&mut tasks, name, Some(key), true))
+    }
+
+    /// The running task registered under `key`.
+    pub fn find(&self, key: &str) -> Option<u64> {
+        self.lock().find(key)
+    }
+
+    pub fn is_running(&self, id: u64) -> bool {
+        self.lock().running.contains_key(&id)
+    }
+
+    fn insert(&self, tasks: &mut Tasks, name: &str, key: Option<&str>, cancellable: bool) -> TaskGuard {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let now = Instant::now();
+        tasks.next_id += 1;
+        let id = tasks.next_id;
+        tasks.running.insert(
+            id,
+            TaskEntry {
+                name: name.to_string(),
+                key: key.map(str::to_string),
+                started: now,
+                last_heartbeat: now,
+                progress: None,
+                message: None,
+                cancel: cancellable.then(|| cancel.clone()),
+                stall_reported: false,
+            },
+        );
+        log::debug!("Task {} started: {}", id, name);
+        TaskGuard {
+            registry: self.clone(),
+            id,
+            cancel,
+        }
+    }
+
+    /// Running tasks, oldest first.
+    pub fn active(&self) -> Vec<ActiveTask> {
+        let now = Instant::now();
+        self.lock()
+            .running
+            .iter()
+            .map(|(id, entry)| entry.snapshot(*id, now))
+            .collect()
+    }
+
+    /// Tasks that went silent for longer than `threshold`. Each silence is reported once; a
+    /// heartbeat ends it.
+    pub fn newly_stalled(&self, threshold: Duration) -> Vec<ActiveTask> {
+        let now = Instant::now();
+        let mut tasks = self.lock();
+        let mut stalled = Vec::new();
+        for (id, entry) in tasks.running.iter_mut() {
+            if !entry.stall_reported && now.duration_since(entry.last_heartbeat) > threshold {
+                entry.stall_reported = true;
+                stalled.push(entry.snapshot(*id, now));
+            }
+        }
+        stalled
+    }
+
+    /// Ask a task to stop at its next opportunity.
+    pub fn cancel(&self, id: u64) -> AppResult<()> {
+        let tasks = self.lock();
+        let entry = tasks
+            .running
+            .get(&id)
+            .ok_or_else(|| AppError::NotFound(format!("task {}", id)))?;
+        let cancel = entry
+            .cancel
+            .as_ref()
+            .ok_or_else(|| AppError::Validation(format!("{} can't be cancelled", entry.name)))?;
+        log::info!("Cancelling task {}: {}", id, entry.name);
+        cancel.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Tasks> {
+        // A panicking task can't leave the map half-updated, so a poisoned lock is still usable
+        self.tasks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A registered task; dropping it marks the task finished.
+pub struct TaskGuard {
+    registry: TaskRegistry,
+    id: u64,
+    cancel: Arc<AtomicBool>,
+}
+
+impl TaskGuard {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Record that the task is making progress, with its latest fraction done and status line.
+    pub fn heartbeat(&self, progress: Option<f32>, message: Option<&str>) {
+        if let Some(entry) = self.registry.lock().running.get_mut(&self.id) {
+            entry.last_heartbeat = Instant::now();
+            entry.stall_reported = false;
+            if progress.is_some() {
+                entry.progress = progress;
+            }
+            if let Some(message) = message {
+                entry.message = Some(message.to_string());
+            }
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        self.registry.lock().running.remove(&self.id);
+        log::debug!("Task {} finished", self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tasks_are_listed_until_their_guard_drops() {
+        let registry = TaskRegistry::default();
+        let search = registry.register("Rebuild search index", true);
+        let gallery = registry.register("Export memories gallery", false);
+        search.heartbeat(Some(0.5), Some("alice"));
+
+        let active = registry.active();
+        assert_eq!(active.len(), 2);
+        assert_eq!(active[0].id, search.id());
+        assert_eq!(active[0].progress, Some(0.5));
+        assert_eq!(active[0].message.as_deref(), Some("alice"));
+        assert!(active[0].cancellable && !active[1].cancellable);
+
+        drop(gallery);
+        assert_eq!(registry.active().len(), 1);
+        drop(search);
+        assert!(registry.active().is_empty());
+    }
+
+    #[test]
+    fn test_silent_tasks_are_reported_stalled_once() {
+        let registry = TaskRegistry::default();
+        let task = registry.register("Refresh media stats", false);
+        assert!(registry.newly_stalled(Duration::from_secs(60)).is_empty());
+
+        std::thread::sleep(Duration::from_millis(20));
+        let stalled = registry.newly_stalled(Duration::from_millis(10));
+        assert_eq!(stalled.len(), 1);
+        assert_eq!(stalled[0].id, task.id());
+        assert!(registry.newly_stalled(Duration::from_millis(10)).is_empty());
+
+        // A heartbeat ends the silence, so a later one is reported again
+        task.heartbeat(None, None);
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(registry.newly_stalled(Duration::from_millis(10)).len(), 1);
+    }
+
+    #[test]
+    fn test_cancel_reaches_cancellable_tasks_only() {
+        let registry = TaskRegistry::default();
+        let search = registry.register("Rebuild search index", true);
+        let stats = registry.register("Refresh media stats", false);
+
+        registry.cancel(search.id()).unwrap();
+        assert!(search.is_cancelled());
+        assert!(registry.active()[0].cancel_requested);
+        assert!(matches!(registry.cancel(stats.id()), Err(AppError::Validation(_))));
+        assert!(!stats.is_cancelled());
+        assert!(matches!(registry.cancel(999), Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_keyed_tasks_are_found_and_unique_while_running() {
+        let registry = TaskRegistry::default();
+        let import = registry.register_keyed("Import", "export_1").unwrap();
+        assert_eq!(registry.find("export_1"), Some(import.id()));
+        assert!(matches!(
+            registry.register_keyed("Import", "export_1"),
+            Err(AppError::Validation(_))
+        ));
+        assert!(registry.active()[0].cancellable);
+
+        let id = import.id();
+        drop(import);
+        assert!(!registry.is_running(id));
+        assert_eq!(registry.find("export_1"), None);
+        assert!(registry.register_keyed("Import", "export_1").is_ok());
+    }
+
+    #[test]
+    fn test_concurrent_keyed_registrations_admit_one() {
+        let registry = TaskRegistry::default();
+        let start = std::sync::Barrier::new(8);
+        let guards: Vec<AppResult<TaskGuard>> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| {
+                    s.spawn(|| {
+                        start.wait();
+                        registry.register_keyed("Import", "export_1")
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(guards.iter().filter(|g| g.is_ok()).count(), 1);
+        assert_eq!(registry.active().len(), 1);
+    }
+}
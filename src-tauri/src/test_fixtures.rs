@@ -0,0 +1,238 @@
+//! Standard small dataset for tests.
+//!
+//! Compiled for unit tests and behind the `test-fixtures` feature so integration tests
+//! and tooling can share it. Everything is deterministic (fixed IDs and timestamps), so
+//! two databases populated with it compare equal.
+
+use crate::db::DatabaseManager;
+use crate::error::AppResult;
+use crate::models::{
+    Conversation, DownloadStatus, Event, ExportSet, ExportSourceType, Memory, Person, ValidationStatus,
+};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use std::path::PathBuf;
+
+pub const EXPORT_ID: &str = "fixture_export";
+pub const OWNER: &str = "me";
+pub const PEOPLE_COUNT: usize = 10;
+pub const CONVERSATION_COUNT: usize = 3;
+pub const EVENT_COUNT: usize = 50;
+pub const MEMORY_COUNT: usize = 5;
+
+/// Conversation IDs, in insertion order. The first two are direct chats, the last a group.
+pub const CONVERSATION_IDS: [&str; CONVERSATION_COUNT] = ["alice", "bob", "group_weekend"];
+
+const PHRASES: &[&str] = &[
+    "hey, are you around later?",
+    "want to grab pizza tonight",
+    "running ten minutes late",
+    "did you see the game",
+    "sending the photos now",
+];
+
+/// Timestamp of the first fixture event; later events follow at fixed intervals.
+pub fn base_time() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2023, 1, 1, 12, 0, 0).unwrap()
+}
+
+pub fn export() -> ExportSet {
+    ExportSet {
+        id: EXPORT_ID.to_string(),
+        source_paths: vec![PathBuf::from("/fixtures/mydata~fixture")],
+        source_type: ExportSourceType::Folder,
+        extraction_path: None,
+        creation_date: Some(base_time()),
+        validation_status: ValidationStatus::Valid,
+        parts: Vec::new(),
+        import_phases: None,
+        ignored_duplicates: Vec::new(),
+        layout: None,
+    }
+}
+
+pub fn people() -> Vec<Person> {
+    let mut people = vec![Person {
+        username: OWNER.to_string(),
+        display_name: Some("Me".to_string()),
+        avatar_path: None,
+        avatar_color: None,
+    }];
+    for name in [
+        "alice", "bob", "carol", "dave", "erin", "frank", "grace", "heidi", "ivan",
+    ] {
+        let mut display = name.to_string();
+        display[..1].make_ascii_uppercase();
+        people.push(Person {
+            username: name.to_string(),
+            display_name: Some(display),
+            avatar_path: None,
+            avatar_color: None,
+        });
+    }
+    people
+}
+
+/// Participants of each fixture conversation, owner excluded.
+fn participants(conversation_id: &str) -> Vec<String> {
+    match conversation_id {
+        "group_weekend" => vec!["carol".to_string(), "dave".to_string(), "erin".to_string()],
+        other => vec![other.to_string()],
+    }
+}
+
+/// 50 events spread over the three conversations (20 / 20 / 10), one hour apart.
+/// Every tenth event is a media message referencing a (non-existent) file. Every eighth,
+/// starting with event 2, was saved in chat and carries an extra export field.
+pub fn events() -> Vec<Event> {
+    (0..EVENT_COUNT)
+        .map(|i| {
+            let conversation_id = match i % 5 {
+                0 | 1 => CONVERSATION_IDS[0],
+                2 | 3 => CONVERSATION_IDS[1],
+                _ => CONVERSATION_IDS[2],
+            };
+            let others = participants(conversation_id);
+            let sender = if i % 2 == 0 {
+                OWNER.to_string()
+            } else {
+                others[i % others.len()].clone()
+            };
+            let is_media = i % 10 == 9;
+            Event {
+                id: format!("fixture_event_{:03}", i),
+                timestamp: base_time() + Duration::hours(i as i64),
+                sender,
+                sender_name: None,
+                media_references: if is_media {
+                    vec![PathBuf::from(format!("/fixtures/media/2023-01-01_MEDIA{:03}.jpg", i))]
+                } else {
+                    Vec::new()
+                },
+                conversation_id: Some(conversation_id.to_string()),
+                content: if is_media {
+                    None
+                } else {
+                    Some(PHRASES[i % PHRASES.len()].to_string())
+                },
+                event_type: if is_media { "MEDIA" } else { "TEXT" }.to_string(),
+                metadata: (i % 8 == 2).then(|| r#"{"saved": true, "extra": {"Screenshot Count": 1}}"#.to_string()),
+            }
+        })
+        .collect()
+}
+
+pub fn conversations() -> Vec<Conversation> {
+    let events = events();
+    CONVERSATION_IDS
+        .iter()
+        .map(|id| {
+            let last_event_at = events
+                .iter()
+                .filter(|e| e.conversation_id.as_deref() == Some(id))
+                .map(|e| e.timestamp)
+                .max();
+            Conversation {
+                id: id.to_string(),
+                display_name: Some(if *id == "group_weekend" {
+                    "Weekend Plans".to_string()
+                } else {
+                    id.to_string()
+                }),
+                participants: participants(id),
+                last_event_at,
+                message_count: 0,
+                has_media: false,
+                media_count: 0,
+                media_bytes: 0,
+                missing_media_count: 0,
+                avatar_path: None,
+                avatar_color: None,
+                is_group: *id == "group_weekend",
+                listed_members: 0,
+                anomaly_flags: Vec::new(),
+                saved_count: 0,
+                shared_location_count: 0,
+                completeness: None,
+            }
+        })
+        .collect()
+}
+
+/// Five memories one day apart; the first two have a local file, the rest are pending downloads.
+pub fn memories() -> Vec<Memory> {
+    (0..MEMORY_COUNT)
+        .map(|i| Memory {
+            id: format!("fixture_memory_{}", i),
+            timestamp: base_time() + Duration::days(i as i64),
+            media_type: if i % 2 == 0 { "Image" } else { "Video" }.to_string(),
+            latitude: Some(40.0 + i as f64 * 0.1),
+            longitude: Some(-74.0),
+            media_path: (i < 2).then(|| PathBuf::from(format!("/fixtures/memories/memory_{}.jpg", i))),
+            export_id: EXPORT_ID.to_string(),
+            download_url: Some(format!("https://example.com/memories/{}", i)),
+            proxy_url: None,
+            overlay_url: None,
+            download_status: if i < 2 {
+                DownloadStatus::Downloaded
+            } else {
+                DownloadStatus::Pending
+            },
+            failure_reason: None,
+        })
+        .collect()
+}
+
+/// Render one fixture conversation as a Snapchat-style `subpage_<id>.html` chat page.
+pub fn subpage_html(conversation_id: &str) -> String {
+    let escape = |s: &str| s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+    let heading = match conversation_id {
+        "group_weekend" => "Group Chat: Weekend Plans".to_string(),
+        other => format!("Chat History with {}", other),
+    };
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{}</title>\n\
+         <style>.rightpanel > div {{ margin: 4px; }} h6 {{ color: #999; }}</style></head>\n\
+         <body><div class=\"leftpanel\"><h1>{}</h1></div>\n<div class=\"rightpanel\">\n",
+        escape(&heading),
+        escape(&heading)
+    );
+    for event in events()
+        .iter()
+        .filter(|e| e.conversation_id.as_deref() == Some(conversation_id))
+    {
+        html.push_str("<div style=\"background: #f2f2f2;\">");
+        html.push_str(&format!("<h4>{}</h4>", escape(&event.sender)));
+        html.push_str(&format!("<span class=\"type\">{}</span>", event.event_type));
+        if let Some(content) = &event.content {
+            html.push_str(&format!("<p>{}</p>", escape(content)));
+        }
+        for media in &event.media_references {
+            html.push_str(&format!("<img src=\"{}\"><br>", media.display()));
+        }
+        html.push_str(&format!(
+            "<h6>{}</h6></div>\n",
+            event.timestamp.format("%Y-%m-%d %H:%M:%S UTC")
+        ));
+    }
+    html.push_str("</div>\n</body></html>\n");
+    html
+}
+
+/// Insert the full standard dataset into `db`.
+pub fn populate_standard(db: &DatabaseManager) -> AppResult<()> {
+    db.insert_export(&export())?;
+    db.insert_people(&people())?;
+    db.batch_insert_conversations(&conversations())?;
+    db.batch_insert_events(&events(), EXPORT_ID)?;
+    db.assign_conversation_exports()?;
+    db.populate_fts_for_export(EXPORT_ID, || false, |_| {})?;
+    db.batch_insert_memories(&memories())?;
+    Ok(())
+}
+
+/// Fresh in-memory database populated with the standard dataset.
+pub fn standard_db() -> DatabaseManager {
+    let db = DatabaseManager::new_in_memory().expect("in-memory database");
+    populate_standard(&db).expect("populate fixtures");
+    db
+}
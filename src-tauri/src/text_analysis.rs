@@ -0,0 +1,391 @@
+//! Word and phrase statistics over message text.
+//!
+//! Text is split into segments at punctuation and emoji, so phrases never span a sentence
+//! break or a "😂". Within a segment, words are runs of letters and digits; apostrophes are
+//! kept inside words ("don't", "it’s") and dropped at the edges.
+//!
+//! Word and emoji rankings split on Unicode word and grapheme boundaries instead, so a
+//! skin-toned or joined emoji counts as one.
+
+use crate::db::DatabaseManager;
+use crate::error::{AppError, AppResult};
+use crate::models::{PhraseCount, TermCount, TextAnalytics, TopPhrases};
+use std::collections::HashMap;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Longest supported phrase length.
+pub const MAX_NGRAM: usize = 3;
+
+/// Stop analysing once this many words have been read; enough for a stable top list.
+pub const DEFAULT_TOKEN_BUDGET: usize = 2_000_000;
+
+/// Words and emoji kept in stored text analytics; `top_n` is capped to this.
+pub const MAX_TOP_TERMS: usize = 200;
+
+const STOPWORDS: &[&str] = &[
+    "a", "about", "after", "all", "also", "am", "an", "and", "any", "are", "as", "at", "be", "been", "but", "by",
+    "can", "could", "did", "do", "does", "for", "from", "had", "has", "have", "he", "her", "him", "his", "how", "i",
+    "i'm", "if", "in", "into", "is", "it", "it's", "its", "just", "me", "my", "no", "not", "of", "on", "or", "our",
+    "out", "she", "so", "than", "that", "the", "their", "them", "then", "there", "they", "this", "to", "up", "us",
+    "was", "we", "were", "what", "when", "which", "who", "will", "with", "would", "you", "your",
+];
+
+pub fn is_stopword(word: &str) -> bool {
+    STOPWORDS.contains(&word)
+}
+
+fn is_apostrophe(c: char) -> bool {
+    c == '\'' || c == '\u{2019}'
+}
+
+/// Lowercased words of `text`, grouped into segments that phrases may not cross.
+pub fn segments(text: &str) -> Vec<Vec<String>> {
+    let mut segments = Vec::new();
+    let mut current: Vec<String> = Vec::new();
+    let mut word = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c.is_alphanumeric() {
+            word.extend(c.to_lowercase());
+            continue;
+        }
+        // Apostrophes only belong to a word when they sit between two word characters
+        if is_apostrophe(c) && !word.is_empty() && chars.peek().is_some_and(|n| n.is_alphanumeric()) {
+            word.push('\'');
+            continue;
+        }
+        if !word.is_empty() {
+            current.push(std::mem::take(&mut word));
+        }
+        if !c.is_whitespace() && !is_apostrophe(c) && !current.is_empty() {
+            segments.push(std::mem::take(&mut current));
+        }
+    }
+    if !word.is_empty() {
+        current.push(word);
+    }
+    if !current.is_empty() {
+        segments.push(current);
+    }
+    segments
+}
+
+/// Counts phrases of a fixed length across many messages, up to a word budget.
+pub struct NgramCounter {
+    n: usize,
+    budget: usize,
+    tokens_seen: usize,
+    counts: HashMap<String, u32>,
+}
+
+impl NgramCounter {
+    /// `n` is clamped to `1..=MAX_NGRAM`.
+    pub fn new(n: usize, budget: usize) -> Self {
+        Self {
+            n: n.clamp(1, MAX_NGRAM),
+            budget,
+            tokens_seen: 0,
+            counts: HashMap::new(),
+        }
+    }
+
+    pub fn exhausted(&self) -> bool {
+        self.tokens_seen >= self.budget
+    }
+
+    /// Add one message. Returns false once the budget is used up and nothing more is counted.
+    pub fn add_text(&mut self, text: &str) -> bool {
+        if self.exhausted() {
+            return false;
+        }
+        for segment in segments(text) {
+            self.tokens_seen += segment.len();
+            for gram in segment.windows(self.n) {
+                // Phrases made only of stopwords ("of the") carry no meaning
+                if gram.iter().all(|w| is_stopword(w)) {
+                    continue;
+                }
+                *self.counts.entry(gram.join(" ")).or_insert(0) += 1;
+            }
+        }
+        !self.exhausted()
+    }
+
+    /// The `k` most frequent phrases, ties broken alphabetically so results are stable.
+    pub fn top(&self, k: usize) -> Vec<PhraseCount> {
+        top_counts(&self.counts, k)
+            .into_iter()
+            .map(|(phrase, count)| PhraseCount { phrase, count })
+            .collect()
+    }
+}
+
+/// The `k` highest counts, ties broken alphabetically.
+fn top_counts(counts: &HashMap<String, u32>, k: usize) -> Vec<(String, u32)> {
+    let mut ranked: Vec<(&String, u32)> = counts.iter().map(|(term, count)| (term, *count)).collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    ranked
+        .into_iter()
+        .take(k)
+        .map(|(term, count)| (term.clone(), count))
+        .collect()
+}
+
+/// Whether a grapheme cluster is an emoji: it holds a pictographic character, or an emoji
+/// presentation selector or keycap that turns a plain character into one.
+pub fn is_emoji(grapheme: &str) -> bool {
+    grapheme.chars().any(|c| {
+        matches!(
+            c,
+            '\u{1F000}'..='\u{1FAFF}'
+                | '\u{2300}'..='\u{23FF}'
+                | '\u{2600}'..='\u{27BF}'
+                | '\u{2B00}'..='\u{2BFF}'
+                | '\u{FE0F}'
+                | '\u{20E3}'
+        )
+    })
+}
+
+/// Counts words (stopwords left out) and emoji across many messages.
+#[derive(Default)]
+pub struct TermCounter {
+    words: HashMap<String, u32>,
+    emojis: HashMap<String, u32>,
+}
+
+impl TermCounter {
+    pub fn add_text(&mut self, text: &str) {
+        for word in text.unicode_words() {
+            if !word.chars().any(char::is_alphabetic) {
+                continue;
+            }
+            let word = word.to_lowercase().replace('\u{2019}', "'");
+            if !is_stopword(&word) {
+                *self.words.entry(word).or_insert(0) += 1;
+            }
+        }
+        for grapheme in text.graphemes(true).filter(|g| is_emoji(g)) {
+            *self.emojis.entry(grapheme.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    pub fn top_words(&self, k: usize) -> Vec<TermCount> {
+        top_terms(&self.words, k)
+    }
+
+    pub fn top_emojis(&self, k: usize) -> Vec<TermCount> {
+        top_terms(&self.emojis, k)
+    }
+}
+
+fn top_terms(counts: &HashMap<String, u32>, k: usize) -> Vec<TermCount> {
+    top_counts(counts, k)
+        .into_iter()
+        .map(|(term, count)| TermCount { term, count })
+        .collect()
+}
+
+/// Top `top_n` words and emoji in the TEXT events of a conversation or of all of them. Results
+/// are cached per conversation and reused until its message count changes.
+pub fn text_analytics(db: &DatabaseManager, conversation_id: Option<&str>, top_n: usize) -> AppResult<TextAnalytics> {
+    let top_n = top_n.clamp(1, MAX_TOP_TERMS);
+    let event_count = db.message_count(conversation_id)?;
+    let mut analytics = match db.get_cached_text_analytics(conversation_id, event_count)? {
+        Some(cached) => cached,
+        None => {
+            let mut counter = TermCounter::default();
+            let messages_scanned = db.for_each_text_event(conversation_id, |text| {
+                counter.add_text(text);
+                true
+            })?;
+            let analytics = TextAnalytics {
+                conversation_id: conversation_id.map(str::to_string),
+                top_words: counter.top_words(MAX_TOP_TERMS),
+                top_emojis: counter.top_emojis(MAX_TOP_TERMS),
+                messages_scanned,
+                event_count,
+            };
+            // A read-only database can still be analysed, just not cached
+            if let Err(e) = db.cache_text_analytics(&analytics) {
+                log::warn!("Could not cache text analytics: {}", e);
+            }
+            analytics
+        }
+    };
+    analytics.top_words.truncate(top_n);
+    analytics.top_emojis.truncate(top_n);
+    Ok(analytics)
+}
+
+/// Top `top_k` phrases of `n` words in a conversation, optionally from one sender only.
+pub fn top_phrases(
+    db: &DatabaseManager,
+    conversation_id: &str,
+    n: usize,
+    top_k: usize,
+    sender: Option<&str>,
+    budget: usize,
+) -> AppResult<TopPhrases> {
+    if !(1..=MAX_NGRAM).contains(&n) {
+        return Err(AppError::Validation(format!(
+            "Phrase length must be between 1 and {}",
+            MAX_NGRAM
+        )));
+    }
+    let mut counter = NgramCounter::new(n, budget);
+    let messages_scanned = db.for_each_message_text(conversation_id, sender, |text| counter.add_text(text))?;
+    Ok(TopPhrases {
+        n,
+        phrases: counter.top(top_k),
+        messages_scanned,
+        budget_exhausted: counter.exhausted(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+
+    #[test]
+    fn test_segments_apostrophes_and_emoji() {
+        assert_eq!(
+            segments("Don’t  worry, I'm on my way😂see you 'soon'"),
+            vec![
+                vec!["don't", "worry"],
+                vec!["i'm", "on", "my", "way"],
+                vec!["see", "you", "soon"],
+            ]
+        );
+        assert!(segments("👍🏽 ... !!").is_empty());
+    }
+
+    #[test]
+    fn test_counter_skips_stopword_phrases_and_respects_budget() {
+        let mut counter = NgramCounter::new(2, 100);
+        counter.add_text("miss you so much");
+        counter.add_text("I miss you");
+        let top = counter.top(10);
+        assert_eq!(
+            top[0],
+            PhraseCount {
+                phrase: "miss you".into(),
+                count: 2
+            }
+        );
+        assert!(!top.iter().any(|p| p.phrase == "you so"));
+
+        let mut small = NgramCounter::new(1, 3);
+        assert!(!small.add_text("one two three"));
+        assert!(!small.add_text("four"));
+        assert_eq!(small.top(10).len(), 3);
+    }
+
+    #[test]
+    fn test_top_phrases_fixture_and_sender_filter() {
+        let db = test_fixtures::standard_db();
+        let all = top_phrases(&db, "alice", 2, 3, None, DEFAULT_TOKEN_BUDGET).unwrap();
+        assert_eq!(all.messages_scanned, 20);
+        assert!(!all.budget_exhausted);
+        assert_eq!(
+            all.phrases,
+            vec![
+                PhraseCount {
+                    phrase: "around later".into(),
+                    count: 10
+                },
+                PhraseCount {
+                    phrase: "grab pizza".into(),
+                    count: 10
+                },
+                PhraseCount {
+                    phrase: "pizza tonight".into(),
+                    count: 10
+                },
+            ]
+        );
+        assert!(!top_phrases(&db, "alice", 2, 50, None, DEFAULT_TOKEN_BUDGET)
+            .unwrap()
+            .phrases
+            .iter()
+            .any(|p| p.phrase == "are you"));
+
+        let alice = top_phrases(&db, "alice", 3, 1, Some("alice"), DEFAULT_TOKEN_BUDGET).unwrap();
+        assert_eq!(alice.messages_scanned, 10);
+        assert_eq!(alice.phrases[0].count, 5);
+
+        // Carol only sent media, so she has no phrases in the group
+        let carol = top_phrases(&db, "group_weekend", 1, 10, Some("carol"), DEFAULT_TOKEN_BUDGET).unwrap();
+        assert!(carol.phrases.is_empty());
+        assert!(top_phrases(&db, "alice", 4, 10, None, DEFAULT_TOKEN_BUDGET).is_err());
+    }
+
+    #[test]
+    fn test_term_counter_words_and_emoji_clusters() {
+        let mut counter = TermCounter::default();
+        counter.add_text("Don’t worry 👍🏽 don't WORRY!! 👨\u{200D}👩\u{200D}👧 the 👍🏽👍");
+        counter.add_text("café 2024 ❤\u{FE0F} 😂😂");
+        assert_eq!(
+            counter.top_words(2),
+            vec![
+                TermCount {
+                    term: "don't".into(),
+                    count: 2
+                },
+                TermCount {
+                    term: "worry".into(),
+                    count: 2
+                },
+            ]
+        );
+        assert!(counter.top_words(10).iter().any(|t| t.term == "café"));
+        assert!(!counter
+            .top_words(10)
+            .iter()
+            .any(|t| t.term == "the" || t.term == "2024"));
+
+        let emojis: Vec<(String, u32)> = counter.top_emojis(10).into_iter().map(|t| (t.term, t.count)).collect();
+        assert_eq!(emojis[0], ("👍🏽".to_string(), 2));
+        assert_eq!(emojis[1], ("😂".to_string(), 2));
+        assert!(emojis.contains(&("👨\u{200D}👩\u{200D}👧".to_string(), 1)));
+        assert!(emojis.contains(&("❤\u{FE0F}".to_string(), 1)));
+        assert!(emojis.contains(&("👍".to_string(), 1)));
+    }
+
+    #[test]
+    fn test_text_analytics_is_cached_until_the_count_changes() {
+        let db = test_fixtures::standard_db();
+        // Only TEXT events count; other events with text are left out
+        let mut status = test_fixtures::events().remove(2);
+        status.id = "status_event".into();
+        status.event_type = "STATUS".into();
+        status.content = Some("zebra zebra zebra zebra".into());
+        db.batch_insert_events(&[status], test_fixtures::EXPORT_ID).unwrap();
+
+        let alice = text_analytics(&db, Some("alice"), 3).unwrap();
+        assert_eq!(alice.top_words.len(), 3);
+        assert_eq!(alice.event_count, 20);
+        let all = text_analytics(&db, None, 500).unwrap();
+        assert!(all.messages_scanned > alice.messages_scanned);
+        assert!(all.top_words.len() <= MAX_TOP_TERMS);
+        assert!(!all.top_words.iter().any(|t| t.term == "zebra"));
+
+        // A stale entry is served while the count matches, and recomputed once it doesn't
+        let mut stale = alice.clone();
+        stale.top_words = vec![TermCount {
+            term: "cached".into(),
+            count: 1,
+        }];
+        db.cache_text_analytics(&stale).unwrap();
+        assert_eq!(
+            text_analytics(&db, Some("alice"), 3).unwrap().top_words[0].term,
+            "cached"
+        );
+        db.redact_events(&["fixture_event_000".to_string()]).unwrap();
+        let fresh = text_analytics(&db, Some("alice"), 3).unwrap();
+        assert_eq!(fresh.event_count, 19);
+        assert_ne!(fresh.top_words[0].term, "cached");
+    }
+}
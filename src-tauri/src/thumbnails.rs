@@ -0,0 +1,260 @@
+//! On-disk thumbnail cache for gallery media.
+//!
+//! Thumbnails are keyed by the source path plus its size and modification time,
+//! so edited or replaced files get a fresh thumbnail automatically. Both the
+//! on-demand `get_thumbnail` command and the background pre-generation task go
+//! through [`ThumbnailCache`], which keeps the two paths consistent.
+
+use crate::error::{AppError, AppResult};
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::UNIX_EPOCH;
+
+/// Longest edge of a generated thumbnail, in pixels.
+pub const THUMBNAIL_SIZE: u32 = 320;
+
+/// File extensions we can decode into thumbnails. Videos are served as-is.
+const SUPPORTED_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "webp"];
+
+/// Outcome of asking the cache for a thumbnail.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ThumbnailOutcome {
+    /// A thumbnail already existed for this exact file version.
+    Cached(PathBuf),
+    /// A new thumbnail was generated and written to the cache.
+    Generated(PathBuf),
+    /// The source file no longer exists.
+    Missing,
+    /// The file type cannot be thumbnailed (e.g. video).
+    Unsupported,
+}
+
+pub struct ThumbnailCache {
+    cache_dir: PathBuf,
+}
+
+impl ThumbnailCache {
+    pub fn new(cache_dir: PathBuf) -> Self {
+        Self { cache_dir }
+    }
+
+    pub fn is_supported(path: &Path) -> bool {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| SUPPORTED_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+    }
+
+    /// Resolve the cache location for `source`, or None if the file is missing.
+    pub fn cache_path_for(&self, source: &Path) -> Option<PathBuf> {
+        let meta = fs::metadata(source).ok()?;
+        let mtime = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let key = format!("{}|{}|{}", source.to_string_lossy(), meta.len(), mtime);
+        Some(self.cache_dir.join(format!("{:016x}.jpg", fnv1a_64(key.as_bytes()))))
+    }
+
+    /// Return the cached thumbnail for `source`, generating it if needed.
+    pub fn get_or_create(&self, source: &Path) -> AppResult<ThumbnailOutcome> {
+        if !Self::is_supported(source) {
+            return Ok(ThumbnailOutcome::Unsupported);
+        }
+        let target = match self.cache_path_for(source) {
+            Some(p) => p,
+            None => return Ok(ThumbnailOutcome::Missing),
+        };
+        if target.exists() {
+            return Ok(ThumbnailOutcome::Cached(target));
+        }
+
+        fs::create_dir_all(&self.cache_dir)?;
+        let img = image::ImageReader::open(source)?
+            .with_guessed_format()?
+            .decode()
+            .map_err(|e| AppError::Parsing(format!("Failed to decode {:?}: {}", source.file_name(), e)))?;
+        let thumb = image::DynamicImage::ImageRgb8(img.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).to_rgb8());
+
+        // Write to a uniquely named temp file first so a crash, or another writer generating the
+        // same thumbnail, never leaves a half-written cache entry
+        let mut tmp = tempfile::NamedTempFile::new_in(&self.cache_dir)?;
+        let mut writer = io::BufWriter::new(tmp.as_file_mut());
+        thumb
+            .write_to(&mut writer, image::ImageFormat::Jpeg)
+            .map_err(|e| AppError::Generic(format!("Failed to write thumbnail: {}", e)))?;
+        // Dropping the writer would flush it but lose the error, e.g. a full disk
+        writer.into_inner().map_err(io::IntoInnerError::into_error)?;
+        tmp.persist(&target).map_err(|e| e.error)?;
+        Ok(ThumbnailOutcome::Generated(target))
+    }
+}
+
+/// Starting state for [`fnv1a_64_update`].
+pub(crate) const FNV1A_64_OFFSET: u64 = 0xcbf29ce484222325;
+
+/// Stable 64-bit FNV-1a hash, used for cache file names.
+fn fnv1a_64(bytes: &[u8]) -> u64 {
+    fnv1a_64_update(FNV1A_64_OFFSET, bytes)
+}
+
+/// Feed more bytes into a running FNV-1a hash, for hashing data in chunks.
+pub(crate) fn fnv1a_64_update(mut hash: u64, bytes: &[u8]) -> u64 {
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Shared state for the background pre-generation task, managed by Tauri.
+#[derive(Default)]
+pub struct ThumbnailJobState {
+    running: AtomicBool,
+    cancel: AtomicBool,
+    /// Number of interactive thumbnail requests currently being served.
+    interactive: AtomicUsize,
+}
+
+impl ThumbnailJobState {
+    /// Mark the job as running. Returns false if one is already in progress.
+    pub fn try_start(&self) -> bool {
+        let started = self
+            .running
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok();
+        if started {
+            self.cancel.store(false, Ordering::SeqCst);
+        }
+        started
+    }
+
+    pub fn finish(&self) {
+        self.running.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    pub fn request_cancel(&self) {
+        self.cancel.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::SeqCst)
+    }
+
+    /// Register an interactive request; background workers pause until the guard drops.
+    pub fn interactive_guard(&self) -> InteractiveGuard<'_> {
+        self.interactive.fetch_add(1, Ordering::SeqCst);
+        InteractiveGuard { state: self }
+    }
+
+    /// Block the calling background worker while interactive requests are in flight.
+    pub fn yield_to_interactive(&self) {
+        while self.interactive.load(Ordering::SeqCst) > 0 && !self.is_cancelled() {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+    }
+}
+
+pub struct InteractiveGuard<'a> {
+    state: &'a ThumbnailJobState,
+}
+
+impl Drop for InteractiveGuard<'_> {
+    fn drop(&mut self) {
+        self.state.interactive.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Progress payload for the `thumbnail-progress` event.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ThumbnailProgress {
+    pub processed: usize,
+    pub total: usize,
+    pub generated: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub done: bool,
+    pub cancelled: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_png(path: &Path) {
+        let img = image::RgbImage::from_pixel(640, 480, image::Rgb([200, 10, 10]));
+        img.save(path).unwrap();
+    }
+
+    #[test]
+    fn test_generate_then_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("2023-01-01_ABC.png");
+        write_png(&src);
+
+        let cache = ThumbnailCache::new(dir.path().join("thumbs"));
+        let first = cache.get_or_create(&src).unwrap();
+        let thumb = match first {
+            ThumbnailOutcome::Generated(p) => p,
+            other => panic!("expected Generated, got {:?}", other),
+        };
+        let (w, h) = image::image_dimensions(&thumb).unwrap();
+        assert_eq!(w.max(h), THUMBNAIL_SIZE);
+
+        assert_eq!(cache.get_or_create(&src).unwrap(), ThumbnailOutcome::Cached(thumb));
+    }
+
+    #[test]
+    fn test_concurrent_generation_leaves_one_complete_thumbnail() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("2023-01-01_ABC.png");
+        write_png(&src);
+
+        let cache = ThumbnailCache::new(dir.path().join("thumbs"));
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| assert!(cache.get_or_create(&src).is_ok()));
+            }
+        });
+        let entries: Vec<PathBuf> = fs::read_dir(dir.path().join("thumbs"))
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        assert_eq!(entries, [cache.cache_path_for(&src).unwrap()]);
+        assert!(image::image_dimensions(&entries[0]).is_ok());
+    }
+
+    #[test]
+    fn test_missing_and_unsupported() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ThumbnailCache::new(dir.path().join("thumbs"));
+        assert_eq!(
+            cache.get_or_create(&dir.path().join("gone.jpg")).unwrap(),
+            ThumbnailOutcome::Missing
+        );
+
+        let video = dir.path().join("clip.mp4");
+        fs::write(&video, b"not really a video").unwrap();
+        assert_eq!(cache.get_or_create(&video).unwrap(), ThumbnailOutcome::Unsupported);
+    }
+
+    #[test]
+    fn test_job_state_single_runner() {
+        let state = ThumbnailJobState::default();
+        assert!(state.try_start());
+        assert!(!state.try_start());
+        state.request_cancel();
+        assert!(state.is_cancelled());
+        state.finish();
+        assert!(state.try_start());
+        assert!(!state.is_cancelled());
+    }
+}
@@ -11,7 +11,7 @@ import { ChillGallery } from "./components/ChillGallery";
 import { Updater } from "./components/Updater";
 import { AboutModal } from "./components/AboutModal";
 import { ToastContainer } from "./components/Toast";
-import { ExportSet, IngestionProgress, IngestionResult } from "./types";
+import { AppState, ExportChanges, ExportSet, IngestionFailure, IngestionProgress, IngestionResult } from "./types";
 import { listen } from "@tauri-apps/api/event";
 import { invoke } from "@tauri-apps/api/core";
 import { useTheme } from "./hooks/useTheme";
@@ -36,16 +36,26 @@ function App() {
 
   const checkData = useCallback(async () => {
     try {
-      const exports = await invoke<ExportSet[]>("get_exports");
-      setHasData(exports.length > 0);
-      if (exports.length > 0) {
-        setCurrentExport(exports[0]);
+      const state = await invoke<AppState>("get_app_state");
+      if (state.state === "ImportInProgress") {
+        setProgress((p) => p ?? {
+          export_id: state.export_id,
+          current_step: "Importing",
+          progress: state.progress,
+          message: "Import in progress...",
+        });
+      }
+      setHasData(state.state === "Ready");
+      if (state.state === "Ready") {
+        const exports = await invoke<ExportSet[]>("get_exports");
+        setCurrentExport(exports[0] ?? null);
       }
     } catch (e) {
       console.error("Failed to check data:", e);
+      addToast("error", `Could not open your data: ${e}`);
       setHasData(false);
     }
-  }, []);
+  }, [addToast]);
 
   useEffect(() => {
     const unlistenProgress = listen<IngestionProgress>("ingestion-progress", (event) => {
@@ -69,11 +79,19 @@ function App() {
       }
     });
 
+    const unlistenFailed = listen<IngestionFailure>("ingestion-failed", (event) => {
+      const f = event.payload;
+      setProgress(null);
+      addToast("error", `Import failed: ${f.error}. Removed ${f.cleanup.events_removed} partially imported messages.`);
+      checkData();
+    });
+
     checkData();
 
     return () => {
       unlistenProgress.then((f) => f());
       unlistenResult.then((f) => f());
+      unlistenFailed.then((f) => f());
     };
   }, [checkData, addToast]);
 
@@ -103,6 +121,21 @@ function App() {
 
   async function handleReimport() {
     try {
+      const exports = await invoke<ExportSet[]>("get_exports");
+      if (exports.length > 0) {
+        const changes = await invoke<ExportChanges>("check_export_changes", { exportId: exports[0].id });
+        const total = changes.changed.length + changes.added.length + changes.removed.length;
+        if (changes.recorded && total === 0) {
+          if (!window.confirm("Nothing has changed since the last import. Reimport anyway?")) return;
+        } else if (total > 0) {
+          const parts = [
+            changes.added.length && `${changes.added.length} new`,
+            changes.changed.length && `${changes.changed.length} changed`,
+            changes.removed.length && `${changes.removed.length} removed`,
+          ].filter(Boolean);
+          addToast("info", `Export files since last import: ${parts.join(", ")}`);
+        }
+      }
       addToast("info", "Reimporting data...");
       setActivePage("dashboard");
       setSelectedConvo(null);
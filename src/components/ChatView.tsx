@@ -1,7 +1,7 @@
 import React, { useState, useEffect, useCallback, useRef, useMemo } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { Virtuoso, VirtuosoHandle } from "react-virtuoso";
-import { Event as Message, MessagePage, MediaViewerItem } from "../types";
+import { Event as Message, ExportFormat, MessagePage, MediaViewerItem } from "../types";
 import { convertFileSrc } from "@tauri-apps/api/core";
 import { save } from "@tauri-apps/plugin-dialog";
 import { Toast } from "../hooks/useToast";
@@ -83,6 +83,14 @@ interface ChatViewProps {
 
 const PAGE_SIZE = 500;
 
+const EXPORT_FORMAT_NAMES: Record<ExportFormat, string> = {
+  text: "Text",
+  json: "JSON",
+  html: "Web page",
+  bundle: "Zip archive",
+};
+const EXPORT_EXTENSIONS: Record<ExportFormat, string> = { text: "txt", json: "json", html: "html", bundle: "zip" };
+
 // Optimized Memoized Message Component
 const MessageItem = React.memo(({
   msg,
@@ -408,20 +416,24 @@ export function ChatView({ conversationId, addToast }: ChatViewProps) {
     }
   }
 
-  async function handleExport(format: "text" | "json") {
+  async function handleExport(format: ExportFormat) {
     setExporting(true);
     try {
-      const ext = format === "json" ? "json" : "txt";
-      const fileName = displayName || conversationId;
+      const ext = EXPORT_EXTENSIONS[format];
+      const suggested = await invoke<string>("suggest_export_path", { conversationId, format }).catch(
+        () => `${displayName || conversationId}.${ext}`
+      );
       const filePath = await save({
-        defaultPath: `${fileName}.${ext}`,
-        filters: [{ name: format === "json" ? "JSON" : "Text", extensions: [ext] }],
+        defaultPath: suggested,
+        filters: [{ name: EXPORT_FORMAT_NAMES[format], extensions: [ext] }],
       });
       if (filePath) {
+        // The save dialog already asked before replacing an existing file
         await invoke("export_conversation", {
           conversationId,
           format,
           outputPath: filePath,
+          overwrite: true,
         });
         addToast("success", `Conversation exported to ${filePath.split("/").pop() || filePath}`);
       }
@@ -521,6 +533,12 @@ export function ChatView({ conversationId, addToast }: ChatViewProps) {
               <button onClick={() => handleExport("json")} className="block w-full text-left px-5 py-3 text-xs font-bold uppercase tracking-widest hover:bg-slate-50 dark:hover:bg-slate-700 dark:text-slate-200 transition-colors">
                 Database (.json)
               </button>
+              <button onClick={() => handleExport("html")} className="block w-full text-left px-5 py-3 text-xs font-bold uppercase tracking-widest hover:bg-slate-50 dark:hover:bg-slate-700 dark:text-slate-200 transition-colors">
+                Web page (.html)
+              </button>
+              <button onClick={() => handleExport("bundle")} className="block w-full text-left px-5 py-3 text-xs font-bold uppercase tracking-widest hover:bg-slate-50 dark:hover:bg-slate-700 dark:text-slate-200 transition-colors">
+                Web page with media (.zip)
+              </button>
             </div>
           </div>
         </div>
@@ -106,7 +106,7 @@ export function ChillGallery({ onExit }: ChillGalleryProps) {
             {/* Immersive Animated Background */}
             <AnimatePresence mode="wait">
                 <motion.div
-                    key={hoveredItem?.id || 'default'}
+                    key={hoveredItem?.path || 'default'}
                     initial={{ opacity: 0 }}
                     animate={{ opacity: 0.25 }}
                     exit={{ opacity: 0 }}
@@ -176,7 +176,7 @@ export function ChillGallery({ onExit }: ChillGalleryProps) {
                     listClassName="grid grid-cols-2 md:grid-cols-3 lg:grid-cols-4 xl:grid-cols-5 2xl:grid-cols-6 gap-6"
                     itemContent={(index, item) => (
                         <GalleryItem
-                            key={item.id}
+                            key={`${item.id}-${item.media_index}`}
                             item={item}
                             index={index}
                             onClick={() => setViewerIndex(index)}
@@ -2,7 +2,7 @@ import React, { useState, useEffect, useMemo, useDeferredValue } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { Virtuoso } from "react-virtuoso";
 import { Conversation } from "../types";
-import { cn } from "../lib/utils";
+import { cn, formatBytes } from "../lib/utils";
 import { ConversationListSkeleton } from "./ui/Skeleton";
 
 type SortOption = "recent" | "oldest" | "most_messages" | "least_messages" | "name_az" | "name_za";
@@ -44,6 +44,14 @@ const ConversationListItem = React.memo(({
       )}>
         {c.display_name || c.id}
       </span>
+      {c.anomaly_flags?.length > 0 && (
+        <span
+          className="text-[10px] font-bold text-amber-500"
+          title={`This conversation may not have imported correctly (${c.anomaly_flags.join(", ")})`}
+        >
+          ⚠
+        </span>
+      )}
       <span className={cn(
         "text-[10px] font-bold px-2 py-0.5 rounded-full min-w-[24px] text-center",
         isSelected
@@ -64,6 +72,15 @@ const ConversationListItem = React.memo(({
         </span>
       )}
     </div>
+
+    {c.media_count > 0 && (
+      <p className="text-[10px] text-surface-400">
+        {c.media_count.toLocaleString()} media · {formatBytes(c.media_bytes)}
+        {c.missing_media_count > 0 && (
+          <span className="text-amber-500"> · {c.missing_media_count.toLocaleString()} missing</span>
+        )}
+      </p>
+    )}
   </button>
 ));
 
@@ -1,7 +1,7 @@
-import { useState, useEffect, useCallback, useRef, useMemo, useDeferredValue } from "react";
+import { useState, useEffect, useCallback, useRef, useMemo } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { VirtuosoGrid } from "react-virtuoso";
-import { MediaStreamEntry, MediaViewerItem, PaginatedMedia } from "../types";
+import { MediaDirection, MediaFilter, MediaStreamEntry, MediaViewerItem, PaginatedMedia } from "../types";
 import { cn } from "../lib/utils";
 import { MediaThumbnail } from "./ui/MediaThumbnail";
 import { MediaViewer } from "./ui/MediaViewer";
@@ -15,7 +15,7 @@ export function GalleryView() {
   const [totalCount, setTotalCount] = useState(0);
   const [viewerIndex, setViewerIndex] = useState(-1);
   const [filter, setFilter] = useState<"all" | "Image" | "Video">("all");
-  const deferredFilter = useDeferredValue(filter);
+  const [direction, setDirection] = useState<MediaDirection>("all");
   const offsetRef = useRef(0);
 
   const loadMedia = useCallback(async (append = false) => {
@@ -29,6 +29,8 @@ export function GalleryView() {
       const data = await invoke<PaginatedMedia>("get_unified_media_stream", {
         limit: 100,
         offset: offsetRef.current,
+        direction,
+        filter: filter === "all" ? null : ({ media_type: filter } satisfies MediaFilter),
       });
       if (append) {
         setMedia(prev => [...prev, ...data.items]);
@@ -44,7 +46,7 @@ export function GalleryView() {
       setLoading(false);
       setLoadingMore(false);
     }
-  }, []);
+  }, [direction, filter]);
 
   useEffect(() => {
     loadMedia();
@@ -56,13 +58,9 @@ export function GalleryView() {
     }
   }, [loadingMore, hasMore, loadMedia]);
 
-  const filtered = useMemo(() =>
-    deferredFilter === "all" ? media : media.filter(m => m.media_type === deferredFilter)
-    , [media, deferredFilter]);
-
   const viewerItems = useMemo(() => 
-    filtered.map((f): MediaViewerItem => ({ ...f, media_path: f.path, media_type: f.media_type }))
-  , [filtered]);
+    media.map((f): MediaViewerItem => ({ ...f, media_path: f.path, media_type: f.media_type }))
+  , [media]);
 
   return (
     <div className="flex-1 flex flex-col bg-zinc-950/20 backdrop-blur-xs h-full overflow-hidden">
@@ -74,24 +72,42 @@ export function GalleryView() {
             Gallery
           </h1>
           <p className="text-sm text-white/40 font-medium mt-1">
-            {totalCount.toLocaleString()} items total • {filtered.length.toLocaleString()} visible
+            {totalCount.toLocaleString()} items total • {media.length.toLocaleString()} loaded
           </p>
         </div>
-        <div className="flex gap-1.5 bg-white/5 border border-white/10 p-1 rounded-2xl backdrop-blur-md">
-          {(["all", "Image", "Video"] as const).map(f => (
-            <button
-              key={f}
-              onClick={() => setFilter(f)}
-              className={cn(
-                "px-5 py-2 rounded-xl text-sm font-bold transition-all",
-                filter === f
-                  ? "bg-purple-600 text-white shadow-lg shadow-purple-600/20"
-                  : "text-white/40 hover:text-white/60 hover:bg-white/5"
-              )}
-            >
-              {f === "all" ? "All" : f === "Image" ? "Photos" : "Videos"}
-            </button>
-          ))}
+        <div className="flex gap-3">
+          <div className="flex gap-1.5 bg-white/5 border border-white/10 p-1 rounded-2xl backdrop-blur-md">
+            {(["all", "Image", "Video"] as const).map(f => (
+              <button
+                key={f}
+                onClick={() => setFilter(f)}
+                className={cn(
+                  "px-5 py-2 rounded-xl text-sm font-bold transition-all",
+                  filter === f
+                    ? "bg-purple-600 text-white shadow-lg shadow-purple-600/20"
+                    : "text-white/40 hover:text-white/60 hover:bg-white/5"
+                )}
+              >
+                {f === "all" ? "All" : f === "Image" ? "Photos" : "Videos"}
+              </button>
+            ))}
+          </div>
+          <div className="flex gap-1.5 bg-white/5 border border-white/10 p-1 rounded-2xl backdrop-blur-md">
+            {(["all", "sent", "received"] as const).map(d => (
+              <button
+                key={d}
+                onClick={() => setDirection(d)}
+                className={cn(
+                  "px-5 py-2 rounded-xl text-sm font-bold transition-all",
+                  direction === d
+                    ? "bg-purple-600 text-white shadow-lg shadow-purple-600/20"
+                    : "text-white/40 hover:text-white/60 hover:bg-white/5"
+                )}
+              >
+                {d === "all" ? "Everyone" : d === "sent" ? "Sent" : "Received"}
+              </button>
+            ))}
+          </div>
         </div>
       </header>
 
@@ -100,7 +116,7 @@ export function GalleryView() {
         <div className="flex-1 flex items-center justify-center">
           <Loader2 className="w-10 h-10 text-purple-500 animate-spin" />
         </div>
-      ) : filtered.length === 0 ? (
+      ) : media.length === 0 ? (
         <div className="flex-1 flex items-center justify-center flex-col gap-6 text-center animate-in fade-in zoom-in">
           <div className="w-24 h-24 rounded-3xl bg-white/5 flex items-center justify-center border border-white/10 shadow-2xl">
             <ImageIcon className="w-12 h-12 text-white/20" />
@@ -116,12 +132,12 @@ export function GalleryView() {
         <div className="flex-1 px-8 pb-8 overflow-hidden">
           <VirtuosoGrid
             style={{ height: "100%" }}
-            totalCount={filtered.length}
+            totalCount={media.length}
             overscan={400}
             listClassName="grid grid-cols-2 sm:grid-cols-3 md:grid-cols-4 lg:grid-cols-5 xl:grid-cols-6 2xl:grid-cols-8 gap-4 pb-10"
             endReached={loadMore}
             itemContent={(index) => {
-              const item = filtered[index];
+              const item = media[index];
               if (!item) return null;
               return (
                 <MediaThumbnail
@@ -16,7 +16,7 @@ import {
 import { invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
 import { open } from '@tauri-apps/plugin-dialog';
-import { Memory, DownloadStatus, DownloadProgress, DiskSpaceInfo } from '../types';
+import { Memory, DownloadStatus, DownloadProgress, DiskSpaceInfo, DownloadJob } from '../types';
 import { MediaThumbnail } from './ui/MediaThumbnail';
 import { MediaViewer } from './ui/MediaViewer';
 import { cn } from '../lib/utils';
@@ -32,6 +32,7 @@ export const MemoriesView: React.FC = () => {
     const [filterStatus, setFilterStatus] = useState<DownloadStatus | 'All'>('All');
     const [selectedIds, setSelectedIds] = useState<Set<string>>(new Set());
     const [progress, setProgress] = useState<Record<string, DownloadProgress>>({});
+    const [interruptedJob, setInterruptedJob] = useState<DownloadJob | null>(null);
 
     // Viewer state
     const [viewerIndex, setViewerIndex] = useState<number>(-1);
@@ -46,6 +47,9 @@ export const MemoriesView: React.FC = () => {
             setMemories(allMemories);
             setStoragePath(savedPath);
 
+            const jobs = await invoke<DownloadJob[]>("get_download_jobs");
+            setInterruptedJob(jobs[0]?.state === "Interrupted" ? jobs[0] : null);
+
             if (savedPath) {
                 const info = await invoke<DiskSpaceInfo>("check_disk_space", { path: savedPath });
                 setDiskInfo(info);
@@ -72,8 +76,13 @@ export const MemoriesView: React.FC = () => {
             }
         });
 
+        const unlistenInterrupted = listen<DownloadJob>("download-job-interrupted", (event) => {
+            setInterruptedJob(event.payload);
+        });
+
         return () => {
             unlisten.then(f => f());
+            unlistenInterrupted.then(f => f());
         };
     }, [loadData]);
 
@@ -113,6 +122,16 @@ export const MemoriesView: React.FC = () => {
         }
     };
 
+    const resumeJob = async (job: DownloadJob) => {
+        setInterruptedJob(null);
+        try {
+            await invoke("resume_download_job", { jobId: job.id });
+        } catch (e) {
+            console.error("Resuming downloads failed:", e);
+        }
+        loadData();
+    };
+
     const filteredMemories = memories.filter(m => {
         const matchesSearch = searchQuery === "" || m.id.toLowerCase().includes(searchQuery.toLowerCase());
         const matchesFilter = filterStatus === 'All' || m.download_status === filterStatus;
@@ -166,6 +185,22 @@ export const MemoriesView: React.FC = () => {
                 </div>
             </header>
 
+            {interruptedJob && (
+                <div className="flex items-center justify-between p-4 rounded-xl bg-amber-500/10 border border-amber-500/20">
+                    <p className="text-sm text-amber-300 font-medium">
+                        A download was interrupted after {interruptedJob.completed} of {interruptedJob.total} memories. Resume downloads?
+                    </p>
+                    <div className="flex gap-2">
+                        <Button variant="outline" onClick={() => setInterruptedJob(null)} className="bg-white/5 border-white/10">
+                            Not now
+                        </Button>
+                        <Button onClick={() => resumeJob(interruptedJob)}>
+                            Resume
+                        </Button>
+                    </div>
+                </div>
+            )}
+
             {/* Main Grid */}
             <div className="flex-1 grid grid-cols-12 gap-8 overflow-hidden">
 
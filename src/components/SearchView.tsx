@@ -1,8 +1,8 @@
 import { useState, useCallback } from "react";
 import { invoke } from "@tauri-apps/api/core";
-import { SearchResult } from "../types";
+import { SearchAllResults } from "../types";
 import { Toast } from "../hooks/useToast";
-import { Search, Loader2, MessageSquare, Calendar, User, ArrowRight, AlertCircle } from "lucide-react";
+import { Search, Loader2, MessageSquare, Calendar, User, Users, ArrowRight, AlertCircle } from "lucide-react";
 import { motion, AnimatePresence } from "framer-motion";
 
 interface SearchViewProps {
@@ -10,9 +10,11 @@ interface SearchViewProps {
   addToast: (type: Toast["type"], message: string) => void;
 }
 
+const EMPTY_RESULTS: SearchAllResults = { conversations: [], people: [], messages: [] };
+
 export function SearchView({ onNavigateToChat, addToast }: SearchViewProps) {
   const [query, setQuery] = useState("");
-  const [results, setResults] = useState<SearchResult[]>([]);
+  const [results, setResults] = useState<SearchAllResults>(EMPTY_RESULTS);
   const [loading, setLoading] = useState(false);
   const [hasSearched, setHasSearched] = useState(false);
   const [searchError, setSearchError] = useState<string | null>(null);
@@ -23,7 +25,7 @@ export function SearchView({ onNavigateToChat, addToast }: SearchViewProps) {
     setHasSearched(true);
     setSearchError(null);
     try {
-      const data = await invoke<SearchResult[]>("search_messages", {
+      const data = await invoke<SearchAllResults>("search_all", {
         query: query.trim(),
         limit: 100,
       });
@@ -31,13 +33,15 @@ export function SearchView({ onNavigateToChat, addToast }: SearchViewProps) {
     } catch (e) {
       const msg = String(e);
       setSearchError(msg.includes("fts5") ? "Search query contains special characters. Try simpler keywords." : `Search failed: ${msg}`);
-      setResults([]);
+      setResults(EMPTY_RESULTS);
       addToast("error", "Search failed. Try different keywords.");
     } finally {
       setLoading(false);
     }
   }, [query, addToast]);
 
+  const totalResults = results.conversations.length + results.people.length + results.messages.length;
+
   function handleKeyDown(e: React.KeyboardEvent) {
     if (e.key === "Enter") {
       handleSearch();
@@ -103,7 +107,7 @@ export function SearchView({ onNavigateToChat, addToast }: SearchViewProps) {
                 <Loader2 className="w-12 h-12 text-brand-500 animate-spin" />
                 <p className="text-surface-400 font-bold animate-pulse">Scanning Archive Database...</p>
               </motion.div>
-            ) : hasSearched && results.length === 0 && !searchError ? (
+            ) : hasSearched && totalResults === 0 && !searchError ? (
               <motion.div 
                 key="no-results"
                 initial={{ opacity: 0, y: 10 }}
@@ -143,12 +147,63 @@ export function SearchView({ onNavigateToChat, addToast }: SearchViewProps) {
                 animate={{ opacity: 1 }}
                 className="space-y-4 pb-20"
               >
+                {results.people.length > 0 && (
+                  <section className="mb-8">
+                    <p className="text-xs font-black text-surface-400 uppercase tracking-widest mb-3 px-1">People</p>
+                    <div className="grid grid-cols-1 sm:grid-cols-2 gap-3">
+                      {results.people.map((person) => (
+                        <button
+                          key={person.username}
+                          disabled={!person.conversation_id}
+                          onClick={() => person.conversation_id && onNavigateToChat(person.conversation_id)}
+                          className="text-left bg-white dark:bg-surface-900 border border-surface-200 dark:border-surface-800 rounded-2xl p-4 hover:border-brand-500/50 transition-all disabled:cursor-default flex items-center gap-3"
+                        >
+                          <div className="w-10 h-10 rounded-xl bg-brand-50 dark:bg-brand-900/30 flex items-center justify-center text-brand-600 dark:text-brand-400">
+                            <User className="w-5 h-5" />
+                          </div>
+                          <div className="min-w-0">
+                            <h4 className="font-bold text-surface-900 dark:text-white truncate">{person.display_name || person.username}</h4>
+                            <p className="text-[10px] font-black text-surface-400 uppercase tracking-tighter">
+                              @{person.username} · {person.message_count.toLocaleString()} messages
+                            </p>
+                          </div>
+                        </button>
+                      ))}
+                    </div>
+                  </section>
+                )}
+
+                {results.conversations.length > 0 && (
+                  <section className="mb-8">
+                    <p className="text-xs font-black text-surface-400 uppercase tracking-widest mb-3 px-1">Conversations</p>
+                    <div className="grid grid-cols-1 sm:grid-cols-2 gap-3">
+                      {results.conversations.map((convo) => (
+                        <button
+                          key={convo.id}
+                          onClick={() => onNavigateToChat(convo.id)}
+                          className="text-left bg-white dark:bg-surface-900 border border-surface-200 dark:border-surface-800 rounded-2xl p-4 hover:border-brand-500/50 transition-all flex items-center gap-3"
+                        >
+                          <div className="w-10 h-10 rounded-xl bg-brand-50 dark:bg-brand-900/30 flex items-center justify-center text-brand-600 dark:text-brand-400">
+                            <Users className="w-5 h-5" />
+                          </div>
+                          <div className="min-w-0">
+                            <h4 className="font-bold text-surface-900 dark:text-white truncate">{convo.display_name || convo.id}</h4>
+                            <p className="text-[10px] font-black text-surface-400 uppercase tracking-tighter">
+                              {convo.message_count.toLocaleString()} messages
+                            </p>
+                          </div>
+                        </button>
+                      ))}
+                    </div>
+                  </section>
+                )}
+
                 <div className="flex justify-between items-center mb-6 px-1">
                   <p className="text-xs font-black text-surface-400 uppercase tracking-widest">
-                    Found {results.length.toLocaleString()} matching events
+                    Found {results.messages.length.toLocaleString()} matching events
                   </p>
                 </div>
-                {results.map((result, i) => (
+                {results.messages.map((result, i) => (
                   <motion.button
                     initial={{ opacity: 0, y: 10 }}
                     animate={{ opacity: 1, y: 0 }}
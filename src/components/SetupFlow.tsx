@@ -1,12 +1,20 @@
 import { useState, useEffect, useRef } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { open } from "@tauri-apps/plugin-dialog";
-import { ExportSet, IngestionProgress, IngestionResult } from "../types";
+import { ExportSet, ImportOptions, IngestionProgress, IngestionResult } from "../types";
 import { listen } from "@tauri-apps/api/event";
 import { Toast } from "../hooks/useToast";
 import { Card, Button, Badge, GhostLogo } from "./ui";
 import { cn } from "../lib/utils";
 
+const IMPORT_PHASES: { key: keyof ImportOptions; label: string }[] = [
+  { key: "chats", label: "Chats" },
+  { key: "snap_history", label: "Snap history" },
+  { key: "memories", label: "Memories" },
+  { key: "location_history", label: "Location history" },
+  { key: "media_linking", label: "Link media" },
+];
+
 interface SetupFlowProps {
   onComplete: () => void;
   progress: IngestionProgress | null;
@@ -18,6 +26,13 @@ export function SetupFlow({ onComplete, progress, addToast }: SetupFlowProps) {
   const [loading, setLoading] = useState(false);
   const [error, setError] = useState<string | null>(null);
   const [importResult, setImportResult] = useState<IngestionResult | null>(null);
+  const [options, setOptions] = useState<ImportOptions>({
+    chats: true,
+    memories: true,
+    location_history: true,
+    snap_history: true,
+    media_linking: true,
+  });
   const scanInFlight = useRef(false);
 
   useEffect(() => {
@@ -91,8 +106,20 @@ export function SetupFlow({ onComplete, progress, addToast }: SetupFlowProps) {
   async function handleProcess(exp: ExportSet) {
     setError(null);
     setImportResult(null);
+    const badParts = (exp.parts ?? []).filter((p) => !p.readable);
+    let skipUnreadableParts = false;
+    if (badParts.length > 0) {
+      const names = badParts.map((p) => p.path.split(/[\\/]/).pop()).join(", ");
+      skipUnreadableParts = window.confirm(
+        `These parts of the export could not be read: ${names}.\n\nRe-downloading them is recommended. Import the remaining parts anyway?`
+      );
+      if (!skipUnreadableParts) {
+        setError(`Re-download the unreadable export parts (${names}) and scan again.`);
+        return;
+      }
+    }
     try {
-      await invoke("process_export", { export: exp });
+      await invoke("process_export", { export: exp, skipUnreadableParts, options });
     } catch (e) {
       setError(friendlyError(String(e)));
       addToast("error", "Import failed. Check the error above for details.");
@@ -307,55 +334,77 @@ export function SetupFlow({ onComplete, progress, addToast }: SetupFlowProps) {
                     <p className="font-bold tracking-widest uppercase text-xs">Accessing File System...</p>
                   </div>
                 ) : detected.length > 0 ? (
-                  <div className="max-h-[380px] overflow-y-auto pr-2 custom-scrollbar space-y-3 p-1">
-                    {detected.map((exp) => (
-                      <Card
-                        key={exp.id}
-                        variant="glass"
-                        padding="md"
-                        className="group hover:border-brand-500/50 hover:bg-brand-500/5 transition-all duration-300 flex items-center justify-between border-surface-800 backdrop-blur-xs"
-                      >
-                        <div className="flex items-center gap-5 min-w-0">
-                          <div className="w-14 h-14 bg-surface-800 rounded-2xl flex items-center justify-center group-hover:bg-brand-500/20 group-hover:text-brand-400 transition-colors shrink-0">
-                            {exp.source_type === "Zip" ? (
-                              <svg className="w-7 h-7" fill="none" viewBox="0 0 24 24" stroke="currentColor">
-                                <path strokeLinecap="round" strokeLinejoin="round" strokeWidth={1.5} d="M5 8h14M5 8a2 2 0 110-4h14a2 2 0 110 4M5 8v10a2 2 0 002 2h10a2 2 0 002-2V8m-9 4h4" />
-                              </svg>
-                            ) : (
-                              <svg className="w-7 h-7" fill="none" viewBox="0 0 24 24" stroke="currentColor">
-                                <path strokeLinecap="round" strokeLinejoin="round" strokeWidth={1.5} d="M3 7v10a2 2 0 002 2h14a2 2 0 002-2V9a2 2 0 00-2-2h-6l-2-2H5a2 2 0 00-2 2z" />
-                              </svg>
-                            )}
-                          </div>
-                          <div className="min-w-0">
-                            <h4 className="font-bold text-white group-hover:text-brand-400 transition-colors truncate">
-                              {exp.id}
-                            </h4>
-                            <p className="text-[10px] text-surface-500 font-mono mt-1 truncate max-w-[300px]" title={exp.source_paths.join(', ')}>
-                              {exp.source_paths.length > 1
-                                ? `${exp.source_paths.length} components detected`
-                                : exp.source_paths[0]}
-                            </p>
-                            <div className="flex gap-2 mt-2">
-                              <Badge variant={exp.validation_status === "Valid" ? "success" : "warning"} size="sm">
-                                {exp.validation_status}
-                              </Badge>
-                              <Badge variant="default" size="sm" className="opacity-60 text-[10px]">
-                                {exp.source_type.toUpperCase()} {exp.source_paths.length > 1 && `(${exp.source_paths.length} PARTS)`}
-                              </Badge>
+                  <>
+                    <div className="flex flex-wrap items-center gap-4 px-1 text-xs text-surface-400">
+                      <span className="font-bold uppercase tracking-widest text-surface-500">Import</span>
+                      {IMPORT_PHASES.map(({ key, label }) => (
+                        <label key={key} className="flex items-center gap-2 cursor-pointer">
+                          <input
+                            type="checkbox"
+                            checked={options[key]}
+                            onChange={(e) => setOptions({ ...options, [key]: e.target.checked })}
+                            className="accent-brand-500"
+                          />
+                          {label}
+                        </label>
+                      ))}
+                    </div>
+                    <div className="max-h-[380px] overflow-y-auto pr-2 custom-scrollbar space-y-3 p-1">
+                      {detected.map((exp) => (
+                        <Card
+                          key={exp.id}
+                          variant="glass"
+                          padding="md"
+                          className="group hover:border-brand-500/50 hover:bg-brand-500/5 transition-all duration-300 flex items-center justify-between border-surface-800 backdrop-blur-xs"
+                        >
+                          <div className="flex items-center gap-5 min-w-0">
+                            <div className="w-14 h-14 bg-surface-800 rounded-2xl flex items-center justify-center group-hover:bg-brand-500/20 group-hover:text-brand-400 transition-colors shrink-0">
+                              {exp.source_type === "Zip" ? (
+                                <svg className="w-7 h-7" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                                  <path strokeLinecap="round" strokeLinejoin="round" strokeWidth={1.5} d="M5 8h14M5 8a2 2 0 110-4h14a2 2 0 110 4M5 8v10a2 2 0 002 2h10a2 2 0 002-2V8m-9 4h4" />
+                                </svg>
+                              ) : (
+                                <svg className="w-7 h-7" fill="none" viewBox="0 0 24 24" stroke="currentColor">
+                                  <path strokeLinecap="round" strokeLinejoin="round" strokeWidth={1.5} d="M3 7v10a2 2 0 002 2h14a2 2 0 002-2V9a2 2 0 00-2-2h-6l-2-2H5a2 2 0 00-2 2z" />
+                                </svg>
+                              )}
+                            </div>
+                            <div className="min-w-0">
+                              <h4 className="font-bold text-white group-hover:text-brand-400 transition-colors truncate">
+                                {exp.id}
+                              </h4>
+                              <p className="text-[10px] text-surface-500 font-mono mt-1 truncate max-w-[300px]" title={exp.source_paths.join(', ')}>
+                                {exp.source_paths.length > 1
+                                  ? `${exp.source_paths.length} components detected`
+                                  : exp.source_paths[0]}
+                              </p>
+                              <div className="flex gap-2 mt-2">
+                                <Badge variant={exp.validation_status === "Valid" ? "success" : "warning"} size="sm">
+                                  {exp.validation_status}
+                                </Badge>
+                                <Badge variant="default" size="sm" className="opacity-60 text-[10px]">
+                                  {exp.source_type.toUpperCase()} {exp.source_paths.length > 1 && `(${exp.source_paths.length} PARTS)`}
+                                </Badge>
+                                {(exp.ignored_duplicates?.length ?? 0) > 0 && (
+                                  <Badge variant="default" size="sm" className="opacity-60 text-[10px]" title={exp.ignored_duplicates!.join(', ')}>
+                                    {exp.ignored_duplicates!.length} duplicate download{exp.ignored_duplicates!.length > 1 ? 's' : ''} ignored
+                                  </Badge>
+                                )}
+                              </div>
                             </div>
                           </div>
-                        </div>
-                        <Button
-                          onClick={() => handleProcess(exp)}
-                          variant={exp.validation_status === "Valid" ? "solid" : "outline"}
-                          className="shrink-0 font-bold ml-4"
-                        >
-                          Process
-                        </Button>
-                      </Card>
-                    ))}
-                  </div>
+                          <Button
+                            onClick={() => handleProcess(exp)}
+                            disabled={!Object.values(options).some(Boolean)}
+                            variant={exp.validation_status === "Valid" ? "solid" : "outline"}
+                            className="shrink-0 font-bold ml-4"
+                          >
+                            Process
+                          </Button>
+                        </Card>
+                      ))}
+                    </div>
+                  </>
                 ) : !error ? (
                   <div className="py-20 text-center bg-surface-900/30 rounded-3xl border-2 border-dashed border-surface-800 flex flex-col items-center">
                     <div className="w-20 h-20 rounded-3xl bg-surface-800 flex items-center justify-center mb-6 shadow-xl">
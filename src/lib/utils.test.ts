@@ -1,5 +1,5 @@
 import { describe, it, expect } from 'vitest';
-import { cn } from './utils';
+import { cn, formatBytes } from './utils';
 
 describe('cn utility', () => {
   it('merges class names', () => {
@@ -14,3 +14,12 @@ describe('cn utility', () => {
     expect(cn(undefined, null, 'foo', false && 'bar')).toBe('foo');
   });
 });
+
+describe('formatBytes', () => {
+  it('formats across units', () => {
+    expect(formatBytes(512)).toBe('512 B');
+    expect(formatBytes(1536)).toBe('1.5 KB');
+    expect(formatBytes(3.2 * 1024 ** 3)).toBe('3.2 GB');
+    expect(formatBytes(250 * 1024 ** 2)).toBe('250 MB');
+  });
+});
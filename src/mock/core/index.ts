@@ -7,8 +7,49 @@ export const invoke = async (cmd: string, args?: any): Promise<any> => {
   switch (cmd) {
     case "get_exports":
       return MOCK_EXPORTS;
+    case "get_app_state":
+      return { state: "Ready", stats: MOCK_STATS };
     case "get_conversations":
       return MOCK_CONVERSATIONS;
+    case "get_conversations_page": {
+      const filter = (args?.nameFilter ?? "").toLowerCase();
+      const matching = MOCK_CONVERSATIONS.filter((c) =>
+        !filter || (c.display_name ?? c.id).toLowerCase().includes(filter) || c.id.toLowerCase().includes(filter));
+      const items = matching.slice(args?.offset ?? 0, (args?.offset ?? 0) + (args?.limit ?? 50));
+      return { items, total_count: matching.length, has_more: (args?.offset ?? 0) + items.length < matching.length };
+    }
+    case "get_conversation":
+      return MOCK_CONVERSATIONS.find((c) => c.id === args?.conversationId) ?? MOCK_CONVERSATIONS[0];
+    case "refresh_conversation_stats":
+      return MOCK_CONVERSATIONS.length;
+    case "relink_media":
+      return { linked: 0, still_missing: 0 };
+    case "get_validation_issues":
+      return [];
+    case "verify_media":
+      return {
+        total_html_files: MOCK_CONVERSATIONS.length,
+        parsed_html_files: MOCK_CONVERSATIONS.length,
+        total_media_referenced: 0,
+        media_found: 0,
+        media_missing: 0,
+        missing_files: [],
+        warnings: [],
+        missing_media_by_conversation: [],
+        missing_file_count: 0,
+        media_verified_at: new Date().toISOString()
+      };
+    case "get_people":
+      return MOCK_CONVERSATIONS.filter(c => c.participants.length === 2).map(c => ({
+        username: c.id,
+        display_name: c.display_name,
+        avatar_path: c.avatar_path,
+        avatar_color: c.avatar_color,
+      }));
+    case "search_messages_on_day":
+      return [];
+    case "get_on_this_day":
+      return [{ year: new Date().getFullYear() - 1, memories: MOCK_MEMORIES.slice(0, 2), events: [], event_total: 0 }];
     case "search_messages":
       return [
         {
@@ -32,18 +73,94 @@ export const invoke = async (cmd: string, args?: any): Promise<any> => {
           event_type: "text"
         }
       ];
+    case "get_saved_searches":
+      return [
+        { id: 1, name: "Pizza plans", query: "pizza", filters: null, last_used_at: new Date().toISOString(), use_count: 3 },
+        { id: 2, name: null, query: "birthday", filters: null, last_used_at: new Date().toISOString(), use_count: 1 },
+      ];
+    case "record_search":
+    case "delete_saved_search":
+      return null;
+    case "save_search":
+      return { id: 3, name: args?.name, query: args?.query, filters: args?.filters ?? null, last_used_at: new Date().toISOString(), use_count: 1 };
+    case "search_all": {
+      const q = String(args?.query || "").toLowerCase();
+      return {
+        conversations: MOCK_CONVERSATIONS.filter(c => (c.display_name || c.id).toLowerCase().includes(q)).map(c => ({
+          id: c.id,
+          display_name: c.display_name,
+          message_count: c.message_count,
+          last_event_at: c.last_event_at
+        })),
+        people: [],
+        messages: await invoke("search_messages", args)
+      };
+    }
     case "get_messages":
     case "get_messages_page":
-      const msgs = generateMockMessages(args?.conversationId || args?.conversation_id || "c1");
+      const msgs = generateMockMessages(args?.conversationId || args?.conversation_id || "c1")
+        .filter((m: any) => !args?.eventTypes?.length || args.eventTypes.includes(m.event_type));
       return {
         messages: msgs,
         total_count: msgs.length,
         has_more: false
       };
+    case "get_messages_around": {
+      const around = generateMockMessages("c1");
+      return { messages: around, total_count: around.length, has_more: false, offset: 0, anchor_index: 0 };
+    }
+    case "cancel_ingestion":
+      return null;
+    case "get_owner_profile":
+      return { username: "kody", display_name: "Kody" };
+    case "get_active_export":
+      return null;
+    case "set_active_export":
+      return null;
     case "get_export_stats":
       return MOCK_STATS;
     case "get_memories":
       return MOCK_MEMORIES;
+    case "get_activity_timeline":
+      return [{ bucket_start: new Date().toISOString().slice(0, 10), count: 12 }];
+    case "get_call_totals":
+      return [
+        { conversation_id: "c1", display_name: "Alice", calls: 6, audio_seconds: 1260, video_seconds: 540, total_call_seconds: 1800 },
+      ];
+    case "get_location_history":
+      return [
+        { timestamp: new Date().toISOString(), latitude: 48.8584, longitude: 2.2945, source: "Latest Location", export_id: "mock" },
+      ];
+    case "get_activity_heatmap": {
+      const grid = () => Array.from({ length: 7 }, () => Array.from({ length: 24 }, () => Math.floor(Math.random() * 10)));
+      return { sent: grid(), received: grid(), timezone_offset_minutes: 0 };
+    }
+    case "get_timezone_offset":
+      return 0;
+    case "set_timezone_offset":
+      return null;
+    case "get_response_stats":
+      return {
+        conversation_id: args?.conversationId ?? "c1",
+        owner_replies: { sender: "kody", replies: 40, median_seconds: 180, mean_seconds: 900 },
+        participant_replies: [{ sender: "alice", replies: 38, median_seconds: 240, mean_seconds: 1100 }],
+        longest_silence: null,
+        longest_streak_days: 5,
+        streak_start: new Date().toISOString().slice(0, 10),
+        one_sided: false
+      };
+    case "get_text_analytics":
+      return {
+        conversation_id: args?.conversationId ?? null,
+        top_words: [{ term: "pizza", count: 42 }, { term: "tonight", count: 30 }],
+        top_emojis: [{ term: "😂", count: 57 }, { term: "❤️", count: 21 }],
+        messages_scanned: 120,
+        event_count: 120
+      };
+    case "get_media_timeline":
+      return [{ month: new Date().toISOString().slice(0, 7), count: MOCK_MEMORIES.length, offset: 0 }];
+    case "get_media_offset_at_date":
+      return 0;
     case "get_unified_media_stream":
       return {
         items: MOCK_MEMORIES.map(m => ({
@@ -51,16 +168,50 @@ export const invoke = async (cmd: string, args?: any): Promise<any> => {
           path: m.media_path,
           media_type: m.media_type,
           timestamp: m.timestamp,
-          source: "local"
+          source: "local",
+          direction: "sent",
+          size_bytes: 2_400_000,
+          conversation_id: null,
+          media_index: 0
         })),
         total_count: MOCK_MEMORIES.length,
         has_more: false
       };
+    case "get_media_info":
+      return { path: args?.path, size_bytes: 2_400_000 };
+    case "lookup_media_id":
+      return { media_id: args?.mediaId, events: [], indexed_file: null, verdict: "Unknown" };
+    case "get_active_tasks":
+      return [];
+    case "cancel_task":
+      return null;
+    case "get_performance_trace":
+      return [];
+    case "get_performance_settings":
+      return { enabled: false, log_slow_calls: true, slow_threshold_ms: 250 };
+    case "set_performance_settings":
+      return null;
+    case "get_diagnostics":
+      return {
+        app_version: "0.0.0-mock",
+        os: "linux",
+        arch: "x86_64",
+        log_path: "/tmp/mock.log",
+        database: null,
+        performance: { enabled: false, slow_threshold_ms: 250, recorded: 0, operations: [] },
+      };
+    case "get_shared_locations":
+      return [
+        { event_id: "m-loc-1", conversation_id: "c1", sender: "Alex", timestamp: new Date(Date.now() - 7200000).toISOString(), lat: 40.7128, lon: -74.006 },
+        { event_id: "m-loc-2", conversation_id: "c2", sender: "Sarah", timestamp: new Date(Date.now() - 90000000).toISOString(), lat: 51.5074, lon: -0.1278 }
+      ].filter(l => !args?.conversationId || l.conversation_id === args.conversationId);
     case "get_conversation_name":
       return MOCK_CONVERSATIONS.find(c => c.id === (args?.conversationId || args?.conversation_id))?.display_name || "Unknown";
     case "auto_detect_exports":
     case "detect_exports":
       return MOCK_EXPORTS;
+    case "complete_import":
+      return { chats: true, memories: true, location_history: true, snap_history: true, media_linking: true };
     case "process_export":
       // Simulate ingestion progress
       setTimeout(() => mockEmit("ingestion-progress", { export_id: "mock", current_step: "Initializing", progress: 0.1, message: "Reading export..." }), 100);
@@ -74,6 +225,39 @@ export const invoke = async (cmd: string, args?: any): Promise<any> => {
       return "/tmp/mock.log";
     case "get_storage_path":
       return "/tmp/mock_storage";
+    case "get_download_jobs":
+      return [];
+    case "rebuild_search_index":
+      return [{ export_id: "mock", indexed: 4200, total: 4200, done: true, cancelled: false }];
+    case "cancel_search_index_rebuild":
+      return null;
+    case "export_user_data":
+      return null;
+    case "import_user_data":
+      return { dry_run: args?.dryRun ?? false, tags_added: 2, tags_updated: 0, taggings_added: 5, taggings_unresolved: 0, redactions_added: 0, events_redacted: 0, saved_searches_added: 1, saved_searches_updated: 0, settings_added: 1, settings_updated: 0, conflicts_kept: 0 };
+    case "get_database_info":
+      return { schema_version: 7, read_only: false, file_bytes: 48_000_000, wal_bytes: 0, connections: 2, idle_connections: 2, last_maintenance: null };
+    case "check_export_changes":
+      return { export_id: args?.exportId, recorded: true, changed: [], added: [], removed: [], unchanged: 12 };
+    case "get_network_settings":
+      return { http_proxy: null, https_proxy: null, use_system_proxy: true, timeout_secs: 60, user_agent: null };
+    case "set_network_settings":
+      return null;
+    case "get_detection_settings":
+      return { name_patterns: [], probe_limit: 200 };
+    case "set_detection_settings":
+      return null;
+    case "get_media_linking_settings":
+      return { filename_patterns: ["^[A-Za-z_]+~(.+)$", "^(\\d+)$"], date_window_minutes: 60 };
+    case "set_media_linking_settings":
+      return null;
+    case "test_connection":
+      return { ok: true, status: 200, latency_ms: 42, error: null };
+    case "get_paths_overview":
+      return {
+        extraction: { path: "/tmp/mock_app_data/exports", source: "Default", available_bytes: 500 * 1024 * 1024 * 1024, total_bytes: 1024 * 1024 * 1024 * 1024 },
+        downloads: { path: "/tmp/mock_storage", source: "Configured", available_bytes: 500 * 1024 * 1024 * 1024, total_bytes: 1024 * 1024 * 1024 * 1024 },
+      };
     case "check_disk_space":
       return { available_bytes: 500 * 1024 * 1024 * 1024, total_bytes: 1024 * 1024 * 1024 * 1024, mount_point: "/" };
     default:
@@ -29,11 +29,11 @@ export const MOCK_STATS: ExportStats = {
 };
 
 export const MOCK_CONVERSATIONS: Conversation[] = [
-  { id: "c1", display_name: "The Boys 🍻", participants: ["Kody", "Alex", "Steve", "Mike"], last_event_at: new Date().toISOString(), message_count: 3200, has_media: true },
-  { id: "c2", display_name: "Sarah J.", participants: ["Kody", "Sarah"], last_event_at: new Date(Date.now() - 3600000).toISOString(), message_count: 4500, has_media: true },
-  { id: "c3", display_name: "Mom ❤️", participants: ["Kody", "Mom"], last_event_at: new Date(Date.now() - 86400000).toISOString(), message_count: 1200, has_media: false },
-  { id: "c4", display_name: "Gym Group", participants: ["Kody", "Chris", "Emma"], last_event_at: new Date(Date.now() - 172800000).toISOString(), message_count: 850, has_media: true },
-  { id: "c5", display_name: "Team Work", participants: ["Kody", "Boss", "Alice"], last_event_at: new Date(Date.now() - 604800000).toISOString(), message_count: 300, has_media: false }
+  { id: "c1", display_name: "The Boys 🍻", participants: ["Kody", "Alex", "Steve", "Mike"], is_group: true, last_event_at: new Date().toISOString(), message_count: 3200, has_media: true, media_count: 412, media_bytes: 1843200000, missing_media_count: 3, avatar_path: null, avatar_color: "#3a7ca5", anomaly_flags: [], saved_count: 14, shared_location_count: 3 },
+  { id: "c2", display_name: "Sarah J.", participants: ["Kody", "Sarah"], is_group: false, last_event_at: new Date(Date.now() - 3600000).toISOString(), message_count: 4500, has_media: true, media_count: 960, media_bytes: 3435973837, missing_media_count: 0, avatar_path: null, avatar_color: "#b5487a", anomaly_flags: [], saved_count: 37, shared_location_count: 9 },
+  { id: "c3", display_name: "Mom ❤️", participants: ["Kody", "Mom"], is_group: false, last_event_at: new Date(Date.now() - 86400000).toISOString(), message_count: 1200, has_media: false, media_count: 0, media_bytes: 0, missing_media_count: 0, avatar_path: null, avatar_color: "#5a9e4b", anomaly_flags: [], saved_count: 5, shared_location_count: 1 },
+  { id: "c4", display_name: "Gym Group", participants: ["Kody", "Chris", "Emma"], is_group: true, last_event_at: new Date(Date.now() - 172800000).toISOString(), message_count: 850, has_media: true, media_count: 128, media_bytes: 402653184, missing_media_count: 12, avatar_path: null, avatar_color: "#c27a2c", anomaly_flags: [], saved_count: 2, shared_location_count: 0 },
+  { id: "c5", display_name: "Team Work", participants: ["Kody", "Boss", "Alice"], is_group: true, last_event_at: new Date(Date.now() - 604800000).toISOString(), message_count: 300, has_media: false, media_count: 0, media_bytes: 0, missing_media_count: 0, avatar_path: null, avatar_color: "#6c55b8", anomaly_flags: [], saved_count: 0, shared_location_count: 0 }
 ];
 
 export const generateMockMessages = (convoId: string): Event[] => {
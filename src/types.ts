@@ -5,6 +5,42 @@ export interface ExportSet {
   extraction_path: string | null;
   creation_date: string | null;
   validation_status: "Valid" | "Incomplete" | "Corrupted" | "Unknown";
+  parts?: ExportPart[];
+  import_phases?: ImportOptions | null;
+  /** Re-downloaded copies of a part that were left out, e.g. "mydata~123 (1).zip". */
+  ignored_duplicates?: string[];
+  /** Where the export's files were found when it was last imported. */
+  layout?: ExportLayout | null;
+}
+
+/** Located artifacts of an export; reused by later imports until a path goes missing. */
+export interface ExportLayout {
+  root: string;
+  friends_json: string | null;
+  chat_history_json: string | null;
+  snap_history_json: string | null;
+  story_history_json?: string | null;
+  talk_history_json?: string | null;
+  location_history_json?: string | null;
+  memories_json: string | null;
+  chat_html_dir: string | null;
+  media_dirs: string[];
+  account_json?: string | null;
+  user_profile_json?: string | null;
+}
+
+export interface ImportOptions {
+  chats: boolean;
+  memories: boolean;
+  location_history: boolean;
+  snap_history: boolean;
+  media_linking: boolean;
+}
+
+export interface ExportPart {
+  path: string;
+  readable: boolean;
+  error: string | null;
 }
 
 export interface IngestionProgress {
@@ -18,9 +54,59 @@ export interface Conversation {
   id: string;
   display_name: string | null;
   participants: string[];
+  is_group: boolean;
   last_event_at: string | null;
   message_count: number;
   has_media: boolean;
+  media_count: number;
+  media_bytes: number;
+  missing_media_count: number;
+  avatar_path: string | null;
+  avatar_color: string | null;
+  anomaly_flags: AnomalyKind[];
+  /** Messages saved in chat; `get_messages_page` with `savedOnly` lists them. */
+  saved_count: number;
+  /** Messages whose text shares a location; see `get_shared_locations`. */
+  shared_location_count: number;
+  /** How much of the history made it in; null until computed. */
+  completeness?: ConversationCompleteness | null;
+}
+
+/** The account the imported data belongs to; its messages can be shown as "You". */
+export interface OwnerProfile {
+  username: string;
+  display_name: string | null;
+}
+
+/** Where a conversation's messages were read from. */
+export type ChatSource = "html" | "json";
+
+export interface ConversationCompleteness {
+  /** 0-100; null without media. */
+  media_linked_pct: number | null;
+  /** 0-100; null without messages. */
+  timestamps_parsed_pct: number | null;
+  sources: ChatSource[];
+  anomaly_count: number;
+}
+
+export interface SharedLocation {
+  event_id: string;
+  conversation_id: string | null;
+  sender: string;
+  timestamp: string;
+  lat: number;
+  lon: number;
+}
+
+export type AnomalyKind = "EpochTimestamps" | "UnparsedTimestamps" | "LargeGap" | "DuplicateMessages";
+
+export interface ConversationAnomaly {
+  conversation_id: string;
+  kind: AnomalyKind;
+  affected: number;
+  total: number;
+  detail: string;
 }
 
 export interface Event {
@@ -46,7 +132,10 @@ export interface Memory {
   export_id: string;
   download_url: string | null;
   proxy_url: string | null;
+  overlay_url?: string | null;
   download_status: DownloadStatus;
+  /** Why the last download attempt failed, while `download_status` is "Failed". */
+  failure_reason?: string | null;
 }
 
 export interface DownloadProgress {
@@ -64,6 +153,13 @@ export interface DiskSpaceInfo {
 }
 
 
+/** What the app should show on launch, from `get_app_state`. */
+export type AppState =
+  | { state: "NoDatabase" }
+  | { state: "EmptyDatabase" }
+  | { state: "ImportInProgress"; export_id: string; progress: number }
+  | { state: "Ready"; stats: ExportStats };
+
 export interface ExportStats {
   total_messages: number;
   total_conversations: number;
@@ -84,14 +180,62 @@ export interface SearchResult {
   content: string;
   timestamp: string;
   event_type: string;
+  /** Matching part of the message; each match is wrapped in SNIPPET_MATCH_START/END. */
+  snippet?: string;
+  /** UTF-16 ranges [start, end) of the matches in `content`, usable with `String.slice`. */
+  match_ranges?: [number, number][];
 }
 
-export interface MediaEntry {
-  path: string;
-  media_type: string;
-  timestamp: string | null;
-  source: string;
+export const SNIPPET_MATCH_START = "\u0002";
+export const SNIPPET_MATCH_END = "\u0003";
+
+/** Optional narrowing for message searches. */
+export interface MessageSearchFilters {
+  conversation_id?: string | null;
+  /** Sender username. */
+  sender?: string | null;
+}
+
+/** One year's matches from `search_messages_on_day`; `results` holds at most the first few. */
+export interface YearSearchResults {
+  year: number;
+  total: number;
+  results: SearchResult[];
+}
+
+/** One year of `get_on_this_day`; `events` holds at most the per-year cap. */
+export interface OnThisDayYear {
+  year: number;
+  memories: Memory[];
+  events: Event[];
+  event_total: number;
+}
+
+export interface ConversationMatch {
+  id: string;
+  display_name: string | null;
+  message_count: number;
+  last_event_at: string | null;
+}
+
+export interface Person {
+  username: string;
+  display_name: string | null;
+  avatar_path: string | null;
+  avatar_color: string | null;
+}
+
+export interface PersonMatch {
+  username: string;
+  display_name: string | null;
   conversation_id: string | null;
+  message_count: number;
+}
+
+export interface SearchAllResults {
+  conversations: ConversationMatch[];
+  people: PersonMatch[];
+  messages: SearchResult[];
 }
 
 export interface IngestionResult {
@@ -102,6 +246,86 @@ export interface IngestionResult {
   parse_failures: number;
   warnings: string[];
   errors: string[];
+  phase_timings: PhaseTiming[];
+  anomalies: ConversationAnomaly[];
+  /** People read from friends.json, per key style found in it. */
+  people_by_schema: FriendsSchemaCount[];
+  media_linked_by_id: number;
+  media_linked_by_date: number;
+}
+
+/** Classic: "Friends"/"Display Name"; Lowercase: "friends"/"display_name". */
+export type FriendsSchema = "Classic" | "Lowercase";
+
+export interface FriendsSchemaCount {
+  schema: FriendsSchema;
+  people: number;
+}
+
+export interface PhaseTiming {
+  phase: string;
+  duration_ms: number;
+  threads: number | null;
+}
+
+export interface IngestionCleanup {
+  events_removed: number;
+  conversations_removed: number;
+  conversations_restored: number;
+  memories_removed: number;
+  locations_removed?: number;
+  export_removed: boolean;
+  extraction_removed: boolean;
+}
+
+export interface IngestionFailure {
+  export_id: string;
+  error: string;
+  cleanup: IngestionCleanup;
+}
+
+export type IngestionRunStatus = "Completed" | "Failed" | "Cancelled";
+
+export interface IngestionRunRecord {
+  id: number;
+  export_id: string;
+  started_at: string;
+  finished_at: string;
+  status: IngestionRunStatus;
+  error: string | null;
+  conversations: number;
+  events: number;
+  memories: number;
+  cleanup: IngestionCleanup | null;
+  file_issues: JsonFileIssue[];
+  timestamp_format: TimestampFormatDecision | null;
+}
+
+export type TimestampFormatHint = "MonthFirst" | "DayFirst";
+
+export interface TimestampFormatDecision {
+  hint: TimestampFormatHint;
+  dates_sampled: number;
+  ambiguous: boolean;
+}
+
+export type JsonFileProblem = "HtmlErrorPage" | { Truncated: { at_byte: number } } | "NotJson";
+
+export interface JsonFileIssue {
+  file: string;
+  problem: JsonFileProblem;
+  salvaged_conversations: number;
+  salvaged_events: number;
+}
+
+export interface ExportPreview {
+  export_id: string;
+  sample_events: Event[];
+  conversation_names: string[];
+  unknown_event_types: string[];
+  timestamp_failures: number;
+  files_sampled: string[];
+  warnings: string[];
 }
 
 export interface ValidationReport {
@@ -112,6 +336,27 @@ export interface ValidationReport {
   media_missing: number;
   missing_files: string[];
   warnings: string[];
+  missing_media_by_conversation: [string, number][];
+  /** Files `verify_media` found missing; `missing_files` lists at most the first few. */
+  missing_file_count: number;
+  /** When `verify_media` last ran; null until then. */
+  media_verified_at: string | null;
+}
+
+/** Payload of `verify-media-progress`. */
+export interface MediaVerifyProgress {
+  checked: number;
+  total: number;
+  missing: number;
+  done: boolean;
+}
+
+export type ConversationSort = "LastActivity" | "MessageCount" | "Name";
+
+export interface ConversationPage {
+  items: Conversation[];
+  total_count: number;
+  has_more: boolean;
 }
 
 export interface MessagePage {
@@ -120,12 +365,179 @@ export interface MessagePage {
   has_more: boolean;
 }
 
+/** Messages around an anchor message; the anchor is at `anchor_index - offset`. */
+export interface MessageWindow extends MessagePage {
+  offset: number;
+  anchor_index: number;
+}
+
 export interface MediaStreamEntry {
   id: string;
   path: string;
   media_type: string;
   timestamp: string;
   source: "local" | "cloud";
+  direction: "sent" | "received";
+  /** Null when the size wasn't cached and the page ran out of time; see `get_media_info`. */
+  size_bytes: number | null;
+  /** The chat the media was sent in; null for memories. */
+  conversation_id: string | null;
+  /** Position among the message's files; each file of a message has its own entry. */
+  media_index: number;
+}
+
+export interface MediaInfo {
+  path: string;
+  size_bytes: number;
+}
+
+export type MediaIdVerdict = "Linked" | "LinkedFileMissing" | "NotLinked" | "NoFile" | "Orphaned" | "Unknown";
+
+export interface MediaIdEvent {
+  event_id: string;
+  conversation_id: string | null;
+  event_type: string;
+  timestamp: string;
+  listed_in_metadata: boolean;
+  /** [path, exists now] for each file linked to the message. */
+  linked_files: [string, boolean][];
+}
+
+/** Formats `export_conversation` writes. "bundle" is a zip of the HTML page and its media. */
+export type ExportFormat = "text" | "json" | "html" | "bundle";
+
+/** Options for `export_conversation`; everything defaults to off. */
+export interface ExportOptions {
+  /** HTML only: put small images in the page instead of linking to the files. */
+  embed_images?: boolean;
+  /** Only messages from this time on (RFC 3339). */
+  after?: string | null;
+  /** Only messages up to this time, inclusive (RFC 3339). */
+  before?: string | null;
+  /** Only messages from these usernames; empty for everyone. */
+  senders?: string[];
+  /** Replace usernames with pseudonyms ("Person A") and leave out display names. */
+  anonymize?: boolean;
+  /** Replace email addresses and phone numbers in message text. */
+  mask_contact_details?: boolean;
+}
+
+/** Payload of `export-progress`, sent while `export_all_conversations` runs. */
+export interface ExportProgress {
+  processed: number;
+  total: number;
+  /** Name of the conversation just written. */
+  current: string | null;
+  done: boolean;
+}
+
+/** Result of `export_all_conversations`. */
+export interface BulkExportReport {
+  output_dir: string;
+  written: number;
+  failed: number;
+  /** `[conversation_id, error]` for each conversation that could not be written. */
+  failures: [string, string][];
+}
+
+/** A file in an export's media folders that no message or memory links to. */
+export interface OrphanedMediaFile {
+  path: string;
+  size_bytes: number;
+}
+
+/** One page of `find_orphaned_media`, sorted by path. */
+export interface OrphanedMediaPage {
+  items: OrphanedMediaFile[];
+  total_count: number;
+  /** Size of every orphaned file, not just this page's. */
+  total_bytes: number;
+  has_more: boolean;
+}
+
+/** Result of `lookup_media_id`: where a media ID turns up at each stage. */
+export interface MediaIdTrace {
+  media_id: string;
+  events: MediaIdEvent[];
+  indexed_file: string | null;
+  verdict: MediaIdVerdict;
+}
+
+/** A long-running command in progress, relayed on `task-heartbeat` and `task-stalled`. */
+export interface ActiveTask {
+  id: number;
+  name: string;
+  elapsed_ms: number;
+  since_heartbeat_ms: number;
+  /** Fraction done, 0-1, when the task knows it. */
+  progress: number | null;
+  message: string | null;
+  cancellable: boolean;
+  cancel_requested: boolean;
+  stalled: boolean;
+}
+
+export type DataDomain = "conversations" | "events" | "memories" | "media" | "settings";
+
+/** Payload of the `data-changed` event, sent once a burst of writes has settled. */
+export interface DataChanged {
+  domains: DataDomain[];
+}
+
+export interface PerformanceSettings {
+  enabled: boolean;
+  log_slow_calls: boolean;
+  slow_threshold_ms: number;
+}
+
+export type TraceKind = "Command" | "Query";
+
+export interface TraceEntry {
+  kind: TraceKind;
+  name: string;
+  started_at: string;
+  duration_ms: number;
+  rows: number | null;
+}
+
+export interface OperationStats {
+  kind: TraceKind;
+  name: string;
+  calls: number;
+  total_ms: number;
+  max_ms: number;
+  slow_calls: number;
+}
+
+export interface PerformanceSummary {
+  enabled: boolean;
+  slow_threshold_ms: number;
+  recorded: number;
+  /** Slowest total first. */
+  operations: OperationStats[];
+}
+
+export interface DiagnosticsBundle {
+  app_version: string;
+  os: string;
+  arch: string;
+  log_path: string | null;
+  database: DatabaseInfo | null;
+  performance: PerformanceSummary;
+}
+
+/** Filter for who media came from; memories always count as sent. */
+export type MediaDirection = "all" | "sent" | "received";
+
+/** Narrows `get_unified_media_stream`; unset fields match everything. */
+export interface MediaFilter {
+  source?: "local" | "cloud" | null;
+  media_type?: "Image" | "Video" | null;
+  /** Only media from this chat, which leaves out memories. */
+  conversation_id?: string | null;
+  /** Inclusive RFC 3339 bounds. */
+  after?: string | null;
+  before?: string | null;
 }
 
 export interface PaginatedMedia {
@@ -134,6 +546,94 @@ export interface PaginatedMedia {
   has_more: boolean;
 }
 
+export type TimelineBucket = "Day" | "Week" | "Month";
+
+/** Messages in one bucket of `get_activity_timeline`; `bucket_start` is `YYYY-MM-DD`. */
+export interface ActivityBucket {
+  bucket_start: string;
+  count: number;
+}
+
+/** A point from the export's location history; `source` names the list it came from. */
+export interface LocationPoint {
+  timestamp: string;
+  latitude: number;
+  longitude: number;
+  source: string;
+  export_id: string;
+}
+
+/** Calls with one conversation; seconds only count calls whose duration was logged. */
+export interface CallTotal {
+  conversation_id: string;
+  display_name: string | null;
+  calls: number;
+  audio_seconds: number;
+  video_seconds: number;
+  total_call_seconds: number;
+}
+
+/** Counts by weekday (rows, Monday first) and local hour (columns). */
+export interface ActivityHeatmap {
+  sent: number[][];
+  received: number[][];
+  timezone_offset_minutes: number;
+}
+
+export interface MediaTimelineMonth {
+  month: string;
+  count: number;
+  offset: number;
+}
+
+/** Payload of `search-index-progress`, and one entry of `rebuild_search_index`'s result. */
+export interface SearchIndexProgress {
+  export_id: string;
+  indexed: number;
+  total: number;
+  done: boolean;
+  cancelled: boolean;
+}
+
+export interface ThumbnailProgress {
+  processed: number;
+  total: number;
+  generated: number;
+  skipped: number;
+  failed: number;
+  done: boolean;
+  cancelled: boolean;
+}
+
+export interface DateRange {
+  start: string | null;
+  end: string | null;
+}
+
+export interface GalleryProgress {
+  processed: number;
+  total: number;
+  current_month: string | null;
+  done: boolean;
+}
+
+export interface GalleryReport {
+  output_dir: string;
+  months: number;
+  files_written: number;
+  thumbnails: number;
+  missing: number;
+  /** Per-copy results when `export_memories_gallery` was asked to strip metadata. */
+  scrubbed_files: ScrubRecord[];
+}
+
+export type ScrubOutcome = "Scrubbed" | "Unnecessary" | "Unsupported" | { Failed: string };
+
+export interface ScrubRecord {
+  file: string;
+  outcome: ScrubOutcome;
+}
+
 /** Structural interface for items passed to MediaViewer. Covers Memory, Event, and MediaStreamEntry shapes. */
 export interface MediaViewerItem {
   id?: string;
@@ -152,3 +652,281 @@ export interface MediaViewerItem {
   content?: string | null;
   metadata?: string | null;
 }
+
+export type TagEntityType = "Conversation" | "Event" | "Memory";
+
+export interface Tag {
+  id: number;
+  name: string;
+  color: string | null;
+  created_at: string;
+  usage_count: number;
+}
+
+export type TaggedEntry =
+  | { entity_type: "Conversation"; entry: Conversation }
+  | { entity_type: "Event"; entry: Event }
+  | { entity_type: "Memory"; entry: Memory };
+
+export interface TaggedPage {
+  items: TaggedEntry[];
+  total_count: number;
+  has_more: boolean;
+}
+
+export type DatabaseSlot = "Live" | "Snapshot";
+
+export interface ActiveDatabase {
+  slot: DatabaseSlot;
+  snapshot_path: string | null;
+  read_only: boolean;
+}
+
+export interface DensityBucket {
+  start: string;
+  end: string;
+  count: number;
+}
+
+export type RedactionKind = "Event" | "Sender";
+
+export interface Redaction {
+  id: number;
+  kind: RedactionKind;
+  value: string;
+  created_at: string;
+}
+
+export interface RelinkSummary {
+  linked: number;
+  still_missing: number;
+}
+
+export interface RedactionSummary {
+  events_removed: number;
+  rules_added: number;
+  media_overwritten: number;
+  media_skipped: number;
+}
+
+export type PathSource = "Configured" | "LegacyStoragePath" | "Default" | "Unset";
+
+export interface ResolvedPath {
+  path: string | null;
+  source: PathSource;
+  available_bytes: number | null;
+  total_bytes: number | null;
+}
+
+export interface PathsOverview {
+  extraction: ResolvedPath;
+  downloads: ResolvedPath;
+}
+
+export interface ExportChanges {
+  export_id: string;
+  recorded: boolean;
+  changed: string[];
+  added: string[];
+  removed: string[];
+  unchanged: number;
+}
+
+/** Reply times in seconds; null without any replies. */
+export interface ReplyLatency {
+  sender: string;
+  replies: number;
+  median_seconds: number | null;
+  mean_seconds: number | null;
+}
+
+export interface Silence {
+  start: string;
+  end: string;
+  seconds: number;
+}
+
+export interface ResponseStats {
+  conversation_id: string;
+  owner_replies: ReplyLatency;
+  participant_replies: ReplyLatency[];
+  longest_silence: Silence | null;
+  longest_streak_days: number;
+  /** `YYYY-MM-DD`. */
+  streak_start: string | null;
+  one_sided: boolean;
+}
+
+export interface TermCount {
+  term: string;
+  count: number;
+}
+
+/** Most used words and emoji; `conversation_id` is null for all conversations. */
+export interface TextAnalytics {
+  conversation_id: string | null;
+  top_words: TermCount[];
+  top_emojis: TermCount[];
+  messages_scanned: number;
+  event_count: number;
+}
+
+export interface PhraseCount {
+  phrase: string;
+  count: number;
+}
+
+export interface TopPhrases {
+  n: number;
+  phrases: PhraseCount[];
+  messages_scanned: number;
+  budget_exhausted: boolean;
+}
+
+export interface MemoryFilter {
+  export_id?: string | null;
+  date_range?: { start: string | null; end: string | null } | null;
+  media_type?: "Image" | "Video" | null;
+  download_status?: DownloadStatus | null;
+  has_location?: boolean | null;
+}
+
+/** Result of `export_memories`. */
+export interface MemoryMapExport {
+  exported: number;
+  /** Memories left out because they have no coordinates. */
+  skipped_without_location: number;
+}
+
+export type DownloadJobState = "Running" | "Completed" | "Interrupted";
+
+/** From `get_download_queue_state` and the `download-queue-state` event. */
+export interface DownloadQueueState {
+  running: boolean;
+  paused: boolean;
+  /** Cancel was asked for and downloads are still winding down. */
+  cancelling: boolean;
+}
+
+/** A bulk memory download, from `get_download_jobs` and the `download-job-*` events. */
+export interface DownloadJob {
+  id: number;
+  filter: MemoryFilter;
+  created_at: string;
+  updated_at: string;
+  state: DownloadJobState;
+  total: number;
+  completed: number;
+  failed: number;
+  /** Memories whose file was already on disk, so nothing was fetched. */
+  skipped: number;
+}
+
+/** Progress and result of `verify_downloaded_memories`; the `verify-downloads-progress` event payload. */
+export interface DownloadVerification {
+  checked: number;
+  total: number;
+  ok: number;
+  missing: number;
+  undersized: number;
+  done: boolean;
+}
+
+/** Failed memory downloads sharing a reason, from `get_download_failure_summary`. */
+export interface DownloadFailureGroup {
+  reason: string;
+  count: number;
+}
+
+/** What the background database maintenance last did. */
+export interface MaintenanceReport {
+  ran_at: string | null;
+  skipped_for_ingestion: boolean;
+  wal_bytes: number;
+  checkpointed_frames: number;
+  connections_checked: number;
+  connections_failed: number;
+  duration_ms: number;
+}
+
+/** From `get_database_info`. */
+export interface DatabaseInfo {
+  schema_version: number;
+  read_only: boolean;
+  file_bytes: number;
+  wal_bytes: number;
+  connections: number;
+  idle_connections: number;
+  last_maintenance: MaintenanceReport | null;
+}
+
+export type UserDataConflictPolicy = "KeepExisting" | "Overwrite";
+
+/** From `import_user_data`; with `dry_run` set, what an import would change. */
+export interface UserDataImportSummary {
+  dry_run: boolean;
+  tags_added: number;
+  tags_updated: number;
+  taggings_added: number;
+  taggings_unresolved: number;
+  redactions_added: number;
+  events_redacted: number;
+  saved_searches_added: number;
+  saved_searches_updated: number;
+  settings_added: number;
+  settings_updated: number;
+  conflicts_kept: number;
+}
+
+export interface MemoryPage {
+  items: Memory[];
+  total_count: number;
+  has_more: boolean;
+}
+
+export interface MemoryDetail {
+  memory: Memory;
+  file_size: number | null;
+  mime_type: string | null;
+  coordinates: string | null;
+}
+
+export interface AdjacentMemories {
+  previous: string | null;
+  next: string | null;
+}
+
+export interface NetworkSettings {
+  http_proxy: string | null;
+  https_proxy: string | null;
+  use_system_proxy: boolean;
+  timeout_secs: number;
+  user_agent: string | null;
+}
+
+export interface DetectionSettings {
+  name_patterns: string[];
+  probe_limit: number;
+}
+
+export interface MediaLinkingSettings {
+  /** Regexes matched against file names without extension; group 1 is the media ID. */
+  filename_patterns: string[];
+  date_window_minutes: number;
+}
+
+export interface ConnectionTestResult {
+  ok: boolean;
+  status: number | null;
+  latency_ms: number;
+  error: string | null;
+}
+
+export interface SavedSearch {
+  id: number;
+  name: string | null;
+  query: string;
+  filters: Record<string, unknown> | null;
+  last_used_at: string;
+  use_count: number;
+}